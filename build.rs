@@ -19,67 +19,143 @@ fn main() {
     // Tell cargo to tell rustc to link the system bzip2
     // shared library.
     // println!("cargo:rustc-link-lib=bz2")
-    let prefix = env::var("HOMEBREW_PREFIX").unwrap();
-    println!("cargo:rustc-link-arg=-L{prefix}/lib");
+    if env::var("TARGET").unwrap().contains("wasm") {
+        return;
+    }
+    // Homebrew's on macOS, vcpkg's or prebuilt SDKs on windows, wherever
+    // pkg-config says everywhere else
+    let includes = match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+        "macos" => homebrew(),
+        "windows" => windows(),
+        _ => pkg_config(&[
+            "sdl2",
+            "libplacebo",
+            "libavformat",
+            "libavdevice",
+            "libavutil",
+            "libswscale",
+            "vulkan",
+        ]),
+    };
 
-    if !env::var("TARGET").unwrap().contains("wasm") {
-        println!("cargo:rustc-env=MACOSX_DEPLOYMENT_TARGET=15.2");
-        // Compile C code
-        println!("cargo:rerun-if-changed=gfxlowlevel.c");
-        println!("cargo:rerun-if-changed=gfxlowlevel.h");
-        cc::Build::new()
-            .file("src/gfxlowlevel.c")
-            .include("/opt/homebrew/include")
-            .compile("gfxlowlevel");
-        println!("cargo:rustc-link-search=native=/opt/homebrew/lib");
-        println!("cargo:rustc-link-lib=dylib=sdl2");
-        println!("cargo:rustc-link-lib=dylib=placebo");
-        println!("cargo:rustc-link-lib=dylib=avformat");
-        println!("cargo:rustc-link-lib=dylib=MoltenVk");
-        let bindings = bindgen::Builder::default()
-            // The input header we would like to generate
-            // bindings for.
-            .header("src/gfxlowlevel.h")
-            .clang_arg("-I/opt/homebrew/include")
-            .allowlist_function("^gfx_lowlevel_.*")
-            .allowlist_type("^gfx_lowlevel_.*")
-            .allowlist_item("GFX_EAGAIN")
-            .allowlist_type("pl_var_type")
-            .allowlist_item("^PL_VAR_.*")
-            // Tell cargo to invalidate the built crate whenever any of the
-            // included header files changed.
-            .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-            // Finish the builder and generate the bindings.
-            .generate()
-            // Unwrap the Result and panic on failure.
-            .expect("Unable to generate bindings");
-        let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-        bindings
-            .write_to_file(out_path.join("gfx_lowlevel_bindings.rs"))
-            .expect("Couldn't write bindings!");
+    // Compile C code
+    println!("cargo:rerun-if-changed=gfxlowlevel.c");
+    println!("cargo:rerun-if-changed=gfxlowlevel.h");
+    let mut build = cc::Build::new();
+    build.file("src/gfxlowlevel.c");
+    for include in &includes {
+        build.include(include);
+    }
+    // msvc only has _Thread_local in c11 mode
+    if env::var("CARGO_CFG_TARGET_ENV").unwrap() == "msvc" {
+        build.flag("/std:c11");
+    }
+    build.compile("gfxlowlevel");
+    let bindings = bindgen::Builder::default()
+        // The input header we would like to generate
+        // bindings for.
+        .header("src/gfxlowlevel.h")
+        .clang_args(includes.iter().map(|include| format!("-I{include}")))
+        .allowlist_function("^gfx_lowlevel_.*")
+        .allowlist_type("^gfx_lowlevel_.*")
+        .allowlist_item("GFX_EAGAIN")
+        .allowlist_type("pl_var_type")
+        .allowlist_item("^PL_VAR_.*")
+        // Tell cargo to invalidate the built crate whenever any of the
+        // included header files changed.
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        // Finish the builder and generate the bindings.
+        .generate()
+        // Unwrap the Result and panic on failure.
+        .expect("Unable to generate bindings");
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    bindings
+        .write_to_file(out_path.join("gfx_lowlevel_bindings.rs"))
+        .expect("Couldn't write bindings!");
+}
 
-        // Tell cargo to invalidate the built crate whenever the wrapper changes
-        println!("cargo:rerun-if-changed=wrapper.h");
+// Links what the C code needs from Homebrew, vulkan through MoltenVK
+fn homebrew() -> Vec<String> {
+    println!("cargo:rerun-if-env-changed=HOMEBREW_PREFIX");
+    let prefix = env::var("HOMEBREW_PREFIX").unwrap_or_else(|_| "/opt/homebrew".into());
+    println!("cargo:rustc-link-arg=-L{prefix}/lib");
+    println!("cargo:rustc-env=MACOSX_DEPLOYMENT_TARGET=15.2");
+    println!("cargo:rustc-link-search=native={prefix}/lib");
+    println!("cargo:rustc-link-lib=dylib=sdl2");
+    println!("cargo:rustc-link-lib=dylib=placebo");
+    println!("cargo:rustc-link-lib=dylib=avformat");
+    println!("cargo:rustc-link-lib=dylib=MoltenVk");
+    vec![format!("{prefix}/include")]
+}
 
-        // The bindgen::Builder is the main entry point
-        // to bindgen, and lets you build up options for
-        // the resulting bindings.
-        let bindings = bindgen::Builder::default()
-            // The input header we would like to generate
-            // bindings for.
-            .header("wrapper.h")
-            // Tell cargo to invalidate the built crate whenever any of the
-            // included header files changed.
-            .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-            // Finish the builder and generate the bindings.
-            .generate()
-            // Unwrap the Result and panic on failure.
-            .expect("Unable to generate bindings");
+// Links what the C code needs from vcpkg's installed tree, or from
+// SDLRIG_DEPS_DIR when that's set, a directory laid out the same way with the
+// prebuilt SDL, ffmpeg and libplacebo SDKs unpacked into it. Vulkan comes from
+// the LunarG SDK.
+fn windows() -> Vec<String> {
+    println!("cargo:rerun-if-env-changed=SDLRIG_DEPS_DIR");
+    println!("cargo:rerun-if-env-changed=VCPKG_ROOT");
+    println!("cargo:rerun-if-env-changed=VCPKGRS_TRIPLET");
+    println!("cargo:rerun-if-env-changed=VULKAN_SDK");
+    let deps = match env::var("SDLRIG_DEPS_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            let root = env::var("VCPKG_ROOT")
+                .expect("VCPKG_ROOT or SDLRIG_DEPS_DIR is needed to find the native libraries");
+            let triplet = env::var("VCPKGRS_TRIPLET").unwrap_or_else(|_| "x64-windows".into());
+            PathBuf::from(root).join("installed").join(triplet)
+        }
+    };
+    let vulkan = PathBuf::from(
+        env::var("VULKAN_SDK").expect("VULKAN_SDK is needed, it's set by the Vulkan SDK installer"),
+    );
+    println!(
+        "cargo:rustc-link-search=native={}",
+        deps.join("lib").display()
+    );
+    println!(
+        "cargo:rustc-link-search=native={}",
+        vulkan.join("Lib").display()
+    );
+    for lib in [
+        "SDL2", "placebo", "avformat", "avdevice", "avcodec", "avutil", "swscale", "vulkan-1",
+    ] {
+        println!("cargo:rustc-link-lib=dylib={lib}");
+    }
+    vec![
+        deps.join("include").display().to_string(),
+        vulkan.join("Include").display().to_string(),
+    ]
+}
 
-        // Write the bindings to the $OUT_DIR/bindings.rs file.
-        let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-        bindings
-            .write_to_file(out_path.join("bindings.rs"))
-            .expect("Couldn't write bindings!");
+// Links packages the way pkg-config says to, returning their include dirs
+fn pkg_config(packages: &[&str]) -> Vec<String> {
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    let run = |flag: &str| {
+        let output = Command::new("pkg-config")
+            .arg(flag)
+            .args(packages)
+            .output()
+            .expect("pkg-config is needed to find the native libraries");
+        if !output.status.success() {
+            panic!(
+                "pkg-config could not find {}: {}",
+                packages.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        String::from_utf8(output.stdout).expect("pkg-config printed something that isn't utf-8")
+    };
+    for flag in run("--libs").split_whitespace() {
+        if let Some(dir) = flag.strip_prefix("-L") {
+            println!("cargo:rustc-link-search=native={dir}");
+        } else if let Some(lib) = flag.strip_prefix("-l") {
+            println!("cargo:rustc-link-lib=dylib={lib}");
+        }
     }
+    run("--cflags-only-I")
+        .split_whitespace()
+        .filter_map(|flag| flag.strip_prefix("-I"))
+        .map(String::from)
+        .collect()
 }
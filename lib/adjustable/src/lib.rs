@@ -1,6 +1,7 @@
 use core::panic;
 use proc_macro;
 use proc_macro2;
+use proc_macro_crate::{crate_name, FoundCrate};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
 use std::{
     collections::{HashMap, HashSet},
@@ -25,10 +26,13 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
         _ => panic!("Only supports named fields"),
     };
 
+    let krate = guest_crate();
+    let alloc = quote! { #krate::__alloc };
     let mut q = quote! {};
 
     let mut adjuster_map = HashMap::new();
     let mut field_data = HashSet::new();
+    let mut schema_fields = vec![];
 
     for field in fields.named.iter() {
         let ident = field.ident.as_ref().unwrap();
@@ -166,11 +170,11 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                     let command_sender_ident = format_ident!("command_{}_spec", ident);
                     q.extend( quote! {
                         impl #impl_generics #struct_ident #ty_generics #where_clause {
-                            pub fn #command_sender_ident(&self) -> Vec<sdlrig::renderspec::RenderSpec> {
-                                vec![sdlrig::renderspec::SendCmd::builder()
+                            pub fn #command_sender_ident(&self) -> #alloc::vec::Vec<#krate::renderspec::RenderSpec> {
+                                #alloc::vec![#krate::renderspec::SendCmd::builder()
                                     .mix(#mix)
                                     .name(#name)
-                                    .value(sdlrig::renderspec::SendValue::#ptype (self.#getter() as #prim ))
+                                    .value(#krate::renderspec::SendValue::#ptype (self.#getter() as #prim ))
                                     .build()
                                     .into()
                                 ]
@@ -219,10 +223,12 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                     tween,
                 ));
 
+                let mut knob_schema = String::new();
                 {
                     let knob = params.remove("k");
                     let index = params.remove("idx");
                     if let (Some(knob), Some(index)) = (&knob, &index) {
+                        knob_schema = format!(",\"x-knob\":\"{}\",\"x-index\":{}", knob, index);
                         if let Some(_) = adjuster_map.insert(
                             (
                                 knob.to_string().clone(),
@@ -250,6 +256,12 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                     Some(t) => t.to_string(),
                     _ => String::from("step"),
                 };
+                let json_ty = match (field_kind.as_str(), ty.to_string().as_str()) {
+                    ("toggle", _) => "boolean",
+                    (_, "f32" | "f64") => "number",
+                    _ => "integer",
+                };
+                let mut schema_range = quote! { #alloc::string::String::new() };
 
                 if field_kind == "step" {
                     let min = if let Some(min) = params.remove("min") {
@@ -268,6 +280,21 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                         quote! { 1.0f64 }
                     };
 
+                    // json has no infinities or NaN, a bound that isn't
+                    // finite is left out as if there were none
+                    schema_range = quote! {{
+                        let bound = |key: &str, v: f64| match v.is_finite() {
+                            true => #alloc::format!(",\"{}\":{}", key, v),
+                            false => #alloc::string::String::new(),
+                        };
+                        #alloc::format!(
+                            "{}{}{}",
+                            bound("minimum", (#min) as f64),
+                            bound("maximum", (#max) as f64),
+                            bound("x-step", (#step) as f64)
+                        )
+                    }};
+
                     let clamp_ident = format_ident!("clamp_{}", setter);
                     let pct_ident = format_ident!("{}_pct", ident);
                     q.extend(quote! {
@@ -338,6 +365,18 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                 } else {
                     panic!("Unknown field type {:?}", field);
                 }
+
+                let field_name = ident.to_string();
+                schema_fields.push(quote! {
+                    #alloc::format!(
+                        "\"{}\":{{\"type\":\"{}\",\"x-kind\":\"{}\"{}{}}}",
+                        #field_name,
+                        #json_ty,
+                        #field_kind,
+                        #schema_range,
+                        #knob_schema
+                    )
+                });
             } else {
                 continue;
             }
@@ -351,6 +390,22 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
         }
     }
 
+    // JSON schema of the adjustable fields for parameter editors, register it
+    // with spec_engine::set_settings_schema
+    let struct_name = struct_ident.to_string();
+    q.extend(quote! {
+        impl #impl_generics #struct_ident #ty_generics #where_clause {
+            pub fn settings_schema(&self) -> #alloc::string::String {
+                let properties: #alloc::vec::Vec<#alloc::string::String> = #alloc::vec![#(#schema_fields),*];
+                #alloc::format!(
+                    "{{\"title\":\"{}\",\"type\":\"object\",\"properties\":{{{}}}}}",
+                    #struct_name,
+                    properties.join(",")
+                )
+            }
+        }
+    });
+
     let all_ident = format_ident!("ALL_{}_UPDATERS", struct_ident.to_string().to_uppercase());
     let field_enum_ident = format_ident!("{}AllFieldsEnum", struct_ident);
     let field_change_ident = format_ident!("{}AllFieldsChange", struct_ident);
@@ -366,9 +421,9 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
 
     q.extend(quote! {
         impl #impl_generics #struct_ident #ty_generics #where_clause {
-            fn adjust(&mut self, kn: sdlrig::gfxinfo::Knob, idx: usize, inc: f64) {
+            fn adjust(&mut self, kn: #krate::gfxinfo::Knob, idx: usize, inc: f64) {
                 match (kn, idx) {
-                    #((sdlrig::gfxinfo::Knob::#knobs, #indexes) => {
+                    #((#krate::gfxinfo::Knob::#knobs, #indexes) => {
                         self.#adjusters(inc);
                     })*
                     _ => (),
@@ -390,8 +445,10 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
     let mut tween_field_idents = vec![];
     let mut tween_tys = vec![];
 
+    let mut field_names = vec![];
     for (field_ident, field_enum, field_ty, commander, do_not_record, tween) in &field_data {
         field_idents.push(field_ident);
+        field_names.push(field_ident.to_string());
         field_enums.push(field_enum);
         field_tys.push(field_ty);
         let fragement_from_new_value;
@@ -426,7 +483,7 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
     let ty_generics_turbo = ty_generics.as_turbofish();
     q.extend(quote! {
         impl #impl_generics #struct_ident #ty_generics #where_clause {
-            pub const #all_ident : [fn(&#struct_ident #ty_generics ) -> Vec<sdlrig::renderspec::RenderSpec>; #count] = [
+            pub const #all_ident : [fn(&#struct_ident #ty_generics ) -> #alloc::vec::Vec<#krate::renderspec::RenderSpec>; #count] = [
                 #(#struct_ident #ty_generics_turbo ::#commanders),*
             ];
         }
@@ -465,8 +522,8 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
         }
 
         impl #impl_generics #struct_ident #ty_generics #where_clause {
-            pub fn diff(&self, other: &#struct_ident #ty_generics) -> Vec<#field_change_ident> {
-                let mut diffs = vec![];
+            pub fn diff(&self, other: &#struct_ident #ty_generics) -> #alloc::vec::Vec<#field_change_ident> {
+                let mut diffs = #alloc::vec![];
                 #(
                     if self.#field_idents != other.#field_idents {
                         diffs.push(#field_change_ident {
@@ -488,8 +545,19 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
                 }
             }
 
-            pub fn get_commands(&self, fields: &[#field_enum_ident]) -> Vec<sdlrig::renderspec::RenderSpec> {
-                let mut commands = vec![];
+            // For editors that only know the names settings_schema gives,
+            // false when there's no such field
+            pub fn set_field_by_name(&mut self, name: &str, value: f64) -> bool {
+                let field = match name {
+                    #(#field_names => #field_enum_ident::#field_enums,)*
+                    _ => return false,
+                };
+                self.apply_diff(&[#field_change_ident { field, new_value: value }]);
+                true
+            }
+
+            pub fn get_commands(&self, fields: &[#field_enum_ident]) -> #alloc::vec::Vec<#krate::renderspec::RenderSpec> {
+                let mut commands = #alloc::vec![];
                 for field in fields {
                     match field {
                         #(#field_enum_ident::#field_enum_with_commanders => { commands.extend(self.#commanders()); })*
@@ -532,3 +600,16 @@ pub fn adjustable_macro_derive(input: proc_macro::TokenStream) -> proc_macro::To
     q.extend(diff_code);
     q.into()
 }
+
+// The guest api as the deriving crate depends on it: sdlrig-guest by whatever
+// name its Cargo.toml gives it, or else sdlrig, which re-exports it
+fn guest_crate() -> proc_macro2::TokenStream {
+    let found = crate_name("sdlrig-guest").or_else(|_| crate_name("sdlrig"));
+    let name = match found {
+        Ok(FoundCrate::Itself) => return quote! { crate },
+        Ok(FoundCrate::Name(name)) => name,
+        Err(_) => "sdlrig".into(),
+    };
+    let ident = format_ident!("{}", name);
+    quote! { ::#ident }
+}
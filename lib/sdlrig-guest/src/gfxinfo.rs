@@ -0,0 +1,2179 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GfxInfo {
+    VidInfo(VidInfo),
+    VidMixerInfo(VidMixerInfo),
+    PluginInfo(PluginAsset),
+    AudioInfo(AudioInfo),
+    // like plugins a font's info is the asset itself
+    FontInfo(FontAsset),
+    ShaderInfo(ShaderInfo),
+    LutInfo(LutInfo),
+    TexInfo(TexInfo),
+    MeshInfo(MeshInfo),
+}
+
+impl GfxInfo {
+    pub fn name(&self) -> &String {
+        //TODO fix this to be consistent (likely all &str and clone on the caller side)
+        match self {
+            GfxInfo::VidInfo(v) => &v.name,
+            GfxInfo::VidMixerInfo(v) => &v.name,
+            GfxInfo::PluginInfo(p) => &p.name,
+            GfxInfo::AudioInfo(a) => &a.name,
+            GfxInfo::FontInfo(f) => &f.name,
+            GfxInfo::ShaderInfo(s) => &s.name,
+            GfxInfo::LutInfo(l) => &l.name,
+            GfxInfo::TexInfo(t) => &t.name,
+            GfxInfo::MeshInfo(m) => &m.name,
+        }
+    }
+}
+macro_rules! gfxinfo_from {
+    ($info_type:ident) => {
+        impl From<$info_type> for GfxInfo {
+            fn from(value: $info_type) -> Self {
+                GfxInfo::$info_type(value)
+            }
+        }
+    };
+}
+
+gfxinfo_from! { VidInfo }
+gfxinfo_from! { VidMixerInfo }
+gfxinfo_from! { AudioInfo }
+gfxinfo_from! { ShaderInfo }
+gfxinfo_from! { LutInfo }
+gfxinfo_from! { TexInfo }
+gfxinfo_from! { MeshInfo }
+
+//Useful for comparing to assets during loading
+impl From<GfxInfo> for Asset {
+    fn from(value: GfxInfo) -> Self {
+        match value {
+            GfxInfo::VidInfo(v) => Asset::Vid(Vid {
+                name: v.name,
+                path: v.path,
+                repeat: v.repeat,
+                realtime: v.realtime,
+                resolution: v.size,
+                tbq: (0, 1),
+                codec: v.codec,
+                format: v.format,
+                opts: v.opts,
+                hardware_decode: v.hardware_decode,
+                software_filter: v.software_filter,
+                variants: v.variants,
+                load: v.load,
+            }),
+            GfxInfo::VidMixerInfo(v) => Asset::VidMixer(VidMixer {
+                name: v.name,
+                shader: v.shader,
+                shader_asset: v.shader_asset,
+                mesh_pass: v.mesh_pass,
+                width: v.width,
+                height: v.height,
+            }),
+            GfxInfo::PluginInfo(p) => Asset::Plugin(p),
+            GfxInfo::AudioInfo(a) => Asset::Audio(Audio {
+                name: a.name,
+                path: a.path,
+                load: a.load,
+            }),
+            GfxInfo::FontInfo(f) => Asset::Font(f),
+            GfxInfo::ShaderInfo(s) => Asset::Shader(Shader {
+                name: s.name,
+                path: s.path,
+            }),
+            GfxInfo::LutInfo(l) => Asset::Lut(Lut {
+                name: l.name,
+                path: l.path,
+            }),
+            GfxInfo::TexInfo(t) => Asset::Tex(Tex {
+                name: t.name,
+                path: t.path,
+                order: t.order,
+                variants: t.variants,
+                raster: t.raster,
+                load: t.load,
+                mipmaps: t.mipmaps,
+                slice: t.slice,
+                ignore: t.ignore,
+            }),
+            GfxInfo::MeshInfo(m) => Asset::Mesh(Mesh {
+                name: m.name,
+                path: m.path,
+                load: m.load,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub enum Asset {
+    #[default]
+    Missing,
+    Vid(Vid),
+    VidMixer(VidMixer),
+    Plugin(PluginAsset),
+    Audio(Audio),
+    Font(FontAsset),
+    Shader(Shader),
+    Lut(Lut),
+    Tex(Tex),
+    Mesh(Mesh),
+}
+
+impl From<Vid> for Asset {
+    fn from(value: Vid) -> Self {
+        Self::Vid(value)
+    }
+}
+
+impl From<VidMixer> for Asset {
+    fn from(value: VidMixer) -> Self {
+        Self::VidMixer(value)
+    }
+}
+
+impl Asset {
+    pub fn name(&self) -> &str {
+        match self {
+            Asset::Missing => "missing",
+            Asset::Vid(v) => &v.name,
+            Asset::VidMixer(vm) => &vm.name,
+            Asset::Plugin(p) => &p.name,
+            Asset::Audio(a) => &a.name,
+            Asset::Font(f) => &f.name,
+            Asset::Shader(s) => &s.name,
+            Asset::Lut(l) => &l.name,
+            Asset::Tex(t) => &t.name,
+            Asset::Mesh(m) => &m.name,
+        }
+    }
+
+    // The file it's loaded from, for the kinds that have one
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Asset::Vid(v) => Some(&v.path),
+            Asset::Audio(a) => Some(&a.path),
+            Asset::Font(f) => Some(&f.path),
+            Asset::Shader(s) => Some(&s.path),
+            Asset::Lut(l) => Some(&l.path),
+            Asset::Tex(t) => Some(&t.path),
+            Asset::Mesh(m) => Some(&m.path),
+            Asset::Missing | Asset::VidMixer(_) | Asset::Plugin(_) => None,
+        }
+    }
+
+    // Only media that takes a while to decode can put its loading off
+    pub fn load_policy(&self) -> LoadPolicy {
+        match self {
+            Asset::Vid(v) => v.load.clone(),
+            Asset::Audio(a) => a.load.clone(),
+            Asset::Tex(t) => t.load.clone(),
+            Asset::Mesh(m) => m.load.clone(),
+            _ => LoadPolicy::Eager,
+        }
+    }
+}
+
+// When an asset the app lists is loaded. Specs that refer to one still on
+// its way are skipped, a Mix clearing its mixer to black in the meantime.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadPolicy {
+    // before the app's first frame
+    #[default]
+    Eager,
+    // in the background once the app is running
+    Lazy,
+    // when a spec first refers to it
+    OnFirstUse,
+}
+
+// Loaded by whichever viz --plugin handles its kind, args is json for the
+// plugin. Its info is the asset itself, the host knows nothing more about it.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct PluginAsset {
+    pub name: String,
+    pub kind: String,
+    pub args: String,
+}
+
+impl PluginAsset {
+    pub fn new<N: AsRef<str>, K: AsRef<str>, T: Serialize>(
+        name: N,
+        kind: K,
+        args: &T,
+    ) -> serde_json::Result<Self> {
+        Ok(Self {
+            name: name.as_ref().into(),
+            kind: kind.as_ref().into(),
+            args: serde_json::to_string(args)?,
+        })
+    }
+}
+
+impl From<PluginAsset> for Asset {
+    fn from(value: PluginAsset) -> Self {
+        Self::Plugin(value)
+    }
+}
+
+// A sound file decoded when it loads, so an AudioPlay of its name starts
+// without waiting
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Audio {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+impl Audio {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+            load: LoadPolicy::default(),
+        }
+    }
+
+    pub fn load(mut self, load: LoadPolicy) -> Self {
+        self.load = load;
+        self
+    }
+}
+
+impl From<Audio> for Asset {
+    fn from(value: Audio) -> Self {
+        Self::Audio(value)
+    }
+}
+
+// The file as it was, before it was decoded for playback
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AudioInfo {
+    pub name: String,
+    pub path: String,
+    pub duration_ms: u64,
+    pub sample_rate: u32,
+    pub channels: u16,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+impl AudioInfo {
+    pub fn duration(&self) -> f64 {
+        self.duration_ms as f64 / 1000.0
+    }
+}
+
+// A ttf/otf file DrawText and the spans of it and HudText can use by name,
+// sizes are opened up front so the first text drawn at them doesn't stall a
+// frame. One that won't open fails its AssetEvent.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct FontAsset {
+    pub name: String,
+    pub path: String,
+    pub sizes: Vec<u16>,
+}
+
+impl FontAsset {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P, sizes: &[u16]) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+            sizes: sizes.to_vec(),
+        }
+    }
+}
+
+impl From<FontAsset> for Asset {
+    fn from(value: FontAsset) -> Self {
+        Self::Font(value)
+    }
+}
+
+// GLSL in a file, for VidMixers built with shader_asset. Its #include "file"
+// lines are resolved next to it, and it's loaded again when any of them change.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Shader {
+    pub name: String,
+    pub path: String,
+}
+
+impl Shader {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+        }
+    }
+}
+
+impl From<Shader> for Asset {
+    fn from(value: Shader) -> Self {
+        Self::Shader(value)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ShaderStatus {
+    // read, but no mixer has compiled it yet
+    #[default]
+    Loaded,
+    Compiled,
+    Failed(String),
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShaderInfo {
+    pub name: String,
+    pub path: String,
+    // every file it included, as the host found them
+    pub includes: Vec<String>,
+    pub status: ShaderStatus,
+}
+
+// A .cube file a Mix can grade with by name, checked when it loads
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Lut {
+    pub name: String,
+    pub path: String,
+}
+
+impl Lut {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+        }
+    }
+}
+
+impl From<Lut> for Asset {
+    fn from(value: Lut) -> Self {
+        Self::Lut(value)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LutInfo {
+    pub name: String,
+    pub path: String,
+    pub title: Option<String>,
+    // 1 or 3
+    pub dimensions: u8,
+    // entries along each dimension
+    pub size: u32,
+}
+
+// Stills from an image file or a glob of them, each match being a frame
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Tex {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub order: TexOrder,
+    // like a Vid's, by label
+    #[serde(default)]
+    pub variants: Vec<(String, String)>,
+    #[serde(default)]
+    pub raster: TexRaster,
+    #[serde(default)]
+    pub load: LoadPolicy,
+    // halved copies down to 1x1, mixers smaller than the texture sample the
+    // nearest one instead of aliasing
+    #[serde(default)]
+    pub mipmaps: bool,
+    // a sprite sheet's cells as frames, instead of a file per frame
+    #[serde(default)]
+    pub slice: Option<TexSlice>,
+    // names path's glob leaves out along with anything under them, like
+    // *.psd or drafts
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+impl Tex {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+            order: TexOrder::default(),
+            variants: vec![],
+            raster: TexRaster::default(),
+            load: LoadPolicy::default(),
+            mipmaps: false,
+            slice: None,
+            ignore: vec![],
+        }
+    }
+
+    pub fn order(mut self, order: TexOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn raster(mut self, raster: TexRaster) -> Self {
+        self.raster = raster;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn slice(mut self, cols: u32, rows: u32, padding: u32) -> Self {
+        self.slice = Some(TexSlice {
+            cols,
+            rows,
+            padding,
+        });
+        self
+    }
+
+    pub fn load(mut self, load: LoadPolicy) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn ignore<I: AsRef<str>>(mut self, pattern: I) -> Self {
+        self.ignore.push(pattern.as_ref().into());
+        self
+    }
+
+    pub fn variant<L: AsRef<str>, P: AsRef<str>>(mut self, label: L, path: P) -> Self {
+        self.variants
+            .push((label.as_ref().into(), path.as_ref().into()));
+        self
+    }
+}
+
+impl From<Tex> for Asset {
+    fn from(value: Tex) -> Self {
+        Self::Tex(value)
+    }
+}
+
+// The size svg files are rasterized at, other files keep their own. Asking
+// for a Tex again with a different one rasterizes it again.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub enum TexRaster {
+    // what the svg says
+    #[default]
+    Native,
+    // width by height, 0 for either keeps the svg's aspect
+    Size(u32, u32),
+    // fit to the output's drawable size, and again whenever that changes
+    Output,
+}
+
+// Cuts each file into a grid of frames, left to right then top to bottom,
+// with padding pixels between neighbouring cells and none around the edge
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct TexSlice {
+    pub cols: u32,
+    pub rows: u32,
+    #[serde(default)]
+    pub padding: u32,
+}
+
+// How a Tex's glob matches become its frames
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub enum TexOrder {
+    // by name with runs of digits compared as numbers, frame_2 before frame_10
+    #[default]
+    Natural,
+    // oldest first
+    Mtime,
+    // the matches with these file names, in this order, anything else is left out
+    List(Vec<String>),
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TexInfo {
+    pub name: String,
+    pub count: usize,
+    pub size: (u32, u32),
+    pub path: String,
+    pub order: TexOrder,
+    // the file of each frame, in order, repeated for an animated file's frames
+    pub frames: Vec<String>,
+    #[serde(default)]
+    pub variants: Vec<(String, String)>,
+    // how long each frame shows, zero for stills
+    #[serde(default)]
+    pub delays_ms: Vec<u32>,
+    // float frames from exr or hdr files, 8-bit rgba otherwise
+    #[serde(default)]
+    pub hdr: bool,
+    #[serde(default)]
+    pub raster: TexRaster,
+    #[serde(default)]
+    pub load: LoadPolicy,
+    #[serde(default)]
+    pub mipmaps: bool,
+    #[serde(default)]
+    pub slice: Option<TexSlice>,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+impl TexInfo {
+    // The frame showing ms into a loop of the animation, 0 without one
+    pub fn frame_at(&self, ms: u64) -> usize {
+        let total: u64 = self.delays_ms.iter().map(|&delay| delay as u64).sum();
+        if total == 0 {
+            return 0;
+        }
+        let mut ms = ms % total;
+        for (i, &delay) in self.delays_ms.iter().enumerate() {
+            if ms < delay as u64 {
+                return i;
+            }
+            ms -= delay as u64;
+        }
+        0
+    }
+}
+
+// Triangles from an obj, gltf or glb file, positions and uvs only. A
+// VidMixer's mesh_pass draws it.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Mesh {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+impl Mesh {
+    pub fn new<N: AsRef<str>, P: AsRef<str>>(name: N, path: P) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            path: path.as_ref().into(),
+            load: LoadPolicy::default(),
+        }
+    }
+
+    pub fn load(mut self, load: LoadPolicy) -> Self {
+        self.load = load;
+        self
+    }
+}
+
+impl From<Mesh> for Asset {
+    fn from(value: Mesh) -> Self {
+        Self::Mesh(value)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MeshInfo {
+    pub name: String,
+    pub path: String,
+    pub triangles: usize,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VidInfo {
+    pub name: String,
+    pub path: String,
+    pub repeat: bool,
+    pub codec: Option<String>,
+    pub format: Option<String>,
+    pub opts: Option<Vec<(String, String)>>,
+    pub size: (u32, u32),
+    pub duration_tbu_q: (i32, i32),
+    pub timebase_q: (i32, i32),
+    pub realtime: bool,
+    pub hardware_decode: bool,
+    pub software_filter: bool,
+    #[serde(default)]
+    pub variants: Vec<(String, String)>,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+impl VidInfo {
+    pub fn duration(&self) -> f64 {
+        self.duration_tbu_q.0 as f64 / self.duration_tbu_q.1 as f64
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Vid {
+    pub name: String,
+    pub path: String,
+    pub repeat: bool,
+    pub realtime: bool,
+    pub resolution: (u32, u32),
+    pub tbq: (i32, i32),
+    pub codec: Option<String>,
+    pub format: Option<String>,
+    pub opts: Option<Vec<(String, String)>>,
+    pub hardware_decode: bool,
+    pub software_filter: bool,
+    // other files of the same clip by label, like ("half", "clip_540p.mp4"),
+    // the one viz --proxy names is loaded instead of path
+    #[serde(default)]
+    pub variants: Vec<(String, String)>,
+    #[serde(default)]
+    pub load: LoadPolicy,
+}
+
+impl Vid {
+    pub fn builder() -> VidBuilder {
+        VidBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct VidBuilder {
+    pub name: String,
+    pub path: String,
+    pub repeat: bool,
+    pub pix_fmt: String,
+    pub resolution: (u32, u32),
+    pub tbq: (i32, i32),
+    pub codec: Option<String>,
+    pub format: Option<String>,
+    pub opts: Option<Vec<(String, String)>>,
+    pub realtime: bool,
+    pub hardware_decode: bool,
+    pub software_filter: bool,
+    pub variants: Vec<(String, String)>,
+    pub load: LoadPolicy,
+}
+
+impl VidBuilder {
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.name = name.as_ref().into();
+        self
+    }
+
+    pub fn path<T>(mut self, path: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.path = path.as_ref().into();
+        self
+    }
+
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn resolution(mut self, resolution: (u32, u32)) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn pix_fmt(mut self, pix_fmt: &str) -> Self {
+        self.pix_fmt = pix_fmt.into();
+        self
+    }
+
+    pub fn tbq(mut self, tbq: (i32, i32)) -> Self {
+        self.tbq = tbq;
+        self
+    }
+
+    pub fn codec<T>(mut self, codec: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.codec = Some(codec.as_ref().into());
+        self
+    }
+
+    pub fn format<T>(mut self, format: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.format = Some(format.as_ref().into());
+        self
+    }
+
+    pub fn opts<T>(mut self, opts: &[(T, T)]) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.opts = Some(
+            opts.iter()
+                .map(|(k, v)| (k.as_ref().into(), v.as_ref().into()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    pub fn hardware_decode(mut self, hardware_decode: bool) -> Self {
+        self.hardware_decode = hardware_decode;
+        self
+    }
+
+    pub fn software_filter(mut self, software_filter: bool) -> Self {
+        self.software_filter = software_filter;
+        self
+    }
+
+    pub fn variant<L, P>(mut self, label: L, path: P) -> Self
+    where
+        L: AsRef<str>,
+        P: AsRef<str>,
+    {
+        self.variants
+            .push((label.as_ref().into(), path.as_ref().into()));
+        self
+    }
+
+    pub fn load(mut self, load: LoadPolicy) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn build(self) -> Vid {
+        Vid {
+            name: self.name,
+            path: self.path,
+            repeat: self.repeat,
+            resolution: self.resolution,
+            tbq: self.tbq,
+            codec: self.codec,
+            format: self.format,
+            opts: self.opts,
+            realtime: self.realtime,
+            hardware_decode: self.hardware_decode,
+            software_filter: self.software_filter,
+            variants: self.variants,
+            load: self.load,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct BufferSrcArgs {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub tbn: i32,
+    pub tbd: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct VidMixer {
+    pub name: String,
+    pub shader: Option<String>,
+    // the name of a Shader asset to use instead of shader
+    #[serde(default)]
+    pub shader_asset: Option<String>,
+    #[serde(default)]
+    pub mesh_pass: Option<MeshPass>,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Makes a mixer's pass0 draw a Mesh asset's triangles over black instead of
+// covering the whole frame. vertex is GLSL defining
+// vec4 vertex(vec3 position, inout vec2 uv), returning clip space with y up,
+// and can use the mixer's uniforms and inputs. pass0 then shades each pixel
+// with src_uv being the interpolated uv. There's no depth buffer, triangles
+// draw in the order the file has them.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct MeshPass {
+    pub mesh: String,
+    pub vertex: String,
+}
+
+impl VidMixer {
+    pub fn builder() -> VidMixerBuilder {
+        VidMixerBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct VidMixerBuilder {
+    name: Option<String>,
+    shader: Option<String>,
+    shader_asset: Option<String>,
+    mesh_pass: Option<MeshPass>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl VidMixerBuilder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            shader: None,
+            shader_asset: None,
+            mesh_pass: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.name = Some(name.as_ref().into());
+        self
+    }
+
+    pub fn shader<T>(mut self, shader: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.shader = Some(shader.as_ref().into());
+        self
+    }
+
+    pub fn shader_asset<T>(mut self, shader_asset: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.shader_asset = Some(shader_asset.as_ref().into());
+        self
+    }
+
+    pub fn mesh_pass<M: AsRef<str>, V: AsRef<str>>(mut self, mesh: M, vertex: V) -> Self {
+        self.mesh_pass = Some(MeshPass {
+            mesh: mesh.as_ref().into(),
+            vertex: vertex.as_ref().into(),
+        });
+        self
+    }
+
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn build(self) -> VidMixer {
+        let shader = match self.shader_asset {
+            Some(_) => self.shader,
+            None => Some(self.shader.unwrap_or(
+                "void pass0(out vec4 color) { color = texture(src_tex0, src_uv); }".into(),
+            )),
+        };
+        VidMixer {
+            name: self.name.unwrap(),
+            shader,
+            shader_asset: self.shader_asset,
+            mesh_pass: self.mesh_pass,
+            width: self.width.unwrap(),
+            height: self.height.unwrap(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct VidMixerInfo {
+    pub name: String,
+    pub shader: Option<String>,
+    #[serde(default)]
+    pub shader_asset: Option<String>,
+    #[serde(default)]
+    pub mesh_pass: Option<MeshPass>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<VidMixer> for VidMixerInfo {
+    fn from(value: VidMixer) -> Self {
+        Self {
+            name: value.name,
+            shader: value.shader,
+            shader_asset: value.shader_asset,
+            mesh_pass: value.mesh_pass,
+            width: value.width,
+            height: value.height,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Knob {
+    B = 0,  // Bottom
+    R = 1,  // Top Right
+    L = 2,  // Top Left
+    CB = 3, // Bottom counter
+    CR = 4, // Right counter
+    CL = 5, // Left counter
+    CF = 6, // Function key counter
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum KeyCode {
+    SDLK_UNKNOWN = 0,
+    SDLK_RETURN = 13,
+    SDLK_ESCAPE = 27,
+    SDLK_BACKSPACE = 8,
+    SDLK_TAB = 9,
+    SDLK_SPACE = 32,
+    SDLK_EXCLAIM = 33,
+    SDLK_QUOTEDBL = 34,
+    SDLK_HASH = 35,
+    SDLK_PERCENT = 37,
+    SDLK_DOLLAR = 36,
+    SDLK_AMPERSAND = 38,
+    SDLK_QUOTE = 39,
+    SDLK_LEFTPAREN = 40,
+    SDLK_RIGHTPAREN = 41,
+    SDLK_ASTERISK = 42,
+    SDLK_PLUS = 43,
+    SDLK_COMMA = 44,
+    SDLK_MINUS = 45,
+    SDLK_PERIOD = 46,
+    SDLK_SLASH = 47,
+    SDLK_0 = 48,
+    SDLK_1 = 49,
+    SDLK_2 = 50,
+    SDLK_3 = 51,
+    SDLK_4 = 52,
+    SDLK_5 = 53,
+    SDLK_6 = 54,
+    SDLK_7 = 55,
+    SDLK_8 = 56,
+    SDLK_9 = 57,
+    SDLK_COLON = 58,
+    SDLK_SEMICOLON = 59,
+    SDLK_LESS = 60,
+    SDLK_EQUALS = 61,
+    SDLK_GREATER = 62,
+    SDLK_QUESTION = 63,
+    SDLK_AT = 64,
+    SDLK_LEFTBRACKET = 91,
+    SDLK_BACKSLASH = 92,
+    SDLK_RIGHTBRACKET = 93,
+    SDLK_CARET = 94,
+    SDLK_UNDERSCORE = 95,
+    SDLK_BACKQUOTE = 96,
+    SDLK_a = 97,
+    SDLK_b = 98,
+    SDLK_c = 99,
+    SDLK_d = 100,
+    SDLK_e = 101,
+    SDLK_f = 102,
+    SDLK_g = 103,
+    SDLK_h = 104,
+    SDLK_i = 105,
+    SDLK_j = 106,
+    SDLK_k = 107,
+    SDLK_l = 108,
+    SDLK_m = 109,
+    SDLK_n = 110,
+    SDLK_o = 111,
+    SDLK_p = 112,
+    SDLK_q = 113,
+    SDLK_r = 114,
+    SDLK_s = 115,
+    SDLK_t = 116,
+    SDLK_u = 117,
+    SDLK_v = 118,
+    SDLK_w = 119,
+    SDLK_x = 120,
+    SDLK_y = 121,
+    SDLK_z = 122,
+    SDLK_CAPSLOCK = 1073741881,
+    SDLK_F1 = 1073741882,
+    SDLK_F2 = 1073741883,
+    SDLK_F3 = 1073741884,
+    SDLK_F4 = 1073741885,
+    SDLK_F5 = 1073741886,
+    SDLK_F6 = 1073741887,
+    SDLK_F7 = 1073741888,
+    SDLK_F8 = 1073741889,
+    SDLK_F9 = 1073741890,
+    SDLK_F10 = 1073741891,
+    SDLK_F11 = 1073741892,
+    SDLK_F12 = 1073741893,
+    SDLK_PRINTSCREEN = 1073741894,
+    SDLK_SCROLLLOCK = 1073741895,
+    SDLK_PAUSE = 1073741896,
+    SDLK_INSERT = 1073741897,
+    SDLK_HOME = 1073741898,
+    SDLK_PAGEUP = 1073741899,
+    SDLK_DELETE = 127,
+    SDLK_END = 1073741901,
+    SDLK_PAGEDOWN = 1073741902,
+    SDLK_RIGHT = 1073741903,
+    SDLK_LEFT = 1073741904,
+    SDLK_DOWN = 1073741905,
+    SDLK_UP = 1073741906,
+    SDLK_NUMLOCKCLEAR = 1073741907,
+    SDLK_KP_DIVIDE = 1073741908,
+    SDLK_KP_MULTIPLY = 1073741909,
+    SDLK_KP_MINUS = 1073741910,
+    SDLK_KP_PLUS = 1073741911,
+    SDLK_KP_ENTER = 1073741912,
+    SDLK_KP_1 = 1073741913,
+    SDLK_KP_2 = 1073741914,
+    SDLK_KP_3 = 1073741915,
+    SDLK_KP_4 = 1073741916,
+    SDLK_KP_5 = 1073741917,
+    SDLK_KP_6 = 1073741918,
+    SDLK_KP_7 = 1073741919,
+    SDLK_KP_8 = 1073741920,
+    SDLK_KP_9 = 1073741921,
+    SDLK_KP_0 = 1073741922,
+    SDLK_KP_PERIOD = 1073741923,
+    SDLK_APPLICATION = 1073741925,
+    SDLK_POWER = 1073741926,
+    SDLK_KP_EQUALS = 1073741927,
+    SDLK_F13 = 1073741928,
+    SDLK_F14 = 1073741929,
+    SDLK_F15 = 1073741930,
+    SDLK_F16 = 1073741931,
+    SDLK_F17 = 1073741932,
+    SDLK_F18 = 1073741933,
+    SDLK_F19 = 1073741934,
+    SDLK_F20 = 1073741935,
+    SDLK_F21 = 1073741936,
+    SDLK_F22 = 1073741937,
+    SDLK_F23 = 1073741938,
+    SDLK_F24 = 1073741939,
+    SDLK_EXECUTE = 1073741940,
+    SDLK_HELP = 1073741941,
+    SDLK_MENU = 1073741942,
+    SDLK_SELECT = 1073741943,
+    SDLK_STOP = 1073741944,
+    SDLK_AGAIN = 1073741945,
+    SDLK_UNDO = 1073741946,
+    SDLK_CUT = 1073741947,
+    SDLK_COPY = 1073741948,
+    SDLK_PASTE = 1073741949,
+    SDLK_FIND = 1073741950,
+    SDLK_MUTE = 1073741951,
+    SDLK_VOLUMEUP = 1073741952,
+    SDLK_VOLUMEDOWN = 1073741953,
+    SDLK_KP_COMMA = 1073741957,
+    SDLK_KP_EQUALSAS400 = 1073741958,
+    SDLK_ALTERASE = 1073741977,
+    SDLK_SYSREQ = 1073741978,
+    SDLK_CANCEL = 1073741979,
+    SDLK_CLEAR = 1073741980,
+    SDLK_PRIOR = 1073741981,
+    SDLK_RETURN2 = 1073741982,
+    SDLK_SEPARATOR = 1073741983,
+    SDLK_OUT = 1073741984,
+    SDLK_OPER = 1073741985,
+    SDLK_CLEARAGAIN = 1073741986,
+    SDLK_CRSEL = 1073741987,
+    SDLK_EXSEL = 1073741988,
+    SDLK_KP_00 = 1073742000,
+    SDLK_KP_000 = 1073742001,
+    SDLK_THOUSANDSSEPARATOR = 1073742002,
+    SDLK_DECIMALSEPARATOR = 1073742003,
+    SDLK_CURRENCYUNIT = 1073742004,
+    SDLK_CURRENCYSUBUNIT = 1073742005,
+    SDLK_KP_LEFTPAREN = 1073742006,
+    SDLK_KP_RIGHTPAREN = 1073742007,
+    SDLK_KP_LEFTBRACE = 1073742008,
+    SDLK_KP_RIGHTBRACE = 1073742009,
+    SDLK_KP_TAB = 1073742010,
+    SDLK_KP_BACKSPACE = 1073742011,
+    SDLK_KP_A = 1073742012,
+    SDLK_KP_B = 1073742013,
+    SDLK_KP_C = 1073742014,
+    SDLK_KP_D = 1073742015,
+    SDLK_KP_E = 1073742016,
+    SDLK_KP_F = 1073742017,
+    SDLK_KP_XOR = 1073742018,
+    SDLK_KP_POWER = 1073742019,
+    SDLK_KP_PERCENT = 1073742020,
+    SDLK_KP_LESS = 1073742021,
+    SDLK_KP_GREATER = 1073742022,
+    SDLK_KP_AMPERSAND = 1073742023,
+    SDLK_KP_DBLAMPERSAND = 1073742024,
+    SDLK_KP_VERTICALBAR = 1073742025,
+    SDLK_KP_DBLVERTICALBAR = 1073742026,
+    SDLK_KP_COLON = 1073742027,
+    SDLK_KP_HASH = 1073742028,
+    SDLK_KP_SPACE = 1073742029,
+    SDLK_KP_AT = 1073742030,
+    SDLK_KP_EXCLAM = 1073742031,
+    SDLK_KP_MEMSTORE = 1073742032,
+    SDLK_KP_MEMRECALL = 1073742033,
+    SDLK_KP_MEMCLEAR = 1073742034,
+    SDLK_KP_MEMADD = 1073742035,
+    SDLK_KP_MEMSUBTRACT = 1073742036,
+    SDLK_KP_MEMMULTIPLY = 1073742037,
+    SDLK_KP_MEMDIVIDE = 1073742038,
+    SDLK_KP_PLUSMINUS = 1073742039,
+    SDLK_KP_CLEAR = 1073742040,
+    SDLK_KP_CLEARENTRY = 1073742041,
+    SDLK_KP_BINARY = 1073742042,
+    SDLK_KP_OCTAL = 1073742043,
+    SDLK_KP_DECIMAL = 1073742044,
+    SDLK_KP_HEXADECIMAL = 1073742045,
+    SDLK_LCTRL = 1073742048,
+    SDLK_LSHIFT = 1073742049,
+    SDLK_LALT = 1073742050,
+    SDLK_LGUI = 1073742051,
+    SDLK_RCTRL = 1073742052,
+    SDLK_RSHIFT = 1073742053,
+    SDLK_RALT = 1073742054,
+    SDLK_RGUI = 1073742055,
+    SDLK_MODE = 1073742081,
+    SDLK_AUDIONEXT = 1073742082,
+    SDLK_AUDIOPREV = 1073742083,
+    SDLK_AUDIOSTOP = 1073742084,
+    SDLK_AUDIOPLAY = 1073742085,
+    SDLK_AUDIOMUTE = 1073742086,
+    SDLK_MEDIASELECT = 1073742087,
+    SDLK_WWW = 1073742088,
+    SDLK_MAIL = 1073742089,
+    SDLK_CALCULATOR = 1073742090,
+    SDLK_COMPUTER = 1073742091,
+    SDLK_AC_SEARCH = 1073742092,
+    SDLK_AC_HOME = 1073742093,
+    SDLK_AC_BACK = 1073742094,
+    SDLK_AC_FORWARD = 1073742095,
+    SDLK_AC_STOP = 1073742096,
+    SDLK_AC_REFRESH = 1073742097,
+    SDLK_AC_BOOKMARKS = 1073742098,
+    SDLK_BRIGHTNESSDOWN = 1073742099,
+    SDLK_BRIGHTNESSUP = 1073742100,
+    SDLK_DISPLAYSWITCH = 1073742101,
+    SDLK_KBDILLUMTOGGLE = 1073742102,
+    SDLK_KBDILLUMDOWN = 1073742103,
+    SDLK_KBDILLUMUP = 1073742104,
+    SDLK_EJECT = 1073742105,
+    SDLK_SLEEP = 1073742106,
+    SDLK_APP1 = 1073742107,
+    SDLK_APP2 = 1073742108,
+    SDLK_AUDIOREWIND = 1073742109,
+    SDLK_AUDIOFASTFORWARD = 1073742110,
+}
+
+impl From<u32> for KeyCode {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => KeyCode::SDLK_UNKNOWN,
+            13 => KeyCode::SDLK_RETURN,
+            27 => KeyCode::SDLK_ESCAPE,
+            8 => KeyCode::SDLK_BACKSPACE,
+            9 => KeyCode::SDLK_TAB,
+            32 => KeyCode::SDLK_SPACE,
+            33 => KeyCode::SDLK_EXCLAIM,
+            34 => KeyCode::SDLK_QUOTEDBL,
+            35 => KeyCode::SDLK_HASH,
+            37 => KeyCode::SDLK_PERCENT,
+            36 => KeyCode::SDLK_DOLLAR,
+            38 => KeyCode::SDLK_AMPERSAND,
+            39 => KeyCode::SDLK_QUOTE,
+            40 => KeyCode::SDLK_LEFTPAREN,
+            41 => KeyCode::SDLK_RIGHTPAREN,
+            42 => KeyCode::SDLK_ASTERISK,
+            43 => KeyCode::SDLK_PLUS,
+            44 => KeyCode::SDLK_COMMA,
+            45 => KeyCode::SDLK_MINUS,
+            46 => KeyCode::SDLK_PERIOD,
+            47 => KeyCode::SDLK_SLASH,
+            48 => KeyCode::SDLK_0,
+            49 => KeyCode::SDLK_1,
+            50 => KeyCode::SDLK_2,
+            51 => KeyCode::SDLK_3,
+            52 => KeyCode::SDLK_4,
+            53 => KeyCode::SDLK_5,
+            54 => KeyCode::SDLK_6,
+            55 => KeyCode::SDLK_7,
+            56 => KeyCode::SDLK_8,
+            57 => KeyCode::SDLK_9,
+            58 => KeyCode::SDLK_COLON,
+            59 => KeyCode::SDLK_SEMICOLON,
+            60 => KeyCode::SDLK_LESS,
+            61 => KeyCode::SDLK_EQUALS,
+            62 => KeyCode::SDLK_GREATER,
+            63 => KeyCode::SDLK_QUESTION,
+            64 => KeyCode::SDLK_AT,
+            91 => KeyCode::SDLK_LEFTBRACKET,
+            92 => KeyCode::SDLK_BACKSLASH,
+            93 => KeyCode::SDLK_RIGHTBRACKET,
+            94 => KeyCode::SDLK_CARET,
+            95 => KeyCode::SDLK_UNDERSCORE,
+            96 => KeyCode::SDLK_BACKQUOTE,
+            97 => KeyCode::SDLK_a,
+            98 => KeyCode::SDLK_b,
+            99 => KeyCode::SDLK_c,
+            100 => KeyCode::SDLK_d,
+            101 => KeyCode::SDLK_e,
+            102 => KeyCode::SDLK_f,
+            103 => KeyCode::SDLK_g,
+            104 => KeyCode::SDLK_h,
+            105 => KeyCode::SDLK_i,
+            106 => KeyCode::SDLK_j,
+            107 => KeyCode::SDLK_k,
+            108 => KeyCode::SDLK_l,
+            109 => KeyCode::SDLK_m,
+            110 => KeyCode::SDLK_n,
+            111 => KeyCode::SDLK_o,
+            112 => KeyCode::SDLK_p,
+            113 => KeyCode::SDLK_q,
+            114 => KeyCode::SDLK_r,
+            115 => KeyCode::SDLK_s,
+            116 => KeyCode::SDLK_t,
+            117 => KeyCode::SDLK_u,
+            118 => KeyCode::SDLK_v,
+            119 => KeyCode::SDLK_w,
+            120 => KeyCode::SDLK_x,
+            121 => KeyCode::SDLK_y,
+            122 => KeyCode::SDLK_z,
+            1073741881 => KeyCode::SDLK_CAPSLOCK,
+            1073741882 => KeyCode::SDLK_F1,
+            1073741883 => KeyCode::SDLK_F2,
+            1073741884 => KeyCode::SDLK_F3,
+            1073741885 => KeyCode::SDLK_F4,
+            1073741886 => KeyCode::SDLK_F5,
+            1073741887 => KeyCode::SDLK_F6,
+            1073741888 => KeyCode::SDLK_F7,
+            1073741889 => KeyCode::SDLK_F8,
+            1073741890 => KeyCode::SDLK_F9,
+            1073741891 => KeyCode::SDLK_F10,
+            1073741892 => KeyCode::SDLK_F11,
+            1073741893 => KeyCode::SDLK_F12,
+            1073741894 => KeyCode::SDLK_PRINTSCREEN,
+            1073741895 => KeyCode::SDLK_SCROLLLOCK,
+            1073741896 => KeyCode::SDLK_PAUSE,
+            1073741897 => KeyCode::SDLK_INSERT,
+            1073741898 => KeyCode::SDLK_HOME,
+            1073741899 => KeyCode::SDLK_PAGEUP,
+            127 => KeyCode::SDLK_DELETE,
+            1073741901 => KeyCode::SDLK_END,
+            1073741902 => KeyCode::SDLK_PAGEDOWN,
+            1073741903 => KeyCode::SDLK_RIGHT,
+            1073741904 => KeyCode::SDLK_LEFT,
+            1073741905 => KeyCode::SDLK_DOWN,
+            1073741906 => KeyCode::SDLK_UP,
+            1073741907 => KeyCode::SDLK_NUMLOCKCLEAR,
+            1073741908 => KeyCode::SDLK_KP_DIVIDE,
+            1073741909 => KeyCode::SDLK_KP_MULTIPLY,
+            1073741910 => KeyCode::SDLK_KP_MINUS,
+            1073741911 => KeyCode::SDLK_KP_PLUS,
+            1073741912 => KeyCode::SDLK_KP_ENTER,
+            1073741913 => KeyCode::SDLK_KP_1,
+            1073741914 => KeyCode::SDLK_KP_2,
+            1073741915 => KeyCode::SDLK_KP_3,
+            1073741916 => KeyCode::SDLK_KP_4,
+            1073741917 => KeyCode::SDLK_KP_5,
+            1073741918 => KeyCode::SDLK_KP_6,
+            1073741919 => KeyCode::SDLK_KP_7,
+            1073741920 => KeyCode::SDLK_KP_8,
+            1073741921 => KeyCode::SDLK_KP_9,
+            1073741922 => KeyCode::SDLK_KP_0,
+            1073741923 => KeyCode::SDLK_KP_PERIOD,
+            1073741925 => KeyCode::SDLK_APPLICATION,
+            1073741926 => KeyCode::SDLK_POWER,
+            1073741927 => KeyCode::SDLK_KP_EQUALS,
+            1073741928 => KeyCode::SDLK_F13,
+            1073741929 => KeyCode::SDLK_F14,
+            1073741930 => KeyCode::SDLK_F15,
+            1073741931 => KeyCode::SDLK_F16,
+            1073741932 => KeyCode::SDLK_F17,
+            1073741933 => KeyCode::SDLK_F18,
+            1073741934 => KeyCode::SDLK_F19,
+            1073741935 => KeyCode::SDLK_F20,
+            1073741936 => KeyCode::SDLK_F21,
+            1073741937 => KeyCode::SDLK_F22,
+            1073741938 => KeyCode::SDLK_F23,
+            1073741939 => KeyCode::SDLK_F24,
+            1073741940 => KeyCode::SDLK_EXECUTE,
+            1073741941 => KeyCode::SDLK_HELP,
+            1073741942 => KeyCode::SDLK_MENU,
+            1073741943 => KeyCode::SDLK_SELECT,
+            1073741944 => KeyCode::SDLK_STOP,
+            1073741945 => KeyCode::SDLK_AGAIN,
+            1073741946 => KeyCode::SDLK_UNDO,
+            1073741947 => KeyCode::SDLK_CUT,
+            1073741948 => KeyCode::SDLK_COPY,
+            1073741949 => KeyCode::SDLK_PASTE,
+            1073741950 => KeyCode::SDLK_FIND,
+            1073741951 => KeyCode::SDLK_MUTE,
+            1073741952 => KeyCode::SDLK_VOLUMEUP,
+            1073741953 => KeyCode::SDLK_VOLUMEDOWN,
+            1073741957 => KeyCode::SDLK_KP_COMMA,
+            1073741958 => KeyCode::SDLK_KP_EQUALSAS400,
+            1073741977 => KeyCode::SDLK_ALTERASE,
+            1073741978 => KeyCode::SDLK_SYSREQ,
+            1073741979 => KeyCode::SDLK_CANCEL,
+            1073741980 => KeyCode::SDLK_CLEAR,
+            1073741981 => KeyCode::SDLK_PRIOR,
+            1073741982 => KeyCode::SDLK_RETURN2,
+            1073741983 => KeyCode::SDLK_SEPARATOR,
+            1073741984 => KeyCode::SDLK_OUT,
+            1073741985 => KeyCode::SDLK_OPER,
+            1073741986 => KeyCode::SDLK_CLEARAGAIN,
+            1073741987 => KeyCode::SDLK_CRSEL,
+            1073741988 => KeyCode::SDLK_EXSEL,
+            1073742000 => KeyCode::SDLK_KP_00,
+            1073742001 => KeyCode::SDLK_KP_000,
+            1073742002 => KeyCode::SDLK_THOUSANDSSEPARATOR,
+            1073742003 => KeyCode::SDLK_DECIMALSEPARATOR,
+            1073742004 => KeyCode::SDLK_CURRENCYUNIT,
+            1073742005 => KeyCode::SDLK_CURRENCYSUBUNIT,
+            1073742006 => KeyCode::SDLK_KP_LEFTPAREN,
+            1073742007 => KeyCode::SDLK_KP_RIGHTPAREN,
+            1073742008 => KeyCode::SDLK_KP_LEFTBRACE,
+            1073742009 => KeyCode::SDLK_KP_RIGHTBRACE,
+            1073742010 => KeyCode::SDLK_KP_TAB,
+            1073742011 => KeyCode::SDLK_KP_BACKSPACE,
+            1073742012 => KeyCode::SDLK_KP_A,
+            1073742013 => KeyCode::SDLK_KP_B,
+            1073742014 => KeyCode::SDLK_KP_C,
+            1073742015 => KeyCode::SDLK_KP_D,
+            1073742016 => KeyCode::SDLK_KP_E,
+            1073742017 => KeyCode::SDLK_KP_F,
+            1073742018 => KeyCode::SDLK_KP_XOR,
+            1073742019 => KeyCode::SDLK_KP_POWER,
+            1073742020 => KeyCode::SDLK_KP_PERCENT,
+            1073742021 => KeyCode::SDLK_KP_LESS,
+            1073742022 => KeyCode::SDLK_KP_GREATER,
+            1073742023 => KeyCode::SDLK_KP_AMPERSAND,
+            1073742024 => KeyCode::SDLK_KP_DBLAMPERSAND,
+            1073742025 => KeyCode::SDLK_KP_VERTICALBAR,
+            1073742026 => KeyCode::SDLK_KP_DBLVERTICALBAR,
+            1073742027 => KeyCode::SDLK_KP_COLON,
+            1073742028 => KeyCode::SDLK_KP_HASH,
+            1073742029 => KeyCode::SDLK_KP_SPACE,
+            1073742030 => KeyCode::SDLK_KP_AT,
+            1073742031 => KeyCode::SDLK_KP_EXCLAM,
+            1073742032 => KeyCode::SDLK_KP_MEMSTORE,
+            1073742033 => KeyCode::SDLK_KP_MEMRECALL,
+            1073742034 => KeyCode::SDLK_KP_MEMCLEAR,
+            1073742035 => KeyCode::SDLK_KP_MEMADD,
+            1073742036 => KeyCode::SDLK_KP_MEMSUBTRACT,
+            1073742037 => KeyCode::SDLK_KP_MEMMULTIPLY,
+            1073742038 => KeyCode::SDLK_KP_MEMDIVIDE,
+            1073742039 => KeyCode::SDLK_KP_PLUSMINUS,
+            1073742040 => KeyCode::SDLK_KP_CLEAR,
+            1073742041 => KeyCode::SDLK_KP_CLEARENTRY,
+            1073742042 => KeyCode::SDLK_KP_BINARY,
+            1073742043 => KeyCode::SDLK_KP_OCTAL,
+            1073742044 => KeyCode::SDLK_KP_DECIMAL,
+            1073742045 => KeyCode::SDLK_KP_HEXADECIMAL,
+            1073742048 => KeyCode::SDLK_LCTRL,
+            1073742049 => KeyCode::SDLK_LSHIFT,
+            1073742050 => KeyCode::SDLK_LALT,
+            1073742051 => KeyCode::SDLK_LGUI,
+            1073742052 => KeyCode::SDLK_RCTRL,
+            1073742053 => KeyCode::SDLK_RSHIFT,
+            1073742054 => KeyCode::SDLK_RALT,
+            1073742055 => KeyCode::SDLK_RGUI,
+            1073742081 => KeyCode::SDLK_MODE,
+            1073742082 => KeyCode::SDLK_AUDIONEXT,
+            1073742083 => KeyCode::SDLK_AUDIOPREV,
+            1073742084 => KeyCode::SDLK_AUDIOSTOP,
+            1073742085 => KeyCode::SDLK_AUDIOPLAY,
+            1073742086 => KeyCode::SDLK_AUDIOMUTE,
+            1073742087 => KeyCode::SDLK_MEDIASELECT,
+            1073742088 => KeyCode::SDLK_WWW,
+            1073742089 => KeyCode::SDLK_MAIL,
+            1073742090 => KeyCode::SDLK_CALCULATOR,
+            1073742091 => KeyCode::SDLK_COMPUTER,
+            1073742092 => KeyCode::SDLK_AC_SEARCH,
+            1073742093 => KeyCode::SDLK_AC_HOME,
+            1073742094 => KeyCode::SDLK_AC_BACK,
+            1073742095 => KeyCode::SDLK_AC_FORWARD,
+            1073742096 => KeyCode::SDLK_AC_STOP,
+            1073742097 => KeyCode::SDLK_AC_REFRESH,
+            1073742098 => KeyCode::SDLK_AC_BOOKMARKS,
+            1073742099 => KeyCode::SDLK_BRIGHTNESSDOWN,
+            1073742100 => KeyCode::SDLK_BRIGHTNESSUP,
+            1073742101 => KeyCode::SDLK_DISPLAYSWITCH,
+            1073742102 => KeyCode::SDLK_KBDILLUMTOGGLE,
+            1073742103 => KeyCode::SDLK_KBDILLUMDOWN,
+            1073742104 => KeyCode::SDLK_KBDILLUMUP,
+            1073742105 => KeyCode::SDLK_EJECT,
+            1073742106 => KeyCode::SDLK_SLEEP,
+            1073742107 => KeyCode::SDLK_APP1,
+            1073742108 => KeyCode::SDLK_APP2,
+            1073742109 => KeyCode::SDLK_AUDIOREWIND,
+            1073742110 => KeyCode::SDLK_AUDIOFASTFORWARD,
+            _ => KeyCode::SDLK_UNKNOWN,
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyEvent {
+    pub key: KeyCode,
+    pub shift: bool,
+    pub alt: bool,
+    pub ctl: bool,
+    pub down: bool,
+    pub repeat: bool,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FrameEvent {
+    pub stream: String,
+    pub real_ts: (i32, i32),
+    pub continuous_ts: (i32, i32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MidiEvent {
+    pub device: String,
+    pub channel: u8,
+    pub kind: u8,
+    pub key: u8,
+    pub velocity: u8,
+    pub timestamp: i64,
+}
+
+pub const MIDI_NOTE_OFF: u8 = 0x80;
+pub const MIDI_NOTE_ON: u8 = 0x90;
+pub const MIDI_POLY_PRESSURE: u8 = 0xA0;
+pub const MIDI_CONTROL_CHANGE: u8 = 0xB0;
+pub const MIDI_PROGRAM_CHANGE: u8 = 0xC0;
+pub const MIDI_CHANNEL_PRESSURE: u8 = 0xD0;
+pub const MIDI_PITCH_BEND: u8 = 0xE0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeviceKind {
+    MidiInput,
+    MidiOutput,
+    Controller,
+}
+
+// A device viz was asked to open was plugged in or went away, midi ports by
+// their --midi-port/--midi-output name
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceEvent {
+    pub name: String,
+    pub kind: DeviceKind,
+}
+
+// What a capture device can deliver, fps as num/den. Not every platform
+// tells, v4l2 only lists sizes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CaptureMode {
+    pub size: (u32, u32),
+    // the ffmpeg pixel format, or codec name when compressed
+    pub pixel_format: Option<String>,
+    pub compressed: bool,
+    pub min_fps: Option<(i32, i32)>,
+    pub max_fps: Option<(i32, i32)>,
+}
+
+// A camera or capture card, opened as a Vid with format and path
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CaptureDevice {
+    pub name: String,
+    // avfoundation, video4linux2 or dshow
+    pub format: String,
+    pub path: String,
+    pub modes: Vec<CaptureMode>,
+}
+
+impl CaptureDevice {
+    // A realtime Vid of the device, in mode if one is given
+    pub fn vid<N: AsRef<str>>(&self, name: N, mode: Option<&CaptureMode>) -> VidBuilder {
+        let mut opts = vec![];
+        if let Some(mode) = mode {
+            opts.push((
+                "video_size".to_string(),
+                format!("{}x{}", mode.size.0, mode.size.1),
+            ));
+            if let Some((num, den)) = mode.max_fps {
+                opts.push(("framerate".to_string(), format!("{}/{}", num, den)));
+            }
+            if let Some(pixel_format) = mode.pixel_format.as_ref() {
+                let key = match (self.format.as_str(), mode.compressed) {
+                    ("video4linux2", _) => "input_format",
+                    ("dshow", true) => "vcodec",
+                    _ => "pixel_format",
+                };
+                opts.push((key.to_string(), pixel_format.clone()));
+            }
+        }
+        Vid::builder()
+            .name(name)
+            .path(&self.path)
+            .format(&self.format)
+            .realtime(true)
+            .resolution(mode.map_or((0, 0), |mode| mode.size))
+            .opts(&opts)
+    }
+}
+
+// The answer to spec_engine::request_capture_devices
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CaptureDevicesEvent {
+    pub devices: Vec<CaptureDevice>,
+    pub error: Option<String>,
+}
+
+// A file dragged onto the output window. asset is the Vid it's being loaded
+// as with --drop-load-vids, its AssetEvent follows.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileDropEvent {
+    pub path: String,
+    pub asset: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileChangeKind {
+    // Shader assets from it or including it are reloaded by the host, inline
+    // shaders are read by the app so reloading them is up to it
+    Shader,
+    // Lut assets from it are reloaded by the host, a Mix naming the file
+    // itself loads it again when it's next used
+    Lut,
+    // reloaded by the host, an AssetEvent follows for each Vid
+    Vid,
+    // images, Tex assets and any Vids from it are reloaded by the host and an
+    // AssetReloaded event follows for each
+    Tex,
+    Other,
+}
+
+// A file under a preopen changed, sent with viz --watch-assets once it has
+// settled. path is where the app sees it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileChangeEvent {
+    pub path: String,
+    pub kind: FileChangeKind,
+    // the Vids, Texes, Shaders or Luts being reloaded from it
+    pub reloading: Vec<String>,
+}
+
+// An asset loaded again under the same name has replaced the old one, which
+// was used until then. Its AssetEvent comes with it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AssetReloadedEvent {
+    pub name: String,
+}
+
+// The answer to spec_engine::watch_glob, first with everything pattern
+// matches and then with files that newly match it once they've stopped
+// changing. error is why the pattern can't be watched.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GlobMatchedEvent {
+    pub pattern: String,
+    pub paths: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MouseInput {
+    // held is every button down while moving, for drags
+    Motion {
+        dx: i32,
+        dy: i32,
+        held: Vec<MouseButton>,
+    },
+    // clicks is 2 for a double click
+    Button {
+        button: MouseButton,
+        down: bool,
+        clicks: u8,
+    },
+    // positive y scrolls away from the user
+    Wheel {
+        x: i32,
+        y: i32,
+    },
+}
+
+// x and y are where the pointer is in canvas pixels, the same space as
+// GfxInfoEvent's canvas size, only sent for the output window
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MouseEvent {
+    pub x: i32,
+    pub y: i32,
+    pub input: MouseInput,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TouchPhase {
+    Down,
+    Move,
+    Up,
+}
+
+// One finger on a touch screen in canvas pixels. finger stays the same from
+// Down to Up so several can be followed at once.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TouchEvent {
+    pub finger: i64,
+    pub phase: TouchPhase,
+    pub x: i32,
+    pub y: i32,
+    pub dx: i32,
+    pub dy: i32,
+    // thousandths, 1000 is pressed as hard as the screen measures
+    pub pressure: u16,
+    pub timestamp: i64,
+}
+
+// SDL's game controller layout, named after the xbox pad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ControllerButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Misc1,
+    Paddle1,
+    Paddle2,
+    Paddle3,
+    Paddle4,
+    Touchpad,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ControllerAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    TriggerLeft,
+    TriggerRight,
+}
+
+// Stick values run -32768..32767 and triggers 0..32767, anything inside
+// --controller-deadzone comes through as 0
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ControllerInput {
+    Button {
+        button: ControllerButton,
+        down: bool,
+    },
+    Axis {
+        axis: ControllerAxis,
+        value: i16,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ControllerEvent {
+    pub device: String,
+    // tells apart two of the same pad, stays the same while it's plugged in
+    pub id: u32,
+    pub input: ControllerInput,
+    pub timestamp: i64,
+}
+
+impl ControllerEvent {
+    // -1..1 for sticks and 0..1 for triggers
+    pub fn axis(&self, axis: ControllerAxis) -> Option<f64> {
+        match self.input {
+            ControllerInput::Axis { axis: a, value } if a == axis => {
+                Some((value as f64 / i16::MAX as f64).max(-1.0))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn pressed(&self, button: ControllerButton) -> bool {
+        self.input == ControllerInput::Button { button, down: true }
+    }
+}
+
+// Midi clock runs at 24 pulses per quarter note
+pub const MIDI_CLOCK_PPQN: u64 = 24;
+
+// The clock on a midi input, sent on frames where it moved, started, stopped
+// or changed tempo. Clock messages don't also arrive as MidiEvents.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClockEvent {
+    pub device: String,
+    pub playing: bool,
+    // since the last start or song position
+    pub pulses: u64,
+    // estimated from the pulse rate, in thousandths of a bpm, 0 until known
+    pub millibpm: u32,
+}
+
+impl ClockEvent {
+    pub fn bpm(&self) -> f64 {
+        self.millibpm as f64 / 1000.0
+    }
+
+    pub fn beat(&self) -> f64 {
+        self.pulses as f64 / MIDI_CLOCK_PPQN as f64
+    }
+
+    // How far into the current beat, 0..1
+    pub fn phase(&self) -> f64 {
+        (self.pulses % MIDI_CLOCK_PPQN) as f64 / MIDI_CLOCK_PPQN as f64
+    }
+}
+
+// The Ableton Link session, sent every frame while viz runs with --link
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LinkEvent {
+    pub peers: u32,
+    pub playing: bool,
+    // in thousandths of a bpm
+    pub millibpm: u32,
+    // in millionths of a beat, the session's beat now
+    pub microbeats: i64,
+    // beats per bar the phase is measured over
+    pub quantum: u32,
+}
+
+impl LinkEvent {
+    pub fn bpm(&self) -> f64 {
+        self.millibpm as f64 / 1000.0
+    }
+
+    pub fn beat(&self) -> f64 {
+        self.microbeats as f64 / 1_000_000.0
+    }
+
+    // How far into the bar, 0..quantum
+    pub fn phase(&self) -> f64 {
+        rem_euclid(self.beat(), self.quantum.max(1) as f64)
+    }
+}
+
+// What last set the host's tempo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TempoSource {
+    // viz --bpm, nothing has set it yet
+    #[default]
+    Free,
+    Tap,
+    MidiClock,
+    Link,
+    Audio,
+}
+
+// The host's tempo, sent every frame. The same beat drives the beat, bpm and
+// beat_phase uniforms once something other than --bpm sets the tempo.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TempoEvent {
+    pub source: TempoSource,
+    // in thousandths of a bpm
+    pub millibpm: u32,
+    // in millionths of a beat, beats since viz started
+    pub microbeats: i64,
+}
+
+impl TempoEvent {
+    pub fn bpm(&self) -> f64 {
+        self.millibpm as f64 / 1000.0
+    }
+
+    pub fn beat(&self) -> f64 {
+        self.microbeats as f64 / 1_000_000.0
+    }
+
+    // How far into the current beat, 0..1
+    pub fn phase(&self) -> f64 {
+        rem_euclid(self.beat(), 1.0)
+    }
+}
+
+// Midi timecode from quarter frames or a full frame message
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TimecodeEvent {
+    pub device: String,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    // 0: 24fps, 1: 25fps, 2: 29.97fps drop frame, 3: 30fps
+    pub rate: u8,
+}
+
+impl TimecodeEvent {
+    pub fn fps(&self) -> f64 {
+        match self.rate {
+            0 => 24.0,
+            1 => 25.0,
+            2 => 30000.0 / 1001.0,
+            _ => 30.0,
+        }
+    }
+
+    pub fn seconds(&self) -> f64 {
+        (self.hours as u32 * 3600 + self.minutes as u32 * 60 + self.seconds as u32) as f64
+            + self.frames as f64 / self.fps()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LogEvent {
+    pub message: String,
+}
+
+// Ordered by severity so a filter can keep everything at or above a level
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<u32> for LogLevel {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => LogLevel::Debug,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Error,
+            _ => LogLevel::Info,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AssetEvent {
+    pub name: String,
+    pub loaded: bool, // false after an unload or a failed load
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpecErrorKind {
+    MissingAsset, // a mixer, video, font or asset the spec names is not loaded
+    BadUniform,
+    SeekFailed,
+    RenderFailed,
+}
+
+// Sent the frame after a spec failed to apply, index is its position in the
+// Vec returned from calculate
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpecErrorEvent {
+    pub frame: i64,
+    pub index: usize,
+    pub kind: SpecErrorKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FramePacingEvent {
+    pub frame: i64,
+    // frames skipped to catch up with the wall clock after the last frame
+    pub dropped: i64,
+    pub render_us: u64,
+    pub present_us: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReadbackKind {
+    // box filtered rgba8, clamped to the mixer size
+    Pixels { width: u32, height: u32 },
+    // luminance histogram, bins clamped to 1..=256
+    Histogram { bins: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReadbackRequest {
+    pub mix: String,
+    pub kind: ReadbackKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReadbackData {
+    Pixels {
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    },
+    Histogram(Vec<u32>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReadbackEvent {
+    pub mix: String,
+    pub frame: i64,
+    pub data: ReadbackData,
+}
+
+// The gfx_info map passed to calculate changed since the last frame, from a
+// runtime load/unload, a reload or an asset's info being updated
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GfxInfoEvent {
+    pub changed: Vec<String>, // added or updated
+    pub removed: Vec<String>,
+}
+
+// The canvas (mix None) or a mixer changed resolution. drawable is the size
+// in pixels, which differs from new on high dpi displays.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResizeEvent {
+    pub mix: Option<String>,
+    pub old: (u32, u32),
+    pub new: (u32, u32),
+    pub drawable: (u32, u32),
+}
+
+impl ResizeEvent {
+    // Pixels per point of the display the canvas is on
+    pub fn scale(&self) -> f32 {
+        match self.new.0 {
+            0 => 1.0,
+            w => self.drawable.0 as f32 / w as f32,
+        }
+    }
+}
+
+// Answers spec_engine::fetch, error is set when the url was refused or failed
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FetchEvent {
+    pub url: String,
+    pub data: Vec<u8>,
+    pub error: Option<String>,
+}
+
+// A remote editor set one of the fields settings_schema describes, apply it
+// with an Adjustable's set_field_by_name
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub field: String,
+    // millionths
+    pub micros: i64,
+}
+
+impl SettingChange {
+    pub fn new<T: AsRef<str>>(field: T, value: f64) -> Self {
+        Self {
+            field: field.as_ref().into(),
+            micros: round(value * 1e6),
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.micros as f64 / 1e6
+    }
+}
+
+// Something a plugin's event source produced, data is json
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PluginEvent {
+    pub plugin: String,
+    pub kind: String,
+    pub data: String,
+}
+
+impl PluginEvent {
+    pub fn parse<'a, T: Deserialize<'a>>(&'a self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.data)
+    }
+}
+
+// Bands in an AudioAnalysisEvent, log spaced from 40Hz to nyquist
+pub const AUDIO_BANDS: usize = 8;
+
+// The viz --audio-in capture since the last frame, sent every frame.
+// Levels are in millionths of full scale.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AudioAnalysisEvent {
+    pub frame: i64,
+    pub rms_micros: i64,
+    // AUDIO_BANDS of them, lowest first
+    pub band_micros: Vec<i64>,
+    // a sudden rise in the spectrum, like a kick or a strum
+    pub onset: bool,
+}
+
+impl AudioAnalysisEvent {
+    pub fn rms(&self) -> f64 {
+        self.rms_micros as f64 / 1e6
+    }
+
+    // 0 for bands past the last
+    pub fn band(&self, band: usize) -> f64 {
+        self.band_micros.get(band).map_or(0.0, |b| *b as f64 / 1e6)
+    }
+
+    pub fn bands(&self) -> Vec<f64> {
+        (0..self.band_micros.len()).map(|b| self.band(b)).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GfxEvent {
+    KeyEvent(KeyEvent),
+    MidiEvent(MidiEvent),
+    FrameEvent(FrameEvent),
+    ReloadEvent(),
+    LogEvent(LogEvent),
+    ReadbackEvent(ReadbackEvent),
+    FramePacingEvent(FramePacingEvent),
+    AssetEvent(AssetEvent),
+    SpecErrorEvent(SpecErrorEvent),
+    FetchEvent(FetchEvent),
+    GfxInfoEvent(GfxInfoEvent),
+    ResizeEvent(ResizeEvent),
+    ClockEvent(ClockEvent),
+    TimecodeEvent(TimecodeEvent),
+    LinkEvent(LinkEvent),
+    DeviceConnected(DeviceEvent),
+    DeviceDisconnected(DeviceEvent),
+    ControllerEvent(ControllerEvent),
+    MouseEvent(MouseEvent),
+    TouchEvent(TouchEvent),
+    FileDropped(FileDropEvent),
+    PluginEvent(PluginEvent),
+    SettingChanged(SettingChange),
+    AudioAnalysisEvent(AudioAnalysisEvent),
+    TempoEvent(TempoEvent),
+    FileChanged(FileChangeEvent),
+    CaptureDevices(CaptureDevicesEvent),
+    AssetReloaded(AssetReloadedEvent),
+    GlobMatched(GlobMatchedEvent),
+}
+
+impl GfxEvent {
+    // The protocol::PROTOCOL_VERSION that introduced the event, guests
+    // speaking an older one can't decode it and aren't sent it
+    pub fn protocol_version(&self) -> u32 {
+        match self {
+            GfxEvent::KeyEvent(_)
+            | GfxEvent::MidiEvent(_)
+            | GfxEvent::FrameEvent(_)
+            | GfxEvent::ReloadEvent()
+            | GfxEvent::LogEvent(_) => 0,
+            GfxEvent::ReadbackEvent(_)
+            | GfxEvent::FramePacingEvent(_)
+            | GfxEvent::AssetEvent(_) => 1,
+            GfxEvent::SpecErrorEvent(_)
+            | GfxEvent::FetchEvent(_)
+            | GfxEvent::GfxInfoEvent(_)
+            | GfxEvent::ResizeEvent(_)
+            | GfxEvent::ClockEvent(_)
+            | GfxEvent::TimecodeEvent(_)
+            | GfxEvent::LinkEvent(_)
+            | GfxEvent::DeviceConnected(_)
+            | GfxEvent::DeviceDisconnected(_)
+            | GfxEvent::ControllerEvent(_)
+            | GfxEvent::MouseEvent(_)
+            | GfxEvent::TouchEvent(_)
+            | GfxEvent::FileDropped(_)
+            | GfxEvent::PluginEvent(_)
+            | GfxEvent::SettingChanged(_)
+            | GfxEvent::AudioAnalysisEvent(_)
+            | GfxEvent::TempoEvent(_)
+            | GfxEvent::FileChanged(_)
+            | GfxEvent::CaptureDevices(_)
+            | GfxEvent::AssetReloaded(_)
+            | GfxEvent::GlobMatched(_) => 2,
+        }
+    }
+
+    // Whether a guest speaking protocol version can decode the event
+    pub fn decodable_by(&self, version: u32) -> bool {
+        self.protocol_version() <= version
+    }
+}
+
+// f64's rem_euclid and round are std's, these are the same on core. Both are
+// exact, x - trunc(x) is for anything an i64 holds, and past that it
+// saturates like the cast does.
+fn rem_euclid(x: f64, q: f64) -> f64 {
+    let r = x % q;
+    match r < 0.0 {
+        true => r + q,
+        false => r,
+    }
+}
+
+fn round(x: f64) -> i64 {
+    let t = x as i64;
+    let frac = x - t as f64;
+    match (frac >= 0.5, frac <= -0.5) {
+        (true, _) => t.saturating_add(1),
+        (_, true) => t.saturating_sub(1),
+        _ => t,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> Vec<GfxEvent> {
+        vec![
+            GfxEvent::ReloadEvent(),
+            GfxEvent::AssetEvent(AssetEvent {
+                name: "a".into(),
+                loaded: true,
+                error: None,
+            }),
+            GfxEvent::SpecErrorEvent(SpecErrorEvent {
+                frame: 1,
+                index: 0,
+                kind: SpecErrorKind::RenderFailed,
+                message: "no".into(),
+            }),
+        ]
+    }
+
+    fn decodable(version: u32) -> Vec<u32> {
+        events()
+            .iter()
+            .filter(|e| e.decodable_by(version))
+            .map(GfxEvent::protocol_version)
+            .collect()
+    }
+
+    #[test]
+    fn older_guests_get_older_events() {
+        assert_eq!(decodable(0), [0]);
+        assert_eq!(decodable(1), [0, 1]);
+        assert_eq!(decodable(2), [0, 1, 2]);
+        assert_eq!(decodable(crate::protocol::PROTOCOL_VERSION), [0, 1, 2]);
+    }
+}
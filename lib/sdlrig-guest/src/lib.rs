@@ -0,0 +1,27 @@
+// What a wasm app builds against: the specs it returns, the events and info
+// it gets, the wire format between them and the engine that runs it. Nothing
+// here needs sdl, ffmpeg or the host's other native libraries.
+//
+// Everything but the wire format and spec_engine builds without std, on core
+// and alloc, with default-features = false.
+//
+// Versioned on its own. Anything an app could have relied on changing is a
+// new minor version while this is 0.x, and specs or events older hosts can't
+// decode also bump protocol::PROTOCOL_VERSION.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// for the Adjustable derive, which can't assume a prelude has Vec and String
+#[doc(hidden)]
+pub extern crate alloc as __alloc;
+
+pub mod gfxinfo;
+pub mod protocol;
+pub mod renderspec;
+pub mod ring;
+#[cfg(all(target_family = "wasm", feature = "std"))]
+pub mod spec_engine;
+#[cfg(feature = "std")]
+pub mod wire;
+pub use adjustable::Adjustable;
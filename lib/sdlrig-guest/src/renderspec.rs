@@ -0,0 +1,1790 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    error::Error,
+    fmt::{Debug, Display},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::gfxinfo::{
+    Asset, MidiEvent, MIDI_CHANNEL_PRESSURE, MIDI_CONTROL_CHANGE, MIDI_NOTE_OFF, MIDI_NOTE_ON,
+    MIDI_PROGRAM_CHANGE,
+};
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub enum RenderSpec {
+    #[default]
+    None,
+    SendCmd(SendCmd),
+    HudText(HudText),
+    Mix(Mix),
+    SeekVid(SeekVid),
+    Reset(Reset),
+    SendMidi(SendMidi),
+    DrawText(DrawText),
+    DrawShape(DrawShape),
+    Batch(Vec<RenderSpec>),
+    Transition(Transition),
+    Clear(Clear),
+    // loaded off the render thread, an AssetEvent reports the outcome and the
+    // gfx info map passed to calculate is refreshed
+    LoadAsset(Asset),
+    UnloadAsset(String),
+    // registered on the host once, then RunMacro replays it each frame. A
+    // macro can't run or define another one, so it can't run itself.
+    DefineMacro(DefineMacro),
+    RunMacro(RunMacro),
+    WindowCtl(WindowCtl),
+    Viewport(Viewport),
+    AudioPlay(AudioPlay),
+    AudioStop(AudioStop),
+    AudioGain(AudioGain),
+    SetScene(Scene),
+    SendOsc(SendOsc),
+    HudWidget(HudWidget),
+    Plugin(PluginSpec),
+    Monitor(Monitor),
+    SetFps(SetFps),
+}
+
+// A batch is validated as a whole in one frame, a missing asset or mixer
+// refuses all of it. A member that fails past that (a seek, a shader) skips
+// the rest and undoes the ones before it, and none of the batch's SendMidi
+// and SendOsc go out. What its mixes and draws already drew stays drawn.
+impl From<Vec<RenderSpec>> for RenderSpec {
+    fn from(value: Vec<RenderSpec>) -> Self {
+        RenderSpec::Batch(value)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct CopyEx {
+    pub name: String,
+    pub idx: usize,
+    pub src: Option<(i32, i32, u32, u32)>,
+    pub dst: Option<(i32, i32, u32, u32)>,
+    pub center: Option<(i32, i32)>,
+    #[serde(default)]
+    pub rotation: f64, // degrees clockwise around center
+    #[serde(default)]
+    pub flip_h: bool,
+    #[serde(default)]
+    pub flip_v: bool,
+    pub color_mod: Option<(u8, u8, u8, u8)>,
+    #[serde(default)]
+    pub layer: i32, // offsets the layer of the Mix it targets
+}
+
+impl CopyEx {
+    pub fn builder() -> CopyExBuilder {
+        CopyExBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct CopyExBuilder {
+    obj: CopyEx,
+}
+
+impl CopyExBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: CopyEx::default(),
+        }
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.name = name.as_ref().into();
+        self
+    }
+
+    pub fn idx(mut self, idx: usize) -> Self {
+        self.obj.idx = idx;
+        self
+    }
+
+    pub fn src(mut self, src: (i32, i32, u32, u32)) -> Self {
+        self.obj.src = Some(src);
+        self
+    }
+
+    pub fn dst(mut self, dst: (i32, i32, u32, u32)) -> Self {
+        self.obj.dst = Some(dst);
+        self
+    }
+
+    pub fn center(mut self, center: (i32, i32)) -> Self {
+        self.obj.center = Some(center);
+        self
+    }
+    pub fn rotation(mut self, rotation: f64) -> Self {
+        self.obj.rotation = rotation;
+        self
+    }
+    pub fn flip_h(mut self, filp_h: bool) -> Self {
+        self.obj.flip_h = filp_h;
+        self
+    }
+    pub fn flip_v(mut self, flip_v: bool) -> Self {
+        self.obj.flip_v = flip_v;
+        self
+    }
+    pub fn color_mod(mut self, color_mod: (u8, u8, u8, u8)) -> Self {
+        self.obj.color_mod = Some(color_mod);
+        self
+    }
+    pub fn layer(mut self, layer: i32) -> Self {
+        self.obj.layer = layer;
+        self
+    }
+
+    pub fn build(self) -> CopyEx {
+        self.obj.clone()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct SendMidi {
+    pub event: MidiEvent,
+}
+
+// Sent to the --midi-output port whose name matches event.device
+impl SendMidi {
+    pub fn note_on<T: AsRef<str>>(device: T, channel: u8, key: u8, velocity: u8) -> Self {
+        Self::message(device, MIDI_NOTE_ON, channel, key, velocity)
+    }
+
+    pub fn note_off<T: AsRef<str>>(device: T, channel: u8, key: u8) -> Self {
+        Self::message(device, MIDI_NOTE_OFF, channel, key, 0)
+    }
+
+    pub fn cc<T: AsRef<str>>(device: T, channel: u8, controller: u8, value: u8) -> Self {
+        Self::message(device, MIDI_CONTROL_CHANGE, channel, controller, value)
+    }
+
+    pub fn program_change<T: AsRef<str>>(device: T, channel: u8, program: u8) -> Self {
+        Self::message(device, MIDI_PROGRAM_CHANGE, channel, program, 0)
+    }
+
+    fn message<T: AsRef<str>>(device: T, kind: u8, channel: u8, key: u8, velocity: u8) -> Self {
+        Self {
+            event: MidiEvent {
+                device: device.as_ref().into(),
+                channel,
+                kind,
+                key,
+                velocity,
+                timestamp: 0,
+            },
+        }
+    }
+
+    // Wire bytes for the message, program change and channel pressure only
+    // carry one data byte
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let status = (self.event.kind & 0xF0) | (self.event.channel & 0x0F);
+        let key = self.event.key & 0x7F;
+        let velocity = self.event.velocity & 0x7F;
+        match self.event.kind & 0xF0 {
+            MIDI_PROGRAM_CHANGE | MIDI_CHANNEL_PRESSURE => vec![status, key],
+            _ => vec![status, key, velocity],
+        }
+    }
+}
+
+impl From<SendMidi> for RenderSpec {
+    fn from(value: SendMidi) -> Self {
+        RenderSpec::SendMidi(value)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub enum SendValue {
+    Float(f32),
+    Integer(i32),
+    Unsigned(u32),
+    Vector(Vec<f32>), // Also used for matrices
+    IVector(Vec<i32>),
+    UVector(Vec<u32>),
+}
+
+impl Default for SendValue {
+    fn default() -> Self {
+        SendValue::Float(0.0)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct SendCmd {
+    pub mix: String,
+    pub name: String,
+    pub value: SendValue,
+}
+
+impl From<SendCmd> for RenderSpec {
+    fn from(value: SendCmd) -> Self {
+        RenderSpec::SendCmd(value)
+    }
+}
+
+impl SendCmd {
+    pub fn builder() -> SendCmdBuilder {
+        SendCmdBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct SendCmdBuilder {
+    obj: SendCmd,
+}
+
+impl SendCmdBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: SendCmd::default(),
+        }
+    }
+
+    pub fn mix<T>(mut self, mix: T) -> Self
+    where
+        T: ToString,
+    {
+        self.obj.mix = mix.to_string();
+        self
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: ToString,
+    {
+        self.obj.name = name.to_string();
+        self
+    }
+
+    pub fn value(mut self, value: SendValue) -> Self {
+        self.obj.value = value;
+        self
+    }
+
+    pub fn build(self) -> SendCmd {
+        self.obj.clone()
+    }
+}
+
+// fields an older guest leaves out take Default's
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[repr(C)]
+pub struct HudText {
+    pub text: String,
+    // shown in place of text when there are any
+    pub spans: Vec<TextSpan>,
+}
+
+#[macro_export]
+macro_rules! hud_text {
+    ($text:expr) => {
+        $crate::renderspec::RenderSpec::HudText($crate::renderspec::HudText {
+            text: String::from($text),
+            spans: vec![],
+        })
+    };
+}
+
+// hud_spans!(TextSpan::builder().text("live").bold(true).build(), " 00:42")
+#[macro_export]
+macro_rules! hud_spans {
+    ($($span:expr),+ $(,)?) => {
+        $crate::renderspec::RenderSpec::HudText($crate::renderspec::HudText {
+            text: String::new(),
+            spans: vec![$($crate::renderspec::TextSpan::from($span)),+],
+        })
+    };
+}
+
+impl From<HudText> for RenderSpec {
+    fn from(value: HudText) -> Self {
+        RenderSpec::HudText(value)
+    }
+}
+
+// Drawn by the host under the HudText, sent every frame they should show.
+// HudWidget::meter("cpu", load, 0.0, 1.0)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum HudWidget {
+    // label and value columns
+    Table {
+        title: Option<String>,
+        rows: Vec<(String, String)>,
+    },
+    Meter {
+        label: String,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    // the host keeps the values sent under a label and scrolls through them
+    Graph {
+        label: String,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    // across the top of the HUD in red
+    Banner(String),
+}
+
+impl HudWidget {
+    pub fn table<T: AsRef<str>>(title: Option<T>) -> Self {
+        HudWidget::Table {
+            title: title.map(|t| t.as_ref().into()),
+            rows: vec![],
+        }
+    }
+
+    // Only adds to tables
+    pub fn row<L: AsRef<str>, V: ToString>(mut self, label: L, value: V) -> Self {
+        if let HudWidget::Table { rows, .. } = &mut self {
+            rows.push((label.as_ref().into(), value.to_string()));
+        }
+        self
+    }
+
+    pub fn meter<T: AsRef<str>>(label: T, value: f64, min: f64, max: f64) -> Self {
+        HudWidget::Meter {
+            label: label.as_ref().into(),
+            value,
+            min,
+            max,
+        }
+    }
+
+    pub fn graph<T: AsRef<str>>(label: T, value: f64, min: f64, max: f64) -> Self {
+        HudWidget::Graph {
+            label: label.as_ref().into(),
+            value,
+            min,
+            max,
+        }
+    }
+
+    pub fn banner<T: AsRef<str>>(text: T) -> Self {
+        HudWidget::Banner(text.as_ref().into())
+    }
+}
+
+impl From<HudWidget> for RenderSpec {
+    fn from(value: HudWidget) -> Self {
+        RenderSpec::HudWidget(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+pub type Rgba = (u8, u8, u8, u8);
+
+// Part of a DrawText or HudText with a look of its own, text that's outlined
+// and shadowed in places or changes color partway
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<Rgba>, // None takes the text's
+    // a Font asset's name or the path to a ttf/otf file, None takes the
+    // text's, the HUD's for HudText
+    pub font: Option<String>,
+    pub bold: bool,
+    // pixels wide around the glyphs, and its color
+    pub outline: Option<(u16, Rgba)>,
+    // the glyphs and their outline again under them, offset by pixels
+    pub shadow: Option<((i32, i32), Rgba)>,
+}
+
+impl TextSpan {
+    pub fn builder() -> TextSpanBuilder {
+        TextSpanBuilder::new()
+    }
+}
+
+impl From<&str> for TextSpan {
+    fn from(value: &str) -> Self {
+        TextSpan {
+            text: value.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<String> for TextSpan {
+    fn from(value: String) -> Self {
+        TextSpan {
+            text: value,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TextSpanBuilder {
+    obj: TextSpan,
+}
+
+impl TextSpanBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: TextSpan::default(),
+        }
+    }
+
+    pub fn text<T>(mut self, text: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.text = text.as_ref().into();
+        self
+    }
+
+    pub fn color(mut self, color: (u8, u8, u8, u8)) -> Self {
+        self.obj.color = Some(color);
+        self
+    }
+
+    pub fn font<T>(mut self, font: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.font = Some(font.as_ref().into());
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.obj.bold = bold;
+        self
+    }
+
+    pub fn outline(mut self, width: u16, color: (u8, u8, u8, u8)) -> Self {
+        self.obj.outline = Some((width, color));
+        self
+    }
+
+    pub fn shadow(mut self, offset: (i32, i32), color: (u8, u8, u8, u8)) -> Self {
+        self.obj.shadow = Some((offset, color));
+        self
+    }
+
+    pub fn build(self) -> TextSpan {
+        self.obj.clone()
+    }
+}
+
+// What moves a GlyphAnimation along
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum GlyphDriver {
+    // seconds at the host's frame rate
+    #[default]
+    Time,
+    // one of the values the host sets mixers' uniforms to, beat, bpm,
+    // beat_phase, audio_rms, audio_onset or audio_band0 to audio_band7, 0
+    // while the host has none
+    Uniform(String),
+    // the app's own, changed from calculate
+    Value(f32),
+}
+
+// How a glyph's phase becomes how far through its animation it is
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GlyphWave {
+    // -1 to 1 and back each unit of phase
+    #[default]
+    Sine,
+    // the same in straight lines
+    Triangle,
+    // 0 to 1 each unit of phase
+    Saw,
+    // the phase itself
+    Linear,
+}
+
+// Moves each glyph of a DrawText by the transform times wave(driver * speed +
+// stagger * i), i counting characters from the start of the text. Offset and
+// scale are of the glyph at 1, scale added to 1, about its center. sdf text
+// stays sharp scaled up.
+// GlyphAnimation::builder().stagger(0.15).offset((0.0, -8.0)).build()
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GlyphAnimation {
+    pub driver: GlyphDriver,
+    pub speed: f32,
+    pub stagger: f32,
+    pub wave: GlyphWave,
+    pub offset: (f32, f32), // pixels
+    pub rotation: f32,      // radians, clockwise
+    pub scale: f32,
+}
+
+impl Default for GlyphAnimation {
+    fn default() -> Self {
+        Self {
+            driver: GlyphDriver::Time,
+            speed: 1.0,
+            stagger: 0.0,
+            wave: GlyphWave::Sine,
+            offset: (0.0, 0.0),
+            rotation: 0.0,
+            scale: 0.0,
+        }
+    }
+}
+
+impl GlyphAnimation {
+    pub fn builder() -> GlyphAnimationBuilder {
+        GlyphAnimationBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct GlyphAnimationBuilder {
+    obj: GlyphAnimation,
+}
+
+impl GlyphAnimationBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: GlyphAnimation::default(),
+        }
+    }
+
+    pub fn driver(mut self, driver: GlyphDriver) -> Self {
+        self.obj.driver = driver;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.obj.speed = speed;
+        self
+    }
+
+    pub fn stagger(mut self, stagger: f32) -> Self {
+        self.obj.stagger = stagger;
+        self
+    }
+
+    pub fn wave(mut self, wave: GlyphWave) -> Self {
+        self.obj.wave = wave;
+        self
+    }
+
+    pub fn offset(mut self, offset: (f32, f32)) -> Self {
+        self.obj.offset = offset;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.obj.rotation = rotation;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.obj.scale = scale;
+        self
+    }
+
+    pub fn build(self) -> GlyphAnimation {
+        self.obj.clone()
+    }
+}
+
+// fields an older guest leaves out take Default's
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+#[repr(C)]
+pub struct DrawText {
+    pub target_mix: Option<String>, // None draws on the window
+    pub text: String,
+    pub font: String, // a Font asset's name or the path to a ttf/otf file
+    pub size: u16,
+    pub pos: (i32, i32),
+    pub color: (u8, u8, u8, u8),
+    pub align: TextAlign,       // of the text at pos, and of its lines
+    pub max_width: Option<u32>, // wraps past it
+    pub line_spacing: f32,      // times the font's
+    pub tab_stops: Vec<u32>,    // pixels from the left
+    // tried in turn for characters font doesn't have, before the system's
+    // emoji and CJK fonts
+    pub fallback_fonts: Vec<String>,
+    // glyphs from one distance field atlas for every size, for text that's
+    // scaled or animated, rather than one atlas per size
+    pub sdf: bool,
+    // drawn in place of text when there are any
+    pub spans: Vec<TextSpan>,
+    // offsets and rotations add up, scales multiply
+    pub animations: Vec<GlyphAnimation>,
+}
+
+impl Default for DrawText {
+    fn default() -> Self {
+        Self {
+            target_mix: None,
+            text: String::new(),
+            font: String::new(),
+            size: 24,
+            pos: (0, 0),
+            color: (255, 255, 255, 255),
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+            tab_stops: vec![],
+            fallback_fonts: vec![],
+            sdf: false,
+            spans: vec![],
+            animations: vec![],
+        }
+    }
+}
+
+impl DrawText {
+    pub fn builder() -> DrawTextBuilder {
+        DrawTextBuilder::new()
+    }
+
+    // What's drawn, text as one span in the text's color when there are no
+    // spans
+    pub fn styled_spans(&self) -> Vec<TextSpan> {
+        match self.spans.is_empty() {
+            true => vec![TextSpan::from(self.text.as_str())],
+            false => self.spans.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DrawTextBuilder {
+    obj: DrawText,
+}
+
+impl DrawTextBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: DrawText::default(),
+        }
+    }
+
+    pub fn target_mix<T>(mut self, target_mix: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.target_mix = Some(target_mix.as_ref().into());
+        self
+    }
+
+    pub fn text<T>(mut self, text: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.text = text.as_ref().into();
+        self
+    }
+
+    pub fn font<T>(mut self, font: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.font = font.as_ref().into();
+        self
+    }
+
+    pub fn size(mut self, size: u16) -> Self {
+        self.obj.size = size;
+        self
+    }
+
+    pub fn pos(mut self, pos: (i32, i32)) -> Self {
+        self.obj.pos = pos;
+        self
+    }
+
+    pub fn color(mut self, color: (u8, u8, u8, u8)) -> Self {
+        self.obj.color = color;
+        self
+    }
+
+    pub fn align(mut self, align: TextAlign) -> Self {
+        self.obj.align = align;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: u32) -> Self {
+        self.obj.max_width = Some(max_width);
+        self
+    }
+
+    pub fn line_spacing(mut self, line_spacing: f32) -> Self {
+        self.obj.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn tab_stops(mut self, tab_stops: &[u32]) -> Self {
+        self.obj.tab_stops = tab_stops.to_vec();
+        self
+    }
+
+    pub fn sdf(mut self, sdf: bool) -> Self {
+        self.obj.sdf = sdf;
+        self
+    }
+
+    pub fn fallback_font<T>(mut self, font: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.fallback_fonts.push(font.as_ref().into());
+        self
+    }
+
+    pub fn span<T>(mut self, span: T) -> Self
+    where
+        T: Into<TextSpan>,
+    {
+        self.obj.spans.push(span.into());
+        self
+    }
+
+    pub fn animate(mut self, animation: GlyphAnimation) -> Self {
+        self.obj.animations.push(animation);
+        self
+    }
+
+    pub fn build(self) -> DrawText {
+        self.obj.clone()
+    }
+}
+
+impl From<DrawText> for RenderSpec {
+    fn from(value: DrawText) -> Self {
+        RenderSpec::DrawText(value)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+    // fill assumes a convex outline
+    Polyline {
+        points: Vec<(f32, f32)>,
+        closed: bool,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct DrawShape {
+    pub target_mix: Option<String>, // None draws on the window
+    pub shapes: Vec<Shape>,
+    pub fill: Option<(u8, u8, u8, u8)>,
+    pub stroke: Option<(u8, u8, u8, u8)>,
+    pub stroke_width: f32,
+    // affine [a, b, c, d, e, f]: x' = a*x + c*y + e, y' = b*x + d*y + f
+    pub transform: [f32; 6],
+}
+
+impl Default for DrawShape {
+    fn default() -> Self {
+        Self {
+            target_mix: None,
+            shapes: vec![],
+            fill: None,
+            stroke: None,
+            stroke_width: 1.0,
+            transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl DrawShape {
+    pub fn builder() -> DrawShapeBuilder {
+        DrawShapeBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct DrawShapeBuilder {
+    obj: DrawShape,
+}
+
+impl DrawShapeBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: DrawShape::default(),
+        }
+    }
+
+    pub fn target_mix<T>(mut self, target_mix: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.target_mix = Some(target_mix.as_ref().into());
+        self
+    }
+
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.obj.shapes.push(shape);
+        self
+    }
+
+    pub fn rect(self, x: f32, y: f32, w: f32, h: f32) -> Self {
+        self.shape(Shape::Rect { x, y, w, h })
+    }
+
+    pub fn line(self, from: (f32, f32), to: (f32, f32)) -> Self {
+        self.shape(Shape::Line { from, to })
+    }
+
+    pub fn circle(self, center: (f32, f32), radius: f32) -> Self {
+        self.shape(Shape::Circle { center, radius })
+    }
+
+    pub fn polyline(self, points: Vec<(f32, f32)>, closed: bool) -> Self {
+        self.shape(Shape::Polyline { points, closed })
+    }
+
+    pub fn fill(mut self, fill: (u8, u8, u8, u8)) -> Self {
+        self.obj.fill = Some(fill);
+        self
+    }
+
+    pub fn stroke(mut self, stroke: (u8, u8, u8, u8), width: f32) -> Self {
+        self.obj.stroke = Some(stroke);
+        self.obj.stroke_width = width;
+        self
+    }
+
+    pub fn transform(mut self, transform: [f32; 6]) -> Self {
+        self.obj.transform = transform;
+        self
+    }
+
+    // canvas style, each call applies to the shapes before the existing transform
+    pub fn translate(self, x: f32, y: f32) -> Self {
+        self.then([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    pub fn scale(self, sx: f32, sy: f32) -> Self {
+        self.then([sx, 0.0, 0.0, sy, 0.0, 0.0])
+    }
+
+    // sin and cos are std's
+    #[cfg(feature = "std")]
+    pub fn rotate(self, radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        self.then([cos, sin, -sin, cos, 0.0, 0.0])
+    }
+
+    fn then(mut self, m: [f32; 6]) -> Self {
+        let t = self.obj.transform;
+        self.obj.transform = [
+            t[0] * m[0] + t[2] * m[1],
+            t[1] * m[0] + t[3] * m[1],
+            t[0] * m[2] + t[2] * m[3],
+            t[1] * m[2] + t[3] * m[3],
+            t[0] * m[4] + t[2] * m[5] + t[4],
+            t[1] * m[4] + t[3] * m[5] + t[5],
+        ];
+        self
+    }
+
+    pub fn build(self) -> DrawShape {
+        self.obj.clone()
+    }
+}
+
+impl From<DrawShape> for RenderSpec {
+    fn from(value: DrawShape) -> Self {
+        RenderSpec::DrawShape(value)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Ord, Eq)]
+pub enum MixInput {
+    Video(String),
+    Mixed(String),
+    // a Tex asset's frame, or its animation playing when None
+    Tex(String, Option<usize>),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Mix {
+    pub name: String,
+    pub inputs: Vec<MixInput>,
+    pub seek_target_hint: Option<String>,
+    pub target: Option<CopyEx>,
+    pub lut: Option<String>, // a Lut asset's name or the path to a .cube file
+    pub no_display: bool,
+    #[serde(default)]
+    pub layer: i32, // higher layers composite on top of lower ones
+}
+
+impl Mix {
+    pub fn builder() -> MixBuilder {
+        MixBuilder::new()
+    }
+
+    pub fn effective_layer(&self) -> i32 {
+        self.layer + self.target.as_ref().map_or(0, |t| t.layer)
+    }
+}
+
+// Reorders the displayed Mix specs by layer in place. Only the slots already
+// holding displayed mixes are reused so the other specs keep their order
+// relative to them, and equal layers keep their order in the Vec.
+// Returns the original index of each spec so results can be reported back.
+pub fn sort_layers(specs: &mut [RenderSpec]) -> Vec<usize> {
+    let mut order = (0..specs.len()).collect::<Vec<_>>();
+    let slots = specs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, spec)| match spec {
+            RenderSpec::Mix(mix) if !mix.no_display => Some(i),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut mixes = slots
+        .iter()
+        .map(|i| (*i, core::mem::take(&mut specs[*i])))
+        .collect::<Vec<_>>();
+    mixes.sort_by_key(|(_, spec)| match spec {
+        RenderSpec::Mix(mix) => mix.effective_layer(),
+        _ => 0,
+    });
+    for (i, (orig, mix)) in slots.into_iter().zip(mixes) {
+        specs[i] = mix;
+        order[i] = orig;
+    }
+    order
+}
+
+#[derive(Default)]
+pub struct MixBuilder {
+    obj: Mix,
+}
+
+impl MixBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: Default::default(),
+        }
+    }
+
+    pub fn build(&self) -> Mix {
+        self.obj.clone()
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.name = name.as_ref().into();
+        self
+    }
+
+    pub fn video<T>(mut self, video: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.inputs.push(MixInput::Video(video.as_ref().into()));
+        self
+    }
+
+    pub fn mixed<T>(mut self, mixed: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.inputs.push(MixInput::Mixed(mixed.as_ref().into()));
+        self
+    }
+
+    pub fn tex<T>(mut self, tex: T, frame: Option<usize>) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj
+            .inputs
+            .push(MixInput::Tex(tex.as_ref().into(), frame));
+        self
+    }
+
+    pub fn seek_target_hint<T>(mut self, seek_target_hint: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.seek_target_hint = Some(seek_target_hint.as_ref().into());
+        self
+    }
+
+    pub fn target(mut self, target: CopyEx) -> Self {
+        self.obj.target = Some(target);
+        self
+    }
+
+    pub fn lut<T>(mut self, lut: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.lut = Some(lut.as_ref().into());
+        self
+    }
+
+    pub fn no_display(mut self, no_display: bool) -> Self {
+        self.obj.no_display = no_display;
+        self
+    }
+
+    pub fn layer(mut self, layer: i32) -> Self {
+        self.obj.layer = layer;
+        self
+    }
+}
+
+impl From<Mix> for RenderSpec {
+    fn from(value: Mix) -> Self {
+        RenderSpec::Mix(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TransitionKind {
+    #[default]
+    Crossfade,
+    Wipe, // left to right
+    LumaDissolve,
+}
+
+// Send once, the host then runs it for `frames` frames drawing over the window.
+// Both mixers still need to be mixed each frame (no_display).
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct Transition {
+    pub from_mix: String,
+    pub to_mix: String,
+    pub kind: TransitionKind,
+    pub frames: i64,
+}
+
+impl Transition {
+    pub fn builder() -> TransitionBuilder {
+        TransitionBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct TransitionBuilder {
+    obj: Transition,
+}
+
+impl TransitionBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: Transition::default(),
+        }
+    }
+
+    pub fn from_mix<T>(mut self, from_mix: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.from_mix = from_mix.as_ref().into();
+        self
+    }
+
+    pub fn to_mix<T>(mut self, to_mix: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.to_mix = to_mix.as_ref().into();
+        self
+    }
+
+    pub fn kind(mut self, kind: TransitionKind) -> Self {
+        self.obj.kind = kind;
+        self
+    }
+
+    pub fn frames(mut self, frames: i64) -> Self {
+        self.obj.frames = frames;
+        self
+    }
+
+    pub fn build(self) -> Transition {
+        self.obj.clone()
+    }
+}
+
+impl From<Transition> for RenderSpec {
+    fn from(value: Transition) -> Self {
+        RenderSpec::Transition(value)
+    }
+}
+
+// exact seeks to sec, otherwise sec is added to the current position.
+// When by is set it replaces sec as a jump relative to the current position.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SeekVid {
+    pub target: String,
+    pub sec: f64,
+    pub exact: bool,
+    #[serde(default)]
+    pub by: Option<SeekBy>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum SeekBy {
+    RelativeFrames(i64), // in the video's own frame rate
+    Beats(f64),          // at the host's bpm
+}
+
+#[macro_export]
+macro_rules! seek {
+    ($target:expr => $sec:expr, $exact:expr) => {
+        $crate::renderspec::RenderSpec::SeekVid($crate::renderspec::SeekVid {
+            target: ($target).into(),
+            sec: $sec,
+            exact: $exact,
+            by: None,
+        })
+    };
+}
+
+#[macro_export]
+macro_rules! nudge {
+    ($target:expr => $frames:expr) => {
+        $crate::renderspec::RenderSpec::SeekVid($crate::renderspec::SeekVid {
+            target: ($target).into(),
+            sec: 0.0,
+            exact: false,
+            by: Some($crate::renderspec::SeekBy::RelativeFrames($frames)),
+        })
+    };
+}
+
+#[macro_export]
+macro_rules! beat_jump {
+    ($target:expr => $beats:expr) => {
+        $crate::renderspec::RenderSpec::SeekVid($crate::renderspec::SeekVid {
+            target: ($target).into(),
+            sec: 0.0,
+            exact: false,
+            by: Some($crate::renderspec::SeekBy::Beats($beats)),
+        })
+    };
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct Clear {
+    pub target: Option<String>, // None clears the window
+    pub rgba: (u8, u8, u8, u8),
+}
+
+impl Default for Clear {
+    fn default() -> Self {
+        Self {
+            target: None,
+            rgba: (0, 0, 0, 255),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! clear {
+    ($rgba:expr) => {
+        $crate::renderspec::RenderSpec::Clear($crate::renderspec::Clear {
+            target: None,
+            rgba: $rgba,
+        })
+    };
+    ($target:expr => $rgba:expr) => {
+        $crate::renderspec::RenderSpec::Clear($crate::renderspec::Clear {
+            target: Some(($target).into()),
+            rgba: $rgba,
+        })
+    };
+}
+
+impl From<Clear> for RenderSpec {
+    fn from(value: Clear) -> Self {
+        RenderSpec::Clear(value)
+    }
+}
+
+// Sets a logical canvas that window dst rects, text and shapes are authored
+// in, letterboxed/pillarboxed into the real window. Stays in effect until the
+// next Viewport, size None goes back to window pixels.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct Viewport {
+    pub size: Option<(u32, u32)>,
+}
+
+impl From<Viewport> for RenderSpec {
+    fn from(value: Viewport) -> Self {
+        RenderSpec::Viewport(value)
+    }
+}
+
+#[macro_export]
+macro_rules! viewport {
+    () => {
+        $crate::renderspec::RenderSpec::Viewport($crate::renderspec::Viewport { size: None })
+    };
+    ($w:expr, $h:expr) => {
+        $crate::renderspec::RenderSpec::Viewport($crate::renderspec::Viewport {
+            size: Some(($w, $h)),
+        })
+    };
+}
+
+// name is the path of a sound file, it is decoded the first time it's played
+// so there can be a short delay before the first play is heard
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct AudioPlay {
+    pub name: String,
+    pub gain: f32,
+    pub loop_: bool,
+}
+
+impl Default for AudioPlay {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            gain: 1.0,
+            loop_: false,
+        }
+    }
+}
+
+impl AudioPlay {
+    pub fn builder() -> AudioPlayBuilder {
+        AudioPlayBuilder::new()
+    }
+}
+
+impl From<AudioPlay> for RenderSpec {
+    fn from(value: AudioPlay) -> Self {
+        RenderSpec::AudioPlay(value)
+    }
+}
+
+#[derive(Default)]
+pub struct AudioPlayBuilder {
+    obj: AudioPlay,
+}
+
+impl AudioPlayBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: AudioPlay::default(),
+        }
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.name = name.as_ref().into();
+        self
+    }
+
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.obj.gain = gain;
+        self
+    }
+
+    pub fn loop_(mut self, loop_: bool) -> Self {
+        self.obj.loop_ = loop_;
+        self
+    }
+
+    pub fn build(self) -> AudioPlay {
+        self.obj
+    }
+}
+
+// Stops every voice playing name, or everything when name is None
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct AudioStop {
+    pub name: Option<String>,
+}
+
+impl From<AudioStop> for RenderSpec {
+    fn from(value: AudioStop) -> Self {
+        RenderSpec::AudioStop(value)
+    }
+}
+
+// Changes the gain of voices already playing name
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct AudioGain {
+    pub name: String,
+    pub gain: f32,
+}
+
+impl From<AudioGain> for RenderSpec {
+    fn from(value: AudioGain) -> Self {
+        RenderSpec::AudioGain(value)
+    }
+}
+
+// An OSC message to the --osc-out target, e.g.
+// SendOsc::new("/lights/dimmer").arg(0.5)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct SendOsc {
+    pub addr: String,
+    pub args: Vec<OscArg>,
+}
+
+impl SendOsc {
+    pub fn new<T: AsRef<str>>(addr: T) -> Self {
+        Self {
+            addr: addr.as_ref().into(),
+            args: vec![],
+        }
+    }
+
+    pub fn arg<T: Into<OscArg>>(mut self, arg: T) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+impl From<SendOsc> for RenderSpec {
+    fn from(value: SendOsc) -> Self {
+        RenderSpec::SendOsc(value)
+    }
+}
+
+// Rendered by whichever viz --plugin handles kind, args is json for the plugin
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct PluginSpec {
+    pub kind: String,
+    pub args: String,
+}
+
+impl PluginSpec {
+    pub fn new<K: AsRef<str>, T: Serialize>(kind: K, args: &T) -> serde_json::Result<Self> {
+        Ok(Self {
+            kind: kind.as_ref().into(),
+            args: serde_json::to_string(args)?,
+        })
+    }
+}
+
+impl From<PluginSpec> for RenderSpec {
+    fn from(value: PluginSpec) -> Self {
+        RenderSpec::Plugin(value)
+    }
+}
+
+// Shows a mixer on the HUD window without taking it to the output, sent
+// every frame it should show. Slots fill the bottom of the HUD left to right.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct Monitor {
+    pub mix: String,
+    pub slot: u32,
+}
+
+impl Monitor {
+    pub fn new<T: AsRef<str>>(mix: T, slot: u32) -> Self {
+        Self {
+            mix: mix.as_ref().into(),
+            slot,
+        }
+    }
+}
+
+impl From<Monitor> for RenderSpec {
+    fn from(value: Monitor) -> Self {
+        RenderSpec::Monitor(value)
+    }
+}
+
+// Changes the host's frame rate from the next frame. Frame numbers are
+// rescaled with it, so frame / fps keeps following the clock.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub struct SetFps {
+    pub fps: i64,
+}
+
+impl SetFps {
+    pub fn new(fps: i64) -> Self {
+        Self { fps }
+    }
+}
+
+impl From<SetFps> for RenderSpec {
+    fn from(value: SetFps) -> Self {
+        RenderSpec::SetFps(value)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[repr(C)]
+pub enum OscArg {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Str(String),
+    Bool(bool),
+}
+
+macro_rules! osc_arg_from {
+    ($ty:ty, $variant:ident) => {
+        impl From<$ty> for OscArg {
+            fn from(value: $ty) -> Self {
+                OscArg::$variant(value.into())
+            }
+        }
+    };
+}
+
+osc_arg_from! { i32, Int }
+osc_arg_from! { i64, Long }
+osc_arg_from! { f32, Float }
+osc_arg_from! { f64, Double }
+osc_arg_from! { String, Str }
+osc_arg_from! { &str, Str }
+osc_arg_from! { bool, Bool }
+
+// Mixes the host renders every frame, after the specs from calculate, until
+// the next SetScene. A Mix from calculate with the same name replaces the
+// scene's for that frame, an empty scene stops it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Scene {
+    pub mixes: Vec<Mix>,
+}
+
+impl From<Scene> for RenderSpec {
+    fn from(value: Scene) -> Self {
+        RenderSpec::SetScene(value)
+    }
+}
+
+impl From<Vec<Mix>> for Scene {
+    fn from(mixes: Vec<Mix>) -> Self {
+        Self { mixes }
+    }
+}
+
+// Changes to the output window, fields left as None are untouched
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(C)]
+pub struct WindowCtl {
+    pub title: Option<String>,
+    pub size: Option<(u32, u32)>,
+    pub position: Option<(i32, i32)>,
+    pub always_on_top: Option<bool>,
+    pub borderless: Option<bool>,
+}
+
+impl WindowCtl {
+    pub fn builder() -> WindowCtlBuilder {
+        WindowCtlBuilder::new()
+    }
+}
+
+impl From<WindowCtl> for RenderSpec {
+    fn from(value: WindowCtl) -> Self {
+        RenderSpec::WindowCtl(value)
+    }
+}
+
+#[derive(Default)]
+pub struct WindowCtlBuilder {
+    obj: WindowCtl,
+}
+
+impl WindowCtlBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: WindowCtl::default(),
+        }
+    }
+
+    pub fn title<T>(mut self, title: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.title = Some(title.as_ref().into());
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.obj.size = Some((width, height));
+        self
+    }
+
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.obj.position = Some((x, y));
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.obj.always_on_top = Some(always_on_top);
+        self
+    }
+
+    pub fn borderless(mut self, borderless: bool) -> Self {
+        self.obj.borderless = Some(borderless);
+        self
+    }
+
+    pub fn build(self) -> WindowCtl {
+        self.obj
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct DefineMacro {
+    pub name: String,
+    pub specs: Vec<RenderSpec>,
+}
+
+impl From<DefineMacro> for RenderSpec {
+    fn from(value: DefineMacro) -> Self {
+        RenderSpec::DefineMacro(value)
+    }
+}
+
+// Any string field in the macro's specs may contain ${param} placeholders,
+// e.g. a macro defined with SeekVid { target: "${deck}", .. }
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct RunMacro {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl RunMacro {
+    pub fn builder() -> RunMacroBuilder {
+        RunMacroBuilder::new()
+    }
+
+    pub fn expand(&self, specs: &[RenderSpec]) -> Result<Vec<RenderSpec>, serde_json::Error> {
+        if self.params.is_empty() {
+            return Ok(specs.to_vec());
+        }
+        let mut value = serde_json::to_value(specs)?;
+        self.substitute(&mut value);
+        serde_json::from_value(value)
+    }
+
+    fn substitute(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => {
+                for (k, v) in &self.params {
+                    *s = s.replace(&format!("${{{}}}", k), v);
+                }
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(|v| self.substitute(v)),
+            serde_json::Value::Object(map) => map.values_mut().for_each(|v| self.substitute(v)),
+            _ => (),
+        }
+    }
+}
+
+impl From<RunMacro> for RenderSpec {
+    fn from(value: RunMacro) -> Self {
+        RenderSpec::RunMacro(value)
+    }
+}
+
+#[derive(Default)]
+pub struct RunMacroBuilder {
+    obj: RunMacro,
+}
+
+impl RunMacroBuilder {
+    pub fn new() -> Self {
+        Self {
+            obj: RunMacro::default(),
+        }
+    }
+
+    pub fn name<T>(mut self, name: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.obj.name = name.as_ref().into();
+        self
+    }
+
+    pub fn param<T, U>(mut self, key: T, value: U) -> Self
+    where
+        T: AsRef<str>,
+        U: AsRef<str>,
+    {
+        self.obj
+            .params
+            .push((key.as_ref().into(), value.as_ref().into()));
+        self
+    }
+
+    pub fn build(self) -> RunMacro {
+        self.obj
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Reset {
+    pub target: String,
+}
+
+impl From<Reset> for RenderSpec {
+    fn from(value: Reset) -> Self {
+        RenderSpec::Reset(value)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum RenderCalcErr {
+    #[default]
+    None = 0,
+    AssetDataErr,
+    Unsupported, // the app needs a protocol version or capability the host lacks
+    Unknown = u8::MAX,
+}
+
+impl From<u8> for RenderCalcErr {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => RenderCalcErr::None,
+            1 => RenderCalcErr::AssetDataErr,
+            2 => RenderCalcErr::Unsupported,
+            _ => RenderCalcErr::Unknown,
+        }
+    }
+}
+
+impl Display for RenderCalcErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+
+impl Error for RenderCalcErr {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hud_text_without_spans() {
+        let spec: RenderSpec = serde_json::from_str(r#"{"HudText":{"text":"hi"}}"#).unwrap();
+        let RenderSpec::HudText(hud) = spec else {
+            panic!("not HudText");
+        };
+        assert_eq!(hud.text, "hi");
+        assert!(hud.spans.is_empty());
+    }
+
+    fn hud(text: &str) -> RenderSpec {
+        serde_json::from_value(serde_json::json!({ "HudText": { "text": text } })).unwrap()
+    }
+
+    fn hud_texts(specs: &[RenderSpec]) -> Vec<String> {
+        specs
+            .iter()
+            .flat_map(|spec| match spec {
+                RenderSpec::HudText(hud) => vec![hud.text.clone()],
+                RenderSpec::Batch(specs) => hud_texts(specs),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn run_macro_substitutes_params() {
+        let specs = vec![
+            hud("${who} at ${where}"),
+            RenderSpec::Batch(vec![hud("${who}${who}"), hud("${unknown}")]),
+        ];
+        let run = RunMacro::builder()
+            .name("greet")
+            .param("who", "deck a")
+            .param("where", "cue 2")
+            .build();
+        let expanded = run.expand(&specs).unwrap();
+        assert_eq!(
+            hud_texts(&expanded),
+            ["deck a at cue 2", "deck adeck a", "${unknown}"]
+        );
+    }
+
+    #[test]
+    fn run_macro_without_params() {
+        let specs = vec![hud("${who}")];
+        let run = RunMacro::builder().name("greet").build();
+        assert_eq!(hud_texts(&run.expand(&specs).unwrap()), ["${who}"]);
+    }
+
+    #[test]
+    fn send_midi_frames_by_status() {
+        assert_eq!(
+            SendMidi::note_on("out", 1, 60, 100).to_bytes(),
+            [0x91, 60, 100]
+        );
+        assert_eq!(SendMidi::note_off("out", 0, 60).to_bytes(), [0x80, 60, 0]);
+        assert_eq!(SendMidi::cc("out", 15, 7, 127).to_bytes(), [0xBF, 7, 127]);
+        // one data byte for program change and channel pressure
+        assert_eq!(SendMidi::program_change("out", 2, 5).to_bytes(), [0xC2, 5]);
+        let mut pressure = SendMidi::cc("out", 3, 40, 0);
+        pressure.event.kind = MIDI_CHANNEL_PRESSURE;
+        assert_eq!(pressure.to_bytes(), [0xD3, 40]);
+    }
+
+    #[test]
+    fn send_midi_masks_out_of_range() {
+        let midi = SendMidi::note_on("out", 0x12, 0xC8, 0xFF);
+        assert_eq!(midi.event.device, "out");
+        assert_eq!(midi.to_bytes(), [0x92, 0x48, 0x7F]);
+    }
+}
@@ -0,0 +1,241 @@
+// Single producer/single consumer byte ring living in the guest's linear
+// memory. The host and guest take turns (the host never touches it while a
+// guest export is running) so plain loads/stores are enough.
+//
+// Layout: capacity u32 | head u32 | tail u32 | data[capacity]
+// head/tail count bytes ever written/read and wrap at u32::MAX, which is why
+// capacity is a power of two. Messages are a u32 length followed by that many
+// bytes, wrapping around the data area.
+//
+// Neither side trusts what the other wrote: capacity is clamped to the region
+// it's in, and a length that runs past what was pushed is an error.
+use alloc::vec;
+use core::fmt::Display;
+
+pub const HEADER_LEN: usize = 12;
+
+#[derive(Debug)]
+pub struct RingFull;
+
+impl Display for RingFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ring buffer full")
+    }
+}
+
+impl core::error::Error for RingFull {}
+
+#[derive(Debug)]
+pub struct RingCorrupt;
+
+impl Display for RingCorrupt {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ring buffer corrupt")
+    }
+}
+
+impl core::error::Error for RingCorrupt {}
+
+// Size of a region holding capacity bytes of messages
+pub fn region_len(capacity: u32) -> usize {
+    HEADER_LEN + capacity as usize
+}
+
+pub fn init(region: &mut [u8]) {
+    let available = region.len().saturating_sub(HEADER_LEN).min(1 << 31) as u32;
+    let capacity = match available {
+        0 => 0,
+        n => 1 << (31 - n.leading_zeros()),
+    };
+    write_u32(region, 0, capacity);
+    write_u32(region, 4, 0);
+    write_u32(region, 8, 0);
+}
+
+pub fn push(region: &mut [u8], msg: &[u8]) -> Result<(), RingFull> {
+    if region.len() < HEADER_LEN {
+        return Err(RingFull);
+    }
+    let capacity = capacity(region) as u32;
+    let head = read_u32(region, 4);
+    let tail = read_u32(region, 8);
+    let used = head.wrapping_sub(tail);
+    let needed = 4 + msg.len() as u64;
+    if capacity == 0 || used as u64 + needed > capacity as u64 {
+        return Err(RingFull);
+    }
+    copy_in(
+        region,
+        capacity as usize,
+        head,
+        &(msg.len() as u32).to_le_bytes(),
+    );
+    copy_in(region, capacity as usize, head.wrapping_add(4), msg);
+    write_u32(region, 4, head.wrapping_add(needed as u32));
+    Ok(())
+}
+
+// Hands the next message to f, in place unless it wraps around the end
+pub fn pop_with<R>(
+    region: &mut [u8],
+    f: impl FnOnce(&[u8]) -> R,
+) -> Result<Option<R>, RingCorrupt> {
+    if region.len() < HEADER_LEN {
+        return Err(RingCorrupt);
+    }
+    let capacity = capacity(region);
+    let head = read_u32(region, 4);
+    let tail = read_u32(region, 8);
+    if head == tail {
+        return Ok(None);
+    }
+    let used = head.wrapping_sub(tail) as usize;
+    if capacity < 4 || used < 4 || used > capacity {
+        return Err(RingCorrupt);
+    }
+    let mut len = [0u8; 4];
+    copy_out(region, capacity, tail, &mut len);
+    let len = u32::from_le_bytes(len);
+    if len as usize > used - 4 {
+        return Err(RingCorrupt);
+    }
+    let start = tail.wrapping_add(4);
+    let offset = start as usize % capacity;
+    let result = if offset + len as usize <= capacity {
+        f(&region[HEADER_LEN + offset..HEADER_LEN + offset + len as usize])
+    } else {
+        let mut msg = vec![0u8; len as usize];
+        copy_out(region, capacity, start, &mut msg);
+        f(&msg)
+    };
+    write_u32(region, 8, start.wrapping_add(len));
+    Ok(Some(result))
+}
+
+// What the header says, but never more than the region holds
+fn capacity(region: &[u8]) -> usize {
+    (read_u32(region, 0) as usize).min(region.len() - HEADER_LEN)
+}
+
+fn copy_in(region: &mut [u8], capacity: usize, pos: u32, bytes: &[u8]) {
+    let offset = pos as usize % capacity;
+    let first = bytes.len().min(capacity - offset);
+    region[HEADER_LEN + offset..HEADER_LEN + offset + first].copy_from_slice(&bytes[..first]);
+    region[HEADER_LEN..HEADER_LEN + bytes.len() - first].copy_from_slice(&bytes[first..]);
+}
+
+fn copy_out(region: &[u8], capacity: usize, pos: u32, bytes: &mut [u8]) {
+    let offset = pos as usize % capacity;
+    let first = bytes.len().min(capacity - offset);
+    let rest = bytes.len() - first;
+    bytes[..first].copy_from_slice(&region[HEADER_LEN + offset..HEADER_LEN + offset + first]);
+    bytes[first..].copy_from_slice(&region[HEADER_LEN..HEADER_LEN + rest]);
+}
+
+fn read_u32(region: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([region[at], region[at + 1], region[at + 2], region[at + 3]])
+}
+
+fn write_u32(region: &mut [u8], at: usize, value: u32) {
+    region[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn ring(capacity: u32) -> Vec<u8> {
+        let mut region = vec![0u8; region_len(capacity)];
+        init(&mut region);
+        region
+    }
+
+    fn pop(region: &mut [u8]) -> Option<Vec<u8>> {
+        pop_with(region, |msg| msg.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut region = ring(64);
+        assert_eq!(pop(&mut region), None);
+        push(&mut region, b"one").unwrap();
+        push(&mut region, b"").unwrap();
+        push(&mut region, b"three").unwrap();
+        assert_eq!(pop(&mut region).as_deref(), Some(&b"one"[..]));
+        assert_eq!(pop(&mut region).as_deref(), Some(&b""[..]));
+        assert_eq!(pop(&mut region).as_deref(), Some(&b"three"[..]));
+        assert_eq!(pop(&mut region), None);
+    }
+
+    #[test]
+    fn capacity_is_a_power_of_two() {
+        let mut region = vec![0u8; HEADER_LEN + 100];
+        init(&mut region);
+        assert_eq!(read_u32(&region, 0), 64);
+    }
+
+    #[test]
+    fn full() {
+        let mut region = ring(16);
+        push(&mut region, &[1; 12]).unwrap();
+        assert!(push(&mut region, b"").is_err());
+        pop(&mut region).unwrap();
+        push(&mut region, &[2; 12]).unwrap();
+    }
+
+    #[test]
+    fn wraps_around_the_end() {
+        let mut region = ring(16);
+        for round in 0..10u8 {
+            // 4 + 6 bytes, so the messages start all over the data area and
+            // most of them are split across its end
+            let msg = [round; 6];
+            push(&mut region, &msg).unwrap();
+            assert_eq!(pop(&mut region).as_deref(), Some(&msg[..]));
+        }
+    }
+
+    #[test]
+    fn wraps_around_u32() {
+        let mut region = ring(16);
+        write_u32(&mut region, 4, u32::MAX - 5);
+        write_u32(&mut region, 8, u32::MAX - 5);
+        push(&mut region, b"across").unwrap();
+        assert!(read_u32(&region, 4) < 16);
+        assert_eq!(pop(&mut region).as_deref(), Some(&b"across"[..]));
+        assert_eq!(pop(&mut region), None);
+    }
+
+    #[test]
+    fn corrupt_lengths() {
+        let mut region = ring(16);
+        push(&mut region, b"abc").unwrap();
+        // a length past what was pushed
+        region[HEADER_LEN] = 9;
+        assert!(pop_with(&mut region, |_| ()).is_err());
+
+        // head further ahead than the ring holds
+        let mut region = ring(16);
+        write_u32(&mut region, 4, 100);
+        assert!(pop_with(&mut region, |_| ()).is_err());
+    }
+
+    #[test]
+    fn gap_shorter_than_a_length() {
+        for head in 1..4 {
+            let mut region = ring(16);
+            write_u32(&mut region, 4, head);
+            assert!(pop_with(&mut region, |_| ()).is_err());
+        }
+    }
+
+    #[test]
+    fn capacity_is_clamped_to_the_region() {
+        let mut region = ring(16);
+        write_u32(&mut region, 0, 1 << 20);
+        push(&mut region, &[7; 8]).unwrap();
+        assert!(push(&mut region, &[7; 8]).is_err());
+        assert_eq!(pop(&mut region).as_deref(), Some(&[7; 8][..]));
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Mutex, Once,
+    },
+    time::Duration,
+    u64,
+};
+
+use crate::{
+    gfxinfo::{
+        Asset, AssetEvent, AssetReloadedEvent, AudioAnalysisEvent, CaptureDevicesEvent, ClockEvent,
+        ControllerEvent, DeviceEvent, FetchEvent, FileChangeEvent, FileDropEvent, FrameEvent,
+        GfxEvent, GfxInfo, GlobMatchedEvent, KeyCode, KeyEvent, LinkEvent, LogLevel, MidiEvent,
+        MouseEvent, PluginEvent, ReadbackEvent, ReadbackRequest, ResizeEvent, SettingChange,
+        SpecErrorEvent, TempoEvent, TimecodeEvent, TouchEvent, MIDI_CONTROL_CHANGE,
+    },
+    protocol::{Capabilities, PROTOCOL_VERSION},
+    renderspec::{RenderCalcErr, RenderSpec, SendMidi},
+    ring,
+    wire::WireFormat,
+};
+use serde_json;
+
+#[link(wasm_import_module = "host")]
+unsafe extern "C" {
+    fn send_bytes(ptr: u32, len: u32);
+    fn send_settings(ptr: u32, len: u32);
+    fn send_settings_schema(ptr: u32, len: u32);
+    fn recv_settings(pts: u32);
+    fn recv_settings_size() -> u64;
+    fn recv_gfx_info(ptr: u32);
+    fn gfx_info_serialized_size() -> u32;
+    fn recv_reg_events(ptr: u32);
+    fn reg_events_serialized_size() -> u32;
+    fn request_readback(ptr: u32, len: u32);
+    fn request_fetch(ptr: u32, len: u32);
+    #[link_name = "request_capture_devices"]
+    fn host_request_capture_devices();
+    fn request_glob_watch(ptr: u32, len: u32);
+    #[link_name = "send_midi"]
+    fn host_send_midi(device_ptr: u32, device_len: u32, ptr: u32, len: u32) -> u32;
+    fn gfx_info_generation() -> u64;
+    fn clock_wall_ns() -> u64;
+    fn clock_monotonic_ns() -> u64;
+    fn clock_frame_deadline_ns() -> u64;
+    #[link_name = "vid_position"]
+    fn host_vid_position(ptr: u32, len: u32, out: u32) -> u32;
+    #[link_name = "mix_present_time"]
+    fn host_mix_present_time(ptr: u32, len: u32, out: u32) -> u32;
+    #[link_name = "log"]
+    fn host_log(level: u32, ptr: u32, len: u32);
+}
+
+extern "Rust" {
+    fn asset_list(fps: i64) -> Vec<Asset>;
+    fn calculate(
+        canvas_w: u32,
+        canvas_h: u32,
+        frame: i64,
+        fps: i64,
+        gfx_info: &HashMap<String, GfxInfo>,
+        reg_events: &[GfxEvent],
+    ) -> Result<Vec<RenderSpec>, Box<dyn Error>>;
+    fn encode_settings() -> Vec<u8>;
+    fn decode_settings(bytes: &[u8]);
+}
+
+static INITIALIZE: Once = Once::new();
+static GFX_INFO: Mutex<Option<HashMap<String, GfxInfo>>> = Mutex::new(None);
+static GFX_INFO_GENERATION: AtomicU64 = AtomicU64::new(0);
+static WIRE_FORMAT: AtomicU32 = AtomicU32::new(WireFormat::Json as u32);
+
+static HOST_PROTOCOL_VERSION: AtomicU32 = AtomicU32::new(0);
+static HOST_CAPABILITIES: AtomicU64 = AtomicU64::new(0);
+
+// Called by the host before asset_list, hosts that predate it leave both at 0
+#[no_mangle]
+pub extern "C" fn negotiate_protocol(host_version: u32, host_capabilities: u64) -> u32 {
+    HOST_PROTOCOL_VERSION.store(host_version, Ordering::SeqCst);
+    HOST_CAPABILITIES.store(host_capabilities, Ordering::SeqCst);
+    PROTOCOL_VERSION
+}
+
+pub fn host_protocol_version() -> u32 {
+    HOST_PROTOCOL_VERSION.load(Ordering::SeqCst)
+}
+
+pub fn host_capabilities() -> Capabilities {
+    HOST_CAPABILITIES.load(Ordering::SeqCst).into()
+}
+
+// For use in asset_list/calculate: `require(Capabilities::DRAW_TEXT)?`
+pub fn require(caps: Capabilities) -> Result<(), Box<dyn Error>> {
+    if host_capabilities().contains(caps) {
+        Ok(())
+    } else {
+        Err(Box::new(RenderCalcErr::Unsupported))
+    }
+}
+
+// Host offers its preferred format, the guest answers with the one it will use
+#[no_mangle]
+pub extern "C" fn negotiate_wire_format(preferred: u32) -> u32 {
+    let format = WireFormat::from(preferred);
+    WIRE_FORMAT.store(format as u32, Ordering::SeqCst);
+    format as u32
+}
+
+// Events in and specs out, see ring.rs. Null until the host asks for them.
+static EVENT_RING: AtomicU32 = AtomicU32::new(0);
+static SPEC_RING: AtomicU32 = AtomicU32::new(0);
+
+// The host calls this once after loading, the two rings sit back to back at
+// the returned address and live as long as the module
+#[no_mangle]
+pub extern "C" fn ring_buffers(capacity: u32) -> u32 {
+    let len = ring::region_len(capacity);
+    let mem: &'static mut [u8] = Box::leak(vec![0u8; len * 2].into_boxed_slice());
+    let (events, specs) = mem.split_at_mut(len);
+    ring::init(events);
+    ring::init(specs);
+    EVENT_RING.store(events.as_ptr() as u32, Ordering::SeqCst);
+    SPEC_RING.store(specs.as_ptr() as u32, Ordering::SeqCst);
+    events.as_ptr() as u32
+}
+
+fn ring_region(ring: &AtomicU32) -> Option<&'static mut [u8]> {
+    let ptr = ring.load(Ordering::SeqCst) as *mut u8;
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe {
+        let capacity = u32::from_le_bytes(*(ptr as *const [u8; 4]));
+        Some(std::slice::from_raw_parts_mut(
+            ptr,
+            ring::region_len(capacity),
+        ))
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn asset_list_internal(fps: i64) -> u32 {
+    let asset_list = unsafe { asset_list(fps) };
+
+    let v = match serde_json::to_vec(&asset_list) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Err serializing asset data {:?}", e);
+            return RenderCalcErr::AssetDataErr as u32;
+        }
+    };
+
+    unsafe {
+        send_bytes(v.as_ptr() as u32, v.len() as u32);
+    }
+    RenderCalcErr::None as u32
+}
+
+#[no_mangle]
+pub extern "C" fn calculate_internal(canvas_w: u32, canvas_h: u32, frame: i64, fps: i64) -> u32 {
+    INITIALIZE.call_once(|| {
+        init_gfx_info();
+    });
+
+    // assets were loaded/unloaded at runtime
+    let generation = unsafe { gfx_info_generation() };
+    if GFX_INFO_GENERATION.swap(generation, Ordering::SeqCst) != generation {
+        init_gfx_info();
+    }
+
+    let wire = WireFormat::from(WIRE_FORMAT.load(Ordering::SeqCst));
+    // bursts too big for the ring still come through the copy path
+    let from_ring = ring_region(&EVENT_RING).and_then(|region| {
+        ring::pop_with(region, |msg| wire.decode::<Vec<GfxEvent>>(msg))
+            .ok()
+            .flatten()
+    });
+    let reg_events: Vec<GfxEvent> = match from_ring {
+        Some(events) => events.unwrap(),
+        None => {
+            let sz = unsafe { reg_events_serialized_size() } as usize;
+            let mut buf: Vec<u8> = Vec::with_capacity(sz);
+            buf.resize_with(sz, || 0u8);
+            unsafe { recv_reg_events(buf.as_mut_ptr() as u32) }
+            wire.decode(buf.as_slice()).unwrap()
+        }
+    };
+
+    match unsafe {
+        calculate(
+            canvas_w,
+            canvas_h,
+            frame,
+            fps,
+            GFX_INFO.lock().unwrap().as_ref().unwrap(),
+            &reg_events,
+        )
+    } {
+        Ok(specs) => {
+            let bytes = wire.encode(&specs).unwrap();
+            let sent =
+                ring_region(&SPEC_RING).map_or(false, |region| ring::push(region, &bytes).is_ok());
+            if !sent {
+                unsafe { send_bytes(bytes.as_ptr() as u32, bytes.len() as u32) };
+            }
+            RenderCalcErr::None as u32
+        }
+        Err(e) => {
+            eprintln!(
+                "Error calculating {} {} {}: {}",
+                canvas_w, canvas_h, frame, e
+            );
+            match e.downcast_ref::<RenderCalcErr>() {
+                Some(RenderCalcErr::Unsupported) => RenderCalcErr::Unsupported as u32,
+                _ => RenderCalcErr::Unknown as u32,
+            }
+        }
+    }
+}
+
+fn init_gfx_info() {
+    let mut lock = GFX_INFO.lock().unwrap();
+    let sz = unsafe { gfx_info_serialized_size() } as usize;
+    let mut buf: Vec<u8> = Vec::with_capacity(sz);
+    buf.resize_with(sz, || 0u8);
+    unsafe { recv_gfx_info(buf.as_mut_ptr() as u32) }
+    let map: HashMap<String, GfxInfo> = serde_json::from_slice(buf.as_slice()).unwrap();
+
+    lock.replace(map);
+}
+
+pub type SettingsMigration = fn(old_version: u32, bytes: &[u8]) -> Option<Vec<u8>>;
+
+static SETTINGS_VERSION: AtomicU32 = AtomicU32::new(0);
+static SETTINGS_MIGRATION: Mutex<Option<SettingsMigration>> = Mutex::new(None);
+
+// Call from asset_list. Bump the version whenever encode_settings changes
+// shape, on reload the host hands settings from an older version to migrate,
+// which returns bytes decode_settings understands or None to start fresh.
+pub fn set_settings_version(version: u32, migrate: Option<SettingsMigration>) {
+    SETTINGS_VERSION.store(version, Ordering::SeqCst);
+    *SETTINGS_MIGRATION.lock().unwrap() = migrate;
+}
+
+#[no_mangle]
+pub extern "C" fn settings_version() -> u32 {
+    SETTINGS_VERSION.load(Ordering::SeqCst)
+}
+
+// 0 when the migrated settings were applied
+#[no_mangle]
+pub extern "C" fn migrate_settings(old_version: u32) -> u32 {
+    let Some(migrate) = *SETTINGS_MIGRATION.lock().unwrap() else {
+        return 1;
+    };
+    let buf = unsafe {
+        let sz = recv_settings_size() as usize;
+        let mut buf: Vec<u8> = Vec::with_capacity(sz);
+        buf.resize_with(sz, || 0u8);
+        recv_settings(buf.as_mut_ptr() as u32);
+        buf
+    };
+    match migrate(old_version, &buf) {
+        Some(bytes) => {
+            unsafe { decode_settings(&bytes) };
+            0
+        }
+        None => 1,
+    }
+}
+
+pub type SettingsSchema = fn() -> String;
+
+static SETTINGS_SCHEMA: Mutex<Option<SettingsSchema>> = Mutex::new(None);
+
+// Call from asset_list with a function returning a JSON schema of the
+// settings, e.g. || Settings::default().settings_schema() for an Adjustable
+// struct. Tools use it to build parameter editors.
+pub fn set_settings_schema(schema: SettingsSchema) {
+    *SETTINGS_SCHEMA.lock().unwrap() = Some(schema);
+}
+
+// 0 when a schema was sent
+#[no_mangle]
+pub extern "C" fn settings_schema() -> u32 {
+    let Some(schema) = *SETTINGS_SCHEMA.lock().unwrap() else {
+        return 1;
+    };
+    let schema = schema();
+    unsafe { send_settings_schema(schema.as_ptr() as u32, schema.len() as u32) };
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn save_settings() {
+    unsafe {
+        let bytes = encode_settings();
+        send_settings(bytes.as_ptr() as u32, bytes.len() as u32)
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn restore_settings() {
+    unsafe {
+        let sz = recv_settings_size() as usize;
+        let mut buf: Vec<u8> = Vec::with_capacity(sz);
+        buf.resize_with(sz, || 0u8);
+        recv_settings(buf.as_mut_ptr() as u32);
+        decode_settings(&buf);
+    }
+}
+
+// Time since the unix epoch on the host
+pub fn wall_clock() -> Duration {
+    Duration::from_nanos(unsafe { clock_wall_ns() })
+}
+
+// Never goes backwards, also across hot reloads
+pub fn monotonic() -> Duration {
+    Duration::from_nanos(unsafe { clock_monotonic_ns() })
+}
+
+// Wall clock time the frame being calculated is presented at. Animating off
+// this instead of the frame number stays smooth when frames are dropped.
+pub fn frame_deadline() -> Duration {
+    Duration::from_nanos(unsafe { clock_frame_deadline_ns() })
+}
+
+// Sent to the --midi-output port named device before this call returns,
+// rather than with the frame's specs. For controller feedback like LED rings
+// and lamps, bytes can be any message including sysex.
+pub fn send_midi(device: &str, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    let failed = unsafe {
+        host_send_midi(
+            device.as_ptr() as u32,
+            device.len() as u32,
+            bytes.as_ptr() as u32,
+            bytes.len() as u32,
+        )
+    };
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("Could not send midi to {}", device).into()),
+    }
+}
+
+pub fn send_midi_now(cmd: &SendMidi) -> Result<(), Box<dyn Error>> {
+    send_midi(&cmd.event.device, &cmd.to_bytes())
+}
+
+// The body arrives as a GfxEvent::FetchEvent once it's downloaded, the host
+// only fetches urls it was told to allow
+pub fn fetch(url: &str) {
+    unsafe { request_fetch(url.as_ptr() as u32, url.len() as u32) }
+}
+
+// Cameras and capture cards with their modes arrive as a
+// GfxEvent::CaptureDevices a few frames later, listing them opens each one
+pub fn request_capture_devices() {
+    unsafe { host_request_capture_devices() }
+}
+
+// Files pattern matches arrive as a GfxEvent::GlobMatched, then again as
+// more turn up, for loading what's dropped into a folder mid-show. Watching a
+// pattern again gets everything it matches again.
+pub fn watch_glob(pattern: &str) {
+    unsafe { request_glob_watch(pattern.as_ptr() as u32, pattern.len() as u32) }
+}
+
+// Shows up in the host's log and, depending on its filter, on the HUD
+pub fn log(level: LogLevel, message: &str) {
+    unsafe { host_log(level as u32, message.as_ptr() as u32, message.len() as u32) }
+}
+
+#[macro_export]
+macro_rules! app_log {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::spec_engine::log($crate::gfxinfo::LogLevel::$level, &format!($($arg)*))
+    };
+}
+
+// (pts, duration) in seconds as of the last rendered frame, for any loaded
+// vid whether or not it's in a mix
+pub fn vid_position(name: &str) -> Option<(f64, f64)> {
+    let mut out = [0f64; 2];
+    let found = unsafe {
+        host_vid_position(
+            name.as_ptr() as u32,
+            name.len() as u32,
+            out.as_mut_ptr() as u32,
+        )
+    };
+    (found != 0).then(|| (out[0], out[1]))
+}
+
+// Seconds the mix will present its next frame at
+pub fn mix_present_time(name: &str) -> Option<f64> {
+    let mut out = 0f64;
+    let found = unsafe {
+        host_mix_present_time(
+            name.as_ptr() as u32,
+            name.len() as u32,
+            &mut out as *mut f64 as u32,
+        )
+    };
+    (found != 0).then_some(out)
+}
+
+// The result arrives as a GfxEvent::ReadbackEvent a calculate or two later,
+// its frame the one that was read
+pub fn readback(req: &ReadbackRequest) {
+    let Ok(v) = serde_json::to_vec(req) else {
+        eprintln!("Err serializing readback request {:?}", req);
+        return;
+    };
+    unsafe { request_readback(v.as_ptr() as u32, v.len() as u32) }
+}
+
+// Typed views over the reg_events slice passed to calculate
+pub trait GfxEvents {
+    fn keys(&self) -> impl Iterator<Item = &KeyEvent>;
+    fn midi(&self) -> impl Iterator<Item = &MidiEvent>;
+    fn controllers(&self) -> impl Iterator<Item = &ControllerEvent>;
+    fn mouse(&self) -> impl Iterator<Item = &MouseEvent>;
+    fn touches(&self) -> impl Iterator<Item = &TouchEvent>;
+    fn dropped(&self) -> impl Iterator<Item = &FileDropEvent>;
+    fn file_changes(&self) -> impl Iterator<Item = &FileChangeEvent>;
+    fn plugin_events(&self) -> impl Iterator<Item = &PluginEvent>;
+    fn setting_changes(&self) -> impl Iterator<Item = &SettingChange>;
+    fn audio_analysis(&self) -> impl Iterator<Item = &AudioAnalysisEvent>;
+    fn tempo(&self) -> Option<&TempoEvent>;
+    fn frames(&self) -> impl Iterator<Item = &FrameEvent>;
+    fn readbacks(&self) -> impl Iterator<Item = &ReadbackEvent>;
+    fn assets(&self) -> impl Iterator<Item = &AssetEvent>;
+    fn asset_reloads(&self) -> impl Iterator<Item = &AssetReloadedEvent>;
+    fn spec_errors(&self) -> impl Iterator<Item = &SpecErrorEvent>;
+    fn fetches(&self) -> impl Iterator<Item = &FetchEvent>;
+    fn capture_devices(&self) -> Option<&CaptureDevicesEvent>;
+    fn glob_matches(&self) -> impl Iterator<Item = &GlobMatchedEvent>;
+    fn resizes(&self) -> impl Iterator<Item = &ResizeEvent>;
+    fn clocks(&self) -> impl Iterator<Item = &ClockEvent>;
+    fn timecodes(&self) -> impl Iterator<Item = &TimecodeEvent>;
+    fn link(&self) -> Option<&LinkEvent>;
+    fn connected(&self) -> impl Iterator<Item = &DeviceEvent>;
+    fn disconnected(&self) -> impl Iterator<Item = &DeviceEvent>;
+    fn reloaded(&self) -> bool;
+
+    // A fresh press of combo this frame, key repeats don't count
+    fn pressed(&self, combo: &KeyCombo) -> bool {
+        self.keys().any(|k| combo.matches(k))
+    }
+
+    // Latest value of a controller this frame scaled to 0..=1
+    fn cc(&self, device: &str, channel: u8, controller: u8) -> Option<f32> {
+        self.midi()
+            .filter(|m| m.device == device && m.channel == channel && m.key == controller)
+            .filter_map(cc_normalized)
+            .last()
+    }
+}
+
+impl GfxEvents for [GfxEvent] {
+    fn keys(&self) -> impl Iterator<Item = &KeyEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::KeyEvent(k) => Some(k),
+            _ => None,
+        })
+    }
+
+    fn midi(&self) -> impl Iterator<Item = &MidiEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::MidiEvent(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    fn controllers(&self) -> impl Iterator<Item = &ControllerEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::ControllerEvent(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    fn mouse(&self) -> impl Iterator<Item = &MouseEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::MouseEvent(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    fn touches(&self) -> impl Iterator<Item = &TouchEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::TouchEvent(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    fn dropped(&self) -> impl Iterator<Item = &FileDropEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::FileDropped(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    fn file_changes(&self) -> impl Iterator<Item = &FileChangeEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::FileChanged(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    fn plugin_events(&self) -> impl Iterator<Item = &PluginEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::PluginEvent(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    fn setting_changes(&self) -> impl Iterator<Item = &SettingChange> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::SettingChanged(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    fn audio_analysis(&self) -> impl Iterator<Item = &AudioAnalysisEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::AudioAnalysisEvent(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    // The latest, there's one a frame
+    fn tempo(&self) -> Option<&TempoEvent> {
+        self.iter().rev().find_map(|e| match e {
+            GfxEvent::TempoEvent(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    fn frames(&self) -> impl Iterator<Item = &FrameEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::FrameEvent(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    fn readbacks(&self) -> impl Iterator<Item = &ReadbackEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::ReadbackEvent(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    fn assets(&self) -> impl Iterator<Item = &AssetEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::AssetEvent(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    fn asset_reloads(&self) -> impl Iterator<Item = &AssetReloadedEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::AssetReloaded(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    fn spec_errors(&self) -> impl Iterator<Item = &SpecErrorEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::SpecErrorEvent(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    fn fetches(&self) -> impl Iterator<Item = &FetchEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::FetchEvent(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    fn capture_devices(&self) -> Option<&CaptureDevicesEvent> {
+        self.iter().rev().find_map(|e| match e {
+            GfxEvent::CaptureDevices(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    fn glob_matches(&self) -> impl Iterator<Item = &GlobMatchedEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::GlobMatched(g) => Some(g),
+            _ => None,
+        })
+    }
+
+    fn resizes(&self) -> impl Iterator<Item = &ResizeEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::ResizeEvent(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    fn clocks(&self) -> impl Iterator<Item = &ClockEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::ClockEvent(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    fn timecodes(&self) -> impl Iterator<Item = &TimecodeEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::TimecodeEvent(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    fn link(&self) -> Option<&LinkEvent> {
+        self.iter().rev().find_map(|e| match e {
+            GfxEvent::LinkEvent(l) => Some(l),
+            _ => None,
+        })
+    }
+
+    fn connected(&self) -> impl Iterator<Item = &DeviceEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::DeviceConnected(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    fn disconnected(&self) -> impl Iterator<Item = &DeviceEvent> {
+        self.iter().filter_map(|e| match e {
+            GfxEvent::DeviceDisconnected(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    fn reloaded(&self) -> bool {
+        self.iter().any(|e| matches!(e, GfxEvent::ReloadEvent()))
+    }
+}
+
+// A key with the modifiers that have to be held, any others must be up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub key: KeyCode,
+    pub shift: bool,
+    pub alt: bool,
+    pub ctl: bool,
+}
+
+impl KeyCombo {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            shift: false,
+            alt: false,
+            ctl: false,
+        }
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    pub fn ctl(mut self) -> Self {
+        self.ctl = true;
+        self
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        event.down
+            && !event.repeat
+            && event.key == self.key
+            && event.shift == self.shift
+            && event.alt == self.alt
+            && event.ctl == self.ctl
+    }
+}
+
+// A control change's value scaled to 0..=1, None for other messages
+pub fn cc_normalized(event: &MidiEvent) -> Option<f32> {
+    (event.kind == MIDI_CONTROL_CHANGE).then(|| (event.velocity & 0x7F) as f32 / 127.0)
+}
+
+// Time since the previous calculate, which can be more than a frame when the
+// host dropped some
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameDelta {
+    pub frame: i64,
+    pub frames: i64,
+    pub seconds: f64,
+}
+
+// Keep one in the app's state and tick it from calculate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameClock {
+    last_frame: Option<i64>,
+}
+
+impl FrameClock {
+    // The first tick, and any after the frame went backwards, count as one frame
+    pub fn tick(&mut self, frame: i64, fps: i64) -> FrameDelta {
+        let frames = match self.last_frame {
+            Some(last) if frame > last => frame - last,
+            _ => 1,
+        };
+        self.last_frame = Some(frame);
+        FrameDelta {
+            frame,
+            frames,
+            seconds: frames as f64 / fps.max(1) as f64,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::cmp::Ordering;
+use std::error::Error;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::protocol::PROTOCOL_VERSION;
+
+// Encoding used for the per frame traffic (specs and events) between host and
+// guest. Negotiated once after instantiation, guests that predate the
+// handshake stay on Json.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WireFormat {
+    #[default]
+    Json = 0,
+    Bincode = 1,
+}
+
+impl From<u32> for WireFormat {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => WireFormat::Bincode,
+            _ => WireFormat::Json,
+        }
+    }
+}
+
+impl WireFormat {
+    // What a host offers a guest speaking guest_version. Older protocols get
+    // json, bincode has no defaults for fields added since, and guests newer
+    // than the host get nothing, they can't be loaded.
+    pub fn offered_to(guest_version: u32) -> Option<WireFormat> {
+        match guest_version.cmp(&PROTOCOL_VERSION) {
+            Ordering::Less => Some(WireFormat::Json),
+            Ordering::Equal => Some(WireFormat::Bincode),
+            Ordering::Greater => None,
+        }
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(match self {
+            WireFormat::Json => serde_json::to_vec(value)?,
+            WireFormat::Bincode => bincode::serialize(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(match self {
+            WireFormat::Json => serde_json::from_slice(bytes)?,
+            WireFormat::Bincode => bincode::deserialize(bytes)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfxinfo::{AssetEvent, GfxEvent, SpecErrorEvent, SpecErrorKind};
+    use crate::renderspec::{Mix, RenderSpec, RunMacro, SendCmd, SendValue};
+
+    fn specs() -> Vec<RenderSpec> {
+        let hud: RenderSpec = serde_json::from_str(r#"{"HudText":{"text":"hi"}}"#).unwrap();
+        let seek: RenderSpec =
+            serde_json::from_str(r#"{"SeekVid":{"target":"v","sec":1.5,"exact":true}}"#).unwrap();
+        vec![
+            Mix::builder().name("main").video("v").build().into(),
+            RenderSpec::Batch(vec![
+                SendCmd::builder()
+                    .mix("main")
+                    .name("gain")
+                    .value(SendValue::Vector(vec![0.5, 1.0]))
+                    .build()
+                    .into(),
+                hud,
+            ]),
+            seek,
+            RunMacro::builder()
+                .name("m")
+                .param("who", "a")
+                .build()
+                .into(),
+        ]
+    }
+
+    fn events() -> Vec<GfxEvent> {
+        vec![
+            GfxEvent::ReloadEvent(),
+            GfxEvent::AssetEvent(AssetEvent {
+                name: "a".into(),
+                loaded: false,
+                error: Some("gone".into()),
+            }),
+            GfxEvent::SpecErrorEvent(SpecErrorEvent {
+                frame: 3,
+                index: 1,
+                kind: SpecErrorKind::BadUniform,
+                message: "no".into(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn round_trips() {
+        for wire in [WireFormat::Json, WireFormat::Bincode] {
+            let specs = specs();
+            let decoded: Vec<RenderSpec> = wire.decode(&wire.encode(&specs).unwrap()).unwrap();
+            assert_eq!(
+                format!("{:?}", decoded),
+                format!("{:?}", specs),
+                "{:?}",
+                wire
+            );
+
+            let events = events();
+            let decoded: Vec<GfxEvent> = wire.decode(&wire.encode(&events).unwrap()).unwrap();
+            assert_eq!(decoded, events, "{:?}", wire);
+        }
+    }
+
+    #[test]
+    fn formats_differ() {
+        let json = WireFormat::Json.encode(&events()).unwrap();
+        assert!(WireFormat::Bincode.decode::<Vec<GfxEvent>>(&json).is_err());
+        assert!(WireFormat::Json
+            .decode::<Vec<GfxEvent>>(b"\x03\x00")
+            .is_err());
+    }
+
+    #[test]
+    fn offered_by_protocol() {
+        assert_eq!(WireFormat::offered_to(0), Some(WireFormat::Json));
+        assert_eq!(
+            WireFormat::offered_to(PROTOCOL_VERSION - 1),
+            Some(WireFormat::Json)
+        );
+        assert_eq!(
+            WireFormat::offered_to(PROTOCOL_VERSION),
+            Some(WireFormat::Bincode)
+        );
+        assert_eq!(WireFormat::offered_to(PROTOCOL_VERSION + 1), None);
+    }
+
+    #[test]
+    fn unknown_formats_are_json() {
+        assert_eq!(WireFormat::from(0), WireFormat::Json);
+        assert_eq!(WireFormat::from(1), WireFormat::Bincode);
+        assert_eq!(WireFormat::from(7), WireFormat::Json);
+        assert_eq!(
+            WireFormat::from(WireFormat::Bincode as u32),
+            WireFormat::Bincode
+        );
+    }
+}
@@ -1,24 +1,143 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     error::Error,
-    path::Path,
-    sync::{Arc, Mutex},
+    fmt::Display,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Result};
-use wasmtime::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store, TypedFunc};
-use wasmtime_wasi::WasiCtxBuilder;
+use tracing::{debug, error, info, warn};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, Trap, TypedFunc,
+};
+use wasmtime_wasi::{cli::OutputFile, WasiCtxBuilder};
 use wasmtime_wasi::{
     p1::{self, WasiP1Ctx},
     DirPerms, FilePerms,
 };
 
 use crate::{
-    gfxinfo::{Asset, GfxEvent, GfxInfo},
+    assetcache,
+    gfxinfo::{
+        Asset, GfxEvent, GfxInfo, GfxInfoEvent, LoadPolicy, LogLevel, ReadbackRequest, ResizeEvent,
+    },
     gfxruntime,
+    protocol::{Capabilities, PROTOCOL_VERSION},
     renderspec::RenderCalcErr,
+    ring,
+    wire::WireFormat,
+};
+use crate::{
+    gfxruntime::{GfxData, PlaybackPositions},
+    renderspec::RenderSpec,
 };
-use crate::{gfxruntime::GfxData, renderspec::RenderSpec};
+
+// What this build offers, sent to the guest at load. The optional parts are
+// there when the features behind them were built in.
+pub fn host_capabilities() -> Capabilities {
+    let mut capabilities = Capabilities::READBACK
+        | Capabilities::DRAW_SHAPE
+        | Capabilities::TRANSITIONS
+        | Capabilities::ASSET_LOADING
+        | Capabilities::AUDIO_PLAYBACK
+        | Capabilities::FETCH
+        | Capabilities::AUDIO_INPUT;
+    if cfg!(feature = "fonts") {
+        capabilities = capabilities | Capabilities::DRAW_TEXT;
+    }
+    if cfg!(feature = "ndi") {
+        capabilities = capabilities | Capabilities::NDI;
+    }
+    if cfg!(feature = "recording") {
+        capabilities = capabilities | Capabilities::RECORDING;
+    }
+    capabilities
+}
+
+// Per direction, enough for a few thousand MIDI events or specs a frame
+const RING_CAPACITY: u32 = 1 << 20;
+
+// The epoch is bumped this often, so calc budgets are counted in these
+const EPOCH_TICK: Duration = Duration::from_millis(1);
+// Deadline for everything but calc, which has its own budget
+const UNBOUNDED_TICKS: u64 = u64::MAX / 2;
+
+// Returned by calc when the guest ran past its budget and was interrupted
+#[derive(Debug)]
+pub struct CalcTimeout {
+    pub budget: Duration,
+}
+
+impl Display for CalcTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "calculate ran past its {:?} budget", self.budget)
+    }
+}
+
+impl Error for CalcTimeout {}
+
+// Sends raw midi bytes to the named output right away
+pub type MidiSink = Arc<dyn Fn(&str, &[u8]) -> Result<()> + Send + Sync>;
+
+// A host dir the guest sees at guest_path, parsed from host:guest:ro|rw
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preopen {
+    pub host_path: PathBuf,
+    pub guest_path: String,
+    pub writable: bool,
+}
+
+impl FromStr for Preopen {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // split from the right so a host path may contain ':'
+        let mut parts = s.rsplitn(3, ':');
+        let (Some(mode), Some(guest_path), Some(host_path)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Expected host_path:guest_path:ro|rw, got {}", s);
+        };
+        let writable = match mode {
+            "ro" => false,
+            "rw" => true,
+            _ => bail!("Preopen mode must be ro or rw, got {}", mode),
+        };
+        Ok(Self {
+            host_path: host_path.into(),
+            guest_path: guest_path.into(),
+            writable,
+        })
+    }
+}
+
+// Under the host's cache directory, holds compiled modules keyed by wasm and
+// engine. Never under a preopen, an app that could write a module there would
+// have it run as native code on the next load.
+const MODULE_CACHE_DIR: &str = "modules";
+
+// Last lines the guest wrote to stderr, kept to explain a crash
+const STDERR_LINES: usize = 20;
+
+// Shared across reloads so a guest's monotonic clock never jumps backwards
+static HOST_START: OnceLock<Instant> = OnceLock::new();
+
+// Offsets of the guest allocated event and spec rings in its memory
+struct GuestRings {
+    memory: Memory,
+    events: usize,
+    specs: usize,
+}
 
 pub struct AppRuntime {
     _engine: Engine,
@@ -26,26 +145,55 @@ pub struct AppRuntime {
     buf_ref: Arc<Mutex<Vec<u8>>>,
     reg_events_ref: Arc<Mutex<Vec<u8>>>,
     loaded_asset_info_ref: Arc<HashMap<Asset, GfxInfo>>,
+    asset_errors: Vec<(String, String)>,
+    // listed with a load policy other than eager, for the gfx runtime to load
+    deferred_assets: Vec<Asset>,
     settings_ref: Arc<Mutex<Vec<u8>>>,
+    readback_ref: Arc<Mutex<Vec<ReadbackRequest>>>,
+    fetch_ref: Arc<Mutex<Vec<String>>>,
+    capture_devices_ref: Arc<AtomicBool>,
+    glob_watch_ref: Arc<Mutex<Vec<String>>>,
+    gfx_info_ref: Arc<Mutex<Vec<u8>>>,
+    // what gfx_info_ref holds, to tell what an update changed
+    shared_gfx_info: Mutex<HashMap<String, GfxInfo>>,
+    gfx_info_generation: Arc<AtomicU64>,
+    frame_deadline: Arc<AtomicU64>,
+    fixed_clock: Arc<AtomicBool>,
+    playback_ref: Arc<Mutex<PlaybackPositions>>,
     store: Arc<Mutex<Store<WasiP1Ctx>>>,
     _module: Module,
     _instance: Instance,
     calc_fn: TypedFunc<(u32, u32, i64, i64), u32>,
     save_settings_fn: TypedFunc<(), ()>,
     restore_settings_fn: TypedFunc<(), ()>,
+    // both missing in guests from before settings were versioned
+    settings_version_fn: Option<TypedFunc<(), u32>>,
+    migrate_settings_fn: Option<TypedFunc<u32, u32>>,
+    settings_schema_fn: Option<TypedFunc<(), u32>>,
+    settings_schema_ref: Arc<Mutex<Option<String>>>,
+    wire: WireFormat,
+    guest_protocol_version: u32,
+    rings: Option<GuestRings>,
+    calc_budget: Duration,
+    _epoch_ticker: EpochTicker,
+    stderr_ref: Arc<Mutex<VecDeque<String>>>,
+    log_ref: Arc<Mutex<Vec<(LogLevel, String)>>>,
+    midi_sink_ref: Arc<Mutex<Option<MidiSink>>>,
 }
 
 impl AppRuntime {
     pub fn load<P: AsRef<Path>>(
         path: P,
-        preopen: P,
+        preopens: &[Preopen],
         cached: Option<&HashMap<Asset, GfxInfo>>,
         frames_per_second: i64,
-        dry_run: bool,
+        calc_budget: Duration,
     ) -> Result<(Self, HashMap<String, GfxData>)> {
         // Define the WASI functions globally on the `Config`.
-        let config = Config::new();
+        let mut config = Config::new();
+        config.epoch_interruption(true);
         let engine = Engine::new(&config)?;
+        let epoch_ticker = EpochTicker::start(&engine);
         let mut linker = Linker::new(&engine);
         p1::add_to_linker_sync(&mut linker, |s| s)?;
 
@@ -150,6 +298,17 @@ impl AppRuntime {
             },
         )?;
 
+        // bumped whenever gfx_info_ref changes after load so the guest re-reads it
+        let gfx_info_generation = Arc::new(AtomicU64::new(0));
+        let guest_gfx_info_generation = gfx_info_generation.clone();
+        linker.func_wrap(
+            "host",
+            "gfx_info_generation",
+            move |_: Caller<'_, WasiP1Ctx>| -> u64 {
+                guest_gfx_info_generation.load(Ordering::SeqCst)
+            },
+        )?;
+
         let reg_events_ref = Arc::new(Mutex::new(Vec::<u8>::new()));
         let guest_reg_events_ref = reg_events_ref.clone();
         linker.func_wrap(
@@ -178,17 +337,254 @@ impl AppRuntime {
             },
         )?;
 
-        let wasi = WasiCtxBuilder::new()
-            .inherit_stdio()
-            .inherit_args()
-            .preopened_dir(preopen, "/tmp/viz", DirPerms::all(), FilePerms::all())
-            .expect("Issue with preopening dir")
-            .build_p1();
+        let readback_ref = Arc::new(Mutex::new(Vec::<ReadbackRequest>::new()));
+        let guest_readback_ref = readback_ref.clone();
+        linker.func_wrap(
+            "host",
+            "request_readback",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32| {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let mut buf = vec![0u8; len as usize];
+                mem.read(&caller, ptr as usize, buf.as_mut_slice()).unwrap();
+                match serde_json::from_slice::<ReadbackRequest>(&buf) {
+                    Ok(req) => guest_readback_ref.lock().unwrap().push(req),
+                    Err(e) => warn!("Bad readback request: {}", e),
+                }
+            },
+        )?;
+
+        // 0 once sent, 1 when there's no such output or sending failed
+        let midi_sink_ref = Arc::new(Mutex::new(None::<MidiSink>));
+        let guest_midi_sink_ref = midi_sink_ref.clone();
+        linker.func_wrap(
+            "host",
+            "send_midi",
+            move |mut caller: Caller<'_, WasiP1Ctx>,
+                  device_ptr: u32,
+                  device_len: u32,
+                  ptr: u32,
+                  len: u32|
+                  -> u32 {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let device = read_guest_str(&mem, &caller, device_ptr, device_len);
+                let mut bytes = vec![0u8; len as usize];
+                mem.read(&caller, ptr as usize, bytes.as_mut_slice())
+                    .unwrap();
+                let sink = guest_midi_sink_ref.lock().unwrap().clone();
+                match sink.map(|sink| sink(&device, &bytes)) {
+                    Some(Ok(_)) => 0,
+                    _ => 1,
+                }
+            },
+        )?;
+
+        let fetch_ref = Arc::new(Mutex::new(Vec::<String>::new()));
+        let guest_fetch_ref = fetch_ref.clone();
+        linker.func_wrap(
+            "host",
+            "request_fetch",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32| {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let url = read_guest_str(&mem, &caller, ptr, len);
+                guest_fetch_ref.lock().unwrap().push(url);
+            },
+        )?;
+
+        // answered with a CaptureDevices event once they're listed
+        let capture_devices_ref = Arc::new(AtomicBool::new(false));
+        let guest_capture_devices_ref = capture_devices_ref.clone();
+        linker.func_wrap(
+            "host",
+            "request_capture_devices",
+            move |_: Caller<'_, WasiP1Ctx>| {
+                guest_capture_devices_ref.store(true, Ordering::SeqCst);
+            },
+        )?;
+
+        // answered with GlobMatched events as files turn up
+        let glob_watch_ref = Arc::new(Mutex::new(Vec::<String>::new()));
+        let guest_glob_watch_ref = glob_watch_ref.clone();
+        linker.func_wrap(
+            "host",
+            "request_glob_watch",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32| {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let pattern = read_guest_str(&mem, &caller, ptr, len);
+                guest_glob_watch_ref.lock().unwrap().push(pattern);
+            },
+        )?;
+
+        let settings_schema_ref = Arc::new(Mutex::new(None));
+        let guest_settings_schema_ref = settings_schema_ref.clone();
+        linker.func_wrap(
+            "host",
+            "send_settings_schema",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32| {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let schema = read_guest_str(&mem, &caller, ptr, len);
+                guest_settings_schema_ref.lock().unwrap().replace(schema);
+            },
+        )?;
+
+        // wall clock ns the frame being calculated has to be presented by
+        let frame_deadline = Arc::new(AtomicU64::new(0));
+        // when set, both clocks read the frame deadline so a replay sees the
+        // same time it did when it was recorded
+        let fixed_clock = Arc::new(AtomicBool::new(false));
+
+        let guest_frame_deadline = frame_deadline.clone();
+        let guest_fixed_clock = fixed_clock.clone();
+        linker.func_wrap(
+            "host",
+            "clock_wall_ns",
+            move |_: Caller<'_, WasiP1Ctx>| -> u64 {
+                if guest_fixed_clock.load(Ordering::SeqCst) {
+                    return guest_frame_deadline.load(Ordering::SeqCst);
+                }
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64
+            },
+        )?;
+
+        let host_start = *HOST_START.get_or_init(Instant::now);
+        let guest_frame_deadline = frame_deadline.clone();
+        let guest_fixed_clock = fixed_clock.clone();
+        linker.func_wrap(
+            "host",
+            "clock_monotonic_ns",
+            move |_: Caller<'_, WasiP1Ctx>| -> u64 {
+                if guest_fixed_clock.load(Ordering::SeqCst) {
+                    return guest_frame_deadline.load(Ordering::SeqCst);
+                }
+                host_start.elapsed().as_nanos() as u64
+            },
+        )?;
+
+        let guest_frame_deadline = frame_deadline.clone();
+        linker.func_wrap(
+            "host",
+            "clock_frame_deadline_ns",
+            move |_: Caller<'_, WasiP1Ctx>| -> u64 { guest_frame_deadline.load(Ordering::SeqCst) },
+        )?;
+
+        // writes (pts, duration) as two f64 at out, 0 when there's no such vid
+        let playback_ref = Arc::new(Mutex::new(PlaybackPositions::default()));
+        let guest_playback_ref = playback_ref.clone();
+        linker.func_wrap(
+            "host",
+            "vid_position",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32, out: u32| -> u32 {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let name = read_guest_str(&mem, &caller, ptr, len);
+                let Some((pts, duration)) =
+                    guest_playback_ref.lock().unwrap().vids.get(&name).copied()
+                else {
+                    return 0;
+                };
+                let mut buf = [0u8; 16];
+                buf[..8].copy_from_slice(&pts.to_le_bytes());
+                buf[8..].copy_from_slice(&duration.to_le_bytes());
+                mem.write(caller, out as usize, &buf).unwrap();
+                1
+            },
+        )?;
+
+        let guest_playback_ref = playback_ref.clone();
+        linker.func_wrap(
+            "host",
+            "mix_present_time",
+            move |mut caller: Caller<'_, WasiP1Ctx>, ptr: u32, len: u32, out: u32| -> u32 {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let name = read_guest_str(&mem, &caller, ptr, len);
+                let Some(present_time) =
+                    guest_playback_ref.lock().unwrap().mixes.get(&name).copied()
+                else {
+                    return 0;
+                };
+                mem.write(caller, out as usize, &present_time.to_le_bytes())
+                    .unwrap();
+                1
+            },
+        )?;
+
+        let stderr_ref = Arc::new(Mutex::new(VecDeque::new()));
+        // logged right away under the app target, and held for the host to
+        // route until taken
+        let log_ref = Arc::new(Mutex::new(Vec::<(LogLevel, String)>::new()));
+        let guest_log_ref = log_ref.clone();
+        linker.func_wrap(
+            "host",
+            "log",
+            move |mut caller: Caller<'_, WasiP1Ctx>, level: u32, ptr: u32, len: u32| {
+                let mem = match caller.get_export("memory") {
+                    Some(Extern::Memory(mem)) => mem,
+                    _ => panic!("failed to get memory"),
+                };
+                let level = LogLevel::from(level);
+                let message = read_guest_str(&mem, &caller, ptr, len);
+                match level {
+                    LogLevel::Debug => debug!(target: "app", "{}", message),
+                    LogLevel::Info => info!(target: "app", "{}", message),
+                    LogLevel::Warn => warn!(target: "app", "{}", message),
+                    LogLevel::Error => error!(target: "app", "{}", message),
+                }
+                guest_log_ref.lock().unwrap().push((level, message));
+            },
+        )?;
+
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.inherit_stdio()
+            .stderr(capture_stderr(stderr_ref.clone())?)
+            .inherit_args();
+        for preopen in preopens {
+            let (dir_perms, file_perms) = match preopen.writable {
+                true => (DirPerms::all(), FilePerms::all()),
+                false => (DirPerms::READ, FilePerms::READ),
+            };
+            if let Err(e) = wasi.preopened_dir(
+                &preopen.host_path,
+                &preopen.guest_path,
+                dir_perms,
+                file_perms,
+            ) {
+                bail!(
+                    "Could not preopen {} as {}: {}",
+                    preopen.host_path.display(),
+                    preopen.guest_path,
+                    e
+                );
+            }
+        }
+        let wasi = wasi.build_p1();
 
         let mut store = Store::<WasiP1Ctx>::new(&engine, wasi);
+        store.set_epoch_deadline(UNBOUNDED_TICKS);
 
         // Instantiate our module with the imports we've created, and run it.
-        let module = Module::from_file(&engine, path)?;
+        let module = load_module(&engine, path.as_ref())?;
         linker.module(&mut store, "", &module)?;
         let instance = linker.instantiate(&mut store, &module)?;
         let calc_fn = instance
@@ -199,11 +595,61 @@ impl AppRuntime {
         let save_settings_fn = instance.get_typed_func::<(), ()>(&mut store, "save_settings")?;
         let restore_settings_fn =
             instance.get_typed_func::<(), ()>(&mut store, "restore_settings")?;
+        let settings_version_fn = instance
+            .get_typed_func::<(), u32>(&mut store, "settings_version")
+            .ok();
+        let migrate_settings_fn = instance
+            .get_typed_func::<u32, u32>(&mut store, "migrate_settings")
+            .ok();
+        let settings_schema_fn = instance
+            .get_typed_func::<(), u32>(&mut store, "settings_schema")
+            .ok();
+
+        // guests from before versioning report 0
+        let guest_protocol_version =
+            match instance.get_typed_func::<(u32, u64), u32>(&mut store, "negotiate_protocol") {
+                Ok(negotiate) => {
+                    negotiate.call(&mut store, (PROTOCOL_VERSION, host_capabilities().0))?
+                }
+                Err(_) => 0,
+            };
+        let Some(preferred) = WireFormat::offered_to(guest_protocol_version) else {
+            bail!(
+                "wasm app speaks protocol {} but this host only {}, it needs a newer sdlrig",
+                guest_protocol_version,
+                PROTOCOL_VERSION
+            );
+        };
+
+        // older guests don't export the handshake and only speak json
+        let wire = match instance.get_typed_func::<u32, u32>(&mut store, "negotiate_wire_format") {
+            Ok(negotiate) => WireFormat::from(negotiate.call(&mut store, preferred as u32)?),
+            Err(_) => WireFormat::Json,
+        };
+
+        // older guests only have the copy through host calls
+        let rings = match (
+            instance.get_typed_func::<u32, u32>(&mut store, "ring_buffers"),
+            instance.get_memory(&mut store, "memory"),
+        ) {
+            (Ok(ring_buffers), Some(memory)) => {
+                let events = ring_buffers.call(&mut store, RING_CAPACITY)? as usize;
+                Some(GuestRings {
+                    memory,
+                    events,
+                    specs: events + ring::region_len(RING_CAPACITY),
+                })
+            }
+            _ => None,
+        };
 
         let asset_ref = Arc::<HashMap<String, Asset>>::new({
             // load in the textures
             match RenderCalcErr::from(asset_list_fn.call(&mut store, (frames_per_second,))? as u8) {
                 RenderCalcErr::None => (),
+                RenderCalcErr::Unsupported => {
+                    bail!("wasm app needs capabilities this host does not provide")
+                }
                 _ => bail!("Got error getting tex list"),
             }
 
@@ -217,21 +663,21 @@ impl AppRuntime {
         let mut loaded_asset_info = HashMap::new();
         let mut gfx_info_map = HashMap::new();
         let mut gfx_data_map = HashMap::new();
+        let mut asset_errors = vec![];
+        let mut deferred_assets = vec![];
         for (name, asset) in asset_ref.iter() {
-            if let Some(Some(info)) = cached.as_ref().map(|c| c.get(asset)) {
+            if asset.load_policy() != LoadPolicy::Eager {
+                deferred_assets.push(asset.clone());
+            } else if let Some(Some(info)) = cached.as_ref().map(|c| c.get(asset)) {
                 gfx_info_map.insert(name.clone(), info.clone());
                 loaded_asset_info.insert(asset.clone(), info.clone());
             } else {
                 let gfx_data = match gfxruntime::load(asset) {
                     Ok(gfx_data) => gfx_data,
                     Err(e) => {
-                        let msg = format!("Error loading in app runtime {:?} {}", asset, e);
-                        if dry_run {
-                            bail!("{}", msg);
-                        } else {
-                            println!("{}", msg);
-                            continue;
-                        }
+                        error!("Error loading in app runtime {:?} {}", asset, e);
+                        asset_errors.push((name.clone(), e.to_string()));
+                        continue;
                     }
                 };
 
@@ -258,13 +704,37 @@ impl AppRuntime {
                 buf_ref,
                 reg_events_ref,
                 settings_ref,
+                readback_ref,
+                fetch_ref,
+                capture_devices_ref,
+                glob_watch_ref,
+                gfx_info_ref,
+                shared_gfx_info: Mutex::new(gfx_info_map),
+                gfx_info_generation,
+                frame_deadline,
+                fixed_clock,
+                playback_ref,
                 loaded_asset_info_ref: Arc::new(loaded_asset_info),
+                asset_errors,
+                deferred_assets,
                 store: Arc::new(Mutex::new(store)),
                 _module: module,
                 _instance: instance,
                 calc_fn,
                 save_settings_fn,
                 restore_settings_fn,
+                settings_version_fn,
+                migrate_settings_fn,
+                settings_schema_fn,
+                settings_schema_ref,
+                wire,
+                guest_protocol_version,
+                rings,
+                calc_budget,
+                _epoch_ticker: epoch_ticker,
+                stderr_ref,
+                log_ref,
+                midi_sink_ref,
             },
             gfx_data_map,
         ))
@@ -278,39 +748,200 @@ impl AppRuntime {
         fps: i64,
         reg_events: &[GfxEvent],
     ) -> Result<Vec<RenderSpec>, Box<dyn Error>> {
+        let mut lock = self.store.lock();
+        let store = lock.as_deref_mut().unwrap();
         {
-            {
-                let Ok(mut reg_lock) = self.reg_events_ref.lock() else {
-                    return Err("Reg events array is poisoned".into());
-                };
+            let events = reg_events
+                .iter()
+                .filter(|e| e.decodable_by(self.guest_protocol_version))
+                .collect::<Vec<_>>();
+            let encoded = self.wire.encode(&events)?;
+            let Ok(mut reg_lock) = self.reg_events_ref.lock() else {
+                return Err("Reg events array is poisoned".into());
+            };
+            reg_lock.clear();
 
-                reg_lock.clear();
-                reg_lock.append(&mut serde_json::to_vec(reg_events)?);
+            // written straight into guest memory when it fits, otherwise the
+            // guest finds the ring empty and asks for the copy
+            let in_ring = match self.rings.as_ref() {
+                Some(rings) => {
+                    let region = Self::ring_region(rings, store, rings.events)?;
+                    ring::push(region, &encoded).is_ok()
+                }
+                None => false,
+            };
+            if !in_ring {
+                reg_lock.extend_from_slice(&encoded);
             }
+        }
 
-            let mut lock = self.store.lock();
-            let store = lock.as_deref_mut().unwrap();
+        // frames are numbered from the epoch, so frame + 1 starts at the deadline
+        let ns_per_frame = 1_000_000_000u64 / fps.max(1) as u64;
+        self.frame_deadline
+            .store((frame + 1).max(0) as u64 * ns_per_frame, Ordering::SeqCst);
 
-            let err = RenderCalcErr::from(
-                self.calc_fn.call(store, (canvas_w, canvas_h, frame, fps))? as u8,
-            );
+        let ticks = (self.calc_budget.as_micros() / EPOCH_TICK.as_micros()).max(1) as u64;
+        store.set_epoch_deadline(ticks);
+        let result = self
+            .calc_fn
+            .call(&mut *store, (canvas_w, canvas_h, frame, fps));
+        store.set_epoch_deadline(UNBOUNDED_TICKS);
+        let err = match result {
+            Ok(err) => RenderCalcErr::from(err as u8),
+            Err(e) if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) => {
+                return Err(Box::new(CalcTimeout {
+                    budget: self.calc_budget,
+                }))
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-            match err {
-                RenderCalcErr::None => (),
-                _ => return Err("Got issue from wasm".into()),
+        match err {
+            RenderCalcErr::None => (),
+            RenderCalcErr::Unsupported => {
+                return Err("wasm app needs capabilities this host does not provide".into())
+            }
+            _ => return Err("Got issue from wasm".into()),
+        }
+
+        if let Some(rings) = self.rings.as_ref() {
+            let region = Self::ring_region(rings, store, rings.specs)?;
+            if let Some(specs) = ring::pop_with(region, |msg| self.wire.decode(msg))? {
+                return specs;
             }
         }
 
         let lock = self.buf_ref.lock().unwrap();
         let specs = lock.as_slice();
 
-        Ok(serde_json::from_slice(specs)?)
+        self.wire.decode(specs)
+    }
+
+    // The guest wrote the capacity, it gets no more than the host asked for
+    // and no more than its memory holds
+    fn ring_region<'a>(
+        rings: &GuestRings,
+        store: &'a mut Store<WasiP1Ctx>,
+        offset: usize,
+    ) -> Result<&'a mut [u8], Box<dyn Error>> {
+        let data = rings.memory.data_mut(store);
+        let end = offset
+            .checked_add(ring::region_len(RING_CAPACITY))
+            .map(|end| end.min(data.len()))
+            .filter(|end| offset + ring::HEADER_LEN <= *end);
+        match end {
+            Some(end) => Ok(&mut data[offset..end]),
+            None => Err(format!("The app's ring at {} is outside its memory", offset).into()),
+        }
+    }
+
+    // Shares the gfx info map with the guest if it differs from what the guest
+    // has, picked up on the next calc. The event says what changed.
+    pub fn update_gfx_info(
+        &self,
+        gfx_info_map: &HashMap<String, GfxInfo>,
+    ) -> Result<Vec<GfxEvent>> {
+        let mut shared = self.shared_gfx_info.lock().unwrap();
+        let mut changed = gfx_info_map
+            .iter()
+            .filter(|(name, info)| shared.get(*name) != Some(info))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let mut removed = shared
+            .keys()
+            .filter(|name| !gfx_info_map.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if changed.is_empty() && removed.is_empty() {
+            return Ok(vec![]);
+        }
+        changed.sort();
+        removed.sort();
+
+        let mut events = vec![];
+        for name in &changed {
+            if let (Some(GfxInfo::VidMixerInfo(old)), Some(GfxInfo::VidMixerInfo(new))) =
+                (shared.get(name), gfx_info_map.get(name))
+            {
+                if (old.width, old.height) != (new.width, new.height) {
+                    events.push(GfxEvent::ResizeEvent(ResizeEvent {
+                        mix: Some(name.clone()),
+                        old: (old.width, old.height),
+                        new: (new.width, new.height),
+                        drawable: (new.width, new.height),
+                    }));
+                }
+            }
+        }
+
+        let serialized = serde_json::to_vec(gfx_info_map)?;
+        *self.gfx_info_ref.lock().unwrap() = serialized;
+        *shared = gfx_info_map.clone();
+        self.gfx_info_generation.fetch_add(1, Ordering::SeqCst);
+        events.insert(0, GfxEvent::GfxInfoEvent(GfxInfoEvent { changed, removed }));
+        Ok(events)
+    }
+
+    // Positions the guest sees from vid_position/mix_present_time, set before calc
+    pub fn update_playback(&self, positions: PlaybackPositions) {
+        *self.playback_ref.lock().unwrap() = positions;
+    }
+
+    pub fn take_readback_requests(&self) -> Vec<ReadbackRequest> {
+        std::mem::take(&mut *self.readback_ref.lock().unwrap())
+    }
+
+    // Makes the guest's clocks follow the frame number instead of real time
+    pub fn set_fixed_clock(&self, fixed: bool) {
+        self.fixed_clock.store(fixed, Ordering::SeqCst);
+    }
+
+    // Messages from the guest's log calls since the last take
+    pub fn take_logs(&self) -> Vec<(LogLevel, String)> {
+        std::mem::take(&mut *self.log_ref.lock().unwrap())
+    }
+
+    // Most recent lines of the guest's stderr, oldest first
+    pub fn guest_stderr(&self) -> Vec<String> {
+        self.stderr_ref.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Where the guest's send_midi calls go, nothing is sent until it's set
+    pub fn set_midi_sink(&self, sink: MidiSink) {
+        *self.midi_sink_ref.lock().unwrap() = Some(sink);
+    }
+
+    pub fn take_fetch_requests(&self) -> Vec<String> {
+        std::mem::take(&mut *self.fetch_ref.lock().unwrap())
+    }
+
+    // Whether the app asked for the capture devices since the last call
+    pub fn take_capture_devices_request(&self) -> bool {
+        self.capture_devices_ref.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn take_glob_watch_requests(&self) -> Vec<String> {
+        std::mem::take(&mut *self.glob_watch_ref.lock().unwrap())
+    }
+
+    pub fn guest_protocol_version(&self) -> u32 {
+        self.guest_protocol_version
     }
 
     pub fn loaded_asset_info(&self) -> Arc<HashMap<Asset, GfxInfo>> {
         self.loaded_asset_info_ref.clone()
     }
 
+    // The assets that failed to load and why, they're left out of gfx info
+    pub fn asset_errors(&self) -> &[(String, String)] {
+        &self.asset_errors
+    }
+
+    // Left out of loading and gfx info, see GfxRuntime::defer
+    pub fn deferred_assets(&self) -> &[Asset] {
+        &self.deferred_assets
+    }
+
     pub fn extract_settings(&self) -> Result<Vec<u8>, Box<dyn Error>> {
         let mut lock = self.store.lock();
         let store = lock.as_deref_mut().unwrap();
@@ -324,6 +955,62 @@ impl AppRuntime {
         Ok(settings_lock.unwrap().to_vec())
     }
 
+    pub fn settings_version(&self) -> Result<u32, Box<dyn Error>> {
+        let Some(settings_version_fn) = self.settings_version_fn.as_ref() else {
+            return Ok(0);
+        };
+        let mut lock = self.store.lock();
+        let store = lock.as_deref_mut().unwrap();
+        Ok(settings_version_fn.call(store, ())?)
+    }
+
+    // JSON schema describing the app's settings, None when the app doesn't
+    // publish one
+    pub fn settings_schema(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(settings_schema_fn) = self.settings_schema_fn.as_ref() else {
+            return Ok(None);
+        };
+        self.settings_schema_ref.lock().unwrap().take();
+        let mut lock = self.store.lock();
+        let store = lock.as_deref_mut().unwrap();
+        match settings_schema_fn.call(store, ())? {
+            0 => Ok(self.settings_schema_ref.lock().unwrap().take()),
+            _ => Ok(None),
+        }
+    }
+
+    // Imports settings saved by a module at from_version, migrating them
+    // through the guest when the version changed
+    pub fn migrate_settings(&self, bytes: &[u8], from_version: u32) -> Result<(), Box<dyn Error>> {
+        let version = self.settings_version()?;
+        if version == from_version {
+            return self.import_settings(bytes);
+        }
+        let Some(migrate_settings_fn) = self.migrate_settings_fn.as_ref() else {
+            return Err(format!(
+                "Settings went from version {} to {} and the app can't migrate them",
+                from_version, version
+            )
+            .into());
+        };
+        {
+            let mut lock = self.settings_ref.lock();
+            let settings = lock.as_deref_mut().unwrap();
+            settings.clear();
+            settings.extend_from_slice(bytes);
+        }
+        let mut lock = self.store.lock();
+        let store = lock.as_deref_mut().unwrap();
+        match migrate_settings_fn.call(store, from_version)? {
+            0 => Ok(()),
+            _ => Err(format!(
+                "App declined to migrate settings from version {} to {}",
+                from_version, version
+            )
+            .into()),
+        }
+    }
+
     pub fn import_settings(&self, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
         {
             let mut lock = self.settings_ref.lock();
@@ -339,4 +1026,145 @@ impl AppRuntime {
             _ => Ok(()),
         }
     }
+
+    // The file holds the settings version as a u32 LE followed by the settings
+    pub fn save_settings_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut bytes = self.settings_version()?.to_le_bytes().to_vec();
+        bytes.extend(self.extract_settings()?);
+        // a crash mid-write leaves the previous file intact
+        let partial = path.as_ref().with_extension("partial");
+        fs::write(&partial, &bytes)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn load_settings_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 4 {
+            return Err("Settings file is truncated".into());
+        }
+        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        self.migrate_settings(&bytes[4..], version)
+    }
+}
+
+// Drives the engine's epoch until dropped
+struct EpochTicker(Arc<AtomicBool>);
+
+impl EpochTicker {
+    fn start(engine: &Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let engine = engine.clone();
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self(stop)
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// Reuses the compiled module from a previous load of the same wasm so a hot
+// reload is mostly instantiation. Each file starts with the sha256 of the wasm
+// and engine it was compiled for, then the sha256 of the module after it, and
+// both have to match before it's deserialized.
+fn load_module(engine: &Engine, path: &Path) -> Result<Module> {
+    let cache_dir = assetcache::cache_dir().join(MODULE_CACHE_DIR);
+    let wasm = fs::read(path)?;
+    // the engine's settings as the bytes its Hash impl feeds a hasher
+    let mut keyed = HashBytes(wasm.clone());
+    engine.precompile_compatibility_hash().hash(&mut keyed);
+    let key = assetcache::sha256(&keyed.0);
+    let hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    let cached = cache_dir.join(format!("{}.cwasm", hex));
+    if let Ok(file) = fs::read(&cached) {
+        let verified = (file.len() >= 64 && file[..32] == key)
+            .then(|| &file[64..])
+            .filter(|module| assetcache::sha256(module)[..] == file[32..64]);
+        match verified {
+            // compiled below by an engine with the same settings, in a
+            // directory apps can't reach
+            Some(module) => match unsafe { Module::deserialize(engine, module) } {
+                Ok(module) => return Ok(module),
+                Err(e) => warn!("Ignoring cached module {}: {}", cached.display(), e),
+            },
+            None => warn!(
+                "Ignoring cached module {}: digest mismatch",
+                cached.display()
+            ),
+        }
+    }
+
+    let module = Module::new(engine, &wasm)?;
+    let serialized = module.serialize()?;
+    let mut file = key.to_vec();
+    file.extend(assetcache::sha256(&serialized));
+    file.extend(serialized);
+    // written aside and renamed so a concurrent load never sees half a file
+    let partial = cached.with_extension("partial");
+    let stored = fs::create_dir_all(&cache_dir)
+        .and_then(|_| fs::write(&partial, &file))
+        .and_then(|_| fs::rename(&partial, &cached));
+    if let Err(e) = stored {
+        warn!(
+            "Could not cache compiled module in {}: {}",
+            cache_dir.display(),
+            e
+        );
+    }
+    Ok(module)
+}
+
+// A Hasher that keeps what it's fed rather than mixing it down to 64 bits
+struct HashBytes(Vec<u8>);
+
+impl Hasher for HashBytes {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        0
+    }
+}
+
+// Passes the guest's stderr on to ours a line at a time, keeping the tail.
+// The thread ends when the store drops the write end.
+fn capture_stderr(tail: Arc<Mutex<VecDeque<String>>>) -> Result<OutputFile> {
+    let Ok((reader, writer)) = std::io::pipe() else {
+        bail!("Could not create a pipe for the guest's stderr");
+    };
+    // wasi wants a file, which either end of a pipe can be
+    #[cfg(unix)]
+    let writer = File::from(std::os::fd::OwnedFd::from(writer));
+    #[cfg(windows)]
+    let writer = File::from(std::os::windows::io::OwnedHandle::from(writer));
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            eprintln!("{}", line);
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == STDERR_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    });
+    Ok(OutputFile::new(writer))
+}
+
+fn read_guest_str(mem: &Memory, caller: &Caller<'_, WasiP1Ctx>, ptr: u32, len: u32) -> String {
+    let mut buf = vec![0u8; len as usize];
+    mem.read(caller, ptr as usize, buf.as_mut_slice()).unwrap();
+    String::from_utf8_lossy(&buf).into_owned()
 }
@@ -0,0 +1,232 @@
+use crate::gfxinfo::Asset;
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// between reads, a whole download can take as long as it needs
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref CACHE: RwLock<AssetCache> = RwLock::new(AssetCache::default());
+    // viz --proxy, the variant label loaded where an asset has one
+    static ref PROXY: RwLock<Option<String>> = RwLock::new(None);
+}
+
+// Names temporary downloads so loads of the same url don't collide
+static NEXT_DOWNLOAD: AtomicU64 = AtomicU64::new(0);
+
+// Where assets with http(s) paths are downloaded to before loading. Files are
+// named by the sha256 of their contents, and a url ending #sha256=<hex> has
+// to match it. Offline only loads what was downloaded already.
+#[derive(Clone, Debug)]
+pub struct AssetCache {
+    pub dir: PathBuf,
+    pub offline: bool,
+}
+
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self {
+            dir: cache_dir().join("assets"),
+            offline: false,
+        }
+    }
+}
+
+// The host's own cache directory, never preopened for an app
+pub fn cache_dir() -> PathBuf {
+    // LOCALAPPDATA is windows' own cache directory
+    let base = env::var_os("XDG_CACHE_HOME")
+        .or_else(|| env::var_os("LOCALAPPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("sdlrig")
+}
+
+// Used by every load after it
+pub fn configure(cache: AssetCache) {
+    *CACHE.write().unwrap() = cache;
+}
+
+// Applies from the next load, assets already loaded stay as they are
+pub fn set_proxy(proxy: Option<String>) {
+    *PROXY.write().unwrap() = proxy;
+}
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// The asset with the --proxy variant's path, and a url swapped for the cached
+// file, downloading it first if it has to. Anything else comes back as it was.
+pub fn localize(asset: &Asset) -> Result<Asset> {
+    let mut asset = asset.clone();
+    let target = match &mut asset {
+        Asset::Vid(v) => Some((&mut v.path, v.variants.as_slice())),
+        Asset::Tex(t) => Some((&mut t.path, t.variants.as_slice())),
+        Asset::Audio(a) => Some((&mut a.path, &[][..])),
+        Asset::Font(f) => Some((&mut f.path, &[][..])),
+        Asset::Shader(s) => Some((&mut s.path, &[][..])),
+        Asset::Lut(l) => Some((&mut l.path, &[][..])),
+        Asset::Mesh(m) => Some((&mut m.path, &[][..])),
+        Asset::Missing | Asset::VidMixer(_) | Asset::Plugin(_) => None,
+    };
+    let Some((path, variants)) = target else {
+        return Ok(asset);
+    };
+    if let Some(proxy) = PROXY.read().unwrap().as_ref() {
+        if let Some((_, variant)) = variants.iter().find(|(label, _)| label == proxy) {
+            *path = variant.clone();
+        }
+    }
+    if is_url(path) {
+        let cache = CACHE.read().unwrap().clone();
+        *path = cache.fetch(path)?.to_string_lossy().into_owned();
+    }
+    Ok(asset)
+}
+
+impl AssetCache {
+    pub fn fetch(&self, url: &str) -> Result<PathBuf> {
+        let (url, expected) = match url.split_once("#sha256=") {
+            Some((url, hex)) => (url, Some(hex.to_ascii_lowercase())),
+            None => (url, None),
+        };
+        if let Some(hex) = expected.as_ref() {
+            if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("{} is not a sha256", hex);
+            }
+        }
+        let ext = extension(url);
+        let index = self.dir.join("urls").join(to_hex(&sha256(url.as_bytes())));
+
+        let cached = match expected.as_ref() {
+            Some(hex) => Some(format!("{}{}", hex, ext)),
+            None => fs::read_to_string(&index).ok(),
+        };
+        if let Some(file) = cached {
+            let path = self.dir.join(file.trim());
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+        if self.offline {
+            bail!("{} isn't cached and downloads are off", url);
+        }
+
+        fs::create_dir_all(self.dir.join("urls"))?;
+        let partial = self.dir.join(format!(
+            ".download-{}-{}",
+            std::process::id(),
+            NEXT_DOWNLOAD.fetch_add(1, Ordering::Relaxed)
+        ));
+        let hex = match download(url, &partial) {
+            Ok(hex) => hex,
+            Err(e) => {
+                fs::remove_file(&partial).ok();
+                return Err(e);
+            }
+        };
+        if let Some(expected) = expected.filter(|expected| *expected != hex) {
+            fs::remove_file(&partial).ok();
+            bail!("{} has sha256 {}, expected {}", url, hex, expected);
+        }
+        let file = format!("{}{}", hex, ext);
+        let path = self.dir.join(&file);
+        fs::rename(&partial, &path)?;
+        fs::write(&index, &file)?;
+        Ok(path)
+    }
+}
+
+// Streams url to path, returning the hex sha256 of what was written
+fn download(url: &str, path: &Path) -> Result<String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| anyhow!("Could not download {}: {}", url, e))?;
+    let mut reader = response.into_reader();
+    let mut file = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read])?;
+    }
+    file.sync_all()?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// Hex sha256 of a file, read a block at a time
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// Kept on the cached file, ffmpeg and the LUT parser go by it
+fn extension(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((_, ext)) if !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            format!(".{}", ext.to_ascii_lowercase())
+        }
+        _ => String::new(),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_hex() {
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension("https://a.com/x/Clip.MP4?v=1#sha256=ab"), ".mp4");
+        assert_eq!(extension("https://a.com/x/lut.cube"), ".cube");
+        assert_eq!(extension("https://a.com/x/noext"), "");
+        assert_eq!(extension("https://a.com/x.y/z"), "");
+        assert_eq!(extension("https://a.com/x/a.b-c"), "");
+    }
+}
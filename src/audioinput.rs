@@ -0,0 +1,215 @@
+use crate::gfxinfo::{AudioAnalysisEvent, AUDIO_BANDS};
+use anyhow::{anyhow, Result};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Samples the spectrum is taken over, a power of two
+const FFT_SIZE: usize = 1024;
+// The lowest band starts here, the highest ends at nyquist
+const LOWEST_HZ: f32 = 40.0;
+// Spectral flux has to beat its recent average by this much to be an onset
+const ONSET_RATIO: f32 = 1.5;
+// and this much outright, so silence with a little noise isn't all onsets
+const ONSET_MIN_FLUX: f32 = 0.01;
+// Analyses the flux average is taken over
+const FLUX_HISTORY: usize = 32;
+// Seconds after an onset before the next can be detected
+const ONSET_HOLDOFF: f32 = 0.1;
+
+struct Capture {
+    fresh: Arc<Mutex<Vec<f32>>>,
+}
+
+impl AudioCallback for Capture {
+    type Channel = f32;
+
+    fn callback(&mut self, input: &mut [f32]) {
+        if let Ok(mut fresh) = self.fresh.lock() {
+            fresh.extend_from_slice(input);
+        }
+    }
+}
+
+// Captures an input device for --audio-in and analyses what arrived since the
+// last frame: its level, a log spaced spectrum, and onsets from spectral flux
+pub struct AudioInput {
+    _device: AudioDevice<Capture>,
+    fresh: Arc<Mutex<Vec<f32>>>,
+    rate: f32,
+    // the last FFT_SIZE samples, oldest first
+    window: VecDeque<f32>,
+    spectrum: Vec<f32>,
+    flux: VecDeque<f32>,
+    // seconds since the last onset
+    since_onset: f32,
+    last: AudioAnalysisEvent,
+}
+
+impl AudioInput {
+    // "default" is the system's default input
+    pub fn open(audio: &AudioSubsystem, device: &str) -> Result<Self> {
+        let fresh = Arc::new(Mutex::new(vec![]));
+        let desired = AudioSpecDesired {
+            freq: Some(crate::audioruntime::SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: Some(512),
+        };
+        let name = (device != "default").then_some(device);
+        let mut rate = 0;
+        let capture = audio
+            .open_capture(name, &desired, |spec| {
+                rate = spec.freq;
+                Capture {
+                    fresh: fresh.clone(),
+                }
+            })
+            .map_err(|e| {
+                let devices = (0..audio.num_audio_capture_devices().unwrap_or(0))
+                    .filter_map(|i| audio.audio_capture_device_name(i).ok())
+                    .collect::<Vec<_>>();
+                anyhow!(
+                    "Could not open audio input {}: {}, inputs are {:?}",
+                    device,
+                    e,
+                    devices
+                )
+            })?;
+        capture.resume();
+        Ok(Self {
+            _device: capture,
+            fresh,
+            rate: rate.max(1) as f32,
+            window: VecDeque::with_capacity(FFT_SIZE),
+            spectrum: vec![0.0; FFT_SIZE / 2],
+            flux: VecDeque::with_capacity(FLUX_HISTORY),
+            since_onset: ONSET_HOLDOFF,
+            last: AudioAnalysisEvent::default(),
+        })
+    }
+
+    // Call once a frame. Frames the device delivered nothing on repeat the
+    // last analysis without an onset.
+    pub fn analyze(&mut self, frame: i64) -> AudioAnalysisEvent {
+        let fresh = std::mem::take(&mut *self.fresh.lock().unwrap());
+        if fresh.is_empty() {
+            return AudioAnalysisEvent {
+                frame,
+                onset: false,
+                ..self.last.clone()
+            };
+        }
+        let rms = (fresh.iter().map(|s| s * s).sum::<f32>() / fresh.len() as f32).sqrt();
+        self.window.extend(fresh.iter());
+        while self.window.len() > FFT_SIZE {
+            self.window.pop_front();
+        }
+        self.since_onset += fresh.len() as f32 / self.rate;
+
+        let spectrum = magnitudes(&self.window);
+        let flux = spectrum
+            .iter()
+            .zip(&self.spectrum)
+            .map(|(now, before)| (now - before).max(0.0))
+            .sum::<f32>()
+            / spectrum.len() as f32;
+        self.spectrum = spectrum;
+        let average = match self.flux.is_empty() {
+            true => f32::MAX,
+            false => self.flux.iter().sum::<f32>() / self.flux.len() as f32,
+        };
+        if self.flux.len() == FLUX_HISTORY {
+            self.flux.pop_front();
+        }
+        self.flux.push_back(flux);
+        let onset = flux > ONSET_MIN_FLUX
+            && flux > average * ONSET_RATIO
+            && self.since_onset >= ONSET_HOLDOFF;
+        if onset {
+            self.since_onset = 0.0;
+        }
+
+        self.last = AudioAnalysisEvent {
+            frame,
+            rms_micros: micros(rms),
+            band_micros: self.bands().into_iter().map(micros).collect(),
+            onset,
+        };
+        self.last.clone()
+    }
+
+    // Mean magnitude of the bins in each log spaced band
+    fn bands(&self) -> Vec<f32> {
+        let nyquist = self.rate / 2.0;
+        let bin_hz = self.rate / FFT_SIZE as f32;
+        let edge = |band: usize| {
+            let hz = LOWEST_HZ * (nyquist / LOWEST_HZ).powf(band as f32 / AUDIO_BANDS as f32);
+            ((hz / bin_hz) as usize).min(self.spectrum.len())
+        };
+        (0..AUDIO_BANDS)
+            .map(|band| {
+                let lo = edge(band).min(self.spectrum.len() - 1);
+                let hi = edge(band + 1).max(lo + 1);
+                let bins = &self.spectrum[lo..hi];
+                bins.iter().sum::<f32>() / bins.len() as f32
+            })
+            .collect()
+    }
+}
+
+fn micros(value: f32) -> i64 {
+    (value.clamp(0.0, 1.0) as f64 * 1e6).round() as i64
+}
+
+// Hann windowed magnitudes of the first FFT_SIZE / 2 bins, scaled so a full
+// scale sine peaks near 1. Short windows are zero padded.
+fn magnitudes(samples: &VecDeque<f32>) -> Vec<f32> {
+    let mut re = vec![0.0f32; FFT_SIZE];
+    let mut im = vec![0.0f32; FFT_SIZE];
+    for (i, sample) in samples.iter().enumerate().take(FFT_SIZE) {
+        let hann = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos();
+        re[i] = sample * hann;
+    }
+    fft(&mut re, &mut im);
+    re.iter()
+        .zip(&im)
+        .take(FFT_SIZE / 2)
+        .map(|(re, im)| (re * re + im * im).sqrt() * 4.0 / FFT_SIZE as f32)
+        .collect()
+}
+
+// In place radix 2, len must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
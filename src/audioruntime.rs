@@ -0,0 +1,259 @@
+use crate::gfxinfo::{Audio, AudioInfo};
+use crate::renderspec::{AudioGain, AudioPlay, AudioStop};
+use anyhow::{anyhow, Result};
+use ffmpeg_next::{
+    codec, decoder,
+    format::{self, sample::Type, Sample},
+    frame, media, ChannelLayout,
+};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tracing::{error, warn};
+
+// Everything is decoded to interleaved stereo f32 at this rate up front so the
+// callback only has to sum
+pub const SAMPLE_RATE: u32 = 48000;
+const CHANNELS: u8 = 2;
+
+// An Audio asset, decoded when it was loaded
+#[derive(Debug)]
+pub struct AudioData {
+    pub info: AudioInfo,
+    pub samples: Arc<Vec<f32>>,
+}
+
+impl AudioData {
+    pub fn load(audio: &Audio) -> Result<Self> {
+        let (samples, sample_rate, channels) = decode_source(&audio.path)?;
+        let frames = (samples.len() / CHANNELS as usize) as u64;
+        Ok(Self {
+            info: AudioInfo {
+                name: audio.name.clone(),
+                path: audio.path.clone(),
+                duration_ms: frames * 1000 / SAMPLE_RATE as u64,
+                sample_rate,
+                channels,
+                load: audio.load.clone(),
+            },
+            samples: Arc::new(samples),
+        })
+    }
+}
+
+struct Voice {
+    name: String,
+    samples: Arc<Vec<f32>>,
+    pos: usize,
+    gain: f32,
+    looping: bool,
+}
+
+struct Mixer {
+    voices: Arc<Mutex<Vec<Voice>>>,
+}
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let Ok(mut voices) = self.voices.lock() else {
+            return;
+        };
+        for voice in voices.iter_mut() {
+            for sample in out.iter_mut() {
+                if voice.pos >= voice.samples.len() {
+                    if !voice.looping || voice.samples.is_empty() {
+                        break;
+                    }
+                    voice.pos = 0;
+                }
+                *sample += voice.samples[voice.pos] * voice.gain;
+                voice.pos += 1;
+            }
+        }
+        voices.retain(|v| v.looping || v.pos < v.samples.len());
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+// Plays sound files for AudioPlay/AudioStop/AudioGain specs. Files are
+// decoded off the render thread the first time they're played and kept.
+pub struct AudioRuntime {
+    _device: AudioDevice<Mixer>,
+    voices: Arc<Mutex<Vec<Voice>>>,
+    clips: HashMap<String, Arc<Vec<f32>>>,
+    // decodes in flight and the plays waiting on them
+    pending: HashMap<String, (JoinHandle<Result<Vec<f32>>>, Vec<AudioPlay>)>,
+}
+
+impl AudioRuntime {
+    pub fn new(audio: &AudioSubsystem) -> Result<Self> {
+        let voices = Arc::new(Mutex::new(vec![]));
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(CHANNELS),
+            samples: Some(1024),
+        };
+        let device = audio
+            .open_playback(None, &desired, |spec| {
+                if spec.freq != SAMPLE_RATE as i32 || spec.channels != CHANNELS {
+                    warn!(
+                        "Audio device wants {}Hz x{}, playback will be off pitch",
+                        spec.freq, spec.channels
+                    );
+                }
+                Mixer {
+                    voices: voices.clone(),
+                }
+            })
+            .map_err(|e| anyhow!("Could not open audio output: {}", e))?;
+        device.resume();
+        Ok(Self {
+            _device: device,
+            voices,
+            clips: HashMap::new(),
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn play(&mut self, play: &AudioPlay) {
+        if let Some(samples) = self.clips.get(&play.name) {
+            self.start(play, samples.clone());
+            return;
+        }
+        if let Some((_, waiting)) = self.pending.get_mut(&play.name) {
+            waiting.push(play.clone());
+            return;
+        }
+        let path = play.name.clone();
+        self.pending.insert(
+            play.name.clone(),
+            (thread::spawn(move || decode(&path)), vec![play.clone()]),
+        );
+    }
+
+    // Plays an Audio asset's samples, which were decoded when it loaded
+    pub fn play_loaded(&mut self, play: &AudioPlay, samples: Arc<Vec<f32>>) {
+        self.start(play, samples);
+    }
+
+    // None stops everything
+    pub fn stop(&mut self, stop: &AudioStop) {
+        if let Some(name) = &stop.name {
+            if let Some((_, waiting)) = self.pending.get_mut(name) {
+                waiting.clear();
+            }
+        } else {
+            self.pending.values_mut().for_each(|(_, w)| w.clear());
+        }
+        if let Ok(mut voices) = self.voices.lock() {
+            voices.retain(|v| stop.name.as_ref().map_or(false, |n| *n != v.name));
+        }
+    }
+
+    pub fn gain(&mut self, gain: &AudioGain) {
+        if let Ok(mut voices) = self.voices.lock() {
+            voices
+                .iter_mut()
+                .filter(|v| v.name == gain.name)
+                .for_each(|v| v.gain = gain.gain);
+        }
+    }
+
+    // Decodes still running
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Starts plays whose decode finished, call once per frame
+    pub fn poll(&mut self) {
+        let done = self
+            .pending
+            .iter()
+            .filter(|(_, (handle, _))| handle.is_finished())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in done {
+            let Some((handle, waiting)) = self.pending.remove(&name) else {
+                continue;
+            };
+            match handle.join() {
+                Ok(Ok(samples)) => {
+                    let samples = Arc::new(samples);
+                    self.clips.insert(name, samples.clone());
+                    for play in waiting {
+                        self.start(&play, samples.clone());
+                    }
+                }
+                Ok(Err(e)) => warn!("Could not decode audio {}: {}", name, e),
+                Err(_) => error!("Audio decode of {} panicked", name),
+            }
+        }
+    }
+
+    fn start(&mut self, play: &AudioPlay, samples: Arc<Vec<f32>>) {
+        if let Ok(mut voices) = self.voices.lock() {
+            voices.push(Voice {
+                name: play.name.clone(),
+                samples,
+                pos: 0,
+                gain: play.gain,
+                looping: play.loop_,
+            });
+        }
+    }
+}
+
+pub fn decode(path: &str) -> Result<Vec<f32>> {
+    decode_source(path).map(|(samples, _, _)| samples)
+}
+
+// Also the file's own sample rate and channel count
+fn decode_source(path: &str) -> Result<(Vec<f32>, u32, u16)> {
+    let mut ictx = format::input(&path)?;
+    let stream = ictx
+        .streams()
+        .best(media::Type::Audio)
+        .ok_or_else(|| anyhow!("No audio stream in {}", path))?;
+    let index = stream.index();
+    let context = codec::context::Context::from_parameters(stream.parameters())?;
+    let mut decoder = context.decoder().audio()?;
+    let (sample_rate, channels) = (decoder.rate(), decoder.channels());
+    let mut resampler = decoder.resampler(
+        Sample::F32(Type::Packed),
+        ChannelLayout::STEREO,
+        SAMPLE_RATE,
+    )?;
+
+    let mut samples = vec![];
+    let mut decoded = frame::Audio::empty();
+    let mut resampled = frame::Audio::empty();
+    let mut drain = |decoder: &mut decoder::Audio, samples: &mut Vec<f32>| -> Result<()> {
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            resampler.run(&decoded, &mut resampled)?;
+            let len = resampled.samples() * CHANNELS as usize * 4;
+            samples.extend(
+                resampled.data(0)[..len]
+                    .chunks_exact(4)
+                    .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+            );
+        }
+        Ok(())
+    };
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        drain(&mut decoder, &mut samples)?;
+    }
+    decoder.send_eof()?;
+    drain(&mut decoder, &mut samples)?;
+    Ok((samples, sample_rate, channels))
+}
@@ -0,0 +1,201 @@
+use crate::perf::FrameStats;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
+
+// --benchmark, parsed from frames=N with optional ,report=path and ,headless
+#[derive(Clone, Debug)]
+pub struct BenchmarkSpec {
+    pub frames: usize,
+    // stdout without one
+    pub report: Option<PathBuf>,
+    // hides the output window and logs the HUD instead of opening it
+    pub headless: bool,
+}
+
+impl FromStr for BenchmarkSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut frames = None;
+        let mut report = None;
+        let mut headless = false;
+        for part in s.split(',') {
+            match part.split_once('=') {
+                Some(("frames", n)) => frames = Some(n.parse()?),
+                Some(("report", path)) => report = Some(PathBuf::from(path)),
+                None if part == "headless" => headless = true,
+                _ => bail!("Unknown benchmark option {}", part),
+            }
+        }
+        match frames {
+            Some(frames) if frames > 0 => Ok(Self {
+                frames,
+                report,
+                headless,
+            }),
+            _ => bail!("A benchmark needs frames=N, got {}", s),
+        }
+    }
+}
+
+// Milliseconds over the frames something ran on
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Timing {
+    pub frames: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Timing {
+    fn new(mut us: Vec<u64>) -> Self {
+        if us.is_empty() {
+            return Self::default();
+        }
+        us.sort_unstable();
+        let ms = |us: u64| us as f64 / 1000.0;
+        let at = |p: f64| ms(us[((us.len() - 1) as f64 * p).round() as usize]);
+        Self {
+            frames: us.len(),
+            mean_ms: ms(us.iter().sum::<u64>()) / us.len() as f64,
+            p50_ms: at(0.5),
+            p95_ms: at(0.95),
+            p99_ms: at(0.99),
+            max_ms: ms(*us.last().unwrap()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub wasm: String,
+    pub fps: i64,
+    pub frames: usize,
+    pub secs: f64,
+    // how fast it went unpaced, compare to fps
+    pub achieved_fps: f64,
+    pub dropped: i64,
+    pub frame: Timing,
+    pub calc: Timing,
+    pub render: Timing,
+    pub present: Timing,
+    pub mixes: BTreeMap<String, Timing>,
+    pub decodes: BTreeMap<String, Timing>,
+    // the process's high water mark, none where it can't be read
+    pub peak_rss_bytes: Option<u64>,
+}
+
+// Collects the stats of every frame from the first the app rendered
+pub struct Benchmark {
+    pub spec: BenchmarkSpec,
+    started: Option<Instant>,
+    frames: Vec<FrameStats>,
+}
+
+impl Benchmark {
+    pub fn new(spec: BenchmarkSpec) -> Self {
+        Self {
+            spec,
+            started: None,
+            frames: vec![],
+        }
+    }
+
+    // True once it has all the frames it wanted
+    pub fn frame(&mut self, stats: &FrameStats) -> bool {
+        self.started.get_or_insert_with(Instant::now);
+        self.frames.push(stats.clone());
+        self.frames.len() >= self.spec.frames
+    }
+
+    pub fn report(&self, wasm: &str, fps: i64) -> BenchmarkReport {
+        let secs = self
+            .started
+            .map_or(0.0, |started| started.elapsed().as_secs_f64());
+        let each = |f: fn(&FrameStats) -> u64| Timing::new(self.frames.iter().map(f).collect());
+        let by_name = |f: fn(&FrameStats) -> &Vec<(String, u64)>| {
+            let mut named = BTreeMap::<String, Vec<u64>>::new();
+            for stats in &self.frames {
+                for (name, us) in f(stats) {
+                    named.entry(name.clone()).or_default().push(*us);
+                }
+            }
+            named
+                .into_iter()
+                .map(|(name, us)| (name, Timing::new(us)))
+                .collect()
+        };
+        BenchmarkReport {
+            wasm: wasm.into(),
+            fps,
+            frames: self.frames.len(),
+            secs,
+            achieved_fps: match secs > 0.0 {
+                true => self.frames.len() as f64 / secs,
+                false => 0.0,
+            },
+            dropped: self.frames.iter().map(|stats| stats.dropped).sum(),
+            frame: each(|stats| stats.calc_us + stats.render_us + stats.present_us),
+            calc: each(|stats| stats.calc_us),
+            render: each(|stats| stats.render_us),
+            present: each(|stats| stats.present_us),
+            mixes: by_name(|stats| &stats.mixes),
+            decodes: by_name(|stats| &stats.decodes),
+            peak_rss_bytes: peak_rss_bytes(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn peak_rss_bytes() -> Option<u64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let max = usage.ru_maxrss.max(0) as u64;
+    // bytes on macos, kilobytes everywhere else
+    match cfg!(target_os = "macos") {
+        true => Some(max),
+        false => Some(max * 1024),
+    }
+}
+
+// The peak working set, which is the closest windows has
+#[cfg(windows)]
+fn peak_rss_bytes() -> Option<u64> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut std::ffi::c_void;
+        fn K32GetProcessMemoryInfo(
+            process: *mut std::ffi::c_void,
+            counters: *mut ProcessMemoryCounters,
+            cb: u32,
+        ) -> i32;
+    }
+    let mut counters = ProcessMemoryCounters {
+        cb: std::mem::size_of::<ProcessMemoryCounters>() as u32,
+        ..Default::default()
+    };
+    let ok = unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) };
+    (ok != 0).then_some(counters.peak_working_set_size as u64)
+}
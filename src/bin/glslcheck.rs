@@ -43,7 +43,7 @@ fn main() -> anyhow::Result<()> {
 
     let lowlevel_ctx = unsafe {
         let raw_window = window.raw();
-        let ctx = gfx_lowlevel_gpu_ctx_init(raw_window as *mut _);
+        let ctx = gfx_lowlevel_gpu_ctx_init(raw_window as *mut _, std::ptr::null());
         if ctx.is_null() {
             panic!("Failed to initialize lowlevel_ctx");
         }
@@ -108,6 +108,8 @@ fn main() -> anyhow::Result<()> {
         frame,
         &inputs,
         None,
+        None,
+        None,
         lowlevel_ctx,
         false,
         true,
@@ -1,31 +1,174 @@
 use chrono::Local;
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, Parser, ValueEnum};
 use ffmpeg_next::log::set_level;
 use lazy_static::lazy_static;
-use midir::{Ignore, MidiInput, MidiOutput};
-use sdl2::event::{Event, WindowEvent};
+use sdl2::event::{DisplayEvent, Event, WindowEvent};
 use sdl2::keyboard::{Keycode, Mod};
-use sdlrig::appruntime::AppRuntime;
-use sdlrig::gfxinfo::{GfxEvent, KeyEvent, LogEvent, MidiEvent};
-use sdlrig::gfxruntime::{GfxData, GfxRuntime};
-use sdlrig::renderspec::RenderSpec;
+use sdl2::video::{Window, WindowPos};
+use sdl2::VideoSubsystem;
+use sdlrig::appruntime::{AppRuntime, CalcTimeout, MidiSink, Preopen};
+use sdlrig::assetcache::{self, AssetCache};
+use sdlrig::audioinput::AudioInput;
+use sdlrig::audioruntime::AudioRuntime;
+use sdlrig::benchmark::{Benchmark, BenchmarkSpec};
+use sdlrig::capture::{self, CaptureLister};
+use sdlrig::config::config_args;
+use sdlrig::controllers::Controllers;
+use sdlrig::fetch::Fetcher;
+use sdlrig::gfxinfo::{
+    FileChangeEvent, FileChangeKind, FileDropEvent, FramePacingEvent, GfxEvent, KeyEvent, LogEvent,
+    LogLevel, ResizeEvent, SettingChange, SpecErrorEvent, Vid,
+};
+use sdlrig::gfxruntime::{downsample_rgba, GfxData, GfxRuntime};
+use sdlrig::hud::Hud;
+use sdlrig::lint::{LintReport, Stage};
+use sdlrig::logging::{LogFilter, Logger};
+use sdlrig::manifest::{self, Manifest};
+#[cfg(feature = "midi")]
+use sdlrig::midiports::MidiPorts;
+#[cfg(feature = "osc")]
+use sdlrig::osc::OscOut;
+use sdlrig::outputshare::{OutputShare, ShareBackend};
+use sdlrig::perf::Perf;
+use sdlrig::placement::{move_to_display, show_on_display, toggle_fullscreen, Placement};
+use sdlrig::plugins;
+use sdlrig::pointer::pointer_event;
+use sdlrig::remote::{Remote, RemoteCall, RemoteReply, RemoteRequest};
+use sdlrig::renderspec::{sort_layers, RenderSpec, TextSpan, WindowCtl};
+use sdlrig::tempo::Tempo;
+use sdlrig::texruntime;
+use sdlrig::vidruntime::FrameDownload;
+use sdlrig::watch::{change_kind, guest_path, DirWatcher, GlobWatcher, WatchRule};
 use std::collections::{HashMap, HashSet};
+use std::ffi::{c_char, CStr, CString, OsString};
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{fs, thread};
+use tracing::{error, info, warn};
 
 use sdlrig::gfx_lowlevel::bindings::{
+    gfx_lowlevel_colorspace, gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_DISPLAY_P3,
+    gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_HDR_PQ, gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_SRGB,
     gfx_lowlevel_gpu_ctx, gfx_lowlevel_gpu_ctx_destroy, gfx_lowlevel_gpu_ctx_finish_frame,
     gfx_lowlevel_gpu_ctx_handle_resize, gfx_lowlevel_gpu_ctx_init,
-    gfx_lowlevel_gpu_ctx_start_frame,
+    gfx_lowlevel_gpu_ctx_set_output_colorspace, gfx_lowlevel_gpu_ctx_set_present_mode,
+    gfx_lowlevel_gpu_ctx_start_frame, gfx_lowlevel_gpu_name, gfx_lowlevel_present_mode,
+    gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_FIFO,
+    gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_IMMEDIATE,
+    gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_MAILBOX,
 };
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputColorspace {
+    Srgb,
+    P3,
+    Pq,
+}
+
+impl From<OutputColorspace> for gfx_lowlevel_colorspace {
+    fn from(csp: OutputColorspace) -> Self {
+        match csp {
+            OutputColorspace::Srgb => gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_SRGB,
+            OutputColorspace::P3 => gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_DISPLAY_P3,
+            OutputColorspace::Pq => gfx_lowlevel_colorspace_GFX_LOWLEVEL_CSP_HDR_PQ,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl From<PresentMode> for gfx_lowlevel_present_mode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_FIFO,
+            PresentMode::Mailbox => gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_MAILBOX,
+            PresentMode::Immediate => gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_IMMEDIATE,
+        }
+    }
+}
+
+impl From<gfx_lowlevel_present_mode> for PresentMode {
+    fn from(mode: gfx_lowlevel_present_mode) -> Self {
+        if mode == gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_MAILBOX {
+            PresentMode::Mailbox
+        } else if mode == gfx_lowlevel_present_mode_GFX_LOWLEVEL_PRESENT_IMMEDIATE {
+            PresentMode::Immediate
+        } else {
+            PresentMode::Fifo
+        }
+    }
+}
+
+// How the loop waits for the next frame to be due
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Pacing {
+    // can wake a millisecond or two late
+    Sleep,
+    // sleeps most of the way then spins, on time at the cost of a core
+    Spin,
+    // doesn't wait, fifo presentation blocks until the display's vblank so
+    // this only plays at --fps on a display refreshing at --fps
+    Vsync,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ShareOutput {
+    Syphon,
+    Spout,
+    Ndi,
+}
+
+impl From<ShareOutput> for ShareBackend {
+    fn from(share: ShareOutput) -> Self {
+        match share {
+            ShareOutput::Syphon => ShareBackend::Syphon,
+            ShareOutput::Spout => ShareBackend::Spout,
+            ShareOutput::Ndi => ShareBackend::Ndi,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum HudLogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl From<HudLogLevel> for Option<LogLevel> {
+    fn from(level: HudLogLevel) -> Self {
+        match level {
+            HudLogLevel::Debug => Some(LogLevel::Debug),
+            HudLogLevel::Info => Some(LogLevel::Info),
+            HudLogLevel::Warn => Some(LogLevel::Warn),
+            HudLogLevel::Error => Some(LogLevel::Error),
+            HudLogLevel::Off => None,
+        }
+    }
+}
+
 #[derive(Parser, Debug, Clone)]
 #[command(author = "VampireExec", version = "1", about = "visualization tool")]
 struct Args {
+    // Any of these options as toml, e.g. fps = 30 or [midi] port = ["a"].
+    // Options on the command line win. Tempo, hud log level, fetch allow
+    // list, restarts, keys, controller deadzone, dropped vid loading, pacing,
+    // hud opacity and the debug options are reloaded when it changes.
+    #[arg(long)]
+    config: Option<PathBuf>,
     #[arg(long, default_value = "540")]
     width: u32,
     #[arg(long, default_value = "960")]
@@ -34,20 +177,316 @@ struct Args {
     wasm: String,
     #[arg(long, default_value = "24")]
     fps: i64,
+    // Loads the app and runs it for --lint-frames without showing anything,
+    // printing what went wrong as JSON and exiting 1 if anything did
     #[arg(long, default_value = "false")]
     dry_run: bool,
+    // Renders frames=N app frames as fast as they'll go then exits, writing
+    // their timings and the memory used as json to report=path or stdout.
+    // headless hides the output and logs the HUD instead.
+    #[arg(long)]
+    benchmark: Option<BenchmarkSpec>,
+    #[arg(long, default_value = "48")]
+    lint_frames: i64,
+    // Where --dry-run writes its report instead of stdout
+    #[arg(long)]
+    lint_report: Option<PathBuf>,
     #[arg(long, default_value = "false")]
     show_mix_time: bool,
+    // Shown to the app read/write as /tmp/viz
     #[arg(long, default_value = "/tmp/viz")]
     preopen_dir: String,
+    // More dirs for the app as host_path:guest_path:ro|rw, repeat for more
+    #[arg(long)]
+    preopen: Vec<Preopen>,
+    // Watches every preopen for changed files, reloading the Vids and LUTs
+    // read from them and sending the app a FileChanged event
+    #[arg(long, default_value = "false")]
+    watch_assets: bool,
+    // What a changed file with an extension means as ext=shader|lut|vid|tex|other|ignore,
+    // repeat for more. Shaders, LUTs, videos and images are known already.
+    #[arg(long)]
+    watch_ext: Vec<WatchRule>,
+    // A file is reloaded, or reported as newly matching a watched glob, once it
+    // hasn't changed for this long
+    #[arg(long, default_value = "300")]
+    watch_debounce_ms: u64,
+    // What decoded Tex frames may take in MB, past it the least recently drawn
+    // are dropped and decoded again from their files if they're wanted
+    #[arg(long, default_value = "1024")]
+    tex_memory_mb: usize,
     #[arg(long, default_value = "false")]
     shader_debug: bool,
+    #[cfg(feature = "midi")]
     #[arg(long)]
     midi_port: Vec<String>,
+    #[cfg(feature = "midi")]
     #[arg(long)]
     midi_output: Vec<String>,
+    // Native extensions, see src/sdlrig_plugin.h, repeat for more
+    #[arg(long)]
+    plugin: Vec<PathBuf>,
+    // Publishes the output as a Syphon server on macOS, a Spout sender on
+    // windows or an NDI source anywhere
+    #[arg(long, value_enum)]
+    share: Option<ShareOutput>,
+    #[arg(long, default_value = "sdlrig")]
+    share_name: String,
+    // Mixer to publish instead of the window
+    #[arg(long)]
+    share_source: Option<String>,
+    // Fifo waits for vblank and never tears, mailbox and immediate present
+    // sooner if the display has them
+    #[arg(long, value_enum, default_value = "fifo")]
+    present_mode: PresentMode,
+    #[arg(long, value_enum, default_value = "sleep")]
+    pacing: Pacing,
+    // PQ only takes effect if the display/driver exposes an HDR10 surface
+    #[arg(long, value_enum, default_value = "srgb")]
+    output_colorspace: OutputColorspace,
+    // The Vulkan device to render on, by index or part of its name, see
+    // --list-gpus. Left to the driver without it.
+    #[arg(long)]
+    gpu: Option<String>,
+    // Prints the Vulkan devices --gpu picks from and exits
+    #[arg(long, default_value = "false")]
+    list_gpus: bool,
+    // Prints the cameras and capture cards an app can open, with their
+    // modes, and exits
+    #[arg(long, default_value = "false")]
+    list_capture_devices: bool,
+    // Tempo for beat relative seeks
+    #[arg(long, default_value = "120")]
+    bpm: f64,
+    // The HUD window is only opened with a font to draw it in
+    #[arg(long)]
+    font: Option<String>,
+    #[arg(long, default_value = "14")]
+    font_size: u16,
+    // Draws the HUD over the output instead of in its own window
+    #[arg(long, default_value = "false")]
+    hud_overlay: bool,
+    // Only the output window opens, what the HUD would show goes to stderr
+    // and the remote instead, no font needed
+    #[arg(long, default_value = "false")]
+    no_hud: bool,
+    #[arg(long, default_value = "0.8")]
+    hud_opacity: f32,
+    // A calculate running longer than this is interrupted and the frame skipped
+    #[arg(long, default_value = "250")]
+    calc_budget_ms: u64,
+    // Settings are restored from here on startup and saved periodically and on exit
+    #[arg(long)]
+    settings_file: Option<PathBuf>,
+    #[arg(long, default_value = "30")]
+    settings_save_secs: u64,
+    // Writes every frame's events to a file that --replay feeds back
+    #[cfg(feature = "recording")]
+    #[arg(long)]
+    record: Option<PathBuf>,
+    // Renders a recording frame by frame with a fixed clock, as fast as it can
+    #[cfg(feature = "recording")]
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    // Writes the performer's key, midi, controller, pointer and drop inputs
+    // with when they arrived, for --replay-session
+    #[cfg(feature = "recording")]
+    #[arg(long)]
+    record_session: Option<PathBuf>,
+    // Plays a session's inputs back on the frames they arrived on, in place of
+    // live ones, while everything else runs live. Frames aren't dropped until
+    // it's done so each input lands where it did.
+    #[cfg(feature = "recording")]
+    #[arg(long, conflicts_with = "replay")]
+    replay_session: Option<PathBuf>,
+    // App log messages are appended here as well as printed
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    // The host's log levels, a default then module=level pairs like
+    // "info,sdlrig::vidruntime=debug", this binary's module is viz and the
+    // app's log is app
+    #[arg(long, default_value = "info")]
+    log_level: LogFilter,
+    // Also logs json lines here, moved aside at --log-max-mb keeping
+    // --log-keep old files
+    #[arg(long)]
+    log_json: Option<PathBuf>,
+    #[arg(long, default_value = "10")]
+    log_max_mb: u64,
+    #[arg(long, default_value = "3")]
+    log_keep: usize,
+    // Least severe app log message shown on the HUD
+    #[arg(long, value_enum, default_value = "warn")]
+    hud_log_level: HudLogLevel,
+    // Url prefix the app may fetch from, repeat for more. Nothing is allowed by default.
+    #[arg(long)]
+    fetch_allow: Vec<String>,
+    // Where assets with http(s) paths are downloaded to, under the user's
+    // cache directory by default
+    #[arg(long)]
+    asset_cache: Option<PathBuf>,
+    // Only load url assets that were downloaded already
+    #[arg(long, default_value = "false")]
+    offline: bool,
+    // Loads the variant with this label from assets that have one, like a half
+    // resolution proxy for rehearsing on a laptop
+    #[arg(long)]
+    proxy: Option<String>,
+    // sha256sum style list of the assets the set expects, checked at startup
+    // and named when one of them fails to load
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    // Runs a second instance of the app in a Preview window that gets the
+    // input, --take-key takes its settings to the output
+    #[arg(long, default_value = "false")]
+    preview: bool,
+    // Crashed apps are restarted this many times in a row before giving up, 0 never restarts
+    #[arg(long, default_value = "5")]
+    max_restarts: u32,
+    // Capture device analysed into an AudioAnalysisEvent and audio_* uniforms
+    // every frame, "default" for the system's default input
+    #[arg(long)]
+    audio_in: Option<String>,
+    // Follow the tempo of the --audio-in onsets, like tapping along
+    #[arg(long, default_value = "false")]
+    tempo_from_onsets: bool,
+    // host:port the app's SendOsc and the transport state are sent to
+    #[cfg(feature = "osc")]
+    #[arg(long)]
+    osc_out: Option<String>,
+    // host:port to serve the websocket remote control on, with a browser UI at
+    // http://host:port/. Anyone who can reach it can control the rig unless
+    // --remote-token is set, and without it browsers have to connect by
+    // address, localhost or the host given here.
+    #[arg(long)]
+    remote: Option<String>,
+    // Required of every --remote client as ?token=<token>, e.g.
+    // http://host:port/?token=<token> for the browser UI
+    #[arg(long)]
+    remote_token: Option<String>,
+    // Also send every SendCmd as /sdlrig/cmd/<mix>/<name>
+    #[cfg(feature = "osc")]
+    #[arg(long, default_value = "false")]
+    osc_mirror_cmds: bool,
+    // Joins an Ableton Link session, which then sets the tempo and beat
+    #[cfg(feature = "link")]
+    #[arg(long, default_value = "false")]
+    link: bool,
+    #[cfg(feature = "link")]
+    #[arg(long, default_value = "4")]
+    link_quantum: u32,
+    // SDL key names for the host's own keys, these don't reach the app
+    #[arg(long, default_value = "Escape")]
+    quit_key: String,
+    #[arg(long, default_value = "F9")]
+    take_key: String,
+    #[arg(long, default_value = "F11")]
+    fullscreen_key: String,
+    // Hides and shows the HUD
+    #[arg(long, default_value = "F1")]
+    hud_key: String,
+    // Hides and shows the performance overlay on the HUD
+    #[arg(long, default_value = "F2")]
+    perf_key: String,
+    // Tap on the beat to set the tempo, a midi clock or Link overrides it
+    #[arg(long, default_value = "F3")]
+    tap_key: String,
+    // Steps the frame rate through --fps-steps, with shift backwards
+    #[arg(long, default_value = "F4")]
+    fps_key: String,
+    #[arg(long, default_values_t = [24, 30, 60])]
+    fps_steps: Vec<i64>,
+    // Starts with the performance overlay shown
+    #[arg(long, default_value = "false")]
+    perf: bool,
+    // Moves the output to the next display, with shift the previous one
+    #[arg(long, default_value = "F10")]
+    display_key: String,
+    // Starts the output fullscreen and/or on this display, without either the
+    // window goes where it was last put with the same displays connected
+    #[arg(long, default_value = "false")]
+    fullscreen: bool,
+    #[arg(long)]
+    display: Option<i32>,
+    // Where window placements are remembered between runs
+    #[arg(long)]
+    placement_file: Option<PathBuf>,
+    // Controller stick values closer to rest than this come through as 0
+    #[arg(long, default_value = "8000")]
+    controller_deadzone: i16,
+    // Video files dropped on the window are loaded as Vids named after the file
+    #[arg(long, default_value = "false")]
+    drop_load_vids: bool,
+}
+
+// The command line over the --config file's values
+fn parse_args() -> anyhow::Result<Args> {
+    let command = Args::command().mut_arg("wasm", |arg| arg.required(false));
+    let cli = command.clone().get_matches();
+    let Some(path) = cli.get_one::<PathBuf>("config") else {
+        return Ok(Args::parse());
+    };
+    let mut argv: Vec<OsString> = std::env::args_os().take(1).collect();
+    for (id, tokens) in config_args(path)? {
+        if id == "config"
+            || !command
+                .get_arguments()
+                .any(|arg| arg.get_id() == id.as_str())
+        {
+            anyhow::bail!("{} has an unknown option {}", path.display(), id);
+        }
+        if cli.value_source(&id) != Some(ValueSource::CommandLine) {
+            argv.extend(tokens.into_iter().map(OsString::from));
+        }
+    }
+    argv.extend(std::env::args_os().skip(1));
+    Args::try_parse_from(argv).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+}
+
+// The host's own keys, these don't reach the app
+struct HostKeys {
+    quit: Keycode,
+    take: Keycode,
+    fullscreen: Keycode,
+    display: Keycode,
+    hud: Keycode,
+    perf: Keycode,
+    tap: Keycode,
+    fps: Keycode,
+}
+
+impl HostKeys {
+    fn new(args: &Args) -> anyhow::Result<Self> {
+        Ok(Self {
+            quit: keycode(&args.quit_key)?,
+            take: keycode(&args.take_key)?,
+            fullscreen: keycode(&args.fullscreen_key)?,
+            display: keycode(&args.display_key)?,
+            hud: keycode(&args.hud_key)?,
+            perf: keycode(&args.perf_key)?,
+            tap: keycode(&args.tap_key)?,
+            fps: keycode(&args.fps_key)?,
+        })
+    }
 }
 
+fn keycode(name: &str) -> anyhow::Result<Keycode> {
+    Keycode::from_name(name).ok_or_else(|| anyhow::anyhow!("No key named {}", name))
+}
+
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+// Restarts are forgotten once an app has run this long
+const RESTART_FORGET: Duration = Duration::from_secs(60);
+// How often the running app's settings are kept for a restart
+const SETTINGS_SNAPSHOT: Duration = Duration::from_secs(1);
+// Spin pacing sleeps until this long before the frame is due
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+// what --drop-load-vids tries to open
+const VID_EXTENSIONS: [&str; 9] = [
+    "mp4", "mov", "mkv", "webm", "avi", "m4v", "mpg", "mpeg", "gif",
+];
+
 // Adding a comment as a test
 pub fn main() -> anyhow::Result<()> {
     // Tee stderr so we can consume it programmatically.
@@ -57,10 +496,80 @@ pub fn main() -> anyhow::Result<()> {
     let mut log_lines = String::new();
 
     set_level(ffmpeg_next::log::Level::Error);
-    let args = Args::parse();
+    let mut args = parse_args()?;
+    let logger = Logger::install(
+        args.log_level.clone(),
+        args.log_json.clone(),
+        args.log_max_mb * 1024 * 1024,
+        args.log_keep,
+    )?;
+    assetcache::configure(AssetCache {
+        dir: args
+            .asset_cache
+            .clone()
+            .unwrap_or_else(|| AssetCache::default().dir),
+        offline: args.offline,
+    });
+    assetcache::set_proxy(args.proxy.clone());
+    if let Some(path) = args.manifest.as_ref() {
+        let manifest = manifest::install(Manifest::load(path)?);
+        // a lint checks it before the app loads instead
+        if !args.dry_run {
+            thread::spawn(move || {
+                for found in manifest.verify() {
+                    warn!(path = %found.path, problem = ?found.problem, "{}", found.message);
+                }
+            });
+        }
+    }
+    if args.benchmark.as_ref().map_or(false, |b| b.headless) {
+        args.no_hud = true;
+    }
+    let mut keys = HostKeys::new(&args)?;
+    let mut last_loaded_config = SystemTime::now();
+    // before any app loads, as apps can ask for the plugins' assets
+    for path in &args.plugin {
+        let name = plugins::install(path)?;
+        info!("Loaded plugin {} from {}", name, path.display());
+    }
 
+    // windows scales windows by the display's scaling setting unless told the
+    // app handles dpi itself, then sizes are pixels like everywhere else and a
+    // 1920x1080 output is the 1920x1080 an LED processor takes at any scaling
+    if cfg!(target_os = "windows") {
+        sdl2::hint::set("SDL_WINDOWS_DPI_AWARENESS", "permonitorv2");
+        sdl2::hint::set("SDL_WINDOWS_DPI_SCALING", "0");
+        // the output stays up while the hud on another display has focus
+        sdl2::hint::set("SDL_VIDEO_MINIMIZE_ON_FOCUS_LOSS", "0");
+    }
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
+    if args.list_gpus {
+        for (index, name) in gpu_names()?.iter().enumerate() {
+            println!("{}: {}", index, name);
+        }
+        return Ok(());
+    }
+    if args.list_capture_devices {
+        for device in capture::list()? {
+            println!("{} ({} {})", device.name, device.format, device.path);
+            for mode in &device.modes {
+                let fps = match (mode.min_fps, mode.max_fps) {
+                    (Some(min), Some(max)) => format!(
+                        " {:.2}-{:.2}fps",
+                        min.0 as f64 / min.1 as f64,
+                        max.0 as f64 / max.1 as f64
+                    ),
+                    _ => String::new(),
+                };
+                let pixel_format = mode.pixel_format.as_deref().unwrap_or("");
+                println!("  {}x{}{} {}", mode.size.0, mode.size.1, fps, pixel_format);
+            }
+        }
+        return Ok(());
+    }
+    let gpu = args.gpu.as_deref().map(select_gpu).transpose()?;
+    let gpu_ptr = gpu.as_ref().map_or(std::ptr::null(), |gpu| gpu.as_ptr());
     // MAIN WINDOW
     let mut window = video_subsystem
         .window("Output", args.width, args.height)
@@ -68,112 +577,290 @@ pub fn main() -> anyhow::Result<()> {
         .position(0, 0)
         .build()
         .unwrap();
+    // placed before the swapchain is made so it starts at the right size
+    let mut placement = Placement::load(args.placement_file.clone());
+    if args.fullscreen || args.display.is_some() {
+        if let Some(index) = args.display {
+            if let Err(e) = show_on_display(&video_subsystem, &mut window, index) {
+                warn!("Could not move the output to display {}: {}", index, e);
+            }
+        }
+        if args.fullscreen {
+            if let Err(e) = toggle_fullscreen(&mut window) {
+                warn!("Could not make the output fullscreen: {}", e);
+            }
+        }
+    } else if let Err(e) = placement.restore(&video_subsystem, &mut window) {
+        warn!("Could not restore the output's placement: {}", e);
+    }
 
     let mut lowlevel_ctx = unsafe {
         let raw_window = window.raw();
-        let ctx = gfx_lowlevel_gpu_ctx_init(raw_window as *mut _);
+        let ctx = gfx_lowlevel_gpu_ctx_init(raw_window as *mut _, gpu_ptr);
         if ctx.is_null() {
             panic!("Failed to initialize lowlevel_ctx");
         }
+        if gfx_lowlevel_gpu_ctx_set_output_colorspace(ctx, args.output_colorspace.into()) != 0 {
+            warn!(
+                "Failed to set output colorspace {:?}, using sRGB",
+                args.output_colorspace
+            );
+        }
+        if gfx_lowlevel_gpu_ctx_set_present_mode(ctx, args.present_mode.into()) != 0 {
+            warn!("Failed to set present mode {:?}", args.present_mode);
+        }
         ctx
     };
     window.raise();
-
-    let mut midi_devices = HashMap::new();
-    {
-        let midi_in = MidiInput::new("sdlrig-midi-probe")?;
-        let ports = midi_in.ports();
-        eprintln!("Available midi ports:");
-        for (i, p) in ports.iter().enumerate() {
-            eprintln!("{}: {}", i, midi_in.port_name(p)?);
-            midi_devices.insert(midi_in.port_name(p)?, i);
+    let present_mode = PresentMode::from(unsafe { (*lowlevel_ctx).present_mode });
+    if present_mode != args.present_mode {
+        warn!(
+            "Presenting with {:?}, the display has no {:?}",
+            present_mode, args.present_mode
+        );
+    }
+    args.pacing = usable_pacing(args.pacing, present_mode);
+    if args.pacing == Pacing::Vsync {
+        let refresh = window
+            .display_mode()
+            .map(|mode| mode.refresh_rate as i64)
+            .unwrap_or(0);
+        if refresh != args.fps {
+            warn!(
+                "Display refreshes at {}Hz, vsync pacing will play at that rather than {} fps",
+                refresh, args.fps
+            );
         }
     }
 
-    let (midi_tx, midi_rx) = channel();
-    let _conns = if !args.midi_port.is_empty() {
-        let mut conns = Vec::new();
-        for device in args.midi_port {
-            if let Some(p) = midi_devices.get(&device) {
-                let name = device.clone();
-                let mut midi_in = MidiInput::new(&name)?;
-                midi_in.ignore(Ignore::None);
-                let ports = midi_in.ports();
-                let port = ports.get(*p).ok_or(anyhow::anyhow!("Invalid midi port"))?;
-                println!("Opening midi port {}", midi_in.port_name(port)?);
-                let midi_tx = midi_tx.clone();
-                conns.push(midi_in.connect(
-                    port,
-                    "midir-read-input",
-                    move |stamp, message, _| {
-                        let key = if message.len() >= 2 { message[1] } else { 0 };
-                        let vel = if message.len() >= 3 { message[2] } else { 0 };
-                        midi_tx
-                            .send(MidiEvent {
-                                device: name.clone(),
-                                channel: message[0] & 0x0F,
-                                kind: message[0] & 0xF0,
-                                key: key,
-                                velocity: vel,
-                                timestamp: stamp as i64,
-                            })
-                            .unwrap();
-                    },
-                    (),
-                )?);
-            } else {
-                eprintln!("Midi device {} not found", device);
+    let mut hud = match args.no_hud {
+        true => Some(Hud::log_only(args.fps)),
+        #[cfg(not(feature = "hud"))]
+        false => {
+            if args.font.is_some() {
+                warn!("The HUD window needs sdlrig built with the hud feature, pass --no-hud");
             }
+            None
         }
-        conns
-    } else {
-        println!("Not listening for midi");
-        vec![]
+        #[cfg(feature = "hud")]
+        false => args.font.as_ref().and_then(|font| {
+            let hud = match args.hud_overlay {
+                true => Hud::overlay(font, args.font_size, args.fps, args.hud_opacity),
+                false => Hud::new(&video_subsystem, font, args.font_size, args.fps),
+            };
+            hud.map_err(|e| warn!("HUD disabled: {}", e)).ok()
+        }),
     };
+    if let Some(hud) = hud.as_mut() {
+        hud.set_info(Some(pacing_info(present_mode, args.pacing)));
+    }
+    let mut calc_budget = Duration::from_millis(args.calc_budget_ms);
+    if args.perf && (hud.is_none() || args.no_hud) {
+        warn!("The performance overlay needs the HUD, pass --font without --no-hud");
+    }
+    let mut perf = Perf::new(args.perf);
+    let mut benchmark = args.benchmark.clone().map(Benchmark::new);
+    let mut tempo = Tempo::new(args.bpm);
 
-    let mut midi_output_devices = HashMap::new();
-    let mut midi_outs = HashMap::new();
-    if !args.midi_output.is_empty() {
-        let midi_out = MidiOutput::new("sdlrig-midi-output-probe")?;
-        let ports = midi_out.ports();
-        eprintln!("Available midi output ports:");
-        for (i, p) in ports.iter().enumerate() {
-            let port_name = midi_out.port_name(p)?;
-            eprintln!("{}: {}", i, port_name);
-            midi_output_devices.insert(port_name, i);
-        }
-
-        for device in args.midi_output {
-            if let Some(p) = midi_output_devices.get(&device) {
-                let midi_out = MidiOutput::new("sdlrig-midi-output")?;
-                let ports = midi_out.ports();
-                let port = ports
-                    .get(*p)
-                    .ok_or(anyhow::anyhow!("Invalid midi output port"))?;
-                println!("Opening midi output port {}", midi_out.port_name(port)?);
-                let conn = midi_out.connect(port, "midir-write-output")?;
-                midi_outs.insert(device, conn);
-            } else {
-                eprintln!("Midi output device {} not found", device);
-            }
-        }
+    let mut recording = recording::Recording::open(&args)?;
+
+    let mut log_file = match args.log_file.as_ref() {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let mut hud_log_level: Option<LogLevel> = args.hud_log_level.into();
+    let mut fetcher = Fetcher::new(args.fetch_allow.clone());
+    let mut capture_lister = CaptureLister::default();
+    let glob_watcher = GlobWatcher::start(Duration::from_millis(args.watch_debounce_ms));
+
+    #[cfg(feature = "midi")]
+    if let Err(e) = MidiPorts::list() {
+        warn!("Could not list midi ports: {}", e);
     }
+    #[cfg(feature = "midi")]
+    let (midi_tx, midi_rx) = channel();
+    #[cfg(feature = "midi")]
+    let mut midi_ports = MidiPorts::new(args.midi_port.clone(), args.midi_output.clone(), midi_tx);
+    #[cfg(feature = "midi")]
+    let midi_clocks = midi_ports.clocks();
+    // devices we already complained about, so a missing port isn't logged every frame
+    let mut warned_midi_outs = HashSet::new();
+    let mut warned_osc_out = false;
+    let mut warned_monitors = false;
+
+    // shared with the apps, which can send feedback while calculating
+    #[cfg(feature = "midi")]
+    let midi_outs = midi_ports.outputs();
+    #[cfg(feature = "midi")]
+    let sink_outs = midi_outs.clone();
+    #[cfg(feature = "midi")]
+    let midi_sink: Option<MidiSink> = Some(Arc::new(move |device: &str, bytes: &[u8]| {
+        let mut outs = sink_outs.lock().unwrap();
+        let Some(conn) = outs.get_mut(device) else {
+            anyhow::bail!("No midi output {} open", device);
+        };
+        conn.send(bytes)
+            .map_err(|e| anyhow::anyhow!("failed to send midi message on {}: {}", device, e))
+    }));
+    #[cfg(not(feature = "midi"))]
+    let midi_sink: Option<MidiSink> = None;
 
     let (mut canvas_w, mut canvas_h) = window.size();
 
+    // audio is optional, the rig still runs on machines without an output
+    let mut audio_runtime = match sdl_context.audio().map_err(|e| anyhow::anyhow!(e)) {
+        Ok(audio) => AudioRuntime::new(&audio)
+            .map_err(|e| warn!("Audio playback disabled: {}", e))
+            .ok(),
+        Err(e) => {
+            warn!("Audio playback disabled: {}", e);
+            None
+        }
+    };
+    let mut audio_input = match args.audio_in.as_ref() {
+        Some(device) => match sdl_context
+            .audio()
+            .map_err(|e| anyhow::anyhow!(e))
+            .and_then(|audio| AudioInput::open(&audio, device))
+        {
+            Ok(input) => Some(input),
+            Err(e) => {
+                warn!("Audio input disabled: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let mut controllers = match sdl_context.game_controller() {
+        Ok(subsystem) => Some(Controllers::new(subsystem, args.controller_deadzone)),
+        Err(e) => {
+            warn!("Game controllers disabled: {}", e);
+            None
+        }
+    };
     let mut event_pump = sdl_context.event_pump().unwrap();
     let start_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 
     let frames_per_sec = args.fps;
-    let ns_per_frame = 1_000_000_000u128 / frames_per_sec as u128;
+    let mut ns_per_frame = 1_000_000_000u128 / frames_per_sec as u128;
+    // from SetFps or the fps key, taken up between frames
+    let mut fps_change = None;
 
     let mut frame = (start_time.as_nanos() / ns_per_frame) as i64;
 
-    let mut loader = RuntimeLoader::new();
+    let mut loader = RuntimeLoader::new(midi_sink);
+
+    let mut gfx_runtime = GfxRuntime::new(frames_per_sec, frame - 1);
+    gfx_runtime.set_bpm(args.bpm);
+
+    let mut output_share = match args.share {
+        Some(share) => match OutputShare::new(
+            share.into(),
+            &args.share_name,
+            args.share_source.clone(),
+            args.fps,
+        ) {
+            Ok(output_share) => Some(output_share),
+            Err(e) => {
+                warn!("Output sharing disabled: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    #[cfg(feature = "link")]
+    let mut link = args
+        .link
+        .then(|| sdlrig::link::Link::new(args.bpm, args.link_quantum));
+
+    #[cfg(feature = "osc")]
+    let osc_out = match args.osc_out.as_ref() {
+        Some(target) => match OscOut::new(target) {
+            Ok(osc_out) => Some(osc_out),
+            Err(e) => {
+                warn!("OSC output disabled: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    if args.remote.is_some() && args.remote_token.is_none() {
+        warn!("--remote without --remote-token lets anyone who can reach it control the rig");
+    }
+    let remote = match args.remote.as_ref() {
+        Some(addr) => match Remote::listen(addr, args.remote_token.clone()) {
+            Ok(remote) => Some(remote),
+            Err(e) => {
+                warn!("Remote control disabled: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    // screenshot requests waiting on this frame's render, then on the
+    // download of it, which finishes a frame or more later
+    let mut screenshots = vec![];
+    let mut shooting = vec![];
+    let mut screenshot_download: Option<FrameDownload> = None;
+
+    let preopens = std::iter::once(Preopen {
+        host_path: PathBuf::from(&args.preopen_dir),
+        guest_path: "/tmp/viz".into(),
+        writable: true,
+    })
+    .chain(args.preopen.iter().cloned())
+    .collect::<Vec<_>>();
+    let watcher = args.watch_assets.then(|| {
+        DirWatcher::start(
+            preopens.iter().map(|p| p.host_path.clone()).collect(),
+            Duration::from_millis(args.watch_debounce_ms),
+        )
+    });
 
-    let gfx_runtime = GfxRuntime::new(frames_per_sec, frame - 1);
+    if args.dry_run {
+        let report = lint(
+            &args,
+            &preopens,
+            &gfx_runtime,
+            lowlevel_ctx,
+            canvas_w,
+            canvas_h,
+            calc_budget,
+        );
+        let json = serde_json::to_string_pretty(&report)?;
+        match args.lint_report.as_ref() {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{}", json),
+        }
+        drop(hud);
+        drop(gfx_runtime);
+        plugins::uninstall_all();
+        unsafe {
+            gfx_lowlevel_gpu_ctx_destroy((&mut lowlevel_ctx) as *mut *mut gfx_lowlevel_gpu_ctx);
+        }
+        std::process::exit(if report.ok { 0 } else { 1 });
+    }
 
-    loader.start(&args.wasm, &args.preopen_dir, None, args.fps, args.dry_run);
+    loader.start(&args.wasm, preopens.clone(), None, args.fps, calc_budget);
+    let mut preview = match args.preview {
+        true => {
+            let mut preview = Preview::new(
+                &video_subsystem,
+                args.width,
+                args.height,
+                frames_per_sec,
+                frame,
+                gpu_ptr,
+            )?;
+            preview.gfx_runtime.set_bpm(args.bpm);
+            preview
+                .loader
+                .start(&args.wasm, preopens.clone(), None, args.fps, calc_budget);
+            Some(preview)
+        }
+        false => None,
+    };
     #[allow(unused)]
     let (mut try_app, mut reloaded) = loader.try_finish(
         true,
@@ -187,11 +874,27 @@ pub fn main() -> anyhow::Result<()> {
     );
     let mut last_loaded_wasm = SystemTime::now();
 
-    if args.dry_run {
-        return Ok(());
-    }
     window.raise();
+    if benchmark.as_ref().map_or(false, |b| b.spec.headless) {
+        window.hide();
+    }
     let mut reg_events = vec![];
+    // rendered again when calculate runs out of budget
+    let mut last_specs = vec![];
+    // a crashed guest isn't called again until it's reloaded
+    let mut guest_crashed = false;
+    // the file is only restored into the first app that loads, reloads
+    // carry settings over from the app they replace
+    let mut settings_restored = false;
+    let mut settings_saved = Instant::now();
+    let mut watchdog = Watchdog::new(args.max_restarts);
+    // apps see a ResizeEvent when this changes
+    let mut last_canvas = (canvas_w, canvas_h);
+    texruntime::set_output_size(window.vulkan_drawable_size());
+    texruntime::set_memory_budget(args.tex_memory_mb << 20);
+    // restarts begin from the app's last settings from before it crashed
+    let mut settings_snapshot = None;
+    let mut settings_snapshot_at = Instant::now();
 
     'running: loop {
         assert_eq!(unsafe { (*lowlevel_ctx).started }, false);
@@ -206,43 +909,325 @@ pub fn main() -> anyhow::Result<()> {
             lowlevel_ctx,
         );
 
+        if let Some(preview) = preview.as_mut() {
+            preview.try_finish(args.dry_run, frame);
+        }
+
+        #[cfg(feature = "midi")]
+        for event in midi_ports.rescan() {
+            if let GfxEvent::DeviceConnected(device) = &event {
+                warned_midi_outs.remove(&device.name);
+            }
+            reg_events.push(event);
+        }
+        #[cfg(feature = "midi")]
         for evt in midi_rx.try_iter() {
             reg_events.push(GfxEvent::MidiEvent(evt));
         }
+        if let Some(input) = audio_input.as_mut() {
+            let analysis = input.analyze(frame);
+            if args.tempo_from_onsets && analysis.onset {
+                tempo.onset(Instant::now());
+            }
+            gfx_runtime.set_audio(Some(analysis.clone()));
+            reg_events.push(GfxEvent::AudioAnalysisEvent(analysis));
+        }
+        reg_events.extend(
+            plugins::poll_events()
+                .into_iter()
+                .map(GfxEvent::PluginEvent),
+        );
+        for call in remote.iter().flat_map(|remote| remote.poll()) {
+            let reply = match &call.request {
+                RemoteRequest::SetSetting { field, value } => {
+                    reg_events.push(GfxEvent::SettingChanged(SettingChange::new(field, *value)));
+                    RemoteReply::Ok
+                }
+                RemoteRequest::Transport => match gfx_runtime.playback_positions() {
+                    Ok(positions) => RemoteReply::Transport {
+                        frame,
+                        bpm: gfx_runtime.bpm(),
+                        vids: positions
+                            .vids
+                            .into_iter()
+                            .map(|(name, (pos, duration))| (name, pos, duration))
+                            .collect(),
+                        mixes: positions.mixes.into_iter().collect(),
+                    },
+                    Err(e) => RemoteReply::Error(e.to_string()),
+                },
+                // answered once the frame is rendered
+                RemoteRequest::Screenshot { .. } => {
+                    screenshots.push(call);
+                    continue;
+                }
+                RemoteRequest::Event(event) => {
+                    reg_events.push(event.clone());
+                    RemoteReply::Ok
+                }
+                RemoteRequest::Hud => match hud.as_mut() {
+                    Some(hud) => RemoteReply::Hud {
+                        sections: hud.text_sections(frame),
+                        widgets: hud.widgets().to_vec(),
+                        text: hud.app_text().into(),
+                    },
+                    None => RemoteReply::Error("The HUD is off, pass --font".into()),
+                },
+                RemoteRequest::Stats => RemoteReply::Stats {
+                    frame,
+                    last: perf.last().clone(),
+                    dropped_total: perf.dropped_total(),
+                    queues: queue_depths(&gfx_runtime, audio_runtime.as_ref(), &fetcher)
+                        .into_iter()
+                        .map(|(name, depth)| (name.into(), depth))
+                        .collect(),
+                },
+                request => match try_app.as_ref() {
+                    Some(app) => app_reply(app, request),
+                    None => RemoteReply::Error("No app is running".into()),
+                },
+            };
+            call.reply(reply);
+        }
+        #[cfg(feature = "midi")]
+        {
+            let mut clocks = midi_clocks.lock().unwrap();
+            for (device, clock) in clocks.iter_mut() {
+                reg_events.extend(clock.take_events(device));
+            }
+            // the first playing clock drives the tempo
+            if let Some(clock) = clocks.values().find(|clock| clock.playing()) {
+                tempo.follow(
+                    clock.bpm(),
+                    clock.beat(),
+                    sdlrig::gfxinfo::TempoSource::MidiClock,
+                );
+            }
+        }
+        // a link session wins over a midi clock
+        #[cfg(feature = "link")]
+        if let Some(link) = link.as_mut() {
+            let event = link.capture();
+            tempo.follow(
+                Some(event.bpm()),
+                event.beat(),
+                sdlrig::gfxinfo::TempoSource::Link,
+            );
+            reg_events.push(GfxEvent::LinkEvent(event));
+        }
+        tempo.advance(Instant::now());
+        gfx_runtime.set_bpm(tempo.bpm());
+        gfx_runtime.set_beat(tempo.driven().then(|| tempo.beat()));
+        reg_events.push(GfxEvent::TempoEvent(tempo.event()));
+
+        if let (Some(app), Some(path)) = (try_app.as_ref(), args.settings_file.as_ref()) {
+            if !settings_restored && path.exists() {
+                match app.load_settings_file(path) {
+                    Ok(_) => info!("Restored settings from {}", path.display()),
+                    Err(e) => {
+                        warn!("Could not restore settings from {}: {}", path.display(), e)
+                    }
+                }
+            }
+            settings_restored = true;
+            // a replay's settings aren't the performer's, so they aren't kept
+            if !guest_crashed
+                && !recording.replaying()
+                && settings_saved.elapsed() >= Duration::from_secs(args.settings_save_secs)
+            {
+                settings_saved = Instant::now();
+                if let Err(e) = app.save_settings_file(path) {
+                    warn!("Could not save settings to {}: {}", path.display(), e);
+                }
+            }
+        }
 
         if reloaded {
             reg_events.push(GfxEvent::ReloadEvent());
+            guest_crashed = false;
+            watchdog.loaded();
+            if let Some(hud) = hud.as_mut() {
+                hud.set_error(None);
+            }
+        } else if watchdog.restarting && !loader.busy() {
+            // the restart itself failed to load
+            watchdog.crashed();
+        }
+
+        if let Some(app) = try_app.as_ref() {
+            if guest_crashed && watchdog.due() {
+                warn!("Restarting crashed app");
+                loader.restore_settings(settings_snapshot.clone());
+                loader.start(
+                    &args.wasm,
+                    preopens.clone(),
+                    Some(app.clone()),
+                    args.fps,
+                    calc_budget,
+                );
+            } else if !guest_crashed {
+                watchdog.healthy();
+                if settings_snapshot_at.elapsed() >= SETTINGS_SNAPSHOT {
+                    settings_snapshot_at = Instant::now();
+                    settings_snapshot = app
+                        .settings_version()
+                        .and_then(|v| app.extract_settings().map(|bytes| (v, bytes)))
+                        .ok();
+                }
+            }
+        }
+        if let Some(hud) = hud.as_mut() {
+            hud.set_status(watchdog.status(guest_crashed));
+            // a --no-hud HUD is the log already
+            if !args.no_hud {
+                for warning in logger.take_warnings() {
+                    hud.warn(frame, warning);
+                }
+            }
         }
 
         lazy_static! {
             static ref ACC: Mod = Mod::RSHIFTMOD | Mod::LSHIFTMOD;
         }
 
+        let mut take_preview = false;
+        let (mut resized, mut placed, mut save_placement) = (false, false, false);
         for event in event_pump.poll_iter() {
+            if let Some(event) = controllers.as_mut().and_then(|c| c.handle(&event)) {
+                reg_events.push(event);
+                continue;
+            }
+            if let Some(event) = pointer_event(&event, window.id(), (canvas_w, canvas_h)) {
+                reg_events.push(event);
+                continue;
+            }
             match event {
-                Event::Window { win_event, .. } => unsafe {
-                    match win_event {
-                        WindowEvent::Resized(w, h) => {
-                            canvas_w = w as u32;
-                            canvas_h = h as u32;
-                        }
-                        WindowEvent::SizeChanged(w, h) => {
-                            canvas_w = w as u32;
-                            canvas_h = h as u32;
-                        }
-                        _ => (),
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Resized(..) | WindowEvent::SizeChanged(..),
+                    ..
+                } if preview
+                    .as_ref()
+                    .map_or(false, |p| p.window.id() == window_id) =>
+                {
+                    if let Some(preview) = preview.as_ref() {
+                        preview.handle_resize();
                     }
-                    gfx_lowlevel_gpu_ctx_handle_resize(
-                        lowlevel_ctx,
-                        canvas_w as i32,
-                        canvas_h as i32,
-                    );
+                }
+                Event::Window {
+                    window_id,
+                    win_event,
+                    ..
+                } if window_id == window.id() => match win_event {
+                    WindowEvent::Resized(..) | WindowEvent::SizeChanged(..) => {
+                        resized = true;
+                        placed = true;
+                    }
+                    WindowEvent::Moved(..) => placed = true,
+                    _ => (),
+                },
+                Event::Display {
+                    display_event: DisplayEvent::Connected | DisplayEvent::Disconnected,
+                    ..
+                } => match placement.restore(&video_subsystem, &mut window) {
+                    Ok(restored) => resized |= restored,
+                    Err(e) => warn!("Could not restore the output's placement: {}", e),
                 },
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                Event::DropFile {
+                    window_id,
+                    filename,
+                    ..
+                } if window_id == window.id() => {
+                    let asset = args
+                        .drop_load_vids
+                        .then(|| dropped_vid(&filename))
+                        .flatten()
+                        .and_then(|vid| {
+                            let name = vid.name.clone();
+                            match gfx_runtime.load_asset(&vid.into()) {
+                                Ok(_) => Some(name),
+                                Err(e) => {
+                                    warn!("Could not load dropped {}: {}", filename, e);
+                                    None
+                                }
+                            }
+                        });
+                    reg_events.push(GfxEvent::FileDropped(FileDropEvent {
+                        path: filename,
+                        asset,
+                    }));
+                }
+                Event::Quit { .. } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(kc), ..
+                } if kc == keys.quit => break 'running,
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } if kc == keys.take && preview.is_some() => take_preview = true,
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } if kc == keys.hud && hud.is_some() => {
+                    if let Some(hud) = hud.as_mut() {
+                        hud.toggle();
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } if kc == keys.perf && hud.is_some() => perf.toggle(),
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } if kc == keys.tap => tempo.tap(Instant::now()),
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    keymod: km,
+                    repeat: false,
+                    ..
+                } if kc == keys.fps && !args.fps_steps.is_empty() => {
+                    let steps = &args.fps_steps;
+                    let step = if km.intersects(*ACC) {
+                        steps.len() - 1
+                    } else {
+                        1
+                    };
+                    let next = match steps
+                        .iter()
+                        .position(|fps| *fps == gfx_runtime.frames_per_sec)
+                    {
+                        Some(pos) => (pos + step) % steps.len(),
+                        None => 0,
+                    };
+                    fps_change = Some(steps[next]);
+                }
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } if kc == keys.fullscreen => {
+                    if let Err(e) = toggle_fullscreen(&mut window) {
+                        warn!("Could not toggle fullscreen: {}", e);
+                    }
+                    (resized, placed, save_placement) = (true, true, true);
+                }
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    keymod: km,
+                    repeat: false,
                     ..
-                } => break 'running,
+                } if kc == keys.display => {
+                    let step = if km.intersects(*ACC) { -1 } else { 1 };
+                    if let Err(e) = move_to_display(&video_subsystem, &mut window, step) {
+                        warn!("Could not move the output to another display: {}", e);
+                    }
+                    (resized, placed, save_placement) = (true, true, true);
+                }
                 Event::KeyDown {
                     keycode: Some(kc),
                     keymod: km,
@@ -287,199 +1272,1313 @@ pub fn main() -> anyhow::Result<()> {
             }
         }
 
-        // add loop to consume lines from stderr here
-        let mut breaker = 0; // safety breaker to prevent infinite loop in case of issues
-        while let Ok(chunk) = err_rx.try_recv() {
-            breaker += 1;
-            if breaker >= 100000 {
-                break;
-            }
-            if let Ok(s) = String::from_utf8(chunk) {
-                log_lines.push_str(&s);
-            }
+        // fullscreen switches and display moves don't always send a size
+        // event, and a minimized window is 0x0 which no swapchain can be made at
+        let size = window.size();
+        if (resized || size != (canvas_w, canvas_h)) && size.0 > 0 && size.1 > 0 {
+            (canvas_w, canvas_h) = size;
+            unsafe {
+                gfx_lowlevel_gpu_ctx_handle_resize(lowlevel_ctx, canvas_w as i32, canvas_h as i32);
+            }
+        }
+        if placed {
+            if let Err(e) = placement.remember(&video_subsystem, &window) {
+                warn!("Could not remember the output's placement: {}", e);
+            }
+        }
+        if save_placement {
+            if let Err(e) = placement.save() {
+                warn!("Could not save the output's placement: {}", e);
+            }
+        }
+
+        // add loop to consume lines from stderr here
+        let mut breaker = 0; // safety breaker to prevent infinite loop in case of issues
+        while let Ok(chunk) = err_rx.try_recv() {
+            breaker += 1;
+            if breaker >= 100000 {
+                break;
+            }
+            if let Ok(s) = String::from_utf8(chunk) {
+                log_lines.push_str(&s);
+            }
+        }
+
+        let mut full_lines = Vec::new();
+        // Check if we have any newlines to process
+        if let Some(last_newline) = log_lines.rfind('\n') {
+            let (complete, partial) = log_lines.split_at(last_newline + 1);
+            for line in complete.lines() {
+                if !line.is_empty() {
+                    full_lines.push(line.to_string());
+                }
+            }
+            log_lines = partial.to_string();
+        }
+
+        for line in full_lines {
+            reg_events.push(GfxEvent::LogEvent(LogEvent {
+                message: line.to_string(),
+            }));
+        }
+
+        if let Some(preview) = preview.as_mut() {
+            // the output keeps playing while the preview is played with
+            let is_input = |e: &GfxEvent| {
+                matches!(
+                    e,
+                    GfxEvent::KeyEvent(_) | GfxEvent::MidiEvent(_) | GfxEvent::ControllerEvent(_)
+                )
+            };
+            let input = reg_events
+                .iter()
+                .filter(|e| is_input(e))
+                .cloned()
+                .collect::<Vec<_>>();
+            reg_events.retain(|e| !is_input(e));
+            preview.render(frame, &input);
+            if take_preview {
+                if let (Some(from), Some(to)) = (preview.app.as_ref(), try_app.as_ref()) {
+                    match take_settings(from, to) {
+                        Ok(_) => info!("Took preview settings at frame {}", frame),
+                        Err(e) => warn!("Could not take preview settings: {}", e),
+                    }
+                }
+            }
+        }
+
+        if (canvas_w, canvas_h) != last_canvas {
+            reg_events.push(GfxEvent::ResizeEvent(ResizeEvent {
+                mix: None,
+                old: last_canvas,
+                new: (canvas_w, canvas_h),
+                drawable: window.vulkan_drawable_size(),
+            }));
+            last_canvas = (canvas_w, canvas_h);
+            gfx_runtime.rasterize_for_output(window.vulkan_drawable_size());
+        }
+
+        let mut render_start = Instant::now();
+        if let Some(app_runtime) = try_app.as_ref() {
+            app_runtime.update_playback(gfx_runtime.playback_positions()?);
+            let (mut calc_w, mut calc_h) = (canvas_w, canvas_h);
+            if !recording.frame(
+                app_runtime,
+                &mut frame,
+                (&mut calc_w, &mut calc_h),
+                &mut reg_events,
+                guest_crashed,
+            ) {
+                break 'running;
+            }
+            let calc_start = Instant::now();
+            let calculated = match guest_crashed {
+                true => Ok(last_specs.clone()),
+                false => app_runtime.calc(
+                    calc_w,
+                    calc_h,
+                    frame,
+                    gfx_runtime.frames_per_sec,
+                    &reg_events,
+                ),
+            };
+            perf.calc(calc_start.elapsed());
+            let mut specs = match calculated {
+                Ok(specs) if guest_crashed => specs,
+                Ok(specs) => {
+                    last_specs = specs.iter().filter(|s| replayable(s)).cloned().collect();
+                    specs
+                }
+                Err(e) if e.downcast_ref::<CalcTimeout>().is_some() => {
+                    // the frame's a field so the HUD shows one warning
+                    warn!(frame, "Frame skipped, {}", e);
+                    last_specs.clone()
+                }
+                Err(e) => {
+                    error!("Error calculating {:?}", e);
+                    guest_crashed = true;
+                    watchdog.crashed();
+                    if let Some(hud) = hud.as_mut() {
+                        hud.set_error(Some(crash_report(e.as_ref(), app_runtime)));
+                    }
+                    last_specs.clone()
+                }
+            };
+
+            reg_events.clear();
+            // scene mixes go after the app's specs, so their SpecErrorEvent
+            // indices start at the length of what calculate returned
+            gfx_runtime.add_scene(&mut specs);
+            let spec_order = sort_layers(&mut specs);
+            render_start = Instant::now();
+
+            unsafe {
+                if !gfx_lowlevel_gpu_ctx_start_frame(lowlevel_ctx) {
+                    error!("Failed to start frame looping");
+                    continue 'running;
+                }
+
+                gfx_runtime.reset_mix_dispatches(lowlevel_ctx)?;
+                gfx_runtime.clear_letterbox(lowlevel_ctx)?;
+            }
+
+            let mut hud_spans: Vec<TextSpan> = vec![];
+            let mut hud_widgets = perf.widgets(
+                gfx_runtime.frames_per_sec,
+                &queue_depths(&gfx_runtime, audio_runtime.as_ref(), &fetcher),
+            );
+            let mut monitors = vec![];
+            for (pos, spec) in specs.drain(..).enumerate() {
+                // specs the host applies itself rather than the gfx runtime,
+                // batches and macros are taken apart once they've applied
+                let host_spec = match &spec {
+                    RenderSpec::SendMidi(_)
+                    | RenderSpec::HudText(_)
+                    | RenderSpec::HudWidget(_)
+                    | RenderSpec::WindowCtl(_)
+                    | RenderSpec::SendOsc(_)
+                    | RenderSpec::AudioStop(_)
+                    | RenderSpec::AudioGain(_) => spec.clone(),
+                    RenderSpec::AudioPlay(_) | RenderSpec::Monitor(_) | RenderSpec::SetFps(_)
+                        if gfx_runtime.validate(&spec).is_ok() =>
+                    {
+                        spec.clone()
+                    }
+                    _ => RenderSpec::None,
+                };
+                let spec_start = Instant::now();
+                let applied = match gfx_runtime.render(
+                    lowlevel_ctx,
+                    spec.clone(),
+                    frame,
+                    args.dry_run,
+                    args.shader_debug,
+                ) {
+                    Err(e) => {
+                        error!("Error rendering {:?}", e);
+                        try_app.take();
+                        break;
+                    }
+                    // delivered with the next calculate
+                    Ok(Some((kind, message))) => {
+                        reg_events.push(GfxEvent::SpecErrorEvent(SpecErrorEvent {
+                            frame,
+                            index: spec_order[pos],
+                            kind,
+                            message,
+                        }));
+                        false
+                    }
+                    Ok(None) => true,
+                };
+
+                // what a batch or macro sends out of the host goes once the
+                // rest of it has applied, a member that failed stops it all
+                let host_specs = match (&spec, applied) {
+                    (RenderSpec::Batch(_) | RenderSpec::RunMacro(_), true) => {
+                        gfx_runtime.members(&spec)
+                    }
+                    (RenderSpec::Batch(_) | RenderSpec::RunMacro(_), false) => vec![],
+                    _ => vec![host_spec],
+                };
+                #[cfg(feature = "osc")]
+                if let (Some(osc_out), true) = (osc_out.as_ref(), args.osc_mirror_cmds) {
+                    // a batch's commands are among its members, any other
+                    // spec is one itself
+                    for member in host_specs.iter().chain(applied.then_some(&spec)) {
+                        if let RenderSpec::SendCmd(cmd) = member {
+                            if let Err(e) = osc_out.mirror_cmd(cmd) {
+                                warn!("failed to mirror {} over osc: {}", cmd.name, e);
+                            }
+                        }
+                    }
+                }
+                let midi_cmds = host_specs.iter().filter_map(|spec| match spec {
+                    RenderSpec::SendMidi(cmd) => Some(cmd),
+                    _ => None,
+                });
+                for cmd in midi_cmds {
+                    #[cfg(not(feature = "midi"))]
+                    if warned_midi_outs.insert(cmd.event.device.clone()) {
+                        warn!(
+                            "SendMidi to {} needs sdlrig built with the midi feature",
+                            &cmd.event.device
+                        );
+                    }
+                    #[cfg(feature = "midi")]
+                    if let Some(conn) = midi_outs.lock().unwrap().get_mut(&cmd.event.device) {
+                        conn.send(&cmd.to_bytes()).unwrap_or_else(|e| {
+                            warn!(
+                                "failed to send midi message on {}: {}",
+                                &cmd.event.device, e
+                            )
+                        });
+                    } else if warned_midi_outs.insert(cmd.event.device.clone()) {
+                        warn!(
+                            "No midi output {} open, pass it with --midi-output",
+                            &cmd.event.device
+                        );
+                    }
+                }
+
+                for host_spec in &host_specs {
+                    match host_spec {
+                        RenderSpec::WindowCtl(ctl) => window_ctl(&mut window, ctl),
+                        RenderSpec::HudText(text) => {
+                            if !hud_spans.is_empty() {
+                                hud_spans.push(TextSpan::from("\n"));
+                            }
+                            match text.spans.is_empty() {
+                                true => hud_spans.push(TextSpan::from(text.text.as_str())),
+                                // the HUD opens fonts by path
+                                false => hud_spans.extend(text.spans.iter().map(|span| TextSpan {
+                                    font: span.font.as_deref().map(|f| gfx_runtime.font_path(f)),
+                                    ..span.clone()
+                                })),
+                            }
+                        }
+                        RenderSpec::HudWidget(widget) => hud_widgets.push(widget.clone()),
+                        RenderSpec::Monitor(monitor) => monitors.push(monitor.clone()),
+                        RenderSpec::SetFps(set) => fps_change = Some(set.fps),
+                        RenderSpec::AudioPlay(play) => {
+                            let loaded = gfx_runtime.audio_samples(&play.name);
+                            audio_runtime.iter_mut().for_each(|a| match loaded.clone() {
+                                Some(samples) => a.play_loaded(play, samples),
+                                None => a.play(play),
+                            })
+                        }
+                        RenderSpec::AudioStop(stop) => {
+                            audio_runtime.iter_mut().for_each(|a| a.stop(stop))
+                        }
+                        RenderSpec::AudioGain(gain) => {
+                            audio_runtime.iter_mut().for_each(|a| a.gain(gain))
+                        }
+                        #[cfg(not(feature = "osc"))]
+                        RenderSpec::SendOsc(_) if !warned_osc_out => {
+                            warned_osc_out = true;
+                            warn!("SendOsc needs sdlrig built with the osc feature");
+                        }
+                        #[cfg(feature = "osc")]
+                        RenderSpec::SendOsc(msg) => match osc_out.as_ref() {
+                            Some(osc_out) => {
+                                if let Err(e) = osc_out.send(msg) {
+                                    warn!("failed to send osc {}: {}", msg.addr, e);
+                                }
+                            }
+                            None if !warned_osc_out => {
+                                warned_osc_out = true;
+                                warn!("No osc output open, pass it with --osc-out");
+                            }
+                            None => (),
+                        },
+                        _ => (),
+                    }
+                }
+
+                if let RenderSpec::Mix(mix) = &spec {
+                    perf.mix(&mix.name, spec_start.elapsed());
+                    if args.show_mix_time {
+                        // let inst = Duration::from_millis(
+                        //     (f64::from(gfx_runtime.get_present_time_for_mix(&mix.name)?) * 1000.0)
+                        //         as u64,
+                        // );
+                        // let hours = inst.as_secs() / 60 / 60;
+                        // let mins = (inst.as_secs() / 60) % 60;
+                        // let secs = inst.as_secs() % 60;
+                        // let millis = inst.as_millis() % 1000;
+                        // write!(
+                        //     &mut hud_text,
+                        //     "{}@{hours:0>2}:{mins:0>2}:{secs:0>2}.{millis:0>3}\n",
+                        //     mix.name
+                        // )?;
+                    }
+                    for input in &mix.inputs {
+                        match input {
+                            sdlrig::renderspec::MixInput::Video(v) => {
+                                if let Some(event) = gfx_runtime.get_last_frame_event(v)? {
+                                    reg_events.push(GfxEvent::FrameEvent(event));
+                                }
+                            }
+                            sdlrig::renderspec::MixInput::Mixed(_)
+                            | sdlrig::renderspec::MixInput::Tex(..) => (),
+                        }
+                    }
+                }
+            }
+            if let Some(hud) = hud.as_mut() {
+                for e in hud.set_spans(hud_spans) {
+                    warn!("{}", e);
+                    hud.warn(frame, e);
+                }
+                hud.set_widgets(hud_widgets);
+            }
+            let shown = hud
+                .as_mut()
+                .map_or(monitors.is_empty(), |hud| hud.set_monitors(&monitors));
+            if !shown && !warned_monitors {
+                warned_monitors = true;
+                warn!("Monitors are shown on the HUD window, pass --font without --hud-overlay");
+            }
+            #[cfg(feature = "osc")]
+            if let Some(osc_out) = osc_out.as_ref() {
+                if let Err(e) = osc_out.send_transport(
+                    frame,
+                    gfx_runtime.bpm(),
+                    &gfx_runtime.playback_positions()?,
+                ) {
+                    warn!("failed to send transport over osc: {}", e);
+                }
+            }
+        }
+
+        if let Some(app_runtime) = try_app.as_ref() {
+            for (level, message) in app_runtime.take_logs() {
+                if let Some(file) = log_file.as_mut() {
+                    let line = format!("{} [{:?}] {}", Local::now().to_rfc3339(), level, message);
+                    if let Err(e) = writeln!(file, "{}", line) {
+                        warn!("Could not write to the log file, closing it: {}", e);
+                        log_file = None;
+                    }
+                }
+                if let (Some(hud), Some(min)) = (hud.as_mut(), hud_log_level) {
+                    if level >= min {
+                        hud.log(level, &message);
+                    }
+                }
+            }
+        }
+
+        if unsafe { (*lowlevel_ctx).started } {
+            gfx_runtime.run_transitions(lowlevel_ctx, frame);
+            gfx_runtime.upload_texes(lowlevel_ctx, frame);
+        }
+
+        if let Some(audio_runtime) = audio_runtime.as_mut() {
+            audio_runtime.poll();
+        }
+
+        for path in watcher.iter().flat_map(|watcher| watcher.poll()) {
+            let Some(kind) = change_kind(&args.watch_ext, &path) else {
+                continue;
+            };
+            let reloading = match kind {
+                FileChangeKind::Vid => gfx_runtime.reload_vids(&path),
+                // an image can be a Vid as well as a Tex
+                FileChangeKind::Tex => {
+                    let mut reloading = gfx_runtime.reload_texes();
+                    reloading.extend(gfx_runtime.reload_vids(&path));
+                    reloading
+                }
+                FileChangeKind::Lut => {
+                    gfx_runtime.forget_lut(&path);
+                    gfx_runtime.reload_luts(&path)
+                }
+                FileChangeKind::Shader => gfx_runtime.reload_shaders(&path),
+                FileChangeKind::Other => vec![],
+            };
+            if let Some(guest_path) = guest_path(&preopens, &path) {
+                reg_events.push(GfxEvent::FileChanged(FileChangeEvent {
+                    path: guest_path,
+                    kind,
+                    reloading,
+                }));
+            }
+        }
+        let asset_events = gfx_runtime.poll_asset_events();
+        reg_events.extend(asset_events.into_iter().map(GfxEvent::AssetEvent));
+        reg_events.extend(
+            gfx_runtime
+                .poll_asset_reloads()
+                .into_iter()
+                .map(GfxEvent::AssetReloaded),
+        );
+        // covers runtime loads as well as assets swapped in by a reload
+        if let Some(app_runtime) = try_app.as_ref() {
+            match app_runtime.update_gfx_info(&gfx_runtime.gfx_info()) {
+                Ok(events) => reg_events.extend(events),
+                Err(e) => warn!("Could not share updated gfx info: {}", e),
+            }
+        }
+
+        if let Some(app_runtime) = try_app.as_ref() {
+            for url in app_runtime.take_fetch_requests() {
+                fetcher.request(url);
+            }
+            if app_runtime.take_capture_devices_request() {
+                capture_lister.request();
+            }
+            for pattern in app_runtime.take_glob_watch_requests() {
+                glob_watcher.watch(&pattern);
+            }
+            for req in app_runtime.take_readback_requests() {
+                gfx_runtime.request_readback(req);
+            }
+        }
+        if unsafe { (*lowlevel_ctx).started } {
+            let readbacks = gfx_runtime.poll_readbacks(lowlevel_ctx, frame);
+            reg_events.extend(readbacks.into_iter().map(GfxEvent::ReadbackEvent));
+        }
+        if let Some(hud) = hud.as_mut() {
+            if unsafe { (*lowlevel_ctx).started } {
+                let mut rgba = vec![];
+                for mix in hud.monitors_due(frame) {
+                    match gfx_runtime.download_rgba(lowlevel_ctx, Some(&mix), &mut rgba) {
+                        Ok(Some((w, h))) => hud.update_monitor(&mix, &rgba, w, h),
+                        Ok(None) => (),
+                        Err(e) => warn!("Could not download {} to monitor: {}", mix, e),
+                    }
+                }
+            }
+        }
+        reg_events.extend(fetcher.poll().into_iter().map(GfxEvent::FetchEvent));
+        reg_events.extend(capture_lister.poll().map(GfxEvent::CaptureDevices));
+        reg_events.extend(glob_watcher.poll().into_iter().map(GfxEvent::GlobMatched));
+
+        if let Some(share) = output_share.as_mut() {
+            if unsafe { (*lowlevel_ctx).started } {
+                if let Err(e) = share.publish(&gfx_runtime, lowlevel_ctx) {
+                    warn!("Output sharing failed, disabling: {}", e);
+                    output_share = None;
+                }
+            }
+        }
+
+        if !unsafe { (*lowlevel_ctx).started } {
+            for call in screenshots.drain(..) {
+                call.reply(RemoteReply::Error("Nothing has been rendered yet".into()));
+            }
+        } else if !screenshots.is_empty() || !shooting.is_empty() {
+            match take_screenshot(
+                &gfx_runtime,
+                lowlevel_ctx,
+                &mut screenshot_download,
+                &mut shooting,
+                &mut screenshots,
+            ) {
+                Ok(()) => (),
+                Err(e) => {
+                    for call in shooting.drain(..).chain(screenshots.drain(..)) {
+                        call.reply(RemoteReply::Error(e.to_string()));
+                    }
+                    // started over for the next request
+                    screenshot_download = None;
+                }
+            }
+        }
+
+        // after sharing so the overlay stays off the shared output
+        if let Some(Err(e)) = hud
+            .as_mut()
+            .map(|hud| hud.draw_overlay(lowlevel_ctx, frame))
+        {
+            error!("HUD failed, closing it: {}", e);
+            hud = None;
+        }
+
+        gfx_runtime.set_last_frame_rendered(frame);
+        let present_start = Instant::now();
+        unsafe {
+            match gfx_lowlevel_gpu_ctx_finish_frame(lowlevel_ctx) {
+                0 => (),
+                err => panic!("Failed to finish frame {}", err),
+            }
+        }
+        let present_us = present_start.elapsed().as_micros() as u64;
+        let render_us = present_start.duration_since(render_start).as_micros() as u64;
+        // sync video
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        // with vsync the display is the clock, and a session replay doesn't
+        // skip frames its inputs might land on
+        let frames_elapsed = match args.pacing {
+            _ if recording.replaying_session() || benchmark.is_some() => 1,
+            Pacing::Vsync => 1,
+            _ => ((current_time.as_nanos() / ns_per_frame) as i64 - frame).max(1),
+        };
+        for (name, us) in gfx_runtime.take_decode_times() {
+            perf.decode(&name, us);
+        }
+        perf.frame(render_us, present_us, frames_elapsed - 1);
+        if let (Some(bench), Some(_)) = (benchmark.as_mut(), try_app.as_ref()) {
+            if bench.frame(perf.last()) {
+                let report = bench.report(&args.wasm, gfx_runtime.frames_per_sec);
+                let written = serde_json::to_string_pretty(&report)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| match bench.spec.report.as_ref() {
+                        Some(path) => Ok(fs::write(path, json)?),
+                        None => {
+                            println!("{}", json);
+                            Ok(())
+                        }
+                    });
+                if let Err(e) = written {
+                    error!("Could not write the benchmark report: {}", e);
+                }
+                break 'running;
+            }
+        }
+        if try_app.is_some() {
+            reg_events.push(GfxEvent::FramePacingEvent(FramePacingEvent {
+                frame,
+                dropped: frames_elapsed - 1,
+                render_us,
+                present_us,
+            }));
+        }
+        if let Some(Err(e)) = hud.as_mut().map(|hud| hud.present(frame)) {
+            error!("HUD failed, closing it: {}", e);
+            hud = None;
+        }
+        frame += frames_elapsed as i64;
+        if let Some(fps) = fps_change
+            .take()
+            .filter(|fps| *fps != gfx_runtime.frames_per_sec)
+        {
+            if recording.active() {
+                warn!("The frame rate can't change while recording or replaying");
+            } else {
+                // the same point in time counted in the new frames
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                ns_per_frame = 1_000_000_000u128 / fps as u128;
+                frame = (now.as_nanos() / ns_per_frame) as i64 + 1;
+                gfx_runtime.frames_per_sec = fps;
+                gfx_runtime.set_last_frame_rendered(frame - 1);
+                if let Some(preview) = preview.as_mut() {
+                    preview.gfx_runtime.frames_per_sec = fps;
+                    preview.gfx_runtime.set_last_frame_rendered(frame - 1);
+                }
+                if let Some(hud) = hud.as_mut() {
+                    hud.set_frames_per_sec(fps);
+                }
+                if let Some(share) = output_share.as_mut() {
+                    share.set_frames_per_sec(fps);
+                }
+                // reloads get it too
+                args.fps = fps;
+                info!("Running at {} fps", fps);
+            }
+        }
+        let next_time = Duration::from_nanos(frame as u64 * ns_per_frame as u64);
+
+        // a replay renders every recorded frame rather than keeping time, and
+        // a benchmark every frame as fast as it can
+        if !recording.replaying() && benchmark.is_none() {
+            wait_until(args.pacing, next_time);
+        }
+
+        if fs::metadata(&args.wasm).unwrap().modified().unwrap() > last_loaded_wasm {
+            last_loaded_wasm = SystemTime::now();
+            info!("Autoloading wasm at: {}", Local::now().to_rfc3339());
+            loader.start(
+                &args.wasm,
+                preopens.clone(),
+                try_app.as_ref().and_then(|app| Some(app.clone())),
+                args.fps,
+                calc_budget,
+            );
+            if let Some(preview) = preview.as_mut() {
+                preview.loader.start(
+                    &args.wasm,
+                    preopens.clone(),
+                    preview.app.clone(),
+                    args.fps,
+                    calc_budget,
+                );
+            }
+        }
+
+        if let Some(path) = args.config.clone() {
+            let modified = fs::metadata(&path).and_then(|m| m.modified());
+            if modified.map_or(false, |m| m > last_loaded_config) {
+                last_loaded_config = SystemTime::now();
+                // only what can change without reopening windows, ports or the app
+                let reloaded = parse_args().and_then(|new| Ok((HostKeys::new(&new)?, new)));
+                match reloaded {
+                    Ok((new_keys, new)) => {
+                        keys = new_keys;
+                        if new.bpm != args.bpm {
+                            tempo.set_bpm(new.bpm);
+                        }
+                        hud_log_level = new.hud_log_level.into();
+                        logger.set_filter(new.log_level.clone());
+                        if let Some(hud) = hud.as_mut() {
+                            hud.set_opacity(new.hud_opacity);
+                        }
+                        fetcher.set_allow(new.fetch_allow.clone());
+                        assetcache::set_proxy(new.proxy.clone());
+                        watchdog.max_restarts = new.max_restarts;
+                        // applies from the next load
+                        calc_budget = Duration::from_millis(new.calc_budget_ms);
+                        texruntime::set_memory_budget(new.tex_memory_mb << 20);
+                        args.bpm = new.bpm;
+                        args.settings_save_secs = new.settings_save_secs;
+                        args.show_mix_time = new.show_mix_time;
+                        args.shader_debug = new.shader_debug;
+                        #[cfg(feature = "osc")]
+                        {
+                            args.osc_mirror_cmds = new.osc_mirror_cmds;
+                        }
+                        args.drop_load_vids = new.drop_load_vids;
+                        args.watch_ext = new.watch_ext.clone();
+                        args.pacing = usable_pacing(new.pacing, present_mode);
+                        if let Some(hud) = hud.as_mut() {
+                            hud.set_info(Some(pacing_info(present_mode, args.pacing)));
+                        }
+                        if let Some(controllers) = controllers.as_mut() {
+                            controllers.set_deadzone(new.controller_deadzone);
+                        }
+                        info!("Reloaded {}", path.display());
+                    }
+                    Err(e) => warn!("Could not reload {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+
+    //cleanup
+    if let Err(e) = placement
+        .remember(&video_subsystem, &window)
+        .and_then(|_| placement.save())
+    {
+        warn!("Could not save the output's placement: {}", e);
+    }
+    if let (Some(app), Some(path)) = (try_app.as_ref(), args.settings_file.as_ref()) {
+        if !guest_crashed && !recording.replaying() {
+            if let Err(e) = app.save_settings_file(path) {
+                warn!("Could not save settings to {}: {}", path.display(), e);
+            }
+        }
+    }
+    if let Some(app) = try_app.take() {
+        drop(app);
+    }
+    if let Some(preview) = preview.take() {
+        preview.destroy();
+    }
+    // the overlay's frame and the share's download were made on the context,
+    // and plugins may have used it too
+    drop(hud);
+    drop(output_share);
+    drop(gfx_runtime);
+    plugins::uninstall_all();
+    unsafe {
+        gfx_lowlevel_gpu_ctx_destroy((&mut lowlevel_ctx) as *mut *mut gfx_lowlevel_gpu_ctx);
+    }
+    Ok(())
+}
+
+// --dry-run loads the app here rather than on the loader's thread so every
+// failure ends up in the report, then runs it on a fixed clock
+fn lint(
+    args: &Args,
+    preopens: &[Preopen],
+    gfx_runtime: &GfxRuntime,
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    canvas_w: u32,
+    canvas_h: u32,
+    calc_budget: Duration,
+) -> LintReport {
+    let mut report = LintReport::new(&args.wasm, args.lint_frames);
+    if let Some(manifest) = manifest::installed() {
+        for found in manifest.verify() {
+            report.error(Stage::Manifest, None, Some(found.path), found.message);
+        }
+    }
+    let (app, loaded_gfx_data) =
+        match AppRuntime::load(&args.wasm, preopens, None, args.fps, calc_budget) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                report.error(Stage::Load, None, None, format!("{:#}", e));
+                return report;
+            }
+        };
+    for (name, e) in app.asset_errors() {
+        report.error(Stage::Asset, None, Some(name.clone()), e.clone());
+    }
+    for (_, gfx_data) in loaded_gfx_data {
+        gfx_runtime.add(gfx_data.info(), gfx_data);
+    }
+    gfx_runtime.defer(app.deferred_assets());
+    for (name, e) in gfx_runtime.prepare_all(lowlevel_ctx) {
+        report.error(Stage::Prepare, None, Some(name), e);
+    }
+    app.set_fixed_clock(true);
+
+    let mut reg_events = vec![GfxEvent::ReloadEvent()];
+    for frame in 1..=args.lint_frames {
+        let mut specs = match app.calc(canvas_w, canvas_h, frame, args.fps, &reg_events) {
+            Ok(specs) => specs,
+            Err(e) => {
+                report.error(
+                    Stage::Calculate,
+                    Some(frame),
+                    None,
+                    crash_report(e.as_ref(), &app),
+                );
+                break;
+            }
+        };
+        reg_events.clear();
+        for (level, message) in app.take_logs() {
+            match level {
+                LogLevel::Error => report.error(Stage::Log, Some(frame), None, message),
+                LogLevel::Warn => report.warning(Stage::Log, Some(frame), None, message),
+                _ => (),
+            }
+        }
+
+        gfx_runtime.add_scene(&mut specs);
+        let spec_order = sort_layers(&mut specs);
+        unsafe {
+            if !gfx_lowlevel_gpu_ctx_start_frame(lowlevel_ctx) {
+                report.error(Stage::Spec, Some(frame), None, "Could not start a frame");
+                break;
+            }
+        }
+        for (pos, spec) in specs.into_iter().enumerate() {
+            let index = spec_order[pos];
+            let subject = Some(format!("spec {}", index));
+            if let Err(e) = gfx_runtime.validate(&spec) {
+                report.error(Stage::Spec, Some(frame), subject, e.to_string());
+                continue;
+            }
+            match gfx_runtime.render(lowlevel_ctx, spec, frame, true, args.shader_debug) {
+                Ok(None) => (),
+                Ok(Some((kind, message))) => {
+                    report.error(
+                        Stage::Spec,
+                        Some(frame),
+                        subject,
+                        format!("{:?}: {}", kind, message),
+                    );
+                    reg_events.push(GfxEvent::SpecErrorEvent(SpecErrorEvent {
+                        frame,
+                        index,
+                        kind,
+                        message,
+                    }));
+                }
+                Err(e) => report.error(Stage::Spec, Some(frame), subject, e.to_string()),
+            }
+        }
+        gfx_runtime.set_last_frame_rendered(frame);
+        unsafe {
+            if gfx_lowlevel_gpu_ctx_finish_frame(lowlevel_ctx) != 0 {
+                report.error(Stage::Spec, Some(frame), None, "Could not finish a frame");
+                break;
+            }
+        }
+
+        for event in gfx_runtime.poll_asset_events() {
+            if let Some(e) = event.error.as_ref() {
+                report.error(
+                    Stage::Asset,
+                    Some(frame),
+                    Some(event.name.clone()),
+                    e.clone(),
+                );
+            }
+            reg_events.push(GfxEvent::AssetEvent(event));
+        }
+        for event in gfx_runtime.poll_asset_reloads() {
+            reg_events.push(GfxEvent::AssetReloaded(event));
+        }
+        match app.update_gfx_info(&gfx_runtime.gfx_info()) {
+            Ok(events) => reg_events.extend(events),
+            Err(e) => report.error(Stage::Asset, Some(frame), None, e.to_string()),
+        }
+    }
+    report
+}
+
+// The trap and what the guest last wrote to stderr, which is where a panic's
+// message ends up
+fn crash_report(e: &dyn std::error::Error, app: &AppRuntime) -> String {
+    let mut report = format!("App crashed, showing its last frame until reload\n{}", e);
+    let mut source = e.source();
+    while let Some(cause) = source {
+        report.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    for line in app.guest_stderr() {
+        report.push('\n');
+        report.push_str(&line);
+    }
+    report
+}
+
+// The Vulkan devices in the order --gpu indexes them
+fn gpu_names() -> anyhow::Result<Vec<String>> {
+    let count = unsafe { gfx_lowlevel_gpu_name(-1, std::ptr::null_mut(), 0) };
+    if count < 0 {
+        anyhow::bail!("Could not list the Vulkan devices");
+    }
+    (0..count)
+        .map(|index| {
+            let mut name = [0 as c_char; 256];
+            if unsafe { gfx_lowlevel_gpu_name(index, name.as_mut_ptr(), name.len()) } < 0 {
+                anyhow::bail!("Could not get the name of Vulkan device {}", index);
+            }
+            Ok(unsafe { CStr::from_ptr(name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned())
+        })
+        .collect()
+}
+
+// --gpu as an index, or the first device with it in its name
+fn select_gpu(gpu: &str) -> anyhow::Result<CString> {
+    let names = gpu_names()?;
+    let found = match gpu.parse::<usize>() {
+        Ok(index) => names.get(index),
+        Err(_) => names
+            .iter()
+            .find(|name| name.to_lowercase().contains(&gpu.to_lowercase())),
+    };
+    match found {
+        Some(name) => Ok(CString::new(name.as_str())?),
+        None => anyhow::bail!("No GPU {}, the devices are {:?}", gpu, names),
+    }
+}
+
+// Vsync pacing relies on fifo blocking, other modes would run unpaced
+fn usable_pacing(pacing: Pacing, present_mode: PresentMode) -> Pacing {
+    if pacing == Pacing::Vsync && present_mode != PresentMode::Fifo {
+        warn!("Vsync pacing needs fifo presentation, sleeping instead");
+        return Pacing::Sleep;
+    }
+    pacing
+}
+
+fn pacing_info(present_mode: PresentMode, pacing: Pacing) -> String {
+    format!("{:?} present, {:?} pacing", present_mode, pacing).to_lowercase()
+}
+
+// until is since the epoch, like the frame clock
+fn wait_until(pacing: Pacing, until: Duration) {
+    let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    match pacing {
+        Pacing::Vsync => (),
+        Pacing::Sleep => {
+            if let Some(left) = until.checked_sub(now()) {
+                thread::sleep(left);
+            }
+        }
+        Pacing::Spin => {
+            if let Some(left) = until.checked_sub(now() + SPIN_MARGIN) {
+                thread::sleep(left);
+            }
+            while now() < until {
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
+
+// None for files that don't look like video
+fn dropped_vid(path: &str) -> Option<Vid> {
+    let path = Path::new(path);
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    if !VID_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    Some(
+        Vid::builder()
+            .name(name)
+            .path(path.to_str()?)
+            .repeat(true)
+            .build(),
+    )
+}
+
+// Work waiting on other threads, for the perf overlay and --remote
+fn queue_depths(
+    gfx_runtime: &GfxRuntime,
+    audio_runtime: Option<&AudioRuntime>,
+    fetcher: &Fetcher,
+) -> Vec<(&'static str, usize)> {
+    vec![
+        ("asset loads", gfx_runtime.pending_loads()),
+        ("audio decodes", audio_runtime.map_or(0, |a| a.pending())),
+        ("fetches", fetcher.pending()),
+    ]
+}
+
+// The remote requests only the app can answer
+fn app_reply(app: &AppRuntime, request: &RemoteRequest) -> RemoteReply {
+    let reply = match request {
+        RemoteRequest::Settings => app.settings_version().and_then(|version| {
+            app.extract_settings()
+                .map(|bytes| RemoteReply::Settings { version, bytes })
+        }),
+        RemoteRequest::SetSettings { version, bytes } => app
+            .migrate_settings(bytes, *version)
+            .map(|_| RemoteReply::Ok),
+        RemoteRequest::Schema => app.settings_schema().map(RemoteReply::Schema),
+        _ => Ok(RemoteReply::Error(format!(
+            "{:?} isn't for the app",
+            request
+        ))),
+    };
+    reply.unwrap_or_else(|e| RemoteReply::Error(e.to_string()))
+}
+
+// Specs that draw the scene rather than act once, safe to render again on a
+// frame calculate didn't finish
+// Remote screenshots that share one download of the window
+const SCREENSHOTS_PER_FRAME: usize = 4;
+
+// Answers the screenshots shooting once their download is done, then
+// downloads the frame just rendered for the next of those waiting
+fn take_screenshot(
+    gfx_runtime: &GfxRuntime,
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    download: &mut Option<FrameDownload>,
+    shooting: &mut Vec<RemoteCall>,
+    waiting: &mut Vec<RemoteCall>,
+) -> anyhow::Result<()> {
+    let shot = match download.as_mut() {
+        Some(shot) => shot,
+        None => download.insert(FrameDownload::new(lowlevel_ctx)?),
+    };
+    if !shooting.is_empty() {
+        let mut rgba = vec![];
+        let Some((w, h)) = shot.poll(&mut rgba)? else {
+            return Ok(());
+        };
+        for call in shooting.drain(..) {
+            let RemoteRequest::Screenshot { width } = call.request else {
+                continue;
+            };
+            let width = width.clamp(1, w);
+            let height = (h * width / w).max(1);
+            call.reply(RemoteReply::Screenshot {
+                width,
+                height,
+                rgba: downsample_rgba(&rgba, w, h, width, height),
+            });
+        }
+    }
+    if !waiting.is_empty() && gfx_runtime.start_download(lowlevel_ctx, None, shot)? {
+        let taken = waiting.len().min(SCREENSHOTS_PER_FRAME);
+        shooting.extend(waiting.drain(..taken));
+    }
+    Ok(())
+}
+
+fn replayable(spec: &RenderSpec) -> bool {
+    matches!(
+        spec,
+        RenderSpec::Mix(_)
+            | RenderSpec::DrawText(_)
+            | RenderSpec::DrawShape(_)
+            | RenderSpec::HudText(_)
+            | RenderSpec::HudWidget(_)
+            | RenderSpec::Monitor(_)
+            | RenderSpec::Viewport(_)
+    )
+}
+
+// A resize comes back through the event pump as SizeChanged, which resizes
+// the swapchain and the canvas passed to calculate
+fn window_ctl(window: &mut Window, ctl: &WindowCtl) {
+    if let Some(title) = &ctl.title {
+        if let Err(e) = window.set_title(title) {
+            warn!("Could not set window title {}: {}", title, e);
         }
+    }
+    if let Some((w, h)) = ctl.size {
+        if let Err(e) = window.set_size(w, h) {
+            warn!("Could not resize window to {}x{}: {}", w, h, e);
+        }
+    }
+    if let Some((x, y)) = ctl.position {
+        window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+    }
+    if let Some(always_on_top) = ctl.always_on_top {
+        window.set_always_on_top(always_on_top);
+    }
+    if let Some(borderless) = ctl.borderless {
+        window.set_bordered(!borderless);
+    }
+}
 
-        let mut full_lines = Vec::new();
-        // Check if we have any newlines to process
-        if let Some(last_newline) = log_lines.rfind('\n') {
-            let (complete, partial) = log_lines.split_at(last_newline + 1);
-            for line in complete.lines() {
-                if !line.is_empty() {
-                    full_lines.push(line.to_string());
-                }
+// Restarts a crashed app from the module cache, waiting twice as long after
+// each restart that crashes again. Gives up after max_restarts in a row until
+// the wasm changes, and forgets earlier restarts once an app stays up.
+struct Watchdog {
+    max_restarts: u32,
+    restarts: u32,
+    restart_at: Option<Instant>,
+    restarting: bool,
+    healthy_since: Instant,
+}
+
+impl Watchdog {
+    fn new(max_restarts: u32) -> Self {
+        Self {
+            max_restarts,
+            restarts: 0,
+            restart_at: None,
+            restarting: false,
+            healthy_since: Instant::now(),
+        }
+    }
+
+    fn crashed(&mut self) {
+        self.restarting = false;
+        self.restart_at = match self.restarts < self.max_restarts {
+            true => {
+                // max_restarts can be large enough to overflow the doubling
+                let backoff = 2u32
+                    .checked_pow(self.restarts)
+                    .map_or(MAX_RESTART_BACKOFF, |m| {
+                        RESTART_BACKOFF.saturating_mul(m).min(MAX_RESTART_BACKOFF)
+                    });
+                Some(Instant::now() + backoff)
             }
-            log_lines = partial.to_string();
+            false => None,
+        };
+    }
+
+    // True once when the backoff is up
+    fn due(&mut self) -> bool {
+        if self.restart_at.map_or(false, |at| Instant::now() >= at) {
+            self.restart_at = None;
+            self.restarting = true;
+            self.restarts += 1;
+            return true;
         }
+        false
+    }
 
-        for line in full_lines {
-            reg_events.push(GfxEvent::LogEvent(LogEvent {
-                message: line.to_string(),
-            }));
+    // An app loaded, either the restart or a new wasm
+    fn loaded(&mut self) {
+        if !self.restarting {
+            self.restarts = 0;
         }
+        self.restarting = false;
+        self.restart_at = None;
+        self.healthy_since = Instant::now();
+    }
 
-        if let Some(app_runtime) = try_app.as_ref() {
-            let mut specs = match app_runtime.calc(
-                canvas_w,
-                canvas_h,
-                frame,
-                gfx_runtime.frames_per_sec,
-                &reg_events,
-            ) {
-                Ok(specs) => specs,
-                Err(e) => {
-                    eprintln!("Error calculating {:?}", e);
-                    try_app.take();
-                    continue;
-                }
-            };
+    fn healthy(&mut self) {
+        if self.restarts > 0 && self.healthy_since.elapsed() >= RESTART_FORGET {
+            self.restarts = 0;
+        }
+    }
 
-            reg_events.clear();
+    fn status(&self, crashed: bool) -> Option<String> {
+        match (crashed, self.restart_at) {
+            (true, Some(at)) => Some(format!(
+                "Restarting app in {:.1}s, restart {} of {}",
+                at.saturating_duration_since(Instant::now()).as_secs_f64(),
+                self.restarts + 1,
+                self.max_restarts
+            )),
+            (true, None) if self.restarting => Some("Restarting app".into()),
+            (true, None) => Some(format!(
+                "Gave up after {} restarts, waiting for the wasm to change",
+                self.restarts
+            )),
+            (false, _) if self.restarts > 0 => {
+                Some(format!("App restarted {} times", self.restarts))
+            }
+            (false, _) => None,
+        }
+    }
+}
 
-            unsafe {
-                if !gfx_lowlevel_gpu_ctx_start_frame(lowlevel_ctx) {
-                    eprintln!("Failed to start frame looping");
-                    continue 'running;
-                }
+// Copies the preview's settings into the output's app
+fn take_settings(from: &AppRuntime, to: &AppRuntime) -> anyhow::Result<()> {
+    let (version, bytes) = from
+        .settings_version()
+        .and_then(|v| from.extract_settings().map(|bytes| (v, bytes)))
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    to.migrate_settings(&bytes, version)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
 
-                gfx_runtime.reset_mix_dispatches(lowlevel_ctx)?;
-            }
+// A second instance of the app with its own store, settings, gpu context and
+// assets, drawn to its own window. Only its specs that draw are applied, it
+// can't send midi, play audio, fetch or read back.
+struct Preview {
+    window: Window,
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    gfx_runtime: GfxRuntime,
+    loader: RuntimeLoader,
+    app: Option<Arc<AppRuntime>>,
+}
 
-            for spec in specs.drain(..) {
-                if let RenderSpec::SendMidi(cmd) = &spec {
-                    let mut bytes: [u8; 3] = [0; 3]; // Placeholder for actual MIDI message bytes
-                    bytes[0] = (cmd.event.kind & 0xF0) | (cmd.event.channel & 0x0F);
-                    bytes[1] = cmd.event.key;
-                    bytes[2] = cmd.event.velocity;
-                    if let Some(conn) = midi_outs.get_mut(&cmd.event.device) {
-                        conn.send(&bytes).unwrap_or_else(|e| {
-                            eprintln!(
-                                "failed to send midi message on {}: {}",
-                                &cmd.event.device, e
-                            )
-                        });
-                    }
-                }
+impl Preview {
+    fn new(
+        video: &VideoSubsystem,
+        width: u32,
+        height: u32,
+        frames_per_sec: i64,
+        frame: i64,
+        // the same device as the output
+        gpu: *const c_char,
+    ) -> anyhow::Result<Self> {
+        let window = video
+            .window("Preview", width, height)
+            .vulkan()
+            .resizable()
+            .build()?;
+        let lowlevel_ctx = unsafe { gfx_lowlevel_gpu_ctx_init(window.raw() as *mut _, gpu) };
+        if lowlevel_ctx.is_null() {
+            anyhow::bail!("Failed to initialize the preview's lowlevel_ctx");
+        }
+        Ok(Self {
+            window,
+            lowlevel_ctx,
+            gfx_runtime: GfxRuntime::new(frames_per_sec, frame - 1),
+            loader: RuntimeLoader::new(None),
+            app: None,
+        })
+    }
 
-                match gfx_runtime.render(
-                    lowlevel_ctx,
-                    spec.clone(),
-                    frame,
-                    args.dry_run,
-                    args.shader_debug,
-                ) {
-                    Err(e) => {
-                        eprintln!("Error rendering {:?}", e);
-                        try_app.take();
-                        break;
-                    }
-                    _ => (),
-                }
+    fn handle_resize(&self) {
+        let (w, h) = self.window.size();
+        unsafe { gfx_lowlevel_gpu_ctx_handle_resize(self.lowlevel_ctx, w as i32, h as i32) };
+    }
 
-                if let RenderSpec::Mix(mix) = &spec {
-                    if args.show_mix_time {
-                        // let inst = Duration::from_millis(
-                        //     (f64::from(gfx_runtime.get_present_time_for_mix(&mix.name)?) * 1000.0)
-                        //         as u64,
-                        // );
-                        // let hours = inst.as_secs() / 60 / 60;
-                        // let mins = (inst.as_secs() / 60) % 60;
-                        // let secs = inst.as_secs() % 60;
-                        // let millis = inst.as_millis() % 1000;
-                        // write!(
-                        //     &mut hud_text,
-                        //     "{}@{hours:0>2}:{mins:0>2}:{secs:0>2}.{millis:0>3}\n",
-                        //     mix.name
-                        // )?;
-                    }
-                    for input in &mix.inputs {
-                        match input {
-                            sdlrig::renderspec::MixInput::Video(v) => {
-                                if let Some(event) = gfx_runtime.get_last_frame_event(v)? {
-                                    reg_events.push(GfxEvent::FrameEvent(event));
-                                }
-                            }
-                            sdlrig::renderspec::MixInput::Mixed(_) => (),
-                        }
-                    }
-                }
-            }
+    fn try_finish(&mut self, dry_run: bool, frame: i64) {
+        let (w, h) = self.window.size();
+        let (app, _) = self.loader.try_finish(
+            false,
+            w,
+            h,
+            &self.gfx_runtime,
+            self.app.take(),
+            frame,
+            dry_run,
+            self.lowlevel_ctx,
+        );
+        self.app = app;
+    }
+
+    fn render(&mut self, frame: i64, events: &[GfxEvent]) {
+        let Some(app) = self.app.as_ref() else {
+            return;
+        };
+        // nothing the preview asks for leaves its window
+        app.take_logs();
+        app.take_fetch_requests();
+        app.take_capture_devices_request();
+        app.take_readback_requests();
+        self.gfx_runtime.poll_asset_events();
+        self.gfx_runtime.poll_asset_reloads();
+        if let Err(e) = app.update_gfx_info(&self.gfx_runtime.gfx_info()) {
+            warn!("Could not share updated gfx info with the preview: {}", e);
         }
 
-        gfx_runtime.set_last_frame_rendered(frame);
+        let (w, h) = self.window.size();
+        let mut specs = match app.calc(w, h, frame, self.gfx_runtime.frames_per_sec, events) {
+            Ok(specs) => specs,
+            Err(e) => {
+                // not called again until the next reload
+                error!("Error calculating the preview {:?}", e);
+                self.app = None;
+                return;
+            }
+        };
+        self.gfx_runtime.add_scene(&mut specs);
+        sort_layers(&mut specs);
         unsafe {
-            match gfx_lowlevel_gpu_ctx_finish_frame(lowlevel_ctx) {
-                0 => (),
-                err => panic!("Failed to finish frame {}", err),
+            if !gfx_lowlevel_gpu_ctx_start_frame(self.lowlevel_ctx) {
+                error!("Failed to start preview frame");
+                return;
+            }
+            if let Err(e) = self
+                .gfx_runtime
+                .reset_mix_dispatches(self.lowlevel_ctx)
+                .and_then(|_| self.gfx_runtime.clear_letterbox(self.lowlevel_ctx))
+            {
+                error!("Error starting preview frame {:?}", e);
             }
         }
-        // sync video
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let frames_elapsed = ((current_time.as_nanos() / ns_per_frame) as i64 - frame).max(1);
-        frame += frames_elapsed as i64;
-        let next_time = Duration::from_nanos(frame as u64 * ns_per_frame as u64);
-
-        if next_time.gt(&current_time) {
-            ::std::thread::sleep(next_time.checked_sub(current_time).unwrap());
+        for spec in specs {
+            if let Err(e) = self
+                .gfx_runtime
+                .render(self.lowlevel_ctx, spec, frame, false, false)
+            {
+                error!("Error rendering preview {:?}", e);
+                break;
+            }
         }
-
-        if fs::metadata(&args.wasm).unwrap().modified().unwrap() > last_loaded_wasm {
-            last_loaded_wasm = SystemTime::now();
-            println!("Autoloading wasm at: {}", Local::now().to_rfc3339());
-            loader.start(
-                &args.wasm,
-                &args.preopen_dir,
-                try_app.as_ref().and_then(|app| Some(app.clone())),
-                args.fps,
-                args.dry_run,
-            );
+        self.gfx_runtime.run_transitions(self.lowlevel_ctx, frame);
+        self.gfx_runtime.upload_texes(self.lowlevel_ctx, frame);
+        self.gfx_runtime.set_last_frame_rendered(frame);
+        unsafe {
+            match gfx_lowlevel_gpu_ctx_finish_frame(self.lowlevel_ctx) {
+                0 => (),
+                err => error!("Failed to finish preview frame {}", err),
+            }
         }
     }
 
-    //cleanup
-    if let Some(app) = try_app.take() {
-        drop(app);
-    }
-    drop(gfx_runtime);
-    unsafe {
-        gfx_lowlevel_gpu_ctx_destroy((&mut lowlevel_ctx) as *mut *mut gfx_lowlevel_gpu_ctx);
+    // The app and gpu resources go before the context they were made on
+    fn destroy(mut self) {
+        self.app = None;
+        drop(self.gfx_runtime);
+        unsafe {
+            gfx_lowlevel_gpu_ctx_destroy(
+                (&mut self.lowlevel_ctx) as *mut *mut gfx_lowlevel_gpu_ctx,
+            );
+        }
     }
-    Ok(())
 }
 
 struct RuntimeLoader {
     handle: Option<JoinHandle<(AppRuntime, HashMap<String, GfxData>)>>,
+    // the preview's loader has none, so only the output sends midi
+    midi_sink: Option<MidiSink>,
+    // imported into the next app instead of the previous app's settings
+    restore_settings: Option<(u32, Vec<u8>)>,
 }
 
 impl RuntimeLoader {
-    fn new() -> Self {
-        Self { handle: None }
+    fn new(midi_sink: Option<MidiSink>) -> Self {
+        Self {
+            handle: None,
+            midi_sink,
+            restore_settings: None,
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    fn restore_settings(&mut self, settings: Option<(u32, Vec<u8>)>) {
+        self.restore_settings = settings;
     }
 
     fn start<T: AsRef<Path>>(
         &mut self,
         path: T,
-        preopen_dir: T,
+        preopens: Vec<Preopen>,
         cached: Option<Arc<AppRuntime>>,
         frames_per_second: i64,
-        dry_run: bool,
+        calc_budget: Duration,
     ) {
         if self.handle.is_some() {
             return;
         }
 
         let path: PathBuf = PathBuf::from(path.as_ref());
-        let preopen_dir: PathBuf = PathBuf::from(preopen_dir.as_ref());
         self.handle = Some(thread::spawn(move || -> _ {
             let cached_assets = cached.as_ref().map(|ar| ar.loaded_asset_info().clone());
             match AppRuntime::load(
                 &path,
-                &preopen_dir,
+                &preopens,
                 cached_assets.as_ref().map(|ca| ca.as_ref()),
                 frames_per_second,
-                dry_run,
+                calc_budget,
             ) {
                 Ok((app, loaded_gfx_data)) => {
-                    println!("Built at: {}", Local::now().to_rfc3339());
+                    info!("Built at: {}", Local::now().to_rfc3339());
                     (app, loaded_gfx_data)
                 }
                 Err(e) => panic!("{}", e),
@@ -513,21 +2612,37 @@ impl RuntimeLoader {
                 if dry_run {
                     panic!("{}", msg);
                 } else {
-                    eprintln!("{}", msg);
+                    warn!("{}", msg);
                 }
                 return (try_app, false);
             }
         };
 
-        //restore settings if possible
-        if let Some(previous) = try_app.as_ref() {
-            match previous.extract_settings() {
-                Err(e) => eprintln!("Error extracting: {}", e),
-                Ok(extracted) => {
-                    eprintln!("Extracted {} settings bytes", extracted.len());
-                    match app.import_settings(&extracted) {
-                        Err(e) => eprintln!("Error restoring settings: {}", e),
-                        Ok(_) => eprintln!("completed settings import"),
+        if let Some(midi_sink) = self.midi_sink.as_ref() {
+            app.set_midi_sink(midi_sink.clone());
+        }
+
+        //restore settings if possible, a crashed app's own may be garbage
+        let extracted = match self.restore_settings.take() {
+            Some(settings) => Some(Ok(settings)),
+            None => try_app.as_ref().map(|previous| {
+                previous
+                    .settings_version()
+                    .and_then(|v| previous.extract_settings().map(|bytes| (v, bytes)))
+            }),
+        };
+        if let Some(extracted) = extracted {
+            match extracted {
+                Err(e) => error!("Error extracting: {}", e),
+                Ok((version, extracted)) => {
+                    info!(
+                        "Extracted {} settings bytes at version {}",
+                        extracted.len(),
+                        version
+                    );
+                    match app.migrate_settings(&extracted, version) {
+                        Err(e) => error!("Error restoring settings: {}", e),
+                        Ok(_) => info!("completed settings import"),
                     }
                 }
             }
@@ -542,21 +2657,25 @@ impl RuntimeLoader {
         for (asset, _) in app.loaded_asset_info().as_ref() {
             to_remove.remove(asset.name());
         }
+        for asset in app.deferred_assets() {
+            to_remove.remove(asset.name());
+        }
 
         for (_, gfx_data) in loaded_gfx_data.drain() {
             gfx_runtime.add(gfx_data.info(), gfx_data)
         }
+        gfx_runtime.defer(app.deferred_assets());
 
         for k in to_remove {
             if let Err(e) = gfx_runtime.remove(&k) {
-                eprintln!("Error removing {}: {}", k, e);
+                error!("Error removing {}: {}", k, e);
             };
         }
 
         //dry run calc
         match app.calc(canvas_w, canvas_h, 1, gfx_runtime.frames_per_sec, &vec![]) {
             Ok(mut _specs) => {
-                eprintln!("Load complete at {}", Local::now().to_rfc3339());
+                info!("Load complete at {}", Local::now().to_rfc3339());
                 // for spec in specs.drain(..) {
                 //     match gfx_runtime.render(lowlevel_ctx as *mut _, spec, frame, true) { //always dry run
                 //         Err(e) => {
@@ -569,7 +2688,7 @@ impl RuntimeLoader {
                 (Some(Arc::new(app)), true)
             }
             Err(e) => {
-                eprintln!("Issue  dry running calculate {}", e);
+                error!("Issue  dry running calculate {}", e);
 
                 return (try_app, false);
             }
@@ -577,13 +2696,197 @@ impl RuntimeLoader {
     }
 }
 
+// --record, --replay and their session versions, which are left out
+// without the recording feature
+mod recording {
+    #[cfg(feature = "recording")]
+    use sdlrig::replay::{session_input, Recorder, Replay, SessionRecorder, SessionReplay};
+    use sdlrig::{appruntime::AppRuntime, gfxinfo::GfxEvent};
+    #[cfg(feature = "recording")]
+    use tracing::{info, warn};
+
+    #[cfg(feature = "recording")]
+    #[derive(Default)]
+    pub struct Recording {
+        replay: Option<Replay>,
+        recorder: Option<Recorder>,
+        // settings are recorded once, from the first app that calculates
+        recorded_settings: bool,
+        session_replay: Option<SessionReplay>,
+        session_recorder: Option<SessionRecorder>,
+        session_settings: bool,
+    }
+
+    #[cfg(not(feature = "recording"))]
+    pub struct Recording;
+
+    #[cfg(feature = "recording")]
+    impl Recording {
+        pub fn open(args: &super::Args) -> anyhow::Result<Self> {
+            let mut recording = Self::default();
+            if let Some(path) = args.replay.as_ref() {
+                let replay = Replay::open(path)?;
+                if replay.fps != args.fps {
+                    anyhow::bail!(
+                        "{} was recorded at {} fps, pass --fps {}",
+                        path.display(),
+                        replay.fps,
+                        replay.fps
+                    );
+                }
+                recording.replay = Some(replay);
+            }
+            if let Some(path) = args.record.as_ref() {
+                recording.recorder = Some(Recorder::create(path, args.fps)?);
+            }
+            if let Some(path) = args.replay_session.as_ref() {
+                let session = SessionReplay::open(path)?;
+                if session.fps != args.fps {
+                    anyhow::bail!(
+                        "{} was recorded at {} fps, pass --fps {}",
+                        path.display(),
+                        session.fps,
+                        session.fps
+                    );
+                }
+                recording.session_replay = Some(session);
+            }
+            if let Some(path) = args.record_session.as_ref() {
+                recording.session_recorder = Some(SessionRecorder::create(path, args.fps)?);
+            }
+            Ok(recording)
+        }
+
+        pub fn replaying(&self) -> bool {
+            self.replay.is_some()
+        }
+
+        pub fn replaying_session(&self) -> bool {
+            self.session_replay.is_some()
+        }
+
+        pub fn active(&self) -> bool {
+            self.replay.is_some()
+                || self.session_replay.is_some()
+                || self.recorder.is_some()
+                || self.session_recorder.is_some()
+        }
+
+        // Swaps in what was recorded for this frame and records what's left,
+        // false once a replay has no frames left
+        pub fn frame(
+            &mut self,
+            app_runtime: &AppRuntime,
+            frame: &mut i64,
+            (calc_w, calc_h): (&mut u32, &mut u32),
+            reg_events: &mut Vec<GfxEvent>,
+            guest_crashed: bool,
+        ) -> bool {
+            if let Some(replay) = self.replay.as_mut() {
+                if let Some((version, bytes)) = replay.settings.take() {
+                    if let Err(e) = app_runtime.migrate_settings(&bytes, version) {
+                        warn!("Could not restore recorded settings: {}", e);
+                    }
+                }
+                let Some(recorded) = replay.next_frame() else {
+                    info!("Replay finished at frame {}", frame);
+                    return false;
+                };
+                // what happened live this frame is replaced by the recording
+                *frame = recorded.frame;
+                (*calc_w, *calc_h) = recorded.canvas;
+                *reg_events = recorded.events;
+                app_runtime.set_fixed_clock(true);
+            }
+            if let Some(session) = self.session_replay.as_mut() {
+                if let Some((version, bytes)) = session.settings.take() {
+                    if let Err(e) = app_runtime.migrate_settings(&bytes, version) {
+                        warn!("Could not restore the session's settings: {}", e);
+                    }
+                }
+                reg_events.retain(|e| !session_input(e));
+                reg_events.extend(session.inputs(*frame));
+                if session.finished() {
+                    info!("Session replay finished at frame {}", frame);
+                    self.session_replay = None;
+                }
+            }
+            if let (Some(rec), false) = (self.session_recorder.as_mut(), guest_crashed) {
+                let mut recorded = Ok(());
+                if !self.session_settings {
+                    self.session_settings = true;
+                    recorded = app_runtime
+                        .settings_version()
+                        .and_then(|v| app_runtime.extract_settings().map(|b| (v, b)))
+                        .map_err(|e| anyhow::anyhow!("{}", e))
+                        .and_then(|(version, bytes)| rec.settings(version, bytes));
+                }
+                if let Err(e) = recorded.and_then(|_| rec.frame(*frame, reg_events)) {
+                    warn!("Session recording failed, stopping it: {}", e);
+                    self.session_recorder = None;
+                }
+            }
+            if let (Some(rec), false) = (self.recorder.as_mut(), guest_crashed) {
+                let mut recorded = Ok(());
+                if !self.recorded_settings {
+                    self.recorded_settings = true;
+                    recorded = app_runtime
+                        .settings_version()
+                        .and_then(|v| app_runtime.extract_settings().map(|b| (v, b)))
+                        .map_err(|e| anyhow::anyhow!("{}", e))
+                        .and_then(|(version, bytes)| rec.settings(version, bytes));
+                }
+                if let Err(e) =
+                    recorded.and_then(|_| rec.frame(*frame, (*calc_w, *calc_h), reg_events))
+                {
+                    warn!("Recording failed, stopping it: {}", e);
+                    self.recorder = None;
+                }
+            }
+            true
+        }
+    }
+
+    #[cfg(not(feature = "recording"))]
+    impl Recording {
+        pub fn open(_: &super::Args) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn replaying(&self) -> bool {
+            false
+        }
+
+        pub fn replaying_session(&self) -> bool {
+            false
+        }
+
+        pub fn active(&self) -> bool {
+            false
+        }
+
+        pub fn frame(
+            &mut self,
+            _: &AppRuntime,
+            _: &mut i64,
+            _: (&mut u32, &mut u32),
+            _: &mut Vec<GfxEvent>,
+            _: bool,
+        ) -> bool {
+            true
+        }
+    }
+}
+
 mod stderr_capture {
     use std::fs::File;
     use std::io::{Read, Write};
-    use std::os::unix::io::FromRawFd;
     use std::sync::mpsc::Sender;
 
+    #[cfg(unix)]
     pub fn tee(tx: Sender<Vec<u8>>) {
+        use std::os::unix::io::FromRawFd;
+
         let mut fds: [libc::c_int; 2] = [0; 2];
         unsafe {
             if libc::pipe(fds.as_mut_ptr()) < 0 {
@@ -612,6 +2915,14 @@ mod stderr_capture {
                 }
                 libc::setvbuf(__stderrp, std::ptr::null_mut(), libc::_IONBF, 0);
             }
+            // glibc's
+            #[cfg(target_os = "linux")]
+            {
+                extern "C" {
+                    static mut stderr: *mut libc::FILE;
+                }
+                libc::setvbuf(stderr, std::ptr::null_mut(), libc::_IONBF, 0);
+            }
         }
 
         unsafe {
@@ -620,27 +2931,68 @@ mod stderr_capture {
         }
 
         std::thread::spawn(move || {
-            let mut reader = unsafe { File::from_raw_fd(fds[0]) };
-            let mut writer = unsafe { File::from_raw_fd(orig_stderr_fd) };
-            let mut buffer = [0u8; 1024];
-
-            loop {
-                match reader.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let chunk = buffer[0..n].to_vec();
-                        // Ignore errors on original stderr
-                        let _ = writer.write_all(&chunk);
-                        let _ = writer.flush();
-
-                        // Send to channel
-                        if tx.send(chunk).is_err() {
-                            break; // Receiver dropped
-                        }
+            let reader = unsafe { File::from_raw_fd(fds[0]) };
+            let writer = unsafe { File::from_raw_fd(orig_stderr_fd) };
+            forward(reader, writer, tx);
+        });
+    }
+
+    // Both the crt's stderr, which ffmpeg and the other C libraries print to,
+    // and the handle rust's goes to
+    #[cfg(windows)]
+    pub fn tee(tx: Sender<Vec<u8>>) {
+        use std::os::windows::io::{AsHandle, IntoRawHandle, OwnedHandle};
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetStdHandle(which: u32, handle: *mut std::ffi::c_void) -> i32;
+        }
+        const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+
+        let Ok(orig_stderr) = std::io::stderr().as_handle().try_clone_to_owned() else {
+            return;
+        };
+        let Ok((reader, writer)) = std::io::pipe() else {
+            eprintln!("Failed to create pipe for stderr tee");
+            return;
+        };
+
+        unsafe {
+            // fd 2 ends up with its own copy of the write end
+            let fd = libc::open_osfhandle(
+                OwnedHandle::from(writer).into_raw_handle() as libc::intptr_t,
+                libc::O_WRONLY,
+            );
+            if fd < 0 {
+                return;
+            }
+            libc::dup2(fd, 2);
+            libc::close(fd);
+            SetStdHandle(STD_ERROR_HANDLE, libc::get_osfhandle(2) as *mut _);
+        }
+
+        std::thread::spawn(move || forward(reader, File::from(orig_stderr), tx));
+    }
+
+    fn forward(mut reader: impl Read, mut writer: File, tx: Sender<Vec<u8>>) {
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let chunk = buffer[0..n].to_vec();
+                    // Ignore errors on original stderr
+                    let _ = writer.write_all(&chunk);
+                    let _ = writer.flush();
+
+                    // Send to channel
+                    if tx.send(chunk).is_err() {
+                        break; // Receiver dropped
                     }
-                    Err(_) => break,
                 }
+                Err(_) => break,
             }
-        });
+        }
     }
 }
@@ -0,0 +1,312 @@
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_probe_input;
+use crate::gfxinfo::{CaptureDevice, CaptureDevicesEvent, CaptureMode};
+use anyhow::{bail, Result};
+use std::ffi::{c_char, CStr, CString};
+use std::fs;
+use std::thread::{self, JoinHandle};
+
+const PROBE_LOG_SIZE: usize = 64 << 10;
+
+// Lists devices off the render thread, asking each for its modes opens it
+// and that can take a while
+#[derive(Default)]
+pub struct CaptureLister {
+    pending: Option<JoinHandle<Result<Vec<CaptureDevice>>>>,
+}
+
+impl CaptureLister {
+    // Asking again while a listing runs gets that listing
+    pub fn request(&mut self) {
+        if self.pending.is_none() {
+            self.pending = Some(thread::spawn(list));
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<CaptureDevicesEvent> {
+        if !self.pending.as_ref()?.is_finished() {
+            return None;
+        }
+        Some(match self.pending.take()?.join() {
+            Ok(Ok(devices)) => CaptureDevicesEvent {
+                devices,
+                error: None,
+            },
+            Ok(Err(e)) => CaptureDevicesEvent {
+                devices: vec![],
+                error: Some(e.to_string()),
+            },
+            Err(_) => CaptureDevicesEvent {
+                devices: vec![],
+                error: Some("capture device listing panicked".into()),
+            },
+        })
+    }
+}
+
+// The cameras and capture cards there are now, with their modes, through
+// whichever libavdevice demuxer the platform has
+pub fn list() -> Result<Vec<CaptureDevice>> {
+    if cfg!(target_os = "macos") {
+        avfoundation()
+    } else if cfg!(target_os = "windows") {
+        dshow()
+    } else {
+        v4l2()
+    }
+}
+
+// What the demuxer logged opening url, the listing options fail the open so
+// its result only matters when nothing was logged
+fn probe(format: &str, url: &str, options: &str) -> Result<(i32, String)> {
+    let (format, url, options) = (
+        CString::new(format)?,
+        CString::new(url)?,
+        CString::new(options)?,
+    );
+    let mut log = vec![0 as c_char; PROBE_LOG_SIZE];
+    let ret = unsafe {
+        gfx_lowlevel_probe_input(
+            format.as_ptr(),
+            url.as_ptr(),
+            options.as_ptr(),
+            log.as_mut_ptr(),
+            log.len(),
+        )
+    };
+    let log = unsafe { CStr::from_ptr(log.as_ptr()) };
+    Ok((ret, log.to_string_lossy().into_owned()))
+}
+
+fn listing(format: &str, url: &str, options: &str) -> Result<String> {
+    let (ret, log) = probe(format, url, options)?;
+    if log.trim().is_empty() {
+        bail!("{} could not list its devices, error {}", format, ret);
+    }
+    Ok(log)
+}
+
+// Asking for a size no camera has makes avfoundation list the ones it does
+fn avfoundation() -> Result<Vec<CaptureDevice>> {
+    let log = listing("avfoundation", "", "list_devices=true")?;
+    let mut devices = vec![];
+    let mut video = false;
+    for line in log.lines() {
+        if line.contains("video devices:") {
+            video = true;
+        } else if line.contains("audio devices:") {
+            video = false;
+        }
+        // [0] FaceTime HD Camera
+        let indexed = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once("] "))
+            .filter(|(index, _)| index.parse::<u32>().is_ok());
+        let Some((_, name)) = indexed.filter(|_| video) else {
+            continue;
+        };
+        let (_, modes) = probe("avfoundation", name, "video_size=1x1:framerate=1")?;
+        devices.push(CaptureDevice {
+            name: name.into(),
+            format: "avfoundation".into(),
+            // it takes the name as well as the index, and the name doesn't
+            // change when other devices come and go
+            path: name.into(),
+            modes: avfoundation_modes(&modes),
+        });
+    }
+    Ok(devices)
+}
+
+//   1280x720@[1.000000 30.000000]fps
+fn avfoundation_modes(log: &str) -> Vec<CaptureMode> {
+    let mut modes = vec![];
+    for line in log.lines() {
+        let Some((size, rest)) = line.trim().split_once('@') else {
+            continue;
+        };
+        let Some(size) = parse_size(size) else {
+            continue;
+        };
+        let fps = rest
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(range, _)| {
+                range
+                    .split_whitespace()
+                    .filter_map(|fps| fps.parse::<f64>().ok())
+                    .map(fps_rational)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mode = CaptureMode {
+            size,
+            pixel_format: None,
+            compressed: false,
+            min_fps: fps.first().copied(),
+            max_fps: fps.last().copied(),
+        };
+        // listed again for each pixel format it comes in
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+    }
+    modes
+}
+
+// /dev/video* in number order, leaving out metadata nodes and anything else
+// that can't capture video
+fn v4l2() -> Result<Vec<CaptureDevice>> {
+    let mut nodes = fs::read_dir("/dev")?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|node| {
+            let number = node.strip_prefix("video")?.parse::<u32>().ok()?;
+            Some((number, node))
+        })
+        .collect::<Vec<_>>();
+    nodes.sort();
+    let mut devices = vec![];
+    for (_, node) in nodes {
+        let path = format!("/dev/{}", node);
+        let (_, log) = probe("video4linux2", &path, "list_formats=all")?;
+        let modes = v4l2_modes(&log);
+        if modes.is_empty() {
+            continue;
+        }
+        let name = fs::read_to_string(format!("/sys/class/video4linux/{}/name", node))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| path.clone());
+        devices.push(CaptureDevice {
+            name,
+            format: "video4linux2".into(),
+            path,
+            modes,
+        });
+    }
+    Ok(devices)
+}
+
+// Raw       :     yuyv422 :           YUYV 4:2:2 : 640x480 1280x720
+// Compressed:       mjpeg :          Motion-JPEG : 640x480 1280x720
+fn v4l2_modes(log: &str) -> Vec<CaptureMode> {
+    let mut modes = vec![];
+    for line in log.lines() {
+        let Some((kind, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let compressed = match kind.trim() {
+            "Raw" => false,
+            "Compressed" => true,
+            _ => continue,
+        };
+        let Some((pixel_format, _)) = rest.split_once(" : ") else {
+            continue;
+        };
+        let pixel_format = pixel_format.trim();
+        if pixel_format == "Unsupported" {
+            continue;
+        }
+        // stepwise sizes are left out, they don't parse
+        let sizes = line.rsplit_once(" : ").map_or("", |(_, sizes)| sizes);
+        for size in sizes.split_whitespace().filter_map(parse_size) {
+            modes.push(CaptureMode {
+                size,
+                pixel_format: Some(pixel_format.into()),
+                compressed,
+                min_fps: None,
+                max_fps: None,
+            });
+        }
+    }
+    modes
+}
+
+fn dshow() -> Result<Vec<CaptureDevice>> {
+    let log = listing("dshow", "dummy", "list_devices=true")?;
+    let mut devices = vec![];
+    // older ffmpeg heads each kind, newer ones mark each device
+    let mut video = false;
+    for line in log.lines() {
+        if line.contains("DirectShow video devices") {
+            video = true;
+        } else if line.contains("DirectShow audio devices") {
+            video = false;
+        }
+        let line = line.trim();
+        let Some((name, kind)) = line.strip_prefix('"').and_then(|rest| rest.split_once('"'))
+        else {
+            continue;
+        };
+        let kind = kind.trim();
+        if !(kind == "(video)" || kind.is_empty() && video) {
+            continue;
+        }
+        let path = format!("video={}", name);
+        let (_, modes) = probe("dshow", &path, "list_options=true")?;
+        devices.push(CaptureDevice {
+            name: name.into(),
+            format: "dshow".into(),
+            path,
+            modes: dshow_modes(&modes),
+        });
+    }
+    Ok(devices)
+}
+
+//   pixel_format=yuyv422  min s=640x480 fps=5 max s=640x480 fps=30
+//   vcodec=mjpeg  min s=1280x720 fps=5 max s=1280x720 fps=30
+fn dshow_modes(log: &str) -> Vec<CaptureMode> {
+    let mut modes = vec![];
+    for line in log.lines() {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let (pixel_format, compressed) = match tokens.first().and_then(|t| t.split_once('=')) {
+            Some(("pixel_format", format)) => (format, false),
+            Some(("vcodec", codec)) => (codec, true),
+            _ => continue,
+        };
+        let bound = |at: &str| {
+            let i = tokens.iter().position(|t| *t == at)?;
+            let size = parse_size(tokens.get(i + 1)?.strip_prefix("s=")?)?;
+            let fps = tokens
+                .get(i + 2)?
+                .strip_prefix("fps=")?
+                .parse::<f64>()
+                .ok()?;
+            Some((size, fps_rational(fps)))
+        };
+        let (Some((_, min_fps)), Some((size, max_fps))) = (bound("min"), bound("max")) else {
+            continue;
+        };
+        let mode = CaptureMode {
+            size,
+            pixel_format: Some(pixel_format.into()),
+            compressed,
+            min_fps: Some(min_fps),
+            max_fps: Some(max_fps),
+        };
+        // one for each pin that offers it
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+    }
+    modes
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (w, h) = size.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+// To the nearest thousandth, 29.97 comes out 2997/100
+fn fps_rational(fps: f64) -> (i32, i32) {
+    let (mut num, mut den) = ((fps * 1000.0).round() as i32, 1000);
+    let (mut a, mut b) = (num.abs(), den);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    if a > 1 {
+        num /= a;
+        den /= a;
+    }
+    (num, den)
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+// Turns a toml file into command line arguments, keyed by the clap id of the
+// argument they set. Keys use the flag's name with - or _, and tables prefix
+// their keys, so [midi] port = ["a", "b"] is --midi-port a --midi-port b.
+// true is a bare flag and false leaves it out.
+pub fn config_args<P: AsRef<Path>>(path: P) -> Result<Vec<(String, Vec<String>)>> {
+    let text = fs::read_to_string(path.as_ref())?;
+    let table: Table = text
+        .parse()
+        .map_err(|e| anyhow!("Could not parse {}: {}", path.as_ref().display(), e))?;
+    let mut args = vec![];
+    flatten("", &table, &mut args)?;
+    Ok(args)
+}
+
+fn flatten(prefix: &str, table: &Table, args: &mut Vec<(String, Vec<String>)>) -> Result<()> {
+    for (key, value) in table {
+        let id = format!("{}{}", prefix, key.replace('-', "_"));
+        if let Value::Table(table) = value {
+            flatten(&format!("{}_", id), table, args)?;
+            continue;
+        }
+        let flag = format!("--{}", id.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        let mut tokens = vec![];
+        for value in values {
+            match value {
+                Value::Boolean(true) => tokens.push(flag.clone()),
+                Value::Boolean(false) => (),
+                Value::String(s) => tokens.extend([flag.clone(), s.clone()]),
+                Value::Integer(i) => tokens.extend([flag.clone(), i.to_string()]),
+                Value::Float(f) => tokens.extend([flag.clone(), f.to_string()]),
+                Value::Datetime(d) => tokens.extend([flag.clone(), d.to_string()]),
+                Value::Array(_) | Value::Table(_) => bail!("{} can't be nested that deep", id),
+            }
+        }
+        args.push((id, tokens));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(toml: &str) -> Result<Vec<(String, Vec<String>)>> {
+        let mut args = vec![];
+        flatten("", &toml.parse::<Table>().unwrap(), &mut args)?;
+        args.sort();
+        Ok(args)
+    }
+
+    fn arg(id: &str, tokens: &[&str]) -> (String, Vec<String>) {
+        (id.into(), tokens.iter().map(|t| t.to_string()).collect())
+    }
+
+    #[test]
+    fn scalars() {
+        let args =
+            args("width = 640\nscale = 1.5\nlib = \"a.wasm\"\nfull-screen = true\nvsync = false")
+                .unwrap();
+        assert_eq!(
+            args,
+            [
+                arg("full_screen", &["--full-screen"]),
+                arg("lib", &["--lib", "a.wasm"]),
+                arg("scale", &["--scale", "1.5"]),
+                arg("vsync", &[]),
+                arg("width", &["--width", "640"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn tables_prefix_their_keys() {
+        let args = args("[midi]\nin-port = \"x\"\n[midi.clock]\nsend = true").unwrap();
+        assert_eq!(
+            args,
+            [
+                arg("midi_clock_send", &["--midi-clock-send"]),
+                arg("midi_in_port", &["--midi-in-port", "x"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_repeat_the_flag() {
+        let args = args("asset = [\"a.png\", \"b.png\"]\nempty = []").unwrap();
+        assert_eq!(
+            args,
+            [
+                arg("asset", &["--asset", "a.png", "--asset", "b.png"]),
+                arg("empty", &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn nesting_in_arrays() {
+        assert!(args("asset = [[\"a\"]]").is_err());
+        assert!(args("[[asset]]\npath = \"a\"").is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::gfxinfo::{
+    ControllerAxis, ControllerButton, ControllerEvent, ControllerInput, DeviceEvent, DeviceKind,
+    GfxEvent,
+};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+// Game controllers as they're plugged in. SDL sends an added event for the
+// ones already connected at startup too.
+pub struct Controllers {
+    subsystem: GameControllerSubsystem,
+    // by joystick instance id, which is what the input events carry
+    open: HashMap<u32, GameController>,
+    deadzone: i16,
+    // sticks resting inside the deadzone are sent once as 0, not every wobble
+    axes: HashMap<(u32, ControllerAxis), i16>,
+}
+
+impl Controllers {
+    pub fn new(subsystem: GameControllerSubsystem, deadzone: i16) -> Self {
+        Self {
+            subsystem,
+            open: HashMap::new(),
+            deadzone,
+            axes: HashMap::new(),
+        }
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: i16) {
+        self.deadzone = deadzone;
+    }
+
+    // Events for the controller sdl events, nothing for the rest
+    pub fn handle(&mut self, event: &Event) -> Option<GfxEvent> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    let name = controller.name();
+                    info!("Opening controller {}", name);
+                    self.open.insert(controller.instance_id(), controller);
+                    Some(GfxEvent::DeviceConnected(DeviceEvent {
+                        name,
+                        kind: DeviceKind::Controller,
+                    }))
+                }
+                Err(e) => {
+                    warn!("Could not open controller {}: {}", which, e);
+                    None
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let controller = self.open.remove(&which)?;
+                self.axes.retain(|(id, _), _| *id != which);
+                info!("Controller {} disconnected", controller.name());
+                Some(GfxEvent::DeviceDisconnected(DeviceEvent {
+                    name: controller.name(),
+                    kind: DeviceKind::Controller,
+                }))
+            }
+            Event::ControllerButtonDown {
+                timestamp,
+                which,
+                button,
+            } => self.event(which, timestamp, button_input(button, true)),
+            Event::ControllerButtonUp {
+                timestamp,
+                which,
+                button,
+            } => self.event(which, timestamp, button_input(button, false)),
+            Event::ControllerAxisMotion {
+                timestamp,
+                which,
+                axis,
+                value,
+            } => {
+                let axis = controller_axis(axis);
+                let value = if (value as i32).abs() < self.deadzone as i32 {
+                    0
+                } else {
+                    value
+                };
+                if self.axes.insert((which, axis), value) == Some(value) {
+                    return None;
+                }
+                self.event(which, timestamp, ControllerInput::Axis { axis, value })
+            }
+            _ => None,
+        }
+    }
+
+    fn event(&self, which: u32, timestamp: u32, input: ControllerInput) -> Option<GfxEvent> {
+        let controller = self.open.get(&which)?;
+        Some(GfxEvent::ControllerEvent(ControllerEvent {
+            device: controller.name(),
+            id: which,
+            input,
+            timestamp: timestamp as i64,
+        }))
+    }
+}
+
+fn button_input(button: Button, down: bool) -> ControllerInput {
+    let button = match button {
+        Button::A => ControllerButton::A,
+        Button::B => ControllerButton::B,
+        Button::X => ControllerButton::X,
+        Button::Y => ControllerButton::Y,
+        Button::Back => ControllerButton::Back,
+        Button::Guide => ControllerButton::Guide,
+        Button::Start => ControllerButton::Start,
+        Button::LeftStick => ControllerButton::LeftStick,
+        Button::RightStick => ControllerButton::RightStick,
+        Button::LeftShoulder => ControllerButton::LeftShoulder,
+        Button::RightShoulder => ControllerButton::RightShoulder,
+        Button::DPadUp => ControllerButton::DPadUp,
+        Button::DPadDown => ControllerButton::DPadDown,
+        Button::DPadLeft => ControllerButton::DPadLeft,
+        Button::DPadRight => ControllerButton::DPadRight,
+        Button::Misc1 => ControllerButton::Misc1,
+        Button::Paddle1 => ControllerButton::Paddle1,
+        Button::Paddle2 => ControllerButton::Paddle2,
+        Button::Paddle3 => ControllerButton::Paddle3,
+        Button::Paddle4 => ControllerButton::Paddle4,
+        Button::Touchpad => ControllerButton::Touchpad,
+    };
+    ControllerInput::Button { button, down }
+}
+
+fn controller_axis(axis: Axis) -> ControllerAxis {
+    match axis {
+        Axis::LeftX => ControllerAxis::LeftX,
+        Axis::LeftY => ControllerAxis::LeftY,
+        Axis::RightX => ControllerAxis::RightX,
+        Axis::RightY => ControllerAxis::RightY,
+        Axis::TriggerLeft => ControllerAxis::TriggerLeft,
+        Axis::TriggerRight => ControllerAxis::TriggerRight,
+    }
+}
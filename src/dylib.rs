@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Result};
+use std::ffi::{c_void, CString};
+use std::path::Path;
+
+// A shared library opened at runtime, closed when dropped. dlopen everywhere
+// but windows, which has LoadLibrary instead.
+pub struct Library(*mut c_void);
+
+impl Library {
+    // What went wrong is the loader's own message
+    pub fn open(path: &Path) -> Result<Self> {
+        let lib = unsafe { sys::open(path)? };
+        if lib.is_null() {
+            return Err(anyhow!(unsafe { sys::error() }));
+        }
+        Ok(Self(lib))
+    }
+
+    // None when the library doesn't export name
+    pub fn symbol(&self, name: &str) -> Result<Option<*mut c_void>> {
+        let name = CString::new(name)?;
+        let ptr = unsafe { sys::symbol(self.0, &name) };
+        Ok((!ptr.is_null()).then_some(ptr))
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { sys::close(self.0) };
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use anyhow::Result;
+    use std::ffi::{c_void, CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    pub unsafe fn open(path: &Path) -> Result<*mut c_void> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        Ok(libc::dlopen(
+            path.as_ptr(),
+            libc::RTLD_NOW | libc::RTLD_LOCAL,
+        ))
+    }
+
+    pub unsafe fn symbol(lib: *mut c_void, name: &CStr) -> *mut c_void {
+        libc::dlsym(lib, name.as_ptr())
+    }
+
+    pub unsafe fn close(lib: *mut c_void) {
+        libc::dlclose(lib);
+    }
+
+    pub unsafe fn error() -> String {
+        let msg = libc::dlerror();
+        match msg.is_null() {
+            true => "unknown error".into(),
+            false => CStr::from_ptr(msg).to_string_lossy().into_owned(),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use anyhow::Result;
+    use std::ffi::{c_char, c_void, CStr};
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(lib: *mut c_void, name: *const c_char) -> *mut c_void;
+        fn FreeLibrary(lib: *mut c_void) -> i32;
+    }
+
+    pub unsafe fn open(path: &Path) -> Result<*mut c_void> {
+        let path = path
+            .as_os_str()
+            .encode_wide()
+            .chain([0])
+            .collect::<Vec<_>>();
+        Ok(LoadLibraryW(path.as_ptr()))
+    }
+
+    pub unsafe fn symbol(lib: *mut c_void, name: &CStr) -> *mut c_void {
+        GetProcAddress(lib, name.as_ptr())
+    }
+
+    pub unsafe fn close(lib: *mut c_void) {
+        FreeLibrary(lib);
+    }
+
+    // GetLastError, which std formats with the system's message for it
+    pub unsafe fn error() -> String {
+        std::io::Error::last_os_error().to_string()
+    }
+}
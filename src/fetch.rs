@@ -0,0 +1,205 @@
+use crate::gfxinfo::FetchEvent;
+use anyhow::{bail, Result};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_FETCH_BYTES: u64 = 16 << 20;
+// Fetches running at once, the rest wait their turn
+const MAX_IN_FLIGHT: usize = 4;
+// Past this many waiting, requests fail straight away
+const MAX_QUEUED: usize = 256;
+
+// Runs the guest's fetch requests off the render thread. Only urls under one
+// of the allowed prefixes are fetched, and redirects aren't followed so they
+// can't lead outside of them.
+pub struct Fetcher {
+    allow: Vec<String>,
+    agent: ureq::Agent,
+    pending: Vec<(String, JoinHandle<Result<Vec<u8>>>)>,
+    queued: VecDeque<String>,
+    // refused or turned away, answered by the next poll
+    failed: Vec<FetchEvent>,
+}
+
+impl Fetcher {
+    pub fn new(allow: Vec<String>) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(FETCH_TIMEOUT)
+            .redirects(0)
+            .build();
+        Self {
+            allow,
+            agent,
+            pending: vec![],
+            queued: VecDeque::new(),
+            failed: vec![],
+        }
+    }
+
+    // Requests already running finish either way, waiting ones are checked
+    // again when their turn comes
+    pub fn set_allow(&mut self, allow: Vec<String>) {
+        self.allow = allow;
+    }
+
+    pub fn allowed(&self, url: &str) -> bool {
+        if has_dot_segment(url) {
+            return false;
+        }
+        self.allow.iter().any(|prefix| {
+            // a prefix only matches up to a path boundary, so an allowed
+            // https://a.com doesn't also allow https://a.com.evil.net
+            url.strip_prefix(prefix.as_str()).map_or(false, |rest| {
+                prefix.ends_with('/')
+                    || rest.is_empty()
+                    || rest.starts_with(|c| matches!(c, '/' | '?' | '#'))
+            })
+        })
+    }
+
+    // Refused urls come back from the next poll as a failed FetchEvent
+    pub fn request(&mut self, url: String) {
+        if !self.allowed(&url) {
+            let error = format!("{} is not in the fetch allow list", url);
+            self.fail(url, error);
+        } else if self.queued.len() >= MAX_QUEUED {
+            let error = format!("{} fetches are already waiting", MAX_QUEUED);
+            self.fail(url, error);
+        } else {
+            self.queued.push_back(url);
+            self.start_queued();
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len() + self.queued.len()
+    }
+
+    fn fail(&mut self, url: String, error: String) {
+        self.failed.push(FetchEvent {
+            url,
+            data: vec![],
+            error: Some(error),
+        });
+    }
+
+    fn start_queued(&mut self) {
+        while self.pending.len() < MAX_IN_FLIGHT {
+            let Some(url) = self.queued.pop_front() else {
+                break;
+            };
+            if !self.allowed(&url) {
+                let error = format!("{} is not in the fetch allow list", url);
+                self.fail(url, error);
+                continue;
+            }
+            let agent = self.agent.clone();
+            let fetch_url = url.clone();
+            let handle = thread::spawn(move || fetch(&agent, &fetch_url));
+            self.pending.push((url, handle));
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<FetchEvent> {
+        let (done, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, handle)| handle.is_finished());
+        self.pending = pending;
+        self.start_queued();
+        let failed = std::mem::take(&mut self.failed);
+        failed
+            .into_iter()
+            .chain(done.into_iter().map(|(url, handle)| match handle.join() {
+                Ok(Ok(data)) => FetchEvent {
+                    url,
+                    data,
+                    error: None,
+                },
+                Ok(Err(e)) => FetchEvent {
+                    url,
+                    data: vec![],
+                    error: Some(e.to_string()),
+                },
+                Err(_) => FetchEvent {
+                    url,
+                    data: vec![],
+                    error: Some("fetch panicked".into()),
+                },
+            }))
+            .collect()
+    }
+}
+
+// ureq resolves . and .. before sending the path, percent encoded and
+// backslashed ones too and after dropping tabs and newlines, so a url that
+// has any could leave the prefix it matched. They're refused instead.
+fn has_dot_segment(url: &str) -> bool {
+    if url.contains(|c: char| c.is_ascii_control()) {
+        return true;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    path.split(['/', '\\']).any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+        segment == "." || segment == ".."
+    })
+}
+
+fn fetch(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>> {
+    let response = agent.get(url).call()?;
+    let mut data = vec![];
+    response
+        .into_reader()
+        .take(MAX_FETCH_BYTES + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_FETCH_BYTES {
+        bail!("{} is larger than {} bytes", url, MAX_FETCH_BYTES);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetcher(allow: &[&str]) -> Fetcher {
+        Fetcher::new(allow.iter().map(|prefix| prefix.to_string()).collect())
+    }
+
+    #[test]
+    fn prefixes_end_at_a_boundary() {
+        let allow = fetcher(&["https://a.com", "https://b.com/api"]);
+        assert!(allow.allowed("https://a.com"));
+        assert!(allow.allowed("https://a.com/x"));
+        assert!(allow.allowed("https://b.com/api?q=1"));
+        assert!(allow.allowed("https://b.com/api#top"));
+        assert!(allow.allowed("https://b.com/api/v1"));
+        assert!(!allow.allowed("https://a.com.evil.net"));
+        assert!(!allow.allowed("https://a.com@evil.net"));
+        assert!(!allow.allowed("https://b.com/apiary"));
+        assert!(!allow.allowed("https://b.com/"));
+        assert!(fetcher(&["https://c.com/x/"]).allowed("https://c.com/x/y"));
+        assert!(!fetcher(&[]).allowed("https://a.com"));
+    }
+
+    #[test]
+    fn dot_segments() {
+        let allow = fetcher(&["https://a.com/api"]);
+        for url in [
+            "https://a.com/api/../admin",
+            "https://a.com/api/./x",
+            "https://a.com/api/..",
+            "https://a.com/api/%2e%2E/admin",
+            "https://a.com/api/.%2e/admin",
+            "https://a.com/api/..\\admin",
+            "https://a.com/api/.\t./admin",
+            "https://a.com/api/\n../admin",
+        ] {
+            assert!(!allow.allowed(url), "{}", url);
+        }
+        assert!(allow.allowed("https://a.com/api/..x/.y"));
+        assert!(allow.allowed("https://a.com/api/x?path=../admin"));
+    }
+}
@@ -1,10 +1,755 @@
+use crate::renderspec::{TextAlign, TextSpan};
 use lazy_static::lazy_static;
-use sdl2::ttf::{self, Font, Sdl2TtfContext};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    render::BlendMode,
+    surface::Surface,
+    sys::{
+        ttf::{TTF_GetFontKerningSizeGlyphs32, TTF_SetFontSDF},
+        SDL_bool,
+    },
+    ttf::{self, Font, FontStyle, Sdl2TtfContext},
+};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
 
 lazy_static! {
     static ref FONT_CTX: Sdl2TtfContext = ttf::init().unwrap();
+    // the ones this machine has
+    static ref SYSTEM_FALLBACKS: Vec<String> = SYSTEM_FALLBACK_FONTS
+        .iter()
+        .filter(|path| Path::new(path).is_file())
+        .map(|path| path.to_string())
+        .collect();
+}
+
+// Emoji and CJK fonts tried after a text's own for characters it doesn't have
+#[cfg(target_os = "macos")]
+const SYSTEM_FALLBACK_FONTS: &[&str] = &[
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+];
+#[cfg(target_os = "windows")]
+const SYSTEM_FALLBACK_FONTS: &[&str] = &[
+    "C:/Windows/Fonts/seguiemj.ttf",
+    "C:/Windows/Fonts/msyh.ttc",
+    "C:/Windows/Fonts/YuGothM.ttc",
+    "C:/Windows/Fonts/malgun.ttf",
+    "C:/Windows/Fonts/seguisym.ttf",
+];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const SYSTEM_FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+];
+
+// Past the last tab stop tabs go to every this many spaces
+const TAB_SPACES: u32 = 8;
+
+// Checks a Font asset's file looks like a font without opening it, sdl_ttf
+// fonts have to stay on the render thread
+pub fn check_font_file(path: &str) -> Result<(), String> {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map_err(|e| format!("Could not read font {}: {}", path, e))?;
+    match &magic {
+        [0, 1, 0, 0] | b"OTTO" | b"true" | b"ttcf" => Ok(()),
+        _ => Err(format!("{} is not a ttf or otf font", path)),
+    }
 }
 
 pub fn load_font(asset: &str, pt_size: u16) -> Result<Font<'static, 'static>, String> {
     FONT_CTX.load_font(asset, pt_size)
 }
+
+// The same rendering glyphs as distance fields, sharp scaled to any size
+pub fn load_sdf_font(asset: &str, pt_size: u16) -> Result<Font<'static, 'static>, String> {
+    let font = load_font(asset, pt_size)?;
+    if unsafe { TTF_SetFontSDF(font.raw(), SDL_bool::SDL_TRUE) } != 0 {
+        return Err("This SDL_ttf can't render distance fields".into());
+    }
+    Ok(font)
+}
+
+// For a TextSpan, outlined fonts render each glyph's outline alone, offset up
+// and left by its width
+pub fn style_font(font: &mut Font, bold: bool, outline: u16) {
+    if bold {
+        font.set_style(FontStyle::BOLD);
+    }
+    font.set_outline_width(outline);
+}
+
+// How much closer or further c goes after previous than their advances say
+pub fn kerning(font: &Font, previous: char, c: char) -> i32 {
+    unsafe { TTF_GetFontKerningSizeGlyphs32(font.raw(), previous as u32, c as u32) }
+}
+
+pub fn system_fallbacks() -> &'static [String] {
+    &SYSTEM_FALLBACKS
+}
+
+// The system fallbacks that open at pt_size
+pub fn load_fallbacks(pt_size: u16) -> Vec<Font<'static, 'static>> {
+    SYSTEM_FALLBACKS
+        .iter()
+        .filter_map(|path| load_font(path, pt_size).ok())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayoutOptions {
+    // of each line within the widest
+    pub align: TextAlign,
+    // wraps at spaces, or between CJK characters and emoji, past this many
+    // pixels, breaking words that don't fit on a line of their own
+    pub max_width: Option<u32>,
+    // times the line skip of the tallest font on a line
+    pub line_spacing: f32,
+    // pixels from the left each tab goes to in turn
+    pub tab_stops: Vec<u32>,
+}
+
+impl Default for TextLayoutOptions {
+    fn default() -> Self {
+        Self {
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+            tab_stops: vec![],
+        }
+    }
+}
+
+// Text of one span drawn with one font, its top left at x, y
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextRun {
+    // into the spans and their fonts it was laid out with
+    pub span: usize,
+    pub font: usize,
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextLayout {
+    pub width: u32,
+    pub height: u32,
+    pub runs: Vec<TextRun>,
+}
+
+// Where a line can break, a word with the spaces after it or a tab. Words
+// are in pieces by the span they're from.
+enum Unit {
+    Text(Vec<(usize, String)>),
+    Tab,
+}
+
+#[derive(Default)]
+struct Line {
+    runs: Vec<TextRun>,
+    // where the next run goes
+    x: i32,
+}
+
+// Lays text out with fonts[0], falling back to the first of the rest that has
+// a character. Lines split on '\n' as well as where they wrap.
+pub fn layout_text(
+    fonts: &[&Font],
+    text: &str,
+    options: &TextLayoutOptions,
+) -> Result<TextLayout, String> {
+    layout_spans(&[fonts.to_vec()], &[text], options)
+}
+
+// Lays spans out one after the other, each with its own fonts the way
+// layout_text does
+pub fn layout_spans(
+    fonts: &[Vec<&Font>],
+    spans: &[&str],
+    options: &TextLayoutOptions,
+) -> Result<TextLayout, String> {
+    let Some(primary) = fonts.first().and_then(|fonts| fonts.first()) else {
+        return Err("No font to lay text out with".into());
+    };
+    if fonts.len() < spans.len() || fonts.iter().any(|fonts| fonts.is_empty()) {
+        return Err("No font to lay a span out with".into());
+    }
+    let tab_every = (width_of(primary, " ")? * TAB_SPACES).max(1);
+    let chars = spans
+        .iter()
+        .enumerate()
+        .flat_map(|(span, text)| text.chars().map(move |c| (span, c)))
+        .collect::<Vec<_>>();
+    let mut lines = vec![];
+    for paragraph in chars.split(|(_, c)| *c == '\n') {
+        let mut line = Line::default();
+        let mut units = units(paragraph);
+        units.reverse();
+        while let Some(unit) = units.pop() {
+            let pieces = match unit {
+                Unit::Tab => {
+                    let x = line.x.max(0) as u32;
+                    line.x = match options.tab_stops.iter().find(|stop| **stop > x) {
+                        Some(stop) => *stop,
+                        None => {
+                            let after = options.tab_stops.last().copied().unwrap_or(0).max(x);
+                            after + tab_every - (after % tab_every)
+                        }
+                    } as i32;
+                    continue;
+                }
+                Unit::Text(pieces) => pieces,
+            };
+            let runs = font_runs(fonts, &pieces);
+            let mut widths = vec![];
+            for (span, font, text) in &runs {
+                widths.push(width_of(fonts[*span][*font], text)?);
+            }
+            let (last_span, last_font, last_text) = &runs[runs.len() - 1];
+            let trailing = widths[widths.len() - 1].saturating_sub(width_of(
+                fonts[*last_span][*last_font],
+                last_text.trim_end(),
+            )?);
+            let inked = widths.iter().sum::<u32>().saturating_sub(trailing);
+            if let Some(max_width) = options.max_width {
+                if line.x > 0 && line.x as u32 + inked > max_width {
+                    lines.push(finish(fonts, line)?);
+                    line = Line::default();
+                }
+                // too long for a line of its own, it's broken between characters
+                let chars = pieces
+                    .iter()
+                    .map(|(_, text)| text.chars().count())
+                    .sum::<usize>();
+                if line.x == 0 && inked > max_width && chars > 1 {
+                    units.extend(pieces.iter().rev().flat_map(|(span, text)| {
+                        text.chars()
+                            .rev()
+                            .map(|c| Unit::Text(vec![(*span, c.to_string())]))
+                    }));
+                    continue;
+                }
+            }
+            for ((span, font, text), width) in runs.into_iter().zip(widths) {
+                match line.runs.last_mut() {
+                    // measured again together, for kerning across them
+                    Some(run)
+                        if run.span == span
+                            && run.font == font
+                            && run.x + run.width as i32 == line.x =>
+                    {
+                        run.text.push_str(&text);
+                        run.width = width_of(fonts[span][font], &run.text)?;
+                        line.x = run.x + run.width as i32;
+                    }
+                    _ => {
+                        line.runs.push(TextRun {
+                            span,
+                            font,
+                            text,
+                            x: line.x,
+                            y: 0,
+                            width,
+                        });
+                        line.x += width as i32;
+                    }
+                }
+            }
+        }
+        lines.push(finish(fonts, line)?);
+    }
+
+    let width = lines.iter().map(|(_, width, _)| *width).max().unwrap_or(0);
+    let mut runs = vec![];
+    let mut top = 0;
+    let mut height = 0;
+    for (mut line_runs, line_width, (ascent, descent, skip)) in lines {
+        let shift = match options.align {
+            TextAlign::Left => 0,
+            TextAlign::Center => (width - line_width) as i32 / 2,
+            TextAlign::Right => (width - line_width) as i32,
+        };
+        for run in line_runs.iter_mut() {
+            // baselines line up whatever the font
+            run.x += shift;
+            run.y = top + ascent - fonts[run.span][run.font].ascent();
+        }
+        runs.extend(line_runs);
+        height = height.max(top + ascent + descent);
+        top += (skip as f32 * options.line_spacing).round().max(1.0) as i32;
+    }
+    Ok(TextLayout {
+        width,
+        height: height.max(0) as u32,
+        runs,
+    })
+}
+
+// A line's runs, its width and (ascent, descent, line skip)
+type Finished = (Vec<TextRun>, u32, (i32, i32, i32));
+
+// The line's runs without the spaces it ends with, how wide that leaves it
+// and its (ascent, descent, line skip) from the tallest of its fonts
+fn finish(fonts: &[Vec<&Font>], mut line: Line) -> Result<Finished, String> {
+    while let Some(run) = line.runs.last_mut() {
+        let trimmed = run.text.trim_end().len();
+        if trimmed == run.text.len() {
+            break;
+        }
+        if trimmed == 0 {
+            line.runs.pop();
+            continue;
+        }
+        run.text.truncate(trimmed);
+        run.width = width_of(fonts[run.span][run.font], &run.text)?;
+        break;
+    }
+    let width = line
+        .runs
+        .last()
+        .map_or(0, |run| (run.x + run.width as i32).max(0) as u32);
+    let mut used = line
+        .runs
+        .iter()
+        .map(|run| (run.span, run.font))
+        .collect::<Vec<_>>();
+    if used.is_empty() {
+        used.push((0, 0));
+    }
+    let metrics = used
+        .iter()
+        .fold((0, 0, 0), |(ascent, descent, skip), (span, font)| {
+            let font = fonts[*span][*font];
+            (
+                ascent.max(font.ascent()),
+                descent.max(-font.descent()),
+                skip.max(font.recommended_line_spacing()),
+            )
+        });
+    Ok((line.runs, width, metrics))
+}
+
+fn units(paragraph: &[(usize, char)]) -> Vec<Unit> {
+    let mut units = vec![];
+    let mut current: Vec<(usize, char)> = vec![];
+    for &(span, c) in paragraph {
+        if c == '\t' {
+            if !current.is_empty() {
+                units.push(Unit::Text(pieces(&std::mem::take(&mut current))));
+            }
+            units.push(Unit::Tab);
+            continue;
+        }
+        let after_break = match current.last() {
+            Some(&(_, last)) => {
+                last == ' ' && c != ' '
+                    || last != ZWJ && !joins(c) && (breaks_around(last) || breaks_around(c))
+            }
+            None => false,
+        };
+        if after_break && c != ' ' {
+            units.push(Unit::Text(pieces(&std::mem::take(&mut current))));
+        }
+        current.push((span, c));
+    }
+    if !current.is_empty() || units.is_empty() {
+        units.push(Unit::Text(pieces(&current)));
+    }
+    units
+}
+
+// Characters joined up by the span they're from, an empty piece for none
+fn pieces(chars: &[(usize, char)]) -> Vec<(usize, String)> {
+    let mut pieces: Vec<(usize, String)> = vec![];
+    for &(span, c) in chars {
+        match pieces.last_mut() {
+            Some((last, text)) if *last == span => text.push(c),
+            _ => pieces.push((span, c.to_string())),
+        }
+    }
+    if pieces.is_empty() {
+        pieces.push((0, String::new()));
+    }
+    pieces
+}
+
+// Runs of (span, font, text) by the font each character is drawn with in its
+// span, spaces and what joins an emoji sequence staying with the character
+// before them
+fn font_runs(fonts: &[Vec<&Font>], pieces: &[(usize, String)]) -> Vec<(usize, usize, String)> {
+    let mut runs: Vec<(usize, usize, String)> = vec![];
+    for (span, text) in pieces {
+        let span_fonts = &fonts[*span];
+        for c in text.chars() {
+            let font = match runs.last() {
+                Some((last, font, _)) if last == span && (c.is_whitespace() || joins(c)) => *font,
+                _ => span_fonts
+                    .iter()
+                    .position(|font| font.find_glyph(c).is_some())
+                    .unwrap_or(0),
+            };
+            match runs.last_mut() {
+                Some((last, last_font, text)) if last == span && *last_font == font => text.push(c),
+                _ => runs.push((*span, font, c.to_string())),
+            }
+        }
+    }
+    if runs.is_empty() {
+        let span = pieces.first().map_or(0, |(span, _)| *span);
+        runs.push((span, 0, String::new()));
+    }
+    runs
+}
+
+// Lines can break before and after these without spaces
+fn breaks_around(c: char) -> bool {
+    matches!(c as u32,
+        0x2e80..=0x9fff | 0xac00..=0xd7af | 0xf900..=0xfaff | 0xff00..=0xffef
+            | 0x1f300..=0x1faff | 0x20000..=0x3ffff)
+}
+
+// Joins the characters either side of it into one emoji
+const ZWJ: char = '\u{200d}';
+
+// Zero width joiners, variation selectors and skin tones, kept with the
+// character before them
+fn joins(c: char) -> bool {
+    matches!(c as u32, 0x200d | 0xfe00..=0xfe0f | 0x1f3fb..=0x1f3ff)
+}
+
+fn width_of(font: &Font, text: &str) -> Result<u32, String> {
+    if text.is_empty() {
+        return Ok(0);
+    }
+    Ok(font.size_of(text).map_err(|e| e.to_string())?.0)
+}
+
+// Returns (width, height, tightly packed straight alpha rgba8) of spans laid
+// out with fonts[span], color for those without their own. Outlines and
+// shadows are grown from the glyphs, widening it past the layout on the sides
+// they reach, and go under all of the text so they don't cover the next span.
+pub fn render_spans_rgba(
+    fonts: &[Vec<&Font>],
+    spans: &[TextSpan],
+    options: &TextLayoutOptions,
+    color: (u8, u8, u8, u8),
+) -> Result<(u32, u32, Vec<u8>), String> {
+    let texts = spans
+        .iter()
+        .map(|span| span.text.as_str())
+        .collect::<Vec<_>>();
+    let layout = layout_spans(fonts, &texts, options)?;
+    // how far past the layout outlines and shadows go, left, top, right, bottom
+    let mut reach = (0, 0, 0, 0);
+    for span in spans {
+        let outline = span.outline.map_or(0, |(width, _)| width as i32);
+        let (dx, dy) = span.shadow.map_or((0, 0), |(offset, _)| offset);
+        reach.0 = i32::max(reach.0, outline - dx.min(0));
+        reach.1 = i32::max(reach.1, outline - dy.min(0));
+        reach.2 = i32::max(reach.2, outline + dx.max(0));
+        reach.3 = i32::max(reach.3, outline + dy.max(0));
+    }
+    let w = (layout.width as i32 + reach.0 + reach.2).max(1) as u32;
+    let h = (layout.height as i32 + reach.1 + reach.3).max(1) as u32;
+    let mut canvas = Canvas {
+        w,
+        h,
+        rgba: vec![0u8; w as usize * h as usize * 4],
+    };
+
+    let mut glyphs = vec![];
+    for run in &layout.runs {
+        if run.text.trim().is_empty() {
+            continue;
+        }
+        let span = &spans[run.span];
+        let fill = span.color.unwrap_or(color);
+        let surface = fonts[run.span][run.font]
+            .render(&run.text)
+            .blended(Color::RGBA(fill.0, fill.1, fill.2, fill.3))
+            .map_err(|e| e.to_string())?
+            .convert_format(PixelFormatEnum::RGBA32)?;
+        let (w, h) = (surface.width(), surface.height());
+        let pitch = surface.pitch() as usize;
+        let rgba = surface.with_lock(|pixels| {
+            pixels
+                .chunks(pitch)
+                .take(h as usize)
+                .flat_map(|row| &row[..w as usize * 4])
+                .copied()
+                .collect::<Vec<_>>()
+        });
+        let (x, y) = (run.x + reach.0, run.y + reach.1);
+        // the outline's shape, its own for emoji as well as glyphs
+        let outline = span
+            .outline
+            .filter(|(width, _)| *width > 0)
+            .map(|(width, color)| {
+                let alpha = rgba.chunks(4).map(|px| px[3]).collect::<Vec<_>>();
+                let width = width as u32;
+                (grow(&alpha, w, h, width), width as i32, color)
+            });
+        glyphs.push((span, x, y, w, h, rgba, outline));
+    }
+    for (span, x, y, w, h, rgba, outline) in &glyphs {
+        let Some(((dx, dy), color)) = span.shadow else {
+            continue;
+        };
+        match outline {
+            Some((grown, width, _)) => {
+                let size = (*w + 2 * *width as u32, *h + 2 * *width as u32);
+                canvas.tint(x - width + dx, y - width + dy, size, grown, color);
+            }
+            None => {
+                let alpha = rgba.chunks(4).map(|px| px[3]).collect::<Vec<_>>();
+                canvas.tint(x + dx, y + dy, (*w, *h), &alpha, color);
+            }
+        }
+    }
+    for (_, x, y, w, h, _, outline) in &glyphs {
+        if let Some((grown, width, color)) = outline {
+            let size = (*w + 2 * *width as u32, *h + 2 * *width as u32);
+            canvas.tint(x - width, y - width, size, grown, *color);
+        }
+    }
+    for (_, x, y, w, h, rgba, _) in &glyphs {
+        canvas.blit(*x, *y, (*w, *h), rgba);
+    }
+    Ok((canvas.w, canvas.h, canvas.rgba))
+}
+
+// Straight alpha rgba8 text is composited into
+struct Canvas {
+    w: u32,
+    h: u32,
+    rgba: Vec<u8>,
+}
+
+impl Canvas {
+    fn blit(&mut self, x: i32, y: i32, (w, h): (u32, u32), rgba: &[u8]) {
+        for sy in 0..h as i32 {
+            for sx in 0..w as i32 {
+                let src = (sy as usize * w as usize + sx as usize) * 4;
+                self.over(x + sx, y + sy, &rgba[src..src + 4]);
+            }
+        }
+    }
+
+    // color with its alpha scaled by each of alpha's
+    fn tint(&mut self, x: i32, y: i32, (w, h): (u32, u32), alpha: &[u8], color: (u8, u8, u8, u8)) {
+        for sy in 0..h as i32 {
+            for sx in 0..w as i32 {
+                let a = alpha[sy as usize * w as usize + sx as usize] as u32 * color.3 as u32;
+                let src = [color.0, color.1, color.2, (a / 255) as u8];
+                self.over(x + sx, y + sy, &src);
+            }
+        }
+    }
+
+    fn over(&mut self, x: i32, y: i32, src: &[u8]) {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return;
+        }
+        let dst = (y as usize * self.w as usize + x as usize) * 4;
+        over(&mut self.rgba[dst..dst + 4], src);
+    }
+}
+
+// Alpha spread out by width pixels all round, w + 2 * width wide and
+// h + 2 * width high, the most of what's within width of each pixel
+fn grow(alpha: &[u8], w: u32, h: u32, width: u32) -> Vec<u8> {
+    let (w, h, r) = (w as i32, h as i32, width as i32);
+    let (gw, gh) = (w + 2 * r, h + 2 * r);
+    let disc = (-r..=r)
+        .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| dx * dx + dy * dy <= r * r)
+        .collect::<Vec<_>>();
+    let mut grown = vec![0u8; (gw * gh) as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let a = alpha[(y * w + x) as usize];
+            if a == 0 {
+                continue;
+            }
+            for (dx, dy) in &disc {
+                let at = ((y + r + dy) * gw + x + r + dx) as usize;
+                grown[at] = grown[at].max(a);
+            }
+        }
+    }
+    grown
+}
+
+// Straight alpha src over dst, for glyphs that overhang their run
+fn over(dst: &mut [u8], src: &[u8]) {
+    let src_a = src[3] as f32 / 255.0;
+    if src_a == 0.0 {
+        return;
+    }
+    if src[3] == 255 || dst[3] == 0 {
+        dst.copy_from_slice(src);
+        return;
+    }
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    for i in 0..3 {
+        let c = (src[i] as f32 * src_a + dst[i] as f32 * dst_a * (1.0 - src_a)) / out_a;
+        dst[i] = c.round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+// Returns (width, height, tightly packed straight alpha rgba8)
+pub fn render_text_rgba(
+    fonts: &[&Font],
+    text: &str,
+    options: &TextLayoutOptions,
+    color: (u8, u8, u8, u8),
+) -> Result<(u32, u32, Vec<u8>), String> {
+    render_spans_rgba(&[fonts.to_vec()], &[TextSpan::from(text)], options, color)
+}
+
+// The same as a surface that blends when blitted
+pub fn render_text_surface(
+    fonts: &[&Font],
+    text: &str,
+    options: &TextLayoutOptions,
+    color: Color,
+) -> Result<Surface<'static>, String> {
+    render_spans_surface(&[fonts.to_vec()], &[TextSpan::from(text)], options, color)
+}
+
+pub fn render_spans_surface(
+    fonts: &[Vec<&Font>],
+    spans: &[TextSpan],
+    options: &TextLayoutOptions,
+    color: Color,
+) -> Result<Surface<'static>, String> {
+    let (w, h, rgba) = render_spans_rgba(fonts, spans, options, color.rgba())?;
+    let mut surface = Surface::new(w, h, PixelFormatEnum::RGBA32)?;
+    let pitch = surface.pitch() as usize;
+    surface.with_lock_mut(|pixels| {
+        for (y, row) in rgba.chunks(w as usize * 4).enumerate() {
+            pixels[y * pitch..y * pitch + row.len()].copy_from_slice(row);
+        }
+    });
+    surface.set_blend_mode(BlendMode::Blend)?;
+    Ok(surface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each unit's text by span, tabs as \t
+    fn unit_texts(spans: &[&str]) -> Vec<Vec<(usize, String)>> {
+        let chars = spans
+            .iter()
+            .enumerate()
+            .flat_map(|(span, text)| text.chars().map(move |c| (span, c)))
+            .collect::<Vec<_>>();
+        units(&chars)
+            .into_iter()
+            .map(|unit| match unit {
+                Unit::Text(pieces) => pieces,
+                Unit::Tab => vec![(0, "\t".to_string())],
+            })
+            .collect()
+    }
+
+    fn piece(span: usize, text: &str) -> (usize, String) {
+        (span, text.to_string())
+    }
+
+    #[test]
+    fn words_keep_their_spaces() {
+        assert_eq!(
+            unit_texts(&["one two  three"]),
+            [
+                vec![piece(0, "one ")],
+                vec![piece(0, "two  ")],
+                vec![piece(0, "three")]
+            ]
+        );
+        assert_eq!(unit_texts(&[""]), [vec![piece(0, "")]]);
+    }
+
+    #[test]
+    fn tabs_are_their_own_units() {
+        assert_eq!(
+            unit_texts(&["a\tb\t"]),
+            [
+                vec![piece(0, "a")],
+                vec![piece(0, "\t")],
+                vec![piece(0, "b")],
+                vec![piece(0, "\t")]
+            ]
+        );
+    }
+
+    #[test]
+    fn words_span_spans() {
+        assert_eq!(
+            unit_texts(&["bo", "ld rest"]),
+            [
+                vec![piece(0, "bo"), piece(1, "ld ")],
+                vec![piece(1, "rest")]
+            ]
+        );
+    }
+
+    #[test]
+    fn breaks_between_cjk_and_emoji() {
+        assert_eq!(
+            unit_texts(&["日本a"]),
+            [
+                vec![piece(0, "日")],
+                vec![piece(0, "本")],
+                vec![piece(0, "a")]
+            ]
+        );
+        // a thumbs up with a skin tone stays whole
+        assert_eq!(
+            unit_texts(&["\u{1f44d}\u{1f3fd}\u{1f44d}"]),
+            [
+                vec![piece(0, "\u{1f44d}\u{1f3fd}")],
+                vec![piece(0, "\u{1f44d}")]
+            ]
+        );
+        // as does a family joined up with zero width joiners
+        assert_eq!(
+            unit_texts(&["\u{1f468}\u{200d}\u{1f467}\u{1f468}"]),
+            [
+                vec![piece(0, "\u{1f468}\u{200d}\u{1f467}")],
+                vec![piece(0, "\u{1f468}")]
+            ]
+        );
+    }
+
+    #[test]
+    fn over_blends_straight_alpha() {
+        let mut dst = [0, 0, 255, 255];
+        over(&mut dst, &[255, 0, 0, 0]);
+        assert_eq!(dst, [0, 0, 255, 255]);
+        over(&mut dst, &[255, 0, 0, 128]);
+        assert_eq!(dst, [128, 0, 127, 255]);
+
+        let mut clear = [9, 9, 9, 0];
+        over(&mut clear, &[1, 2, 3, 4]);
+        assert_eq!(clear, [1, 2, 3, 4]);
+
+        let mut half = [0, 0, 0, 128];
+        over(&mut half, &[255, 255, 255, 128]);
+        assert_eq!(half, [170, 170, 170, 192]);
+    }
+}
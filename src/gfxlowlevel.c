@@ -2,10 +2,12 @@
 
 #include <SDL2/SDL.h>
 #include <SDL2/SDL_vulkan.h>
+#include <libavdevice/avdevice.h>
 #include <libavformat/avformat.h>
 #include <libplacebo/gpu.h>
 #include <libplacebo/renderer.h>
 #include <libplacebo/shaders.h>
+#include <libplacebo/shaders/colorspace.h>
 #include <libplacebo/shaders/custom.h>
 #include <libplacebo/shaders/lut.h>
 #include <libplacebo/shaders/sampling.h>
@@ -14,10 +16,18 @@
 #pragma GCC diagnostic ignored "-Wswitch"
 #include <libplacebo/utils/libav.h>
 #pragma GCC diagnostic pop
+#include <libavutil/hwcontext.h>
 #include <libavutil/pixfmt.h>
 #include <libplacebo/utils/upload.h>
+#include <stdarg.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <pthread.h>
+#endif
 
 void gfx_lowlevel_gpu_ctx_destroy(struct gfx_lowlevel_gpu_ctx** ctx) {
   if (ctx == NULL || *ctx == NULL) {
@@ -40,6 +50,9 @@ void gfx_lowlevel_gpu_ctx_destroy(struct gfx_lowlevel_gpu_ctx** ctx) {
   free((*ctx)->resource_pool.descs);
   free((*ctx)->resource_pool.attribs);
 
+  if ((*ctx)->readback_tex != NULL) {
+    pl_tex_destroy((*ctx)->vk->gpu, &((*ctx)->readback_tex));
+  }
   if ((*ctx)->dispatch != NULL) {
     pl_dispatch_destroy(&((*ctx)->dispatch));
   }
@@ -76,8 +89,138 @@ void gfx_lowlevel_swapwindow(struct gfx_lowlevel_gpu_ctx* ctx) {
   }
 }
 
+int gfx_lowlevel_gpu_name(int index, char* name, size_t len) {
+  if (SDL_Vulkan_GetVkGetInstanceProcAddr() == NULL &&
+      SDL_Vulkan_LoadLibrary(NULL) != 0) {
+    fprintf(stderr, "gfx_ll> Failed to load Vulkan: %s\n", SDL_GetError());
+    return -1;
+  }
+  struct pl_log_params log_params = {
+      .log_cb = log_callback,
+      .log_priv = NULL,
+      .log_level = PL_LOG_WARN,
+  };
+  pl_log log = pl_log_create(PL_API_VER, &log_params);
+  pl_vk_inst inst = pl_vk_inst_create(
+      log, &(struct pl_vk_inst_params){
+               .get_proc_addr = SDL_Vulkan_GetVkGetInstanceProcAddr(),
+           });
+  if (inst == NULL) {
+    fprintf(stderr, "gfx_ll> Failed to create Vulkan instance\n");
+    pl_log_destroy(&log);
+    return -1;
+  }
+
+  PFN_vkEnumeratePhysicalDevices enumerate =
+      (PFN_vkEnumeratePhysicalDevices)inst->get_proc_addr(
+          inst->instance, "vkEnumeratePhysicalDevices");
+  PFN_vkGetPhysicalDeviceProperties properties =
+      (PFN_vkGetPhysicalDeviceProperties)inst->get_proc_addr(
+          inst->instance, "vkGetPhysicalDeviceProperties");
+  uint32_t count = 0;
+  int ret = -1;
+  if (enumerate && properties &&
+      enumerate(inst->instance, &count, NULL) == VK_SUCCESS) {
+    ret = (int)count;
+    if (index >= 0 && (uint32_t)index < count) {
+      VkPhysicalDevice* devices = calloc(count, sizeof(VkPhysicalDevice));
+      if (devices && enumerate(inst->instance, &count, devices) == VK_SUCCESS &&
+          (uint32_t)index < count) {
+        VkPhysicalDeviceProperties props;
+        properties(devices[index], &props);
+        snprintf(name, len, "%s", props.deviceName);
+      } else {
+        ret = -1;
+      }
+      free(devices);
+    }
+  }
+
+  pl_vk_inst_destroy(&inst);
+  pl_log_destroy(&log);
+  return ret;
+}
+
+struct probe_log {
+  char* buf;
+  size_t size;
+  size_t len;
+};
+
+// only the probing thread has one, so other threads' logs pass straight by
+static _Thread_local struct probe_log* probe_log = NULL;
+#ifdef _WIN32
+static SRWLOCK probe_lock = SRWLOCK_INIT;
+#define probe_lock_acquire() AcquireSRWLockExclusive(&probe_lock)
+#define probe_lock_release() ReleaseSRWLockExclusive(&probe_lock)
+#else
+static pthread_mutex_t probe_lock = PTHREAD_MUTEX_INITIALIZER;
+#define probe_lock_acquire() pthread_mutex_lock(&probe_lock)
+#define probe_lock_release() pthread_mutex_unlock(&probe_lock)
+#endif
+
+static void probe_log_callback(void* avcl, int level, const char* fmt,
+                               va_list vl) {
+  struct probe_log* log = probe_log;
+  if (log == NULL) {
+    av_log_default_callback(avcl, level, fmt, vl);
+    return;
+  }
+  if (log->len + 1 >= log->size) {
+    return;
+  }
+  int written = vsnprintf(log->buf + log->len, log->size - log->len, fmt, vl);
+  if (written > 0) {
+    log->len += (size_t)written;
+    if (log->len > log->size - 1) {
+      log->len = log->size - 1;
+    }
+  }
+}
+
+int gfx_lowlevel_probe_input(const char* format, const char* url,
+                             const char* options, char* log, size_t log_size) {
+  if (log == NULL || log_size == 0) {
+    return AVERROR(EINVAL);
+  }
+  log[0] = '\0';
+  avdevice_register_all();
+  const AVInputFormat* input_format = av_find_input_format(format);
+  if (input_format == NULL) {
+    return AVERROR_DEMUXER_NOT_FOUND;
+  }
+  AVDictionary* dict = NULL;
+  if (options && av_dict_parse_string(&dict, options, "=", ":", 0) < 0) {
+    av_dict_free(&dict);
+    return AVERROR(EINVAL);
+  }
+
+  struct probe_log capture = {.buf = log, .size = log_size, .len = 0};
+  // the callback is process wide, one probe at a time keeps restoring it simple
+  probe_lock_acquire();
+  int old_level = av_log_get_level();
+  if (old_level < AV_LOG_INFO) {
+    av_log_set_level(AV_LOG_INFO);
+  }
+  av_log_set_callback(probe_log_callback);
+  probe_log = &capture;
+
+  AVFormatContext* fmt_ctx = NULL;
+  int ret = avformat_open_input(&fmt_ctx, url, input_format, &dict);
+  if (ret == 0) {
+    avformat_close_input(&fmt_ctx);
+  }
+
+  probe_log = NULL;
+  av_log_set_callback(av_log_default_callback);
+  av_log_set_level(old_level);
+  probe_lock_release();
+  av_dict_free(&dict);
+  return ret;
+}
+
 struct gfx_lowlevel_gpu_ctx* gfx_lowlevel_gpu_ctx_init(
-    struct SDL_Window* window) {
+    struct SDL_Window* window, const char* device_name) {
   struct gfx_lowlevel_gpu_ctx* ctx =
       malloc(sizeof(struct gfx_lowlevel_gpu_ctx));
   if (!ctx) {
@@ -100,21 +243,44 @@ struct gfx_lowlevel_gpu_ctx* gfx_lowlevel_gpu_ctx_init(
     return NULL;
   }
 
+#ifdef __APPLE__
   const char* extensions[] = {
       "VK_MVK_moltenvk",
       "VK_MVK_macos_surface",
       "VK_EXT_metal_surface",
   };
   unsigned int num_extensions = sizeof(extensions) / sizeof(extensions[0]);
+#else
+  // the native driver's surface extensions for whichever of win32, x11 or
+  // wayland SDL's window is on
+  const char* extensions[16];
+  unsigned int num_extensions = sizeof(extensions) / sizeof(extensions[0]);
+  if (!SDL_Vulkan_GetInstanceExtensions(window, &num_extensions, extensions)) {
+    fprintf(stderr, "gfx_ll> Failed to get Vulkan surface extensions: %s\n",
+            SDL_GetError());
+    gfx_lowlevel_gpu_ctx_destroy(&ctx);
+    return NULL;
+  }
+#endif
+
+  // Needed for anything other than plain sRGB output (P3, HDR)
+  const char* opt_extensions[] = {
+      "VK_EXT_swapchain_colorspace",
+  };
+  unsigned int num_opt_extensions =
+      sizeof(opt_extensions) / sizeof(opt_extensions[0]);
 
   struct pl_vulkan_params vk_params = {
       .async_transfer = 1,
       .async_compute = 1,
       .queue_count = 1,
+      .device_name = device_name,
       .instance_params =
           &(struct pl_vk_inst_params){
               .extensions = (const char**)extensions,
               .num_extensions = num_extensions,
+              .opt_extensions = (const char**)opt_extensions,
+              .num_opt_extensions = num_opt_extensions,
           },
       .get_proc_addr = SDL_Vulkan_GetVkGetInstanceProcAddr(),
   };
@@ -219,6 +385,124 @@ int gfx_lowlevel_gpu_ctx_handle_resize(struct gfx_lowlevel_gpu_ctx* ctx,
   return 0;
 }
 
+int gfx_lowlevel_gpu_ctx_set_output_colorspace(
+    struct gfx_lowlevel_gpu_ctx* ctx, enum gfx_lowlevel_colorspace csp) {
+  if (!ctx || !ctx->swchain) {
+    fprintf(stderr, "gfx_ll> Invalid context or swapchain\n");
+    return EINVAL;
+  }
+
+  struct pl_color_space hint = pl_color_space_srgb;
+  switch (csp) {
+    case GFX_LOWLEVEL_CSP_SRGB:
+      break;
+    case GFX_LOWLEVEL_CSP_DISPLAY_P3:
+      hint = pl_color_space_display_p3;
+      break;
+    case GFX_LOWLEVEL_CSP_HDR_PQ:
+      hint = pl_color_space_hdr10;
+      break;
+    default:
+      fprintf(stderr, "gfx_ll> Unknown output colorspace %d\n", csp);
+      return EINVAL;
+  }
+
+  // Only a hint, the swapchain falls back to whatever the surface supports.
+  // The negotiated space shows up in window_frame.color on the next frame.
+  pl_swapchain_colorspace_hint(ctx->swchain, &hint);
+  ctx->output_csp = csp;
+  return 0;
+}
+
+static bool surface_has_present_mode(struct gfx_lowlevel_gpu_ctx* ctx,
+                                     VkPresentModeKHR mode) {
+  // fifo is the one mode every surface has to support
+  if (mode == VK_PRESENT_MODE_FIFO_KHR) {
+    return true;
+  }
+  PFN_vkGetPhysicalDeviceSurfacePresentModesKHR get_modes =
+      (PFN_vkGetPhysicalDeviceSurfacePresentModesKHR)ctx->vk->get_proc_addr(
+          ctx->vk->instance, "vkGetPhysicalDeviceSurfacePresentModesKHR");
+  if (get_modes == NULL) {
+    return false;
+  }
+  VkPresentModeKHR modes[16];
+  uint32_t count = sizeof(modes) / sizeof(modes[0]);
+  // VK_INCOMPLETE still fills in the first count modes
+  if (get_modes(ctx->vk->phys_device, ctx->vk_surface, &count, modes) < 0) {
+    return false;
+  }
+  for (uint32_t i = 0; i < count; i++) {
+    if (modes[i] == mode) {
+      return true;
+    }
+  }
+  return false;
+}
+
+// Only between frames. A swapchain can't change its present mode so a new
+// one is made on the same surface.
+int gfx_lowlevel_gpu_ctx_set_present_mode(struct gfx_lowlevel_gpu_ctx* ctx,
+                                          enum gfx_lowlevel_present_mode mode) {
+  if (!ctx || !ctx->swchain || ctx->started) {
+    fprintf(stderr, "gfx_ll> Invalid context or swapchain\n");
+    return EINVAL;
+  }
+
+  VkPresentModeKHR vk_mode;
+  switch (mode) {
+    case GFX_LOWLEVEL_PRESENT_FIFO:
+      vk_mode = VK_PRESENT_MODE_FIFO_KHR;
+      break;
+    case GFX_LOWLEVEL_PRESENT_MAILBOX:
+      vk_mode = VK_PRESENT_MODE_MAILBOX_KHR;
+      break;
+    case GFX_LOWLEVEL_PRESENT_IMMEDIATE:
+      vk_mode = VK_PRESENT_MODE_IMMEDIATE_KHR;
+      break;
+    default:
+      fprintf(stderr, "gfx_ll> Unknown present mode %d\n", mode);
+      return EINVAL;
+  }
+  if (!surface_has_present_mode(ctx, vk_mode)) {
+    fprintf(stderr, "gfx_ll> Present mode %d not supported, using fifo\n",
+            mode);
+    vk_mode = VK_PRESENT_MODE_FIFO_KHR;
+    mode = GFX_LOWLEVEL_PRESENT_FIFO;
+  }
+  if (mode == ctx->present_mode) {
+    return 0;
+  }
+
+  pl_gpu_finish(ctx->vk->gpu);
+  pl_swapchain_destroy(&ctx->swchain);
+  struct pl_vulkan_swapchain_params swapchain_params = {
+      .surface = ctx->vk_surface,
+      .present_mode = vk_mode,
+  };
+  ctx->swchain = pl_vulkan_create_swapchain(ctx->vk, &swapchain_params);
+  if (ctx->swchain == NULL && mode != GFX_LOWLEVEL_PRESENT_FIFO) {
+    fprintf(stderr, "gfx_ll> Failed to create swapchain, back to fifo\n");
+    swapchain_params.present_mode = VK_PRESENT_MODE_FIFO_KHR;
+    mode = GFX_LOWLEVEL_PRESENT_FIFO;
+    ctx->swchain = pl_vulkan_create_swapchain(ctx->vk, &swapchain_params);
+  }
+  if (ctx->swchain == NULL) {
+    fprintf(stderr, "gfx_ll> Failed to create libplacebo swapchain\n");
+    return -1;
+  }
+  ctx->present_mode = mode;
+
+  int width, height;
+  SDL_GetWindowSize(ctx->shared_window, &width, &height);
+  if (!pl_swapchain_resize(ctx->swchain, &width, &height)) {
+    fprintf(stderr, "gfx_ll> Failed to resize swapchain\n");
+    return -1;
+  }
+  // the colorspace hint belonged to the old swapchain
+  return gfx_lowlevel_gpu_ctx_set_output_colorspace(ctx, ctx->output_csp);
+}
+
 // This may return and need to be rerun after window events are drained
 bool gfx_lowlevel_gpu_ctx_start_frame(struct gfx_lowlevel_gpu_ctx* ctx) {
   assert(ctx != NULL);
@@ -242,34 +526,21 @@ int gfx_lowlevel_map_frame_ctx(struct gfx_lowlevel_gpu_ctx* ctx,
     return EINVAL;
   }
 
-  if (dst->to_rgba == NULL) {
-    enum AVPixelFormat src_format = src->format;
-    if (src_format == AV_PIX_FMT_VIDEOTOOLBOX) {
-      src_format = AV_PIX_FMT_NV12;
-    }
-    struct SwsContext* sws_ctx = sws_getContext(
-        src->width, src->height, src_format, src->width, src->height,
-        AV_PIX_FMT_RGBA, SWS_BILINEAR, NULL, NULL, NULL);
-    if (!sws_ctx) {
-      fprintf(stderr, "gfx_ll> Failed to create sws context\n");
-      return ENOMEM;
-    }
-    dst->to_rgba = sws_ctx;
-  }
-
   if (dst->is_mapped) {
     pl_unmap_avframe(ctx->vk->gpu, &dst->pl_frame);
   }
 
   int ret = 0;
   AVFrame* tmp = NULL;
-  if (src->format == AV_PIX_FMT_VIDEOTOOLBOX) {
+  // videotoolbox, vaapi, d3d11va and cuda frames are downloaded in the format
+  // their decoder decodes to, nv12 or p010 mostly
+  if (src->hw_frames_ctx) {
     tmp = av_frame_alloc();
     if (!tmp) {
       fprintf(stderr, "gfx_ll> Failed to allocate temporary AVFrame\n");
       return ENOMEM;
     }
-    tmp->format = AV_PIX_FMT_NV12;
+    tmp->format = ((AVHWFramesContext*)src->hw_frames_ctx->data)->sw_format;
     ret = av_hwframe_transfer_data(tmp, src, 0);
     if (ret < 0) {
       fprintf(stderr, "gfx_ll> Failed to transfer data %d\n", ret);
@@ -280,6 +551,18 @@ int gfx_lowlevel_map_frame_ctx(struct gfx_lowlevel_gpu_ctx* ctx,
     src = tmp;
   }
 
+  if (dst->to_rgba == NULL) {
+    struct SwsContext* sws_ctx = sws_getContext(
+        src->width, src->height, src->format, src->width, src->height,
+        AV_PIX_FMT_RGBA, SWS_BILINEAR, NULL, NULL, NULL);
+    if (!sws_ctx) {
+      fprintf(stderr, "gfx_ll> Failed to create sws context\n");
+      av_frame_free(&tmp);
+      return ENOMEM;
+    }
+    dst->to_rgba = sws_ctx;
+  }
+
   AVFrame *map_src, *rgba_frame = NULL;
   if (dst->to_rgba != NULL) {
     rgba_frame = av_frame_alloc();
@@ -585,6 +868,17 @@ int gfx_lowlevel_gpu_ctx_render(struct gfx_lowlevel_gpu_ctx* ctx,
     pl_shader_custom_lut(sh, lut->lut, &lut->lut_state);
   }
 
+  // Mixers are authored in sRGB, map into whatever the swapchain negotiated
+  if (dst_frame == &ctx->window_frame &&
+      ctx->output_csp != GFX_LOWLEVEL_CSP_SRGB &&
+      !pl_color_space_equal(&pl_color_space_srgb, &dst_frame->color)) {
+    pl_shader_color_map_ex(sh, &pl_color_map_default_params,
+                           &(struct pl_color_map_args){
+                               .src = pl_color_space_srgb,
+                               .dst = dst_frame->color,
+                           });
+  }
+
   if (debug) {
     const struct pl_shader_res* res = pl_shader_finalize(sh);
     if (!res) {
@@ -615,6 +909,7 @@ int gfx_lowlevel_gpu_ctx_render(struct gfx_lowlevel_gpu_ctx* ctx,
             &(struct pl_dispatch_params){
                 .shader = &sh,
                 .target = dst_frame->planes[0].texture,
+                .blend_params = params->blend ? &pl_alpha_overlay : NULL,
                 .rect =
                     {
                         .x0 = params->dst.x0 *
@@ -813,3 +1108,831 @@ int gfx_lowlevel_frame_copy(struct gfx_lowlevel_gpu_ctx* ctx,
 
   return 0;
 }
+
+int gfx_lowlevel_draw_triangles(struct gfx_lowlevel_gpu_ctx* ctx,
+                                struct pl_frame* dst_frame,
+                                const float* vertices, int num_vertices) {
+  if (!ctx || !dst_frame || !vertices || !dst_frame->planes[0].texture) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+  if (num_vertices < 3) {
+    return 0;
+  }
+
+  pl_shader sh = pl_dispatch_begin(ctx->dispatch);
+  if (!sh) {
+    fprintf(stderr, "gfx_ll> Failed to begin dispatch\n");
+    return EINVAL;
+  }
+
+  // non position attributes are passed through to the fragment shader by name
+  struct pl_custom_shader sh_params = {
+      .description = "Draw triangles",
+      .body = "color = vcolor;",
+      .input = PL_SHADER_SIG_NONE,
+      .output = PL_SHADER_SIG_COLOR,
+  };
+  if (!pl_shader_custom(sh, &sh_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create geometry shader\n");
+    pl_dispatch_abort(ctx->dispatch, &sh);
+    return EINVAL;
+  }
+
+  struct pl_vertex_attrib attribs[] = {
+      {
+          .name = "vpos",
+          .fmt = pl_find_vertex_fmt(ctx->vk->gpu, PL_FMT_FLOAT, 2),
+          .offset = 0,
+      },
+      {
+          .name = "vcolor",
+          .fmt = pl_find_vertex_fmt(ctx->vk->gpu, PL_FMT_FLOAT, 4),
+          .offset = sizeof(float) * 2,
+      },
+  };
+
+  pl_tex target = dst_frame->planes[0].texture;
+  if (!pl_dispatch_vertex(
+          ctx->dispatch,
+          &(struct pl_dispatch_vertex_params){
+              .shader = &sh,
+              .target = target,
+              .scissors = {0, 0, target->params.w, target->params.h},
+              .blend_params = &pl_alpha_overlay,
+              .vertex_attribs = attribs,
+              .num_vertex_attribs = 2,
+              .vertex_stride = sizeof(float) * 6,
+              .vertex_position_idx = 0,
+              .vertex_coords = PL_COORDS_ABSOLUTE,
+              .vertex_type = PL_PRIM_TRIANGLE_LIST,
+              .vertex_count = num_vertices,
+              .vertex_data = vertices,
+          })) {
+    fprintf(stderr, "gfx_ll> Failed to dispatch geometry\n");
+    return EINVAL;
+  }
+  return 0;
+}
+
+int gfx_lowlevel_draw_glyphs(struct gfx_lowlevel_gpu_ctx* ctx,
+                             struct pl_frame* dst_frame,
+                             struct pl_frame* atlas, const float* vertices,
+                             int num_vertices, bool sdf) {
+  if (!ctx || !dst_frame || !atlas || !vertices ||
+      !dst_frame->planes[0].texture || !atlas->planes[0].texture) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+  if (num_vertices < 3) {
+    return 0;
+  }
+
+  pl_shader sh = pl_dispatch_begin(ctx->dispatch);
+  if (!sh) {
+    fprintf(stderr, "gfx_ll> Failed to begin dispatch\n");
+    return EINVAL;
+  }
+
+  struct pl_shader_desc desc = {
+      .desc = {.name = "atlas",
+               .type = PL_DESC_SAMPLED_TEX,
+               .access = PL_DESC_ACCESS_READONLY},
+      .binding =
+          {
+              .object = atlas->planes[0].texture,
+              .address_mode = PL_TEX_ADDRESS_CLAMP,
+              .sample_mode = PL_TEX_SAMPLE_LINEAR,
+          },
+  };
+  // the edge is smoothed over a screen pixel whatever the scale
+  const char* sdf_body =
+      "float d = texture(atlas, vuv).a;\n"
+      "float w = max(fwidth(d), 1e-4);\n"
+      "color = vec4(vcolor.rgb, vcolor.a * smoothstep(0.5 - w, 0.5 + w, d));";
+  struct pl_custom_shader sh_params = {
+      .description = "Draw glyphs",
+      .body = sdf ? sdf_body : "color = texture(atlas, vuv) * vcolor;",
+      .input = PL_SHADER_SIG_NONE,
+      .output = PL_SHADER_SIG_COLOR,
+      .descriptors = &desc,
+      .num_descriptors = 1,
+  };
+  if (!pl_shader_custom(sh, &sh_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create glyph shader\n");
+    pl_dispatch_abort(ctx->dispatch, &sh);
+    return EINVAL;
+  }
+
+  struct pl_vertex_attrib attribs[] = {
+      {
+          .name = "vpos",
+          .fmt = pl_find_vertex_fmt(ctx->vk->gpu, PL_FMT_FLOAT, 2),
+          .offset = 0,
+      },
+      {
+          .name = "vuv",
+          .fmt = pl_find_vertex_fmt(ctx->vk->gpu, PL_FMT_FLOAT, 2),
+          .offset = sizeof(float) * 2,
+      },
+      {
+          .name = "vcolor",
+          .fmt = pl_find_vertex_fmt(ctx->vk->gpu, PL_FMT_FLOAT, 4),
+          .offset = sizeof(float) * 4,
+      },
+  };
+
+  pl_tex target = dst_frame->planes[0].texture;
+  if (!pl_dispatch_vertex(
+          ctx->dispatch,
+          &(struct pl_dispatch_vertex_params){
+              .shader = &sh,
+              .target = target,
+              .scissors = {0, 0, target->params.w, target->params.h},
+              .blend_params = &pl_alpha_overlay,
+              .vertex_attribs = attribs,
+              .num_vertex_attribs = 3,
+              .vertex_stride = sizeof(float) * 8,
+              .vertex_position_idx = 0,
+              .vertex_coords = PL_COORDS_ABSOLUTE,
+              .vertex_type = PL_PRIM_TRIANGLE_LIST,
+              .vertex_count = num_vertices,
+              .vertex_data = vertices,
+          })) {
+    fprintf(stderr, "gfx_ll> Failed to dispatch glyphs\n");
+    return EINVAL;
+  }
+  return 0;
+}
+
+struct gfx_lowlevel_mesh_pass* gfx_lowlevel_mesh_pass_init(
+    struct gfx_lowlevel_gpu_ctx* ctx, const char* vertex) {
+  if (!ctx || !vertex) {
+    fprintf(stderr, "gfx_ll> Invalid context or vertex shader\n");
+    return NULL;
+  }
+  struct gfx_lowlevel_mesh_pass* mesh_pass =
+      malloc(sizeof(struct gfx_lowlevel_mesh_pass));
+  if (!mesh_pass) {
+    fprintf(stderr, "gfx_ll> Failed to allocate memory for mesh pass\n");
+    return NULL;
+  }
+  memset(mesh_pass, 0, sizeof(struct gfx_lowlevel_mesh_pass));
+  mesh_pass->ctx = ctx;
+  mesh_pass->vertex = malloc(strlen(vertex) + 1);
+  if (!mesh_pass->vertex) {
+    free(mesh_pass);
+    return NULL;
+  }
+  strncpy(mesh_pass->vertex, vertex, strlen(vertex) + 1);
+  return mesh_pass;
+}
+
+static void mesh_pass_release(pl_gpu gpu,
+                              struct gfx_lowlevel_mesh_pass* mesh_pass) {
+  pl_pass_destroy(gpu, &mesh_pass->pass);
+  pl_buf_destroy(gpu, &mesh_pass->vars_buf);
+  free(mesh_pass->vars_data);
+  mesh_pass->vars_data = NULL;
+  mesh_pass->vars_size = 0;
+}
+
+void gfx_lowlevel_mesh_pass_destroy(struct gfx_lowlevel_mesh_pass** mesh_pass) {
+  if (mesh_pass && *mesh_pass) {
+    mesh_pass_release((*mesh_pass)->ctx->vk->gpu, *mesh_pass);
+    free((*mesh_pass)->vertex);
+    free(*mesh_pass);
+    *mesh_pass = NULL;
+  }
+}
+
+// Appends printf style to a malloced string, false if it couldn't grow
+static bool glsl_append(char** glsl, size_t* len, const char* fmt, ...) {
+  va_list args;
+  va_start(args, fmt);
+  int add = vsnprintf(NULL, 0, fmt, args);
+  va_end(args);
+  if (add < 0) {
+    return false;
+  }
+  char* grown = realloc(*glsl, *len + add + 1);
+  if (!grown) {
+    return false;
+  }
+  *glsl = grown;
+  va_start(args, fmt);
+  vsnprintf(*glsl + *len, add + 1, fmt, args);
+  va_end(args);
+  *len += add;
+  return true;
+}
+
+// The inputs and variables a mixer's shader sees, the same in both stages.
+// Bindings are samplers for the frames then the passes, then the variables'
+// uniform block.
+static bool mesh_pass_decls(char** glsl, size_t* len,
+                            struct gfx_lowlevel_filter_params const* params,
+                            int num_frames, int num_passes) {
+  int binding = 0;
+  for (int i = 0; i < num_frames; i++) {
+    if (!glsl_append(glsl, len,
+                     "layout(binding=%d) uniform sampler2D src_tex%d;\n",
+                     binding++, i)) {
+      return false;
+    }
+  }
+  for (int i = 0; i < num_passes; i++) {
+    if (!glsl_append(glsl, len,
+                     "layout(binding=%d) uniform sampler2D pass_tex%d;\n",
+                     binding++, i)) {
+      return false;
+    }
+  }
+  if (params->num_vars == 0) {
+    return true;
+  }
+  if (!glsl_append(glsl, len,
+                   "layout(std140, binding=%d) uniform mix_vars {\n",
+                   binding)) {
+    return false;
+  }
+  for (int i = 0; i < params->num_vars; i++) {
+    const struct pl_var* var = &params->vars[i].var;
+    bool ok = var->dim_a > 1
+                  ? glsl_append(glsl, len, "  %s %s[%d];\n",
+                                pl_var_glsl_type_name(*var), var->name,
+                                var->dim_a)
+                  : glsl_append(glsl, len, "  %s %s;\n",
+                                pl_var_glsl_type_name(*var), var->name);
+    if (!ok) {
+      return false;
+    }
+  }
+  return glsl_append(glsl, len, "};\n");
+}
+
+static int mesh_pass_build(struct gfx_lowlevel_gpu_ctx* ctx,
+                           struct gfx_lowlevel_mesh_pass* mesh_pass,
+                           struct gfx_lowlevel_filter_params const* params,
+                           pl_fmt target_format, int num_frames,
+                           int num_passes) {
+  pl_gpu gpu = ctx->vk->gpu;
+  mesh_pass_release(gpu, mesh_pass);
+
+  // std140, the same layout the block is declared with
+  size_t vars_size = 0;
+  for (int i = 0; i < params->num_vars; i++) {
+    struct pl_var_layout layout =
+        pl_std140_layout(vars_size, &params->vars[i].var);
+    vars_size = layout.offset + layout.size;
+  }
+  vars_size = (vars_size + 15) & ~(size_t)15;
+  if (vars_size > 0) {
+    mesh_pass->vars_data = calloc(1, vars_size);
+    mesh_pass->vars_buf =
+        pl_buf_create(gpu, &(struct pl_buf_params){
+                               .size = vars_size,
+                               .uniform = true,
+                               .host_writable = true,
+                           });
+    if (!mesh_pass->vars_data || !mesh_pass->vars_buf) {
+      fprintf(stderr, "gfx_ll> Failed to create mesh pass variables\n");
+      mesh_pass_release(gpu, mesh_pass);
+      return ENOMEM;
+    }
+    mesh_pass->vars_size = vars_size;
+  }
+
+  char* vert = NULL;
+  size_t vert_len = 0;
+  char* frag = NULL;
+  size_t frag_len = 0;
+  bool ok =
+      glsl_append(&vert, &vert_len,
+                  "layout(location=0) in vec3 mesh_position;\n"
+                  "layout(location=1) in vec2 mesh_uv;\n"
+                  "layout(location=0) out vec2 src_uv;\n") &&
+      mesh_pass_decls(&vert, &vert_len, params, num_frames, num_passes) &&
+      glsl_append(&vert, &vert_len,
+                  "%s\n"
+                  "void main() {\n"
+                  "  vec2 uv = mesh_uv;\n"
+                  "  gl_Position = vertex(mesh_position, uv);\n"
+                  // vulkan's clip space has y down
+                  "  gl_Position.y = -gl_Position.y;\n"
+                  "  src_uv = uv;\n"
+                  "}\n",
+                  mesh_pass->vertex) &&
+      glsl_append(&frag, &frag_len,
+                  "layout(location=0) in vec2 src_uv;\n"
+                  "layout(location=0) out vec4 frag_color;\n") &&
+      mesh_pass_decls(&frag, &frag_len, params, num_frames, num_passes) &&
+      glsl_append(&frag, &frag_len,
+                  "%s\n%s\n"
+                  "void main() {\n"
+                  "  vec4 color = vec4(0.0, 0.0, 0.0, 1.0);\n"
+                  "  %s\n"
+                  "  frag_color = color;\n"
+                  "}\n",
+                  params->prelude ? params->prelude : "",
+                  params->header ? params->header : "",
+                  params->body ? params->body : "");
+  if (!ok) {
+    free(vert);
+    free(frag);
+    mesh_pass_release(gpu, mesh_pass);
+    return ENOMEM;
+  }
+
+  int num_descs = num_frames + num_passes + (vars_size > 0 ? 1 : 0);
+  struct pl_desc* descs = calloc(num_descs > 0 ? num_descs : 1,
+                                 sizeof(struct pl_desc));
+  char(*names)[32] = calloc(num_descs > 0 ? num_descs : 1, 32);
+  if (!descs || !names) {
+    free(vert);
+    free(frag);
+    free(descs);
+    free(names);
+    mesh_pass_release(gpu, mesh_pass);
+    return ENOMEM;
+  }
+  for (int i = 0; i < num_descs; i++) {
+    bool is_vars = vars_size > 0 && i == num_descs - 1;
+    if (i < num_frames) {
+      snprintf(names[i], 32, "src_tex%d", i);
+    } else if (!is_vars) {
+      snprintf(names[i], 32, "pass_tex%d", i - num_frames);
+    } else {
+      snprintf(names[i], 32, "mix_vars");
+    }
+    descs[i] = (struct pl_desc){
+        .name = names[i],
+        .type = is_vars ? PL_DESC_BUF_UNIFORM : PL_DESC_SAMPLED_TEX,
+        .binding = i,
+        .access = PL_DESC_ACCESS_READONLY,
+    };
+  }
+
+  struct pl_vertex_attrib attribs[] = {
+      {
+          .name = "mesh_position",
+          .fmt = pl_find_vertex_fmt(gpu, PL_FMT_FLOAT, 3),
+          .offset = 0,
+          .location = 0,
+      },
+      {
+          .name = "mesh_uv",
+          .fmt = pl_find_vertex_fmt(gpu, PL_FMT_FLOAT, 2),
+          .offset = sizeof(float) * 3,
+          .location = 1,
+      },
+  };
+  mesh_pass->pass = pl_pass_create(
+      gpu, &(struct pl_pass_params){
+               .type = PL_PASS_RASTER,
+               .glsl_shader = frag,
+               .vertex_shader = vert,
+               .descriptors = descs,
+               .num_descriptors = num_descs,
+               .vertex_attribs = attribs,
+               .num_vertex_attribs = 2,
+               .vertex_stride = sizeof(float) * 5,
+               .target_format = target_format,
+               .load_target = true,
+           });
+  free(vert);
+  free(frag);
+  free(descs);
+  free(names);
+  if (!mesh_pass->pass) {
+    fprintf(stderr, "gfx_ll> Failed to create mesh pass\n");
+    mesh_pass_release(gpu, mesh_pass);
+    return EINVAL;
+  }
+  mesh_pass->target_format = target_format;
+  mesh_pass->num_frames = num_frames;
+  mesh_pass->num_passes = num_passes;
+  mesh_pass->num_vars = params->num_vars;
+  return 0;
+}
+
+int gfx_lowlevel_mesh_pass_render(
+    struct gfx_lowlevel_gpu_ctx* ctx, struct gfx_lowlevel_mesh_pass* mesh_pass,
+    struct gfx_lowlevel_filter_params const* params, struct pl_frame* dst_frame,
+    struct pl_frame** src_frames, int num_frames, struct pl_frame** passes,
+    int num_passes, const float* vertices, int num_vertices) {
+  if (!ctx || !mesh_pass || !params || !dst_frame ||
+      !dst_frame->planes[0].texture || (num_vertices > 0 && !vertices)) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+  pl_gpu gpu = ctx->vk->gpu;
+  pl_tex target = dst_frame->planes[0].texture;
+
+  if (!mesh_pass->pass || mesh_pass->target_format != target->params.format ||
+      mesh_pass->num_frames != num_frames ||
+      mesh_pass->num_passes != num_passes ||
+      mesh_pass->num_vars != params->num_vars) {
+    int err = mesh_pass_build(ctx, mesh_pass, params, target->params.format,
+                              num_frames, num_passes);
+    if (err) {
+      return err;
+    }
+  }
+
+  pl_tex_clear(gpu, target, (float[4]){0.0, 0.0, 0.0, 1.0});
+  if (num_vertices < 3) {
+    return 0;
+  }
+
+  if (mesh_pass->vars_size > 0) {
+    size_t offset = 0;
+    for (int i = 0; i < params->num_vars; i++) {
+      const struct pl_var* var = &params->vars[i].var;
+      struct pl_var_layout layout = pl_std140_layout(offset, var);
+      memcpy_layout(mesh_pass->vars_data, layout, params->vars[i].data,
+                    pl_var_host_layout(0, var));
+      offset = layout.offset + layout.size;
+    }
+    pl_buf_write(gpu, mesh_pass->vars_buf, 0, mesh_pass->vars_data,
+                 mesh_pass->vars_size);
+  }
+
+  int num_bindings = num_frames + num_passes + (mesh_pass->vars_buf ? 1 : 0);
+  struct pl_desc_binding* bindings =
+      calloc(num_bindings > 0 ? num_bindings : 1,
+             sizeof(struct pl_desc_binding));
+  if (!bindings) {
+    return ENOMEM;
+  }
+  for (int i = 0; i < num_frames; i++) {
+    bindings[i] = (struct pl_desc_binding){
+        .object = src_frames[i]->planes[0].texture,
+        .address_mode = PL_TEX_ADDRESS_REPEAT,
+        .sample_mode = PL_TEX_SAMPLE_LINEAR,
+    };
+  }
+  for (int i = 0; i < num_passes; i++) {
+    bindings[num_frames + i] = (struct pl_desc_binding){
+        .object = passes[i]->planes[0].texture,
+        .address_mode = PL_TEX_ADDRESS_REPEAT,
+        .sample_mode = PL_TEX_SAMPLE_LINEAR,
+    };
+  }
+  if (mesh_pass->vars_buf) {
+    bindings[num_bindings - 1] =
+        (struct pl_desc_binding){.object = mesh_pass->vars_buf};
+  }
+
+  pl_rect2d whole = {0, 0, target->params.w, target->params.h};
+  pl_pass_run(gpu, &(struct pl_pass_run_params){
+                       .pass = mesh_pass->pass,
+                       .desc_bindings = bindings,
+                       .target = target,
+                       .viewport = whole,
+                       .scissors = whole,
+                       .vertex_type = PL_PRIM_TRIANGLE_LIST,
+                       .vertex_count = num_vertices,
+                       .vertex_data = vertices,
+                   });
+  free(bindings);
+  return 0;
+}
+
+static void frame_set_plane(struct gfx_lowlevel_frame_ctx* frame, pl_fmt fmt) {
+  struct pl_frame* f = &frame->pl_frame;
+  f->num_planes = 1;
+  f->planes[0] = (struct pl_plane){
+      .texture = frame->tex[0],
+      .components = fmt->num_components,
+      .component_mapping = {fmt->sample_order[0], fmt->sample_order[1],
+                            fmt->sample_order[2], fmt->sample_order[3]},
+  };
+  f->repr = pl_color_repr_unknown;
+  f->color = pl_color_space_unknown;
+}
+
+// Uploads tightly packed rgba8 pixels into the first texture of frame,
+// recreating it if the size changed.
+int gfx_lowlevel_frame_upload_rgba(struct gfx_lowlevel_gpu_ctx* ctx,
+                                   struct gfx_lowlevel_frame_ctx* frame,
+                                   const uint8_t* src, int width, int height) {
+  return gfx_lowlevel_frame_upload_pixels(ctx, frame, src, width, height,
+                                          false);
+}
+
+// The same for rgba32f when is_float
+int gfx_lowlevel_frame_upload_pixels(struct gfx_lowlevel_gpu_ctx* ctx,
+                                     struct gfx_lowlevel_frame_ctx* frame,
+                                     const void* src, int width, int height,
+                                     bool is_float) {
+  if (!ctx || !frame || !src || width <= 0 || height <= 0) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+
+  pl_fmt fmt = pl_find_named_fmt(ctx->vk->gpu, is_float ? "rgba32f" : "rgba8");
+  if (!fmt) {
+    fprintf(stderr, "gfx_ll> Failed to find format\n");
+    return EINVAL;
+  }
+
+  struct pl_tex_params tex_params = {
+      .w = width,
+      .h = height,
+      .d = 0,
+      .format = fmt,
+      .sampleable = true,
+      .renderable = true,
+      .blit_src = true,
+      .blit_dst = true,
+      .host_writable = true,
+  };
+  if (!pl_tex_recreate(ctx->vk->gpu, &frame->tex[0], &tex_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create upload texture\n");
+    return EINVAL;
+  }
+
+  size_t pixel_size = is_float ? 4 * sizeof(float) : 4;
+  if (!pl_tex_upload(ctx->vk->gpu, &(struct pl_tex_transfer_params){
+                                       .tex = frame->tex[0],
+                                       .row_pitch = (size_t)width * pixel_size,
+                                       .ptr = (void*)src,
+                                   })) {
+    fprintf(stderr, "gfx_ll> Failed to upload texture\n");
+    return EIO;
+  }
+
+  frame_set_plane(frame, fmt);
+  return 0;
+}
+
+// The vulkan backend signs each format with its VkFormat
+static pl_fmt find_vk_fmt(struct gfx_lowlevel_gpu_ctx* ctx, uint32_t vk_format) {
+  pl_gpu gpu = ctx->vk->gpu;
+  for (int i = 0; i < gpu->num_formats; i++) {
+    pl_fmt fmt = gpu->formats[i];
+    if (fmt->signature == vk_format && (fmt->caps & PL_FMT_CAP_SAMPLEABLE)) {
+      return fmt;
+    }
+  }
+  return NULL;
+}
+
+bool gfx_lowlevel_compressed_supported(struct gfx_lowlevel_gpu_ctx* ctx,
+                                       uint32_t vk_format) {
+  return ctx && find_vk_fmt(ctx, vk_format);
+}
+
+int gfx_lowlevel_frame_upload_compressed(struct gfx_lowlevel_gpu_ctx* ctx,
+                                         struct gfx_lowlevel_frame_ctx* frame,
+                                         uint32_t vk_format, const uint8_t* src,
+                                         size_t row_pitch, int width,
+                                         int height) {
+  if (!ctx || !frame || !src || width <= 0 || height <= 0) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+  pl_fmt fmt = find_vk_fmt(ctx, vk_format);
+  if (!fmt) {
+    fprintf(stderr, "gfx_ll> VkFormat %u can't be sampled\n", vk_format);
+    return ENOTSUP;
+  }
+  struct pl_tex_params tex_params = {
+      .w = width,
+      .h = height,
+      .d = 0,
+      .format = fmt,
+      .sampleable = true,
+      .host_writable = true,
+  };
+  if (!pl_tex_recreate(ctx->vk->gpu, &frame->tex[0], &tex_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create compressed texture\n");
+    return EINVAL;
+  }
+  if (!pl_tex_upload(ctx->vk->gpu, &(struct pl_tex_transfer_params){
+                                       .tex = frame->tex[0],
+                                       .row_pitch = row_pitch,
+                                       .ptr = (void*)src,
+                                   })) {
+    fprintf(stderr, "gfx_ll> Failed to upload compressed texture\n");
+    return EIO;
+  }
+  frame_set_plane(frame, fmt);
+  return 0;
+}
+
+// Makes dst a half size copy of src_frame's first plane, one mip level down
+int gfx_lowlevel_frame_halve(struct gfx_lowlevel_gpu_ctx* ctx,
+                             struct gfx_lowlevel_frame_ctx* dst,
+                             struct pl_frame* src_frame) {
+  if (!ctx || !dst || !src_frame || src_frame->num_planes < 1) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+  pl_tex src = src_frame->planes[0].texture;
+  struct pl_tex_params tex_params = {
+      .w = src->params.w > 1 ? src->params.w / 2 : 1,
+      .h = src->params.h > 1 ? src->params.h / 2 : 1,
+      .d = 0,
+      .format = src->params.format,
+      .sampleable = true,
+      .renderable = true,
+      .blit_src = true,
+      .blit_dst = true,
+  };
+  if (!pl_tex_recreate(ctx->vk->gpu, &dst->tex[0], &tex_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create mip texture\n");
+    return EINVAL;
+  }
+  // linear halving averages each 2x2 block, nearest is all some formats have
+  bool linear = src->params.format->caps & PL_FMT_CAP_LINEAR;
+  pl_tex_blit(ctx->vk->gpu,
+              &(struct pl_tex_blit_params){
+                  .src = src,
+                  .dst = dst->tex[0],
+                  .sample_mode =
+                      linear ? PL_TEX_SAMPLE_LINEAR : PL_TEX_SAMPLE_NEAREST,
+              });
+  frame_set_plane(dst, src->params.format);
+  return 0;
+}
+
+// Blits the first plane of src_frame into *tex, an rgba8 texture made host
+// readable, which is (re)created to fit
+static int blit_readback(struct gfx_lowlevel_gpu_ctx* ctx,
+                         struct pl_frame* src_frame, pl_tex* tex) {
+  pl_tex src = src_frame->planes[0].texture;
+  if (!src) {
+    return EINVAL;
+  }
+  if (!src->params.blit_src) {
+    fprintf(stderr, "gfx_ll> Frame texture is not blittable, cannot read back\n");
+    return ENOTSUP;
+  }
+
+  pl_fmt fmt = pl_find_named_fmt(ctx->vk->gpu, "rgba8");
+  if (!fmt) {
+    fprintf(stderr, "gfx_ll> Failed to find format\n");
+    return EINVAL;
+  }
+
+  struct pl_tex_params tex_params = {
+      .w = src->params.w,
+      .h = src->params.h,
+      .d = 0,
+      .format = fmt,
+      .blit_dst = true,
+      .host_readable = true,
+  };
+  if (!pl_tex_recreate(ctx->vk->gpu, tex, &tex_params)) {
+    fprintf(stderr, "gfx_ll> Failed to create readback texture\n");
+    return EINVAL;
+  }
+
+  pl_tex_blit(ctx->vk->gpu, &(struct pl_tex_blit_params){
+                                .src = src,
+                                .dst = *tex,
+                            });
+  return 0;
+}
+
+// Blits the first plane of src_frame into a host readable rgba8 staging
+// texture and downloads it into dst (tightly packed, width * height * 4).
+int gfx_lowlevel_frame_download_rgba(struct gfx_lowlevel_gpu_ctx* ctx,
+                                     struct pl_frame* src_frame, uint8_t* dst,
+                                     size_t dst_size) {
+  if (!ctx || !src_frame || !dst || src_frame->num_planes < 1) {
+    fprintf(stderr, "gfx_ll> Invalid context or frame\n");
+    return EINVAL;
+  }
+
+  pl_tex src = src_frame->planes[0].texture;
+  if (!src) {
+    return EINVAL;
+  }
+  int w = src->params.w;
+  int h = src->params.h;
+  if (dst_size < (size_t)w * (size_t)h * 4) {
+    return ENOBUFS;
+  }
+
+  int err = blit_readback(ctx, src_frame, &ctx->readback_tex);
+  if (err) {
+    return err;
+  }
+
+  if (!pl_tex_download(ctx->vk->gpu, &(struct pl_tex_transfer_params){
+                                         .tex = ctx->readback_tex,
+                                         .row_pitch = (size_t)w * 4,
+                                         .ptr = dst,
+                                     })) {
+    fprintf(stderr, "gfx_ll> Failed to download texture\n");
+    return EIO;
+  }
+
+  return 0;
+}
+
+struct gfx_lowlevel_download* gfx_lowlevel_download_init(
+    struct gfx_lowlevel_gpu_ctx* ctx) {
+  if (!ctx) {
+    fprintf(stderr, "gfx_ll> Invalid context\n");
+    return NULL;
+  }
+  struct gfx_lowlevel_download* download =
+      calloc(1, sizeof(struct gfx_lowlevel_download));
+  if (!download) {
+    fprintf(stderr, "gfx_ll> Failed to allocate memory for download\n");
+    return NULL;
+  }
+  download->ctx = ctx;
+  return download;
+}
+
+void gfx_lowlevel_download_destroy(struct gfx_lowlevel_download** download) {
+  if (download && *download) {
+    pl_gpu gpu = (*download)->ctx->vk->gpu;
+    // destroying waits for the gpu to be done with them
+    pl_buf_destroy(gpu, &(*download)->buf);
+    pl_tex_destroy(gpu, &(*download)->tex);
+    free(*download);
+    *download = NULL;
+  }
+}
+
+// Copies the first plane of src_frame aside and queues its download into a
+// host buffer without waiting for it, GFX_EAGAIN while the last one is still
+// in flight.
+int gfx_lowlevel_download_start(struct gfx_lowlevel_download* download,
+                                struct pl_frame* src_frame) {
+  if (!download || !src_frame || src_frame->num_planes < 1) {
+    fprintf(stderr, "gfx_ll> Invalid download or frame\n");
+    return EINVAL;
+  }
+  if (download->pending) {
+    return GFX_EAGAIN;
+  }
+  struct gfx_lowlevel_gpu_ctx* ctx = download->ctx;
+
+  int err = blit_readback(ctx, src_frame, &download->tex);
+  if (err) {
+    return err;
+  }
+  int w = download->tex->params.w;
+  int h = download->tex->params.h;
+  size_t size = (size_t)w * (size_t)h * 4;
+  if (!download->buf || download->buf->params.size != size) {
+    pl_buf_destroy(ctx->vk->gpu, &download->buf);
+    download->buf = pl_buf_create(ctx->vk->gpu, &(struct pl_buf_params){
+                                                    .size = size,
+                                                    .host_readable = true,
+                                                });
+    if (!download->buf) {
+      fprintf(stderr, "gfx_ll> Failed to create download buffer\n");
+      return ENOMEM;
+    }
+  }
+
+  if (!pl_tex_download(ctx->vk->gpu, &(struct pl_tex_transfer_params){
+                                         .tex = download->tex,
+                                         .row_pitch = (size_t)w * 4,
+                                         .buf = download->buf,
+                                     })) {
+    fprintf(stderr, "gfx_ll> Failed to download texture\n");
+    return EIO;
+  }
+  download->w = w;
+  download->h = h;
+  download->pending = true;
+  return 0;
+}
+
+// Copies a finished download into dst (tightly packed, w * h * 4),
+// GFX_EAGAIN while it's still in flight and ENOENT if none was started.
+int gfx_lowlevel_download_poll(struct gfx_lowlevel_download* download,
+                               uint8_t* dst, size_t dst_size, int* w, int* h) {
+  if (!download || !dst || !w || !h) {
+    fprintf(stderr, "gfx_ll> Invalid download\n");
+    return EINVAL;
+  }
+  if (!download->pending) {
+    return ENOENT;
+  }
+  pl_gpu gpu = download->ctx->vk->gpu;
+  if (pl_buf_poll(gpu, download->buf, 0)) {
+    return GFX_EAGAIN;
+  }
+  size_t size = (size_t)download->w * (size_t)download->h * 4;
+  if (dst_size < size) {
+    return ENOBUFS;
+  }
+  download->pending = false;
+  if (!pl_buf_read(gpu, download->buf, 0, dst, size)) {
+    fprintf(stderr, "gfx_ll> Failed to read download buffer\n");
+    return EIO;
+  }
+  *w = download->w;
+  *h = download->h;
+  return 0;
+}
@@ -22,6 +22,18 @@ struct gfx_lowlevel_frame_ctx {
   struct SwsContext* to_rgba;
 };
 
+enum gfx_lowlevel_colorspace {
+  GFX_LOWLEVEL_CSP_SRGB = 0,
+  GFX_LOWLEVEL_CSP_DISPLAY_P3,
+  GFX_LOWLEVEL_CSP_HDR_PQ,
+};
+
+enum gfx_lowlevel_present_mode {
+  GFX_LOWLEVEL_PRESENT_FIFO = 0,
+  GFX_LOWLEVEL_PRESENT_MAILBOX,
+  GFX_LOWLEVEL_PRESENT_IMMEDIATE,
+};
+
 struct gfx_lowlevel_gpu_ctx {
   SDL_Window* shared_window;
   pl_vulkan vk;
@@ -33,6 +45,13 @@ struct gfx_lowlevel_gpu_ctx {
   pl_log log;
   pl_dispatch dispatch;  // Shared dispatch for shader caching
   bool started;
+  // Requested output colorspace, the swapchain may negotiate something else
+  enum gfx_lowlevel_colorspace output_csp;
+  // The mode the swapchain was made with, fifo unless the surface has the
+  // one asked for
+  enum gfx_lowlevel_present_mode present_mode;
+  // Host readable staging texture for frame downloads, created on demand
+  pl_tex readback_tex;
   
   // Resource pool for render operations to avoid per-frame allocations
   struct {
@@ -54,6 +73,7 @@ struct gfx_lowlevel_filter_params {
   const char* body;
   struct pl_shader_var* vars;
   int num_vars;
+  bool blend;  // alpha blend over the destination instead of replacing it
 };
 
 struct gfx_lowlevel_mix_ctx {
@@ -71,10 +91,50 @@ struct gfx_lowlevel_lut {
   pl_shader_obj lut_state;
 };
 
+// A mixer pass that rasterizes triangles through a user vertex() instead of
+// covering the target, built on first render and again when what it's bound
+// to changes
+struct gfx_lowlevel_mesh_pass {
+  struct gfx_lowlevel_gpu_ctx* ctx;
+  char* vertex;
+  pl_pass pass;
+  pl_buf vars_buf;
+  uint8_t* vars_data;
+  size_t vars_size;
+  pl_fmt target_format;
+  int num_frames;
+  int num_passes;
+  int num_vars;
+};
+
+// A frame copied aside and downloaded in the background, so reading it back
+// every frame doesn't stall rendering
+struct gfx_lowlevel_download {
+  struct gfx_lowlevel_gpu_ctx* ctx;
+  pl_tex tex;
+  pl_buf buf;
+  int w;
+  int h;
+  bool pending;
+};
+
 #define GFX_EAGAIN 35
+// device_name picks the Vulkan device, NULL lets libplacebo choose
 struct gfx_lowlevel_gpu_ctx* gfx_lowlevel_gpu_ctx_init(
-    struct SDL_Window* window);
+    struct SDL_Window* window, const char* device_name);
+// Copies the name of device index into name, returns the number of devices
+// or -1 if they can't be listed
+int gfx_lowlevel_gpu_name(int index, char* name, size_t len);
+// Opens url with the format's demuxer and closes it again, copying what the
+// demuxer logged on this thread into log. options are key=value pairs split
+// by ':'. Returns avformat_open_input's result, listing options make it fail.
+int gfx_lowlevel_probe_input(const char* format, const char* url,
+                             const char* options, char* log, size_t log_size);
 void gfx_lowlevel_gpu_ctx_destroy(struct gfx_lowlevel_gpu_ctx** ctx);
+int gfx_lowlevel_gpu_ctx_set_output_colorspace(
+    struct gfx_lowlevel_gpu_ctx* ctx, enum gfx_lowlevel_colorspace csp);
+int gfx_lowlevel_gpu_ctx_set_present_mode(struct gfx_lowlevel_gpu_ctx* ctx,
+                                          enum gfx_lowlevel_present_mode mode);
 int gfx_lowlevel_gpu_ctx_handle_resize(struct gfx_lowlevel_gpu_ctx* ctx,
                                        int width, int height);
 bool gfx_lowlevel_gpu_ctx_start_frame(struct gfx_lowlevel_gpu_ctx* ctx);
@@ -118,4 +178,56 @@ struct gfx_lowlevel_lut* gfx_lowlevel_init_lut(struct gfx_lowlevel_gpu_ctx* ctx,
                                                const char* lut_filename);
 int gfx_lowlevel_destroy_lut(struct gfx_lowlevel_lut** lut);
 int gfx_lowlevel_reset_dispatch(struct gfx_lowlevel_gpu_ctx* ctx);
+// vertices are x, y (dst pixels), r, g, b, a (0-1) triangle lists
+int gfx_lowlevel_draw_triangles(struct gfx_lowlevel_gpu_ctx* ctx,
+                                struct pl_frame* dst_frame,
+                                const float* vertices, int num_vertices);
+// vertices are x, y (dst pixels), u, v (0-1 in atlas), r, g, b, a (0-1)
+// triangle lists, each texel of atlas tinted by its vertices' color. With sdf
+// the atlas' alpha is a distance field, 0.5 on the edge.
+int gfx_lowlevel_draw_glyphs(struct gfx_lowlevel_gpu_ctx* ctx,
+                             struct pl_frame* dst_frame,
+                             struct pl_frame* atlas, const float* vertices,
+                             int num_vertices, bool sdf);
+struct gfx_lowlevel_mesh_pass* gfx_lowlevel_mesh_pass_init(
+    struct gfx_lowlevel_gpu_ctx* ctx, const char* vertex);
+void gfx_lowlevel_mesh_pass_destroy(struct gfx_lowlevel_mesh_pass** mesh_pass);
+// vertices are x, y, z, u, v triangle lists, shaded by params' header and
+// body like gfx_lowlevel_gpu_ctx_render with src_uv being the vertex uv
+int gfx_lowlevel_mesh_pass_render(
+    struct gfx_lowlevel_gpu_ctx* ctx, struct gfx_lowlevel_mesh_pass* mesh_pass,
+    struct gfx_lowlevel_filter_params const* params, struct pl_frame* dst_frame,
+    struct pl_frame** src_frames, int num_frames, struct pl_frame** passes,
+    int num_passes, const float* vertices, int num_vertices);
+int gfx_lowlevel_frame_upload_rgba(struct gfx_lowlevel_gpu_ctx* ctx,
+                                   struct gfx_lowlevel_frame_ctx* frame,
+                                   const uint8_t* src, int width, int height);
+// src is rgba8, or rgba32f when is_float
+int gfx_lowlevel_frame_upload_pixels(struct gfx_lowlevel_gpu_ctx* ctx,
+                                     struct gfx_lowlevel_frame_ctx* frame,
+                                     const void* src, int width, int height,
+                                     bool is_float);
+// Whether the gpu samples vk_format, block compressed ones included
+bool gfx_lowlevel_compressed_supported(struct gfx_lowlevel_gpu_ctx* ctx,
+                                       uint32_t vk_format);
+// One level of a block compressed texture, row_pitch bytes per row of blocks
+int gfx_lowlevel_frame_upload_compressed(struct gfx_lowlevel_gpu_ctx* ctx,
+                                         struct gfx_lowlevel_frame_ctx* frame,
+                                         uint32_t vk_format, const uint8_t* src,
+                                         size_t row_pitch, int width,
+                                         int height);
+// Makes dst a half size copy of src, for mip chains
+int gfx_lowlevel_frame_halve(struct gfx_lowlevel_gpu_ctx* ctx,
+                             struct gfx_lowlevel_frame_ctx* dst,
+                             struct pl_frame* src_frame);
+int gfx_lowlevel_frame_download_rgba(struct gfx_lowlevel_gpu_ctx* ctx,
+                                     struct pl_frame* src_frame, uint8_t* dst,
+                                     size_t dst_size);
+struct gfx_lowlevel_download* gfx_lowlevel_download_init(
+    struct gfx_lowlevel_gpu_ctx* ctx);
+void gfx_lowlevel_download_destroy(struct gfx_lowlevel_download** download);
+int gfx_lowlevel_download_start(struct gfx_lowlevel_download* download,
+                                struct pl_frame* src_frame);
+int gfx_lowlevel_download_poll(struct gfx_lowlevel_download* download,
+                               uint8_t* dst, size_t dst_size, int* w, int* h);
 #endif  // GFXLOWLEVEL_H
\ No newline at end of file
@@ -1,26 +1,75 @@
+use crate::assetcache;
+use crate::audioruntime::AudioData;
+#[cfg(feature = "fonts")]
+use crate::fonts::{
+    check_font_file, layout_spans, load_font, load_sdf_font, style_font, system_fallbacks,
+    TextLayoutOptions,
+};
+#[cfg(feature = "fonts")]
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_draw_glyphs;
 use crate::gfx_lowlevel::bindings::{
-    gfx_lowlevel_destroy_lut, gfx_lowlevel_gpu_ctx, gfx_lowlevel_init_lut, gfx_lowlevel_lut,
+    gfx_lowlevel_destroy_lut, gfx_lowlevel_draw_triangles, gfx_lowlevel_frame_clear,
+    gfx_lowlevel_gpu_ctx, gfx_lowlevel_init_lut, gfx_lowlevel_lut,
+};
+use crate::gfxinfo::{
+    AssetEvent, AssetReloadedEvent, AudioAnalysisEvent, FrameEvent, ReadbackData, ReadbackEvent,
+    ReadbackKind, ReadbackRequest, SpecErrorKind, AUDIO_BANDS,
+};
+use crate::glob::glob;
+#[cfg(feature = "fonts")]
+use crate::glyphatlas::{GlyphAtlas, GlyphPlacement, GlyphTransform, GLYPH_VERTEX_LEN};
+use crate::manifest;
+use crate::meshruntime::MeshData;
+use crate::plugins;
+use crate::renderspec::{
+    Clear, DrawShape, DrawText, GlyphDriver, Mix, MixInput, RenderSpec, Reset, RunMacro, SeekBy,
+    SeekVid, SendCmd, SendValue, Transition, TransitionKind,
+};
+#[cfg(feature = "fonts")]
+use crate::renderspec::{GlyphWave, TextAlign};
+use crate::shapes::tessellate;
+use crate::texruntime::{self, GpuTex, TexData};
+use crate::vidruntime::{
+    blend_frames, download_frame_rgba, letterbox, FrameDownload, Uniform, VidMixerData,
+    VidMixerInput, VidMixerStream, WrapFrame,
 };
-use crate::gfxinfo::FrameEvent;
-use crate::renderspec::{Mix, MixInput, RenderSpec, Reset, SeekVid, SendCmd};
-use crate::vidruntime::VidMixerData;
 use anyhow::{anyhow, bail, Result};
 use ffmpeg_next::Rational;
-use sdl2::render::Texture;
+#[cfg(feature = "fonts")]
+use sdl2::ttf::Font;
 use std::ffi::CString;
-use std::{cell::RefCell, collections::HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    rc::Rc,
+};
+use tracing::{error, warn};
 
 extern crate ffmpeg_next as ffmpeg;
 
 use crate::{
-    gfxinfo::{Asset, GfxInfo},
-    vidruntime::{VidData, VidInput},
+    gfxinfo::{
+        Asset, FontAsset, GfxInfo, LoadPolicy, Lut, LutInfo, PluginAsset, ShaderStatus, TexRaster,
+    },
+    vidruntime::{ShaderData, VidData, VidInput},
 };
 
 #[derive(Debug)]
 pub enum GfxData {
     VidData(VidData),
     VidMixerData(VidMixerData),
+    // the plugin holds whatever it loaded
+    PluginData(PluginAsset),
+    AudioData(AudioData),
+    // the fonts themselves are opened on the render thread, in font_cache
+    FontData(FontAsset),
+    ShaderData(ShaderData),
+    LutData(LutData),
+    TexData(TexData),
+    MeshData(MeshData),
 }
 
 impl From<VidData> for GfxData {
@@ -34,6 +83,13 @@ impl GfxData {
         match self {
             GfxData::VidData(vd) => vd.info.name.clone(),
             GfxData::VidMixerData(vmd) => vmd.info.name.clone(),
+            GfxData::PluginData(p) => p.name.clone(),
+            GfxData::AudioData(a) => a.info.name.clone(),
+            GfxData::FontData(f) => f.name.clone(),
+            GfxData::ShaderData(s) => s.info.name.clone(),
+            GfxData::LutData(l) => l.info.name.clone(),
+            GfxData::TexData(t) => t.info.name.clone(),
+            GfxData::MeshData(m) => m.info.name.clone(),
         }
     }
 
@@ -41,6 +97,13 @@ impl GfxData {
         match self {
             GfxData::VidData(vd) => vd.info.clone().into(),
             GfxData::VidMixerData(vmd) => vmd.info().into(),
+            GfxData::PluginData(p) => GfxInfo::PluginInfo(p.clone()),
+            GfxData::AudioData(a) => a.info.clone().into(),
+            GfxData::FontData(f) => GfxInfo::FontInfo(f.clone()),
+            GfxData::ShaderData(s) => s.info.clone().into(),
+            GfxData::LutData(l) => l.info.clone().into(),
+            GfxData::TexData(t) => t.info.clone().into(),
+            GfxData::MeshData(m) => m.info.clone().into(),
         }
     }
 }
@@ -56,24 +119,299 @@ impl Drop for WrapLut {
     }
 }
 
+// A Lut asset, parsed when it loads so a bad file fails then, and handed to
+// libplacebo by the first Mix using it
+#[derive(Debug)]
+pub struct LutData {
+    pub info: LutInfo,
+    lut: RefCell<Option<WrapLut>>,
+}
+
+impl LutData {
+    fn load(lut: &Lut) -> Result<Self> {
+        let text = std::fs::read_to_string(&lut.path)
+            .map_err(|e| anyhow!("Could not read LUT {}: {}", lut.path, e))?;
+        let (title, dimensions, size) =
+            parse_cube(&text).map_err(|e| anyhow!("Bad LUT {}: {}", lut.path, e))?;
+        Ok(Self {
+            info: LutInfo {
+                name: lut.name.clone(),
+                path: lut.path.clone(),
+                title,
+                dimensions,
+                size,
+            },
+            lut: RefCell::new(None),
+        })
+    }
+
+    fn prepare(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<*mut gfx_lowlevel_lut> {
+        let mut lut = self.lut.borrow_mut();
+        if let Some(lut) = lut.as_ref() {
+            return Ok(lut.0);
+        }
+        let path = CString::new(self.info.path.as_str())?;
+        let lut_ptr = unsafe { gfx_lowlevel_init_lut(lowlevel_ctx, path.as_ptr()) };
+        if lut_ptr.is_null() {
+            bail!("Could not create LUT {}", self.info.name);
+        }
+        lut.replace(WrapLut(lut_ptr));
+        Ok(lut_ptr)
+    }
+}
+
+// The title, dimensions and size of a .cube, checking there's a row for each entry
+fn parse_cube(text: &str) -> Result<(Option<String>, u8, u32)> {
+    let mut title = None;
+    let mut shape = None;
+    let mut rows = 0u64;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match keyword {
+            "TITLE" => title = Some(rest.trim().trim_matches('"').to_string()),
+            "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                let size = rest.trim().parse::<u32>()?;
+                let (dimensions, max) = match keyword {
+                    "LUT_1D_SIZE" => (1, 65536),
+                    _ => (3, 256),
+                };
+                if !(2..=max).contains(&size) {
+                    bail!("{} {} is out of range", keyword, size);
+                }
+                shape = Some((dimensions, size));
+            }
+            "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => (),
+            _ => {
+                let values = line
+                    .split_whitespace()
+                    .map(str::parse::<f32>)
+                    .collect::<Vec<_>>();
+                if values.len() != 3 || values.iter().any(|v| v.is_err()) {
+                    bail!("Expected three numbers, got {}", line);
+                }
+                rows += 1;
+            }
+        }
+    }
+    let Some((dimensions, size)) = shape else {
+        bail!("No LUT_1D_SIZE or LUT_3D_SIZE");
+    };
+    let expected = (size as u64).pow(dimensions as u32);
+    if rows != expected {
+        bail!("Expected {} rows, found {}", expected, rows);
+    }
+    Ok((title, dimensions, size))
+}
+
+// Whether the file at changed is pattern or one of its glob's matches
+fn opened_from(pattern: &str, changed: &Path) -> bool {
+    glob(pattern)
+        .unwrap_or_else(|| vec![pattern.to_string()])
+        .iter()
+        .any(|p| Path::new(p).canonicalize().ok().as_deref() == Some(changed))
+}
+
+// Whether name is one of host_uniforms, whether or not the host has it now
+fn is_host_uniform(name: &str) -> bool {
+    let band = name
+        .strip_prefix(AUDIO_BAND_UNIFORM)
+        .and_then(|band| band.parse::<usize>().ok());
+    [
+        BEAT_UNIFORM,
+        BPM_UNIFORM,
+        BEAT_PHASE_UNIFORM,
+        AUDIO_RMS_UNIFORM,
+        AUDIO_ONSET_UNIFORM,
+    ]
+    .contains(&name)
+        || band.is_some_and(|band| band < AUDIO_BANDS)
+}
+
+// A DrawText font as it's opened, bold and outlined ones are fonts of their own
+#[cfg(feature = "fonts")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FontKey {
+    path: String,
+    size: u16,
+    // renders distance fields
+    sdf: bool,
+    bold: bool,
+    // pixels wide, it renders only the outline
+    outline: u16,
+}
+
 pub struct GfxRuntime {
     gfx_info: RefCell<HashMap<String, GfxInfo>>,
     gfx_data: RefCell<HashMap<String, GfxData>>,
-    texture: RefCell<HashMap<String, HashMap<usize, Texture>>>,
+    // uploaded Tex frames by asset name and frame index
+    tex_cache: RefCell<HashMap<(String, usize), Arc<GpuTex>>>,
+    // Texes a Mix has drawn that still have frames to upload
+    tex_warming: RefCell<HashSet<String>>,
     stream: RefCell<HashMap<String, VidInput>>,
     pub frames_per_sec: i64,
     pub last_frame_rendered: RefCell<i64>,
     pub lut_cache: RefCell<HashMap<String, WrapLut>>,
+    #[cfg(feature = "fonts")]
+    font_cache: RefCell<HashMap<FontKey, Font<'static, 'static>>>,
+    // system fallbacks and outlines that didn't open, so they aren't tried
+    // every frame
+    #[cfg(feature = "fonts")]
+    unopenable_fonts: RefCell<HashSet<FontKey>>,
+    // DrawText glyphs, by font_cache's key
+    #[cfg(feature = "fonts")]
+    glyph_atlases: RefCell<HashMap<FontKey, GlyphAtlas>>,
+    // running transitions and the frame they started on
+    transitions: RefCell<Vec<(Transition, i64)>>,
+    pending_loads: RefCell<Vec<(String, JoinHandle<Result<GfxData>>)>>,
+    // pending loads that replace the asset even if nothing about it changed
+    reloads: RefCell<HashSet<String>>,
+    asset_events: RefCell<Vec<AssetEvent>>,
+    asset_reloads: RefCell<Vec<AssetReloadedEvent>>,
+    // on_first_use assets no spec has referred to yet
+    deferred: RefCell<HashMap<String, Asset>>,
+    macros: RefCell<HashMap<String, Vec<RenderSpec>>>,
+    // by name and params, so a macro run every frame is substituted once
+    expansions: RefCell<HashMap<RunMacro, Rc<Vec<RenderSpec>>>>,
+    viewport: RefCell<Option<(u32, u32)>>,
+    bpm: RefCell<f64>,
+    // from an external clock, see set_beat
+    beat: RefCell<Option<f64>>,
+    // from --audio-in, see set_audio
+    audio: RefCell<Option<AudioAnalysisEvent>>,
+    scene: RefCell<Vec<Mix>>,
+    // while a batch applies, how to undo it
+    journal: RefCell<Option<Journal>>,
+    // readbacks waiting to start, see request_readback
+    readback_queue: RefCell<VecDeque<ReadbackRequest>>,
+    // and the ones downloading, with the frame they read
+    readbacks: RefCell<Vec<(ReadbackRequest, i64, FrameDownload)>>,
+    spare_downloads: RefCell<Vec<FrameDownload>>,
+}
+
+// What a batch has changed so far, put back if one of its members fails.
+// Pixels it already drew stay until those targets are drawn again, and
+// frames its mixes decoded stay decoded.
+struct Journal {
+    undo: Vec<Undo>,
+    scene: Vec<Mix>,
+    viewport: Option<(u32, u32)>,
+    transitions: Vec<(Transition, i64)>,
+    macros: HashMap<String, Vec<RenderSpec>>,
+    asset_events: usize,
+}
+
+enum Undo {
+    // a mixer that wasn't prepared before a SendCmd prepared it
+    Unprepare(String),
+    Uniform(String, Uniform),
+    // a video that wasn't open before a seek opened it
+    Close(String),
+    Seek(String, f64),
+    Reopen(String, Option<VidInput>),
+    Restream(String, VidMixerStream),
+    Load(String),
+    // plugins are only told to unload once the batch has applied
+    Unload(Box<(GfxInfo, GfxData)>),
+}
+
+// Where every vid and mix is, for the guest to query while calculating
+#[derive(Debug, Default, Clone)]
+pub struct PlaybackPositions {
+    // name -> (pts, duration) in seconds
+    pub vids: HashMap<String, (f64, f64)>,
+    // name -> present time in seconds
+    pub mixes: HashMap<String, f64>,
 }
 
 pub fn load(asset: &Asset) -> Result<GfxData> {
+    // the manifest knows better than a failed open
+    load_local(&assetcache::localize(asset)?).map_err(|e| {
+        match asset.path().and_then(manifest::explain) {
+            Some(why) => e.context(why),
+            None => e,
+        }
+    })
+}
+
+fn load_local(asset: &Asset) -> Result<GfxData> {
     match asset {
         Asset::Missing => Err(anyhow!("asset is missing")),
         Asset::Vid(v) => VidData::load(v).map(|vd| vd.into()),
         Asset::VidMixer(m) => Ok(GfxData::VidMixerData(VidMixerData::new(m.clone().into()))),
+        Asset::Plugin(p) => plugins::load_asset(p).map(|_| GfxData::PluginData(p.clone())),
+        Asset::Audio(a) => AudioData::load(a).map(GfxData::AudioData),
+        #[cfg(feature = "fonts")]
+        Asset::Font(f) => check_font_file(&f.path)
+            .map(|_| GfxData::FontData(f.clone()))
+            .map_err(|e| anyhow!(e)),
+        #[cfg(not(feature = "fonts"))]
+        Asset::Font(f) => Err(anyhow!(
+            "Font {} needs sdlrig built with the fonts feature",
+            f.name
+        )),
+        Asset::Shader(s) => ShaderData::load(s).map(GfxData::ShaderData),
+        Asset::Lut(l) => LutData::load(l).map(GfxData::LutData),
+        Asset::Tex(t) => TexData::load(t).map(GfxData::TexData),
+        Asset::Mesh(m) => MeshData::load(m).map(GfxData::MeshData),
+    }
+}
+
+// Gives mixers the source of their Shader asset, whichever of them was added
+fn link_shaders(gfx_data: &HashMap<String, GfxData>, added: &str) {
+    let link = |mixer: &VidMixerData| {
+        let shader = mixer
+            .info
+            .shader_asset
+            .as_ref()
+            .and_then(|n| gfx_data.get(n));
+        if let Some(GfxData::ShaderData(shader)) = shader {
+            mixer.link_shader(&shader.source);
+        }
+    };
+    match gfx_data.get(added) {
+        Some(GfxData::ShaderData(_)) => {
+            for data in gfx_data.values() {
+                match data {
+                    GfxData::VidMixerData(mixer)
+                        if mixer.info.shader_asset.as_deref() == Some(added) =>
+                    {
+                        link(mixer)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Some(GfxData::VidMixerData(mixer)) => link(mixer),
+        _ => (),
     }
 }
 
+// Fastest a SetFps can run the host
+pub const MAX_FPS: i64 = 240;
+const BEAT_UNIFORM: &str = "beat";
+const BPM_UNIFORM: &str = "bpm";
+const BEAT_PHASE_UNIFORM: &str = "beat_phase";
+const AUDIO_RMS_UNIFORM: &str = "audio_rms";
+const AUDIO_ONSET_UNIFORM: &str = "audio_onset";
+// followed by the band number
+const AUDIO_BAND_UNIFORM: &str = "audio_band";
+
+// Tex frames uploaded ahead of being drawn each render frame
+const TEX_UPLOADS_PER_FRAME: usize = 2;
+// Readback downloads in flight at once, more wait for a later frame
+const READBACKS_IN_FLIGHT: usize = 2;
+// and how many can wait before the oldest are dropped
+const MAX_QUEUED_READBACKS: usize = 32;
+// RunMacro expansions kept, params that change every frame would otherwise
+// grow the cache without end
+const MAX_EXPANSIONS: usize = 256;
+// What sdf DrawText glyphs are rasterized at, big enough that the distance
+// field keeps corners when scaled up
+#[cfg(feature = "fonts")]
+const SDF_SIZE: u16 = 64;
 const FFMPEG_INIT_ONCE: std::sync::Once = std::sync::Once::new();
 
 impl GfxRuntime {
@@ -85,11 +423,35 @@ impl GfxRuntime {
         Self {
             gfx_info: RefCell::new(HashMap::new()),
             gfx_data: RefCell::new(HashMap::new()),
-            texture: RefCell::new(HashMap::new()),
+            tex_cache: RefCell::new(HashMap::new()),
+            tex_warming: RefCell::new(HashSet::new()),
             stream: RefCell::new(HashMap::new()),
             frames_per_sec,
             last_frame_rendered: RefCell::new(frame),
             lut_cache: RefCell::new(HashMap::new()),
+            #[cfg(feature = "fonts")]
+            font_cache: RefCell::new(HashMap::new()),
+            #[cfg(feature = "fonts")]
+            unopenable_fonts: RefCell::new(HashSet::new()),
+            #[cfg(feature = "fonts")]
+            glyph_atlases: RefCell::new(HashMap::new()),
+            transitions: RefCell::new(vec![]),
+            pending_loads: RefCell::new(vec![]),
+            reloads: RefCell::new(HashSet::new()),
+            asset_events: RefCell::new(vec![]),
+            asset_reloads: RefCell::new(vec![]),
+            deferred: RefCell::new(HashMap::new()),
+            macros: RefCell::new(HashMap::new()),
+            expansions: RefCell::new(HashMap::new()),
+            viewport: RefCell::new(None),
+            bpm: RefCell::new(120.0),
+            beat: RefCell::new(None),
+            audio: RefCell::new(None),
+            scene: RefCell::new(vec![]),
+            journal: RefCell::new(None),
+            readback_queue: RefCell::new(VecDeque::new()),
+            readbacks: RefCell::new(vec![]),
+            spare_downloads: RefCell::new(vec![]),
         }
     }
 
@@ -104,45 +466,43 @@ impl GfxRuntime {
         }
 
         if let Err(e) = self.remove(add_info.name()) {
-            eprintln!("Error removing {}: {}", add_info.name(), e);
+            error!("Error removing {}: {}", add_info.name(), e);
         }
 
         let mut info = self.gfx_info.borrow_mut();
         let mut data = self.gfx_data.borrow_mut();
-        let mut texture = self.texture.borrow_mut();
-        let tex_map = texture.remove(add_info.name());
-        if let Some(mut tex_map) = tex_map {
-            for (_, tex) in tex_map.drain() {
-                unsafe {
-                    tex.destroy();
-                }
-            }
-        }
+        self.tex_cache
+            .borrow_mut()
+            .retain(|(name, _), _| name != add_info.name());
 
         let mut stream = self.stream.borrow_mut();
         stream.remove(add_info.name());
 
-        data.insert(add_info.name().clone(), add_data);
-        info.insert(add_info.name().clone(), add_info);
+        if let GfxData::FontData(font) = &add_data {
+            if let Err(e) = self.open_font_sizes(font) {
+                warn!("{}", e);
+            }
+        }
+
+        let name = add_info.name().clone();
+        data.insert(name.clone(), add_data);
+        info.insert(name.clone(), add_info);
+        link_shaders(&data, &name);
     }
 
     pub fn remove(&self, name: &str) -> Result<()> {
+        self.take(name);
+        Ok(())
+    }
+
+    fn take(&self, name: &str) -> Option<(GfxInfo, GfxData)> {
         let mut info = self.gfx_info.borrow_mut();
         let mut data = self.gfx_data.borrow_mut();
-        let mut texture = self.texture.borrow_mut();
 
-        info.remove(name);
-        data.remove(name);
-
-        if let Some(mut tex_map) = texture.remove(name) {
-            for (_, tex) in tex_map.drain() {
-                unsafe {
-                    tex.destroy();
-                }
-            }
-        }
-
-        Ok(())
+        self.tex_cache
+            .borrow_mut()
+            .retain(|(tex, _), _| tex != name);
+        info.remove(name).zip(data.remove(name))
     }
 
     pub fn gfx_info(&self) -> HashMap<String, GfxInfo> {
@@ -154,6 +514,7 @@ impl GfxRuntime {
         *last_frame = value;
     }
 
+    // Failures are logged and handed back so they can be reported to the app
     pub fn render(
         &self,
         lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
@@ -161,16 +522,67 @@ impl GfxRuntime {
         next_frame: i64,
         dry_run: bool,
         shader_debug: bool,
+    ) -> Result<Option<(SpecErrorKind, String)>> {
+        if self.load_on_use(&spec) {
+            // a mixer waiting on its inputs shows black rather than a stale frame
+            if let (RenderSpec::Mix(mix), false) = (&spec, dry_run) {
+                if let Some(GfxData::VidMixerData(mixer)) = self.gfx_data.borrow().get(&mix.name) {
+                    if let Err(e) = mixer.clear(lowlevel_ctx, (0.0, 0.0, 0.0, 1.0)) {
+                        warn!("Could not clear {} while it loads: {}", mix.name, e);
+                    }
+                }
+            }
+            return Ok(None);
+        }
+        if let Err(e) = self.apply(lowlevel_ctx, &spec, next_frame, dry_run, shader_debug) {
+            warn!(spec = ?spec, "Could not render: {}", e);
+            return Ok(Some((self.error_kind(&spec), e.to_string())));
+        }
+
+        Ok(None)
+    }
+
+    fn error_kind(&self, spec: &RenderSpec) -> SpecErrorKind {
+        if self.validate(spec).is_err() {
+            return SpecErrorKind::MissingAsset;
+        }
+        match spec {
+            RenderSpec::SendCmd(_) => SpecErrorKind::BadUniform,
+            RenderSpec::SeekVid(_) => SpecErrorKind::SeekFailed,
+            _ => SpecErrorKind::RenderFailed,
+        }
+    }
+
+    fn apply(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        spec: &RenderSpec,
+        next_frame: i64,
+        dry_run: bool,
+        shader_debug: bool,
     ) -> Result<()> {
-        let last_frame = self.last_frame_rendered.borrow();
-        if let Err(e) = match &spec {
+        let last_frame = *self.last_frame_rendered.borrow();
+        match spec {
             RenderSpec::None => Ok(()),
-            RenderSpec::SendCmd(send_cmd) => self.send_cmd(lowlevel_ctx, send_cmd.clone()),
-            RenderSpec::HudText(_) => Ok(()),
+            RenderSpec::SendCmd(send_cmd) => {
+                self.journal(|| match self.gfx_data.borrow().get(&send_cmd.mix) {
+                    Some(GfxData::VidMixerData(mixer)) if !mixer.is_prepared() => {
+                        Some(Undo::Unprepare(send_cmd.mix.clone()))
+                    }
+                    Some(GfxData::VidMixerData(mixer)) => mixer
+                        .uniform(&send_cmd.name)
+                        .map(|uniform| Undo::Uniform(send_cmd.mix.clone(), uniform)),
+                    _ => None,
+                });
+                self.send_cmd(lowlevel_ctx, send_cmd.clone())
+            }
+            RenderSpec::HudText(_) | RenderSpec::HudWidget(_) => Ok(()),
+            RenderSpec::Monitor(_) => self.validate(spec), // the host shows it on the HUD
+            RenderSpec::Plugin(plugin) => plugins::render(lowlevel_ctx, plugin, next_frame),
             RenderSpec::Mix(mix) => self.mix(
                 lowlevel_ctx,
                 mix,
-                next_frame - *last_frame,
+                next_frame - last_frame,
                 next_frame,
                 dry_run,
                 shader_debug,
@@ -178,16 +590,368 @@ impl GfxRuntime {
             RenderSpec::SeekVid(seek_vid) => self.seek_vid(seek_vid, lowlevel_ctx),
             RenderSpec::Reset(reset) => self.reset(reset),
             RenderSpec::SendMidi(_) => Ok(()), // Midi sending is handled elsewhere
-        } {
-            let msg = format!("Could not render {:?}: {}", spec, e);
-            if dry_run {
-                panic!("{}", msg);
-            } else {
-                eprintln!("{}", msg);
+            RenderSpec::WindowCtl(_) => Ok(()), // so is the window
+            RenderSpec::SendOsc(_) => Ok(()),  // and osc
+            RenderSpec::SetFps(_) => self.validate(spec), // and the frame rate
+            RenderSpec::AudioPlay(_) | RenderSpec::AudioStop(_) | RenderSpec::AudioGain(_) => {
+                Ok(()) // and audio
+            }
+            RenderSpec::DrawText(draw_text) => self.draw_text(lowlevel_ctx, draw_text, next_frame),
+            RenderSpec::DrawShape(draw_shape) => self.draw_shape(lowlevel_ctx, draw_shape),
+            RenderSpec::Clear(clear) => self.clear(lowlevel_ctx, clear),
+            RenderSpec::SetScene(scene) => {
+                *self.scene.borrow_mut() = scene.mixes.clone();
+                Ok(())
+            }
+            RenderSpec::Viewport(viewport) => {
+                *self.viewport.borrow_mut() = viewport.size;
+                Ok(())
+            }
+            RenderSpec::LoadAsset(asset) => {
+                let loading = self.pending_loads.borrow().len();
+                self.load_asset(asset)?;
+                if self.pending_loads.borrow().len() > loading {
+                    self.journal(|| Some(Undo::Load(asset.name().to_string())));
+                }
+                Ok(())
+            }
+            RenderSpec::UnloadAsset(name) => {
+                let Some((info, data)) = self.take(name) else {
+                    bail!("No asset named {} to unload", name);
+                };
+                if let (GfxData::PluginData(asset), false) = (&data, self.journaling()) {
+                    plugins::unload_asset(asset);
+                }
+                self.journal(|| Some(Undo::Unload(Box::new((info, data)))));
+                self.asset_events.borrow_mut().push(AssetEvent {
+                    name: name.clone(),
+                    loaded: false,
+                    error: None,
+                });
+                Ok(())
+            }
+            RenderSpec::Transition(transition) => {
+                self.validate(spec)?;
+                let mut transitions = self.transitions.borrow_mut();
+                // restarting a transition to the same mixer replaces it
+                transitions.retain(|(t, _)| t.to_mix != transition.to_mix);
+                transitions.push((transition.clone(), next_frame));
+                Ok(())
+            }
+            RenderSpec::Batch(specs) => {
+                self.apply_batch(lowlevel_ctx, specs, next_frame, dry_run, shader_debug)
+            }
+            RenderSpec::DefineMacro(define) => {
+                self.validate(spec)?;
+                // redefining replaces, so a reloaded app can change its macros
+                self.macros
+                    .borrow_mut()
+                    .insert(define.name.clone(), define.specs.clone());
+                self.expansions
+                    .borrow_mut()
+                    .retain(|run, _| run.name != define.name);
+                Ok(())
+            }
+            RenderSpec::RunMacro(run) => {
+                let specs = self.expand_macro(run)?;
+                self.apply_batch(lowlevel_ctx, &specs, next_frame, dry_run, shader_debug)
             }
         }
+    }
 
-        Ok(())
+    // Nothing in the batch is applied unless every member checks out, and
+    // a member that fails past that undoes the ones before it
+    fn apply_batch(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        specs: &[RenderSpec],
+        next_frame: i64,
+        dry_run: bool,
+        shader_debug: bool,
+    ) -> Result<()> {
+        for member in specs {
+            self.validate(member)?;
+        }
+        // a batch inside another, or in a macro, is undone with the outermost
+        let outermost = !self.journaling();
+        if outermost {
+            *self.journal.borrow_mut() = Some(Journal {
+                undo: vec![],
+                scene: self.scene.borrow().clone(),
+                viewport: *self.viewport.borrow(),
+                transitions: self.transitions.borrow().clone(),
+                macros: self.macros.borrow().clone(),
+                asset_events: self.asset_events.borrow().len(),
+            });
+        }
+        let applied = specs.iter().try_for_each(|member| {
+            self.apply(lowlevel_ctx, member, next_frame, dry_run, shader_debug)
+        });
+        if let (true, Some(journal)) = (outermost, self.journal.take()) {
+            match applied {
+                Ok(()) => self.commit(journal),
+                Err(_) => self.roll_back(lowlevel_ctx, journal),
+            }
+        }
+        applied
+    }
+
+    fn journaling(&self) -> bool {
+        self.journal.borrow().is_some()
+    }
+
+    // Notes how to undo a change while a batch applies
+    fn journal(&self, undo: impl FnOnce() -> Option<Undo>) {
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            journal.undo.extend(undo());
+        }
+    }
+
+    fn commit(&self, journal: Journal) {
+        for undo in journal.undo {
+            if let Undo::Unload(unloaded) = undo {
+                if let (_, GfxData::PluginData(asset)) = *unloaded {
+                    plugins::unload_asset(&asset);
+                }
+            }
+        }
+    }
+
+    fn roll_back(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx, journal: Journal) {
+        for undo in journal.undo.into_iter().rev() {
+            if let Undo::Unload(unloaded) = undo {
+                let (info, data) = *unloaded;
+                self.add(info, data);
+                continue;
+            }
+            let gfx_data = self.gfx_data.borrow();
+            let undone = match undo {
+                Undo::Unprepare(name) => match gfx_data.get(&name) {
+                    Some(GfxData::VidMixerData(mixer)) => mixer.reset(),
+                    _ => Ok(()),
+                },
+                Undo::Uniform(name, uniform) => {
+                    if let Some(GfxData::VidMixerData(mixer)) = gfx_data.get(&name) {
+                        mixer.restore_uniform(&uniform);
+                    }
+                    Ok(())
+                }
+                Undo::Close(name) => match gfx_data.get(&name) {
+                    Some(GfxData::VidData(vid)) => vid.reset(),
+                    _ => Ok(()),
+                },
+                Undo::Seek(name, sec) => match gfx_data.get(&name) {
+                    Some(GfxData::VidData(vid)) => vid.seek_vid(sec, true, lowlevel_ctx),
+                    _ => Ok(()),
+                },
+                Undo::Reopen(name, input) => {
+                    if let Some(GfxData::VidData(vid)) = gfx_data.get(&name) {
+                        vid.restore_input(input);
+                    }
+                    Ok(())
+                }
+                Undo::Restream(name, stream) => {
+                    if let Some(GfxData::VidMixerData(mixer)) = gfx_data.get(&name) {
+                        mixer.restore_stream(stream);
+                    }
+                    Ok(())
+                }
+                Undo::Load(name) => {
+                    // the load runs on, its result is dropped
+                    self.pending_loads.borrow_mut().retain(|(n, _)| *n != name);
+                    Ok(())
+                }
+                Undo::Unload(..) => Ok(()),
+            };
+            if let Err(e) = undone {
+                warn!("Could not undo part of a failed batch: {}", e);
+            }
+        }
+        *self.scene.borrow_mut() = journal.scene;
+        *self.viewport.borrow_mut() = journal.viewport;
+        *self.transitions.borrow_mut() = journal.transitions;
+        *self.macros.borrow_mut() = journal.macros;
+        self.expansions.borrow_mut().clear();
+        self.asset_events
+            .borrow_mut()
+            .truncate(journal.asset_events);
+    }
+
+    // The specs a RunMacro stands for, with its params substituted
+    pub fn expand_macro(&self, run: &RunMacro) -> Result<Rc<Vec<RenderSpec>>> {
+        if let Some(specs) = self.expansions.borrow().get(run) {
+            return Ok(specs.clone());
+        }
+        let macros = self.macros.borrow();
+        let Some(specs) = macros.get(&run.name) else {
+            bail!("No macro named {}", run.name);
+        };
+        let specs = Rc::new(
+            run.expand(specs)
+                .map_err(|e| anyhow!("Could not expand macro {}: {}", run.name, e))?,
+        );
+        let mut expansions = self.expansions.borrow_mut();
+        if expansions.len() >= MAX_EXPANSIONS {
+            expansions.clear();
+        }
+        expansions.insert(run.clone(), specs.clone());
+        Ok(specs)
+    }
+
+    // A batch or macro as the specs it applies, any other spec as itself
+    pub fn members(&self, spec: &RenderSpec) -> Vec<RenderSpec> {
+        match spec {
+            RenderSpec::Batch(specs) => specs.iter().flat_map(|s| self.members(s)).collect(),
+            RenderSpec::RunMacro(run) => self
+                .expand_macro(run)
+                .map(|specs| specs.iter().flat_map(|s| self.members(s)).collect())
+                .unwrap_or_default(),
+            spec => vec![spec.clone()],
+        }
+    }
+
+    // Checks that everything a spec refers to is loaded, without touching any state
+    pub fn validate(&self, spec: &RenderSpec) -> Result<()> {
+        let gfx_data = self.gfx_data.borrow();
+        let mixer = |name: &str| match gfx_data.get(name) {
+            Some(GfxData::VidMixerData(mixer)) => match mixer.info.mesh_pass.as_ref() {
+                Some(pass) if !matches!(gfx_data.get(&pass.mesh), Some(GfxData::MeshData(_))) => {
+                    Err(anyhow!("No mesh named {} for {}", pass.mesh, name))
+                }
+                _ => Ok(()),
+            },
+            _ => Err(anyhow!("No mixer named {}", name)),
+        };
+        let video = |name: &str| match gfx_data.get(name) {
+            Some(GfxData::VidData(_)) => Ok(()),
+            _ => Err(anyhow!("No video named {}", name)),
+        };
+        match spec {
+            RenderSpec::None
+            | RenderSpec::HudText(_)
+            | RenderSpec::HudWidget(_)
+            | RenderSpec::SendMidi(_)
+            | RenderSpec::WindowCtl(_)
+            | RenderSpec::SendOsc(_)
+            | RenderSpec::Viewport(_)
+            | RenderSpec::AudioStop(_)
+            | RenderSpec::AudioGain(_) => Ok(()),
+            // the scene's mixes are checked each frame as they're rendered
+            RenderSpec::SetScene(_) => Ok(()),
+            // the name of an Audio asset or a path
+            RenderSpec::AudioPlay(play) => match gfx_data.get(&play.name) {
+                Some(GfxData::AudioData(_)) => Ok(()),
+                _ if std::path::Path::new(&play.name).exists() => Ok(()),
+                _ => bail!("No audio asset or file {}", play.name),
+            },
+            RenderSpec::SendCmd(send_cmd) => mixer(&send_cmd.mix),
+            RenderSpec::Monitor(monitor) => mixer(&monitor.mix),
+            RenderSpec::SetFps(set) => match set.fps {
+                1..=MAX_FPS => Ok(()),
+                fps => bail!("Can't run at {} fps", fps),
+            },
+            RenderSpec::Mix(mix) => {
+                mixer(&mix.name)?;
+                for input in &mix.inputs {
+                    match input {
+                        MixInput::Video(name) => video(name)?,
+                        MixInput::Mixed(name) => mixer(name)?,
+                        MixInput::Tex(name, frame) => match gfx_data.get(name) {
+                            Some(GfxData::TexData(tex)) => match frame {
+                                Some(frame) if *frame >= tex.info.count => bail!(
+                                    "{} has {} frames, no frame {}",
+                                    name,
+                                    tex.info.count,
+                                    frame
+                                ),
+                                _ => (),
+                            },
+                            _ => bail!("No texture named {}", name),
+                        },
+                    }
+                }
+                match mix.lut.as_ref() {
+                    Some(lut) => match gfx_data.get(lut) {
+                        Some(GfxData::LutData(_)) => Ok(()),
+                        _ if std::path::Path::new(lut).exists() => Ok(()),
+                        _ => bail!("No LUT asset or file {}", lut),
+                    },
+                    None => Ok(()),
+                }
+            }
+            RenderSpec::SeekVid(seek_vid) => video(&seek_vid.target),
+            RenderSpec::Reset(reset) => match gfx_data.contains_key(&reset.target) {
+                true => Ok(()),
+                false => bail!("Unable to find filter named {} to rebuild.", reset.target),
+            },
+            RenderSpec::DrawText(draw_text) => {
+                if cfg!(not(feature = "fonts")) {
+                    bail!("DrawText needs sdlrig built with the fonts feature");
+                }
+                let span_fonts = draw_text.spans.iter().filter_map(|span| span.font.as_ref());
+                let fonts = std::iter::once(&draw_text.font)
+                    .chain(&draw_text.fallback_fonts)
+                    .chain(span_fonts);
+                for font in fonts {
+                    let font_asset = matches!(gfx_data.get(font), Some(GfxData::FontData(_)));
+                    if !font_asset && !std::path::Path::new(font).exists() {
+                        bail!("No font asset or file {}", font);
+                    }
+                }
+                for animation in &draw_text.animations {
+                    if let GlyphDriver::Uniform(name) = &animation.driver {
+                        if !is_host_uniform(name) {
+                            bail!("No host uniform {} to animate glyphs with", name);
+                        }
+                    }
+                }
+                draw_text.target_mix.as_deref().map_or(Ok(()), mixer)
+            }
+            RenderSpec::DrawShape(draw_shape) => {
+                draw_shape.target_mix.as_deref().map_or(Ok(()), mixer)
+            }
+            RenderSpec::Clear(clear) => clear.target.as_deref().map_or(Ok(()), mixer),
+            RenderSpec::Plugin(plugin) => match plugins::handles_spec(&plugin.kind) {
+                true => Ok(()),
+                false => bail!("No plugin renders {}", plugin.kind),
+            },
+            RenderSpec::LoadAsset(asset) => match asset {
+                Asset::Missing => bail!("Cannot load a missing asset"),
+                Asset::Plugin(p) if !plugins::handles_asset(&p.kind) => {
+                    bail!("No plugin loads {} assets", p.kind)
+                }
+                _ => Ok(()),
+            },
+            RenderSpec::UnloadAsset(name) => match gfx_data.contains_key(name) {
+                true => Ok(()),
+                false => bail!("No asset named {} to unload", name),
+            },
+            RenderSpec::Transition(transition) => {
+                mixer(&transition.from_mix)?;
+                mixer(&transition.to_mix)
+            }
+            RenderSpec::Batch(specs) => {
+                for member in specs {
+                    self.validate(member)?;
+                }
+                Ok(())
+            }
+            // macro bodies are only checked once params are filled in, but
+            // one that could expand into itself is refused outright
+            RenderSpec::DefineMacro(define) => match define.specs.iter().any(nests_macro) {
+                true => bail!(
+                    "Macro {} runs or defines a macro, they can't nest",
+                    define.name
+                ),
+                false => Ok(()),
+            },
+            RenderSpec::RunMacro(run) => {
+                for member in self.expand_macro(run)?.iter() {
+                    self.validate(member)?;
+                }
+                Ok(())
+            }
+        }
     }
 
     pub fn mix(
@@ -212,9 +976,48 @@ impl GfxRuntime {
             Some(GfxData::VidMixerData(vid_mixer)) => vid_mixer,
             _ => bail!("No data for mixer data for {}", mix.name),
         };
+        if let Some(shader) = vid_mixer.info.shader_asset.as_ref() {
+            let prepared = vid_mixer.prepare(lowlevel_ctx);
+            self.shader_status(shader, &prepared);
+            prepared?;
+        }
+
+        // uploaded on first use, they stay until the asset changes
+        let mut stills = vec![];
+        for input in &mix.inputs {
+            let MixInput::Tex(name, frame) = input else {
+                stills.push(None);
+                continue;
+            };
+            let tex = match gfx_data.get(name) {
+                Some(GfxData::TexData(tex)) => tex,
+                _ => bail!("No such texture as {}", name),
+            };
+            let index = match frame {
+                Some(index) => *index,
+                None => tex
+                    .info
+                    .frame_at((frames * 1000 / self.frames_per_sec.max(1)) as u64),
+            };
+            let mut tex_cache = self.tex_cache.borrow_mut();
+            let key = (name.clone(), index);
+            let gpu_tex = match tex_cache.get(&key) {
+                Some(gpu_tex) => gpu_tex.clone(),
+                None => {
+                    let pixels = tex.frame(index, frames)?;
+                    let gpu_tex =
+                        Arc::new(GpuTex::upload(lowlevel_ctx, &pixels, tex.info.mipmaps)?);
+                    tex_cache.insert(key, gpu_tex.clone());
+                    gpu_tex
+                }
+            };
+            self.tex_warming.borrow_mut().insert(name.clone());
+            stills.push(Some(gpu_tex));
+        }
 
+        let mixer_size = (vid_mixer.info.width, vid_mixer.info.height);
         let mut inputs = vec![];
-        for name in &mix.inputs {
+        for (name, still) in mix.inputs.iter().zip(&stills) {
             match name {
                 MixInput::Video(name) => {
                     inputs.push(match gfx_data.get(name) {
@@ -226,23 +1029,52 @@ impl GfxRuntime {
                     Some(GfxData::VidMixerData(vid_mixer_data)) => vid_mixer_data.into(),
                     _ => bail!("No mixer for feedback {}", name),
                 }),
+                MixInput::Tex(..) => {
+                    let gpu_tex = still.as_ref().unwrap();
+                    inputs.push(VidMixerInput::Still(
+                        gpu_tex.level(mixer_size),
+                        gpu_tex.size,
+                    ));
+                }
             }
         }
 
-        let lut_ptr = if let Some(lut) = mix.lut.as_ref() {
-            let mut lut_cache = self.lut_cache.borrow_mut();
-            if let Some(cached) = lut_cache.get(&lut.to_string()) {
-                cached.0
-            } else {
-                let lut_str = CString::new(lut.to_string())?;
-                unsafe {
-                    let lut_ptr = gfx_lowlevel_init_lut(lowlevel_ctx, lut_str.as_ptr());
-                    lut_cache.insert(lut.to_string(), WrapLut(lut_ptr));
-                    lut_ptr
+        let lut_ptr =
+            if let Some(GfxData::LutData(lut)) = mix.lut.as_ref().and_then(|l| gfx_data.get(l)) {
+                lut.prepare(lowlevel_ctx)?
+            } else if let Some(lut) = mix.lut.as_ref() {
+                let mut lut_cache = self.lut_cache.borrow_mut();
+                if let Some(cached) = lut_cache.get(&lut.to_string()) {
+                    cached.0
+                } else {
+                    let lut_str = CString::new(lut.to_string())?;
+                    unsafe {
+                        let lut_ptr = gfx_lowlevel_init_lut(lowlevel_ctx, lut_str.as_ptr());
+                        lut_cache.insert(lut.to_string(), WrapLut(lut_ptr));
+                        lut_ptr
+                    }
                 }
-            }
-        } else {
-            std::ptr::null_mut()
+            } else {
+                std::ptr::null_mut()
+            };
+
+        for (name, value) in self.host_uniforms() {
+            vid_mixer.do_cmd(
+                lowlevel_ctx,
+                &SendCmd {
+                    mix: mix.name.clone(),
+                    name,
+                    value: SendValue::Float(value as f32),
+                },
+            )?;
+        }
+
+        let mesh = match vid_mixer.info.mesh_pass.as_ref() {
+            Some(pass) => match gfx_data.get(&pass.mesh) {
+                Some(GfxData::MeshData(mesh)) => Some(mesh),
+                _ => bail!("Mesh {} for {} is not loaded", pass.mesh, mix.name),
+            },
+            None => None,
         };
 
         match vid_mixer.mix(
@@ -250,7 +1082,9 @@ impl GfxRuntime {
             frames_to_mix,
             frames,
             &inputs,
+            mesh,
             mix.target.as_ref(),
+            *self.viewport.borrow(),
             lowlevel_ctx,
             dry_run,
             mix.no_display,
@@ -266,6 +1100,853 @@ impl GfxRuntime {
         Ok(())
     }
 
+    #[cfg(feature = "fonts")]
+    fn draw_text(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        draw_text: &DrawText,
+        frame: i64,
+    ) -> Result<()> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let spans = draw_text.styled_spans();
+        if spans.iter().all(|span| span.text.is_empty()) {
+            return Ok(());
+        }
+
+        // text for the window is rasterized at the letterboxed size so it stays sharp
+        let (scale, off_x, off_y) = match draw_text.target_mix {
+            Some(_) => (1.0, 0.0, 0.0),
+            None => self.window_letterbox(lowlevel_ctx),
+        };
+        let size = ((draw_text.size as f32 * scale).round() as u16).max(1);
+        // distance fields are rasterized once at SDF_SIZE, for every size
+        let raster = if draw_text.sdf { SDF_SIZE } else { size };
+        let to_target = size as f32 / raster as f32;
+        let to_raster = |px: u32| (px as f32 * scale / to_target).round() as u32;
+        let options = TextLayoutOptions {
+            align: draw_text.align,
+            max_width: draw_text.max_width.map(to_raster),
+            line_spacing: draw_text.line_spacing,
+            tab_stops: draw_text.tab_stops.iter().copied().map(to_raster).collect(),
+        };
+        // each span's fonts, and the outline of each where it has one
+        let mut span_keys = vec![];
+        let mut outline_keys = vec![];
+        for span in &spans {
+            let font = span.font.as_ref().unwrap_or(&draw_text.font);
+            let keys = self.open_fonts(draw_text, font, raster, span.bold)?;
+            let width = span
+                .outline
+                .map_or(0, |(width, _)| to_raster(width as u32) as u16);
+            outline_keys.push(
+                keys.iter()
+                    .map(|key| {
+                        let key = FontKey {
+                            outline: width,
+                            ..key.clone()
+                        };
+                        (width > 0 && self.try_open_font(&key)).then_some(key)
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            span_keys.push(keys);
+        }
+        let font_cache = self.font_cache.borrow();
+        let fonts = span_keys
+            .iter()
+            .map(|keys| keys.iter().map(|key| &font_cache[key]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let texts = spans
+            .iter()
+            .map(|span| span.text.as_str())
+            .collect::<Vec<_>>();
+        let layout = layout_spans(&fonts, &texts, &options)
+            .map_err(|e| anyhow!("Could not lay out text: {}", e))?;
+
+        let w = layout.width as f32 * to_target;
+        let pos_x = off_x + draw_text.pos.0 as f32 * scale;
+        let pos_y = off_y + draw_text.pos.1 as f32 * scale;
+        let x = match draw_text.align {
+            TextAlign::Left => pos_x,
+            TextAlign::Center => pos_x - w / 2.0,
+            TextAlign::Right => pos_x - w,
+        };
+        let (x, y) = (x.round(), pos_y.round());
+
+        // where each run starts in the text, for glyph animations
+        let mut firsts = vec![];
+        let mut chars = 0;
+        for run in &layout.runs {
+            firsts.push(chars);
+            chars += run.text.chars().count();
+        }
+        let animate = self.glyph_animator(draw_text, frame, scale);
+
+        // shadows then outlines under all of the text, so neither covers the
+        // span next to it. Outline glyphs reach up and left by their width.
+        let mut draws = vec![];
+        for pass in 0..3 {
+            for (span_index, span) in spans.iter().enumerate() {
+                for (font_index, key) in span_keys[span_index].iter().enumerate() {
+                    let runs = firsts
+                        .iter()
+                        .copied()
+                        .zip(&layout.runs)
+                        .filter(|(_, run)| run.span == span_index && run.font == font_index)
+                        .collect::<Vec<_>>();
+                    if runs.is_empty() {
+                        continue;
+                    }
+                    let outline = outline_keys[span_index][font_index]
+                        .as_ref()
+                        .map(|outline| (outline, outline.outline as f32 * to_target));
+                    let (glyphs, origin, color) = match (pass, span.shadow, outline) {
+                        (0, Some(((dx, dy), color)), Some((outline, inset))) => {
+                            let (dx, dy) = (dx as f32 * scale, dy as f32 * scale);
+                            (outline, (x + dx - inset, y + dy - inset), color)
+                        }
+                        (0, Some(((dx, dy), color)), None) => {
+                            (key, (x + dx as f32 * scale, y + dy as f32 * scale), color)
+                        }
+                        (1, _, Some((outline, inset))) => {
+                            let color = span.outline.map_or(draw_text.color, |(_, c)| c);
+                            (outline, (x - inset, y - inset), color)
+                        }
+                        (2, _, _) => (key, (x, y), span.color.unwrap_or(draw_text.color)),
+                        _ => continue,
+                    };
+                    draws.push((glyphs, fonts[span_index][font_index], runs, origin, color));
+                }
+            }
+        }
+
+        let target = self.draw_target(draw_text.target_mix.as_deref())?;
+        let dst = match target.as_ref() {
+            Some(frame) => frame.pl_frame(),
+            None => unsafe { &mut (*lowlevel_ctx).window_frame as _ },
+        };
+        // a draw for each font and look the text uses, from its font's atlas
+        let mut atlases = self.glyph_atlases.borrow_mut();
+        for (glyphs, metrics, runs, origin, color) in draws {
+            let atlas = atlases.entry(glyphs.clone()).or_default();
+            let place = GlyphPlacement {
+                origin,
+                scale: to_target,
+                color,
+                transform: &animate,
+            };
+            let vertices = atlas.quads(&font_cache[glyphs], metrics, &runs, &place)?;
+            if vertices.is_empty() {
+                continue;
+            }
+            let atlas = atlas.upload(lowlevel_ctx)?;
+            match unsafe {
+                gfx_lowlevel_draw_glyphs(
+                    lowlevel_ctx,
+                    dst,
+                    atlas.pl_frame(),
+                    vertices.as_ptr(),
+                    (vertices.len() / GLYPH_VERTEX_LEN) as i32,
+                    draw_text.sdf,
+                )
+            } {
+                0 => (),
+                err => bail!("Could not draw text {}", err),
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "fonts"))]
+    fn draw_text(&self, _: *mut gfx_lowlevel_gpu_ctx, _: &DrawText, _: i64) -> Result<()> {
+        bail!("DrawText needs sdlrig built with the fonts feature")
+    }
+
+    // How the glyph with each index in the text is moved by the DrawText's
+    // animations, in target pixels
+    #[cfg(feature = "fonts")]
+    fn glyph_animator(
+        &self,
+        draw_text: &DrawText,
+        frame: i64,
+        scale: f32,
+    ) -> impl Fn(usize) -> GlyphTransform {
+        let uniforms = self.host_uniforms();
+        let seconds = frame as f64 / self.frames_per_sec.max(1) as f64;
+        let animations = draw_text
+            .animations
+            .iter()
+            .map(|animation| {
+                let driven = match &animation.driver {
+                    GlyphDriver::Time => seconds,
+                    GlyphDriver::Uniform(name) => uniforms
+                        .iter()
+                        .find(|(uniform, _)| uniform == name)
+                        .map_or(0.0, |(_, value)| *value),
+                    GlyphDriver::Value(value) => *value as f64,
+                };
+                (driven * animation.speed as f64, animation.clone())
+            })
+            .collect::<Vec<_>>();
+        move |index| {
+            let mut transform = GlyphTransform::default();
+            for (phase, animation) in &animations {
+                let phase = phase + animation.stagger as f64 * index as f64;
+                let v = match animation.wave {
+                    GlyphWave::Sine => (phase * std::f64::consts::TAU).sin(),
+                    GlyphWave::Triangle => 1.0 - 4.0 * ((phase + 0.25).rem_euclid(1.0) - 0.5).abs(),
+                    GlyphWave::Saw => phase.rem_euclid(1.0),
+                    GlyphWave::Linear => phase,
+                } as f32;
+                transform.offset.0 += animation.offset.0 * v * scale;
+                transform.offset.1 += animation.offset.1 * v * scale;
+                transform.rotation += animation.rotation * v;
+                transform.scale *= 1.0 + animation.scale * v;
+            }
+            transform
+        }
+    }
+
+    // font, the DrawText's fallbacks then the system's that open, all at size
+    // and bold or not
+    #[cfg(feature = "fonts")]
+    fn open_fonts(
+        &self,
+        draw_text: &DrawText,
+        font: &str,
+        size: u16,
+        bold: bool,
+    ) -> Result<Vec<FontKey>> {
+        let key = |path: String| FontKey {
+            path,
+            size,
+            sdf: draw_text.sdf,
+            bold,
+            outline: 0,
+        };
+        let mut keys = vec![];
+        for font in std::iter::once(font).chain(draw_text.fallback_fonts.iter().map(|f| f.as_str()))
+        {
+            let key = key(self.font_path(font));
+            self.open_font(&key)?;
+            keys.push(key);
+        }
+        // left out if they don't open
+        for path in system_fallbacks() {
+            let key = key(path.clone());
+            if self.try_open_font(&key) {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    // A text spec's font is a Font asset's name or a path
+    pub fn font_path(&self, font: &str) -> String {
+        match self.gfx_data.borrow().get(font) {
+            Some(GfxData::FontData(asset)) => asset.path.clone(),
+            _ => font.to_string(),
+        }
+    }
+
+    // Opens a Font asset at each of its sizes, fonts that won't open are only
+    // found out on the render thread
+    #[cfg(feature = "fonts")]
+    fn open_font_sizes(&self, font: &FontAsset) -> Result<()> {
+        for size in &font.sizes {
+            let key = FontKey {
+                path: font.path.clone(),
+                size: *size,
+                sdf: false,
+                bold: false,
+                outline: 0,
+            };
+            self.open_font(&key)
+                .map_err(|e| anyhow!("Font {} at {}pt: {}", font.name, size, e))?;
+        }
+        Ok(())
+    }
+
+    // Font assets don't load without fonts, so there's never one to open
+    #[cfg(not(feature = "fonts"))]
+    fn open_font_sizes(&self, font: &FontAsset) -> Result<()> {
+        bail!(
+            "Font {} needs sdlrig built with the fonts feature",
+            font.name
+        )
+    }
+
+    #[cfg(feature = "fonts")]
+    fn open_font(&self, key: &FontKey) -> Result<()> {
+        let mut font_cache = self.font_cache.borrow_mut();
+        if !font_cache.contains_key(key) {
+            let font = match key.sdf {
+                true => load_sdf_font(&key.path, key.size),
+                false => load_font(&key.path, key.size),
+            };
+            let mut font = font.map_err(|e| anyhow!("Could not load font {}: {}", key.path, e))?;
+            style_font(&mut font, key.bold, key.outline);
+            font_cache.insert(key.clone(), font);
+        }
+        Ok(())
+    }
+
+    // Whether a font that can go without opened, remembering the ones that
+    // don't open
+    #[cfg(feature = "fonts")]
+    fn try_open_font(&self, key: &FontKey) -> bool {
+        if self.unopenable_fonts.borrow().contains(key) {
+            return false;
+        }
+        let opened = self.open_font(key).is_ok();
+        if !opened {
+            self.unopenable_fonts.borrow_mut().insert(key.clone());
+        }
+        opened
+    }
+
+    fn draw_shape(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        draw_shape: &DrawShape,
+    ) -> Result<()> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let mut vertices = tessellate(draw_shape);
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        if draw_shape.target_mix.is_none() {
+            let (scale, off_x, off_y) = self.window_letterbox(lowlevel_ctx);
+            for v in vertices.chunks_exact_mut(6) {
+                v[0] = off_x + v[0] * scale;
+                v[1] = off_y + v[1] * scale;
+            }
+        }
+
+        let target = self.draw_target(draw_shape.target_mix.as_deref())?;
+        let dst = match target.as_ref() {
+            Some(frame) => frame.pl_frame(),
+            None => unsafe { &mut (*lowlevel_ctx).window_frame as _ },
+        };
+        match unsafe {
+            gfx_lowlevel_draw_triangles(
+                lowlevel_ctx,
+                dst,
+                vertices.as_ptr(),
+                (vertices.len() / 6) as i32,
+            )
+        } {
+            0 => Ok(()),
+            err => bail!("Could not draw shapes {}", err),
+        }
+    }
+
+    fn window_letterbox(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> (f32, f32, f32) {
+        let tex = unsafe { (*lowlevel_ctx).window_frame.planes[0].texture };
+        if tex.is_null() {
+            return (1.0, 0.0, 0.0);
+        }
+        let (w, h) = unsafe { ((*tex).params.w as f32, (*tex).params.h as f32) };
+        letterbox(*self.viewport.borrow(), w, h)
+    }
+
+    // Blacks out the window each frame while a viewport is set so the bars
+    // around it don't show stale swapchain images
+    pub fn clear_letterbox(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<()> {
+        if self.viewport.borrow().is_none() {
+            return Ok(());
+        }
+        self.clear(lowlevel_ctx, &Clear::default())
+    }
+
+    // None means the window frame
+    fn draw_target(&self, target_mix: Option<&str>) -> Result<Option<Arc<WrapFrame>>> {
+        let Some(name) = target_mix else {
+            return Ok(None);
+        };
+        match self.gfx_data.borrow().get(name) {
+            Some(GfxData::VidMixerData(vid_mixer)) => {
+                Ok(Some(vid_mixer.output_frame().ok_or_else(|| {
+                    anyhow!("Mixer {} has not been mixed yet", name)
+                })?))
+            }
+            _ => bail!("No mixer named {} to draw into", name),
+        }
+    }
+
+    // Also how the host loads assets of its own, like dropped files
+    pub fn load_asset(&self, asset: &Asset) -> Result<()> {
+        if let Asset::Missing = asset {
+            bail!("Cannot load a missing asset");
+        }
+        let name = asset.name().to_string();
+        if let Some(info) = self.gfx_info.borrow().get(&name) {
+            if Asset::from(info.clone()) == *asset {
+                return Ok(());
+            }
+        }
+        let mut pending = self.pending_loads.borrow_mut();
+        if pending.iter().any(|(n, _)| *n == name) {
+            return Ok(());
+        }
+        let asset = asset.clone();
+        pending.push((name, thread::spawn(move || load(&asset))));
+        Ok(())
+    }
+
+    // Takes the assets the app listed with a load policy other than eager,
+    // replacing any from the app before it. Lazy ones start loading now, the
+    // rest wait for a spec to refer to them. Ones already loaded as they are
+    // stay as they are.
+    pub fn defer(&self, assets: &[Asset]) {
+        let mut deferred = self.deferred.borrow_mut();
+        deferred.clear();
+        for asset in assets {
+            let loaded = self.gfx_info.borrow().get(asset.name()).cloned();
+            if loaded.is_some_and(|info| Asset::from(info) == *asset) {
+                continue;
+            }
+            match asset.load_policy() {
+                LoadPolicy::OnFirstUse => {
+                    deferred.insert(asset.name().to_string(), asset.clone());
+                }
+                _ => {
+                    if let Err(e) = self.load_asset(asset) {
+                        warn!("Could not start loading {}: {}", asset.name(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Starts loading any deferred assets the spec refers to, true while one
+    // it needs is still on its way
+    fn load_on_use(&self, spec: &RenderSpec) -> bool {
+        let mut waiting = false;
+        for name in self.referenced(spec) {
+            let asset = self.deferred.borrow_mut().remove(&name);
+            if let Some(asset) = asset {
+                if let Err(e) = self.load_asset(&asset) {
+                    warn!("Could not start loading {}: {}", name, e);
+                }
+            }
+            waiting |= !self.gfx_data.borrow().contains_key(&name)
+                && self.pending_loads.borrow().iter().any(|(n, _)| *n == name);
+        }
+        waiting
+    }
+
+    // The names of the media assets a spec draws or plays
+    fn referenced(&self, spec: &RenderSpec) -> Vec<String> {
+        match spec {
+            RenderSpec::Mix(mix) => {
+                let mut names = mix
+                    .inputs
+                    .iter()
+                    .filter_map(|input| match input {
+                        MixInput::Video(name) => Some(name.clone()),
+                        MixInput::Mixed(_) => None,
+                        MixInput::Tex(name, _) => Some(name.clone()),
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(GfxData::VidMixerData(mixer)) = self.gfx_data.borrow().get(&mix.name) {
+                    names.extend(mixer.info.mesh_pass.as_ref().map(|pass| pass.mesh.clone()));
+                }
+                names
+            }
+            RenderSpec::SeekVid(seek_vid) => vec![seek_vid.target.clone()],
+            RenderSpec::AudioPlay(play) => vec![play.name.clone()],
+            RenderSpec::Batch(specs) => specs.iter().flat_map(|s| self.referenced(s)).collect(),
+            RenderSpec::RunMacro(run) => self
+                .expand_macro(run)
+                .map(|specs| specs.iter().flat_map(|s| self.referenced(s)).collect())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    // Loads again every Vid opened from path, or whose glob now matches it,
+    // returning their names. The old ones play until the new ones are ready.
+    pub fn reload_vids(&self, path: &Path) -> Vec<String> {
+        let Ok(changed) = path.canonicalize() else {
+            return vec![];
+        };
+        self.reload(
+            |info| matches!(info, GfxInfo::VidInfo(vid) if opened_from(&vid.path, &changed)),
+        )
+    }
+
+    // Loads again every Tex whose files were written to, removed or newly
+    // match its glob since it loaded, their frames are uploaded again when
+    // next drawn
+    pub fn reload_texes(&self) -> Vec<String> {
+        let stale = self
+            .gfx_data
+            .borrow()
+            .values()
+            .filter_map(|data| match data {
+                GfxData::TexData(tex) if tex.stale() => Some(tex.info.name.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        self.reload(|info| stale.contains(info.name()))
+    }
+
+    // Loads again every Shader read from path or that included it, their
+    // mixers are rebuilt once the new source is in
+    pub fn reload_shaders(&self, path: &Path) -> Vec<String> {
+        let Ok(changed) = path.canonicalize() else {
+            return vec![];
+        };
+        let same = |p: &String| Path::new(p).canonicalize().ok().as_ref() == Some(&changed);
+        self.reload(|info| match info {
+            GfxInfo::ShaderInfo(shader) => same(&shader.path) || shader.includes.iter().any(same),
+            _ => false,
+        })
+    }
+
+    // Loads again every Lut read from path
+    pub fn reload_luts(&self, path: &Path) -> Vec<String> {
+        let Ok(changed) = path.canonicalize() else {
+            return vec![];
+        };
+        self.reload(|info| match info {
+            GfxInfo::LutInfo(lut) => {
+                Path::new(&lut.path).canonicalize().ok().as_ref() == Some(&changed)
+            }
+            _ => false,
+        })
+    }
+
+    fn reload(&self, wanted: impl Fn(&GfxInfo) -> bool) -> Vec<String> {
+        let infos = self
+            .gfx_info
+            .borrow()
+            .values()
+            .filter(|info| wanted(info))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut pending = self.pending_loads.borrow_mut();
+        let mut names = vec![];
+        for info in infos {
+            let name = info.name().clone();
+            if pending.iter().any(|(n, _)| *n == name) {
+                continue;
+            }
+            let asset = Asset::from(info);
+            self.reloads.borrow_mut().insert(name.clone());
+            pending.push((name.clone(), thread::spawn(move || load(&asset))));
+            names.push(name);
+        }
+        names
+    }
+
+    // Rasterizes again every svg Tex fit to the output, for its new drawable size
+    pub fn rasterize_for_output(&self, size: (u32, u32)) -> Vec<String> {
+        texruntime::set_output_size(size);
+        self.reload(|info| match info {
+            GfxInfo::TexInfo(tex) => tex.raster == TexRaster::Output && texruntime::is_svg(tex),
+            _ => false,
+        })
+    }
+
+    // Forgets a LUT loaded from path so the next Mix using it reads it again
+    pub fn forget_lut(&self, path: &Path) {
+        let Ok(changed) = path.canonicalize() else {
+            return;
+        };
+        self.lut_cache
+            .borrow_mut()
+            .retain(|lut, _| Path::new(lut).canonicalize().ok().as_ref() != Some(&changed));
+    }
+
+    // Opens every video and compiles every mixer's shader now instead of on
+    // first use, with the name and error of each that failed
+    pub fn prepare_all(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Vec<(String, String)> {
+        let mut failed = self
+            .gfx_data
+            .borrow()
+            .iter()
+            .filter_map(|(name, data)| {
+                let prepared = match data {
+                    GfxData::VidData(vid) => vid.prepare(lowlevel_ctx),
+                    GfxData::VidMixerData(mixer) => {
+                        let prepared = mixer.prepare(lowlevel_ctx);
+                        if let Some(shader) = mixer.info.shader_asset.as_ref() {
+                            self.shader_status(shader, &prepared);
+                        }
+                        prepared
+                    }
+                    // opened as they were added
+                    GfxData::LutData(lut) => lut.prepare(lowlevel_ctx).map(|_| ()),
+                    // already open unless they failed to as they were added
+                    GfxData::FontData(font) => self.open_font_sizes(font),
+                    GfxData::PluginData(_)
+                    | GfxData::AudioData(_)
+                    | GfxData::ShaderData(_)
+                    | GfxData::TexData(_)
+                    | GfxData::MeshData(_) => Ok(()),
+                };
+                prepared.err().map(|e| (name.clone(), e.to_string()))
+            })
+            .collect::<Vec<_>>();
+        failed.sort();
+        failed
+    }
+
+    // Records how the last mixer to build a Shader asset got on, the app
+    // sees it in the asset's info
+    fn shader_status(&self, name: &str, prepared: &Result<()>) {
+        let status = match prepared {
+            Ok(()) => ShaderStatus::Compiled,
+            Err(e) => ShaderStatus::Failed(e.to_string()),
+        };
+        if let Some(GfxInfo::ShaderInfo(info)) = self.gfx_info.borrow_mut().get_mut(name) {
+            if info.status != status {
+                if let ShaderStatus::Failed(e) = &status {
+                    warn!("Shader {} failed to compile: {}", name, e);
+                }
+                info.status = status;
+            }
+        }
+    }
+
+    // The samples of a loaded Audio asset, for an AudioPlay of its name
+    pub fn audio_samples(&self, name: &str) -> Option<Arc<Vec<f32>>> {
+        match self.gfx_data.borrow().get(name) {
+            Some(GfxData::AudioData(audio)) => Some(audio.samples.clone()),
+            _ => None,
+        }
+    }
+
+    // Each video's decode time since the last call, leaving out the idle ones
+    pub fn take_decode_times(&self) -> Vec<(String, u64)> {
+        self.gfx_data
+            .borrow()
+            .iter()
+            .filter_map(|(name, data)| match data {
+                GfxData::VidData(vid) => Some((name.clone(), vid.take_decode_us())),
+                _ => None,
+            })
+            .filter(|(_, us)| *us > 0)
+            .collect()
+    }
+
+    // Asset loads still running in the background
+    pub fn pending_loads(&self) -> usize {
+        self.pending_loads.borrow().len()
+    }
+
+    // Adds finished background loads and returns what changed since the last
+    // call, callers should refresh the guest's gfx info when this is not empty
+    pub fn poll_asset_events(&self) -> Vec<AssetEvent> {
+        let mut pending = self.pending_loads.borrow_mut();
+        let mut i = 0;
+        while i < pending.len() {
+            if !pending[i].1.is_finished() {
+                i += 1;
+                continue;
+            }
+            let (name, handle) = pending.remove(i);
+            let reload = self.reloads.borrow_mut().remove(&name);
+            let loaded = handle.join().map(|loaded| {
+                // a font that doesn't open fails its load rather than each text
+                // drawn with it
+                if let Ok(GfxData::FontData(font)) = &loaded {
+                    self.open_font_sizes(font)?;
+                }
+                loaded
+            });
+            let event = match loaded {
+                Ok(Ok(gfx_data)) => {
+                    if reload {
+                        self.remove(&name).ok();
+                        self.asset_reloads
+                            .borrow_mut()
+                            .push(AssetReloadedEvent { name: name.clone() });
+                    }
+                    self.add(gfx_data.info(), gfx_data);
+                    AssetEvent {
+                        name,
+                        loaded: true,
+                        error: None,
+                    }
+                }
+                Ok(Err(e)) => AssetEvent {
+                    name,
+                    loaded: false,
+                    error: Some(e.to_string()),
+                },
+                Err(_) => AssetEvent {
+                    name,
+                    loaded: false,
+                    error: Some("loader panicked".into()),
+                },
+            };
+            self.asset_events.borrow_mut().push(event);
+        }
+        self.asset_events.borrow_mut().drain(..).collect()
+    }
+
+    // Uploads a few more frames of each Tex a Mix has drawn, so an animation
+    // doesn't stall through its first loop, then drops decoded frames beyond
+    // the memory budget, the least recently drawn of those uploaded first
+    pub fn upload_texes(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx, frame: i64) {
+        let gfx_data = self.gfx_data.borrow();
+        let mut tex_cache = self.tex_cache.borrow_mut();
+        let mut uploads = TEX_UPLOADS_PER_FRAME;
+        self.tex_warming.borrow_mut().retain(|name| {
+            let Some(GfxData::TexData(tex)) = gfx_data.get(name) else {
+                return false;
+            };
+            let missing = (0..tex.info.count)
+                .filter(|i| !tex_cache.contains_key(&(name.clone(), *i)))
+                .collect::<Vec<_>>();
+            let mut done = 0;
+            for &index in missing.iter().take(uploads) {
+                let uploaded = tex
+                    .frame(index, frame)
+                    .and_then(|pixels| GpuTex::upload(lowlevel_ctx, &pixels, tex.info.mipmaps));
+                match uploaded {
+                    Ok(gpu_tex) => {
+                        tex_cache.insert((name.clone(), index), Arc::new(gpu_tex));
+                        done += 1;
+                    }
+                    Err(e) => {
+                        warn!("Could not upload frame {} of {}: {}", index, name, e);
+                        return false;
+                    }
+                }
+            }
+            uploads -= done;
+            missing.len() > done
+        });
+
+        let mut resident = vec![];
+        for (name, data) in gfx_data.iter() {
+            let GfxData::TexData(tex) = data else {
+                continue;
+            };
+            for (index, last_used, bytes) in tex.resident() {
+                let uploaded = tex_cache.contains_key(&(name.clone(), index));
+                resident.push((!uploaded, last_used, bytes, tex, index));
+            }
+        }
+        let mut total = resident.iter().map(|r| r.2).sum::<usize>();
+        let budget = texruntime::memory_budget();
+        if total <= budget {
+            return;
+        }
+        resident.sort_by_key(|&(not_uploaded, last_used, ..)| (not_uploaded, last_used));
+        for (_, _, bytes, tex, index) in resident {
+            if total <= budget {
+                break;
+            }
+            tex.evict(index);
+            total -= bytes;
+        }
+    }
+
+    // Assets a reload has replaced since the last call, with poll_asset_events
+    // adding them
+    pub fn poll_asset_reloads(&self) -> Vec<AssetReloadedEvent> {
+        self.asset_reloads.borrow_mut().drain(..).collect()
+    }
+
+    fn clear(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx, clear: &Clear) -> Result<()> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let rgba = (
+            clear.rgba.0 as f32 / 255.0,
+            clear.rgba.1 as f32 / 255.0,
+            clear.rgba.2 as f32 / 255.0,
+            clear.rgba.3 as f32 / 255.0,
+        );
+        let Some(name) = clear.target.as_ref() else {
+            return match unsafe {
+                gfx_lowlevel_frame_clear(
+                    lowlevel_ctx,
+                    &mut (*lowlevel_ctx).window_frame,
+                    rgba.0,
+                    rgba.1,
+                    rgba.2,
+                    rgba.3,
+                )
+            } {
+                0 => Ok(()),
+                err => bail!("Could not clear window {}", err),
+            };
+        };
+        match self.gfx_data.borrow().get(name) {
+            Some(GfxData::VidMixerData(vid_mixer)) => vid_mixer.clear(lowlevel_ctx, rgba),
+            _ => bail!("No mixer named {} to clear", name),
+        }
+    }
+
+    // Called once per frame after the guest's specs, draws active transitions
+    // over the window and drops finished ones
+    pub fn run_transitions(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx, frame: i64) {
+        let mut transitions = self.transitions.borrow_mut();
+        transitions.retain(|(transition, start)| {
+            let progress = if transition.frames <= 0 {
+                1.0
+            } else {
+                ((frame - start) as f32 / transition.frames as f32).clamp(0.0, 1.0)
+            };
+            if let Err(e) = self.transition(lowlevel_ctx, transition, progress) {
+                warn!("Dropping transition {:?}: {}", transition, e);
+                return false;
+            }
+            progress < 1.0
+        });
+    }
+
+    fn transition(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        transition: &Transition,
+        progress: f32,
+    ) -> Result<()> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let from = self
+            .draw_target(Some(&transition.from_mix))?
+            .ok_or_else(|| anyhow!("No frame for {}", transition.from_mix))?;
+        let to = self
+            .draw_target(Some(&transition.to_mix))?
+            .ok_or_else(|| anyhow!("No frame for {}", transition.to_mix))?;
+        let body = match transition.kind {
+            TransitionKind::Crossfade => {
+                "color = mix(texture(src_tex0, src_uv), texture(src_tex1, src_uv), progress);"
+            }
+            TransitionKind::Wipe => {
+                "color = src_uv.x < progress ? texture(src_tex1, src_uv) : texture(src_tex0, src_uv);"
+            }
+            TransitionKind::LumaDissolve => {
+                "vec4 a = texture(src_tex0, src_uv);
+                vec4 b = texture(src_tex1, src_uv);
+                float l = dot(b.rgb, vec3(0.2126, 0.7152, 0.0722));
+                color = mix(a, b, clamp((progress * 1.2 - l) / 0.2, 0.0, 1.0));"
+            }
+        };
+        unsafe {
+            blend_frames(
+                lowlevel_ctx,
+                &mut (*lowlevel_ctx).window_frame,
+                &from,
+                &to,
+                body,
+                progress,
+            )
+        }
+    }
+
     fn send_cmd(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx, send_cmd: SendCmd) -> Result<()> {
         let gfx_data = self.gfx_data.borrow();
         let Some(GfxData::VidMixerData(mix)) = gfx_data.get(&send_cmd.mix) else {
@@ -287,10 +1968,126 @@ impl GfxRuntime {
         vid_mixer.get_present_time()
     }
 
+    pub fn playback_positions(&self) -> Result<PlaybackPositions> {
+        let mut positions = PlaybackPositions::default();
+        for (name, data) in self.gfx_data.borrow().iter() {
+            match data {
+                GfxData::VidData(vid_data) => {
+                    let time_base = f64::from(vid_data.time_base()?);
+                    let pts = vid_data.last_real_pts()?.map_or(0.0, f64::from) * time_base;
+                    let (num, den) = vid_data.info.timebase_q;
+                    let duration = vid_data.info.duration() * num as f64 / den.max(1) as f64;
+                    positions.vids.insert(name.clone(), (pts, duration));
+                }
+                GfxData::VidMixerData(vid_mixer_data) => {
+                    let present_time = f64::from(vid_mixer_data.get_present_time()?);
+                    positions.mixes.insert(name.clone(), present_time);
+                }
+                GfxData::PluginData(_)
+                | GfxData::AudioData(_)
+                | GfxData::FontData(_)
+                | GfxData::ShaderData(_)
+                | GfxData::LutData(_)
+                | GfxData::TexData(_)
+                | GfxData::MeshData(_) => (),
+            }
+        }
+        Ok(positions)
+    }
+
+    // Takes any top level SetScene from this frame's specs and appends the
+    // scene's mixes the specs don't already mix themselves
+    pub fn add_scene(&self, specs: &mut Vec<RenderSpec>) {
+        for spec in specs.iter() {
+            if let RenderSpec::SetScene(scene) = spec {
+                *self.scene.borrow_mut() = scene.mixes.clone();
+            }
+        }
+        let scene = self.scene.borrow();
+        let mixed = specs
+            .iter()
+            .filter_map(|spec| match spec {
+                RenderSpec::Mix(mix) => Some(mix.name.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        specs.extend(
+            scene
+                .iter()
+                .filter(|mix| !mixed.contains(&mix.name))
+                .cloned()
+                .map(RenderSpec::Mix),
+        );
+    }
+
+    // The beat and audio values mixers get set, those the host has now
+    fn host_uniforms(&self) -> Vec<(String, f64)> {
+        let mut uniforms = vec![];
+        if let Some(beat) = *self.beat.borrow() {
+            uniforms.extend([
+                (BEAT_UNIFORM.to_string(), beat),
+                (BPM_UNIFORM.to_string(), self.bpm()),
+                (BEAT_PHASE_UNIFORM.to_string(), beat.rem_euclid(1.0)),
+            ]);
+        }
+        if let Some(audio) = self.audio.borrow().as_ref() {
+            uniforms.extend([
+                (AUDIO_RMS_UNIFORM.to_string(), audio.rms()),
+                (AUDIO_ONSET_UNIFORM.to_string(), audio.onset as u8 as f64),
+            ]);
+            uniforms.extend(
+                audio
+                    .bands()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(band, value)| (format!("{}{}", AUDIO_BAND_UNIFORM, band), value)),
+            );
+        }
+        uniforms
+    }
+
+    pub fn bpm(&self) -> f64 {
+        *self.bpm.borrow()
+    }
+
+    // Mixers declaring //!VAR float beat, bpm or beat_phase get them set before
+    // every mix, None leaves them to the app
+    pub fn set_beat(&self, beat: Option<f64>) {
+        *self.beat.borrow_mut() = beat;
+    }
+
+    // Mixers declaring //!VAR float audio_rms, audio_onset or audio_band0 up to
+    // audio_band7 get them set before every mix from the latest analysis
+    pub fn set_audio(&self, audio: Option<AudioAnalysisEvent>) {
+        *self.audio.borrow_mut() = audio;
+    }
+
+    pub fn set_bpm(&self, bpm: f64) {
+        if bpm > 0.0 {
+            *self.bpm.borrow_mut() = bpm;
+        }
+    }
+
     fn seek_vid(&self, seek_vid: &SeekVid, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<()> {
         let gfx_data = self.gfx_data.borrow();
         if let Some(GfxData::VidData(vid_data)) = gfx_data.get(&seek_vid.target) {
-            vid_data.seek_vid(seek_vid.sec, seek_vid.exact, lowlevel_ctx)
+            let target = seek_vid.target.clone();
+            self.journal(|| match vid_data.position() {
+                Some(sec) => Some(Undo::Seek(target, sec)),
+                None => Some(Undo::Close(target)),
+            });
+            match seek_vid.by {
+                None => vid_data.seek_vid(seek_vid.sec, seek_vid.exact, lowlevel_ctx),
+                Some(SeekBy::RelativeFrames(frames)) => {
+                    // the video is opened on first use, so its fps may not be known yet
+                    vid_data.prepare(lowlevel_ctx)?;
+                    let sec = frames as f64 / f64::from(vid_data.fps()?);
+                    vid_data.seek_vid(sec, false, lowlevel_ctx)
+                }
+                Some(SeekBy::Beats(beats)) => {
+                    vid_data.seek_vid(beats * 60.0 / self.bpm(), false, lowlevel_ctx)
+                }
+            }
         } else {
             bail!("No video stream named {}", seek_vid.target)
         }
@@ -313,6 +2110,119 @@ impl GfxRuntime {
         }
     }
 
+    // None reads back the window frame, it must be called before finish_frame
+    pub fn download_rgba(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        source: Option<&str>,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<(u32, u32)>> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let Some(name) = source else {
+            return unsafe {
+                download_frame_rgba(lowlevel_ctx, &mut (*lowlevel_ctx).window_frame, buf).map(Some)
+            };
+        };
+        let gfx_data = self.gfx_data.borrow();
+        match gfx_data.get(name) {
+            Some(GfxData::VidMixerData(vid_mixer_data)) => {
+                vid_mixer_data.download_rgba(lowlevel_ctx, buf)
+            }
+            _ => bail!("No mixer named {} to download", name),
+        }
+    }
+
+    // Like download_rgba without waiting, poll download for the pixels
+    pub fn start_download(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        source: Option<&str>,
+        download: &mut FrameDownload,
+    ) -> Result<bool> {
+        if lowlevel_ctx.is_null() {
+            bail!("Lowlevel context is null");
+        }
+        let Some(name) = source else {
+            return unsafe { download.start(&mut (*lowlevel_ctx).window_frame) };
+        };
+        let gfx_data = self.gfx_data.borrow();
+        match gfx_data.get(name) {
+            Some(GfxData::VidMixerData(vid_mixer_data)) => vid_mixer_data.start_download(download),
+            _ => bail!("No mixer named {} to download", name),
+        }
+    }
+
+    // Queues an app's readback, answered by poll_readbacks a frame or more
+    // later
+    pub fn request_readback(&self, req: ReadbackRequest) {
+        let mut queue = self.readback_queue.borrow_mut();
+        if queue.len() >= MAX_QUEUED_READBACKS {
+            if let Some(dropped) = queue.pop_front() {
+                warn!("Too many readbacks queued, dropped one of {}", dropped.mix);
+            }
+        }
+        queue.push_back(req);
+    }
+
+    // The readbacks whose downloads finished since the last call, then starts
+    // downloading queued ones from the frame just rendered
+    pub fn poll_readbacks(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        frame: i64,
+    ) -> Vec<ReadbackEvent> {
+        let mut events = vec![];
+        let mut readbacks = self.readbacks.borrow_mut();
+        let mut spare = self.spare_downloads.borrow_mut();
+        let mut rgba = vec![];
+        let mut in_flight = vec![];
+        for (req, read, mut download) in readbacks.drain(..) {
+            match download.poll(&mut rgba) {
+                Ok(None) => {
+                    in_flight.push((req, read, download));
+                    continue;
+                }
+                Ok(Some((w, h))) => events.push(readback_event(req, read, &rgba, w, h)),
+                // the download isn't reused after failing
+                Err(e) => {
+                    warn!("Readback of {} failed: {}", req.mix, e);
+                    continue;
+                }
+            }
+            spare.push(download);
+        }
+        *readbacks = in_flight;
+
+        let mut queue = self.readback_queue.borrow_mut();
+        while readbacks.len() < READBACKS_IN_FLIGHT {
+            let Some(req) = queue.pop_front() else {
+                break;
+            };
+            let mut download = match spare.pop() {
+                Some(download) => download,
+                None => match FrameDownload::new(lowlevel_ctx) {
+                    Ok(download) => download,
+                    Err(e) => {
+                        warn!("Readback of {} failed: {}", req.mix, e);
+                        break;
+                    }
+                },
+            };
+            match self.start_download(lowlevel_ctx, Some(&req.mix), &mut download) {
+                Ok(true) => readbacks.push((req, frame, download)),
+                // a mixer that hasn't rendered has nothing to read back
+                Ok(false) => spare.push(download),
+                Err(e) => {
+                    warn!("Readback of {} failed: {}", req.mix, e);
+                    spare.push(download);
+                }
+            }
+        }
+        events
+    }
+
     pub fn reset_mix_dispatches(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<()> {
         let gfx_data = self.gfx_data.borrow();
         for data in gfx_data.values() {
@@ -324,11 +2234,86 @@ impl GfxRuntime {
     }
 
     fn reset(&self, reset: &Reset) -> Result<()> {
-        let mut gfx_data = self.gfx_data.borrow_mut();
-        match gfx_data.get_mut(&reset.target) {
-            Some(GfxData::VidData(vid_data)) => vid_data.reset(),
-            Some(GfxData::VidMixerData(vid_mixer_data)) => vid_mixer_data.reset(),
+        let gfx_data = self.gfx_data.borrow();
+        // what it had is only kept while a batch might need it back
+        let undo = match gfx_data.get(&reset.target) {
+            Some(GfxData::VidData(vid_data)) => {
+                Undo::Reopen(reset.target.clone(), vid_data.take_input())
+            }
+            Some(GfxData::VidMixerData(vid_mixer_data)) => {
+                Undo::Restream(reset.target.clone(), vid_mixer_data.take_stream())
+            }
             _ => bail!("Unable to find filter named {} to rebuild.", reset.target),
+        };
+        self.journal(|| Some(undo));
+        Ok(())
+    }
+}
+
+fn readback_event(req: ReadbackRequest, frame: i64, rgba: &[u8], w: u32, h: u32) -> ReadbackEvent {
+    let data = match req.kind {
+        ReadbackKind::Pixels { width, height } => {
+            let (width, height) = (width.clamp(1, w), height.clamp(1, h));
+            ReadbackData::Pixels {
+                width,
+                height,
+                rgba: downsample_rgba(rgba, w, h, width, height),
+            }
         }
+        ReadbackKind::Histogram { bins } => {
+            ReadbackData::Histogram(luma_histogram(rgba, bins.clamp(1, 256)))
+        }
+    };
+    ReadbackEvent {
+        mix: req.mix,
+        frame,
+        data,
+    }
+}
+
+// Box filters src down to dst_w x dst_h, which must not be larger
+pub fn downsample_rgba(src: &[u8], src_w: u32, src_h: u32, dst_w: u32, dst_h: u32) -> Vec<u8> {
+    let mut dst = vec![0u8; dst_w as usize * dst_h as usize * 4];
+    for dy in 0..dst_h {
+        let y0 = dy * src_h / dst_h;
+        let y1 = ((dy + 1) * src_h / dst_h).max(y0 + 1);
+        for dx in 0..dst_w {
+            let x0 = dx * src_w / dst_w;
+            let x1 = ((dx + 1) * src_w / dst_w).max(x0 + 1);
+            let mut sum = [0u64; 4];
+            for y in y0..y1 {
+                let row = (y * src_w) as usize * 4;
+                for x in x0..x1 {
+                    let px = row + x as usize * 4;
+                    for c in 0..4 {
+                        sum[c] += src[px + c] as u64;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            let out = (dy * dst_w + dx) as usize * 4;
+            for c in 0..4 {
+                dst[out + c] = (sum[c] / count) as u8;
+            }
+        }
+    }
+    dst
+}
+
+fn luma_histogram(rgba: &[u8], bins: u32) -> Vec<u32> {
+    let mut hist = vec![0u32; bins as usize];
+    for px in rgba.chunks_exact(4) {
+        // Rec. 709 weights in 8.8 fixed point
+        let y = (54 * px[0] as u32 + 183 * px[1] as u32 + 19 * px[2] as u32) >> 8;
+        hist[(y * bins / 256) as usize] += 1;
+    }
+    hist
+}
+
+fn nests_macro(spec: &RenderSpec) -> bool {
+    match spec {
+        RenderSpec::DefineMacro(_) | RenderSpec::RunMacro(_) => true,
+        RenderSpec::Batch(specs) => specs.iter().any(nests_macro),
+        _ => false,
     }
 }
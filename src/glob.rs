@@ -1,42 +1,585 @@
-#![allow(non_upper_case_globals)]
-#![allow(non_camel_case_types)]
-#![allow(non_snake_case)]
-#![allow(unused)]
-mod _internal {
-    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
-}
+use anyhow::{anyhow, bail, Result};
+use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
 
-use std::{
-    ffi::{CStr, CString},
-    iter::once,
-};
+// Files and directories glob leaves out wherever they are, the ones each OS
+// drops into folders it has shown
+const IGNORED: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
 
+// Paths matching pattern sorted bytewise, None when nothing matches or the
+// pattern is bad, so callers can fall back to it as a plain path. Works like
+// glob(3) with GLOB_TILDE the same on every platform: * and ? don't cross a
+// /, which separates components everywhere, and don't match a leading dot
+// unless the component starts with one, [a-z] and [!a-z] are classes, \
+// escapes and ~ is the home directory. A ** component is any number of
+// directories, including none, without following links or going into hidden
+// ones. On windows \ separates components too, so [*] is how to match a
+// literal *, and a pattern can start at a drive, C:/, or a share,
+// //server/share/. Matches come back with / between components either way.
 pub fn glob(pattern: &str) -> Option<Vec<String>> {
-    let terminated = pattern.bytes().chain(once('\0' as u8)).collect();
-    let cpattern = CString::from_vec_with_nul(terminated).unwrap();
-    let mut pathv = vec![];
-
-    unsafe {
-        let mut res: _internal::glob_t = std::mem::zeroed();
-        let err = _internal::glob(
-            cpattern.as_ptr(),
-            _internal::GLOB_TILDE as i32,
-            None,
-            &mut res as *mut _,
+    glob_paths(pattern).ok()
+}
+
+// The same, saying what was wrong with pattern or that nothing matched it
+pub fn glob_paths(pattern: &str) -> Result<Vec<String>> {
+    glob_ignoring::<&str>(pattern, &[])
+}
+
+// The same, leaving out what has a name matching one of the ignore patterns
+// and everything under it, as well as IGNORED
+pub fn glob_ignoring<S: AsRef<str>>(pattern: &str, ignore: &[S]) -> Result<Vec<String>> {
+    let paths = walk(pattern, ignore, &mut vec![])?;
+    if paths.is_empty() {
+        bail!("Nothing matches {}", pattern);
+    }
+    Ok(paths)
+}
+
+// What pattern matches now, which can be nothing, and the directories it was
+// looked for in. Anything that could change the matches being added or
+// removed changes the time of one of them.
+pub fn glob_searched<S: AsRef<str>>(
+    pattern: &str,
+    ignore: &[S],
+) -> Result<(Vec<GlobMatch>, Vec<String>)> {
+    let mut searched = vec![];
+    let matches = walk(pattern, ignore, &mut searched)?
+        .iter()
+        .filter_map(|path| GlobMatch::stat(path).ok())
+        .collect();
+    searched.sort();
+    searched.dedup();
+    Ok((matches, searched))
+}
+
+fn walk<S: AsRef<str>>(
+    pattern: &str,
+    ignore: &[S],
+    searched: &mut Vec<String>,
+) -> Result<Vec<String>> {
+    let ignore = IGNORED
+        .iter()
+        .copied()
+        .chain(ignore.iter().map(|i| i.as_ref()))
+        .map(|i| parse(i).map_err(|e| anyhow!("{} in ignore pattern {}", e, i)))
+        .collect::<Result<Vec<_>>>()?;
+    let expanded = expand_tilde(&separators(pattern))?;
+    let (root, rest) = root(&expanded);
+    let mut paths = vec![root.to_string()];
+    // a trailing / only matches directories
+    let dirs_only = rest.ends_with('/');
+    let components = rest
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        searched.extend(paths.iter().map(|base| dir_of(base).to_string()));
+        if *component == "**" {
+            let mut next = vec![];
+            for base in &paths {
+                // ending with it is everything under base, otherwise base
+                // itself is the no directories case
+                if !last {
+                    next.push(base.clone());
+                }
+                descend(base, &ignore, last && !dirs_only, &mut next, searched);
+            }
+            paths = next;
+            continue;
+        }
+        let tokens = parse(component).map_err(|e| anyhow!("{} in {}", e, pattern))?;
+        let mut next = vec![];
+        for base in &paths {
+            if let Some(literal) = literal(&tokens) {
+                let path = join(base, &literal);
+                let found = match last && !dirs_only {
+                    true => fs::symlink_metadata(&path).is_ok(),
+                    false => Path::new(&path).is_dir(),
+                };
+                if found {
+                    next.push(path);
+                }
+                continue;
+            }
+            // unreadable directories match nothing, as with glob(3)
+            let Ok(entries) = fs::read_dir(dir_of(base)) else {
+                continue;
+            };
+            let mut names = entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                .filter(|name| matches(&tokens, &name.chars().collect::<Vec<_>>()))
+                .filter(|name| !ignored(&ignore, name))
+                .collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                let path = join(base, &name);
+                if !(last && !dirs_only) && !Path::new(&path).is_dir() {
+                    continue;
+                }
+                next.push(path);
+            }
+        }
+        paths = next;
+    }
+    if components.is_empty() {
+        paths.retain(|path| !path.is_empty());
+    }
+    if dirs_only {
+        for path in paths.iter_mut() {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    // more than one ** can reach the same place
+    paths.dedup();
+    Ok(paths)
+}
+
+// Every directory under base depth first, and every file too when files,
+// skipping hidden and ignored names and not following links
+fn descend(
+    base: &str,
+    ignore: &[Vec<Token>],
+    files: bool,
+    out: &mut Vec<String>,
+    searched: &mut Vec<String>,
+) {
+    searched.push(dir_of(base).to_string());
+    let Ok(entries) = fs::read_dir(dir_of(base)) else {
+        return;
+    };
+    let mut entries = entries
+        .flatten()
+        .filter_map(|entry| {
+            Some((
+                entry.file_name().into_string().ok()?,
+                entry.file_type().ok()?,
+            ))
+        })
+        .filter(|(name, _)| !name.starts_with('.') && !ignored(ignore, name))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, file_type) in entries {
+        let path = join(base, &name);
+        if file_type.is_dir() {
+            out.push(path.clone());
+            descend(&path, ignore, files, out, searched);
+        } else if files {
+            out.push(path);
+        }
+    }
+}
+
+fn ignored(ignore: &[Vec<Token>], name: &str) -> bool {
+    let name = name.chars().collect::<Vec<_>>();
+    ignore.iter().any(|tokens| matches(tokens, &name))
+}
+
+// A match with what it was when globbed, two globs finding the same files
+// compare equal unless one was written to in between
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GlobMatch {
+    pub path: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+impl GlobMatch {
+    // Follows links, so it's the file a link points at
+    pub fn stat(path: &str) -> Result<Self> {
+        let meta = fs::metadata(path).map_err(|e| anyhow!("Could not stat {}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            size: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        })
+    }
+}
+
+// glob_ignoring's matches in the same order, with their sizes and times, ones
+// removed since being matched are left out
+pub fn glob_matches<S: AsRef<str>>(pattern: &str, ignore: &[S]) -> Result<Vec<GlobMatch>> {
+    let matches = glob_ignoring(pattern, ignore)?
+        .iter()
+        .filter_map(|path| GlobMatch::stat(path).ok())
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        bail!("Nothing matches {}", pattern);
+    }
+    Ok(matches)
+}
+
+// Whether s has anything glob would match other than itself
+pub fn is_pattern(s: &str) -> bool {
+    match cfg!(windows) {
+        true => s.starts_with('~') || s.contains(['*', '?', '[']),
+        false => s.starts_with('~') || s.contains(['*', '?', '[', '\\']),
+    }
+}
+
+fn separators(pattern: &str) -> String {
+    match cfg!(windows) {
+        true => pattern.replace('\\', "/"),
+        false => pattern.to_string(),
+    }
+}
+
+// Where an absolute pattern starts from and the rest of it, which for a
+// relative one is all of it
+fn root(pattern: &str) -> (&str, &str) {
+    if cfg!(windows) {
+        let bytes = pattern.as_bytes();
+        // C:/
+        if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == b":/" {
+            return pattern.split_at(3);
+        }
+        // //server/share/, the server and share are names rather than
+        // patterns
+        if let Some(unc) = pattern.strip_prefix("//") {
+            let mut parts = unc.splitn(3, '/');
+            if let (Some(server), Some(share)) = (parts.next(), parts.next()) {
+                if !server.is_empty() && !share.is_empty() {
+                    let end = 2 + server.len() + 1 + share.len();
+                    let rest = pattern[end..].trim_start_matches('/');
+                    return (&pattern[..end], rest);
+                }
+            }
+        }
+    }
+    match pattern.strip_prefix('/') {
+        Some(rest) => ("/", rest),
+        None => ("", pattern),
+    }
+}
+
+fn expand_tilde(pattern: &str) -> Result<String> {
+    let Some(rest) = pattern.strip_prefix('~') else {
+        return Ok(pattern.to_string());
+    };
+    if !(rest.is_empty() || rest.starts_with('/')) {
+        bail!(
+            "Only ~ on its own can be expanded, not ~user in {}",
+            pattern
         );
+    }
+    let home = env::var("HOME")
+        .or_else(|_| env::var("USERPROFILE"))
+        .map_err(|_| anyhow!("No home directory for ~ in {}", pattern))?;
+    Ok(format!(
+        "{}{}",
+        home.replace('\\', "/").trim_end_matches('/'),
+        rest
+    ))
+}
 
-        if err != 0 {
-            _internal::globfree(&mut res as *mut _);
-            return None;
+fn dir_of(base: &str) -> &str {
+    if base.is_empty() {
+        "."
+    } else {
+        base
+    }
+}
+
+fn join(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{}{}", base, name)
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+enum Token {
+    Char(char),
+    // ?
+    One,
+    // *
+    Any,
+    // [...], its ranges and whether it was negated with ! or ^
+    Class(Vec<(char, char)>, bool),
+}
+
+fn parse(component: &str) -> Result<Vec<Token>> {
+    let chars = component.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                let Some(&c) = chars.get(i + 1) else {
+                    bail!("Nothing after \\ at the end of {}", component);
+                };
+                tokens.push(Token::Char(c));
+                i += 2;
+            }
+            '?' => {
+                tokens.push(Token::One);
+                i += 1;
+            }
+            '*' => {
+                // runs of * are one
+                if !matches!(tokens.last(), Some(Token::Any)) {
+                    tokens.push(Token::Any);
+                }
+                i += 1;
+            }
+            '[' => {
+                let (class, end) = parse_class(&chars, i)?;
+                tokens.push(class);
+                i = end;
+            }
+            c => {
+                tokens.push(Token::Char(c));
+                i += 1;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// The class opened at start and where it ends, a ] first in it is a member
+fn parse_class(chars: &[char], start: usize) -> Result<(Token, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = vec![];
+    let first = i;
+    loop {
+        let Some(&c) = chars.get(i) else {
+            bail!("Unclosed [");
+        };
+        if c == ']' && i > first {
+            return Ok((Token::Class(ranges, negated), i + 1));
+        }
+        let (low, after) = match c {
+            '\\' => match chars.get(i + 1) {
+                Some(&escaped) => (escaped, i + 2),
+                None => bail!("Unclosed ["),
+            },
+            c => (c, i + 1),
+        };
+        // a - before the ] is itself
+        if chars.get(after) == Some(&'-') && chars.get(after + 1).is_some_and(|&c| c != ']') {
+            let (high, end) = match chars[after + 1] {
+                '\\' => match chars.get(after + 2) {
+                    Some(&escaped) => (escaped, after + 3),
+                    None => bail!("Unclosed ["),
+                },
+                c => (c, after + 2),
+            };
+            if high < low {
+                bail!("Backwards range {}-{}", low, high);
+            }
+            ranges.push((low, high));
+            i = end;
+        } else {
+            ranges.push((low, low));
+            i = after;
+        }
+    }
+}
+
+fn literal(tokens: &[Token]) -> Option<String> {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+// Backtracking to the last * on a mismatch, so it's linear in practice
+fn matches(tokens: &[Token], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        let matched = match tokens.get(t) {
+            Some(Token::Any) => {
+                star = Some((t, n));
+                t += 1;
+                continue;
+            }
+            Some(Token::Char(c)) => *c == name[n],
+            Some(Token::One) => true,
+            Some(Token::Class(ranges, negated)) => {
+                ranges
+                    .iter()
+                    .any(|(low, high)| (*low..=*high).contains(&name[n]))
+                    != *negated
+            }
+            None => false,
+        };
+        if matched {
+            t += 1;
+            n += 1;
+        } else if let Some((star_t, star_n)) = star {
+            t = star_t + 1;
+            n = star_n + 1;
+            star = Some((star_t, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    tokens[t..].iter().all(|token| matches!(token, Token::Any))
+}
+
+// Compares runs of digits by their value, so frame_2 comes before frame_10
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (run_a, rest_a) = a.split_at(digits(a));
+                let (run_b, rest_b) = b.split_at(digits(b));
+                let value_a = strip_zeros(run_a);
+                let value_b = strip_zeros(run_b);
+                // longer without leading zeros is bigger, then digit by digit,
+                // then fewer leading zeros first
+                let order = value_a
+                    .len()
+                    .cmp(&value_b.len())
+                    .then_with(|| value_a.cmp(value_b))
+                    .then_with(|| run_a.len().cmp(&run_b.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+fn strip_zeros(run: &[u8]) -> &[u8] {
+    let zeros = run.iter().take_while(|c| **c == b'0').count();
+    &run[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches_str(pattern: &str, name: &str) -> bool {
+        matches(&parse(pattern).unwrap(), &name.chars().collect::<Vec<_>>())
+    }
+
+    // A fresh directory under the system temp dir, removed on drop
+    struct TempTree(String);
+
+    impl TempTree {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let root = env::temp_dir()
+                .join(format!("sdlrig-glob-{}-{}", name, std::process::id()))
+                .to_string_lossy()
+                .replace('\\', "/");
+            fs::remove_dir_all(&root).ok();
+            for file in files {
+                let path = format!("{}/{}", root, file);
+                fs::create_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+                fs::write(&path, b"").unwrap();
+            }
+            Self(root)
         }
 
-        for i in 0..res.gl_matchc {
-            let entry = CStr::from_ptr(*(res.gl_pathv.offset(i as isize)));
-            pathv.push(entry.to_string_lossy().to_string());
+        fn glob(&self, pattern: &str) -> Vec<String> {
+            glob_paths(&format!("{}/{}", self.0, pattern))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| path[self.0.len() + 1..].to_string())
+                .collect()
         }
+    }
 
-        _internal::globfree(&mut res as *mut _);
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
     }
 
-    Some(pathv)
+    #[test]
+    fn class_ranges() {
+        assert!(matches_str("[a-c]x", "bx"));
+        assert!(!matches_str("[a-c]x", "dx"));
+        assert!(matches_str("[!a-c]x", "dx"));
+        assert!(!matches_str("[^a-c]x", "ax"));
+        assert!(matches_str("[a-cx-z]", "y"));
+        // ] first is a member, - last is itself
+        assert!(matches_str("[]a]", "]"));
+        assert!(matches_str("[a-]", "-"));
+        assert!(matches_str("[\\]]", "]"));
+        assert!(parse("[z-a]").is_err());
+        assert!(parse("[abc").is_err());
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches_str("*.mp4", "clip.mp4"));
+        assert!(matches_str("a*b*c", "aXbYbc"));
+        assert!(!matches_str("a*b*c", "aXbYbd"));
+        assert!(matches_str("frame_???.png", "frame_001.png"));
+        assert!(!matches_str("frame_???.png", "frame_01.png"));
+        assert!(matches_str("\\*", "*"));
+        assert!(!matches_str("\\*", "a"));
+        assert!(parse("a\\").is_err());
+    }
+
+    #[test]
+    fn double_star() {
+        let tree = TempTree::new(
+            "double-star",
+            &[
+                "top.txt",
+                "a/mid.txt",
+                "a/b/deep.txt",
+                "a/b/deep.png",
+                ".hidden/no.txt",
+            ],
+        );
+        assert_eq!(
+            tree.glob("**/*.txt"),
+            ["a/b/deep.txt", "a/mid.txt", "top.txt"]
+        );
+        assert_eq!(tree.glob("a/**/deep.*"), ["a/b/deep.png", "a/b/deep.txt"]);
+        assert_eq!(tree.glob("**/"), ["a/", "a/b/"]);
+    }
+
+    #[test]
+    fn dotfiles() {
+        let tree = TempTree::new("dotfiles", &["shown.txt", ".dot.txt", ".DS_Store"]);
+        assert_eq!(tree.glob("*.txt"), ["shown.txt"]);
+        assert_eq!(tree.glob(".*.txt"), [".dot.txt"]);
+        // ignored wherever it is, even when asked for by name pattern
+        assert_eq!(tree.glob(".*"), [".dot.txt"]);
+    }
+
+    #[test]
+    fn natural_ordering() {
+        assert_eq!(natural_cmp("frame_2", "frame_10"), Ordering::Less);
+        assert_eq!(natural_cmp("frame_10", "frame_9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1", "a01"), Ordering::Less);
+        assert_eq!(natural_cmp("a01b", "a01b"), Ordering::Equal);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        let mut names = vec!["x10.png", "x2.png", "x1.png", "x02.png"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["x1.png", "x2.png", "x02.png", "x10.png"]);
+    }
 }
@@ -0,0 +1,234 @@
+use crate::fonts::{kerning, TextRun};
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_gpu_ctx;
+use crate::vidruntime::WrapFrame;
+use anyhow::{anyhow, bail, Result};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::ttf::Font;
+use std::collections::HashMap;
+
+const ATLAS_SIZE: u32 = 1024;
+// between glyphs, so linear sampling doesn't bleed one into the next
+const PADDING: u32 = 1;
+// floats in each vertex, see gfx_lowlevel_draw_glyphs
+pub const GLYPH_VERTEX_LEN: usize = 8;
+
+// Moves a glyph about its center
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphTransform {
+    pub offset: (f32, f32),
+    pub rotation: f32, // radians, clockwise
+    pub scale: f32,
+}
+
+impl Default for GlyphTransform {
+    fn default() -> Self {
+        Self {
+            offset: (0.0, 0.0),
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+// Where quads go, each glyph from its layout pixels times scale from origin,
+// then moved by the transform for its character's index in the text
+pub struct GlyphPlacement<'a> {
+    pub origin: (f32, f32),
+    pub scale: f32,
+    pub color: (u8, u8, u8, u8),
+    pub transform: &'a dyn Fn(usize) -> GlyphTransform,
+}
+
+// One font's glyphs at one size packed in rows of a texture as they're first
+// drawn. White where they're monochrome so the vertex color tints them,
+// emoji keep their own colors. A full atlas is emptied and starts over.
+pub struct GlyphAtlas {
+    pixels: Vec<u8>,
+    // where each glyph is, None for ones with nothing to draw
+    glyphs: HashMap<char, Option<(u32, u32, u32, u32)>>,
+    // where the next glyph goes and the height of its row so far
+    cursor: (u32, u32),
+    row_height: u32,
+    // bumped each time it's emptied
+    generation: u64,
+    uploaded: Option<WrapFrame>,
+    dirty: bool,
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self {
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize],
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            generation: 0,
+            uploaded: None,
+            dirty: false,
+        }
+    }
+}
+
+impl GlyphAtlas {
+    // Two triangles for each glyph of runs, each with the index in the text of
+    // its first character. Glyphs come from font and are spaced by metrics, the
+    // same font unless font is its outline.
+    pub fn quads(
+        &mut self,
+        font: &Font,
+        metrics: &Font,
+        runs: &[(usize, &TextRun)],
+        place: &GlyphPlacement,
+    ) -> Result<Vec<f32>> {
+        let generation = self.generation;
+        let quads = self.quads_once(font, metrics, runs, place)?;
+        if self.generation == generation {
+            return Ok(quads);
+        }
+        // emptied partway, the glyphs placed before that are gone
+        let generation = self.generation;
+        let quads = self.quads_once(font, metrics, runs, place)?;
+        if self.generation != generation {
+            bail!("Too many different glyphs to fit in one atlas");
+        }
+        Ok(quads)
+    }
+
+    fn quads_once(
+        &mut self,
+        font: &Font,
+        metrics: &Font,
+        runs: &[(usize, &TextRun)],
+        place: &GlyphPlacement,
+    ) -> Result<Vec<f32>> {
+        let (origin, scale, color) = (place.origin, place.scale, place.color);
+        let tint = [
+            color.0 as f32 / 255.0,
+            color.1 as f32 / 255.0,
+            color.2 as f32 / 255.0,
+            color.3 as f32 / 255.0,
+        ];
+        let mut vertices = vec![];
+        for (first, run) in runs {
+            let mut pen = run.x;
+            let mut previous = None;
+            for (i, c) in run.text.chars().enumerate() {
+                if let Some(previous) = previous {
+                    pen += kerning(metrics, previous, c);
+                }
+                if let Some((u, v, w, h)) = self.glyph(font, c)? {
+                    let x0 = origin.0 + pen as f32 * scale;
+                    let y0 = origin.1 + run.y as f32 * scale;
+                    let (x1, y1) = (x0 + w as f32 * scale, y0 + h as f32 * scale);
+                    let size = ATLAS_SIZE as f32;
+                    let (u0, v0) = (u as f32 / size, v as f32 / size);
+                    let (u1, v1) = ((u + w) as f32 / size, (v + h) as f32 / size);
+                    let moved = move_corner((place.transform)(first + i), (x0, y0), (x1, y1));
+                    for (x, y, u, v) in [
+                        (x0, y0, u0, v0),
+                        (x1, y0, u1, v0),
+                        (x0, y1, u0, v1),
+                        (x1, y0, u1, v0),
+                        (x1, y1, u1, v1),
+                        (x0, y1, u0, v1),
+                    ] {
+                        let (x, y) = moved((x, y));
+                        vertices.extend_from_slice(&[x, y, u, v]);
+                        vertices.extend_from_slice(&tint);
+                    }
+                }
+                pen += metrics.find_glyph_metrics(c).map_or(0, |m| m.advance);
+                previous = Some(c);
+            }
+        }
+        Ok(vertices)
+    }
+
+    // Where c is, rasterizing it the first time
+    fn glyph(&mut self, font: &Font, c: char) -> Result<Option<(u32, u32, u32, u32)>> {
+        if let Some(placed) = self.glyphs.get(&c) {
+            return Ok(*placed);
+        }
+        if c.is_whitespace() || font.find_glyph(c).is_none() {
+            self.glyphs.insert(c, None);
+            return Ok(None);
+        }
+        let surface = font
+            .render_char(c)
+            .blended(Color::RGBA(255, 255, 255, 255))
+            .map_err(|e| anyhow!("Could not render {:?}: {}", c, e))?
+            .convert_format(PixelFormatEnum::RGBA32)
+            .map_err(|e| anyhow!(e))?;
+        let (w, h) = (surface.width(), surface.height());
+        if w == 0 || h == 0 {
+            self.glyphs.insert(c, None);
+            return Ok(None);
+        }
+        if w > ATLAS_SIZE || h > ATLAS_SIZE {
+            bail!("{:?} is too big for a glyph atlas at {}x{}", c, w, h);
+        }
+        if self.cursor.0 + w > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + h > ATLAS_SIZE {
+            self.empty();
+        }
+        let (x, y) = self.cursor;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock(|pixels| {
+            for row in 0..h as usize {
+                let src = &pixels[row * pitch..row * pitch + w as usize * 4];
+                let at = ((y as usize + row) * ATLAS_SIZE as usize + x as usize) * 4;
+                self.pixels[at..at + src.len()].copy_from_slice(src);
+            }
+        });
+        self.cursor.0 += w + PADDING;
+        self.row_height = self.row_height.max(h);
+        self.dirty = true;
+        self.glyphs.insert(c, Some((x, y, w, h)));
+        Ok(Some((x, y, w, h)))
+    }
+
+    fn empty(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.cursor = (0, 0);
+        self.row_height = 0;
+        self.generation += 1;
+    }
+
+    // The texture, uploaded again when glyphs were added since
+    pub fn upload(&mut self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<&WrapFrame> {
+        if self.dirty || self.uploaded.is_none() {
+            let frame = self
+                .uploaded
+                .take()
+                .unwrap_or_else(|| WrapFrame::new(lowlevel_ctx));
+            frame.upload_rgba(lowlevel_ctx, &self.pixels, ATLAS_SIZE, ATLAS_SIZE)?;
+            self.uploaded = Some(frame);
+            self.dirty = false;
+        }
+        Ok(self.uploaded.as_ref().unwrap())
+    }
+}
+
+// Where each corner of the glyph from top left to bottom right goes
+fn move_corner(
+    transform: GlyphTransform,
+    (x0, y0): (f32, f32),
+    (x1, y1): (f32, f32),
+) -> impl Fn((f32, f32)) -> (f32, f32) {
+    let center = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+    let (sin, cos) = transform.rotation.sin_cos();
+    move |(x, y)| {
+        let (dx, dy) = (
+            (x - center.0) * transform.scale,
+            (y - center.1) * transform.scale,
+        );
+        (
+            center.0 + dx * cos - dy * sin + transform.offset.0,
+            center.1 + dx * sin + dy * cos + transform.offset.1,
+        )
+    }
+}
@@ -0,0 +1,831 @@
+#[cfg(feature = "hud")]
+use crate::fonts::{
+    load_fallbacks, load_font, render_spans_surface, render_text_surface, style_font,
+    TextLayoutOptions,
+};
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_gpu_ctx;
+use crate::gfxinfo::LogLevel;
+use crate::gfxruntime::downsample_rgba;
+use crate::renderspec::{HudWidget, Monitor, TextSpan};
+#[cfg(feature = "hud")]
+use crate::vidruntime::{overlay_frame, WrapFrame};
+#[cfg(feature = "hud")]
+use anyhow::anyhow;
+use anyhow::Result;
+use sdl2::pixels::Color;
+#[cfg(feature = "hud")]
+use sdl2::{
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{BlendMode, Canvas},
+    surface::Surface,
+    sys::SDL_WindowFlags,
+    ttf::Font,
+    video::Window,
+    VideoSubsystem,
+};
+#[cfg(feature = "hud")]
+use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tracing::info;
+
+// Seconds a warning stays up after it was last raised
+const WARNING_SECS: i64 = 3;
+#[cfg(feature = "hud")]
+const MARGIN: i32 = 8;
+// Most recent app log messages shown
+const LOG_LINES: usize = 8;
+#[cfg(feature = "hud")]
+// Widest the overlay gets, so it doesn't cover the whole output
+const OVERLAY_WIDTH: u32 = 640;
+#[cfg(feature = "hud")]
+// Alpha of the overlay's backing panel at full opacity
+const OVERLAY_PANEL_ALPHA: u8 = 160;
+#[cfg(feature = "hud")]
+const BAR_HEIGHT: u32 = 8;
+#[cfg(feature = "hud")]
+const GRAPH_HEIGHT: u32 = 32;
+#[cfg(feature = "hud")]
+// Narrowest meters and graphs are drawn, wider if text next to them is
+const BAR_WIDTH: u32 = 200;
+// Values a graph scrolls through
+const GRAPH_LEN: usize = 120;
+// Box a monitored mix is scaled to fit
+const MONITOR_WIDTH: u32 = 160;
+const MONITOR_HEIGHT: u32 = 90;
+#[cfg(feature = "hud")]
+// How often monitored mixes are downloaded, each one stalls the gpu
+const MONITOR_FPS: i64 = 10;
+
+enum Output {
+    #[cfg(feature = "hud")]
+    Window(Canvas<Window>),
+    // drawn over the output window by draw_overlay
+    #[cfg(feature = "hud")]
+    Overlay {
+        visible: bool,
+        opacity: f32,
+        // what's uploaded, only redone when the text changes
+        uploaded: Option<(String, WrapFrame)>,
+    },
+    // for --no-hud, written to stderr each time it changes
+    Log {
+        printed: String,
+    },
+}
+
+// The app's HudText and host status, either in a window next to the output,
+// over the output itself or in the log
+pub struct Hud {
+    output: Output,
+    // only the log goes without
+    #[cfg(feature = "hud")]
+    font: Option<Font<'static, 'static>>,
+    // the system's emoji and CJK fonts at the same size
+    #[cfg(feature = "hud")]
+    fallbacks: Vec<Font<'static, 'static>>,
+    // both again in bold, for the app's bold spans
+    #[cfg(feature = "hud")]
+    bold: Vec<Font<'static, 'static>>,
+    #[cfg(feature = "hud")]
+    font_size: u16,
+    // the fonts the app's spans name by path and whether they're bold, with
+    // the ones that wouldn't open
+    #[cfg(feature = "hud")]
+    span_fonts: HashMap<(String, bool), Font<'static, 'static>>,
+    #[cfg(feature = "hud")]
+    unopenable_fonts: HashSet<String>,
+    frames_per_sec: i64,
+    app_text: String,
+    app_spans: Vec<TextSpan>,
+    // stays up until cleared
+    error: Option<String>,
+    // what the host is doing about the error
+    status: Option<String>,
+    // host settings worth knowing during a show, like the pacing
+    info: Option<String>,
+    // host messages and the frame they expire on
+    warnings: Vec<(String, i64)>,
+    logs: VecDeque<String>,
+    widgets: Vec<HudWidget>,
+    // the values each graph was sent, oldest first
+    graphs: HashMap<String, VecDeque<f64>>,
+    // by slot, the mix shown and its last downloaded frame
+    monitors: BTreeMap<u32, (String, Option<(u32, u32, Vec<u8>)>)>,
+}
+
+impl Hud {
+    #[cfg(feature = "hud")]
+    pub fn new(
+        video: &VideoSubsystem,
+        font: &str,
+        font_size: u16,
+        frames_per_sec: i64,
+    ) -> Result<Self> {
+        let window = video
+            .window("HUD", 480, 320)
+            .resizable()
+            .position(0, 0)
+            .build()?;
+        let canvas = window.into_canvas().build()?;
+        Self::with_output(Output::Window(canvas), font, font_size, frames_per_sec)
+    }
+
+    // For single display setups, opacity 0..1 covers the text and its panel
+    #[cfg(feature = "hud")]
+    pub fn overlay(font: &str, font_size: u16, frames_per_sec: i64, opacity: f32) -> Result<Self> {
+        let output = Output::Overlay {
+            visible: true,
+            opacity: opacity.clamp(0.0, 1.0),
+            uploaded: None,
+        };
+        Self::with_output(output, font, font_size, frames_per_sec)
+    }
+
+    // Needs no font or window, widgets and monitors aren't shown. The only
+    // HUD there is without the hud feature.
+    pub fn log_only(frames_per_sec: i64) -> Self {
+        Self {
+            output: Output::Log {
+                printed: String::new(),
+            },
+            #[cfg(feature = "hud")]
+            font: None,
+            #[cfg(feature = "hud")]
+            fallbacks: vec![],
+            #[cfg(feature = "hud")]
+            bold: vec![],
+            #[cfg(feature = "hud")]
+            font_size: 0,
+            #[cfg(feature = "hud")]
+            span_fonts: HashMap::new(),
+            #[cfg(feature = "hud")]
+            unopenable_fonts: HashSet::new(),
+            frames_per_sec,
+            app_text: String::new(),
+            app_spans: vec![],
+            error: None,
+            status: None,
+            info: None,
+            warnings: vec![],
+            logs: VecDeque::new(),
+            widgets: vec![],
+            graphs: HashMap::new(),
+            monitors: BTreeMap::new(),
+        }
+    }
+
+    #[cfg(feature = "hud")]
+    fn with_output(
+        output: Output,
+        font: &str,
+        font_size: u16,
+        frames_per_sec: i64,
+    ) -> Result<Self> {
+        let path = font;
+        let font = load_font(path, font_size)
+            .map_err(|e| anyhow!("Could not load HUD font {}: {}", path, e))?;
+        let mut hud = Self::log_only(frames_per_sec);
+        hud.output = output;
+        hud.font = Some(font);
+        hud.font_size = font_size;
+        hud.fallbacks = load_fallbacks(font_size);
+        hud.bold = load_font(path, font_size)
+            .into_iter()
+            .chain(load_fallbacks(font_size))
+            .map(|mut font| {
+                style_font(&mut font, true, 0);
+                font
+            })
+            .collect();
+        Ok(hud)
+    }
+
+    pub fn window_id(&self) -> Option<u32> {
+        match &self.output {
+            #[cfg(feature = "hud")]
+            Output::Window(canvas) => Some(canvas.window().id()),
+            _ => None,
+        }
+    }
+
+    // Hides or shows the window or overlay
+    pub fn toggle(&mut self) {
+        match &mut self.output {
+            #[cfg(feature = "hud")]
+            Output::Window(canvas) => {
+                let window = canvas.window_mut();
+                if window.window_flags() & SDL_WindowFlags::SDL_WINDOW_HIDDEN as u32 != 0 {
+                    window.show();
+                } else {
+                    window.hide();
+                }
+            }
+            #[cfg(feature = "hud")]
+            Output::Overlay { visible, .. } => *visible = !*visible,
+            Output::Log { .. } => (),
+        }
+    }
+
+    #[cfg_attr(not(feature = "hud"), allow(unused_variables))]
+    pub fn set_opacity(&mut self, new_opacity: f32) {
+        #[cfg(feature = "hud")]
+        if let Output::Overlay {
+            opacity, uploaded, ..
+        } = &mut self.output
+        {
+            *opacity = new_opacity.clamp(0.0, 1.0);
+            *uploaded = None;
+        }
+    }
+
+    pub fn set_frames_per_sec(&mut self, frames_per_sec: i64) {
+        self.frames_per_sec = frames_per_sec;
+    }
+
+    pub fn set_text<T: AsRef<str>>(&mut self, text: T) {
+        self.app_text = text.as_ref().into();
+        self.app_spans = vec![TextSpan::from(text.as_ref())];
+    }
+
+    // The app's text with the look of each part, app_text is the text alone.
+    // Span fonts are paths, opened at the HUD's size the first time they're
+    // shown. Returns why each that hadn't failed before wouldn't open, those
+    // spans are shown in the HUD's font.
+    pub fn set_spans(&mut self, spans: Vec<TextSpan>) -> Vec<String> {
+        #[allow(unused_mut)]
+        let mut errors = vec![];
+        #[cfg(feature = "hud")]
+        if self.font.is_some() {
+            for span in &spans {
+                let Some(path) = span.font.as_ref() else {
+                    continue;
+                };
+                let key = (path.clone(), span.bold);
+                if self.span_fonts.contains_key(&key) || self.unopenable_fonts.contains(path) {
+                    continue;
+                }
+                match load_font(path, self.font_size) {
+                    Ok(mut font) => {
+                        style_font(&mut font, span.bold, 0);
+                        self.span_fonts.insert(key, font);
+                    }
+                    Err(e) => {
+                        errors.push(format!("Could not load HUD font {}: {}", path, e));
+                        self.unopenable_fonts.insert(path.clone());
+                    }
+                }
+            }
+        }
+        self.app_text = spans.iter().map(|span| span.text.as_str()).collect();
+        self.app_spans = spans;
+        errors
+    }
+
+    // This frame's widgets, graphs not sent again are forgotten
+    pub fn set_widgets(&mut self, widgets: Vec<HudWidget>) {
+        for widget in &widgets {
+            if let HudWidget::Graph { label, value, .. } = widget {
+                let values = self.graphs.entry(label.clone()).or_default();
+                if values.len() == GRAPH_LEN {
+                    values.pop_front();
+                }
+                values.push_back(*value);
+            }
+        }
+        self.graphs.retain(|label, _| {
+            widgets
+                .iter()
+                .any(|w| matches!(w, HudWidget::Graph { label: l, .. } if l == label))
+        });
+        self.widgets = widgets;
+    }
+
+    // This frame's monitors, false when there's no HUD window to show them on
+    pub fn set_monitors(&mut self, monitors: &[Monitor]) -> bool {
+        if self.window_id().is_none() {
+            self.monitors.clear();
+            return monitors.is_empty();
+        }
+        self.monitors
+            .retain(|slot, (mix, _)| monitors.iter().any(|m| m.slot == *slot && m.mix == *mix));
+        for monitor in monitors {
+            self.monitors
+                .entry(monitor.slot)
+                .or_insert_with(|| (monitor.mix.clone(), None));
+        }
+        true
+    }
+
+    // The mixes to download this frame, every one of them every few frames
+    // while the window is up
+    #[cfg_attr(not(feature = "hud"), allow(unused_variables))]
+    pub fn monitors_due(&self, frame: i64) -> Vec<String> {
+        #[cfg(feature = "hud")]
+        if let Output::Window(canvas) = &self.output {
+            let hidden = canvas.window().window_flags() & SDL_WindowFlags::SDL_WINDOW_HIDDEN as u32;
+            let every = (self.frames_per_sec / MONITOR_FPS).max(1);
+            if hidden == 0 && frame % every == 0 {
+                return self.monitors.values().map(|(mix, _)| mix.clone()).collect();
+            }
+        }
+        vec![]
+    }
+
+    // Scales down a mix's rgba download for the monitors showing it
+    pub fn update_monitor(&mut self, mix: &str, rgba: &[u8], w: u32, h: u32) {
+        let scale = f64::min(
+            MONITOR_WIDTH as f64 / w.max(1) as f64,
+            MONITOR_HEIGHT as f64 / h.max(1) as f64,
+        )
+        .min(1.0);
+        let width = ((w as f64 * scale) as u32).max(1);
+        let height = ((h as f64 * scale) as u32).max(1);
+        let small = downsample_rgba(rgba, w, h, width, height);
+        for (monitored, frame) in self.monitors.values_mut() {
+            if monitored == mix {
+                *frame = Some((width, height, small.clone()));
+            }
+        }
+    }
+
+    pub fn set_error(&mut self, error: Option<String>) {
+        self.error = error;
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn set_info(&mut self, info: Option<String>) {
+        self.info = info;
+    }
+
+    pub fn log(&mut self, level: LogLevel, message: &str) {
+        if self.logs.len() == LOG_LINES {
+            self.logs.pop_front();
+        }
+        self.logs.push_back(format!("{:?}: {}", level, message));
+    }
+
+    // Raising the same warning again keeps it up rather than repeating it
+    pub fn warn<T: AsRef<str>>(&mut self, frame: i64, warning: T) {
+        let until = frame + WARNING_SECS * self.frames_per_sec;
+        match self
+            .warnings
+            .iter_mut()
+            .find(|(w, _)| w == warning.as_ref())
+        {
+            Some((_, expires)) => *expires = until,
+            None => self.warnings.push((warning.as_ref().into(), until)),
+        }
+    }
+
+    // What's shown as text, without the app's, for a remote to show
+    pub fn text_sections(&mut self, frame: i64) -> Vec<String> {
+        self.sections(frame)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    pub fn app_text(&self) -> &str {
+        &self.app_text
+    }
+
+    pub fn widgets(&self) -> &[HudWidget] {
+        &self.widgets
+    }
+
+    // Everything shown, top to bottom, leaving out what's empty
+    fn sections(&mut self, frame: i64) -> Vec<(String, Color)> {
+        self.warnings.retain(|(_, expires)| *expires > frame);
+        let warnings = self
+            .warnings
+            .iter()
+            .map(|(w, _)| w.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let logs = Vec::from(self.logs.clone()).join("\n");
+        [
+            (
+                self.error.clone().unwrap_or_default(),
+                Color::RGB(255, 64, 64),
+            ),
+            (
+                self.status.clone().unwrap_or_default(),
+                Color::RGB(200, 200, 200),
+            ),
+            (
+                self.info.clone().unwrap_or_default(),
+                Color::RGB(128, 128, 128),
+            ),
+            (warnings, Color::RGB(255, 200, 0)),
+            (logs, Color::RGB(160, 200, 255)),
+        ]
+        .into_iter()
+        .filter(|(text, _)| !text.is_empty())
+        .collect()
+    }
+
+    // Banners, host sections, widgets then the app's text
+    #[cfg(feature = "hud")]
+    fn pieces(&self, sections: Vec<(String, Color)>, wrap: u32) -> Result<Vec<Piece>> {
+        let Some(font) = self.font.as_ref() else {
+            return Ok(vec![]);
+        };
+        let fonts = std::iter::once(font)
+            .chain(&self.fallbacks)
+            .collect::<Vec<_>>();
+        let options = TextLayoutOptions {
+            max_width: Some(wrap),
+            ..Default::default()
+        };
+        let text = |text: &str, color| -> Result<Surface<'static>> {
+            render_text_surface(&fonts, text, &options, color).map_err(|e| anyhow!(e))
+        };
+        let mut pieces = vec![];
+        for widget in &self.widgets {
+            if let HudWidget::Banner(banner) = widget {
+                pieces.push(Piece::Banner(text(banner, Color::RGB(255, 255, 255))?));
+            }
+        }
+        for (section, color) in sections {
+            pieces.push(Piece::Text(text(&section, color)?));
+        }
+        for widget in &self.widgets {
+            match widget {
+                HudWidget::Banner(_) => (),
+                HudWidget::Table { title, rows } => {
+                    if let Some(title) = title {
+                        pieces.push(Piece::Text(text(title, Color::RGB(255, 255, 255))?));
+                    }
+                    let rows = rows
+                        .iter()
+                        .map(|(label, value)| {
+                            Ok((
+                                text(label, Color::RGB(160, 160, 160))?,
+                                text(value, Color::RGB(255, 255, 255))?,
+                            ))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let column = rows.iter().map(|(l, _)| l.width()).max().unwrap_or(0);
+                    for (label, value) in rows {
+                        pieces.push(Piece::Row(label, value, column + MARGIN as u32));
+                    }
+                }
+                HudWidget::Meter {
+                    label,
+                    value,
+                    min,
+                    max,
+                } => {
+                    let label = format!("{} {:.2}", label, value);
+                    pieces.push(Piece::Text(text(&label, Color::RGB(200, 200, 200))?));
+                    pieces.push(Piece::Bar(fraction(*value, *min, *max)));
+                }
+                HudWidget::Graph {
+                    label,
+                    value,
+                    min,
+                    max,
+                } => {
+                    let title = format!("{} {:.2}", label, value);
+                    pieces.push(Piece::Text(text(&title, Color::RGB(200, 200, 200))?));
+                    let values = self.graphs.get(label).into_iter().flatten();
+                    pieces.push(Piece::Graph(
+                        values.map(|v| fraction(*v, *min, *max)).collect(),
+                    ));
+                }
+            }
+        }
+        if !self.app_text.is_empty() {
+            let bold = self.bold.iter().collect::<Vec<_>>();
+            let span_fonts = self
+                .app_spans
+                .iter()
+                .map(|span| {
+                    let mut fonts = match span.bold {
+                        true => bold.clone(),
+                        false => fonts.clone(),
+                    };
+                    // its own in place of the HUD's, with the same fallbacks
+                    let own = span
+                        .font
+                        .as_ref()
+                        .and_then(|path| self.span_fonts.get(&(path.clone(), span.bold)));
+                    if let Some(own) = own {
+                        fonts[0] = own;
+                    }
+                    fonts
+                })
+                .collect::<Vec<_>>();
+            let spans = render_spans_surface(
+                &span_fonts,
+                &self.app_spans,
+                &options,
+                Color::RGB(255, 255, 255),
+            )
+            .map_err(|e| anyhow!(e))?;
+            pieces.push(Piece::Text(spans));
+        }
+        Ok(pieces)
+    }
+
+    // Draws the HUD window or logs what changed, the overlay is drawn by
+    // draw_overlay instead
+    #[cfg_attr(not(feature = "hud"), allow(irrefutable_let_patterns))]
+    pub fn present(&mut self, frame: i64) -> Result<()> {
+        let sections = self.sections(frame);
+        if let Output::Log { printed } = &mut self.output {
+            let text = sections
+                .into_iter()
+                .map(|(text, _)| text)
+                .chain(std::iter::once(self.app_text.clone()))
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text != *printed {
+                for line in text.lines() {
+                    info!(target: "hud", "{}", line);
+                }
+                *printed = text;
+            }
+            return Ok(());
+        }
+        #[cfg(feature = "hud")]
+        self.present_window(sections)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "hud")]
+    fn present_window(&mut self, sections: Vec<(String, Color)>) -> Result<()> {
+        let Output::Window(canvas) = &self.output else {
+            return Ok(());
+        };
+        let (w, h) = canvas.window().size();
+        let wrap = w.saturating_sub(2 * MARGIN as u32).max(1);
+        let pieces = self.pieces(sections, wrap)?;
+        let panel = render_panel(pieces, Color::RGB(0, 0, 0))?;
+
+        let Output::Window(canvas) = &mut self.output else {
+            return Ok(());
+        };
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        if let Some(panel) = panel {
+            let texture_creator = canvas.texture_creator();
+            let texture = texture_creator.create_texture_from_surface(&panel)?;
+            let dst = Rect::new(0, 0, panel.width(), panel.height());
+            canvas.copy(&texture, None, dst).map_err(|e| anyhow!(e))?;
+        }
+        if let Some(font) = self.font.as_ref() {
+            draw_monitors(canvas, font, &self.monitors, w, h)?;
+        }
+        canvas.present();
+        Ok(())
+    }
+
+    // Draws the overlay into the output window's frame, call between
+    // starting and finishing it. Does nothing for a HUD window.
+    #[cfg(feature = "hud")]
+    pub fn draw_overlay(
+        &mut self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        frame: i64,
+    ) -> Result<()> {
+        let sections = self.sections(frame);
+        let Output::Overlay {
+            visible, uploaded, ..
+        } = &self.output
+        else {
+            return Ok(());
+        };
+        if !*visible || lowlevel_ctx.is_null() {
+            return Ok(());
+        }
+        let dst = unsafe { std::ptr::addr_of_mut!((*lowlevel_ctx).window_frame) };
+        let window_w = unsafe {
+            if !(*lowlevel_ctx).started || (*dst).planes[0].texture.is_null() {
+                return Ok(());
+            }
+            (*(*dst).planes[0].texture).params.w as u32
+        };
+        let wrap = window_w
+            .min(OVERLAY_WIDTH)
+            .saturating_sub(4 * MARGIN as u32)
+            .max(1);
+        let key = format!(
+            "{}|{:?}|{:?}|{:?}",
+            wrap, sections, self.app_spans, self.widgets
+        );
+        if uploaded.as_ref().map_or(true, |(k, _)| *k != key) {
+            let pieces = self.pieces(sections, wrap)?;
+            let Output::Overlay {
+                opacity, uploaded, ..
+            } = &mut self.output
+            else {
+                return Ok(());
+            };
+            *uploaded = match render_panel(pieces, Color::RGBA(0, 0, 0, OVERLAY_PANEL_ALPHA))? {
+                Some(panel) => {
+                    let (w, h, rgba) = surface_rgba(&panel, *opacity);
+                    let frame = WrapFrame::new(lowlevel_ctx);
+                    frame.upload_rgba(lowlevel_ctx, &rgba, w, h)?;
+                    Some((key, frame))
+                }
+                None => None,
+            };
+        }
+        let Output::Overlay {
+            uploaded: Some((_, panel)),
+            ..
+        } = &self.output
+        else {
+            return Ok(());
+        };
+        unsafe { overlay_frame(lowlevel_ctx, dst, panel, MARGIN, MARGIN) }
+    }
+
+    #[cfg(not(feature = "hud"))]
+    pub fn draw_overlay(&mut self, _: *mut gfx_lowlevel_gpu_ctx, _: i64) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Rows of monitors up from the bottom of the window, each mix scaled into
+// its box with its name underneath
+#[cfg(feature = "hud")]
+fn draw_monitors(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    monitors: &BTreeMap<u32, (String, Option<(u32, u32, Vec<u8>)>)>,
+    w: u32,
+    h: u32,
+) -> Result<()> {
+    let cell_w = MONITOR_WIDTH + MARGIN as u32;
+    let cell_h = MONITOR_HEIGHT + font.height().max(0) as u32 + MARGIN as u32;
+    let per_row = (w.saturating_sub(MARGIN as u32) / cell_w).max(1);
+    let texture_creator = canvas.texture_creator();
+    for (slot, (mix, frame)) in monitors {
+        let x = MARGIN + ((slot % per_row) * cell_w) as i32;
+        let y = h as i32 - ((slot / per_row + 1) * cell_h) as i32;
+        let cell = Rect::new(x, y, MONITOR_WIDTH, MONITOR_HEIGHT);
+        canvas.set_draw_color(Color::RGB(32, 32, 32));
+        canvas.fill_rect(cell).map_err(|e| anyhow!(e))?;
+        if let Some((width, height, rgba)) = frame {
+            let mut texture =
+                texture_creator.create_texture_static(PixelFormatEnum::RGBA32, *width, *height)?;
+            texture.update(None, rgba, *width as usize * 4)?;
+            texture.set_blend_mode(BlendMode::None);
+            let dst = Rect::new(
+                x + (MONITOR_WIDTH - width) as i32 / 2,
+                y + (MONITOR_HEIGHT - height) as i32 / 2,
+                *width,
+                *height,
+            );
+            canvas.copy(&texture, None, dst).map_err(|e| anyhow!(e))?;
+        }
+        let label = format!("{} {}", slot, mix);
+        let label = font.render(&label).blended(Color::RGB(200, 200, 200))?;
+        let texture = texture_creator.create_texture_from_surface(&label)?;
+        // clipped to the box
+        let src = Rect::new(0, 0, label.width().min(MONITOR_WIDTH), label.height());
+        let dst = Rect::new(x, y + MONITOR_HEIGHT as i32, src.width(), src.height());
+        canvas.copy(&texture, src, dst).map_err(|e| anyhow!(e))?;
+    }
+    Ok(())
+}
+
+// One stacked line or strip of the HUD
+#[cfg(feature = "hud")]
+enum Piece {
+    Text(Surface<'static>),
+    Banner(Surface<'static>),
+    // label, value, and where the value column starts
+    Row(Surface<'static>, Surface<'static>, u32),
+    Bar(f64),
+    Graph(Vec<f64>),
+}
+
+#[cfg(feature = "hud")]
+impl Piece {
+    fn width(&self) -> u32 {
+        match self {
+            Piece::Text(s) | Piece::Banner(s) => s.width(),
+            Piece::Row(_, value, column) => column + value.width(),
+            Piece::Bar(_) | Piece::Graph(_) => BAR_WIDTH,
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            Piece::Text(s) => s.height(),
+            Piece::Banner(s) => s.height() + MARGIN as u32,
+            Piece::Row(label, value, _) => label.height().max(value.height()),
+            Piece::Bar(_) => BAR_HEIGHT + MARGIN as u32 / 2,
+            Piece::Graph(_) => GRAPH_HEIGHT + MARGIN as u32 / 2,
+        }
+    }
+}
+
+#[cfg(feature = "hud")]
+fn fraction(value: f64, min: f64, max: f64) -> f64 {
+    if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+// None when there's nothing to show
+#[cfg(feature = "hud")]
+fn render_panel(pieces: Vec<Piece>, background: Color) -> Result<Option<Surface<'static>>> {
+    if pieces.is_empty() {
+        return Ok(None);
+    }
+    let inner = pieces.iter().map(Piece::width).max().unwrap_or(0);
+    let w = inner + 2 * MARGIN as u32;
+    let h = pieces.iter().map(Piece::height).sum::<u32>() + 2 * MARGIN as u32;
+    let mut panel = Surface::new(w, h, PixelFormatEnum::RGBA32).map_err(|e| anyhow!(e))?;
+    panel.fill_rect(None, background).map_err(|e| anyhow!(e))?;
+    let mut y = MARGIN;
+    for piece in pieces {
+        let height = piece.height();
+        match &piece {
+            Piece::Text(text) => {
+                let dst = Rect::new(MARGIN, y, text.width(), text.height());
+                text.blit(None, &mut panel, dst).map_err(|e| anyhow!(e))?;
+            }
+            Piece::Banner(text) => {
+                let strip = Rect::new(0, y, w, height);
+                panel
+                    .fill_rect(strip, Color::RGB(200, 0, 0))
+                    .map_err(|e| anyhow!(e))?;
+                let dst = Rect::new(MARGIN, y + MARGIN / 2, text.width(), text.height());
+                text.blit(None, &mut panel, dst).map_err(|e| anyhow!(e))?;
+            }
+            Piece::Row(label, value, column) => {
+                let dst = Rect::new(MARGIN, y, label.width(), label.height());
+                label.blit(None, &mut panel, dst).map_err(|e| anyhow!(e))?;
+                let x = MARGIN + *column as i32;
+                let dst = Rect::new(x, y, value.width(), value.height());
+                value.blit(None, &mut panel, dst).map_err(|e| anyhow!(e))?;
+            }
+            Piece::Bar(fill) => {
+                let back = Rect::new(MARGIN, y, inner, BAR_HEIGHT);
+                panel
+                    .fill_rect(back, Color::RGB(48, 48, 48))
+                    .map_err(|e| anyhow!(e))?;
+                let filled = (inner as f64 * fill).round() as u32;
+                if filled > 0 {
+                    let bar = Rect::new(MARGIN, y, filled, BAR_HEIGHT);
+                    panel
+                        .fill_rect(bar, Color::RGB(96, 200, 96))
+                        .map_err(|e| anyhow!(e))?;
+                }
+            }
+            Piece::Graph(values) => {
+                let back = Rect::new(MARGIN, y, inner, GRAPH_HEIGHT);
+                panel
+                    .fill_rect(back, Color::RGB(32, 32, 32))
+                    .map_err(|e| anyhow!(e))?;
+                // newest on the right
+                let column = (inner / GRAPH_LEN as u32).max(1);
+                let right = MARGIN + inner as i32;
+                for (age, fill) in values.iter().rev().enumerate() {
+                    let x = right - (age as i32 + 1) * column as i32;
+                    if x < MARGIN {
+                        break;
+                    }
+                    let bar_h = (GRAPH_HEIGHT as f64 * fill).round() as u32;
+                    if bar_h > 0 {
+                        let bar = Rect::new(x, y + (GRAPH_HEIGHT - bar_h) as i32, column, bar_h);
+                        panel
+                            .fill_rect(bar, Color::RGB(160, 200, 255))
+                            .map_err(|e| anyhow!(e))?;
+                    }
+                }
+            }
+        }
+        y += height as i32;
+    }
+    Ok(Some(panel))
+}
+
+// Straight alpha rgba8, faded by opacity
+#[cfg(feature = "hud")]
+fn surface_rgba(surface: &Surface, opacity: f32) -> (u32, u32, Vec<u8>) {
+    let (w, h) = (surface.width(), surface.height());
+    let pitch = surface.pitch() as usize;
+    let mut rgba = Vec::with_capacity(w as usize * h as usize * 4);
+    surface.with_lock(|pixels| {
+        for row in pixels.chunks(pitch).take(h as usize) {
+            rgba.extend_from_slice(&row[..w as usize * 4]);
+        }
+    });
+    for alpha in rgba.iter_mut().skip(3).step_by(4) {
+        *alpha = (*alpha as f32 * opacity).round() as u8;
+    }
+    (w, h, rgba)
+}
@@ -1,18 +1,85 @@
 #[cfg(not(target_family = "wasm"))]
 pub mod appruntime;
 #[cfg(not(target_family = "wasm"))]
+pub mod assetcache;
+#[cfg(not(target_family = "wasm"))]
+pub mod audioinput;
+#[cfg(not(target_family = "wasm"))]
+pub mod audioruntime;
+#[cfg(not(target_family = "wasm"))]
+pub mod benchmark;
+#[cfg(not(target_family = "wasm"))]
+pub mod capture;
+#[cfg(not(target_family = "wasm"))]
+pub mod config;
+#[cfg(not(target_family = "wasm"))]
+pub mod controllers;
+#[cfg(not(target_family = "wasm"))]
+pub mod dylib;
+#[cfg(not(target_family = "wasm"))]
+pub mod fetch;
+#[cfg(all(not(target_family = "wasm"), feature = "fonts"))]
 pub mod fonts;
-pub mod gfxinfo;
 #[cfg(not(target_family = "wasm"))]
 pub mod gfxruntime;
 #[cfg(not(target_family = "wasm"))]
 pub mod glob;
-pub mod renderspec;
-#[cfg(target_family = "wasm")]
-pub mod spec_engine;
+#[cfg(all(not(target_family = "wasm"), feature = "fonts"))]
+pub mod glyphatlas;
+#[cfg(not(target_family = "wasm"))]
+pub mod hud;
+#[cfg(all(not(target_family = "wasm"), feature = "link"))]
+pub mod link;
+#[cfg(not(target_family = "wasm"))]
+pub mod lint;
+#[cfg(not(target_family = "wasm"))]
+pub mod logging;
+#[cfg(not(target_family = "wasm"))]
+pub mod manifest;
+#[cfg(not(target_family = "wasm"))]
+pub mod meshruntime;
+#[cfg(all(not(target_family = "wasm"), feature = "midi"))]
+pub mod midiclock;
+#[cfg(all(not(target_family = "wasm"), feature = "midi"))]
+pub mod midiports;
+#[cfg(all(not(target_family = "wasm"), feature = "ndi"))]
+pub mod ndi;
+#[cfg(all(not(target_family = "wasm"), feature = "osc"))]
+pub mod osc;
+#[cfg(not(target_family = "wasm"))]
+pub mod outputshare;
+#[cfg(not(target_family = "wasm"))]
+pub mod perf;
+#[cfg(not(target_family = "wasm"))]
+pub mod placement;
+#[cfg(not(target_family = "wasm"))]
+pub mod plugins;
+#[cfg(not(target_family = "wasm"))]
+pub mod pointer;
+#[cfg(not(target_family = "wasm"))]
+pub mod remote;
+#[cfg(all(not(target_family = "wasm"), feature = "recording"))]
+pub mod replay;
+#[cfg(not(target_family = "wasm"))]
+pub mod shapes;
+#[cfg(all(not(target_family = "wasm"), feature = "spout"))]
+pub mod spout;
+#[cfg(all(not(target_family = "wasm"), feature = "syphon"))]
+pub mod syphon;
+#[cfg(not(target_family = "wasm"))]
+pub mod tempo;
+#[cfg(not(target_family = "wasm"))]
+pub mod texblocks;
+#[cfg(not(target_family = "wasm"))]
+pub mod texruntime;
 #[cfg(not(target_family = "wasm"))]
 pub mod vidruntime;
-pub use adjustable::Adjustable;
+#[cfg(not(target_family = "wasm"))]
+pub mod watch;
+#[cfg(not(target_family = "wasm"))]
+pub mod websocket;
+// the guest api, under the paths it had before it was split out
+pub use sdlrig_guest::*;
 #[cfg(not(target_family = "wasm"))]
 pub mod gfx_lowlevel;
 pub mod shaderhelper;
@@ -0,0 +1,37 @@
+use crate::gfxinfo::LinkEvent;
+use rusty_link::{AblLink, SessionState};
+
+// A member of the Ableton Link session on the local network. Only reads the
+// session, the rig follows the tempo rather than setting it.
+pub struct Link {
+    link: AblLink,
+    state: SessionState,
+    quantum: u32,
+}
+
+impl Link {
+    pub fn new(bpm: f64, quantum: u32) -> Self {
+        let link = AblLink::new(bpm);
+        link.enable_start_stop_sync(true);
+        link.enable(true);
+        Self {
+            link,
+            state: SessionState::new(),
+            quantum: quantum.max(1),
+        }
+    }
+
+    // The session as of now
+    pub fn capture(&mut self) -> LinkEvent {
+        self.link.capture_app_session_state(&mut self.state);
+        let now = self.link.clock_micros();
+        let beat = self.state.beat_at_time(now, self.quantum as f64);
+        LinkEvent {
+            peers: self.link.num_peers() as u32,
+            playing: self.state.is_playing(),
+            millibpm: (self.state.tempo() * 1000.0).round() as u32,
+            microbeats: (beat * 1_000_000.0).round() as i64,
+            quantum: self.quantum,
+        }
+    }
+}
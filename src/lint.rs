@@ -0,0 +1,108 @@
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Which part of a --dry-run found the problem
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    // instantiating the wasm and getting its asset list
+    Load,
+    // an asset that couldn't be loaded, up front or requested at runtime
+    Asset,
+    // opening a video or compiling a mixer's shader
+    Prepare,
+    Calculate,
+    // a spec that refers to something missing or failed to apply
+    Spec,
+    // the app's own warnings and errors
+    Log,
+    // an asset that's missing or changed from what --manifest lists
+    Manifest,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    // none for anything found before the first calculate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<i64>,
+    // the asset, mixer or spec index it's about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub message: String,
+}
+
+// What --dry-run prints, ok is false when there was any error
+#[derive(Serialize, Clone, Debug)]
+pub struct LintReport {
+    pub wasm: String,
+    pub frames: i64,
+    pub ok: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LintReport {
+    pub fn new(wasm: &str, frames: i64) -> Self {
+        Self {
+            wasm: wasm.into(),
+            frames,
+            ok: true,
+            errors: 0,
+            warnings: 0,
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn error(
+        &mut self,
+        stage: Stage,
+        frame: Option<i64>,
+        subject: Option<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(Severity::Error, stage, frame, subject, message.into());
+    }
+
+    pub fn warning(
+        &mut self,
+        stage: Stage,
+        frame: Option<i64>,
+        subject: Option<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(Severity::Warning, stage, frame, subject, message.into());
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        stage: Stage,
+        frame: Option<i64>,
+        subject: Option<String>,
+        message: String,
+    ) {
+        match severity {
+            Severity::Error => {
+                self.errors += 1;
+                self.ok = false;
+            }
+            Severity::Warning => self.warnings += 1,
+        }
+        self.diagnostics.push(Diagnostic {
+            severity,
+            stage,
+            frame,
+            subject,
+            message,
+        });
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+
+// Warnings and errors kept for the HUD until they're taken
+const TAIL_LEN: usize = 32;
+
+// --log-level, a default level then module=level pairs, separated by commas
+// like "info,sdlrig::vidruntime=debug". The longest matching module wins and
+// off silences a module.
+#[derive(Clone, Debug)]
+pub struct LogFilter {
+    default: Option<Level>,
+    modules: Vec<(String, Option<Level>)>,
+}
+
+impl FromStr for LogFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let level = |s: &str| match s.trim() {
+            "off" => Ok(None),
+            level => Level::from_str(level)
+                .map(Some)
+                .map_err(|_| anyhow!("Unknown log level {}", level)),
+        };
+        let mut filter = Self {
+            default: Some(Level::INFO),
+            modules: vec![],
+        };
+        for part in s.split(',').filter(|part| !part.trim().is_empty()) {
+            match part.split_once('=') {
+                Some((module, _)) if module.trim().is_empty() => {
+                    bail!("Missing a module in {}", part)
+                }
+                Some((module, lvl)) => filter.modules.push((module.trim().into(), level(lvl)?)),
+                None => filter.default = level(part)?,
+            }
+        }
+        filter
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(filter)
+    }
+}
+
+impl LogFilter {
+    pub fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        let level = self
+            .modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .map_or(false, |rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level);
+        level.map_or(false, |level| *metadata.level() <= level)
+    }
+}
+
+// --log-json, one object per line, moved to path.1, path.2 and so on once it
+// grows past max_bytes
+struct JsonFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl JsonFile {
+    fn open(path: PathBuf, max_bytes: u64, keep: usize) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> Result<()> {
+        if self.max_bytes > 0 && self.written + line.len() as u64 >= self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.keep == 0 {
+            fs::remove_file(&self.path).ok();
+        } else {
+            fs::remove_file(numbered(self.keep)).ok();
+            for n in (1..self.keep).rev() {
+                fs::rename(numbered(n), numbered(n + 1)).ok();
+            }
+            fs::rename(&self.path, numbered(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+// The host's tracing subscriber. Everything enabled goes to stderr, and the
+// json file when there is one, and warnings and errors are kept for the HUD.
+pub struct Logger {
+    filter: RwLock<LogFilter>,
+    json: Option<Mutex<JsonFile>>,
+    tail: Mutex<VecDeque<String>>,
+    next_span: AtomicU64,
+}
+
+impl Logger {
+    // Becomes the global subscriber, there can only be the one
+    pub fn install(
+        filter: LogFilter,
+        json: Option<PathBuf>,
+        max_bytes: u64,
+        keep: usize,
+    ) -> Result<Arc<Self>> {
+        let json = match json {
+            Some(path) => Some(Mutex::new(JsonFile::open(path, max_bytes, keep)?)),
+            None => None,
+        };
+        let logger = Arc::new(Self {
+            filter: RwLock::new(filter),
+            json,
+            tail: Mutex::new(VecDeque::with_capacity(TAIL_LEN)),
+            next_span: AtomicU64::new(1),
+        });
+        tracing::subscriber::set_global_default(logger.clone())?;
+        Ok(logger)
+    }
+
+    // From a reloaded --log-level
+    pub fn set_filter(&self, filter: LogFilter) {
+        *self.filter.write().unwrap() = filter;
+    }
+
+    // Warnings and errors since the last take, oldest first
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.tail.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: Map<String, Value>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.into(),
+            name => {
+                self.rest.insert(name.into(), value.into());
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => {
+                self.rest.insert(name.into(), format!("{:?}", value).into());
+            }
+        }
+    }
+}
+
+impl Subscriber for Logger {
+    // the filter can be reloaded, so every callsite is asked each time
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.read().unwrap().enabled(metadata)
+    }
+
+    // spans aren't used, they only need telling apart
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let mut line = format!(
+            "{} {}: {}",
+            metadata.level(),
+            metadata.target(),
+            fields.message
+        );
+        for (name, value) in &fields.rest {
+            match value {
+                Value::String(s) => write!(line, " {}={}", name, s),
+                value => write!(line, " {}={}", name, value),
+            }
+            .ok();
+        }
+        eprintln!("{}", line);
+
+        // the app's own log reaches the HUD through --hud-log-level
+        if *metadata.level() <= Level::WARN && metadata.target() != "app" {
+            let mut tail = self.tail.lock().unwrap();
+            if tail.len() == TAIL_LEN {
+                tail.pop_front();
+            }
+            tail.push_back(fields.message.clone());
+        }
+
+        if let Some(json) = self.json.as_ref() {
+            let object = json!({
+                "time": Local::now().to_rfc3339(),
+                "level": metadata.level().as_str(),
+                "target": metadata.target(),
+                "message": fields.message,
+                "fields": fields.rest,
+            });
+            if let Err(e) = json.lock().unwrap().write(&object.to_string()) {
+                // not logged, that would come straight back here
+                eprintln!("Could not write the json log: {}", e);
+            }
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
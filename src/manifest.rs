@@ -0,0 +1,172 @@
+use crate::assetcache::sha256_file;
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+lazy_static! {
+    static ref MANIFEST: RwLock<Option<Arc<Manifest>>> = RwLock::new(None);
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    Missing,
+    Mismatch,
+    Unreadable,
+}
+
+// An asset that isn't what the manifest says it should be
+#[derive(Serialize, Clone, Debug)]
+pub struct Discrepancy {
+    // as the manifest lists it
+    pub path: String,
+    pub problem: Problem,
+    pub expected: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    pub message: String,
+}
+
+struct Entry {
+    listed: String,
+    path: PathBuf,
+    sha256: String,
+}
+
+// viz --manifest, the assets a set expects in sha256sum's format, a hex hash
+// then the path relative to the manifest on each line
+pub struct Manifest {
+    source: PathBuf,
+    entries: Vec<Entry>,
+    // from the last verify, none until then
+    checked: Mutex<Option<Vec<Discrepancy>>>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Could not read manifest {}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut entries = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((sha256, listed)) = line.split_once(char::is_whitespace) else {
+                bail!("{}:{} needs a sha256 and a path", path.display(), n + 1);
+            };
+            if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("{}:{} has a bad sha256 {}", path.display(), n + 1, sha256);
+            }
+            // sha256sum marks binary mode with a * before the path
+            let listed = listed.trim_start().trim_start_matches('*');
+            entries.push(Entry {
+                listed: listed.into(),
+                path: absolute(&dir.join(listed)),
+                sha256: sha256.to_ascii_lowercase(),
+            });
+        }
+        Ok(Self {
+            source: path.into(),
+            entries,
+            checked: Mutex::new(None),
+        })
+    }
+
+    // Hashes every listed file, which can take a while for a media library
+    pub fn verify(&self) -> Vec<Discrepancy> {
+        let mut found = vec![];
+        for entry in &self.entries {
+            let discrepancy = |problem, actual: Option<String>, message: String| Discrepancy {
+                path: entry.listed.clone(),
+                problem,
+                expected: entry.sha256.clone(),
+                actual,
+                message,
+            };
+            if !entry.path.is_file() {
+                found.push(discrepancy(
+                    Problem::Missing,
+                    None,
+                    format!("{} is missing", entry.listed),
+                ));
+                continue;
+            }
+            match sha256_file(&entry.path) {
+                Ok(actual) if actual == entry.sha256 => (),
+                Ok(actual) => found.push(discrepancy(
+                    Problem::Mismatch,
+                    Some(actual),
+                    format!("{} doesn't match its sha256", entry.listed),
+                )),
+                Err(e) => found.push(discrepancy(
+                    Problem::Unreadable,
+                    None,
+                    format!("{} can't be read: {}", entry.listed, e),
+                )),
+            }
+        }
+        *self.checked.lock().unwrap() = Some(found.clone());
+        found
+    }
+
+    // Why an asset at path didn't load, as far as the manifest can tell
+    pub fn explain(&self, path: &str) -> Option<String> {
+        let path = absolute(Path::new(path));
+        let entry = self.entries.iter().find(|entry| entry.path == path)?;
+        if !entry.path.is_file() {
+            return Some(format!(
+                "{} is missing, {} expects it",
+                entry.listed,
+                self.source.display()
+            ));
+        }
+        self.checked
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .find(|d| d.path == entry.listed)
+            .map(|d| format!("{} in {}", d.message, self.source.display()))
+    }
+}
+
+// Used to explain failed loads from then on
+pub fn install(manifest: Manifest) -> Arc<Manifest> {
+    let manifest = Arc::new(manifest);
+    *MANIFEST.write().unwrap() = Some(manifest.clone());
+    manifest
+}
+
+pub fn installed() -> Option<Arc<Manifest>> {
+    MANIFEST.read().unwrap().clone()
+}
+
+pub fn explain(path: &str) -> Option<String> {
+    installed().and_then(|manifest| manifest.explain(path))
+}
+
+// Against the working directory, with . and .. taken out without touching
+// the filesystem so missing files compare too
+fn absolute(path: &Path) -> PathBuf {
+    let joined = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir().unwrap_or_default().join(path),
+    };
+    let mut out = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                out.pop();
+            }
+            component => out.push(component),
+        }
+    }
+    out
+}
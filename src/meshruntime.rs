@@ -0,0 +1,434 @@
+use crate::gfxinfo::{Mesh, MeshInfo};
+use anyhow::{anyhow, bail, Result};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// x, y, z, u, v
+pub const VERTEX_FLOATS: usize = 5;
+
+// A Mesh asset as a triangle list, read when it loads
+pub struct MeshData {
+    pub info: MeshInfo,
+    pub vertices: Vec<f32>,
+}
+
+impl std::fmt::Debug for MeshData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MeshData")
+            .field("name", &self.info.name)
+            .field("triangles", &self.info.triangles)
+            .finish()
+    }
+}
+
+impl MeshData {
+    pub fn load(mesh: &Mesh) -> Result<Self> {
+        let ext = Path::new(&mesh.path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let vertices = match ext.as_deref() {
+            Some("obj") => read_obj(&mesh.path),
+            Some("gltf" | "glb") => read_gltf(&mesh.path),
+            _ => bail!("{} isn't an obj, gltf or glb file", mesh.path),
+        }
+        .map_err(|e| anyhow!("Could not read mesh {}: {}", mesh.path, e))?;
+        if vertices.is_empty() {
+            bail!("{} has no triangles", mesh.path);
+        }
+        Ok(Self {
+            info: MeshInfo {
+                name: mesh.name.clone(),
+                path: mesh.path.clone(),
+                triangles: vertices.len() / (VERTEX_FLOATS * 3),
+                load: mesh.load.clone(),
+            },
+            vertices,
+        })
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / VERTEX_FLOATS
+    }
+}
+
+// Faces with more than three corners are fanned into triangles
+fn read_obj(path: &str) -> Result<Vec<f32>> {
+    let text = fs::read_to_string(path)?;
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut uvs: Vec<[f32; 2]> = vec![];
+    let mut vertices = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let mut parts = line.split_whitespace();
+        let floats = |parts: std::str::SplitWhitespace| {
+            parts
+                .map(|p| p.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("line {}: {}", n + 1, e))
+        };
+        match parts.next() {
+            Some("v") => match floats(parts)?[..] {
+                [x, y, z, ..] => positions.push([x, y, z]),
+                _ => bail!("line {}: a vertex needs x y z", n + 1),
+            },
+            // obj has v going up, textures are sampled top down
+            Some("vt") => match floats(parts)?[..] {
+                [u, v, ..] => uvs.push([u, 1.0 - v]),
+                [u] => uvs.push([u, 1.0]),
+                _ => bail!("line {}: a texture coordinate needs u", n + 1),
+            },
+            Some("f") => {
+                let mut corners = vec![];
+                for corner in parts {
+                    let mut indices = corner.split('/');
+                    let position = obj_index(indices.next(), positions.len(), n)?
+                        .ok_or_else(|| anyhow!("line {}: a face needs positions", n + 1))?;
+                    let uv = obj_index(indices.next(), uvs.len(), n)?;
+                    corners.push((position, uv));
+                }
+                if corners.len() < 3 {
+                    bail!("line {}: a face needs three corners", n + 1);
+                }
+                for i in 1..corners.len() - 1 {
+                    for (position, uv) in [corners[0], corners[i], corners[i + 1]] {
+                        vertices.extend(positions[position]);
+                        vertices.extend(uv.map_or([0.0, 0.0], |uv| uvs[uv]));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(vertices)
+}
+
+// 1 based, negative counts back from the last one read
+fn obj_index(index: Option<&str>, len: usize, n: usize) -> Result<Option<usize>> {
+    let Some(index) = index.filter(|index| !index.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = index
+        .parse()
+        .map_err(|e| anyhow!("line {}: {}", n + 1, e))?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => len as i64 + i,
+        _ => bail!("line {}: indices start at 1", n + 1),
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        bail!("line {}: index {} is out of range", n + 1, index);
+    }
+    Ok(Some(resolved as usize))
+}
+
+// Every triangle primitive of every mesh, node transforms aren't applied
+fn read_gltf(path: &str) -> Result<Vec<f32>> {
+    let bytes = fs::read(path)?;
+    let (json, bin) = match bytes.starts_with(b"glTF") {
+        true => split_glb(&bytes)?,
+        false => (bytes.as_slice(), None),
+    };
+    let gltf: Value = serde_json::from_slice(json)?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let buffers = gltf["buffers"]
+        .as_array()
+        .map_or(&[][..], |buffers| buffers.as_slice())
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| match buffer["uri"].as_str() {
+            Some(uri) => match uri.strip_prefix("data:") {
+                Some(data) => data
+                    .split_once(";base64,")
+                    .ok_or_else(|| anyhow!("buffer {} isn't base64", i))
+                    .and_then(|(_, encoded)| base64_decode(encoded)),
+                None => fs::read(dir.join(uri)).map_err(|e| anyhow!("{}: {}", uri, e)),
+            },
+            None => bin
+                .map(|bin| bin.to_vec())
+                .ok_or_else(|| anyhow!("buffer {} has no uri or glb chunk", i)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut vertices = vec![];
+    for mesh in gltf["meshes"].as_array().into_iter().flatten() {
+        for primitive in mesh["primitives"].as_array().into_iter().flatten() {
+            // 4 is triangles, the default
+            if primitive["mode"].as_u64().unwrap_or(4) != 4 {
+                continue;
+            }
+            let attributes = &primitive["attributes"];
+            let Some(position) = attributes["POSITION"].as_u64() else {
+                continue;
+            };
+            let positions = accessor(&gltf, &buffers, position as usize, 3)?;
+            let count = positions.len() / 3;
+            let uvs = match attributes["TEXCOORD_0"].as_u64() {
+                Some(uv) => accessor(&gltf, &buffers, uv as usize, 2)?,
+                None => vec![0.0; count * 2],
+            };
+            if uvs.len() / 2 != count {
+                bail!("TEXCOORD_0 and POSITION counts differ");
+            }
+            let indices = match primitive["indices"].as_u64() {
+                Some(indices) => accessor(&gltf, &buffers, indices as usize, 1)?
+                    .into_iter()
+                    .map(|i| i as usize)
+                    .collect(),
+                None => (0..count).collect::<Vec<_>>(),
+            };
+            for i in indices {
+                if i >= count {
+                    bail!("index {} is out of range", i);
+                }
+                vertices.extend(positions[i * 3..i * 3 + 3].iter().map(|&p| p as f32));
+                vertices.extend(uvs[i * 2..i * 2 + 2].iter().map(|&uv| uv as f32));
+            }
+        }
+    }
+    Ok(vertices)
+}
+
+// The json chunk and the binary one if there is one
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let u32_at = |at: usize| -> Result<usize> {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| anyhow!("glb is truncated"))
+    };
+    let mut chunks = vec![];
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let len = u32_at(at)?;
+        let kind = &bytes[at + 4..at + 8];
+        let data = bytes
+            .get(at + 8..at + 8 + len)
+            .ok_or_else(|| anyhow!("glb is truncated"))?;
+        chunks.push((kind, data));
+        at += 8 + len;
+    }
+    let json = chunks
+        .iter()
+        .find(|(kind, _)| *kind == b"JSON")
+        .map(|(_, data)| *data)
+        .ok_or_else(|| anyhow!("glb has no json chunk"))?;
+    let bin = chunks
+        .iter()
+        .find(|(kind, _)| *kind == b"BIN\0")
+        .map(|(_, data)| *data);
+    Ok((json, bin))
+}
+
+// Components as doubles so u32 indices come through exactly, integer ones
+// normalized where the accessor says so
+fn accessor(gltf: &Value, buffers: &[Vec<u8>], index: usize, width: usize) -> Result<Vec<f64>> {
+    let accessor = &gltf["accessors"][index];
+    let count = accessor["count"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("accessor {} has no count", index))? as usize;
+    let components = match accessor["type"].as_str() {
+        Some("SCALAR") => 1,
+        Some("VEC2") => 2,
+        Some("VEC3") => 3,
+        other => bail!(
+            "accessor {} is a {:?}, not a scalar or vector",
+            index,
+            other
+        ),
+    };
+    if components != width {
+        bail!(
+            "accessor {} has {} components, {} wanted",
+            index,
+            components,
+            width
+        );
+    }
+    let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+    let (size, read): (usize, fn(&[u8]) -> f64) = match accessor["componentType"].as_u64() {
+        Some(5121) => (1, |b| b[0] as f64),
+        Some(5123) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f64),
+        Some(5125) => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+        Some(5126) => (4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+        other => bail!(
+            "accessor {} has unsupported component type {:?}",
+            index,
+            other
+        ),
+    };
+    let scale = match (normalized, size) {
+        (true, 1) => 1.0 / u8::MAX as f64,
+        (true, 2) => 1.0 / u16::MAX as f64,
+        _ => 1.0,
+    };
+
+    let view = &gltf["bufferViews"][accessor["bufferView"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("accessor {} has no buffer view", index))?
+        as usize];
+    let buffer = buffers
+        .get(view["buffer"].as_u64().unwrap_or(0) as usize)
+        .ok_or_else(|| anyhow!("accessor {} has no buffer", index))?;
+    let start = (view["byteOffset"].as_u64().unwrap_or(0) as usize)
+        .checked_add(accessor["byteOffset"].as_u64().unwrap_or(0) as usize)
+        .ok_or_else(|| anyhow!("accessor {} starts past its buffer", index))?;
+    let stride = view["byteStride"]
+        .as_u64()
+        .map_or(size * components, |stride| stride as usize);
+
+    if stride < size * components {
+        bail!(
+            "accessor {} has a {} byte stride, shorter than its elements",
+            index,
+            stride
+        );
+    }
+    // the count is the file's word, it has to fit the buffer before anything
+    // is allocated for it
+    let end = match count {
+        0 => Some(start),
+        _ => (count - 1)
+            .checked_mul(stride)
+            .and_then(|last| last.checked_add(start))
+            .and_then(|last| last.checked_add(size * components)),
+    };
+    if end.map_or(true, |end| end > buffer.len()) {
+        bail!("accessor {} runs past its buffer", index);
+    }
+
+    let mut out = Vec::with_capacity(count * components);
+    for i in 0..count {
+        for c in 0..components {
+            let at = start + i * stride + c * size;
+            let bytes = buffer
+                .get(at..at + size)
+                .ok_or_else(|| anyhow!("accessor {} runs past its buffer", index))?;
+            out.push(read(bytes) * scale);
+        }
+    }
+    Ok(out)
+}
+
+// Data uris may be wrapped or leave the padding off
+const DATA_URI: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+fn base64_decode(encoded: &str) -> Result<Vec<u8>> {
+    let encoded: String = encoded
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    DATA_URI
+        .decode(encoded)
+        .map_err(|e| anyhow!("Bad base64: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn gltf(accessor: Value, view: Value) -> Value {
+        json!({ "accessors": [accessor], "bufferViews": [view] })
+    }
+
+    #[test]
+    fn tight_floats() {
+        let buffer = floats(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let gltf = gltf(
+            json!({"bufferView": 0, "count": 2, "type": "VEC3", "componentType": 5126}),
+            json!({"buffer": 0}),
+        );
+        let out = accessor(&gltf, &[buffer], 0, 3).unwrap();
+        assert_eq!(out, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert!(accessor(&gltf, &[floats(&[0.0; 6])], 0, 2).is_err());
+    }
+
+    #[test]
+    fn interleaved() {
+        // x, y, z, u, v per vertex, the accessor reads the uvs
+        let buffer = floats(&[0.0, 0.0, 0.0, 0.25, 0.5, 1.0, 1.0, 1.0, 0.75, 1.0]);
+        let gltf = gltf(
+            json!({"bufferView": 0, "byteOffset": 12, "count": 2, "type": "VEC2", "componentType": 5126}),
+            json!({"buffer": 0, "byteStride": 20}),
+        );
+        let out = accessor(&gltf, &[buffer], 0, 2).unwrap();
+        assert_eq!(out, [0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn normalized_bytes() {
+        let gltf = gltf(
+            json!({"bufferView": 0, "count": 1, "type": "VEC2", "componentType": 5121, "normalized": true}),
+            json!({"buffer": 0, "byteOffset": 1}),
+        );
+        let out = accessor(&gltf, &[vec![9, 0, 255]], 0, 2).unwrap();
+        assert_eq!(out, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let buffer = floats(&[0.0; 6]);
+        let run = |accessor_json: Value, view: Value| {
+            accessor(&gltf(accessor_json, view), &[buffer.clone()], 0, 3)
+        };
+        let vec3 = |count: u64| json!({"bufferView": 0, "count": count, "type": "VEC3", "componentType": 5126});
+        assert!(run(vec3(2), json!({"buffer": 0})).is_ok());
+        assert!(run(vec3(3), json!({"buffer": 0})).is_err());
+        assert!(run(vec3(u64::MAX), json!({"buffer": 0})).is_err());
+        assert!(run(vec3(1), json!({"buffer": 0, "byteOffset": 13})).is_err());
+        assert!(run(vec3(1), json!({"buffer": 0, "byteOffset": u64::MAX})).is_err());
+        assert!(run(vec3(2), json!({"buffer": 0, "byteStride": 4})).is_err());
+        assert!(run(vec3(1), json!({"buffer": 1})).is_err());
+        assert!(run(vec3(0), json!({"buffer": 0})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn glb_chunks() {
+        let mut glb = b"glTF\x02\0\0\0\0\0\0\0".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(b"JSON{}");
+        glb.extend_from_slice(&3u32.to_le_bytes());
+        glb.extend_from_slice(b"BIN\0abc");
+        let (json, bin) = split_glb(&glb).unwrap();
+        assert_eq!(json, b"{}");
+        assert_eq!(bin, Some(&b"abc"[..]));
+
+        assert!(split_glb(&glb[..glb.len() - 1]).is_err());
+        assert!(split_glb(&glb[..12]).is_err());
+        let mut huge = glb[..12].to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(b"JSON{}");
+        assert!(split_glb(&huge).is_err());
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(base64_decode("aGVs\nbG8").unwrap(), b"hello");
+        assert_eq!(base64_decode("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(base64_decode("+/8").unwrap(), [0xfb, 0xff]);
+        assert!(base64_decode("aGV*").is_err());
+    }
+
+    #[test]
+    fn obj_indices() {
+        assert_eq!(obj_index(Some("1"), 3, 0).unwrap(), Some(0));
+        assert_eq!(obj_index(Some("-1"), 3, 0).unwrap(), Some(2));
+        assert_eq!(obj_index(Some(""), 3, 0).unwrap(), None);
+        assert_eq!(obj_index(None, 3, 0).unwrap(), None);
+        assert!(obj_index(Some("0"), 3, 0).is_err());
+        assert!(obj_index(Some("4"), 3, 0).is_err());
+        assert!(obj_index(Some("-4"), 3, 0).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::gfxinfo::{ClockEvent, GfxEvent, TimecodeEvent, MIDI_CLOCK_PPQN};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION: u8 = 0xF2;
+const QUARTER_FRAME: u8 = 0xF1;
+const SYSEX: u8 = 0xF0;
+// Tempo is averaged over a beat's worth of pulses
+const TEMPO_WINDOW: usize = MIDI_CLOCK_PPQN as usize;
+
+// Follows the clock and timecode on one midi input. Fed from the midi
+// callback, read once a frame.
+#[derive(Default)]
+pub struct MidiClock {
+    playing: bool,
+    pulses: u64,
+    // midir timestamps in microseconds of the latest pulses
+    stamps: VecDeque<u64>,
+    last_pulse: Option<Instant>,
+    quarter_frames: [u8; 8],
+    timecode: Option<TimecodeEvent>,
+    sent: Option<ClockEvent>,
+}
+
+impl MidiClock {
+    // True when message was clock or timecode, which isn't passed on as a MidiEvent
+    pub fn handle(&mut self, stamp: u64, message: &[u8]) -> bool {
+        match message {
+            [CLOCK, ..] => {
+                if self.stamps.len() == TEMPO_WINDOW + 1 {
+                    self.stamps.pop_front();
+                }
+                self.stamps.push_back(stamp);
+                if self.playing {
+                    self.pulses += 1;
+                }
+                self.last_pulse = Some(Instant::now());
+            }
+            [START, ..] => {
+                self.playing = true;
+                self.pulses = 0;
+            }
+            [CONTINUE, ..] => self.playing = true,
+            [STOP, ..] => self.playing = false,
+            // in sixteenths, six pulses each
+            [SONG_POSITION, lsb, msb, ..] => {
+                self.pulses = ((*msb as u64) << 7 | *lsb as u64) * 6;
+            }
+            [QUARTER_FRAME, data, ..] => {
+                let piece = (data >> 4) as usize & 7;
+                self.quarter_frames[piece] = data & 0xF;
+                // the last piece completes the time
+                if piece == 7 {
+                    let q = &self.quarter_frames;
+                    self.timecode = Some(TimecodeEvent {
+                        device: String::new(),
+                        frames: q[0] | (q[1] & 1) << 4,
+                        seconds: q[2] | (q[3] & 3) << 4,
+                        minutes: q[4] | (q[5] & 3) << 4,
+                        hours: q[6] | (q[7] & 1) << 4,
+                        rate: (q[7] >> 1) & 3,
+                    });
+                }
+            }
+            // full frame: F0 7F <device> 01 01 hh mm ss ff F7
+            [SYSEX, 0x7F, _, 0x01, 0x01, hh, mm, ss, ff, ..] => {
+                self.timecode = Some(TimecodeEvent {
+                    device: String::new(),
+                    hours: hh & 0x1F,
+                    minutes: *mm,
+                    seconds: *ss,
+                    frames: *ff,
+                    rate: (hh >> 5) & 3,
+                });
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn bpm(&self) -> Option<f64> {
+        let (first, last) = (self.stamps.front()?, self.stamps.back()?);
+        let pulses = self.stamps.len() as u64 - 1;
+        if pulses == 0 || last <= first {
+            return None;
+        }
+        let us_per_pulse = (last - first) as f64 / pulses as f64;
+        Some(60_000_000.0 / (us_per_pulse * MIDI_CLOCK_PPQN as f64))
+    }
+
+    // Beats since start, moving smoothly between pulses but never past the next
+    pub fn beat(&self) -> f64 {
+        let pulses = self.pulses as f64;
+        let between = match (self.playing, self.last_pulse, self.bpm()) {
+            (true, Some(last_pulse), Some(bpm)) => {
+                let pulse_secs = 60.0 / (bpm * MIDI_CLOCK_PPQN as f64);
+                (last_pulse.elapsed().as_secs_f64() / pulse_secs).min(1.0)
+            }
+            _ => 0.0,
+        };
+        (pulses + between) / MIDI_CLOCK_PPQN as f64
+    }
+
+    // What changed since the last call
+    pub fn take_events(&mut self, device: &str) -> Vec<GfxEvent> {
+        let mut events = vec![];
+        let clock = ClockEvent {
+            device: device.into(),
+            playing: self.playing,
+            pulses: self.pulses,
+            // to a tenth of a bpm so jitter doesn't send an event every frame
+            millibpm: self
+                .bpm()
+                .map_or(0, |bpm| (bpm * 10.0).round() as u32 * 100),
+        };
+        if self.sent.as_ref() != Some(&clock) {
+            self.sent = Some(clock.clone());
+            events.push(GfxEvent::ClockEvent(clock));
+        }
+        if let Some(mut timecode) = self.timecode.take() {
+            timecode.device = device.into();
+            events.push(GfxEvent::TimecodeEvent(timecode));
+        }
+        events
+    }
+}
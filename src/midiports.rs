@@ -0,0 +1,240 @@
+use crate::gfxinfo::{DeviceEvent, DeviceKind, GfxEvent, MidiEvent};
+use crate::midiclock::MidiClock;
+use anyhow::{anyhow, Result};
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// How often ports are looked for, midir can't tell us when they change
+const RESCAN: Duration = Duration::from_secs(2);
+
+pub type MidiOutputs = Arc<Mutex<HashMap<String, MidiOutputConnection>>>;
+// clock and timecode are parsed in the midi callbacks, per device
+pub type MidiClocks = Arc<Mutex<HashMap<String, MidiClock>>>;
+
+// The midi inputs and outputs asked for by name, connected whenever a port
+// with that name is present so controllers can be plugged in at any time
+pub struct MidiPorts {
+    wanted_inputs: Vec<String>,
+    wanted_outputs: Vec<String>,
+    inputs: HashMap<String, MidiInputConnection<()>>,
+    outputs: MidiOutputs,
+    clocks: MidiClocks,
+    events: Sender<MidiEvent>,
+    last_scan: Option<Instant>,
+}
+
+impl MidiPorts {
+    pub fn new(
+        wanted_inputs: Vec<String>,
+        wanted_outputs: Vec<String>,
+        events: Sender<MidiEvent>,
+    ) -> Self {
+        if wanted_inputs.is_empty() {
+            info!("Not listening for midi");
+        }
+        Self {
+            wanted_inputs,
+            wanted_outputs,
+            inputs: HashMap::new(),
+            outputs: Arc::new(Mutex::new(HashMap::new())),
+            clocks: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            last_scan: None,
+        }
+    }
+
+    // Logs the ports there are to pick from
+    pub fn list() -> Result<()> {
+        let midi_in = MidiInput::new("sdlrig-midi-probe")?;
+        info!("Available midi ports:");
+        for (i, p) in midi_in.ports().iter().enumerate() {
+            info!("{}: {}", i, midi_in.port_name(p)?);
+        }
+        let midi_out = MidiOutput::new("sdlrig-midi-output-probe")?;
+        info!("Available midi output ports:");
+        for (i, p) in midi_out.ports().iter().enumerate() {
+            info!("{}: {}", i, midi_out.port_name(p)?);
+        }
+        Ok(())
+    }
+
+    pub fn outputs(&self) -> MidiOutputs {
+        self.outputs.clone()
+    }
+
+    pub fn clocks(&self) -> MidiClocks {
+        self.clocks.clone()
+    }
+
+    // Connects wanted ports that appeared and drops ones that went away, at
+    // most once every RESCAN
+    pub fn rescan(&mut self) -> Vec<GfxEvent> {
+        let first = self.last_scan.is_none();
+        if self.last_scan.map_or(false, |at| at.elapsed() < RESCAN) {
+            return vec![];
+        }
+        self.last_scan = Some(Instant::now());
+        let mut events = vec![];
+        if let Err(e) = self.scan_inputs(&mut events) {
+            warn!("Could not scan midi inputs: {}", e);
+        }
+        if let Err(e) = self.scan_outputs(&mut events) {
+            warn!("Could not scan midi outputs: {}", e);
+        }
+        if first {
+            let outputs = self.outputs.lock().unwrap();
+            let missing = self
+                .wanted_inputs
+                .iter()
+                .filter(|name| !self.inputs.contains_key(*name))
+                .chain(
+                    self.wanted_outputs
+                        .iter()
+                        .filter(|name| !outputs.contains_key(*name)),
+                );
+            for name in missing {
+                warn!("Midi device {} not found, waiting for it", name);
+            }
+        }
+        events
+    }
+
+    fn scan_inputs(&mut self, events: &mut Vec<GfxEvent>) -> Result<()> {
+        let probe = MidiInput::new("sdlrig-midi-probe")?;
+        let present = probe
+            .ports()
+            .iter()
+            .filter_map(|p| probe.port_name(p).ok())
+            .collect::<HashSet<_>>();
+        let gone = self
+            .inputs
+            .keys()
+            .filter(|name| !present.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in gone {
+            // dropping the connection closes it
+            self.inputs.remove(&name);
+            self.clocks.lock().unwrap().remove(&name);
+            info!("Midi input {} disconnected", name);
+            events.push(GfxEvent::DeviceDisconnected(DeviceEvent {
+                name,
+                kind: DeviceKind::MidiInput,
+            }));
+        }
+        for name in &self.wanted_inputs {
+            if self.inputs.contains_key(name) || !present.contains(name) {
+                continue;
+            }
+            match self.connect_input(name) {
+                Ok(conn) => {
+                    info!("Opening midi port {}", name);
+                    self.inputs.insert(name.clone(), conn);
+                    events.push(GfxEvent::DeviceConnected(DeviceEvent {
+                        name: name.clone(),
+                        kind: DeviceKind::MidiInput,
+                    }));
+                }
+                Err(e) => warn!("Could not open midi port {}: {}", name, e),
+            }
+        }
+        Ok(())
+    }
+
+    fn connect_input(&self, name: &str) -> Result<MidiInputConnection<()>> {
+        let mut midi_in = MidiInput::new(name)?;
+        midi_in.ignore(Ignore::None);
+        let port = midi_in
+            .ports()
+            .into_iter()
+            .find(|p| midi_in.port_name(p).map_or(false, |n| n == name))
+            .ok_or_else(|| anyhow!("Invalid midi port"))?;
+        let device = name.to_string();
+        let midi_tx = self.events.clone();
+        let clocks = self.clocks.clone();
+        midi_in
+            .connect(
+                &port,
+                "midir-read-input",
+                move |stamp, message, _| {
+                    let mut clocks = clocks.lock().unwrap();
+                    if clocks
+                        .entry(device.clone())
+                        .or_default()
+                        .handle(stamp, message)
+                    {
+                        return;
+                    }
+                    let key = if message.len() >= 2 { message[1] } else { 0 };
+                    let vel = if message.len() >= 3 { message[2] } else { 0 };
+                    // the receiver is gone when viz is shutting down
+                    let _ = midi_tx.send(MidiEvent {
+                        device: device.clone(),
+                        channel: message[0] & 0x0F,
+                        kind: message[0] & 0xF0,
+                        key: key,
+                        velocity: vel,
+                        timestamp: stamp as i64,
+                    });
+                },
+                (),
+            )
+            .map_err(|e| anyhow!("{}", e))
+    }
+
+    fn scan_outputs(&mut self, events: &mut Vec<GfxEvent>) -> Result<()> {
+        let probe = MidiOutput::new("sdlrig-midi-output-probe")?;
+        let present = probe
+            .ports()
+            .iter()
+            .filter_map(|p| probe.port_name(p).ok())
+            .collect::<HashSet<_>>();
+        let mut outputs = self.outputs.lock().unwrap();
+        let gone = outputs
+            .keys()
+            .filter(|name| !present.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in gone {
+            outputs.remove(&name);
+            info!("Midi output {} disconnected", name);
+            events.push(GfxEvent::DeviceDisconnected(DeviceEvent {
+                name,
+                kind: DeviceKind::MidiOutput,
+            }));
+        }
+        for name in &self.wanted_outputs {
+            if outputs.contains_key(name) || !present.contains(name) {
+                continue;
+            }
+            match connect_output(name) {
+                Ok(conn) => {
+                    info!("Opening midi output port {}", name);
+                    outputs.insert(name.clone(), conn);
+                    events.push(GfxEvent::DeviceConnected(DeviceEvent {
+                        name: name.clone(),
+                        kind: DeviceKind::MidiOutput,
+                    }));
+                }
+                Err(e) => warn!("Could not open midi output port {}: {}", name, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn connect_output(name: &str) -> Result<MidiOutputConnection> {
+    let midi_out = MidiOutput::new("sdlrig-midi-output")?;
+    let port = midi_out
+        .ports()
+        .into_iter()
+        .find(|p| midi_out.port_name(p).map_or(false, |n| n == name))
+        .ok_or_else(|| anyhow!("Invalid midi output port"))?;
+    midi_out
+        .connect(&port, "midir-write-output")
+        .map_err(|e| anyhow!("{}", e))
+}
@@ -0,0 +1,162 @@
+use crate::dylib::Library;
+use crate::outputshare::{check_frame, ShareBackend, ShareSink};
+use anyhow::{bail, Result};
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::path::Path;
+use tracing::info;
+
+#[repr(C)]
+struct NdiSendCreate {
+    p_ndi_name: *const c_char,
+    p_groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+#[repr(C)]
+struct NdiVideoFrameV2 {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: f32,
+    frame_format_type: c_int,
+    timecode: i64,
+    p_data: *const u8,
+    line_stride_in_bytes: c_int,
+    p_metadata: *const c_char,
+    timestamp: i64,
+}
+
+const NDI_FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+const NDI_FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+const NDI_SEND_TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+type NdiInitialize = unsafe extern "C" fn() -> bool;
+type NdiSendCreateFn = unsafe extern "C" fn(*const NdiSendCreate) -> *mut c_void;
+type NdiSendVideo = unsafe extern "C" fn(*mut c_void, *const NdiVideoFrameV2);
+type NdiSendDestroy = unsafe extern "C" fn(*mut c_void);
+
+// The NDI runtime is loaded at runtime so it stays an optional install
+pub struct NdiSink {
+    // kept open while the sender is
+    _lib: Library,
+    sender: *mut c_void,
+    send_video: NdiSendVideo,
+    send_destroy: NdiSendDestroy,
+    frames_per_sec: i64,
+}
+
+impl NdiSink {
+    pub fn new(name: &str, frames_per_sec: i64) -> Result<Self> {
+        let lib = Self::open_runtime()?;
+        unsafe {
+            let initialize: NdiInitialize =
+                std::mem::transmute(Self::sym(&lib, "NDIlib_initialize")?);
+            let send_create: NdiSendCreateFn =
+                std::mem::transmute(Self::sym(&lib, "NDIlib_send_create")?);
+            let send_video: NdiSendVideo =
+                std::mem::transmute(Self::sym(&lib, "NDIlib_send_send_video_v2")?);
+            let send_destroy: NdiSendDestroy =
+                std::mem::transmute(Self::sym(&lib, "NDIlib_send_destroy")?);
+
+            if !initialize() {
+                bail!("NDI runtime failed to initialize (unsupported CPU?)");
+            }
+
+            let c_name = CString::new(name)?;
+            let create = NdiSendCreate {
+                p_ndi_name: c_name.as_ptr(),
+                p_groups: std::ptr::null(),
+                clock_video: false,
+                clock_audio: false,
+            };
+            let sender = send_create(&create);
+            if sender.is_null() {
+                bail!("Could not create NDI sender {}", name);
+            }
+
+            info!("Publishing NDI source {}", name);
+            Ok(Self {
+                _lib: lib,
+                sender,
+                send_video,
+                send_destroy,
+                frames_per_sec,
+            })
+        }
+    }
+
+    fn open_runtime() -> Result<Library> {
+        let lib_name = if cfg!(target_os = "macos") {
+            "libndi.dylib"
+        } else if cfg!(target_os = "windows") {
+            "Processing.NDI.Lib.x64.dll"
+        } else {
+            "libndi.so.6"
+        };
+        let mut candidates = vec![];
+        for var in ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"] {
+            if let Ok(dir) = std::env::var(var) {
+                candidates.push(Path::new(&dir).join(lib_name).display().to_string());
+            }
+        }
+        if !cfg!(target_os = "windows") {
+            candidates.push(format!("/usr/local/lib/{}", lib_name));
+        }
+        candidates.push(lib_name.to_string());
+        if cfg!(target_os = "linux") {
+            candidates.push("libndi.so.5".to_string());
+        }
+
+        for candidate in &candidates {
+            if let Ok(lib) = Library::open(Path::new(candidate)) {
+                return Ok(lib);
+            }
+        }
+        bail!("Could not find the NDI runtime, tried {:?}", candidates)
+    }
+
+    fn sym(lib: &Library, name: &str) -> Result<*mut c_void> {
+        match lib.symbol(name)? {
+            Some(ptr) => Ok(ptr),
+            None => bail!("NDI runtime is missing {}", name),
+        }
+    }
+}
+
+impl ShareSink for NdiSink {
+    fn publish(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+        check_frame(ShareBackend::Ndi, width, height, rgba)?;
+        let frame = NdiVideoFrameV2 {
+            xres: width as c_int,
+            yres: height as c_int,
+            four_cc: NDI_FOURCC_RGBA,
+            frame_rate_n: self.frames_per_sec as c_int,
+            frame_rate_d: 1,
+            picture_aspect_ratio: width as f32 / height.max(1) as f32,
+            frame_format_type: NDI_FRAME_FORMAT_PROGRESSIVE,
+            timecode: NDI_SEND_TIMECODE_SYNTHESIZE,
+            p_data: rgba.as_ptr(),
+            line_stride_in_bytes: (width * 4) as c_int,
+            p_metadata: std::ptr::null(),
+            timestamp: 0,
+        };
+        // synchronous send, NDI copies the buffer before returning
+        unsafe { (self.send_video)(self.sender, &frame) };
+        Ok(())
+    }
+
+    fn set_frames_per_sec(&mut self, frames_per_sec: i64) {
+        self.frames_per_sec = frames_per_sec;
+    }
+}
+
+impl Drop for NdiSink {
+    fn drop(&mut self) {
+        unsafe {
+            (self.send_destroy)(self.sender);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::gfxruntime::PlaybackPositions;
+use crate::renderspec::{OscArg, SendCmd, SendOsc, SendValue};
+use anyhow::Result;
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+
+// Prefix of the addresses the host sends on its own
+const HOST_ADDR: &str = "/sdlrig";
+
+// Sends OSC over udp to one target, the app's SendOsc specs as well as the
+// rig's transport so other software can follow along
+pub struct OscOut {
+    socket: UdpSocket,
+}
+
+impl OscOut {
+    pub fn new(target: &str) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+        // a frame never waits on the network
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+
+    pub fn send(&self, msg: &SendOsc) -> Result<()> {
+        match self.socket.send(&encode(msg)) {
+            Ok(_) => Ok(()),
+            // nobody listening yet, or more queued than the socket holds
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::ConnectionRefused | ErrorKind::WouldBlock
+                ) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // /sdlrig/cmd/<mix>/<name> with the value's components as floats or ints
+    pub fn mirror_cmd(&self, cmd: &SendCmd) -> Result<()> {
+        let args = match &cmd.value {
+            SendValue::Float(v) => vec![OscArg::Float(*v)],
+            SendValue::Integer(v) => vec![OscArg::Int(*v)],
+            SendValue::Unsigned(v) => vec![OscArg::Long(*v as i64)],
+            SendValue::Vector(v) => v.iter().map(|v| OscArg::Float(*v)).collect(),
+            SendValue::IVector(v) => v.iter().map(|v| OscArg::Int(*v)).collect(),
+            SendValue::UVector(v) => v.iter().map(|v| OscArg::Long(*v as i64)).collect(),
+        };
+        self.send(&SendOsc {
+            addr: format!("{}/cmd/{}/{}", HOST_ADDR, cmd.mix, cmd.name),
+            args,
+        })
+    }
+
+    // Sent once a frame: /sdlrig/frame, /sdlrig/bpm, /sdlrig/mix/<name>/time
+    // and /sdlrig/vid/<name>/position with the position and duration
+    pub fn send_transport(
+        &self,
+        frame: i64,
+        bpm: f64,
+        positions: &PlaybackPositions,
+    ) -> Result<()> {
+        self.send(&SendOsc::new(format!("{}/frame", HOST_ADDR)).arg(frame))?;
+        self.send(&SendOsc::new(format!("{}/bpm", HOST_ADDR)).arg(bpm))?;
+        for (name, time) in &positions.mixes {
+            self.send(&SendOsc::new(format!("{}/mix/{}/time", HOST_ADDR, name)).arg(*time))?;
+        }
+        for (name, (pts, duration)) in &positions.vids {
+            self.send(
+                &SendOsc::new(format!("{}/vid/{}/position", HOST_ADDR, name))
+                    .arg(*pts)
+                    .arg(*duration),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// OSC 1.0: the address, a type tag string then the arguments big endian,
+// strings nul terminated and everything padded to 4 bytes
+pub fn encode(msg: &SendOsc) -> Vec<u8> {
+    let mut tags = String::from(",");
+    let mut args = vec![];
+    for arg in &msg.args {
+        match arg {
+            OscArg::Int(v) => {
+                tags.push('i');
+                args.extend(v.to_be_bytes());
+            }
+            OscArg::Long(v) => {
+                tags.push('h');
+                args.extend(v.to_be_bytes());
+            }
+            OscArg::Float(v) => {
+                tags.push('f');
+                args.extend(v.to_be_bytes());
+            }
+            OscArg::Double(v) => {
+                tags.push('d');
+                args.extend(v.to_be_bytes());
+            }
+            OscArg::Str(v) => {
+                tags.push('s');
+                push_str(&mut args, v);
+            }
+            OscArg::Bool(v) => tags.push(if *v { 'T' } else { 'F' }),
+        }
+    }
+    let mut bytes = vec![];
+    push_str(&mut bytes, &msg.addr);
+    push_str(&mut bytes, &tags);
+    bytes.extend(args);
+    bytes
+}
+
+fn push_str(bytes: &mut Vec<u8>, s: &str) {
+    bytes.extend(s.as_bytes());
+    bytes.push(0);
+    while bytes.len() % 4 != 0 {
+        bytes.push(0);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_gpu_ctx;
+use crate::gfxruntime::GfxRuntime;
+#[cfg(feature = "ndi")]
+use crate::ndi::NdiSink;
+#[cfg(feature = "spout")]
+use crate::spout::SpoutSink;
+#[cfg(feature = "syphon")]
+use crate::syphon::SyphonSink;
+use crate::vidruntime::FrameDownload;
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareBackend {
+    // macOS only
+    Syphon,
+    // windows only
+    Spout,
+    Ndi,
+}
+
+pub trait ShareSink {
+    fn publish(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<()>;
+    fn set_frames_per_sec(&mut self, frames_per_sec: i64);
+}
+
+// Sinks hand rgba to libraries that read width * height * 4 bytes of it
+pub fn check_frame(backend: ShareBackend, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    if rgba.len() < width as usize * height as usize * 4 {
+        bail!(
+            "Short frame for {:?} {}x{} got {} bytes",
+            backend,
+            width,
+            height,
+            rgba.len()
+        );
+    }
+    Ok(())
+}
+
+// Publishes the window (source None) or a named mixer to other apps every frame
+pub struct OutputShare {
+    sink: Box<dyn ShareSink>,
+    source: Option<String>,
+    // made on the first publish, once there is a gpu context
+    download: Option<FrameDownload>,
+    buf: Vec<u8>,
+}
+
+impl OutputShare {
+    // A backend whose feature is off fails
+    #[cfg_attr(
+        not(all(feature = "ndi", feature = "spout", feature = "syphon")),
+        allow(unused_variables, unreachable_code)
+    )]
+    pub fn new(
+        backend: ShareBackend,
+        name: &str,
+        source: Option<String>,
+        frames_per_sec: i64,
+    ) -> Result<Self> {
+        let sink: Box<dyn ShareSink> = match backend {
+            #[cfg(feature = "syphon")]
+            ShareBackend::Syphon => Box::new(SyphonSink::new(name)?),
+            #[cfg(not(feature = "syphon"))]
+            ShareBackend::Syphon => {
+                bail!("Syphon output needs sdlrig built with the syphon feature")
+            }
+            #[cfg(feature = "spout")]
+            ShareBackend::Spout => Box::new(SpoutSink::new(name)?),
+            #[cfg(not(feature = "spout"))]
+            ShareBackend::Spout => bail!("Spout output needs sdlrig built with the spout feature"),
+            #[cfg(feature = "ndi")]
+            ShareBackend::Ndi => Box::new(NdiSink::new(name, frames_per_sec)?),
+            #[cfg(not(feature = "ndi"))]
+            ShareBackend::Ndi => bail!("NDI output needs sdlrig built with the ndi feature"),
+        };
+        Ok(Self {
+            sink,
+            source,
+            download: None,
+            buf: Vec::new(),
+        })
+    }
+
+    // Sends the frame downloaded since the last call, so what's shared runs a
+    // frame behind the window rather than waiting on the gpu, and starts
+    // downloading this one
+    pub fn publish(
+        &mut self,
+        gfx_runtime: &GfxRuntime,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    ) -> Result<()> {
+        let download = match self.download.as_mut() {
+            Some(download) => download,
+            None => self.download.insert(FrameDownload::new(lowlevel_ctx)?),
+        };
+        if let Some((w, h)) = download.poll(&mut self.buf)? {
+            self.sink.publish(w, h, &self.buf)?;
+        }
+        // a download still in flight means this frame is skipped
+        gfx_runtime.start_download(lowlevel_ctx, self.source.as_deref(), download)?;
+        Ok(())
+    }
+
+    pub fn set_frames_per_sec(&mut self, frames_per_sec: i64) {
+        self.sink.set_frames_per_sec(frames_per_sec);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::renderspec::HudWidget;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Where the host's time went for one frame
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FrameStats {
+    pub calc_us: u64,
+    pub render_us: u64,
+    pub present_us: u64,
+    pub dropped: i64,
+    // cpu time spent on each mix spec, decoding its videos included
+    pub mixes: Vec<(String, u64)>,
+    // of that, the time each video spent decoding
+    #[serde(default)]
+    pub decodes: Vec<(String, u64)>,
+}
+
+// Collects per-frame timings for the performance overlay. The HUD keeps the
+// graphs' history, so this only has to hand it the last finished frame.
+pub struct Perf {
+    pub shown: bool,
+    current: FrameStats,
+    last: FrameStats,
+    dropped_total: i64,
+}
+
+impl Perf {
+    pub fn new(shown: bool) -> Self {
+        Self {
+            shown,
+            current: FrameStats::default(),
+            last: FrameStats::default(),
+            dropped_total: 0,
+        }
+    }
+
+    // The last finished frame
+    pub fn last(&self) -> &FrameStats {
+        &self.last
+    }
+
+    pub fn dropped_total(&self) -> i64 {
+        self.dropped_total
+    }
+
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+    }
+
+    pub fn calc(&mut self, took: Duration) {
+        self.current.calc_us += took.as_micros() as u64;
+    }
+
+    pub fn mix(&mut self, name: &str, took: Duration) {
+        let us = took.as_micros() as u64;
+        match self.current.mixes.iter_mut().find(|(n, _)| n == name) {
+            Some((_, total)) => *total += us,
+            None => self.current.mixes.push((name.into(), us)),
+        }
+    }
+
+    pub fn decode(&mut self, name: &str, us: u64) {
+        self.current.decodes.push((name.into(), us));
+    }
+
+    // Ends the frame, render_us is the cpu side and present_us the wait for
+    // the gpu to finish and present it
+    pub fn frame(&mut self, render_us: u64, present_us: u64, dropped: i64) {
+        let mut stats = std::mem::take(&mut self.current);
+        stats.render_us = render_us;
+        stats.present_us = present_us;
+        stats.dropped = dropped;
+        self.dropped_total += dropped;
+        self.last = stats;
+    }
+
+    // The last frame as HUD widgets, graphs are scaled to twice the frame
+    // budget and queues are (name, depth)
+    pub fn widgets(&self, fps: i64, queues: &[(&str, usize)]) -> Vec<HudWidget> {
+        if !self.shown {
+            return vec![];
+        }
+        let ms = |us: u64| us as f64 / 1000.0;
+        let budget = 1000.0 / fps.max(1) as f64;
+        let last = &self.last;
+        let cpu = last.calc_us + last.render_us;
+        let mut widgets = vec![
+            HudWidget::graph("frame ms", ms(cpu + last.present_us), 0.0, budget * 2.0),
+            HudWidget::graph("cpu ms", ms(cpu), 0.0, budget * 2.0),
+            HudWidget::graph("gpu ms", ms(last.present_us), 0.0, budget * 2.0),
+            HudWidget::graph("dropped", last.dropped as f64, 0.0, 4.0),
+        ];
+        let mut mixes = last.mixes.clone();
+        mixes.sort_by(|a, b| b.1.cmp(&a.1));
+        let table = HudWidget::table(Some("perf"))
+            .row("calc", format!("{:.2} ms", ms(last.calc_us)))
+            .row("render", format!("{:.2} ms", ms(last.render_us)))
+            .row("present", format!("{:.2} ms", ms(last.present_us)))
+            .row("dropped", self.dropped_total);
+        let table = mixes.into_iter().fold(table, |t, (name, us)| {
+            t.row(format!("mix {}", name), format!("{:.2} ms", ms(us)))
+        });
+        widgets.push(last.decodes.iter().fold(table, |t, (name, us)| {
+            t.row(format!("decode {}", name), format!("{:.2} ms", ms(*us)))
+        }));
+        widgets.push(
+            queues
+                .iter()
+                .fold(HudWidget::table(Some("queues")), |t, (name, depth)| {
+                    t.row(name, depth)
+                }),
+        );
+        widgets
+    }
+}
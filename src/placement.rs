@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use sdl2::rect::Rect;
+use sdl2::video::{FullscreenType, Window, WindowPos};
+use sdl2::VideoSubsystem;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Saved {
+    display: i32,
+    fullscreen: bool,
+    // the windowed geometry, relative to the display's corner
+    position: (i32, i32),
+    size: (u32, u32),
+}
+
+// Where the output window was last put for each arrangement of displays, so
+// plugging the projector back in puts the output back on it
+pub struct Placement {
+    file: Option<PathBuf>,
+    saved: HashMap<String, Saved>,
+}
+
+impl Placement {
+    // A missing or unreadable file starts over
+    pub fn load(file: Option<PathBuf>) -> Self {
+        let saved = file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| warn!("Ignoring saved window placement: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self { file, saved }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = self.file.as_ref() {
+            fs::write(path, serde_json::to_string_pretty(&self.saved)?)?;
+        }
+        Ok(())
+    }
+
+    // Keeps where the window is now for the current displays
+    pub fn remember(&mut self, video: &VideoSubsystem, window: &Window) -> Result<()> {
+        let display = window.display_index().map_err(|e| anyhow!(e))?;
+        let bounds = video.display_bounds(display).map_err(|e| anyhow!(e))?;
+        let fullscreen = window.fullscreen_state() != FullscreenType::Off;
+        let key = layout(video)?;
+        let saved = match self.saved.get(&key) {
+            // a fullscreen window's geometry is the display's, keep the last windowed one
+            Some(saved) if fullscreen => Saved {
+                display,
+                fullscreen,
+                ..saved.clone()
+            },
+            _ => {
+                let (x, y) = window.position();
+                Saved {
+                    display,
+                    fullscreen,
+                    position: (x - bounds.x(), y - bounds.y()),
+                    size: window.size(),
+                }
+            }
+        };
+        self.saved.insert(key, saved);
+        Ok(())
+    }
+
+    // Puts the window back where it was for the current displays, false if
+    // it was never placed with them
+    pub fn restore(&self, video: &VideoSubsystem, window: &mut Window) -> Result<bool> {
+        let Some(saved) = self.saved.get(&layout(video)?) else {
+            return Ok(false);
+        };
+        let bounds = video
+            .display_bounds(saved.display)
+            .map_err(|e| anyhow!(e))?;
+        window
+            .set_fullscreen(FullscreenType::Off)
+            .map_err(|e| anyhow!(e))?;
+        window.set_size(saved.size.0, saved.size.1)?;
+        place(window, bounds, saved.position);
+        if saved.fullscreen {
+            window
+                .set_fullscreen(FullscreenType::Desktop)
+                .map_err(|e| anyhow!(e))?;
+        }
+        Ok(true)
+    }
+}
+
+// Desktop fullscreen keeps the display's mode, so the swapchain is only
+// resized rather than the display switching modes under it
+pub fn toggle_fullscreen(window: &mut Window) -> Result<()> {
+    let next = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(next).map_err(|e| anyhow!(e))
+}
+
+// Moves the window step displays along, keeping it fullscreen if it was
+pub fn move_to_display(video: &VideoSubsystem, window: &mut Window, step: i32) -> Result<()> {
+    let count = video.num_video_displays().map_err(|e| anyhow!(e))?;
+    let current = window.display_index().map_err(|e| anyhow!(e))?;
+    show_on_display(video, window, (current + step).rem_euclid(count.max(1)))
+}
+
+pub fn show_on_display(video: &VideoSubsystem, window: &mut Window, display: i32) -> Result<()> {
+    let bounds = video.display_bounds(display).map_err(|e| anyhow!(e))?;
+    let fullscreen = window.fullscreen_state();
+    if fullscreen != FullscreenType::Off {
+        window
+            .set_fullscreen(FullscreenType::Off)
+            .map_err(|e| anyhow!(e))?;
+    }
+    let from = window
+        .display_index()
+        .and_then(|current| video.display_bounds(current))
+        .map_err(|e| anyhow!(e))?;
+    let (x, y) = window.position();
+    place(window, bounds, (x - from.x(), y - from.y()));
+    if fullscreen != FullscreenType::Off {
+        window.set_fullscreen(fullscreen).map_err(|e| anyhow!(e))?;
+    }
+    Ok(())
+}
+
+// Keeps the window on the display even if it was placed on a larger one
+fn place(window: &mut Window, bounds: Rect, (x, y): (i32, i32)) {
+    let (w, h) = window.size();
+    let x = x.min(bounds.width() as i32 - w as i32).max(0);
+    let y = y.min(bounds.height() as i32 - h as i32).max(0);
+    window.set_position(
+        WindowPos::Positioned(bounds.x() + x),
+        WindowPos::Positioned(bounds.y() + y),
+    );
+}
+
+// Every display's bounds, which is what a placement is remembered under
+fn layout(video: &VideoSubsystem) -> Result<String> {
+    let count = video.num_video_displays().map_err(|e| anyhow!(e))?;
+    let displays = (0..count)
+        .map(|i| {
+            let bounds = video.display_bounds(i).map_err(|e| anyhow!(e))?;
+            Ok(format!(
+                "{}x{}+{}+{}",
+                bounds.width(),
+                bounds.height(),
+                bounds.x(),
+                bounds.y()
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(displays.join(","))
+}
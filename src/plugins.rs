@@ -0,0 +1,264 @@
+use crate::dylib::Library;
+use crate::gfx_lowlevel::bindings::gfx_lowlevel_gpu_ctx;
+use crate::gfxinfo::{PluginAsset, PluginEvent};
+use crate::renderspec::PluginSpec;
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::warn;
+
+// See sdlrig_plugin.h, the two have to change together
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+#[repr(C)]
+struct PluginTable {
+    abi_version: u32,
+    name: *const c_char,
+    state: *mut c_void,
+    spec_kinds: *const *const c_char,
+    asset_kinds: *const *const c_char,
+    render: Option<
+        unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, i64, *mut c_void) -> i32,
+    >,
+    load_asset: Option<
+        unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, *const c_char) -> i32,
+    >,
+    unload_asset: Option<unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char)>,
+    poll_events: Option<unsafe extern "C" fn(*mut c_void) -> *const c_char>,
+    last_error: Option<unsafe extern "C" fn(*mut c_void) -> *const c_char>,
+    shutdown: Option<unsafe extern "C" fn(*mut c_void)>,
+}
+
+type PluginInit = unsafe extern "C" fn(u32) -> *const PluginTable;
+
+struct Plugin {
+    // kept open while the table it handed out is used
+    _lib: Library,
+    table: *const PluginTable,
+    name: String,
+    spec_kinds: Vec<String>,
+    asset_kinds: Vec<String>,
+}
+
+// Only ever used behind PLUGINS' lock, which is what the header promises
+unsafe impl Send for Plugin {}
+
+#[derive(Deserialize)]
+struct PolledEvent {
+    kind: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+lazy_static! {
+    // Global so assets can be loaded by plugins from the app loader's threads
+    static ref PLUGINS: Mutex<Vec<Plugin>> = Mutex::new(vec![]);
+}
+
+impl Plugin {
+    fn open(path: &Path) -> Result<Self> {
+        let lib = Library::open(path)
+            .map_err(|e| anyhow!("Could not open plugin {}: {}", path.display(), e))?;
+        let Some(init) = lib.symbol("sdlrig_plugin_init")? else {
+            bail!("{} doesn't export sdlrig_plugin_init", path.display());
+        };
+        let init: PluginInit = unsafe { std::mem::transmute(init) };
+        let table = unsafe { init(PLUGIN_ABI_VERSION) };
+        if table.is_null() || unsafe { (*table).abi_version } != PLUGIN_ABI_VERSION {
+            bail!(
+                "{} doesn't support plugin abi version {}",
+                path.display(),
+                PLUGIN_ABI_VERSION
+            );
+        }
+        unsafe {
+            Ok(Self {
+                _lib: lib,
+                table,
+                name: c_string((*table).name).unwrap_or_else(|| path.display().to_string()),
+                spec_kinds: c_strings((*table).spec_kinds),
+                asset_kinds: c_strings((*table).asset_kinds),
+            })
+        }
+    }
+
+    fn table(&self) -> &PluginTable {
+        unsafe { &*self.table }
+    }
+
+    fn check(&self, result: i32) -> Result<()> {
+        if result == 0 {
+            return Ok(());
+        }
+        let table = self.table();
+        let msg = table
+            .last_error
+            .and_then(|last_error| unsafe { c_string(last_error(table.state)) });
+        Err(anyhow!(
+            "plugin {} failed ({}): {}",
+            self.name,
+            result,
+            msg.unwrap_or_else(|| "no error message".into())
+        ))
+    }
+
+    fn poll(&self) -> Result<Vec<PluginEvent>> {
+        let table = self.table();
+        let Some(poll_events) = table.poll_events else {
+            return Ok(vec![]);
+        };
+        let Some(json) = (unsafe { c_string(poll_events(table.state)) }) else {
+            return Ok(vec![]);
+        };
+        let polled = serde_json::from_str::<Vec<PolledEvent>>(&json)
+            .map_err(|e| anyhow!("plugin {} sent bad events: {}", self.name, e))?;
+        Ok(polled
+            .into_iter()
+            .map(|event| PluginEvent {
+                plugin: self.name.clone(),
+                kind: event.kind,
+                data: event.data.to_string(),
+            })
+            .collect())
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        let table = self.table();
+        unsafe {
+            if let Some(shutdown) = table.shutdown {
+                shutdown(table.state);
+            }
+        }
+    }
+}
+
+unsafe fn c_string(ptr: *const c_char) -> Option<String> {
+    match ptr.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(ptr).to_string_lossy().into_owned()),
+    }
+}
+
+unsafe fn c_strings(mut list: *const *const c_char) -> Vec<String> {
+    let mut strings = vec![];
+    while !list.is_null() && !(*list).is_null() {
+        strings.extend(c_string(*list));
+        list = list.add(1);
+    }
+    strings
+}
+
+// Loads a plugin for the rest of the run, returning its name. Kinds another
+// plugin already handles stay with that one.
+pub fn install(path: &Path) -> Result<String> {
+    let plugin = Plugin::open(path)?;
+    let name = plugin.name.clone();
+    let mut plugins = PLUGINS.lock().unwrap();
+    if plugins.iter().any(|p| p.name == name) {
+        bail!("A plugin named {} is already loaded", name);
+    }
+    plugins.push(plugin);
+    Ok(name)
+}
+
+// Shuts every plugin down, call before the gpu context goes away
+pub fn uninstall_all() {
+    PLUGINS.lock().unwrap().clear();
+}
+
+pub fn handles_spec(kind: &str) -> bool {
+    let plugins = PLUGINS.lock().unwrap();
+    plugins
+        .iter()
+        .any(|p| p.spec_kinds.iter().any(|k| k == kind))
+}
+
+pub fn handles_asset(kind: &str) -> bool {
+    let plugins = PLUGINS.lock().unwrap();
+    plugins
+        .iter()
+        .any(|p| p.asset_kinds.iter().any(|k| k == kind))
+}
+
+pub fn render(
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    spec: &PluginSpec,
+    frame: i64,
+) -> Result<()> {
+    let plugins = PLUGINS.lock().unwrap();
+    let Some(plugin) = plugins
+        .iter()
+        .find(|p| p.spec_kinds.iter().any(|k| *k == spec.kind))
+    else {
+        bail!("No plugin renders {}", spec.kind);
+    };
+    let table = plugin.table();
+    let Some(render) = table.render else {
+        bail!("plugin {} has no render", plugin.name);
+    };
+    let kind = CString::new(spec.kind.as_str())?;
+    let args = CString::new(spec.args.as_str())?;
+    plugin.check(unsafe {
+        render(
+            table.state,
+            kind.as_ptr(),
+            args.as_ptr(),
+            frame,
+            lowlevel_ctx as *mut c_void,
+        )
+    })
+}
+
+pub fn load_asset(asset: &PluginAsset) -> Result<()> {
+    let plugins = PLUGINS.lock().unwrap();
+    let Some(plugin) = plugins
+        .iter()
+        .find(|p| p.asset_kinds.iter().any(|k| *k == asset.kind))
+    else {
+        bail!("No plugin loads {} assets", asset.kind);
+    };
+    let table = plugin.table();
+    let Some(load_asset) = table.load_asset else {
+        bail!("plugin {} has no load_asset", plugin.name);
+    };
+    let kind = CString::new(asset.kind.as_str())?;
+    let name = CString::new(asset.name.as_str())?;
+    let args = CString::new(asset.args.as_str())?;
+    plugin.check(unsafe { load_asset(table.state, kind.as_ptr(), name.as_ptr(), args.as_ptr()) })
+}
+
+pub fn unload_asset(asset: &PluginAsset) {
+    let plugins = PLUGINS.lock().unwrap();
+    let Some(plugin) = plugins
+        .iter()
+        .find(|p| p.asset_kinds.iter().any(|k| *k == asset.kind))
+    else {
+        return;
+    };
+    let table = plugin.table();
+    if let (Some(unload_asset), Ok(kind), Ok(name)) = (
+        table.unload_asset,
+        CString::new(asset.kind.as_str()),
+        CString::new(asset.name.as_str()),
+    ) {
+        unsafe { unload_asset(table.state, kind.as_ptr(), name.as_ptr()) };
+    }
+}
+
+// Every plugin's events since the last call, call once per frame
+pub fn poll_events() -> Vec<PluginEvent> {
+    let plugins = PLUGINS.lock().unwrap();
+    let mut events = vec![];
+    for plugin in plugins.iter() {
+        match plugin.poll() {
+            Ok(polled) => events.extend(polled),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    events
+}
@@ -0,0 +1,177 @@
+use crate::gfxinfo::{GfxEvent, MouseButton, MouseEvent, MouseInput, TouchEvent, TouchPhase};
+use sdl2::event::Event;
+use sdl2::mouse::{MouseButton as SdlButton, MouseState};
+
+// SDL also sends touches as mouse events from this mouse, apps get them once
+// as TouchEvents instead
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+// Mouse events on the window and all touches, in canvas pixels. Nothing for
+// other events.
+pub fn pointer_event(event: &Event, window_id: u32, canvas: (u32, u32)) -> Option<GfxEvent> {
+    let mouse = |x, y, input, timestamp: u32| {
+        Some(GfxEvent::MouseEvent(MouseEvent {
+            x,
+            y,
+            input,
+            timestamp: timestamp as i64,
+        }))
+    };
+    match *event {
+        Event::MouseMotion {
+            timestamp,
+            window_id: id,
+            which,
+            mousestate,
+            x,
+            y,
+            xrel,
+            yrel,
+        } if id == window_id && which != TOUCH_MOUSE_ID => mouse(
+            x,
+            y,
+            MouseInput::Motion {
+                dx: xrel,
+                dy: yrel,
+                held: held(&mousestate),
+            },
+            timestamp,
+        ),
+        Event::MouseButtonDown {
+            timestamp,
+            window_id: id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } if id == window_id && which != TOUCH_MOUSE_ID => mouse(
+            x,
+            y,
+            MouseInput::Button {
+                button: mouse_button(mouse_btn)?,
+                down: true,
+                clicks,
+            },
+            timestamp,
+        ),
+        Event::MouseButtonUp {
+            timestamp,
+            window_id: id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } if id == window_id && which != TOUCH_MOUSE_ID => mouse(
+            x,
+            y,
+            MouseInput::Button {
+                button: mouse_button(mouse_btn)?,
+                down: false,
+                clicks,
+            },
+            timestamp,
+        ),
+        Event::MouseWheel {
+            timestamp,
+            window_id: id,
+            which,
+            x,
+            y,
+            mouse_x,
+            mouse_y,
+            ..
+        } if id == window_id && which != TOUCH_MOUSE_ID => {
+            mouse(mouse_x, mouse_y, MouseInput::Wheel { x, y }, timestamp)
+        }
+        Event::FingerDown {
+            timestamp,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        } => touch(
+            canvas,
+            TouchPhase::Down,
+            finger_id,
+            (x, y, dx, dy, pressure),
+            timestamp,
+        ),
+        Event::FingerMotion {
+            timestamp,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        } => touch(
+            canvas,
+            TouchPhase::Move,
+            finger_id,
+            (x, y, dx, dy, pressure),
+            timestamp,
+        ),
+        Event::FingerUp {
+            timestamp,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        } => touch(
+            canvas,
+            TouchPhase::Up,
+            finger_id,
+            (x, y, dx, dy, pressure),
+            timestamp,
+        ),
+        _ => None,
+    }
+}
+
+// Touch positions come normalized to the touch surface, taken to cover the canvas
+fn touch(
+    canvas: (u32, u32),
+    phase: TouchPhase,
+    finger: i64,
+    (x, y, dx, dy, pressure): (f32, f32, f32, f32, f32),
+    timestamp: u32,
+) -> Option<GfxEvent> {
+    let (w, h) = (canvas.0 as f32, canvas.1 as f32);
+    Some(GfxEvent::TouchEvent(TouchEvent {
+        finger,
+        phase,
+        x: (x * w).round() as i32,
+        y: (y * h).round() as i32,
+        dx: (dx * w).round() as i32,
+        dy: (dy * h).round() as i32,
+        pressure: (pressure.clamp(0.0, 1.0) * 1000.0).round() as u16,
+        timestamp: timestamp as i64,
+    }))
+}
+
+fn mouse_button(button: SdlButton) -> Option<MouseButton> {
+    match button {
+        SdlButton::Left => Some(MouseButton::Left),
+        SdlButton::Middle => Some(MouseButton::Middle),
+        SdlButton::Right => Some(MouseButton::Right),
+        SdlButton::X1 => Some(MouseButton::X1),
+        SdlButton::X2 => Some(MouseButton::X2),
+        SdlButton::Unknown => None,
+    }
+}
+
+fn held(state: &MouseState) -> Vec<MouseButton> {
+    state
+        .pressed_mouse_buttons()
+        .filter_map(mouse_button)
+        .collect()
+}
@@ -0,0 +1,314 @@
+use crate::gfxinfo::GfxEvent;
+use crate::perf::FrameStats;
+use crate::renderspec::HudWidget;
+use crate::websocket::{self, Message};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::warn;
+
+// What a --remote client sends, one json message each, e.g. "Stats" or
+// {"Event": {"KeyEvent": ...}}. Every request gets one reply.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteRequest {
+    // delivered with the next calculate like the host's own events
+    Event(GfxEvent),
+    Hud,
+    Stats,
+    Settings,
+    SetSettings { version: u32, bytes: Vec<u8> },
+    // the app's settings_schema, None when it doesn't describe its settings
+    Schema,
+    // sent to the app as a SettingChanged event
+    SetSetting { field: String, value: f64 },
+    Transport,
+    // the window as it was rendered, scaled down to width
+    Screenshot { width: u32 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteReply {
+    Ok,
+    Error(String),
+    Hud {
+        sections: Vec<String>,
+        widgets: Vec<HudWidget>,
+        text: String,
+    },
+    Stats {
+        frame: i64,
+        last: FrameStats,
+        dropped_total: i64,
+        queues: Vec<(String, usize)>,
+    },
+    Settings {
+        version: u32,
+        bytes: Vec<u8>,
+    },
+    Schema(Option<String>),
+    Transport {
+        frame: i64,
+        bpm: f64,
+        // (name, position, duration) in seconds
+        vids: Vec<(String, f64, f64)>,
+        // (name, present time) in seconds
+        mixes: Vec<(String, f64)>,
+    },
+    Screenshot {
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    },
+}
+
+// Served at / for controlling the rig from a browser
+const REMOTE_UI: &str = include_str!("remote.html");
+
+// A request waiting on the render loop to answer it
+pub struct RemoteCall {
+    pub request: RemoteRequest,
+    reply: Sender<RemoteReply>,
+}
+
+impl RemoteCall {
+    // The client may be gone by now, which is fine
+    pub fn reply(&self, reply: RemoteReply) {
+        self.reply.send(reply).ok();
+    }
+}
+
+// Serves clients on their own threads and queues their requests for the
+// render loop, which has everything needed to answer them. With a token
+// every request needs ?token=<token>, without one anyone who can reach the
+// address can control the rig, so keep it on a trusted network. A browser
+// page from anywhere else can't upgrade, its Origin gives it away, unless it
+// rebinds its own name to the rig's address. So without a token a browser has
+// to connect by an ip address, localhost or the name listened on.
+pub struct Remote {
+    calls: Receiver<RemoteCall>,
+}
+
+impl Remote {
+    pub fn listen(addr: &str, token: Option<String>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (tx, rx) = channel();
+        let token = Arc::new(token);
+        let addr = Arc::new(addr.to_string());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let calls = tx.clone();
+                        let token = token.clone();
+                        let addr = addr.clone();
+                        thread::spawn(move || {
+                            if let Err(e) = serve(stream, calls, token.as_deref(), &addr) {
+                                warn!("Remote client dropped: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Remote connection failed: {}", e),
+                }
+            }
+        });
+        Ok(Self { calls: rx })
+    }
+
+    // Requests since the last call, answer each one
+    pub fn poll(&self) -> Vec<RemoteCall> {
+        self.calls.try_iter().collect()
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    calls: Sender<RemoteCall>,
+    token: Option<&str>,
+    addr: &str,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = websocket::read_request(&mut reader)?;
+    let mut stream = stream;
+    if token.is_none() && !trusted_host(request.host.as_deref(), addr) {
+        return websocket::respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"Connect by address or localhost, or set --remote-token to allow any host name\n",
+        );
+    }
+    let (path, query) = request
+        .path
+        .split_once('?')
+        .unwrap_or((request.path.as_str(), ""));
+    if let Some(token) = token {
+        let given = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .unwrap_or("");
+        if !same_token(given, token) {
+            return websocket::respond(
+                &mut stream,
+                "403 Forbidden",
+                "text/plain",
+                b"Pass ?token=<the --remote-token>\n",
+            );
+        }
+    }
+    let Some(key) = request.key.as_deref() else {
+        return match (request.method.as_str(), path) {
+            ("GET", "/") => websocket::respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                REMOTE_UI.as_bytes(),
+            ),
+            _ => websocket::respond(&mut stream, "404 Not Found", "text/plain", b"Not found\n"),
+        };
+    };
+    if !same_origin(request.origin.as_deref(), request.host.as_deref()) {
+        return websocket::respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"Cross-origin upgrade refused\n",
+        );
+    }
+    websocket::upgrade(&mut stream, key)?;
+
+    // replies are written from their own thread, pongs from this one
+    let writer = Arc::new(Mutex::new(stream));
+    let (reply_tx, reply_rx) = channel::<RemoteReply>();
+    let reply_writer = writer.clone();
+    thread::spawn(move || {
+        for reply in reply_rx {
+            let Ok(json) = serde_json::to_string(&reply) else {
+                continue;
+            };
+            if websocket::write_text(&mut *reply_writer.lock().unwrap(), &json).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let message = websocket::read_message(&mut reader, |payload| {
+            websocket::write_pong(&mut *writer.lock().unwrap(), payload)
+        })?;
+        let parsed = match message {
+            Message::Text(text) => serde_json::from_str::<RemoteRequest>(&text),
+            Message::Binary(bytes) => serde_json::from_slice::<RemoteRequest>(&bytes),
+            Message::Close => {
+                websocket::write_close(&mut *writer.lock().unwrap()).ok();
+                return Ok(());
+            }
+        };
+        match parsed {
+            Ok(request) => {
+                let call = RemoteCall {
+                    request,
+                    reply: reply_tx.clone(),
+                };
+                if calls.send(call).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(e) => {
+                reply_tx
+                    .send(RemoteReply::Error(format!("Bad request: {}", e)))
+                    .ok();
+            }
+        }
+    }
+}
+
+// Clients that aren't browsers don't send an Origin, pages send their own
+// scheme://host:port, which has to be the host they connected to
+fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    match (origin_host, host) {
+        (Some(origin_host), Some(host)) => origin_host.eq_ignore_ascii_case(host),
+        _ => false,
+    }
+}
+
+// Host without its port, and an ipv6 address without its brackets
+fn host_name(host: &str) -> &str {
+    match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    }
+}
+
+// A page can only rebind a name it owns, never an address or localhost.
+// Clients that aren't browsers may not send a Host, they can't be rebound.
+fn trusted_host(host: Option<&str>, addr: &str) -> bool {
+    let Some(host) = host else {
+        return true;
+    };
+    let name = host_name(host.trim());
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok()
+        || name.eq_ignore_ascii_case(host_name(addr))
+}
+
+// Takes as long whichever byte differs
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins() {
+        assert!(same_origin(None, Some("a:80")));
+        assert!(same_origin(Some("http://a:80"), Some("a:80")));
+        assert!(same_origin(Some("https://A:80"), Some("a:80")));
+        assert!(!same_origin(Some("http://a:81"), Some("a:80")));
+        assert!(!same_origin(Some("http://b"), Some("a")));
+        assert!(!same_origin(Some("file://a"), Some("a")));
+        assert!(!same_origin(Some("http://a"), None));
+        assert!(!same_origin(Some("null"), Some("a")));
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(same_token("secret", "secret"));
+        assert!(!same_token("secreT", "secret"));
+        assert!(!same_token("secre", "secret"));
+        assert!(!same_token("", "secret"));
+    }
+
+    #[test]
+    fn hosts() {
+        let addr = "0.0.0.0:9000";
+        assert!(trusted_host(None, addr));
+        assert!(trusted_host(Some("localhost:9000"), addr));
+        assert!(trusted_host(Some("127.0.0.1:9000"), addr));
+        assert!(trusted_host(Some("192.168.1.20:9000"), addr));
+        assert!(trusted_host(Some("[::1]:9000"), addr));
+        assert!(trusted_host(Some("[fe80::1]"), addr));
+        assert!(!trusted_host(Some("evil.example:9000"), addr));
+        assert!(!trusted_host(Some("localhost.evil.example"), addr));
+        assert!(!trusted_host(Some(""), addr));
+        assert!(trusted_host(Some("rig.local:9000"), "rig.local:9000"));
+        assert!(trusted_host(Some("RIG.local"), "rig.local:9000"));
+        assert!(!trusted_host(Some("other.local"), "rig.local:9000"));
+    }
+}
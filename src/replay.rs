@@ -0,0 +1,245 @@
+use crate::gfxinfo::GfxEvent;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+use tracing::warn;
+
+// One json object per line, a Start first
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum RecordedLine {
+    Start { fps: i64 },
+    // the app's settings when recording began, see AppRuntime::migrate_settings
+    Settings { version: u32, bytes: Vec<u8> },
+    Frame(RecordedFrame),
+}
+
+// Everything calculate was given for a frame
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedFrame {
+    pub frame: i64,
+    pub canvas: (u32, u32),
+    pub events: Vec<GfxEvent>,
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P, fps: i64) -> Result<Self> {
+        let mut recorder = Self {
+            out: BufWriter::new(File::create(path)?),
+        };
+        recorder.write(&RecordedLine::Start { fps })?;
+        Ok(recorder)
+    }
+
+    pub fn settings(&mut self, version: u32, bytes: Vec<u8>) -> Result<()> {
+        self.write(&RecordedLine::Settings { version, bytes })
+    }
+
+    pub fn frame(&mut self, frame: i64, canvas: (u32, u32), events: &[GfxEvent]) -> Result<()> {
+        self.write(&RecordedLine::Frame(RecordedFrame {
+            frame,
+            canvas,
+            events: events.to_vec(),
+        }))
+    }
+
+    fn write(&mut self, line: &RecordedLine) -> Result<()> {
+        serde_json::to_writer(&mut self.out, line)?;
+        self.out.write_all(b"\n")?;
+        // flushed every line so a crash still leaves a usable recording
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub struct Replay {
+    pub fps: i64,
+    pub settings: Option<(u32, Vec<u8>)>,
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let fps = match lines.next() {
+            Some(line) => match serde_json::from_str(&line?)? {
+                RecordedLine::Start { fps } => fps,
+                _ => bail!("Recording doesn't start with a Start line"),
+            },
+            None => bail!("Recording is empty"),
+        };
+        let mut replay = Self {
+            fps,
+            settings: None,
+            frames: VecDeque::new(),
+        };
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            // the last line may be cut short by a crash while recording
+            let Ok(parsed) = serde_json::from_str(&line) else {
+                warn!("Stopping replay at unreadable line {}", n + 2);
+                break;
+            };
+            match parsed {
+                RecordedLine::Start { .. } => {
+                    return Err(anyhow!("Recording has a second Start at line {}", n + 2))
+                }
+                RecordedLine::Settings { version, bytes } => {
+                    replay.settings.get_or_insert((version, bytes));
+                }
+                RecordedLine::Frame(frame) => replay.frames.push_back(frame),
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.pop_front()
+    }
+}
+
+// What a session recording keeps, the performer's inputs
+pub fn session_input(event: &GfxEvent) -> bool {
+    matches!(
+        event,
+        GfxEvent::KeyEvent(_)
+            | GfxEvent::MidiEvent(_)
+            | GfxEvent::ControllerEvent(_)
+            | GfxEvent::MouseEvent(_)
+            | GfxEvent::TouchEvent(_)
+            | GfxEvent::FileDropped(_)
+    )
+}
+
+// One json object per line, a Start first. Unlike a full recording only the
+// inputs are kept, so a replayed session still runs against live assets and
+// timing but sees the same inputs on the same frames.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SessionLine {
+    Start {
+        fps: i64,
+    },
+    Settings {
+        version: u32,
+        bytes: Vec<u8>,
+    },
+    // frame counts from the session's first frame, ms is when it arrived and
+    // is only there to line a recording up with a report of the show
+    Input {
+        frame: i64,
+        ms: u64,
+        event: GfxEvent,
+    },
+}
+
+pub struct SessionRecorder {
+    out: BufWriter<File>,
+    started: Instant,
+    first_frame: Option<i64>,
+}
+
+impl SessionRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, fps: i64) -> Result<Self> {
+        let mut recorder = Self {
+            out: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+            first_frame: None,
+        };
+        recorder.write(&SessionLine::Start { fps })?;
+        Ok(recorder)
+    }
+
+    pub fn settings(&mut self, version: u32, bytes: Vec<u8>) -> Result<()> {
+        self.write(&SessionLine::Settings { version, bytes })
+    }
+
+    // Keeps the inputs out of everything calculate is given for a frame
+    pub fn frame(&mut self, frame: i64, events: &[GfxEvent]) -> Result<()> {
+        let first = *self.first_frame.get_or_insert(frame);
+        let ms = self.started.elapsed().as_millis() as u64;
+        for event in events.iter().filter(|e| session_input(e)) {
+            self.write(&SessionLine::Input {
+                frame: frame - first,
+                ms,
+                event: event.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, line: &SessionLine) -> Result<()> {
+        serde_json::to_writer(&mut self.out, line)?;
+        self.out.write_all(b"\n")?;
+        // flushed every line so a crash during a show still leaves the inputs
+        // that led up to it
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub struct SessionReplay {
+    pub fps: i64,
+    pub settings: Option<(u32, Vec<u8>)>,
+    first_frame: Option<i64>,
+    inputs: VecDeque<(i64, GfxEvent)>,
+}
+
+impl SessionReplay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let fps = match lines.next() {
+            Some(line) => match serde_json::from_str(&line?)? {
+                SessionLine::Start { fps } => fps,
+                _ => bail!("Session doesn't start with a Start line"),
+            },
+            None => bail!("Session is empty"),
+        };
+        let mut replay = Self {
+            fps,
+            settings: None,
+            first_frame: None,
+            inputs: VecDeque::new(),
+        };
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            let Ok(parsed) = serde_json::from_str(&line) else {
+                warn!("Stopping session replay at unreadable line {}", n + 2);
+                break;
+            };
+            match parsed {
+                SessionLine::Start { .. } => {
+                    return Err(anyhow!("Session has a second Start at line {}", n + 2))
+                }
+                SessionLine::Settings { version, bytes } => {
+                    replay.settings.get_or_insert((version, bytes));
+                }
+                SessionLine::Input { frame, event, .. } => replay.inputs.push_back((frame, event)),
+            }
+        }
+        Ok(replay)
+    }
+
+    // The inputs due by this frame, counting from the first frame asked for
+    pub fn inputs(&mut self, frame: i64) -> Vec<GfxEvent> {
+        let first = *self.first_frame.get_or_insert(frame);
+        let mut due = vec![];
+        while let Some((at, _)) = self.inputs.front() {
+            if *at > frame - first {
+                break;
+            }
+            due.extend(self.inputs.pop_front().map(|(_, event)| event));
+        }
+        due
+    }
+
+    pub fn finished(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
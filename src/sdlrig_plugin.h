@@ -0,0 +1,45 @@
+#ifndef SDLRIG_PLUGIN_H
+#define SDLRIG_PLUGIN_H
+
+#include <stdint.h>
+
+// Native extensions loaded by viz --plugin. A plugin is a shared library
+// exporting sdlrig_plugin_init, which returns a table that stays valid until
+// shutdown is called. Strings are utf-8 and NUL terminated, args and data are
+// json. Calls come from the render thread or an asset loading thread, but
+// never two at once.
+
+#define SDLRIG_PLUGIN_ABI_VERSION 1
+
+struct sdlrig_plugin {
+  // the version the plugin was built against
+  uint32_t abi_version;
+  const char* name;
+  // handed back as the first argument of every call
+  void* state;
+  // NULL terminated lists of the PluginSpec and PluginAsset kinds it handles
+  const char* const* spec_kinds;
+  const char* const* asset_kinds;
+
+  // Any of these may be NULL. Those returning int32_t return 0 on success,
+  // and last_error then describes a failure.
+
+  // gpu_ctx is the host's struct gfx_lowlevel_gpu_ctx, see gfxlowlevel.h
+  int32_t (*render)(void* state, const char* kind, const char* args,
+                    int64_t frame, void* gpu_ctx);
+  // loading again under the same name replaces the asset
+  int32_t (*load_asset)(void* state, const char* kind, const char* name,
+                        const char* args);
+  void (*unload_asset)(void* state, const char* kind, const char* name);
+  // Called once a frame, a json array of {"kind": ..., "data": ...} objects
+  // or NULL when there's nothing new. It's the plugin's to free, the host is
+  // done with it by the next call.
+  const char* (*poll_events)(void* state);
+  const char* (*last_error)(void* state);
+  void (*shutdown)(void* state);
+};
+
+// NULL when the plugin can't work with the host's abi_version
+const struct sdlrig_plugin* sdlrig_plugin_init(uint32_t abi_version);
+
+#endif
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use regex::Regex;
+use tracing::warn;
 
 pub fn include_files<S: AsRef<str>, F: Fn(&dyn AsRef<str>) -> Option<String>>(
     shader: S,
@@ -30,7 +31,7 @@ fn include_files_recusive<S: AsRef<str>, F: Fn(&dyn AsRef<str>) -> Option<String
                 if let Some(include_source) = lookup(&include_name) {
                     output.push_str(&include_files_recusive(include_source, seen, lookup));
                 } else {
-                    eprintln!("Included file not found: {}", include_name);
+                    warn!("Included file not found: {}", include_name);
                 }
             }
             last = end;
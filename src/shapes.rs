@@ -0,0 +1,115 @@
+use crate::renderspec::{DrawShape, Shape};
+use std::f32::consts::TAU;
+
+const FLOATS_PER_VERTEX: usize = 6;
+
+// Tessellates a DrawShape into a triangle list of x, y, r, g, b, a vertices in
+// target pixels, ready for gfx_lowlevel_draw_triangles
+pub fn tessellate(spec: &DrawShape) -> Vec<f32> {
+    let mut out = vec![];
+    for shape in &spec.shapes {
+        let outline = outline(shape);
+        let closed = match shape {
+            Shape::Line { .. } => false,
+            Shape::Polyline { closed, .. } => *closed,
+            _ => true,
+        };
+        if let Some(fill) = spec.fill {
+            if closed && outline.len() >= 3 {
+                fill_convex(&mut out, &outline, fill);
+            }
+        }
+        if let Some(stroke) = spec.stroke {
+            stroke_outline(&mut out, &outline, closed, spec.stroke_width, stroke);
+        }
+    }
+
+    let t = spec.transform;
+    for v in out.chunks_exact_mut(FLOATS_PER_VERTEX) {
+        let (x, y) = (v[0], v[1]);
+        v[0] = t[0] * x + t[2] * y + t[4];
+        v[1] = t[1] * x + t[3] * y + t[5];
+    }
+    out
+}
+
+fn outline(shape: &Shape) -> Vec<(f32, f32)> {
+    match shape {
+        Shape::Rect { x, y, w, h } => vec![(*x, *y), (x + w, *y), (x + w, y + h), (*x, y + h)],
+        Shape::Line { from, to } => vec![*from, *to],
+        Shape::Circle { center, radius } => {
+            let segments = (radius.abs() * 0.5).clamp(16.0, 128.0) as usize;
+            (0..segments)
+                .map(|i| {
+                    let a = TAU * i as f32 / segments as f32;
+                    (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+                })
+                .collect()
+        }
+        Shape::Polyline { points, .. } => points.clone(),
+    }
+}
+
+fn push(out: &mut Vec<f32>, p: (f32, f32), color: [f32; 4]) {
+    out.extend_from_slice(&[p.0, p.1, color[0], color[1], color[2], color[3]]);
+}
+
+fn norm(color: (u8, u8, u8, u8)) -> [f32; 4] {
+    [
+        color.0 as f32 / 255.0,
+        color.1 as f32 / 255.0,
+        color.2 as f32 / 255.0,
+        color.3 as f32 / 255.0,
+    ]
+}
+
+fn fill_convex(out: &mut Vec<f32>, points: &[(f32, f32)], color: (u8, u8, u8, u8)) {
+    let color = norm(color);
+    for i in 1..points.len() - 1 {
+        push(out, points[0], color);
+        push(out, points[i], color);
+        push(out, points[i + 1], color);
+    }
+}
+
+// Each segment becomes a quad, joins are squared off by extending the ends
+fn stroke_outline(
+    out: &mut Vec<f32>,
+    points: &[(f32, f32)],
+    closed: bool,
+    width: f32,
+    color: (u8, u8, u8, u8),
+) {
+    if points.len() < 2 || width <= 0.0 {
+        return;
+    }
+    let color = norm(color);
+    let half = width / 2.0;
+    let count = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    for i in 0..count {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 {
+            continue;
+        }
+        let (ux, uy) = (dx / len * half, dy / len * half);
+        let (nx, ny) = (-uy, ux);
+        let a = (a.0 - ux, a.1 - uy);
+        let b = (b.0 + ux, b.1 + uy);
+        let quad = [
+            (a.0 + nx, a.1 + ny),
+            (b.0 + nx, b.1 + ny),
+            (b.0 - nx, b.1 - ny),
+            (a.0 - nx, a.1 - ny),
+        ];
+        for idx in [0, 1, 2, 0, 2, 3] {
+            push(out, quad[idx], color);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::dylib::Library;
+use crate::outputshare::{check_frame, ShareBackend, ShareSink};
+use anyhow::{bail, Result};
+use std::ffi::{c_char, CString};
+use std::path::Path;
+use tracing::info;
+
+// The first methods of SpoutLibrary.h's SPOUTLIBRARY, a C++ interface whose
+// vtable holds them in the order they're declared
+#[repr(C)]
+struct SpoutVtable {
+    set_sender_name: unsafe extern "system" fn(*mut Spout, *const c_char),
+    // unused, they hold their places
+    _set_sender_format: unsafe extern "system" fn(*mut Spout, u32),
+    release_sender: unsafe extern "system" fn(*mut Spout, u32),
+    _send_fbo: unsafe extern "system" fn(*mut Spout, u32, u32, u32, bool) -> bool,
+    _send_texture: unsafe extern "system" fn(*mut Spout, u32, u32, u32, u32, bool, u32) -> bool,
+    send_image: unsafe extern "system" fn(*mut Spout, *const u8, u32, u32, u32, bool, u32) -> bool,
+}
+
+#[repr(C)]
+struct Spout {
+    vtable: *const SpoutVtable,
+}
+
+type GetSpout = unsafe extern "system" fn() -> *mut Spout;
+
+const GL_RGBA: u32 = 0x1908;
+
+// SpoutLibrary.dll is loaded at runtime like the NDI runtime. Without an
+// OpenGL context on the thread it makes a hidden one of its own to share
+// frames through.
+pub struct SpoutSink {
+    // kept open while the sender is
+    _lib: Library,
+    spout: *mut Spout,
+}
+
+impl SpoutSink {
+    pub fn new(name: &str) -> Result<Self> {
+        if !cfg!(target_os = "windows") {
+            bail!("Spout is only on windows, try ndi");
+        }
+        let lib = Self::open_library()?;
+        let Some(get_spout) = lib.symbol("GetSpout")? else {
+            bail!("SpoutLibrary is missing GetSpout");
+        };
+        let c_name = CString::new(name)?;
+        unsafe {
+            let get_spout: GetSpout = std::mem::transmute(get_spout);
+            let spout = get_spout();
+            if spout.is_null() {
+                bail!("SpoutLibrary could not make a sender");
+            }
+            // the sender itself is made with the first frame
+            ((*(*spout).vtable).set_sender_name)(spout, c_name.as_ptr());
+
+            info!("Publishing Spout sender {}", name);
+            Ok(Self { _lib: lib, spout })
+        }
+    }
+
+    fn open_library() -> Result<Library> {
+        let mut candidates = vec![];
+        if let Ok(dir) = std::env::var("SPOUT_DIR") {
+            candidates.push(
+                Path::new(&dir)
+                    .join("SpoutLibrary.dll")
+                    .display()
+                    .to_string(),
+            );
+        }
+        candidates.push("SpoutLibrary.dll".to_string());
+
+        for candidate in &candidates {
+            if let Ok(lib) = Library::open(Path::new(candidate)) {
+                return Ok(lib);
+            }
+        }
+        bail!("Could not find SpoutLibrary.dll, tried {:?}", candidates)
+    }
+}
+
+impl ShareSink for SpoutSink {
+    fn publish(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+        check_frame(ShareBackend::Spout, width, height, rgba)?;
+        // the rows are top down, as the shared DirectX texture has them, so
+        // they aren't inverted
+        let sent = unsafe {
+            ((*(*self.spout).vtable).send_image)(
+                self.spout,
+                rgba.as_ptr(),
+                width,
+                height,
+                GL_RGBA,
+                false,
+                0,
+            )
+        };
+        if !sent {
+            bail!("Spout could not send a {}x{} frame", width, height);
+        }
+        Ok(())
+    }
+
+    // receivers take frames as they come
+    fn set_frames_per_sec(&mut self, _: i64) {}
+}
+
+impl Drop for SpoutSink {
+    fn drop(&mut self) {
+        unsafe {
+            ((*(*self.spout).vtable).release_sender)(self.spout, 0);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use crate::dylib::Library;
+use crate::outputshare::{check_frame, ShareBackend, ShareSink};
+use anyhow::{bail, Result};
+use std::ffi::{c_char, c_void, CString};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+type Id = *mut c_void;
+type Sel = *mut c_void;
+
+#[repr(C)]
+struct MtlRegion {
+    origin: [usize; 3],
+    size: [usize; 3],
+}
+
+#[repr(C)]
+struct NsRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+const MTL_PIXEL_FORMAT_RGBA8_UNORM: usize = 70;
+
+type ObjcGetClass = unsafe extern "C" fn(*const c_char) -> Id;
+type SelRegisterName = unsafe extern "C" fn(*const c_char) -> Sel;
+type PoolPush = unsafe extern "C" fn() -> *mut c_void;
+type PoolPop = unsafe extern "C" fn(*mut c_void);
+type MtlCreateSystemDefaultDevice = unsafe extern "C" fn() -> Id;
+
+// The objective-c runtime, messages are sent through objc_msgSend cast to
+// each method's signature
+struct Objc {
+    // kept open while its functions are used
+    _lib: Library,
+    get_class: ObjcGetClass,
+    register_name: SelRegisterName,
+    msg_send: *mut c_void,
+    pool_push: PoolPush,
+    pool_pop: PoolPop,
+}
+
+impl Objc {
+    fn open() -> Result<Self> {
+        let lib = Library::open(Path::new("/usr/lib/libobjc.A.dylib"))?;
+        unsafe {
+            let get_class: ObjcGetClass = std::mem::transmute(sym(&lib, "objc_getClass")?);
+            let register_name: SelRegisterName =
+                std::mem::transmute(sym(&lib, "sel_registerName")?);
+            let pool_push: PoolPush = std::mem::transmute(sym(&lib, "objc_autoreleasePoolPush")?);
+            let pool_pop: PoolPop = std::mem::transmute(sym(&lib, "objc_autoreleasePoolPop")?);
+            Ok(Self {
+                get_class,
+                register_name,
+                msg_send: sym(&lib, "objc_msgSend")?,
+                pool_push,
+                pool_pop,
+                _lib: lib,
+            })
+        }
+    }
+
+    fn class(&self, name: &str) -> Result<Id> {
+        let c_name = CString::new(name)?;
+        let class = unsafe { (self.get_class)(c_name.as_ptr()) };
+        if class.is_null() {
+            bail!("No objective-c class {}", name);
+        }
+        Ok(class)
+    }
+
+    fn sel(&self, name: &str) -> Sel {
+        let c_name = CString::new(name).unwrap();
+        unsafe { (self.register_name)(c_name.as_ptr()) }
+    }
+
+    // objc_msgSend as F, which must match the method's signature
+    unsafe fn send<F: Copy>(&self) -> F {
+        std::mem::transmute_copy(&self.msg_send)
+    }
+
+    unsafe fn call(&self, receiver: Id, sel: &str) -> Id {
+        self.send::<unsafe extern "C" fn(Id, Sel) -> Id>()(receiver, self.sel(sel))
+    }
+
+    unsafe fn tell(&self, receiver: Id, sel: &str) {
+        self.send::<unsafe extern "C" fn(Id, Sel)>()(receiver, self.sel(sel))
+    }
+}
+
+fn sym(lib: &Library, name: &str) -> Result<*mut c_void> {
+    match lib.symbol(name)? {
+        Some(ptr) => Ok(ptr),
+        None => bail!("Missing {}", name),
+    }
+}
+
+// A SyphonMetalServer, fed each frame through a Metal texture. Syphon.framework
+// is loaded at runtime like the NDI runtime, from SYPHON_DIR, next to the
+// executable or the usual Frameworks directories.
+pub struct SyphonSink {
+    objc: Objc,
+    // kept open while the server is
+    _metal: Library,
+    _syphon: Library,
+    device: Id,
+    queue: Id,
+    server: Id,
+    // remade when the frame size changes
+    texture: Option<(Id, u32, u32)>,
+    // the last publish, waited on before its texture is written again
+    in_flight: Option<Id>,
+}
+
+impl SyphonSink {
+    pub fn new(name: &str) -> Result<Self> {
+        if !cfg!(target_os = "macos") {
+            bail!("Syphon is only on macOS, try ndi");
+        }
+        let objc = Objc::open()?;
+        let metal = Library::open(Path::new(
+            "/System/Library/Frameworks/Metal.framework/Metal",
+        ))?;
+        let syphon = Self::open_framework()?;
+        unsafe {
+            let create_device: MtlCreateSystemDefaultDevice =
+                std::mem::transmute(sym(&metal, "MTLCreateSystemDefaultDevice")?);
+            let device = create_device();
+            if device.is_null() {
+                bail!("No Metal device for Syphon");
+            }
+            let queue = objc.call(device, "newCommandQueue");
+
+            let c_name = CString::new(name)?;
+            let init_string: unsafe extern "C" fn(Id, Sel, *const c_char) -> Id = objc.send();
+            let server_name = init_string(
+                objc.call(objc.class("NSString")?, "alloc"),
+                objc.sel("initWithUTF8String:"),
+                c_name.as_ptr(),
+            );
+            let init_server: unsafe extern "C" fn(Id, Sel, Id, Id, Id) -> Id = objc.send();
+            let server = init_server(
+                objc.call(objc.class("SyphonMetalServer")?, "alloc"),
+                objc.sel("initWithName:device:options:"),
+                server_name,
+                device,
+                std::ptr::null_mut(),
+            );
+            objc.tell(server_name, "release");
+            if server.is_null() {
+                objc.tell(queue, "release");
+                objc.tell(device, "release");
+                bail!("Could not start Syphon server {}", name);
+            }
+
+            info!("Publishing Syphon server {}", name);
+            Ok(Self {
+                objc,
+                _metal: metal,
+                _syphon: syphon,
+                device,
+                queue,
+                server,
+                texture: None,
+                in_flight: None,
+            })
+        }
+    }
+
+    fn open_framework() -> Result<Library> {
+        let mut dirs = vec![];
+        if let Ok(dir) = std::env::var("SYPHON_DIR") {
+            dirs.push(PathBuf::from(dir));
+        }
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            dirs.push(exe_dir.clone());
+            dirs.push(exe_dir.join("../Frameworks"));
+        }
+        if let Ok(home) = std::env::var("HOME") {
+            dirs.push(Path::new(&home).join("Library/Frameworks"));
+        }
+        dirs.push("/Library/Frameworks".into());
+
+        let candidates = dirs
+            .iter()
+            .map(|dir| dir.join("Syphon.framework/Syphon"))
+            .collect::<Vec<_>>();
+        for candidate in &candidates {
+            if let Ok(lib) = Library::open(candidate) {
+                return Ok(lib);
+            }
+        }
+        bail!("Could not find Syphon.framework, tried {:?}", candidates)
+    }
+
+    fn wait_in_flight(&mut self) {
+        if let Some(commands) = self.in_flight.take() {
+            unsafe {
+                self.objc.tell(commands, "waitUntilCompleted");
+                self.objc.tell(commands, "release");
+            }
+        }
+    }
+
+    unsafe fn texture(&mut self, width: u32, height: u32) -> Result<Id> {
+        match self.texture {
+            Some((texture, w, h)) if (w, h) == (width, height) => return Ok(texture),
+            Some((texture, _, _)) => {
+                self.objc.tell(texture, "release");
+                self.texture = None;
+            }
+            None => (),
+        }
+        let objc = &self.objc;
+        let describe: unsafe extern "C" fn(Id, Sel, usize, usize, usize, bool) -> Id = objc.send();
+        let descriptor = describe(
+            objc.class("MTLTextureDescriptor")?,
+            objc.sel("texture2DDescriptorWithPixelFormat:width:height:mipmapped:"),
+            MTL_PIXEL_FORMAT_RGBA8_UNORM,
+            width as usize,
+            height as usize,
+            false,
+        );
+        let new_texture: unsafe extern "C" fn(Id, Sel, Id) -> Id = objc.send();
+        let texture = new_texture(
+            self.device,
+            objc.sel("newTextureWithDescriptor:"),
+            descriptor,
+        );
+        if texture.is_null() {
+            bail!("Could not make a {}x{} texture for Syphon", width, height);
+        }
+        self.texture = Some((texture, width, height));
+        Ok(texture)
+    }
+
+    unsafe fn publish_texture(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+        let texture = self.texture(width, height)?;
+        let objc = &self.objc;
+        let replace: unsafe extern "C" fn(Id, Sel, MtlRegion, usize, *const c_void, usize) =
+            objc.send();
+        replace(
+            texture,
+            objc.sel("replaceRegion:mipmapLevel:withBytes:bytesPerRow:"),
+            MtlRegion {
+                origin: [0, 0, 0],
+                size: [width as usize, height as usize, 1],
+            },
+            0,
+            rgba.as_ptr() as *const c_void,
+            width as usize * 4,
+        );
+        let commands = objc.call(self.queue, "commandBuffer");
+        if commands.is_null() {
+            bail!("No Metal command buffer for Syphon");
+        }
+        // the rows are top down, as Metal has them, so not flipped
+        let publish: unsafe extern "C" fn(Id, Sel, Id, Id, NsRect, bool) = objc.send();
+        publish(
+            self.server,
+            objc.sel("publishFrameTexture:onCommandBuffer:imageRegion:flipped:"),
+            texture,
+            commands,
+            NsRect {
+                x: 0.0,
+                y: 0.0,
+                width: width as f64,
+                height: height as f64,
+            },
+            false,
+        );
+        objc.tell(commands, "commit");
+        // outlives the pool until it's waited on
+        self.in_flight = Some(objc.call(commands, "retain"));
+        Ok(())
+    }
+}
+
+impl ShareSink for SyphonSink {
+    fn publish(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+        check_frame(ShareBackend::Syphon, width, height, rgba)?;
+        // done with by now, it was committed a frame ago
+        self.wait_in_flight();
+        unsafe {
+            let pool = (self.objc.pool_push)();
+            let published = self.publish_texture(width, height, rgba);
+            (self.objc.pool_pop)(pool);
+            published
+        }
+    }
+
+    // clients take frames as they come
+    fn set_frames_per_sec(&mut self, _: i64) {}
+}
+
+impl Drop for SyphonSink {
+    fn drop(&mut self) {
+        self.wait_in_flight();
+        unsafe {
+            self.objc.tell(self.server, "stop");
+            self.objc.tell(self.server, "release");
+            if let Some((texture, _, _)) = self.texture.take() {
+                self.objc.tell(texture, "release");
+            }
+            self.objc.tell(self.queue, "release");
+            self.objc.tell(self.device, "release");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::gfxinfo::{TempoEvent, TempoSource};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// Taps further apart than this start a new count
+const TAP_TIMEOUT_SECS: f64 = 2.0;
+// Intervals averaged for the tapped tempo
+const TAPS: usize = 8;
+// Onsets kept for the audio tempo, and the seconds they're kept for
+const ONSETS: usize = 16;
+const ONSET_WINDOW_SECS: f64 = 8.0;
+// Onset intervals are halved or doubled into this range before the median
+const ONSET_MIN_BPM: f64 = 80.0;
+const ONSET_MAX_BPM: f64 = 160.0;
+// How far an onset pulls the beat towards the nearest whole one
+const ONSET_PULL: f64 = 0.25;
+
+// The one clock behind every beat synced effect. Runs free at its last tempo
+// and follows whichever of a midi clock, Link, taps or audio onsets last
+// drove it, the external clocks every frame they're playing.
+pub struct Tempo {
+    bpm: f64,
+    beat: f64,
+    source: TempoSource,
+    last_advance: Option<Instant>,
+    taps: VecDeque<Instant>,
+    onsets: VecDeque<Instant>,
+}
+
+impl Tempo {
+    pub fn new(bpm: f64) -> Self {
+        Self {
+            bpm,
+            beat: 0.0,
+            source: TempoSource::Free,
+            last_advance: None,
+            taps: VecDeque::new(),
+            onsets: VecDeque::new(),
+        }
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+
+    // From a reloaded --bpm, until something drives the tempo again
+    pub fn set_bpm(&mut self, bpm: f64) {
+        if bpm > 0.0 {
+            self.bpm = bpm;
+        }
+    }
+
+    // Whether anything has set the tempo, until then the app owns the beat
+    pub fn driven(&self) -> bool {
+        self.source != TempoSource::Free
+    }
+
+    // A tap lands on a beat, two or more set the tempo
+    pub fn tap(&mut self, now: Instant) {
+        if let Some(last) = self.taps.back() {
+            if now.duration_since(*last).as_secs_f64() > TAP_TIMEOUT_SECS {
+                self.taps.clear();
+            }
+        }
+        if self.taps.len() == TAPS + 1 {
+            self.taps.pop_front();
+        }
+        self.taps.push_back(now);
+        if let (Some(first), Some(last)) = (self.taps.front(), self.taps.back()) {
+            let intervals = self.taps.len() - 1;
+            if intervals > 0 {
+                let secs = last.duration_since(*first).as_secs_f64() / intervals as f64;
+                self.bpm = 60.0 / secs.max(1e-3);
+            }
+        }
+        self.beat = self.beat.round();
+        self.source = TempoSource::Tap;
+    }
+
+    // An audio onset, the tempo comes from the median gap between them
+    pub fn onset(&mut self, now: Instant) {
+        self.onsets
+            .retain(|o| now.duration_since(*o).as_secs_f64() <= ONSET_WINDOW_SECS);
+        if self.onsets.len() == ONSETS {
+            self.onsets.pop_front();
+        }
+        self.onsets.push_back(now);
+        let mut gaps = self
+            .onsets
+            .iter()
+            .zip(self.onsets.iter().skip(1))
+            .map(|(a, b)| fold_bpm(60.0 / b.duration_since(*a).as_secs_f64().max(1e-3)))
+            .collect::<Vec<_>>();
+        if gaps.len() < 3 {
+            return;
+        }
+        gaps.sort_by(f64::total_cmp);
+        self.bpm = gaps[gaps.len() / 2];
+        self.beat += (self.beat.round() - self.beat) * ONSET_PULL;
+        self.source = TempoSource::Audio;
+    }
+
+    // A midi clock or Link session that's playing
+    pub fn follow(&mut self, bpm: Option<f64>, beat: f64, source: TempoSource) {
+        if let Some(bpm) = bpm.filter(|bpm| *bpm > 0.0) {
+            self.bpm = bpm;
+        }
+        self.beat = beat;
+        self.source = source;
+        self.last_advance = None;
+    }
+
+    // Call once a frame, after any follow
+    pub fn advance(&mut self, now: Instant) {
+        if let Some(last) = self.last_advance {
+            self.beat += now.duration_since(last).as_secs_f64() * self.bpm / 60.0;
+        }
+        self.last_advance = Some(now);
+    }
+
+    pub fn event(&self) -> TempoEvent {
+        TempoEvent {
+            source: self.source,
+            millibpm: (self.bpm * 1000.0).round() as u32,
+            microbeats: (self.beat * 1_000_000.0).round() as i64,
+        }
+    }
+}
+
+fn fold_bpm(mut bpm: f64) -> f64 {
+    while bpm < ONSET_MIN_BPM {
+        bpm *= 2.0;
+    }
+    while bpm > ONSET_MAX_BPM {
+        bpm /= 2.0;
+    }
+    bpm
+}
@@ -0,0 +1,725 @@
+use anyhow::{bail, Result};
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const KTX2_MAGIC: &[u8] = &[
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+// Block compressed layouts a ktx2 or dds file can hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6h,
+    Bc7,
+    // block width and height
+    Astc(u32, u32),
+}
+
+// In VkFormat order, unorm then srgb for each
+const ASTC_BLOCKS: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+const VK_FORMAT_ASTC_4X4_UNORM_BLOCK: u32 = 157;
+
+impl BlockFormat {
+    pub fn block_size(&self) -> (u32, u32) {
+        match self {
+            BlockFormat::Astc(w, h) => (*w, *h),
+            _ => (4, 4),
+        }
+    }
+
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        *self == BlockFormat::Bc6h
+    }
+
+    // The unorm VkFormat, srgb files are sampled as is like rgba8 ones are
+    pub fn vk_format(&self) -> u32 {
+        match self {
+            BlockFormat::Bc1 => 133,
+            BlockFormat::Bc2 => 135,
+            BlockFormat::Bc3 => 137,
+            BlockFormat::Bc4 => 139,
+            BlockFormat::Bc5 => 141,
+            BlockFormat::Bc6h => 143,
+            BlockFormat::Bc7 => 145,
+            BlockFormat::Astc(w, h) => {
+                let i = ASTC_BLOCKS.iter().position(|b| *b == (*w, *h)).unwrap_or(0);
+                VK_FORMAT_ASTC_4X4_UNORM_BLOCK + 2 * i as u32
+            }
+        }
+    }
+
+    fn from_vk_format(vk_format: u32) -> Option<Self> {
+        Some(match vk_format {
+            131..=134 => BlockFormat::Bc1,
+            135 | 136 => BlockFormat::Bc2,
+            137 | 138 => BlockFormat::Bc3,
+            139 => BlockFormat::Bc4,
+            141 => BlockFormat::Bc5,
+            143 => BlockFormat::Bc6h,
+            145 | 146 => BlockFormat::Bc7,
+            157..=184 => {
+                let (w, h) = ASTC_BLOCKS[(vk_format - VK_FORMAT_ASTC_4X4_UNORM_BLOCK) as usize / 2];
+                BlockFormat::Astc(w, h)
+            }
+            _ => return None,
+        })
+    }
+
+    fn from_dxgi_format(dxgi_format: u32) -> Option<Self> {
+        Some(match dxgi_format {
+            70..=72 => BlockFormat::Bc1,
+            73..=75 => BlockFormat::Bc2,
+            76..=78 => BlockFormat::Bc3,
+            79 | 80 => BlockFormat::Bc4,
+            82 | 83 => BlockFormat::Bc5,
+            94 | 95 => BlockFormat::Bc6h,
+            97..=99 => BlockFormat::Bc7,
+            _ => return None,
+        })
+    }
+
+    fn from_fourcc(fourcc: &[u8]) -> Option<Self> {
+        Some(match fourcc {
+            b"DXT1" => BlockFormat::Bc1,
+            b"DXT2" | b"DXT3" => BlockFormat::Bc2,
+            b"DXT4" | b"DXT5" => BlockFormat::Bc3,
+            b"ATI1" | b"BC4U" => BlockFormat::Bc4,
+            b"ATI2" | b"BC5U" => BlockFormat::Bc5,
+            _ => return None,
+        })
+    }
+
+    fn level_len(&self, size: (u32, u32)) -> usize {
+        let (bw, bh) = self.block_size();
+        size.0.div_ceil(bw) as usize * size.1.div_ceil(bh) as usize * self.block_bytes()
+    }
+}
+
+// A compressed file's mip levels as stored, largest first
+#[derive(Clone, Debug)]
+pub struct CompressedTex {
+    pub format: BlockFormat,
+    pub size: (u32, u32),
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedTex {
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        let halve = |side: u32| side.checked_shr(level as u32).unwrap_or(0).max(1);
+        (halve(self.size.0), halve(self.size.1))
+    }
+
+    // Bytes from one row of blocks to the next in level
+    pub fn row_pitch(&self, level: usize) -> usize {
+        let (bw, _) = self.format.block_size();
+        self.level_size(level).0.div_ceil(bw) as usize * self.format.block_bytes()
+    }
+}
+
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(DDS_MAGIC) || bytes.starts_with(KTX2_MAGIC)
+}
+
+pub fn parse(bytes: &[u8]) -> Result<CompressedTex> {
+    if bytes.starts_with(KTX2_MAGIC) {
+        ktx2(bytes)
+    } else if bytes.starts_with(DDS_MAGIC) {
+        dds(bytes)
+    } else {
+        bail!("Neither a ktx2 nor a dds file")
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32> {
+    match bytes.get(at..at + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => bail!("Truncated at {}", at),
+    }
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> Result<u64> {
+    Ok(u32_at(bytes, at)? as u64 | (u32_at(bytes, at + 4)? as u64) << 32)
+}
+
+// Past 32 halvings every side of a u32 sized texture is 1
+const MAX_LEVELS: usize = 32;
+
+fn checked_level_count(level_count: usize) -> Result<usize> {
+    if level_count > MAX_LEVELS {
+        bail!(
+            "{} mip levels, there are never more than {}",
+            level_count,
+            MAX_LEVELS
+        );
+    }
+    Ok(level_count)
+}
+
+// The level's bytes at offset, when all of them are there
+fn level_at(bytes: &[u8], level: usize, offset: usize, len: usize) -> Result<&[u8]> {
+    match offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+    {
+        Some(data) => Ok(data),
+        None => bail!("Level {} is truncated", level),
+    }
+}
+
+fn checked_size(size: (u32, u32)) -> Result<(u32, u32)> {
+    if size.0 == 0 || size.1 == 0 {
+        bail!("{}x{} is no texture", size.0, size.1);
+    }
+    Ok(size)
+}
+
+// Only 2d textures, the first of any layers or faces
+fn ktx2(bytes: &[u8]) -> Result<CompressedTex> {
+    let vk_format = u32_at(bytes, 12)?;
+    let Some(format) = BlockFormat::from_vk_format(vk_format) else {
+        bail!("VkFormat {} is not block compressed", vk_format);
+    };
+    let size = checked_size((u32_at(bytes, 20)?, u32_at(bytes, 24)?))?;
+    if u32_at(bytes, 28)? > 1 {
+        bail!("3d textures aren't supported");
+    }
+    // basis and zstd supercompression need a transcoder
+    let supercompression = u32_at(bytes, 44)?;
+    if supercompression != 0 {
+        bail!(
+            "Supercompression scheme {} isn't supported",
+            supercompression
+        );
+    }
+    // 0 asks the loader to make the mips, the file has just the one
+    let level_count = checked_level_count(u32_at(bytes, 40)?.max(1) as usize)?;
+    let mut tex = CompressedTex {
+        format,
+        size,
+        levels: vec![],
+    };
+    for level in 0..level_count {
+        let index = 80 + level * 24;
+        let offset = usize::try_from(u64_at(bytes, index)?).unwrap_or(usize::MAX);
+        let len = format.level_len(tex.level_size(level));
+        tex.levels
+            .push(level_at(bytes, level, offset, len)?.to_vec());
+    }
+    Ok(tex)
+}
+
+const DDPF_FOURCC: u32 = 0x4;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+
+// Offsets count the magic, the header follows it
+fn dds(bytes: &[u8]) -> Result<CompressedTex> {
+    if u32_at(bytes, 4)? != 124 {
+        bail!("Bad dds header");
+    }
+    if u32_at(bytes, 80)? & DDPF_FOURCC == 0 {
+        bail!("Uncompressed dds files aren't supported");
+    }
+    let fourcc = &u32_at(bytes, 84)?.to_le_bytes();
+    let (format, mut offset) = if fourcc == b"DX10" {
+        let dxgi_format = u32_at(bytes, 128)?;
+        match BlockFormat::from_dxgi_format(dxgi_format) {
+            Some(format) => (format, 148),
+            None => bail!("DXGI format {} is not block compressed", dxgi_format),
+        }
+    } else {
+        match BlockFormat::from_fourcc(fourcc) {
+            Some(format) => (format, 128),
+            None => bail!(
+                "{} is not block compressed",
+                String::from_utf8_lossy(fourcc)
+            ),
+        }
+    };
+    let size = checked_size((u32_at(bytes, 16)?, u32_at(bytes, 12)?))?;
+    let level_count = match u32_at(bytes, 8)? & DDSD_MIPMAPCOUNT {
+        0 => 1,
+        _ => checked_level_count(u32_at(bytes, 28)?.max(1) as usize)?,
+    };
+    let mut tex = CompressedTex {
+        format,
+        size,
+        levels: vec![],
+    };
+    for level in 0..level_count {
+        let len = format.level_len(tex.level_size(level));
+        tex.levels
+            .push(level_at(bytes, level, offset, len)?.to_vec());
+        offset += len;
+    }
+    Ok(tex)
+}
+
+// The first level as tightly packed rgba8, for gpus that can't sample the
+// format. Channels a format doesn't have come out as the gpu gives them,
+// 0 for color and 255 for alpha.
+pub fn decode(tex: &CompressedTex) -> Result<Vec<u8>> {
+    let decode_block: fn(&[u8]) -> [[u8; 4]; 16] = match tex.format {
+        BlockFormat::Bc1 => |block| bc1(block, true),
+        BlockFormat::Bc2 => bc2,
+        BlockFormat::Bc3 => bc3,
+        BlockFormat::Bc4 => bc4,
+        BlockFormat::Bc5 => bc5,
+        BlockFormat::Bc7 => bc7,
+        format => bail!("{:?} needs a gpu that samples it", format),
+    };
+    let (w, h) = (tex.size.0 as usize, tex.size.1 as usize);
+    let block_bytes = tex.format.block_bytes();
+    let blocks_w = w.div_ceil(4);
+    let mut rgba = vec![0u8; w * h * 4];
+    for (i, block) in tex.levels[0].chunks_exact(block_bytes).enumerate() {
+        let (bx, by) = (i % blocks_w * 4, i / blocks_w * 4);
+        for (j, texel) in decode_block(block).iter().enumerate() {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < w && y < h {
+                rgba[(y * w + x) * 4..][..4].copy_from_slice(texel);
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+fn rgb565(c: u16) -> [u32; 3] {
+    let (r, g, b) = ((c >> 11) as u32 & 31, (c >> 5) as u32 & 63, c as u32 & 31);
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+// Bc2 and bc3 colors always use four of them, no punch through alpha
+fn bc1(block: &[u8], punch_through: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mut palette = [[0u8; 4]; 4];
+    for c in 0..3 {
+        palette[0][c] = e0[c] as u8;
+        palette[1][c] = e1[c] as u8;
+        if c0 > c1 || !punch_through {
+            palette[2][c] = ((2 * e0[c] + e1[c]) / 3) as u8;
+            palette[3][c] = ((e0[c] + 2 * e1[c]) / 3) as u8;
+        } else {
+            palette[2][c] = ((e0[c] + e1[c]) / 2) as u8;
+        }
+    }
+    palette[0][3] = 255;
+    palette[1][3] = 255;
+    palette[2][3] = 255;
+    if c0 > c1 || !punch_through {
+        palette[3][3] = 255;
+    }
+    std::array::from_fn(|i| palette[(indices >> (2 * i)) as usize & 3])
+}
+
+fn bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let mut texels = bc1(&block[8..], false);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * i)) & 15) as u8 * 17;
+    }
+    texels
+}
+
+// Bc3's alpha, bc4's red and bc5's red and green
+fn interpolated_channel(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 2..8 {
+            palette[i] = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for i in 2..6 {
+            palette[i] = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[(indices >> (3 * i)) as usize & 7] as u8)
+}
+
+fn bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = interpolated_channel(&block[..8]);
+    let mut texels = bc1(&block[8..], false);
+    for (texel, a) in texels.iter_mut().zip(alpha) {
+        texel[3] = a;
+    }
+    texels
+}
+
+fn bc4(block: &[u8]) -> [[u8; 4]; 16] {
+    let red = interpolated_channel(block);
+    std::array::from_fn(|i| [red[i], 0, 0, 255])
+}
+
+fn bc5(block: &[u8]) -> [[u8; 4]; 16] {
+    let red = interpolated_channel(&block[..8]);
+    let green = interpolated_channel(&block[8..]);
+    std::array::from_fn(|i| [red[i], green[i], 0, 255])
+}
+
+struct Bits<'a> {
+    block: &'a [u8],
+    at: usize,
+}
+
+impl Bits<'_> {
+    // lsb first across the whole block
+    fn read(&mut self, n: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..n {
+            let bit = self.block[self.at / 8] >> (self.at % 8) & 1;
+            value |= (bit as u32) << i;
+            self.at += 1;
+        }
+        value
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+}
+
+macro_rules! bc7_mode {
+    ($ns:expr, $pb:expr, $rb:expr, $isb:expr, $cb:expr, $ab:expr, $epb:expr, $spb:expr, $ib:expr, $ib2:expr) => {
+        Bc7Mode {
+            subsets: $ns,
+            partition_bits: $pb,
+            rotation_bits: $rb,
+            index_selection_bits: $isb,
+            color_bits: $cb,
+            alpha_bits: $ab,
+            endpoint_pbits: $epb,
+            shared_pbits: $spb,
+            index_bits: $ib,
+            index2_bits: $ib2,
+        }
+    };
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode!(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode!(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode!(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode!(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode!(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode!(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode!(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode!(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+// A bit per texel, set for subset 1
+const BC7_PARTITIONS2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// Two bits per texel, its subset
+const BC7_PARTITIONS3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+// Where each subset after the first has its anchor, stored a bit short
+const BC7_ANCHORS2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+const BC7_ANCHORS3_1: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+const BC7_ANCHORS3_2: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+fn bc7_weight(bits: u32, index: u32) -> u32 {
+    const WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+    const WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+    const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+    match bits {
+        2 => WEIGHTS2[index as usize],
+        3 => WEIGHTS3[index as usize],
+        _ => WEIGHTS4[index as usize],
+    }
+}
+
+fn bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    let mode_index = block[0].trailing_zeros() as usize;
+    // reserved, decoders give transparent black
+    let Some(mode) = BC7_MODES.get(mode_index) else {
+        return [[0; 4]; 16];
+    };
+    let mut bits = Bits {
+        block,
+        at: mode_index + 1,
+    };
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // [subset][end][channel]
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for c in 0..4 {
+        let n = if c < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for end in subset.iter_mut() {
+                end[c] = bits.read(n);
+            }
+        }
+    }
+    let mut pbits = [[0u32; 2]; 3];
+    for subset in pbits.iter_mut().take(mode.subsets) {
+        if mode.endpoint_pbits {
+            *subset = [bits.read(1), bits.read(1)];
+        } else if mode.shared_pbits {
+            let p = bits.read(1);
+            *subset = [p, p];
+        }
+    }
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for s in 0..mode.subsets {
+        for e in 0..2 {
+            for c in 0..4 {
+                let mut n = if c < 3 {
+                    mode.color_bits
+                } else {
+                    mode.alpha_bits
+                };
+                if n == 0 {
+                    endpoints[s][e][c] = 255;
+                    continue;
+                }
+                let mut v = endpoints[s][e][c];
+                if has_pbits {
+                    v = v << 1 | pbits[s][e];
+                    n += 1;
+                }
+                endpoints[s][e][c] = v << (8 - n) | v >> (2 * n - 8);
+            }
+        }
+    }
+
+    let subset_of = |i: usize| match mode.subsets {
+        2 => (BC7_PARTITIONS2[partition] >> i) as usize & 1,
+        3 => (BC7_PARTITIONS3[partition] >> (2 * i)) as usize & 3,
+        _ => 0,
+    };
+    let is_anchor = |i: usize| {
+        i == 0
+            || mode.subsets == 2 && i == BC7_ANCHORS2[partition] as usize
+            || mode.subsets == 3
+                && (i == BC7_ANCHORS3_1[partition] as usize
+                    || i == BC7_ANCHORS3_2[partition] as usize)
+    };
+    let mut indices = [0u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = bits.read(mode.index_bits - is_anchor(i) as u32);
+    }
+    let mut indices2 = [0u32; 16];
+    if mode.index2_bits > 0 {
+        for (i, index) in indices2.iter_mut().enumerate() {
+            *index = bits.read(mode.index2_bits - (i == 0) as u32);
+        }
+    }
+
+    std::array::from_fn(|i| {
+        let [e0, e1] = endpoints[subset_of(i)];
+        // modes 4 and 5 index alpha separately, 4 can swap which set is which
+        let ((color_bits, color_index), (alpha_bits, alpha_index)) = match mode.index2_bits {
+            0 => ((mode.index_bits, indices[i]), (mode.index_bits, indices[i])),
+            _ if index_selection == 0 => (
+                (mode.index_bits, indices[i]),
+                (mode.index2_bits, indices2[i]),
+            ),
+            _ => (
+                (mode.index2_bits, indices2[i]),
+                (mode.index_bits, indices[i]),
+            ),
+        };
+        let mut texel = [0u8; 4];
+        for c in 0..4 {
+            let w = match c {
+                3 => bc7_weight(alpha_bits, alpha_index),
+                _ => bc7_weight(color_bits, color_index),
+            };
+            texel[c] = (((64 - w) * e0[c] + w * e1[c] + 32) >> 6) as u8;
+        }
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => (),
+        }
+        texel
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u32(bytes: &mut [u8], at: usize, value: u32) {
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // An 8x4 DXT1 file with the given mip count and data after its header
+    fn dds_file(levels: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0u8; 128];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        put_u32(&mut bytes, 4, 124);
+        put_u32(&mut bytes, 8, DDSD_MIPMAPCOUNT);
+        put_u32(&mut bytes, 12, 4);
+        put_u32(&mut bytes, 16, 8);
+        put_u32(&mut bytes, 28, levels);
+        put_u32(&mut bytes, 80, DDPF_FOURCC);
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn ktx2_file(vk_format: u32, size: (u32, u32), levels: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = vec![0u8; 80 + levels.len() * 24];
+        bytes[..12].copy_from_slice(KTX2_MAGIC);
+        put_u32(&mut bytes, 12, vk_format);
+        put_u32(&mut bytes, 20, size.0);
+        put_u32(&mut bytes, 24, size.1);
+        put_u32(&mut bytes, 40, levels.len() as u32);
+        for (i, (offset, len)) in levels.iter().enumerate() {
+            bytes[80 + i * 24..][..8].copy_from_slice(&offset.to_le_bytes());
+            bytes[88 + i * 24..][..8].copy_from_slice(&len.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn dds_levels() {
+        // 8x4 is two blocks, 4x2 and 2x1 are one each
+        let data: Vec<u8> = (0..32).collect();
+        let tex = parse(&dds_file(3, &data)).unwrap();
+        assert_eq!(tex.format, BlockFormat::Bc1);
+        assert_eq!(tex.size, (8, 4));
+        assert_eq!(tex.levels.len(), 3);
+        assert_eq!(tex.levels[0], &data[..16]);
+        assert_eq!(tex.levels[1], &data[16..24]);
+        assert_eq!(tex.levels[2], &data[24..32]);
+        assert_eq!(tex.level_size(2), (2, 1));
+        assert_eq!(tex.row_pitch(0), 16);
+        assert_eq!(tex.level_size(usize::MAX), (1, 1));
+    }
+
+    #[test]
+    fn dds_truncated() {
+        assert!(parse(&dds_file(3, &[0; 31])).is_err());
+        assert!(parse(&dds_file(1, &[0; 15])).is_err());
+        assert!(parse(&dds_file(1, &[])[..100]).is_err());
+    }
+
+    #[test]
+    fn too_many_levels() {
+        assert!(parse(&dds_file(33, &[0; 1024])).is_err());
+        let bytes = ktx2_file(VK_FORMAT_ASTC_4X4_UNORM_BLOCK, (4, 4), &[(0, 16); 33]);
+        assert!(parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn ktx2_offsets() {
+        let mut bytes = ktx2_file(131, (4, 4), &[(104, 8)]);
+        bytes.extend_from_slice(&[9; 8]);
+        let tex = parse(&bytes).unwrap();
+        assert_eq!(tex.format, BlockFormat::Bc1);
+        assert_eq!(tex.levels, vec![vec![9; 8]]);
+
+        // offsets that run off the end or overflow are errors, not panics
+        for offset in [105, u64::MAX - 4, u64::MAX] {
+            let mut bytes = ktx2_file(131, (4, 4), &[(offset, 8)]);
+            bytes.extend_from_slice(&[9; 8]);
+            assert!(parse(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn not_a_texture() {
+        assert!(!is_container(b"\x89PNG"));
+        assert!(parse(b"DDS").is_err());
+        assert!(parse(&ktx2_file(131, (0, 4), &[(0, 0)])).is_err());
+        assert!(parse(&ktx2_file(37, (4, 4), &[(0, 0)])).is_err());
+    }
+
+    #[test]
+    fn bc1_decode() {
+        // red and blue endpoints, texels walking the four palette entries
+        let mut block = vec![0x00, 0xf8, 0x1f, 0x00];
+        block.extend_from_slice(&0xe4e4_e4e4u32.to_le_bytes());
+        let tex = CompressedTex {
+            format: BlockFormat::Bc1,
+            size: (2, 1),
+            levels: vec![block],
+        };
+        let rgba = decode(&tex).unwrap();
+        assert_eq!(rgba, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+        // c0 <= c1 has a transparent black fourth entry
+        let mut block = vec![0x1f, 0x00, 0x00, 0xf8];
+        block.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        assert_eq!(bc1(&block, true)[0], [0, 0, 0, 0]);
+        assert_eq!(bc1(&block, false)[0], [170, 0, 85, 255]);
+    }
+}
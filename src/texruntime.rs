@@ -0,0 +1,829 @@
+use crate::gfx_lowlevel::bindings::{gfx_lowlevel_compressed_supported, gfx_lowlevel_gpu_ctx};
+use crate::gfxinfo::{Tex, TexInfo, TexOrder, TexRaster, TexSlice};
+use crate::glob::{glob_matches, is_pattern, natural_cmp, GlobMatch};
+use crate::texblocks::{self, CompressedTex};
+use crate::vidruntime::WrapFrame;
+use anyhow::{anyhow, bail, Result};
+use ffmpeg_next::{
+    codec, decoder,
+    format::{self, Pixel},
+    frame, media,
+    software::scaling,
+};
+use lazy_static::lazy_static;
+use sdl2::image::ImageRWops;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
+use sdl2::sys::{SDL_RWops, SDL_Surface};
+use std::cell::{Cell, RefCell};
+use std::ffi::c_int;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+// SDL_image 2.6, the sdl2 crate doesn't bind it
+extern "C" {
+    fn IMG_LoadSizedSVG_RW(src: *mut SDL_RWops, width: c_int, height: c_int) -> *mut SDL_Surface;
+}
+
+lazy_static! {
+    // the output's drawable size, what TexRaster::Output fits svgs to
+    static ref OUTPUT_SIZE: RwLock<Option<(u32, u32)>> = RwLock::new(None);
+    // how much decoded Tex frames may take, the least recently drawn are
+    // dropped beyond it
+    static ref MEMORY_BUDGET: RwLock<usize> = RwLock::new(DEFAULT_MEMORY_BUDGET);
+}
+
+const DEFAULT_MEMORY_BUDGET: usize = 1 << 30;
+const MAX_DECODE_WORKERS: usize = 8;
+
+// Browsers show gifs asking for less than this at 100ms, files are made for that
+const MIN_DELAY_MS: u32 = 20;
+const DEFAULT_DELAY_MS: u32 = 100;
+
+// Tightly packed rgba rows
+pub enum TexPixels {
+    Rgba8(Vec<u8>),
+    // linear, from exr and hdr files, kept unclamped
+    RgbaF32(Vec<f32>),
+    // ktx2 and dds files, blocks as stored with the file's own mips
+    Compressed(CompressedTex),
+}
+
+pub struct TexFrame {
+    pub size: (u32, u32),
+    pub pixels: TexPixels,
+}
+
+impl TexFrame {
+    pub fn bytes(&self) -> usize {
+        match &self.pixels {
+            TexPixels::Rgba8(pixels) => pixels.len(),
+            TexPixels::RgbaF32(pixels) => std::mem::size_of_val(pixels.as_slice()),
+            TexPixels::Compressed(compressed) => compressed.levels.iter().map(Vec::len).sum(),
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        match &self.pixels {
+            TexPixels::RgbaF32(_) => true,
+            TexPixels::Compressed(compressed) => compressed.format.is_float(),
+            TexPixels::Rgba8(_) => false,
+        }
+    }
+}
+
+// A Tex asset's frames, decoded when it loads. Animated gif, png and webp files
+// give a frame each with its delay in the info. Exr and hdr files give float
+// frames, a Tex can't mix them with 8-bit ones. Ktx2 and dds files keep their
+// blocks for gpus that sample them and are decoded for the ones that don't.
+// Frames can be dropped to stay in the memory budget, they're decoded from
+// their file again if they're wanted after.
+pub struct TexData {
+    pub info: TexInfo,
+    source: Tex,
+    // what the path matched when loaded, in glob order
+    matched: Vec<GlobMatch>,
+    frames: Vec<TexSlot>,
+}
+
+struct TexSlot {
+    frame: RefCell<Option<Arc<TexFrame>>>,
+    // the render frame it was last drawn on, -1 before that
+    last_used: Cell<i64>,
+}
+
+impl std::fmt::Debug for TexData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TexData")
+            .field("name", &self.info.name)
+            .field("frames", &self.frames.len())
+            .finish()
+    }
+}
+
+impl TexData {
+    pub fn load(tex: &Tex) -> Result<Self> {
+        let matched = match_files(tex)?;
+        let mut files = matched.clone();
+        order(&mut files, &tex.order)?;
+        if files.is_empty() {
+            bail!("Nothing loaded for {}", tex.name);
+        }
+        let paths = files.into_iter().map(|file| file.path).collect::<Vec<_>>();
+
+        let mut frames = vec![];
+        let mut files = vec![];
+        let mut delays_ms = vec![];
+        for (path, decoded) in paths.iter().zip(decode_files(&paths, tex)) {
+            for (frame, delay_ms) in decoded? {
+                frames.push(frame);
+                files.push(path.clone());
+                delays_ms.push(delay_ms);
+            }
+        }
+        let hdr = frames[0].is_float();
+        if let Some(i) = frames.iter().position(|frame| frame.is_float() != hdr) {
+            bail!(
+                "{} mixes float and 8-bit files, {} and {}",
+                tex.name,
+                files[0],
+                files[i]
+            );
+        }
+        Ok(Self {
+            info: TexInfo {
+                name: tex.name.clone(),
+                count: frames.len(),
+                size: frames[0].size,
+                path: tex.path.clone(),
+                order: tex.order.clone(),
+                frames: files,
+                variants: tex.variants.clone(),
+                delays_ms,
+                hdr,
+                raster: tex.raster.clone(),
+                load: tex.load.clone(),
+                mipmaps: tex.mipmaps,
+                slice: tex.slice.clone(),
+                ignore: tex.ignore.clone(),
+            },
+            source: tex.clone(),
+            matched,
+            frames: frames
+                .into_iter()
+                .map(|frame| TexSlot {
+                    frame: RefCell::new(Some(Arc::new(frame))),
+                    last_used: Cell::new(-1),
+                })
+                .collect(),
+        })
+    }
+
+    // Whether files were added to or removed from what the path matches, or
+    // written to, since loading
+    pub fn stale(&self) -> bool {
+        match_files(&self.source).map_or(true, |matched| matched != self.matched)
+    }
+
+    // Frame index as of render frame now, decoding its file again if it was
+    // dropped
+    pub fn frame(&self, index: usize, now: i64) -> Result<Arc<TexFrame>> {
+        let Some(slot) = self.frames.get(index) else {
+            bail!(
+                "{} has {} frames, no frame {}",
+                self.info.name,
+                self.info.count,
+                index
+            );
+        };
+        slot.last_used.set(now);
+        if let Some(frame) = slot.frame.borrow().as_ref() {
+            return Ok(frame.clone());
+        }
+        // where the run of frames from the same file starts, animations and
+        // sheets give more than one
+        let file = &self.info.frames[index];
+        let first = (0..index)
+            .rev()
+            .take_while(|&i| self.info.frames[i] == *file)
+            .last()
+            .unwrap_or(index);
+        let mut decoded = decode_file(file, &self.source)?;
+        if decoded.is_empty() {
+            bail!("{} has no frames now", file);
+        }
+        let at = (index - first) % decoded.len();
+        let frame = Arc::new(decoded.swap_remove(at).0);
+        *slot.frame.borrow_mut() = Some(frame.clone());
+        Ok(frame)
+    }
+
+    // Frames still in memory as (index, last drawn, bytes)
+    pub fn resident(&self) -> Vec<(usize, i64, usize)> {
+        self.frames
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let bytes = slot.frame.borrow().as_ref()?.bytes();
+                Some((i, slot.last_used.get(), bytes))
+            })
+            .collect()
+    }
+
+    pub fn evict(&self, index: usize) {
+        if let Some(slot) = self.frames.get(index) {
+            slot.frame.borrow_mut().take();
+        }
+    }
+}
+
+// What one file gives, its frames cut up when the Tex is a sprite sheet
+fn decode_file(path: &str, tex: &Tex) -> Result<Vec<(TexFrame, u32)>> {
+    let bytes = fs::read(path).map_err(|e| anyhow!("Could not read {}: {}", path, e))?;
+    let decoded = if texblocks::is_container(&bytes) {
+        texblocks::parse(&bytes).map(|compressed| {
+            let frame = TexFrame {
+                size: compressed.size,
+                pixels: TexPixels::Compressed(compressed),
+            };
+            vec![(frame, 0)]
+        })
+    } else if high_dynamic_range(path, &bytes) {
+        decode_float(path).map(|frame| vec![(frame, 0)])
+    } else if svg(path, &bytes) {
+        rasterize(&bytes, &tex.raster).map(|frame| vec![(frame, 0)])
+    } else if animated(&bytes) {
+        decode_animation(path)
+    } else {
+        decode_still(&bytes).map(|frame| vec![(frame, 0)])
+    };
+    let decoded = decoded.map_err(|e| anyhow!("Could not decode {}: {}", path, e))?;
+    let Some(grid) = &tex.slice else {
+        return Ok(decoded);
+    };
+    let mut cells = vec![];
+    for (frame, delay_ms) in decoded {
+        let sliced = slice(&frame, grid).map_err(|e| anyhow!("Could not slice {}: {}", path, e))?;
+        cells.extend(sliced.into_iter().map(|cell| (cell, delay_ms)));
+    }
+    Ok(cells)
+}
+
+// Each of paths decoded on a pool of workers, in the order of paths
+fn decode_files(paths: &[String], tex: &Tex) -> Vec<Result<Vec<(TexFrame, u32)>>> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, MAX_DECODE_WORKERS)
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let decoded = paths.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else {
+                    break;
+                };
+                *decoded[i].lock().unwrap() = Some(decode_file(path, tex));
+            });
+        }
+    });
+    decoded
+        .into_iter()
+        .map(|d| d.into_inner().unwrap().unwrap())
+        .collect()
+}
+
+// Applies from the next eviction pass
+pub fn set_memory_budget(bytes: usize) {
+    *MEMORY_BUDGET.write().unwrap() = bytes;
+}
+
+pub fn memory_budget() -> usize {
+    *MEMORY_BUDGET.read().unwrap()
+}
+
+// A Tex frame on the gpu, with its mip chain when the Tex asked for one
+pub struct GpuTex {
+    pub size: (u32, u32),
+    levels: Vec<Arc<WrapFrame>>,
+}
+
+impl GpuTex {
+    pub fn upload(
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        frame: &TexFrame,
+        mipmaps: bool,
+    ) -> Result<Self> {
+        let (w, h) = frame.size;
+        let base = WrapFrame::new(lowlevel_ctx);
+        match &frame.pixels {
+            TexPixels::Compressed(compressed)
+                if unsafe {
+                    gfx_lowlevel_compressed_supported(lowlevel_ctx, compressed.format.vk_format())
+                } =>
+            {
+                return Self::upload_compressed(lowlevel_ctx, compressed, mipmaps);
+            }
+            // decoded every upload, the cache keeps it to the first
+            TexPixels::Compressed(compressed) => {
+                let rgba = texblocks::decode(compressed)?;
+                base.upload_pixels(lowlevel_ctx, &rgba, w, h, false)?
+            }
+            TexPixels::Rgba8(pixels) => base.upload_pixels(lowlevel_ctx, pixels, w, h, false)?,
+            TexPixels::RgbaF32(pixels) => {
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(
+                        pixels.as_ptr() as *const u8,
+                        std::mem::size_of_val(pixels.as_slice()),
+                    )
+                };
+                base.upload_pixels(lowlevel_ctx, bytes, w, h, true)?
+            }
+        }
+        let mut levels = vec![Arc::new(base)];
+        let (mut w, mut h) = (w, h);
+        while mipmaps && (w > 1 || h > 1) {
+            levels.push(Arc::new(levels.last().unwrap().halve(lowlevel_ctx)?));
+            (w, h) = ((w / 2).max(1), (h / 2).max(1));
+        }
+        Ok(Self {
+            size: frame.size,
+            levels,
+        })
+    }
+
+    // Blocks can't be blitted, so the mips are the ones the file came with
+    fn upload_compressed(
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        compressed: &CompressedTex,
+        mipmaps: bool,
+    ) -> Result<Self> {
+        let count = if mipmaps { compressed.levels.len() } else { 1 };
+        let mut levels = vec![];
+        for (i, blocks) in compressed.levels.iter().take(count).enumerate() {
+            let (w, h) = compressed.level_size(i);
+            let level = WrapFrame::new(lowlevel_ctx);
+            level.upload_compressed(
+                lowlevel_ctx,
+                compressed.format.vk_format(),
+                blocks,
+                compressed.row_pitch(i),
+                w,
+                h,
+            )?;
+            levels.push(Arc::new(level));
+        }
+        Ok(Self {
+            size: compressed.size,
+            levels,
+        })
+    }
+
+    // The smallest level still covering size, sampling down from there is
+    // less than halving so it doesn't alias
+    pub fn level(&self, size: (u32, u32)) -> &Arc<WrapFrame> {
+        let (mut w, mut h) = self.size;
+        let mut level = 0;
+        while level + 1 < self.levels.len() && w / 2 >= size.0 && h / 2 >= size.1 {
+            (w, h) = (w / 2, h / 2);
+            level += 1;
+        }
+        &self.levels[level]
+    }
+}
+
+impl std::fmt::Debug for GpuTex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuTex")
+            .field("size", &self.size)
+            .field("levels", &self.levels.len())
+            .finish()
+    }
+}
+
+// The cells of a sprite sheet, an animated sheet's delay goes to each of them
+fn slice(sheet: &TexFrame, grid: &TexSlice) -> Result<Vec<TexFrame>> {
+    if grid.cols == 0 || grid.rows == 0 {
+        bail!("{}x{} is no grid", grid.cols, grid.rows);
+    }
+    let (w, h) = sheet.size;
+    let gaps = (
+        grid.padding * (grid.cols - 1),
+        grid.padding * (grid.rows - 1),
+    );
+    let cell = (
+        w.saturating_sub(gaps.0) / grid.cols,
+        h.saturating_sub(gaps.1) / grid.rows,
+    );
+    if cell.0 == 0 || cell.1 == 0 {
+        bail!(
+            "{}x{} is too small for a {}x{} grid",
+            w,
+            h,
+            grid.cols,
+            grid.rows
+        );
+    }
+    // cells don't have to line up with blocks, so sheets are cut decoded
+    let decoded = match &sheet.pixels {
+        TexPixels::Compressed(compressed) => Some(texblocks::decode(compressed)?),
+        _ => None,
+    };
+    let mut cells = vec![];
+    for row in 0..grid.rows {
+        for col in 0..grid.cols {
+            let at = (col * (cell.0 + grid.padding), row * (cell.1 + grid.padding));
+            let pixels = match &sheet.pixels {
+                TexPixels::Rgba8(pixels) => TexPixels::Rgba8(cut(pixels, w, at, cell)),
+                TexPixels::RgbaF32(pixels) => TexPixels::RgbaF32(cut(pixels, w, at, cell)),
+                TexPixels::Compressed(_) => {
+                    TexPixels::Rgba8(cut(decoded.as_deref().unwrap_or_default(), w, at, cell))
+                }
+            };
+            cells.push(TexFrame { size: cell, pixels });
+        }
+    }
+    Ok(cells)
+}
+
+// The size rectangle at at out of rgba rows width pixels long
+fn cut<T: Copy>(pixels: &[T], width: u32, at: (u32, u32), size: (u32, u32)) -> Vec<T> {
+    let mut out = Vec::with_capacity(size.0 as usize * size.1 as usize * 4);
+    for y in at.1..at.1 + size.1 {
+        let start = (y as usize * width as usize + at.0 as usize) * 4;
+        out.extend_from_slice(&pixels[start..start + size.0 as usize * 4]);
+    }
+    out
+}
+
+// Applies from the next load, GfxRuntime::rasterize_for_output loads the ones
+// following it again
+pub fn set_output_size(size: (u32, u32)) {
+    *OUTPUT_SIZE.write().unwrap() = Some(size);
+}
+
+// Any Tex frame read from an svg
+pub fn is_svg(info: &TexInfo) -> bool {
+    info.frames.iter().any(|path| has_extension(path, &["svg"]))
+}
+
+fn has_extension(path: &str, wanted: &[&str]) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| {
+            wanted.iter().any(|want| ext.eq_ignore_ascii_case(want))
+        })
+}
+
+fn decode_still(bytes: &[u8]) -> Result<TexFrame> {
+    let surface = RWops::from_bytes(bytes)
+        .and_then(|rwops| rwops.load())
+        .map_err(|e| anyhow!(e))?;
+    to_frame(surface)
+}
+
+fn svg(path: &str, bytes: &[u8]) -> bool {
+    has_extension(path, &["svg"])
+        || bytes[..bytes.len().min(1024)]
+            .windows(4)
+            .any(|w| w == b"<svg")
+}
+
+fn rasterize(bytes: &[u8], raster: &TexRaster) -> Result<TexFrame> {
+    let native = RWops::from_bytes(bytes)
+        .and_then(|rwops| rwops.load())
+        .map_err(|e| anyhow!(e))?;
+    let (width, height) = match raster {
+        TexRaster::Native => return to_frame(native),
+        TexRaster::Size(width, height) => (*width, *height),
+        TexRaster::Output => match *OUTPUT_SIZE.read().unwrap() {
+            Some(output) => fit(native.size(), output),
+            None => return to_frame(native),
+        },
+    };
+    let rwops = RWops::from_bytes(bytes).map_err(|e| anyhow!(e))?;
+    let raw = unsafe { IMG_LoadSizedSVG_RW(rwops.raw(), width as c_int, height as c_int) };
+    if raw.is_null() {
+        bail!("{}", sdl2::get_error());
+    }
+    to_frame(unsafe { Surface::from_ll(raw) })
+}
+
+// The largest size with the same aspect that fits inside
+fn fit(size: (u32, u32), within: (u32, u32)) -> (u32, u32) {
+    let scale = f64::min(
+        within.0 as f64 / size.0.max(1) as f64,
+        within.1 as f64 / size.1.max(1) as f64,
+    );
+    (
+        ((size.0 as f64 * scale).round() as u32).max(1),
+        ((size.1 as f64 * scale).round() as u32).max(1),
+    )
+}
+
+fn to_frame(surface: Surface) -> Result<TexFrame> {
+    let surface = surface
+        .convert_format(PixelFormatEnum::RGBA32)
+        .map_err(|e| anyhow!(e))?;
+    let (width, height) = surface.size();
+    let pitch = surface.pitch() as usize;
+    let pixels = surface
+        .without_lock()
+        .ok_or_else(|| anyhow!("Surface needs locking"))?;
+    Ok(TexFrame {
+        size: (width, height),
+        pixels: TexPixels::Rgba8(tightly_packed(pixels, pitch, width, height)),
+    })
+}
+
+// By extension too, radiance files don't all start with the #? header
+fn high_dynamic_range(path: &str, bytes: &[u8]) -> bool {
+    has_extension(path, &["exr", "hdr"])
+        || bytes.starts_with(&[0x76, 0x2f, 0x31, 0x01])
+        || bytes.starts_with(b"#?RADIANCE")
+        || bytes.starts_with(b"#?RGBE")
+}
+
+// The first frame as rgba floats, from the planar formats ffmpeg decodes exr
+// and hdr to
+fn decode_float(path: &str) -> Result<TexFrame> {
+    let (mut ictx, index, _, mut decoder) = open(path)?;
+    let mut decoded = frame::Video::empty();
+    let mut received = false;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        if decoder.receive_frame(&mut decoded).is_ok() {
+            received = true;
+            break;
+        }
+    }
+    if !received {
+        decoder.send_eof()?;
+        if decoder.receive_frame(&mut decoded).is_err() {
+            bail!("No frames decoded");
+        }
+    }
+
+    // gbr order, with alpha last where there is one
+    let (planes, alpha) = match decoder.format() {
+        Pixel::GBRPF32LE => ([2, 0, 1], None),
+        Pixel::GBRAPF32LE => ([2, 0, 1], Some(3)),
+        Pixel::GRAYF32LE => ([0, 0, 0], None),
+        other => bail!("{:?} is not a float format", other),
+    };
+    let (width, height) = (decoded.width(), decoded.height());
+    let sample = |plane: usize, x: usize, y: usize| {
+        let at = y * decoded.stride(plane) + x * 4;
+        let bytes = &decoded.data(plane)[at..at + 4];
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            for plane in planes {
+                rgba.push(sample(plane, x, y));
+            }
+            rgba.push(alpha.map_or(1.0, |plane| sample(plane, x, y)));
+        }
+    }
+    Ok(TexFrame {
+        size: (width, height),
+        pixels: TexPixels::RgbaF32(rgba),
+    })
+}
+
+// Gifs are always sent to ffmpeg, pngs and webps only with animation chunks
+fn animated(bytes: &[u8]) -> bool {
+    if bytes.starts_with(b"GIF8") {
+        return true;
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // acTL has to come before the first IDAT
+        let mut at = 8;
+        while at + 8 <= bytes.len() {
+            let len = u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+            match &bytes[at + 4..at + 8] {
+                b"acTL" => return true,
+                b"IDAT" => return false,
+                _ => at += 12 + len as usize,
+            }
+        }
+        return false;
+    }
+    // the extended header's flags mark animation
+    bytes.len() > 20
+        && bytes.starts_with(b"RIFF")
+        && &bytes[8..12] == b"WEBP"
+        && &bytes[12..16] == b"VP8X"
+        && bytes[20] & 0x02 != 0
+}
+
+// Every frame with how long it shows, a single frame counts as a still
+fn decode_animation(path: &str) -> Result<Vec<(TexFrame, u32)>> {
+    let (mut ictx, index, time_base, mut decoder) = open(path)?;
+    let mut decoded = Decoded {
+        time_base,
+        scaler: None,
+        frames: vec![],
+    };
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        decoded.receive(&mut decoder)?;
+    }
+    decoder.send_eof()?;
+    decoded.receive(&mut decoder)?;
+
+    let frames = decoded.frames;
+    if frames.is_empty() {
+        bail!("No frames decoded");
+    }
+    if frames.len() == 1 {
+        return Ok(frames.into_iter().map(|(_, _, frame)| (frame, 0)).collect());
+    }
+    let starts: Vec<Option<f64>> = frames.iter().map(|(start, _, _)| *start).collect();
+    Ok(frames
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, duration, frame))| {
+            // the gap to the next frame where there are timestamps, which
+            // apng and webp don't always give durations for
+            let ms = match (start, starts.get(i + 1).copied().flatten()) {
+                (Some(start), Some(next)) if next > start => next - start,
+                _ => duration,
+            };
+            let delay_ms = match ms.round() as u32 {
+                delay if delay < MIN_DELAY_MS => DEFAULT_DELAY_MS,
+                delay => delay,
+            };
+            (frame, delay_ms)
+        })
+        .collect())
+}
+
+// With the image stream's index and time base
+fn open(path: &str) -> Result<(format::context::Input, usize, f64, decoder::Video)> {
+    let ictx = format::input(&path)?;
+    let stream = ictx
+        .streams()
+        .best(media::Type::Video)
+        .ok_or_else(|| anyhow!("No image stream"))?;
+    let index = stream.index();
+    let time_base = f64::from(stream.time_base());
+    let context = codec::context::Context::from_parameters(stream.parameters())?;
+    let decoder = context.decoder().video()?;
+    Ok((ictx, index, time_base, decoder))
+}
+
+struct Decoded {
+    time_base: f64,
+    // made for the decoder's format from its first frame
+    scaler: Option<scaling::Context>,
+    // start and duration in ms with the frame
+    frames: Vec<(Option<f64>, f64, TexFrame)>,
+}
+
+impl Decoded {
+    fn receive(&mut self, decoder: &mut decoder::Video) -> Result<()> {
+        let mut decoded = frame::Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let (width, height) = (decoded.width(), decoded.height());
+            let scaler = match self.scaler.as_mut() {
+                Some(scaler) => scaler,
+                None => self.scaler.insert(scaling::Context::get(
+                    decoder.format(),
+                    width,
+                    height,
+                    Pixel::RGBA,
+                    width,
+                    height,
+                    scaling::Flags::BILINEAR,
+                )?),
+            };
+            let mut rgba = frame::Video::empty();
+            scaler.run(&decoded, &mut rgba)?;
+            let ms = |tbu: i64| tbu as f64 * self.time_base * 1000.0;
+            self.frames.push((
+                decoded.pts().map(ms),
+                ms(decoded.packet().duration),
+                TexFrame {
+                    size: (width, height),
+                    pixels: TexPixels::Rgba8(tightly_packed(
+                        rgba.data(0),
+                        rgba.stride(0),
+                        width,
+                        height,
+                    )),
+                },
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn tightly_packed(pixels: &[u8], pitch: usize, width: u32, height: u32) -> Vec<u8> {
+    let row = width as usize * 4;
+    let mut rgba = Vec::with_capacity(row * height as usize);
+    for y in 0..height as usize {
+        rgba.extend_from_slice(&pixels[y * pitch..y * pitch + row]);
+    }
+    rgba
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+fn match_files(tex: &Tex) -> Result<Vec<GlobMatch>> {
+    match is_pattern(&tex.path) {
+        true => glob_matches(&tex.path, &tex.ignore),
+        false => Ok(vec![GlobMatch::stat(&tex.path)?]),
+    }
+}
+
+fn order(files: &mut Vec<GlobMatch>, order: &TexOrder) -> Result<()> {
+    match order {
+        TexOrder::Natural => files.sort_by(|a, b| natural_cmp(&a.path, &b.path)),
+        TexOrder::Mtime => {
+            files.sort_by(|a, b| {
+                a.modified
+                    .cmp(&b.modified)
+                    .then_with(|| natural_cmp(&a.path, &b.path))
+            });
+        }
+        TexOrder::List(names) => {
+            let mut listed = vec![];
+            for name in names {
+                match files
+                    .iter()
+                    .find(|file| file.path == *name || file_name(&file.path) == name)
+                {
+                    Some(file) => listed.push(file.clone()),
+                    None => bail!("{} is not among the matched files", name),
+                }
+            }
+            *files = listed;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn file(path: &str, secs: u64) -> GlobMatch {
+        GlobMatch {
+            path: path.to_string(),
+            size: 0,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+        }
+    }
+
+    fn paths(files: &[GlobMatch]) -> Vec<&str> {
+        files.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    fn matched() -> Vec<GlobMatch> {
+        vec![
+            file("frames/f10.png", 1),
+            file("frames/f2.png", 3),
+            file("frames/f1.png", 3),
+        ]
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut files = matched();
+        order(&mut files, &TexOrder::Natural).unwrap();
+        assert_eq!(
+            paths(&files),
+            ["frames/f1.png", "frames/f2.png", "frames/f10.png"]
+        );
+    }
+
+    #[test]
+    fn mtime_order() {
+        let mut files = matched();
+        order(&mut files, &TexOrder::Mtime).unwrap();
+        // ties by name
+        assert_eq!(
+            paths(&files),
+            ["frames/f10.png", "frames/f1.png", "frames/f2.png"]
+        );
+    }
+
+    #[test]
+    fn list_order() {
+        let mut files = matched();
+        let list = vec!["f2.png".to_string(), "frames/f10.png".to_string()];
+        order(&mut files, &TexOrder::List(list)).unwrap();
+        assert_eq!(paths(&files), ["frames/f2.png", "frames/f10.png"]);
+
+        let mut files = matched();
+        let missing = vec!["f3.png".to_string()];
+        assert!(order(&mut files, &TexOrder::List(missing)).is_err());
+    }
+
+    #[test]
+    fn packs_padded_rows() {
+        // 1x2 with a pitch of 8, the padding is dropped
+        let pixels = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        assert_eq!(tightly_packed(&pixels, 8, 1, 2), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
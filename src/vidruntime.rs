@@ -1,19 +1,26 @@
 use crate::{
     gfx_lowlevel::bindings::{
-        gfx_lowlevel_filter_params, gfx_lowlevel_frame_clear, gfx_lowlevel_frame_copy,
-        gfx_lowlevel_frame_create_texture, gfx_lowlevel_frame_ctx, gfx_lowlevel_frame_ctx_destroy,
-        gfx_lowlevel_frame_ctx_init, gfx_lowlevel_gpu_ctx, gfx_lowlevel_gpu_ctx_render,
-        gfx_lowlevel_lut, gfx_lowlevel_map_frame_ctx, gfx_lowlevel_mix_ctx,
-        gfx_lowlevel_mix_ctx_destroy, gfx_lowlevel_mix_ctx_init, gfx_lowlevel_reset_dispatch,
-        pl_frame, pl_rect2df, pl_shader_var, pl_var, pl_var_type_PL_VAR_FLOAT,
-        pl_var_type_PL_VAR_SINT, pl_var_type_PL_VAR_UINT,
+        gfx_lowlevel_download, gfx_lowlevel_download_destroy, gfx_lowlevel_download_init,
+        gfx_lowlevel_download_poll, gfx_lowlevel_download_start, gfx_lowlevel_filter_params,
+        gfx_lowlevel_frame_clear, gfx_lowlevel_frame_copy, gfx_lowlevel_frame_create_texture,
+        gfx_lowlevel_frame_ctx, gfx_lowlevel_frame_ctx_destroy, gfx_lowlevel_frame_ctx_init,
+        gfx_lowlevel_frame_download_rgba, gfx_lowlevel_frame_halve,
+        gfx_lowlevel_frame_upload_compressed, gfx_lowlevel_frame_upload_pixels,
+        gfx_lowlevel_frame_upload_rgba, gfx_lowlevel_gpu_ctx, gfx_lowlevel_gpu_ctx_render,
+        gfx_lowlevel_lut, gfx_lowlevel_map_frame_ctx, gfx_lowlevel_mesh_pass,
+        gfx_lowlevel_mesh_pass_destroy, gfx_lowlevel_mesh_pass_init, gfx_lowlevel_mesh_pass_render,
+        gfx_lowlevel_mix_ctx, gfx_lowlevel_mix_ctx_destroy, gfx_lowlevel_mix_ctx_init,
+        gfx_lowlevel_reset_dispatch, pl_frame, pl_rect2df, pl_shader_var, pl_var,
+        pl_var_type_PL_VAR_FLOAT, pl_var_type_PL_VAR_SINT, pl_var_type_PL_VAR_UINT, GFX_EAGAIN,
     },
-    gfxinfo::{Vid, VidInfo, VidMixerInfo},
+    gfxinfo::{Shader, ShaderInfo, ShaderStatus, Vid, VidInfo, VidMixerInfo},
     glob::glob,
+    meshruntime::MeshData,
     renderspec::{CopyEx, SendCmd, SendValue},
+    shaderhelper::include_files,
 };
 use anyhow::{bail, Context as AnyhowContext, Error, Result};
-use ffmpeg_next::ffi::{AVCodecContext, AVPixelFormat};
+use ffmpeg_next::ffi::{AVCodecContext, AVHWDeviceContext, AVHWDeviceType, AVPixelFormat};
 use ffmpeg_next::{
     decoder,
     format::{context::Input, input_with_decoder_format},
@@ -23,22 +30,58 @@ use ffmpeg_next::{
 };
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     ffi::{CStr, CString},
     fmt::Debug,
-    i32,
+    fs, i32,
     iter::repeat_with,
+    path::Path,
     sync::Arc,
+    time::Instant,
     usize,
 };
+use tracing::{debug, error, warn};
 
 use regex;
 extern crate ffmpeg_next as ffmpeg;
 
+// The platform's hardware decoders for Vids with hardware_decode, tried in
+// order, and the format of the frames each decodes to. vaapi and d3d11va open
+// the first gpu, cuda is nvdec on nvidia cards.
+#[cfg(target_os = "macos")]
+const HW_DECODE: &[(AVHWDeviceType, AVPixelFormat)] = &[(
+    AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX,
+    AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX,
+)];
+#[cfg(target_os = "windows")]
+const HW_DECODE: &[(AVHWDeviceType, AVPixelFormat)] = &[
+    (
+        AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA,
+        AVPixelFormat::AV_PIX_FMT_D3D11,
+    ),
+    (
+        AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+        AVPixelFormat::AV_PIX_FMT_CUDA,
+    ),
+];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const HW_DECODE: &[(AVHWDeviceType, AVPixelFormat)] = &[
+    (
+        AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+        AVPixelFormat::AV_PIX_FMT_VAAPI,
+    ),
+    (
+        AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+        AVPixelFormat::AV_PIX_FMT_CUDA,
+    ),
+];
+
 #[derive(Debug)]
 pub struct VidData {
     pub info: VidInfo,
     pub vid_input: RefCell<Option<VidInput>>,
+    // spent in decode_frame since the last take
+    decode_us: Cell<u64>,
 }
 
 #[derive(Debug)]
@@ -47,6 +90,97 @@ impl WrapFrame {
     pub fn new(ctx: *mut gfx_lowlevel_gpu_ctx) -> Self {
         unsafe { Self(gfx_lowlevel_frame_ctx_init(ctx)) }
     }
+
+    pub fn upload_rgba(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        if rgba.len() < width as usize * height as usize * 4 {
+            bail!("Short rgba buffer for {}x{}", width, height);
+        }
+        match unsafe {
+            gfx_lowlevel_frame_upload_rgba(
+                lowlevel_ctx,
+                self.0,
+                rgba.as_ptr(),
+                width as i32,
+                height as i32,
+            )
+        } {
+            0 => Ok(()),
+            err => bail!("Could not upload frame {}", err),
+        }
+    }
+
+    // rgba8 or, when is_float, rgba32f, as bytes
+    pub fn upload_pixels(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        is_float: bool,
+    ) -> Result<()> {
+        let pixel_size = if is_float { 16 } else { 4 };
+        if pixels.len() < width as usize * height as usize * pixel_size {
+            bail!("Short pixel buffer for {}x{}", width, height);
+        }
+        match unsafe {
+            gfx_lowlevel_frame_upload_pixels(
+                lowlevel_ctx,
+                self.0,
+                pixels.as_ptr() as _,
+                width as i32,
+                height as i32,
+                is_float,
+            )
+        } {
+            0 => Ok(()),
+            err => bail!("Could not upload frame {}", err),
+        }
+    }
+
+    // One level of a block compressed texture, the gpu has to sample vk_format
+    pub fn upload_compressed(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        vk_format: u32,
+        blocks: &[u8],
+        row_pitch: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        match unsafe {
+            gfx_lowlevel_frame_upload_compressed(
+                lowlevel_ctx,
+                self.0,
+                vk_format,
+                blocks.as_ptr(),
+                row_pitch,
+                width as i32,
+                height as i32,
+            )
+        } {
+            0 => Ok(()),
+            err => bail!("Could not upload compressed frame {}", err),
+        }
+    }
+
+    // A frame half this one's size, for the next mip level
+    pub fn halve(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<WrapFrame> {
+        let half = WrapFrame::new(lowlevel_ctx);
+        match unsafe { gfx_lowlevel_frame_halve(lowlevel_ctx, half.0, self.pl_frame()) } {
+            0 => Ok(half),
+            err => bail!("Could not halve frame {}", err),
+        }
+    }
+
+    pub fn pl_frame(&self) -> *mut pl_frame {
+        unsafe { &mut (*self.0).pl_frame as _ }
+    }
 }
 unsafe impl Send for WrapFrame {}
 unsafe impl Sync for WrapFrame {}
@@ -69,6 +203,17 @@ impl Drop for WrapMixCtx {
     }
 }
 
+#[derive(Debug)]
+pub struct WrapMeshPass(*mut gfx_lowlevel_mesh_pass);
+unsafe impl Send for WrapMeshPass {}
+impl Drop for WrapMeshPass {
+    fn drop(&mut self) {
+        unsafe {
+            gfx_lowlevel_mesh_pass_destroy(&mut self.0 as _);
+        }
+    }
+}
+
 pub struct VidInput {
     pub ictx: Input,
     pub video_stream_index: usize,
@@ -97,17 +242,23 @@ impl Debug for VidInput {
     }
 }
 
+// The format of whichever device prepare opened
 unsafe extern "C" fn get_hw_format(
-    _ctx: *mut AVCodecContext,
+    ctx: *mut AVCodecContext,
     mut pix_fmts: *const AVPixelFormat,
 ) -> AVPixelFormat {
+    let device = (*(*ctx).hw_device_ctx).data as *const AVHWDeviceContext;
+    let Some(&(_, hw_format)) = HW_DECODE.iter().find(|(kind, _)| *kind == (*device).type_) else {
+        error!("Failed to get HW surface format");
+        return AVPixelFormat::AV_PIX_FMT_NONE;
+    };
     while *pix_fmts != AVPixelFormat::AV_PIX_FMT_NONE {
-        if *pix_fmts == AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX {
+        if *pix_fmts == hw_format {
             return *pix_fmts;
         }
         pix_fmts = pix_fmts.offset(1);
     }
-    eprintln!("Failed to get HW surface format.\n");
+    error!("Failed to get HW surface format");
     return AVPixelFormat::AV_PIX_FMT_NONE;
 }
 
@@ -201,8 +352,11 @@ impl VidData {
                 realtime: spec.realtime,
                 hardware_decode: spec.hardware_decode,
                 software_filter: spec.software_filter,
+                variants: spec.variants.clone(),
+                load: spec.load.clone(),
             },
             vid_input: RefCell::new(None),
+            decode_us: Cell::new(0),
         })
     }
 
@@ -244,16 +398,22 @@ impl VidData {
             unsafe {
                 let mut hw_device_ctx: *mut ffmpeg_next::ffi::AVBufferRef = std::ptr::null_mut();
 
-                // Set the hw_device_ctx
-                if ffmpeg_next::ffi::av_hwdevice_ctx_create(
-                    &mut hw_device_ctx as *mut *mut ffmpeg_next::ffi::AVBufferRef,
-                    ffmpeg_next::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX,
-                    std::ptr::null_mut(),
-                    std::ptr::null_mut(),
-                    0,
-                ) < 0
-                {
-                    bail!("Could not create hwdevice context")
+                // Set the hw_device_ctx, from the first decoder there's a
+                // device for
+                let opened = HW_DECODE.iter().any(|(kind, _)| {
+                    ffmpeg_next::ffi::av_hwdevice_ctx_create(
+                        &mut hw_device_ctx as *mut *mut ffmpeg_next::ffi::AVBufferRef,
+                        *kind,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        0,
+                    ) >= 0
+                });
+                if !opened {
+                    bail!(
+                        "Could not create a hwdevice context, tried {:?}",
+                        HW_DECODE.iter().map(|(kind, _)| kind).collect::<Vec<_>>()
+                    )
                 }
 
                 (*context_decoder.as_mut_ptr()).hw_device_ctx =
@@ -298,6 +458,18 @@ impl VidData {
     }
 
     pub fn decode_frame(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<()> {
+        let start = Instant::now();
+        let decoded = self.decode_next(lowlevel_ctx);
+        self.decode_us
+            .set(self.decode_us.get() + start.elapsed().as_micros() as u64);
+        decoded
+    }
+
+    pub fn take_decode_us(&self) -> u64 {
+        self.decode_us.replace(0)
+    }
+
+    fn decode_next(&self, lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<()> {
         self.prepare(lowlevel_ctx)
             .with_context(|| format!("error preparing {}:{}", file!(), line!()))?;
         let mut borrowed = self.vid_input.borrow_mut();
@@ -329,7 +501,7 @@ impl VidData {
                                         .set_pts(Some(f64::from(vid_input.continuous_pts) as i64));
                                     Rational::new(delta as i32, 1)
                                 } else {
-                                    eprintln!("Skip a frame to get a duration....");
+                                    debug!("Skip a frame to get a duration....");
                                     vid_input.last_frame_pts = next_decoded.pts().unwrap();
                                     vid_input.last_frame_duration = next_decoded.packet().duration;
                                     continue;
@@ -361,7 +533,7 @@ impl VidData {
                             errno: ffmpeg_next::ffi::EAGAIN,
                         }) => (), //resource temporarily unavailable
                         Err(e) => {
-                            eprintln!("Error receiving frame {}:{}: {}", file!(), line!(), e);
+                            error!("Error receiving frame {}:{}: {}", file!(), line!(), e);
                             error_counter += 1;
                             if error_counter > 2 {
                                 return Err(e.into());
@@ -423,6 +595,14 @@ impl VidData {
         Ok(vid_input.as_ref().unwrap().last_real_pts)
     }
 
+    pub fn fps(&self) -> Result<Rational> {
+        let vid_input = self.vid_input.borrow();
+        match vid_input.as_ref() {
+            Some(vid_input) => Ok(vid_input.fps),
+            None => bail!("Video {} is not open", self.info.name),
+        }
+    }
+
     pub fn time_base(&self) -> Result<Rational> {
         let vid_input = self.vid_input.borrow();
         if vid_input.is_none() {
@@ -507,7 +687,7 @@ impl VidData {
         let pts_min = if pts_min < Rational::new(0, 0) {
             Rational::new(0, 0)
         } else if pts_min >= self.duration_tbu()? {
-            eprintln!("Min somehow beyond duration, just scan the whole thing");
+            debug!("Min somehow beyond duration, just scan the whole thing");
             Rational::new(0, 0)
         } else {
             pts_min
@@ -529,7 +709,7 @@ impl VidData {
                 last_last_pts.replace(last_pts);
             }
             if circuit_breaker <= 0 {
-                eprintln!(
+                error!(
                     "CIRCUIT BREAKER seek_tbu={seek_tbu:?} min={pts_min:?} duration_tbu={:?}",
                     self.duration_tbu()
                 );
@@ -543,6 +723,24 @@ impl VidData {
         self.vid_input.borrow_mut().take();
         Ok(())
     }
+
+    // Where the video is in seconds, for seek_vid to go back to exactly, or
+    // None when it isn't open
+    pub fn position(&self) -> Option<f64> {
+        let vid_input = self.vid_input.borrow();
+        let vid_input = vid_input.as_ref()?;
+        let pts = vid_input.last_real_pts.unwrap_or(Rational::new(0, 1));
+        Some(f64::from(pts * vid_input.time_base))
+    }
+
+    // Resets the video, handing back what it had open
+    pub fn take_input(&self) -> Option<VidInput> {
+        self.vid_input.borrow_mut().take()
+    }
+
+    pub fn restore_input(&self, input: Option<VidInput>) {
+        *self.vid_input.borrow_mut() = input;
+    }
 }
 
 fn get_codec_context(
@@ -571,9 +769,58 @@ fn get_codec_context(
     Ok(context_decoder)
 }
 
+// A Shader asset with its includes resolved
+#[derive(Debug)]
+pub struct ShaderData {
+    pub info: ShaderInfo,
+    pub source: String,
+}
+
+impl ShaderData {
+    pub fn load(shader: &Shader) -> Result<Self> {
+        let path = Path::new(&shader.path);
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read shader {}", shader.path))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let includes = RefCell::new(vec![]);
+        let missing = RefCell::new(vec![]);
+        let source = include_files(text, |name: &dyn AsRef<str>| {
+            let include = dir.join(name.as_ref());
+            match fs::read_to_string(&include) {
+                Ok(text) => {
+                    let found = include.canonicalize().unwrap_or(include);
+                    includes
+                        .borrow_mut()
+                        .push(found.to_string_lossy().into_owned());
+                    Some(text)
+                }
+                Err(_) => {
+                    missing.borrow_mut().push(name.as_ref().to_string());
+                    None
+                }
+            }
+        });
+        let missing = missing.into_inner();
+        if !missing.is_empty() {
+            bail!("{} includes missing {}", shader.path, missing.join(", "));
+        }
+        Ok(Self {
+            info: ShaderInfo {
+                name: shader.name.clone(),
+                path: shader.path.clone(),
+                includes: includes.into_inner(),
+                status: ShaderStatus::Loaded,
+            },
+            source,
+        })
+    }
+}
+
 pub struct VidMixerData {
     pub info: VidMixerInfo,
     stream: RefCell<VidMixerStream>,
+    // the source of its shader_asset, once that's loaded
+    linked_shader: RefCell<Option<String>>,
 }
 
 impl Debug for VidMixerData {
@@ -595,12 +842,33 @@ pub struct VidMixerStream {
     pub last_frame_time: Option<Rational>,
     pub frame_count: i64,
     pub mix_ctx: Option<WrapMixCtx>,
+    // pass0's when the mixer has a mesh_pass
+    pub mesh_pass: Option<WrapMeshPass>,
     pub has_been_rendered: bool,
 }
 
+// A mixer uniform's raw value, see VidMixerData::uniform
+#[derive(Debug)]
+pub struct Uniform {
+    name: String,
+    data: Vec<u8>,
+    dim_a: i32,
+}
+
+fn find_var(ctx: *mut gfx_lowlevel_mix_ctx, name: &str) -> Option<*mut pl_shader_var> {
+    let num_vars = unsafe { (*ctx).num_vars } as isize;
+    (0..num_vars)
+        .map(|i| unsafe { (*ctx).vars.offset(i) })
+        .find(|var| {
+            unsafe { CStr::from_ptr((**var).var.name as *mut i8) }.to_bytes() == name.as_bytes()
+        })
+}
+
 pub enum VidMixerInput<'a> {
     Video(&'a VidData),
     Feedback(&'a VidMixerData),
+    // an uploaded Tex frame, with its size
+    Still(&'a Arc<WrapFrame>, (u32, u32)),
 }
 
 impl<'a> From<&'a VidData> for VidMixerInput<'a> {
@@ -620,6 +888,7 @@ impl VidMixerData {
         Self {
             info,
             stream: RefCell::new(VidMixerStream::default()),
+            linked_shader: RefCell::new(None),
         }
     }
 
@@ -627,6 +896,25 @@ impl VidMixerData {
         self.info.clone()
     }
 
+    // Uses a Shader asset's source, rebuilding the mixer when it changed
+    pub fn link_shader(&self, source: &str) {
+        let mut linked = self.linked_shader.borrow_mut();
+        if linked.as_deref() != Some(source) {
+            linked.replace(source.into());
+            self.stream.replace(VidMixerStream::default());
+        }
+    }
+
+    fn shader(&self) -> Result<Option<String>> {
+        match self.info.shader_asset.as_ref() {
+            Some(name) => match self.linked_shader.borrow().clone() {
+                Some(source) => Ok(Some(source)),
+                None => bail!("Shader asset {} for {} is not loaded", name, self.info.name),
+            },
+            None => Ok(self.info.shader.clone()),
+        }
+    }
+
     fn extract_vars(txt: &str, _addendum: &mut String) -> Result<Vec<pl_shader_var>> {
         let mut vars = vec![];
         let mut lines = vec![];
@@ -666,7 +954,7 @@ impl VidMixerData {
                 let (var_type, dim_v, dim_m, dim_a, ptr) = match parts[1] {
                     "float" => {
                         if parts.len() != 4 {
-                            eprintln!("Invalid number of parts for float: {}", line);
+                            warn!("Invalid number of parts for float: {}", line);
                             continue;
                         }
                         let data = unsafe { libc::malloc(size_of::<libc::c_float>()) };
@@ -678,7 +966,7 @@ impl VidMixerData {
                     }
                     "int" => {
                         if parts.len() != 4 {
-                            eprintln!("Invalid number of parts for int: {}", line);
+                            warn!("Invalid number of parts for int: {}", line);
                             continue;
                         }
                         let data = unsafe { libc::malloc(size_of::<libc::c_int>()) };
@@ -694,7 +982,7 @@ impl VidMixerData {
                     }
                     "uint" => {
                         if parts.len() != 4 {
-                            eprintln!("Invalid number of parts for uint: {}", line);
+                            warn!("Invalid number of parts for uint: {}", line);
                             continue;
                         }
                         let data = unsafe { libc::malloc(size_of::<libc::c_uint>()) };
@@ -710,7 +998,7 @@ impl VidMixerData {
                     }
                     "vec2" => {
                         if parts.len() != 5 {
-                            eprintln!("Invalid number of parts for vec2: {}", line);
+                            warn!("Invalid number of parts for vec2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 2;
@@ -723,7 +1011,7 @@ impl VidMixerData {
                     }
                     "vec3" => {
                         if parts.len() != 6 {
-                            eprintln!("Invalid number of parts for vec3: {}", line);
+                            warn!("Invalid number of parts for vec3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 3;
@@ -736,7 +1024,7 @@ impl VidMixerData {
                     }
                     "vec4" => {
                         if parts.len() != 7 {
-                            eprintln!("Invalid number of parts for vec4: {}", line);
+                            warn!("Invalid number of parts for vec4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 4;
@@ -749,7 +1037,7 @@ impl VidMixerData {
                     }
                     "ivec2" => {
                         if parts.len() != 5 {
-                            eprintln!("Invalid number of parts for ivec2: {}", line);
+                            warn!("Invalid number of parts for ivec2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_int>() * 2;
@@ -766,7 +1054,7 @@ impl VidMixerData {
                     }
                     "ivec3" => {
                         if parts.len() != 6 {
-                            eprintln!("Invalid number of parts for ivec3: {}", line);
+                            warn!("Invalid number of parts for ivec3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_int>() * 3;
@@ -783,7 +1071,7 @@ impl VidMixerData {
                     }
                     "ivec4" => {
                         if parts.len() != 7 {
-                            eprintln!("Invalid number of parts for ivec4: {}", line);
+                            warn!("Invalid number of parts for ivec4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_int>() * 4;
@@ -801,7 +1089,7 @@ impl VidMixerData {
 
                     "uvec2" => {
                         if parts.len() != 5 {
-                            eprintln!("Invalid number of parts for uvec2: {}", line);
+                            warn!("Invalid number of parts for uvec2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_uint>() * 2;
@@ -818,7 +1106,7 @@ impl VidMixerData {
                     }
                     "uvec3" => {
                         if parts.len() != 6 {
-                            eprintln!("Invalid number of parts for uvec3: {}", line);
+                            warn!("Invalid number of parts for uvec3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_uint>() * 3;
@@ -835,7 +1123,7 @@ impl VidMixerData {
                     }
                     "uvec4" => {
                         if parts.len() != 7 {
-                            eprintln!("Invalid number of parts for uvec4: {}", line);
+                            warn!("Invalid number of parts for uvec4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_uint>() * 4;
@@ -852,7 +1140,7 @@ impl VidMixerData {
                     }
                     "mat2x2" | "mat2" => {
                         if parts.len() != 7 {
-                            eprintln!("Invalid number of parts for mat2: {}", line);
+                            warn!("Invalid number of parts for mat2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 2 * 2;
@@ -865,7 +1153,7 @@ impl VidMixerData {
                     }
                     "mat3x3" | "mat3" => {
                         if parts.len() != 11 {
-                            eprintln!("Invalid number of parts for mat3: {}", line);
+                            warn!("Invalid number of parts for mat3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 3 * 3;
@@ -878,7 +1166,7 @@ impl VidMixerData {
                     }
                     "mat4x4" | "mat4" => {
                         if parts.len() != 19 {
-                            eprintln!("Invalid number of parts for mat4: {}", line);
+                            warn!("Invalid number of parts for mat4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 4 * 4;
@@ -891,7 +1179,7 @@ impl VidMixerData {
                     }
                     "mat2x3" => {
                         if parts.len() != 9 {
-                            eprintln!("Invalid number of parts for mat2x3: {}", line);
+                            warn!("Invalid number of parts for mat2x3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 2 * 3;
@@ -904,7 +1192,7 @@ impl VidMixerData {
                     }
                     "mat2x4" => {
                         if parts.len() != 11 {
-                            eprintln!("Invalid number of parts for mat2x4: {}", line);
+                            warn!("Invalid number of parts for mat2x4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 2 * 4;
@@ -917,7 +1205,7 @@ impl VidMixerData {
                     }
                     "mat3x2" => {
                         if parts.len() != 9 {
-                            eprintln!("Invalid number of parts for mat3x2: {}", line);
+                            warn!("Invalid number of parts for mat3x2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 3 * 2;
@@ -930,7 +1218,7 @@ impl VidMixerData {
                     }
                     "mat3x4" => {
                         if parts.len() != 11 {
-                            eprintln!("Invalid number of parts for mat3x4: {}", line);
+                            warn!("Invalid number of parts for mat3x4: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 3 * 4;
@@ -943,7 +1231,7 @@ impl VidMixerData {
                     }
                     "mat4x2" => {
                         if parts.len() != 11 {
-                            eprintln!("Invalid number of parts for mat4x2: {}", line);
+                            warn!("Invalid number of parts for mat4x2: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 4 * 2;
@@ -956,7 +1244,7 @@ impl VidMixerData {
                     }
                     "mat4x3" => {
                         if parts.len() != 15 {
-                            eprintln!("Invalid number of parts for mat4x3: {}", line);
+                            warn!("Invalid number of parts for mat4x3: {}", line);
                             continue;
                         }
                         let size = size_of::<libc::c_float>() * 4 * 3;
@@ -969,7 +1257,7 @@ impl VidMixerData {
                     }
                     "int[]" => {
                         if parts.len() < 3 {
-                            eprintln!("Invalid number of parts for int[]: {}", line);
+                            warn!("Invalid number of parts for int[]: {}", line);
                             continue;
                         }
 
@@ -997,7 +1285,7 @@ impl VidMixerData {
                     }
                     "uint[]" => {
                         if parts.len() < 3 {
-                            eprintln!("Invalid number of parts for uint[]: {}", line);
+                            warn!("Invalid number of parts for uint[]: {}", line);
                             continue;
                         }
 
@@ -1025,11 +1313,11 @@ impl VidMixerData {
                     }
                     "vec2[]" => {
                         if parts.len() < 3 {
-                            eprintln!("Invalid number of parts for vec2[]: {}", line);
+                            warn!("Invalid number of parts for vec2[]: {}", line);
                             continue;
                         }
                         if parts.len() != 3 && (parts.len() - 3) % 2 != 0 {
-                            eprintln!(
+                            warn!(
                                 "Must have an even number of extra values for vec2[]: {}",
                                 line
                             );
@@ -1055,7 +1343,7 @@ impl VidMixerData {
                         )
                     }
                     _ => {
-                        eprintln!("Unknown uniform type: {}", line);
+                        warn!("Unknown uniform type: {}", line);
                         continue;
                     }
                 };
@@ -1139,11 +1427,11 @@ impl VidMixerData {
                             dynamic: true,
                         });
                     } else {
-                        eprintln!("Invalid string declaration (end>=start): {}", line);
+                        warn!("Invalid string declaration (end>=start): {}", line);
                         continue;
                     }
                 } else {
-                    eprintln!("Invalid string declaration (no quote): {}", line);
+                    warn!("Invalid string declaration (no quote): {}", line);
                     continue;
                 }
             }
@@ -1156,8 +1444,9 @@ impl VidMixerData {
         if stream.mix_ctx.is_none() {
             let mut vars = vec![];
             let mut prelude = String::new();
+            let shader = self.shader()?;
 
-            if let Some(shader) = self.info.shader.as_ref() {
+            if let Some(shader) = shader.as_ref() {
                 vars.extend(Self::extract_vars(shader, &mut prelude)?);
             }
 
@@ -1166,7 +1455,7 @@ impl VidMixerData {
             let prelude =
                 Some(CString::new(re.replace_all(&prelude, "").as_bytes()).unwrap_or_default());
 
-            let header = if let Some(shader) = self.info.shader.as_ref() {
+            let header = if let Some(shader) = shader.as_ref() {
                 Some(CString::new(re.replace_all(shader, "").as_bytes()).unwrap_or_default())
             } else {
                 None // honestly this is likely going to be an error but leaving for future use
@@ -1175,7 +1464,7 @@ impl VidMixerData {
             let re_comments = regex::Regex::new(r"(?m)//.*\n").unwrap();
             let re_c_comments = regex::Regex::new(r"(?s)/\*.*?\*/").unwrap();
             let re_pass = regex::Regex::new(r"(?m)(^|\W)pass\d+(\W|$)").unwrap();
-            stream.pass_count = if let Some(shader) = self.info.shader.as_ref() {
+            stream.pass_count = if let Some(shader) = shader.as_ref() {
                 re_pass
                     .find_iter(
                         re_comments
@@ -1225,6 +1514,16 @@ impl VidMixerData {
                 bail!("Error creating mix ctx for {}", self.info.name);
             }
             stream.mix_ctx.replace(WrapMixCtx(mix_ctx));
+
+            if let Some(pass) = self.info.mesh_pass.as_ref() {
+                let vertex = CString::new(pass.vertex.as_bytes())?;
+                let mesh_pass =
+                    unsafe { gfx_lowlevel_mesh_pass_init(lowlevel_ctx, vertex.as_ptr()) };
+                if mesh_pass.is_null() {
+                    bail!("Error creating mesh pass for {}", self.info.name);
+                }
+                stream.mesh_pass.replace(WrapMeshPass(mesh_pass));
+            }
             stream.last_frame_time.replace(Rational::new(0, 1));
 
             stream.pass_buffers.clear();
@@ -1292,7 +1591,9 @@ impl VidMixerData {
         frames_to_mix: i64,
         frames: i64,
         inputs: &[VidMixerInput],
+        mesh: Option<&MeshData>,
         target: Option<&CopyEx>,
+        viewport: Option<(u32, u32)>,
         lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
         dry_run: bool,
         no_display: bool,
@@ -1330,7 +1631,7 @@ impl VidMixerData {
                             match vid_data.decode_frame(lowlevel_ctx) {
                                 Ok(()) => decoded_frames[i] = unsafe { vid_data.last_frame()? },
                                 Err(e) => {
-                                    eprintln!(
+                                    error!(
                                         "Could not decode input {} at {}:{} because {}",
                                         vid_data.info.name,
                                         file!(),
@@ -1370,7 +1671,7 @@ impl VidMixerData {
                                 last_frame = match vid_data.decode_frame(lowlevel_ctx) {
                                     Ok(()) => unsafe { vid_data.last_frame()? },
                                     Err(e) => {
-                                        eprintln!(
+                                        error!(
                                             "Could not decode input {} at {}:{} because {}",
                                             vid_data.info.name,
                                             file!(),
@@ -1424,6 +1725,7 @@ impl VidMixerData {
                             other_mix.scratch_frame.clone()
                         };
                     }
+                    VidMixerInput::Still(frame, _) => decoded_frames[i] = Some(frame.clone()),
                 }
             }
             break;
@@ -1510,6 +1812,13 @@ impl VidMixerData {
                             .mix(target.clone())
                             .build(),
                     ),
+                    &VidMixerInput::Still(_, size) => std_vars.push(
+                        SendCmd::builder()
+                            .name(format!("iResolution{inp_idx}"))
+                            .value(SendValue::Vector(vec![size.0 as f32, size.1 as f32]))
+                            .mix(target.clone())
+                            .build(),
+                    ),
                 }
                 inp_idx += 1;
             }
@@ -1549,6 +1858,7 @@ impl VidMixerData {
                     body: body.as_ptr(),
                     vars: unsafe { (*mix.mix_ctx.as_ref().unwrap().0).vars },
                     num_vars: unsafe { (*mix.mix_ctx.as_ref().unwrap().0).num_vars },
+                    blend: false,
                 };
                 unsafe {
                     let one_lut_only = if i == mix.pass_count - 1 {
@@ -1556,18 +1866,33 @@ impl VidMixerData {
                     } else {
                         std::ptr::null_mut()
                     };
-                    match gfx_lowlevel_gpu_ctx_render(
-                        lowlevel_ctx,
-                        //mix.mix_ctx.as_ref().unwrap().0,
-                        &params as _,
-                        &mut (*mix.scratch_frame.as_ref().unwrap().0).pl_frame as _, //dst frame
-                        raw_frames.as_mut_ptr(),
-                        num_frames,
-                        previous_passes.as_mut_ptr() as _,
-                        previous_passes.len() as i32,
-                        one_lut_only,
-                        shader_debug,
-                    ) {
+                    let rendered = match (i, mix.mesh_pass.as_ref(), mesh) {
+                        (0, Some(mesh_pass), Some(mesh)) => gfx_lowlevel_mesh_pass_render(
+                            lowlevel_ctx,
+                            mesh_pass.0,
+                            &params as _,
+                            &mut (*mix.scratch_frame.as_ref().unwrap().0).pl_frame as _,
+                            raw_frames.as_mut_ptr(),
+                            num_frames,
+                            previous_passes.as_mut_ptr() as _,
+                            previous_passes.len() as i32,
+                            mesh.vertices.as_ptr(),
+                            mesh.vertex_count() as i32,
+                        ),
+                        _ => gfx_lowlevel_gpu_ctx_render(
+                            lowlevel_ctx,
+                            //mix.mix_ctx.as_ref().unwrap().0,
+                            &params as _,
+                            &mut (*mix.scratch_frame.as_ref().unwrap().0).pl_frame as _, //dst frame
+                            raw_frames.as_mut_ptr(),
+                            num_frames,
+                            previous_passes.as_mut_ptr() as _,
+                            previous_passes.len() as i32,
+                            one_lut_only,
+                            shader_debug,
+                        ),
+                    };
+                    match rendered {
                         0 => (),
                         err => bail!("Could not render frame {}", err),
                     }
@@ -1590,7 +1915,6 @@ impl VidMixerData {
         }
 
         // basic copy params - just sample the mixed frame into the fbo
-        let body = CString::new("color = texture(src_tex0, src_uv);")?;
         let mut params = gfx_lowlevel_filter_params {
             src: pl_rect2df {
                 x0: 0.0,
@@ -1607,15 +1931,28 @@ impl VidMixerData {
             rotation: 0.0,
             prelude: std::ptr::null() as _,
             header: std::ptr::null() as _,
-            body: body.as_ptr(),
+            body: std::ptr::null() as _,
             vars: std::ptr::null_mut(),
             num_vars: 0,
+            blend: false,
         };
 
         let mut raw_frame = unsafe {
             vec![&mut (*mix.scratch_frame.as_mut().unwrap().0).pl_frame as *mut pl_frame]
         };
 
+        let win_w = unsafe { (*(*lowlevel_ctx).window_frame.planes[0].texture).params.w as f32 };
+        let win_h = unsafe { (*(*lowlevel_ctx).window_frame.planes[0].texture).params.h as f32 };
+        // dst rects are in viewport units, which are letterboxed into the window
+        let (scale, off_x, off_y) = letterbox(viewport, win_w, win_h);
+        let (canvas_w, canvas_h) = viewport.map_or((win_w, win_h), |(w, h)| (w as f32, h as f32));
+        params.dst = pl_rect2df {
+            x0: off_x / win_w,
+            y0: off_y / win_h,
+            x1: (off_x + canvas_w * scale) / win_w,
+            y1: (off_y + canvas_h * scale) / win_h,
+        };
+        let mut copy = CopyParams::default();
         if let Some(target) = target.as_ref() {
             if let Some(src) = target.src {
                 let w = unsafe { (*(*raw_frame[0]).planes[0].texture).params.w as f32 };
@@ -1628,19 +1965,52 @@ impl VidMixerData {
                 };
             }
             if let Some(dst) = target.dst {
-                let w =
-                    unsafe { (*(*lowlevel_ctx).window_frame.planes[0].texture).params.w as f32 };
-                let h =
-                    unsafe { (*(*lowlevel_ctx).window_frame.planes[0].texture).params.h as f32 };
                 params.dst = pl_rect2df {
-                    x0: dst.0 as f32 / w,
-                    y0: dst.1 as f32 / h,
-                    x1: (dst.0 + dst.2 as i32) as f32 / w,
-                    y1: (dst.1 + dst.3 as i32) as f32 / h,
+                    x0: (off_x + dst.0 as f32 * scale) / win_w,
+                    y0: (off_y + dst.1 as f32 * scale) / win_h,
+                    x1: (off_x + (dst.0 + dst.2 as i32) as f32 * scale) / win_w,
+                    y1: (off_y + (dst.1 + dst.3 as i32) as f32 * scale) / win_h,
                 };
             }
+            // flips just sample the source rect backwards
+            if target.flip_h {
+                std::mem::swap(&mut params.src.x0, &mut params.src.x1);
+            }
+            if target.flip_v {
+                std::mem::swap(&mut params.src.y0, &mut params.src.y1);
+            }
+            copy = CopyParams::new(target, &params, win_w, win_h, scale);
         };
 
+        let body = CString::new(match copy.is_simple() {
+            true => "color = texture(src_tex0, src_uv);",
+            false => COPY_EX_BODY,
+        })?;
+        params.body = body.as_ptr();
+        let names = COPY_EX_VARS
+            .iter()
+            .map(|n| CString::new(*n))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut vars = copy.vars(&names);
+        if !copy.is_simple() {
+            // sample over the rotated bounds, the shader maps back into dst
+            params.src = pl_rect2df {
+                x0: 0.0,
+                y0: 0.0,
+                x1: 1.0,
+                y1: 1.0,
+            };
+            params.dst = pl_rect2df {
+                x0: copy.bbox[0] / win_w,
+                y0: copy.bbox[1] / win_h,
+                x1: copy.bbox[2] / win_w,
+                y1: copy.bbox[3] / win_h,
+            };
+            params.vars = vars.as_mut_ptr();
+            params.num_vars = vars.len() as i32;
+            params.blend = copy.needs_blend();
+        }
+
         unsafe {
             match gfx_lowlevel_gpu_ctx_render(
                 lowlevel_ctx,
@@ -1673,11 +2043,122 @@ impl VidMixerData {
             .clone());
     }
 
+    // Clears the output and every pass buffer so feedback restarts from rgba
+    pub fn clear(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        rgba: (f32, f32, f32, f32),
+    ) -> Result<()> {
+        self.prepare(lowlevel_ctx)?;
+        let mut mix = self.stream.borrow_mut();
+        let frames = mix
+            .pass_buffers
+            .iter()
+            .chain(mix.scratch_frame.iter())
+            .map(|f| f.pl_frame())
+            .collect::<Vec<_>>();
+        for frame in frames {
+            match unsafe {
+                gfx_lowlevel_frame_clear(lowlevel_ctx, frame, rgba.0, rgba.1, rgba.2, rgba.3)
+            } {
+                0 => (),
+                err => bail!("Could not clear frame {}", err),
+            }
+        }
+        mix.has_been_rendered = true;
+        Ok(())
+    }
+
+    // The frame feedback inputs and readback see, drawing into it shows up downstream
+    pub fn output_frame(&self) -> Option<Arc<WrapFrame>> {
+        self.stream.borrow().scratch_frame.clone()
+    }
+
+    // Reads back the last mixed frame, returns None if nothing has been mixed yet
+    pub fn download_rgba(
+        &self,
+        lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<(u32, u32)>> {
+        let mix = self.stream.borrow();
+        let Some(scratch) = mix.scratch_frame.as_ref() else {
+            return Ok(None);
+        };
+        unsafe { download_frame_rgba(lowlevel_ctx, &mut (*scratch.0).pl_frame, buf).map(Some) }
+    }
+
+    // Starts reading back the last mixed frame, false if nothing has been
+    // mixed yet or the last download is still in flight
+    pub fn start_download(&self, download: &mut FrameDownload) -> Result<bool> {
+        let mix = self.stream.borrow();
+        let Some(scratch) = mix.scratch_frame.as_ref() else {
+            return Ok(false);
+        };
+        unsafe { download.start(&mut (*scratch.0).pl_frame) }
+    }
+
     pub fn reset(&self) -> std::result::Result<(), Error> {
         self.stream.replace(VidMixerStream::default());
         Ok(())
     }
 
+    // Resets the mixer, handing back its stream
+    pub fn take_stream(&self) -> VidMixerStream {
+        self.stream.replace(VidMixerStream::default())
+    }
+
+    pub fn restore_stream(&self, stream: VidMixerStream) {
+        self.stream.replace(stream);
+    }
+
+    pub fn is_prepared(&self) -> bool {
+        self.stream.borrow().mix_ctx.is_some()
+    }
+
+    // A copy of a uniform as it is now, None if the mixer isn't prepared or
+    // its shader has no such uniform
+    pub fn uniform(&self, name: &str) -> Option<Uniform> {
+        let stream = self.stream.borrow();
+        let ctx = stream.mix_ctx.as_ref()?.0;
+        let var = find_var(ctx, name)?;
+        unsafe {
+            if (*var).data.is_null() {
+                return None;
+            }
+            let len = ((*var).var.dim_v * (*var).var.dim_m * (*var).var.dim_a) as usize;
+            // floats, ints and uints are all four bytes
+            let data = std::slice::from_raw_parts((*var).data as *const u8, len * 4);
+            Some(Uniform {
+                name: name.to_string(),
+                data: data.to_vec(),
+                dim_a: (*var).var.dim_a,
+            })
+        }
+    }
+
+    // Puts back a uniform copied with uniform
+    pub fn restore_uniform(&self, uniform: &Uniform) {
+        let stream = self.stream.borrow();
+        let Some(ctx) = stream.mix_ctx.as_ref().map(|ctx| ctx.0) else {
+            return;
+        };
+        let Some(var) = find_var(ctx, &uniform.name) else {
+            return;
+        };
+        unsafe {
+            if (*var).var.dim_a != uniform.dim_a {
+                libc::free((*var).data as *mut libc::c_void);
+                (*var).data = libc::malloc(uniform.data.len());
+                (*var).var.dim_a = uniform.dim_a;
+            }
+            std::ptr::copy_nonoverlapping(
+                uniform.data.as_ptr(),
+                (*var).data as *mut u8,
+                uniform.data.len(),
+            );
+        }
+    }
+
     pub fn do_cmd(
         &self,
         lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
@@ -1819,3 +2300,344 @@ impl VidMixerData {
         Ok(())
     }
 }
+
+// Reads frames back in the background, a download started on one frame is
+// usually ready to poll on the next instead of stalling the one it started on
+pub struct FrameDownload(*mut gfx_lowlevel_download);
+unsafe impl Send for FrameDownload {}
+impl Drop for FrameDownload {
+    fn drop(&mut self) {
+        unsafe {
+            gfx_lowlevel_download_destroy(&mut self.0 as _);
+        }
+    }
+}
+
+impl FrameDownload {
+    pub fn new(lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx) -> Result<Self> {
+        let download = unsafe { gfx_lowlevel_download_init(lowlevel_ctx) };
+        if download.is_null() {
+            bail!("Could not create a frame download");
+        }
+        Ok(Self(download))
+    }
+
+    // Starts downloading frame, false if the last download is still in flight
+    pub unsafe fn start(&mut self, frame: *mut pl_frame) -> Result<bool> {
+        if frame.is_null() || (*frame).planes[0].texture.is_null() {
+            bail!("No frame to download");
+        }
+        match gfx_lowlevel_download_start(self.0, frame) {
+            0 => Ok(true),
+            err if err == GFX_EAGAIN as i32 => Ok(false),
+            err => bail!("Could not start frame download {}", err),
+        }
+    }
+
+    // Copies a finished download into buf, None while it's in flight or if
+    // none was started
+    pub fn poll(&mut self, buf: &mut Vec<u8>) -> Result<Option<(u32, u32)>> {
+        unsafe {
+            if !(*self.0).pending {
+                return Ok(None);
+            }
+            let (mut w, mut h) = (0, 0);
+            buf.resize((*self.0).w as usize * (*self.0).h as usize * 4, 0);
+            match gfx_lowlevel_download_poll(self.0, buf.as_mut_ptr(), buf.len(), &mut w, &mut h) {
+                0 => Ok(Some((w as u32, h as u32))),
+                err if err == GFX_EAGAIN as i32 => Ok(None),
+                err => bail!("Could not finish frame download {}", err),
+            }
+        }
+    }
+}
+
+pub unsafe fn download_frame_rgba(
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    frame: *mut pl_frame,
+    buf: &mut Vec<u8>,
+) -> Result<(u32, u32)> {
+    if lowlevel_ctx.is_null() || frame.is_null() || (*frame).planes[0].texture.is_null() {
+        bail!("No frame to download");
+    }
+    let w = (*(*frame).planes[0].texture).params.w as u32;
+    let h = (*(*frame).planes[0].texture).params.h as u32;
+    buf.resize(w as usize * h as usize * 4, 0);
+    match gfx_lowlevel_frame_download_rgba(lowlevel_ctx, frame, buf.as_mut_ptr(), buf.len()) {
+        0 => Ok((w, h)),
+        err => bail!("Could not download frame {}", err),
+    }
+}
+
+// Alpha blends src over dst with its top left corner at (x, y) in dst pixels
+// Scale and offset fitting a logical canvas centered inside the window,
+// identity when there is no viewport
+pub fn letterbox(viewport: Option<(u32, u32)>, win_w: f32, win_h: f32) -> (f32, f32, f32) {
+    match viewport {
+        Some((w, h)) if w > 0 && h > 0 => {
+            let scale = (win_w / w as f32).min(win_h / h as f32);
+            (
+                scale,
+                (win_w - w as f32 * scale) / 2.0,
+                (win_h - h as f32 * scale) / 2.0,
+            )
+        }
+        _ => (1.0, 0.0, 0.0),
+    }
+}
+
+pub unsafe fn overlay_frame(
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    dst: *mut pl_frame,
+    src: &WrapFrame,
+    x: i32,
+    y: i32,
+) -> Result<()> {
+    let src_frame = src.pl_frame();
+    if dst.is_null()
+        || (*dst).planes[0].texture.is_null()
+        || (*src_frame).planes[0].texture.is_null()
+    {
+        bail!("No frame to overlay");
+    }
+    let dst_w = (*(*dst).planes[0].texture).params.w as f32;
+    let dst_h = (*(*dst).planes[0].texture).params.h as f32;
+    let src_w = (*(*src_frame).planes[0].texture).params.w as f32;
+    let src_h = (*(*src_frame).planes[0].texture).params.h as f32;
+
+    let body = CString::new("color = texture(src_tex0, src_uv);")?;
+    let params = gfx_lowlevel_filter_params {
+        src: pl_rect2df {
+            x0: 0.0,
+            y0: 0.0,
+            x1: 1.0,
+            y1: 1.0,
+        },
+        dst: pl_rect2df {
+            x0: x as f32 / dst_w,
+            y0: y as f32 / dst_h,
+            x1: (x as f32 + src_w) / dst_w,
+            y1: (y as f32 + src_h) / dst_h,
+        },
+        rotation: 0.0,
+        prelude: std::ptr::null(),
+        header: std::ptr::null(),
+        body: body.as_ptr(),
+        vars: std::ptr::null_mut(),
+        num_vars: 0,
+        blend: true,
+    };
+    let mut raw_frame = [src_frame];
+    match gfx_lowlevel_gpu_ctx_render(
+        lowlevel_ctx,
+        &params as _,
+        dst,
+        raw_frame.as_mut_ptr(),
+        1,
+        std::ptr::null_mut(),
+        0,
+        std::ptr::null_mut(),
+        false,
+    ) {
+        0 => Ok(()),
+        err => bail!("Could not overlay frame {}", err),
+    }
+}
+
+// Renders body over the whole of dst with a as src_tex0, b as src_tex1 and a
+// float uniform `progress`
+// Rotation and color_mod for the window copy of a mix. Everything is in
+// window pixels so rotation doesn't skew with the window aspect.
+const COPY_EX_BODY: &str = "
+    vec2 p = mix(copy_bbox.xy, copy_bbox.zw, src_uv);
+    float c = cos(copy_rot);
+    float s = sin(copy_rot);
+    vec2 d = p - copy_center;
+    vec2 q = copy_center + vec2(c * d.x + s * d.y, -s * d.x + c * d.y);
+    vec2 t = (q - copy_dst.xy) / (copy_dst.zw - copy_dst.xy);
+    if (any(lessThan(t, vec2(0.0))) || any(greaterThan(t, vec2(1.0)))) {
+        color = vec4(0.0);
+    } else {
+        color = texture(src_tex0, mix(copy_src.xy, copy_src.zw, t)) * copy_mod;
+    }
+";
+const COPY_EX_VARS: [&str; 6] = [
+    "copy_bbox",
+    "copy_rot",
+    "copy_center",
+    "copy_dst",
+    "copy_src",
+    "copy_mod",
+];
+
+#[derive(Default)]
+struct CopyParams {
+    bbox: [f32; 4],
+    rot: f32,
+    center: [f32; 2],
+    dst: [f32; 4],
+    src: [f32; 4],
+    color_mod: Option<[f32; 4]>,
+}
+
+impl CopyParams {
+    fn new(
+        target: &CopyEx,
+        params: &gfx_lowlevel_filter_params,
+        win_w: f32,
+        win_h: f32,
+        scale: f32,
+    ) -> Self {
+        let dst = [
+            params.dst.x0 * win_w,
+            params.dst.y0 * win_h,
+            params.dst.x1 * win_w,
+            params.dst.y1 * win_h,
+        ];
+        // like SDL_RenderCopyEx, center is relative to the dst rect
+        let center = match target.center {
+            Some((x, y)) => [dst[0] + x as f32 * scale, dst[1] + y as f32 * scale],
+            None => [(dst[0] + dst[2]) / 2.0, (dst[1] + dst[3]) / 2.0],
+        };
+        let rot = (target.rotation as f32).to_radians();
+        let (s, c) = rot.sin_cos();
+        let corners = [
+            [dst[0], dst[1]],
+            [dst[2], dst[1]],
+            [dst[0], dst[3]],
+            [dst[2], dst[3]],
+        ]
+        .map(|[x, y]| {
+            let (dx, dy) = (x - center[0], y - center[1]);
+            [center[0] + c * dx - s * dy, center[1] + s * dx + c * dy]
+        });
+        let bbox = [
+            corners
+                .iter()
+                .map(|p| p[0])
+                .fold(f32::MAX, f32::min)
+                .max(0.0),
+            corners
+                .iter()
+                .map(|p| p[1])
+                .fold(f32::MAX, f32::min)
+                .max(0.0),
+            corners
+                .iter()
+                .map(|p| p[0])
+                .fold(f32::MIN, f32::max)
+                .min(win_w),
+            corners
+                .iter()
+                .map(|p| p[1])
+                .fold(f32::MIN, f32::max)
+                .min(win_h),
+        ];
+        Self {
+            bbox,
+            rot,
+            center,
+            dst,
+            src: [params.src.x0, params.src.y0, params.src.x1, params.src.y1],
+            color_mod: target
+                .color_mod
+                .map(|(r, g, b, a)| [r, g, b, a].map(|v| v as f32 / 255.0)),
+        }
+    }
+
+    fn is_simple(&self) -> bool {
+        self.rot == 0.0 && self.color_mod.is_none()
+    }
+
+    fn needs_blend(&self) -> bool {
+        self.rot != 0.0 || self.color_mod.map_or(false, |m| m[3] < 1.0)
+    }
+
+    // The returned vars point into self and names, keep both alive until render
+    fn vars(&self, names: &[CString]) -> Vec<pl_shader_var> {
+        const NO_MOD: [f32; 4] = [1.0; 4];
+        let color_mod = self.color_mod.as_ref().unwrap_or(&NO_MOD);
+        let data: [(*const f32, i32); 6] = [
+            (self.bbox.as_ptr(), 4),
+            (&self.rot, 1),
+            (self.center.as_ptr(), 2),
+            (self.dst.as_ptr(), 4),
+            (self.src.as_ptr(), 4),
+            (color_mod.as_ptr(), 4),
+        ];
+        names
+            .iter()
+            .zip(data)
+            .map(|(name, (data, dim_v))| pl_shader_var {
+                var: pl_var {
+                    name: name.as_ptr(),
+                    type_: pl_var_type_PL_VAR_FLOAT,
+                    dim_v,
+                    dim_m: 1,
+                    dim_a: 1,
+                },
+                data: data as _,
+                dynamic: true,
+            })
+            .collect()
+    }
+}
+
+pub unsafe fn blend_frames(
+    lowlevel_ctx: *mut gfx_lowlevel_gpu_ctx,
+    dst: *mut pl_frame,
+    a: &WrapFrame,
+    b: &WrapFrame,
+    body: &str,
+    progress: f32,
+) -> Result<()> {
+    let body = CString::new(body)?;
+    let name = CString::new("progress")?;
+    let mut vars = [pl_shader_var {
+        var: pl_var {
+            name: name.as_ptr(),
+            type_: pl_var_type_PL_VAR_FLOAT,
+            dim_v: 1,
+            dim_m: 1,
+            dim_a: 1,
+        },
+        data: &progress as *const f32 as _,
+        dynamic: true,
+    }];
+    let params = gfx_lowlevel_filter_params {
+        src: pl_rect2df {
+            x0: 0.0,
+            y0: 0.0,
+            x1: 1.0,
+            y1: 1.0,
+        },
+        dst: pl_rect2df {
+            x0: 0.0,
+            y0: 0.0,
+            x1: 1.0,
+            y1: 1.0,
+        },
+        rotation: 0.0,
+        prelude: std::ptr::null(),
+        header: std::ptr::null(),
+        body: body.as_ptr(),
+        vars: vars.as_mut_ptr(),
+        num_vars: vars.len() as i32,
+        blend: false,
+    };
+    let mut raw_frames = [a.pl_frame(), b.pl_frame()];
+    match gfx_lowlevel_gpu_ctx_render(
+        lowlevel_ctx,
+        &params as _,
+        dst,
+        raw_frames.as_mut_ptr(),
+        raw_frames.len() as i32,
+        std::ptr::null_mut(),
+        0,
+        std::ptr::null_mut(),
+        false,
+    ) {
+        0 => Ok(()),
+        err => bail!("Could not blend frames {}", err),
+    }
+}
@@ -0,0 +1,321 @@
+use crate::appruntime::Preopen;
+use crate::gfxinfo::{FileChangeKind, GlobMatchedEvent};
+use crate::glob::{glob_searched, GlobMatch};
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// How often the directories are walked
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+// What --watch-ext does with files ending in ext, parsed from ext=kind where
+// kind is shader, lut, vid, tex, other or ignore
+#[derive(Clone, Debug)]
+pub struct WatchRule {
+    pub ext: String,
+    pub kind: Option<FileChangeKind>,
+}
+
+impl FromStr for WatchRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((ext, kind)) = s.split_once('=') else {
+            bail!("Expected ext=shader|lut|vid|tex|other|ignore, got {}", s);
+        };
+        let kind = match kind {
+            "shader" => Some(FileChangeKind::Shader),
+            "lut" => Some(FileChangeKind::Lut),
+            "vid" => Some(FileChangeKind::Vid),
+            "tex" => Some(FileChangeKind::Tex),
+            "other" => Some(FileChangeKind::Other),
+            "ignore" => None,
+            _ => bail!("Unknown watch policy {} for {}", kind, ext),
+        };
+        Ok(Self {
+            ext: ext.trim_start_matches('.').to_ascii_lowercase(),
+            kind,
+        })
+    }
+}
+
+// Used unless a rule says otherwise, anything else is ignored
+const DEFAULT_RULES: &[(&str, FileChangeKind)] = &[
+    ("glsl", FileChangeKind::Shader),
+    ("frag", FileChangeKind::Shader),
+    ("hook", FileChangeKind::Shader),
+    ("cube", FileChangeKind::Lut),
+    ("3dl", FileChangeKind::Lut),
+    ("mp4", FileChangeKind::Vid),
+    ("mov", FileChangeKind::Vid),
+    ("mkv", FileChangeKind::Vid),
+    ("webm", FileChangeKind::Vid),
+    ("avi", FileChangeKind::Vid),
+    ("png", FileChangeKind::Tex),
+    ("jpg", FileChangeKind::Tex),
+    ("jpeg", FileChangeKind::Tex),
+    ("gif", FileChangeKind::Tex),
+    ("webp", FileChangeKind::Tex),
+    ("bmp", FileChangeKind::Tex),
+    ("tga", FileChangeKind::Tex),
+    ("svg", FileChangeKind::Tex),
+    ("exr", FileChangeKind::Tex),
+    ("hdr", FileChangeKind::Tex),
+    ("ktx2", FileChangeKind::Tex),
+    ("dds", FileChangeKind::Tex),
+];
+
+pub fn change_kind(rules: &[WatchRule], path: &Path) -> Option<FileChangeKind> {
+    let ext = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    // later rules win, so a config file's can be overridden on the command line
+    if let Some(rule) = rules.iter().rev().find(|rule| rule.ext == ext) {
+        return rule.kind;
+    }
+    DEFAULT_RULES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, kind)| *kind)
+}
+
+// Where the guest sees a host path, None outside every preopen
+pub fn guest_path(preopens: &[Preopen], path: &Path) -> Option<String> {
+    preopens.iter().find_map(|preopen| {
+        let host = preopen.host_path.canonicalize().ok()?;
+        let rest = path.strip_prefix(&host).ok()?;
+        Some(
+            Path::new(&preopen.guest_path)
+                .join(rest)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    })
+}
+
+// Walks directories on its own thread for files that were added or modified,
+// each reported once it has stopped changing for the debounce. Hidden files
+// and directories are skipped, as are symlinked directories.
+pub struct DirWatcher {
+    changes: Receiver<PathBuf>,
+}
+
+impl DirWatcher {
+    pub fn start(roots: Vec<PathBuf>, debounce: Duration) -> Self {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let roots = roots
+                .iter()
+                .filter_map(|root| root.canonicalize().ok())
+                .collect::<Vec<_>>();
+            let mut seen = scan(&roots);
+            // changed files and when they last changed
+            let mut settling = HashMap::<PathBuf, Instant>::new();
+            loop {
+                thread::sleep(SCAN_INTERVAL);
+                let now = scan(&roots);
+                for (path, modified) in &now {
+                    if seen.get(path) != Some(modified) {
+                        settling.insert(path.clone(), Instant::now());
+                    }
+                }
+                seen = now;
+                let settled = settling
+                    .iter()
+                    .filter(|(_, changed)| changed.elapsed() >= debounce)
+                    .map(|(path, _)| path.clone())
+                    .collect::<Vec<_>>();
+                for path in settled {
+                    settling.remove(&path);
+                    // removed while settling
+                    if !seen.contains_key(&path) {
+                        continue;
+                    }
+                    if tx.send(path).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { changes: rx }
+    }
+
+    // Files that changed since the last call
+    pub fn poll(&self) -> Vec<PathBuf> {
+        self.changes.try_iter().collect()
+    }
+}
+
+// Globs again on its own thread when a directory a pattern was looked for in
+// changes, reporting files that newly match once they've stopped changing
+// for the debounce, so one still being copied in isn't opened half written
+pub struct GlobWatcher {
+    patterns: Sender<String>,
+    matches: Receiver<GlobMatchedEvent>,
+}
+
+struct WatchedGlob {
+    pattern: String,
+    // the directories searched and their times then
+    searched: Vec<(String, Option<SystemTime>)>,
+    reported: HashSet<String>,
+    // new matches as they were when last seen to change, and when that was
+    settling: HashMap<String, (GlobMatch, Instant)>,
+    // everything it matches is to be reported, without waiting
+    fresh: bool,
+}
+
+impl GlobWatcher {
+    pub fn start(debounce: Duration) -> Self {
+        let (patterns, pattern_rx) = channel::<String>();
+        let (tx, matches) = channel();
+        thread::spawn(move || {
+            let mut watched = Vec::<WatchedGlob>::new();
+            loop {
+                for pattern in pattern_rx.try_iter() {
+                    match watched.iter_mut().find(|w| w.pattern == pattern) {
+                        Some(w) => w.fresh = true,
+                        None => watched.push(WatchedGlob {
+                            pattern,
+                            searched: vec![],
+                            reported: HashSet::new(),
+                            settling: HashMap::new(),
+                            fresh: true,
+                        }),
+                    }
+                }
+                let mut failed = vec![];
+                for (i, w) in watched.iter_mut().enumerate() {
+                    let moved = w.searched.iter().any(|(dir, time)| modified(dir) != *time);
+                    if !(w.fresh || moved || !w.settling.is_empty()) {
+                        continue;
+                    }
+                    let event = match glob_searched::<&str>(&w.pattern, &[]) {
+                        Ok((found, searched)) => {
+                            w.searched = searched
+                                .into_iter()
+                                .map(|dir| {
+                                    let time = modified(&dir);
+                                    (dir, time)
+                                })
+                                .collect();
+                            w.update(found, debounce)
+                        }
+                        Err(e) => {
+                            failed.push(i);
+                            Some(GlobMatchedEvent {
+                                pattern: w.pattern.clone(),
+                                paths: vec![],
+                                error: Some(e.to_string()),
+                            })
+                        }
+                    };
+                    if let Some(event) = event {
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+                for i in failed.into_iter().rev() {
+                    watched.remove(i);
+                }
+                thread::sleep(SCAN_INTERVAL);
+            }
+        });
+        Self { patterns, matches }
+    }
+
+    // Reports what pattern matches now, then what else comes to
+    pub fn watch(&self, pattern: &str) {
+        let _ = self.patterns.send(pattern.to_string());
+    }
+
+    pub fn poll(&self) -> Vec<GlobMatchedEvent> {
+        self.matches.try_iter().collect()
+    }
+}
+
+impl WatchedGlob {
+    // The matches to report out of what the pattern matches now
+    fn update(&mut self, found: Vec<GlobMatch>, debounce: Duration) -> Option<GlobMatchedEvent> {
+        let now = found.iter().map(|m| m.path.clone()).collect::<HashSet<_>>();
+        // gone ones are reported again if they come back
+        self.reported.retain(|path| now.contains(path));
+        self.settling.retain(|path, _| now.contains(path));
+        let mut paths = vec![];
+        for m in found {
+            if self.reported.contains(&m.path) {
+                continue;
+            }
+            if !self.fresh {
+                match self.settling.get(&m.path) {
+                    Some((seen, since)) if *seen == m => {
+                        if since.elapsed() < debounce {
+                            continue;
+                        }
+                    }
+                    _ => {
+                        self.settling.insert(m.path.clone(), (m, Instant::now()));
+                        continue;
+                    }
+                }
+            }
+            self.settling.remove(&m.path);
+            self.reported.insert(m.path.clone());
+            paths.push(m.path);
+        }
+        let fresh = std::mem::take(&mut self.fresh);
+        if paths.is_empty() && !fresh {
+            return None;
+        }
+        if fresh {
+            // a pattern watched again gets all of it
+            paths = self.reported.iter().cloned().collect();
+            paths.sort();
+        }
+        Some(GlobMatchedEvent {
+            pattern: self.pattern.clone(),
+            paths,
+            error: None,
+        })
+    }
+}
+
+fn modified(dir: &str) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|meta| meta.modified()).ok()
+}
+
+fn scan(roots: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut dirs = roots.to_vec();
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+                continue;
+            }
+            // follows symlinked files
+            match fs::metadata(entry.path()) {
+                Ok(meta) if meta.is_file() => {
+                    if let Ok(modified) = meta.modified() {
+                        files.insert(entry.path(), modified);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    files
+}
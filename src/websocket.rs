@@ -0,0 +1,458 @@
+// Just enough of HTTP/1.1 and RFC 6455 for --remote: one request per
+// connection, which is either answered or upgraded to a websocket carrying
+// unfragmented text messages out and any messages in.
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::io::{BufRead, Read, Write};
+use std::net::TcpStream;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEADER_BYTES: usize = 16 << 10;
+const MAX_MESSAGE_BYTES: u64 = 16 << 20;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    // Sec-WebSocket-Key, only set when the client asked to upgrade
+    pub key: Option<String>,
+    pub host: Option<String>,
+    // set by browsers, the page that opened the connection
+    pub origin: Option<String>,
+}
+
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request> {
+    let mut line = String::new();
+    let mut left = MAX_HEADER_BYTES;
+    read_header_line(reader, &mut line, &mut left)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        bail!("Bad request line {:?}", line.trim());
+    };
+    let mut request = Request {
+        method: method.into(),
+        path: path.into(),
+        key: None,
+        host: None,
+        origin: None,
+    };
+    let mut upgrade = false;
+    loop {
+        read_header_line(reader, &mut line, &mut left)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = value.trim().eq_ignore_ascii_case("websocket"),
+            "sec-websocket-key" => request.key = Some(value.trim().into()),
+            "host" => request.host = Some(value.trim().into()),
+            "origin" => request.origin = Some(value.trim().into()),
+            _ => (),
+        }
+    }
+    if !upgrade {
+        request.key = None;
+    }
+    Ok(request)
+}
+
+// Reads no further than what's left of the header budget, so a line that
+// never ends can't hold more than that
+fn read_header_line<R: BufRead>(reader: &mut R, line: &mut String, left: &mut usize) -> Result<()> {
+    line.clear();
+    let read = reader.by_ref().take(*left as u64).read_line(line)?;
+    if read == *left && !line.ends_with('\n') {
+        bail!("Request headers are too long");
+    }
+    if read == 0 {
+        bail!("Connection closed before the request headers ended");
+    }
+    *left -= read;
+    Ok(())
+}
+
+pub fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+pub fn upgrade(stream: &mut TcpStream, key: &str) -> Result<()> {
+    let accept = accept_key(key);
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+    Ok(())
+}
+
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Close,
+}
+
+// Pings can arrive in the middle of a fragmented message, so they're handed
+// to ping to answer with write_pong as they're read
+pub fn read_message<R: Read>(
+    reader: &mut R,
+    mut ping: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<Message> {
+    let mut message = vec![];
+    let mut kind = None;
+    loop {
+        let mut head = [0u8; 2];
+        reader.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        if head[1] & 0x80 == 0 {
+            bail!("Client frames must be masked");
+        }
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        // control frames can't be fragmented or carry more than 125 bytes
+        if opcode & 0x8 != 0 && (len > 125 || !fin) {
+            bail!("Bad control frame, opcode {} with {} bytes", opcode, len);
+        }
+        // message is never over the limit, len can be anything up to u64::MAX
+        if len > MAX_MESSAGE_BYTES - message.len() as u64 {
+            bail!("Message is larger than {} bytes", MAX_MESSAGE_BYTES);
+        }
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask)?;
+        let mut payload = vec![0u8; len as usize];
+        reader.read_exact(&mut payload)?;
+        payload
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b ^= mask[i % 4]);
+        match opcode {
+            OP_CLOSE => return Ok(Message::Close),
+            OP_PING => ping(&payload)?,
+            OP_PONG => (),
+            OP_TEXT | OP_BINARY if kind.is_none() => {
+                kind = Some(opcode);
+                message.extend(payload);
+            }
+            OP_CONTINUATION if kind.is_some() => message.extend(payload),
+            _ => bail!("Unexpected websocket opcode {}", opcode),
+        }
+        // a control frame's fin says nothing about the message around it
+        if fin && opcode & 0x8 == 0 {
+            break;
+        }
+    }
+    match kind {
+        Some(OP_TEXT) => Ok(Message::Text(
+            String::from_utf8(message).map_err(|e| anyhow!("Text isn't utf-8: {}", e))?,
+        )),
+        _ => Ok(Message::Binary(message)),
+    }
+}
+
+pub fn write_text<W: Write>(writer: &mut W, text: &str) -> Result<()> {
+    write_frame(writer, OP_TEXT, text.as_bytes())
+}
+
+pub fn write_pong<W: Write>(writer: &mut W, payload: &[u8]) -> Result<()> {
+    write_frame(writer, OP_PONG, payload)
+}
+
+pub fn write_close<W: Write>(writer: &mut W) -> Result<()> {
+    write_frame(writer, OP_CLOSE, &[])
+}
+
+fn write_frame<W: Write>(writer: &mut W, opcode: u8, payload: &[u8]) -> Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    writer.write_all(&frame)?;
+    Ok(())
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend((data.len() as u64 * 8).to_be_bytes());
+    for chunk in padded.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                chunk[i * 4],
+                chunk[i * 4 + 1],
+                chunk[i * 4 + 2],
+                chunk[i * 4 + 3],
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (i, v) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(text: &str) -> Result<Request> {
+        read_request(&mut Cursor::new(text.as_bytes()))
+    }
+
+    // A client frame, masked with a fixed key
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![(fin as u8) << 7 | opcode];
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend((len as u16).to_be_bytes());
+            }
+        }
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn message(bytes: &[u8]) -> Result<Message> {
+        read_message(&mut Cursor::new(bytes), |_| Ok(()))
+    }
+
+    #[test]
+    fn upgrade_request() {
+        let upgrade = request(
+            "GET /ws?token=x HTTP/1.1\r\nHost: a:8080\r\nUpgrade: WebSocket\r\nSec-WebSocket-Key: abc\r\nOrigin: http://a\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(upgrade.method, "GET");
+        assert_eq!(upgrade.path, "/ws?token=x");
+        assert_eq!(upgrade.key.as_deref(), Some("abc"));
+        assert_eq!(upgrade.host.as_deref(), Some("a:8080"));
+        assert_eq!(upgrade.origin.as_deref(), Some("http://a"));
+
+        // a key without the upgrade is a plain request
+        let plain = request("GET / HTTP/1.1\r\nSec-WebSocket-Key: abc\r\n\r\n").unwrap();
+        assert_eq!(plain.key, None);
+    }
+
+    #[test]
+    fn truncated_request() {
+        assert!(request("").is_err());
+        assert!(request("GET\r\n\r\n").is_err());
+        assert!(request("GET / HTTP/1.1\r\nHost: a\r\n").is_err());
+        assert!(request("GET / HTTP/1.1\r\nHost: a").is_err());
+    }
+
+    #[test]
+    fn overlong_request() {
+        let long = format!(
+            "GET / HTTP/1.1\r\nX: {}\r\n\r\n",
+            "a".repeat(MAX_HEADER_BYTES)
+        );
+        assert!(request(&long).is_err());
+        // many short lines count against the same budget
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: a\r\n".repeat(MAX_HEADER_BYTES / 6)
+        );
+        assert!(request(&many).is_err());
+        let line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER_BYTES));
+        assert!(request(&line).is_err());
+    }
+
+    #[test]
+    fn handshake() {
+        // the example handshake from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha1_vectors() {
+        // FIPS 180 examples, one block, two blocks and a million bytes
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex(&sha1(&vec![b'a'; 1_000_000])),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    #[test]
+    fn masked_text() {
+        match message(&frame(true, OP_TEXT, b"hello")).unwrap() {
+            Message::Text(text) => assert_eq!(text, "hello"),
+            _ => panic!("not text"),
+        }
+        let long = vec![b'a'; 300];
+        match message(&frame(true, OP_BINARY, &long)).unwrap() {
+            Message::Binary(bytes) => assert_eq!(bytes, long),
+            _ => panic!("not binary"),
+        }
+        assert!(matches!(
+            message(&frame(true, OP_CLOSE, b"")),
+            Ok(Message::Close)
+        ));
+        assert!(message(&frame(true, OP_TEXT, &[0xff])).is_err());
+    }
+
+    #[test]
+    fn unmasked_frame() {
+        let mut unmasked = vec![];
+        write_text(&mut unmasked, "hello").unwrap();
+        assert!(message(&unmasked).is_err());
+    }
+
+    #[test]
+    fn bad_frames() {
+        assert!(message(&frame(true, OP_PING, &[0; 126])).is_err());
+        assert!(message(&frame(false, OP_PING, b"")).is_err());
+        assert!(message(&frame(true, OP_CONTINUATION, b"a")).is_err());
+        assert!(message(&frame(true, 0x3, b"a")).is_err());
+        // truncated payload
+        let full = frame(true, OP_TEXT, b"hello");
+        assert!(message(&full[..full.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        for len in [MAX_MESSAGE_BYTES + 1, u64::MAX] {
+            let mut frame = vec![0x80 | OP_BINARY, 0x80 | 127];
+            frame.extend(len.to_be_bytes());
+            frame.extend([0; 4]);
+            assert!(message(&frame).is_err());
+        }
+        // fragments add up against the same limit
+        let mut frames = frame(false, OP_BINARY, b"a");
+        frames.extend([0x80 | OP_CONTINUATION, 0x80 | 127]);
+        frames.extend(MAX_MESSAGE_BYTES.to_be_bytes());
+        frames.extend([0; 4]);
+        assert!(message(&frames).is_err());
+    }
+
+    #[test]
+    fn fragments_and_pings() {
+        let mut frames = frame(false, OP_TEXT, b"hel");
+        frames.extend(frame(true, OP_PING, b"p1"));
+        frames.extend(frame(false, OP_CONTINUATION, b"l"));
+        frames.extend(frame(true, OP_PONG, b""));
+        frames.extend(frame(true, OP_CONTINUATION, b"o"));
+        let mut pings = vec![];
+        let message = read_message(&mut Cursor::new(frames), |payload| {
+            pings.push(payload.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert!(matches!(message, Message::Text(text) if text == "hello"));
+        assert_eq!(pings, [b"p1"]);
+    }
+
+    #[test]
+    fn server_frames() {
+        let mut out = vec![];
+        write_text(&mut out, "hi").unwrap();
+        assert_eq!(out, [0x81, 2, b'h', b'i']);
+        let mut out = vec![];
+        write_pong(&mut out, &[0; 200]).unwrap();
+        assert_eq!(out[..4], [0x8a, 126, 0, 200]);
+        let mut out = vec![];
+        write_close(&mut out).unwrap();
+        assert_eq!(out, [0x88, 0]);
+    }
+}
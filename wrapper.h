@@ -1 +0,0 @@
-#include <glob.h>
\ No newline at end of file